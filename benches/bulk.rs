@@ -0,0 +1,70 @@
+// Adding many tasks at once, as an import does: `cargo bench --bench bulk`
+use criterion::{BatchSize, Criterion, criterion_group, criterion_main};
+use task_manager::import::{self, ImportedTask};
+use task_manager::{Priority, Task, TaskManager, TaskStatus};
+
+const SIZE: u32 = 10_000;
+
+fn tasks() -> Vec<Task> {
+    (1..=SIZE)
+        .map(|id| {
+            let mut task = Task::new(0, format!("Issue {}", id), format!("Imported issue {}", id), Priority::Medium);
+            task.add_tag("imported");
+            task
+        })
+        .collect()
+}
+
+fn rows() -> Vec<ImportedTask> {
+    (1..=SIZE)
+        .map(|id| ImportedTask {
+            external_id: format!("bench#{}", id),
+            title: format!("Issue {}", id),
+            description: format!("Imported issue {}", id),
+            priority: None,
+            tags: vec!["imported".to_string()],
+            status: TaskStatus::Pending,
+            key: Some(format!("B-{}", id)),
+        })
+        .collect()
+}
+
+fn bench_bulk(c: &mut Criterion) {
+    let mut group = c.benchmark_group(format!("{} new tasks", SIZE));
+    group.sample_size(10);
+    group.bench_function("create_task loop", |b| {
+        b.iter_batched(
+            tasks,
+            |tasks| {
+                let mut manager = TaskManager::new();
+                for task in tasks {
+                    manager.create_task(task).unwrap();
+                }
+                manager
+            },
+            BatchSize::LargeInput,
+        )
+    });
+    group.bench_function("add_tasks_bulk", |b| {
+        b.iter_batched(
+            tasks,
+            |tasks| {
+                let mut manager = TaskManager::new();
+                manager.add_tasks_bulk(tasks);
+                manager
+            },
+            BatchSize::LargeInput,
+        )
+    });
+    group.bench_function("import::apply", |b| {
+        b.iter_batched(
+            rows,
+            |rows| import::apply(&mut TaskManager::new(), "bench", rows, &Priority::Medium),
+            BatchSize::LargeInput,
+        )
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_bulk);
+criterion_main!(benches);
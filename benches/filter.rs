@@ -0,0 +1,45 @@
+// Filtering and listing over generated task lists: `cargo bench --bench filter`
+use std::hint::black_box;
+
+use criterion::{BenchmarkId, Criterion, criterion_group, criterion_main};
+use task_manager::storage::SavedState;
+use task_manager::{Priority, Task, TaskManager};
+
+const SIZES: [u32; 3] = [1_000, 10_000, 100_000];
+
+const WORDS: [&str; 8] = ["deploy", "review", "invoice", "backup", "Release", "meeting", "refactor", "Café"];
+
+// Mostly ASCII, with some accented text so the non-ASCII path is measured too
+fn dataset(size: u32) -> TaskManager {
+    let tasks = (1..=size)
+        .map(|id| {
+            let word = WORDS[id as usize % WORDS.len()];
+            let title = format!("{} {} for project {}", word, id, id % 97);
+            let description = format!("Notes on {} number {}: check the logs and report back", word, id);
+            let mut task = Task::new(id, title, description, Priority::Medium);
+            task.add_tag(format!("tag{}", id % 20));
+            task.add_tag(format!("team{}", id % 7));
+            task
+        })
+        .collect();
+    TaskManager::from_state(SavedState { tasks, next_id: size + 1, ..SavedState::default() })
+}
+
+fn bench_views(c: &mut Criterion) {
+    for size in SIZES {
+        let manager = dataset(size);
+        let mut group = c.benchmark_group(format!("{} tasks", size));
+        for filter in ["DEPLOY", "café", "no such text"] {
+            group.bench_with_input(BenchmarkId::new("filter_tasks", filter), filter, |b, filter| {
+                b.iter(|| manager.filter_tasks(black_box(filter)).len())
+            });
+        }
+        let tags = vec!["tag3".to_string(), "team5".to_string()];
+        group.bench_function("get_tasks_by_tags", |b| b.iter(|| manager.get_tasks_by_tags(black_box(&tags)).len()));
+        group.bench_function("list_tasks", |b| b.iter(|| manager.list_tasks().len()));
+        group.finish();
+    }
+}
+
+criterion_group!(benches, bench_views);
+criterion_main!(benches);
@@ -0,0 +1,44 @@
+// Saving and loading the data file as JSON and as MessagePack:
+// `cargo bench --features msgpack --bench store`
+use std::hint::black_box;
+
+use criterion::{BenchmarkId, Criterion, criterion_group, criterion_main};
+use task_manager::storage::{self, FileStorage, SavedState, Storage, StoreFormat};
+use task_manager::{Priority, Task};
+
+const SIZE: u32 = 100_000;
+
+fn dataset() -> SavedState {
+    let tasks = (1..=SIZE)
+        .map(|id| {
+            let title = format!("Task {} for project {}", id, id % 97);
+            let mut task = Task::new(id, title, format!("Notes on task {}", id), Priority::Medium);
+            task.add_tag(format!("tag{}", id % 20));
+            task
+        })
+        .collect();
+    SavedState { tasks, next_id: SIZE + 1, ..SavedState::default() }
+}
+
+fn bench_formats(c: &mut Criterion) {
+    let state = dataset();
+    let mut group = c.benchmark_group(format!("{} tasks", SIZE));
+    group.sample_size(10);
+    for format in [StoreFormat::Json, StoreFormat::MessagePack] {
+        let path = std::env::temp_dir().join(format!("task-manager-bench-{}.{}", std::process::id(), format));
+        let storage = FileStorage::new(&path).with_format(format);
+        storage.save(&state).unwrap();
+        println!("{}: {} bytes", format, std::fs::metadata(&path).unwrap().len());
+
+        group.bench_function(BenchmarkId::new("encode", format), |b| {
+            b.iter(|| storage::encode(black_box(&state), format).unwrap().len())
+        });
+        group.bench_function(BenchmarkId::new("save", format), |b| b.iter(|| storage.save(black_box(&state))));
+        group.bench_function(BenchmarkId::new("load", format), |b| b.iter(|| storage.load().unwrap().tasks.len()));
+        std::fs::remove_file(&path).unwrap();
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_formats);
+criterion_main!(benches);
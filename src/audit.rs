@@ -0,0 +1,181 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::TaskEvent;
+
+// Entries kept when the config does not say otherwise
+pub const DEFAULT_AUDIT_LIMIT: usize = 1000;
+
+// One recorded change: who did what to which task, and when
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct AuditEntry {
+    pub timestamp: DateTime<Utc>,
+    pub user: String,
+    pub action: String,
+    // None for changes affecting the whole list, such as a rollback
+    pub task_id: Option<u32>,
+    pub detail: String,
+}
+
+impl AuditEntry {
+    pub fn from_event(event: &TaskEvent, user: &str) -> AuditEntry {
+        let (action, task_id, detail) = match event {
+            TaskEvent::Added(id) => ("added", Some(*id), String::new()),
+            TaskEvent::StatusChanged { id, from, to } => ("status", Some(*id), format!("{} -> {}", from, to)),
+            TaskEvent::PriorityChanged { id, from, to } => ("priority", Some(*id), format!("{} -> {}", from, to)),
+            TaskEvent::Tagged { id, tag } => ("tagged", Some(*id), tag.clone()),
+            TaskEvent::Deleted(id) => ("deleted", Some(*id), String::new()),
+            TaskEvent::Edited(id) => ("edited", Some(*id), String::new()),
+            TaskEvent::Notified(id) => ("notified", Some(*id), String::new()),
+            TaskEvent::Restored => ("restored", None, String::new()),
+            TaskEvent::ViewChanged { name } => ("view", None, name.clone()),
+            TaskEvent::TagDescribed { tag } => ("tag-registry", None, tag.clone()),
+            TaskEvent::GoalChanged => ("goal", None, String::new()),
+            TaskEvent::Overridden { id, owner } => ("override", Some(*id), format!("task of {}", owner)),
+            TaskEvent::RetentionApplied { archived, purged } => {
+                ("retention", None, format!("archived {}; purged {}", id_list(archived), id_list(purged)))
+            }
+        };
+        AuditEntry {
+            timestamp: Utc::now(),
+            user: user.to_string(),
+            action: action.to_string(),
+            task_id,
+            detail,
+        }
+    }
+}
+
+// "#3, #7", or "none"
+fn id_list(ids: &[u32]) -> String {
+    if ids.is_empty() {
+        return "none".to_string();
+    }
+    ids.iter().map(|id| format!("#{}", id)).collect::<Vec<_>>().join(", ")
+}
+
+// Append-only change history, oldest first; past `limit` the oldest entries are dropped
+#[derive(Debug, Clone, PartialEq)]
+pub struct AuditLog {
+    entries: Vec<AuditEntry>,
+    limit: usize,
+}
+
+impl Default for AuditLog {
+    fn default() -> Self {
+        AuditLog::new(Vec::new(), DEFAULT_AUDIT_LIMIT)
+    }
+}
+
+impl AuditLog {
+    pub fn new(entries: Vec<AuditEntry>, limit: usize) -> Self {
+        let mut log = AuditLog { entries, limit };
+        log.rotate();
+        log
+    }
+
+    pub fn set_limit(&mut self, limit: usize) {
+        self.limit = limit;
+        self.rotate();
+    }
+
+    // Another log's entries in place of these, under this log's limit
+    pub fn replace(&mut self, entries: Vec<AuditEntry>) {
+        self.entries = entries;
+        self.rotate();
+    }
+
+    pub fn record(&mut self, entry: AuditEntry) {
+        self.entries.push(entry);
+        self.rotate();
+    }
+
+    fn rotate(&mut self) {
+        if self.entries.len() > self.limit {
+            let excess = self.entries.len() - self.limit;
+            self.entries.drain(..excess);
+        }
+    }
+
+    pub fn entries(&self) -> &[AuditEntry] {
+        &self.entries
+    }
+
+    // Newest first, optionally only the entries for one task
+    pub fn history(&self, task_id: Option<u32>) -> Vec<AuditEntry> {
+        self.entries
+            .iter()
+            .rev()
+            .filter(|entry| task_id.is_none() || entry.task_id == task_id)
+            .cloned()
+            .collect()
+    }
+}
+
+// The name recorded against changes made in this process
+pub fn current_user() -> String {
+    std::env::var("USER")
+        .or_else(|_| std::env::var("USERNAME"))
+        .ok()
+        .filter(|user| !user.is_empty())
+        .unwrap_or_else(|| "unknown".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::SavedState;
+    use crate::{Priority, TaskManager, TaskStatus};
+
+    #[test]
+    fn test_log_is_capped_to_newest_entries() {
+        let mut log = AuditLog::new(Vec::new(), 3);
+        for id in 1..=5 {
+            log.record(AuditEntry::from_event(&TaskEvent::Added(id), "alice"));
+        }
+        let ids: Vec<Option<u32>> = log.entries().iter().map(|e| e.task_id).collect();
+        assert_eq!(ids, vec![Some(3), Some(4), Some(5)]);
+        log.set_limit(1);
+        assert_eq!(log.entries().len(), 1);
+        assert_eq!(log.history(None)[0].task_id, Some(5));
+    }
+
+    #[test]
+    fn test_history_survives_delete_and_round_trips() {
+        let mut manager = TaskManager::new();
+        manager.enable_audit("alice".to_string(), DEFAULT_AUDIT_LIMIT);
+        let id = manager.add_task("Audit me".to_string(), String::new(), Priority::High).unwrap();
+        manager.add_task("Other".to_string(), String::new(), Priority::Low).unwrap();
+        manager.update_task_status(id, TaskStatus::Completed).unwrap();
+        manager.add_tag_to_task(id, "work".to_string()).unwrap();
+        manager.delete_task(id).unwrap();
+
+        let actions: Vec<String> = manager.audit_history(Some(id)).into_iter().map(|e| e.action).collect();
+        assert_eq!(actions, vec!["deleted", "tagged", "status", "added"]);
+        assert_eq!(manager.audit_history(None).len(), 5);
+        assert_eq!(manager.audit_history(Some(id))[2].detail, "Pending -> Completed");
+        assert!(manager.audit_history(None).iter().all(|e| e.user == "alice"));
+
+        let json = serde_json::to_string(&manager.to_state()).unwrap();
+        let state: SavedState = serde_json::from_str(&json).unwrap();
+        assert_eq!(state.audit.len(), 5);
+        let reloaded = TaskManager::from_state(state);
+        assert_eq!(reloaded.audit_history(Some(id)), manager.audit_history(Some(id)));
+    }
+
+    #[test]
+    fn test_loading_keeps_entries_past_the_default_limit() {
+        let entries: Vec<AuditEntry> =
+            (1..=DEFAULT_AUDIT_LIMIT as u32 + 500).map(|id| AuditEntry::from_event(&TaskEvent::Added(id), "alice")).collect();
+        let state = SavedState { audit: entries, ..SavedState::default() };
+
+        let mut manager = TaskManager::from_state(state.clone());
+        manager.enable_audit("alice".to_string(), 5000);
+        assert_eq!(manager.audit_len(), DEFAULT_AUDIT_LIMIT + 500);
+
+        let mut manager = TaskManager::from_state(state);
+        manager.enable_audit("alice".to_string(), 10);
+        assert_eq!(manager.audit_history(None)[0].task_id, Some(DEFAULT_AUDIT_LIMIT as u32 + 500));
+        assert_eq!(manager.audit_len(), 10);
+    }
+}
@@ -0,0 +1,299 @@
+use serde::Serialize;
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+use crate::msg;
+use crate::render::{pin_marker, priority_glyph};
+use crate::{SortKey, Task, TaskStatus};
+
+// Completed cards shown when `board` is not told otherwise
+pub const DEFAULT_DONE_SHOWN: usize = 5;
+
+// Below this many columns the board is printed as stacked sections
+pub const MIN_SIDE_BY_SIDE_WIDTH: usize = 60;
+
+const GAP: &str = " │ ";
+
+// Title lines per card before the rest is cut off
+const CARD_LINES: usize = 2;
+
+// One column per status, in workflow order
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Board {
+    pub columns: Vec<Column>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Column {
+    pub status: TaskStatus,
+    pub tasks: Vec<Task>,
+    // Cards left out, shown as a "+N more" footer
+    pub more: usize,
+}
+
+// Open columns put the most urgent first, or follow `sort` when given; Completed
+// shows only the `done_shown` most recently finished
+pub fn build<'a>(tasks: impl IntoIterator<Item = &'a Task>, done_shown: usize, sort: Option<&SortKey>) -> Board {
+    let mut columns: [Vec<&Task>; 3] = Default::default();
+    for task in tasks {
+        let slot = match task.status {
+            TaskStatus::Pending => 0,
+            TaskStatus::InProgress => 1,
+            TaskStatus::Completed => 2,
+        };
+        columns[slot].push(task);
+    }
+    let [mut pending, mut in_progress, mut completed] = columns;
+    for open in [&mut pending, &mut in_progress] {
+        match sort {
+            Some(sort) => sort.sort(open),
+            None => open.sort_by(|a, b| b.priority.cmp(&a.priority).then_with(|| a.id.cmp(&b.id))),
+        }
+    }
+    completed.sort_by(|a, b| b.updated_at.cmp(&a.updated_at).then_with(|| b.id.cmp(&a.id)));
+    let more = completed.len().saturating_sub(done_shown);
+    completed.truncate(done_shown);
+
+    let column = |status, tasks: Vec<&Task>, more| Column { status, tasks: tasks.into_iter().cloned().collect(), more };
+    Board {
+        columns: vec![
+            column(TaskStatus::Pending, pending, 0),
+            column(TaskStatus::InProgress, in_progress, 0),
+            column(TaskStatus::Completed, completed, more),
+        ],
+    }
+}
+
+// The board as text `width` columns wide. Depends on nothing but its arguments.
+pub fn layout(board: &Board, width: usize) -> String {
+    let count = board.columns.len();
+    if count == 0 || width < MIN_SIDE_BY_SIDE_WIDTH {
+        return stacked(board, width);
+    }
+    let column_width = (width - GAP.width() * (count - 1)) / count;
+    let cells: Vec<Vec<String>> = board.columns.iter().map(|column| column_lines(column, column_width)).collect();
+    let rows = cells.iter().map(Vec::len).max().unwrap_or(0);
+    (0..rows)
+        .map(|row| {
+            let line: Vec<String> = cells
+                .iter()
+                .map(|cell| pad(cell.get(row).map_or("", String::as_str), column_width))
+                .collect();
+            line.join(GAP).trim_end().to_string()
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+// Narrow terminals: each column as a section, the cards indented under it
+fn stacked(board: &Board, width: usize) -> String {
+    let card_width = width.saturating_sub(2);
+    let sections: Vec<String> = board
+        .columns
+        .iter()
+        .map(|column| {
+            let mut lines = vec![fit(&heading(column), width)];
+            lines.extend(cards(column, card_width).into_iter().map(|line| format!("  {}", line)));
+            lines.join("\n")
+        })
+        .collect();
+    sections.join("\n\n")
+}
+
+fn column_lines(column: &Column, width: usize) -> Vec<String> {
+    let mut lines = vec![fit(&heading(column), width), "─".repeat(width)];
+    lines.extend(cards(column, width));
+    lines
+}
+
+fn heading(column: &Column) -> String {
+    format!("{} ({})", column.status, column.tasks.len() + column.more)
+}
+
+fn cards(column: &Column, width: usize) -> Vec<String> {
+    let mut lines: Vec<String> = column.tasks.iter().flat_map(|task| card(task, width)).collect();
+    if column.tasks.is_empty() && column.more == 0 {
+        lines.push(fit(&msg!("board_empty"), width));
+    }
+    if column.more > 0 {
+        lines.push(fit(&msg!("board_more", count = column.more), width));
+    }
+    lines
+}
+
+// "#12 !! Title" (or "#12 !! ★ Title" when pinned) with the title wrapped under
+// itself; too narrow to wrap, one cut line
+fn card(task: &Task, width: usize) -> Vec<String> {
+    let prefix = format!("#{} {} {}", task.id, priority_glyph(&task.priority), pin_marker(task.pinned));
+    let indent = prefix.width();
+    if indent + 4 > width {
+        return vec![fit(&format!("{}{}", prefix, task.title), width)];
+    }
+    wrap(&task.title, width - indent, CARD_LINES)
+        .into_iter()
+        .enumerate()
+        .map(|(i, line)| if i == 0 { format!("{}{}", prefix, line) } else { format!("{}{}", " ".repeat(indent), line) })
+        .collect()
+}
+
+// Break at spaces into at most `max_lines` lines, mid-word only when a word is
+// longer than a line; the last line ends in … if text is left over
+fn wrap(text: &str, width: usize, max_lines: usize) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut rest = text.trim();
+    while !rest.is_empty() {
+        if lines.len() + 1 == max_lines || rest.width() <= width {
+            lines.push(fit(rest, width));
+            break;
+        }
+        let head = take_width(rest, width);
+        let cut = if rest[head.len()..].starts_with(' ') {
+            head.len()
+        } else {
+            head.rfind(' ').filter(|&space| space > 0).unwrap_or(head.len())
+        };
+        lines.push(rest[..cut].trim_end().to_string());
+        rest = rest[cut..].trim_start();
+    }
+    lines
+}
+
+// The longest prefix of `text` at most `width` columns wide
+fn take_width(text: &str, width: usize) -> &str {
+    let mut used = 0;
+    for (i, c) in text.char_indices() {
+        used += c.width().unwrap_or(0);
+        if used > width {
+            return &text[..i];
+        }
+    }
+    text
+}
+
+pub(crate) fn fit(text: &str, width: usize) -> String {
+    if text.width() <= width {
+        return text.to_string();
+    }
+    match width {
+        0 => String::new(),
+        _ => format!("{}…", take_width(text, width - 1)),
+    }
+}
+
+pub(crate) fn pad(text: &str, width: usize) -> String {
+    format!("{}{}", text, " ".repeat(width.saturating_sub(text.width())))
+}
+
+// $COLUMNS when the shell exports it, else the terminal on stdout, else 80
+pub fn terminal_width() -> usize {
+    std::env::var("COLUMNS")
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .filter(|&width| width > 0)
+        .or_else(terminal_columns)
+        .unwrap_or(80)
+}
+
+#[cfg(unix)]
+fn terminal_columns() -> Option<usize> {
+    let mut size = libc::winsize { ws_row: 0, ws_col: 0, ws_xpixel: 0, ws_ypixel: 0 };
+    // SAFETY: TIOCGWINSZ only writes a winsize through the pointer it is given
+    let ok = unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut size) } == 0;
+    (ok && size.ws_col > 0).then_some(usize::from(size.ws_col))
+}
+
+#[cfg(not(unix))]
+fn terminal_columns() -> Option<usize> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, TimeZone, Utc};
+
+    use super::*;
+    use crate::Priority;
+
+    fn sample() -> Vec<Task> {
+        let start = Utc.with_ymd_and_hms(2024, 5, 1, 9, 0, 0).unwrap();
+        let rows = [
+            ("Write the quarterly report for the finance team", Priority::High, TaskStatus::Pending),
+            ("Fix login bug", Priority::Critical, TaskStatus::Pending),
+            ("Review PR", Priority::Medium, TaskStatus::InProgress),
+            ("Ship 1.0", Priority::Low, TaskStatus::Completed),
+            ("Triage", Priority::Critical, TaskStatus::Completed),
+            ("Plan sprint", Priority::Medium, TaskStatus::Completed),
+        ];
+        rows.into_iter()
+            .enumerate()
+            .map(|(i, (title, priority, status))| {
+                let mut task = Task::new(i as u32 + 1, title.to_string(), String::new(), priority);
+                task.status = status;
+                task.updated_at = start + Duration::hours(i as i64);
+                task
+            })
+            .collect()
+    }
+
+    fn ids(column: &Column) -> Vec<u32> {
+        column.tasks.iter().map(|task| task.id).collect()
+    }
+
+    #[test]
+    fn test_columns_sort_by_priority_and_keep_recent_completed() {
+        let tasks = sample();
+        let board = build(&tasks, 2, None);
+        assert_eq!(ids(&board.columns[0]), vec![2, 1]);
+        assert_eq!(ids(&board.columns[1]), vec![3]);
+        assert_eq!((ids(&board.columns[2]), board.columns[2].more), (vec![6, 5], 1));
+    }
+
+    #[test]
+    fn test_side_by_side_layout() {
+        let tasks = sample();
+        let expected = "\
+Pending (2)          │ In Progress (1)      │ Completed (3)
+──────────────────── │ ──────────────────── │ ────────────────────
+#2 !!! Fix login bug │ #3 ! Review PR       │ #6 ! Plan sprint
+#1 !! Write the      │                      │ #5 !!! Triage
+      quarterly rep… │                      │ +1 more";
+        assert_eq!(layout(&build(&tasks, 2, None), 66), expected);
+    }
+
+    #[test]
+    fn test_narrow_terminals_get_stacked_sections() {
+        let tasks = sample();
+        let expected = "\
+Pending (2)
+  #2 !!! Fix login bug
+  #1 !! Write the quarterly report for the
+        finance team
+
+In Progress (1)
+  #3 ! Review PR
+
+Completed (3)
+  +3 more";
+        assert_eq!(layout(&build(&tasks, 0, None), 42), expected);
+    }
+
+    #[test]
+    fn test_lines_never_exceed_the_width() {
+        let mut tasks = sample();
+        tasks[1].title = "Überprüfung der Lieferkette 供应链审查 mit einem sehr langen Wort: Donaudampfschifffahrt".to_string();
+        tasks[2].title = "x".repeat(200);
+        for width in [20, 45, 59, 60, 61, 80, 133] {
+            let text = layout(&build(&tasks, DEFAULT_DONE_SHOWN, None), width);
+            for line in text.lines() {
+                assert!(line.width() <= width, "{} columns wide at width {}: {:?}", line.width(), width, line);
+            }
+        }
+    }
+
+    #[test]
+    fn test_wrap_breaks_at_spaces_then_mid_word() {
+        assert_eq!(wrap("one two three", 7, 3), vec!["one two", "three"]);
+        assert_eq!(wrap("abcdefghij", 4, 2), vec!["abcd", "efg…"]);
+        assert_eq!(wrap("供应链审查", 5, 1), vec!["供应…"]);
+        assert_eq!(fit("exact", 5), "exact");
+    }
+}
@@ -0,0 +1,280 @@
+use chrono::{Datelike, Months, NaiveDate, Weekday};
+use serde::Serialize;
+use unicode_width::UnicodeWidthStr;
+
+use crate::board::{fit, pad};
+use crate::msg;
+use crate::{Task, TaskStatus};
+
+// Titles shown in a day's cell before the rest become "+N"
+pub const TITLES_PER_DAY: usize = 2;
+
+// Narrowest cell that still shows "#12 Title"; below it titles lose their ids,
+// below MIN_TITLE_CELL the cell only counts its tasks
+const ID_CELL: usize = 12;
+const MIN_TITLE_CELL: usize = 5;
+
+// Below this a cell cannot hold "[31]" and the month is printed as a list
+const MIN_CELL: usize = 4;
+
+const GAP: &str = "│";
+
+// Next to the day number when a task due that day is still open after it
+const OVERDUE_MARKER: &str = "●";
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Calendar {
+    // The first of the month
+    pub month: NaiveDate,
+    pub today: NaiveDate,
+    pub week_start: Weekday,
+    // Every day of the month in order
+    pub days: Vec<Day>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Day {
+    pub date: NaiveDate,
+    // Open tasks due that day, most important first
+    pub tasks: Vec<Task>,
+    // The day has passed and its tasks are still open
+    pub overdue: bool,
+}
+
+// The open tasks due in the month of `month`, day by day
+pub fn build<'a>(tasks: impl IntoIterator<Item = &'a Task>, month: NaiveDate, today: NaiveDate, week_start: Weekday) -> Calendar {
+    let first = month.with_day(1).expect("every month has a first");
+    let next = first + Months::new(1);
+    let mut days: Vec<Day> =
+        first.iter_days().take_while(|&date| date < next).map(|date| Day { date, tasks: Vec::new(), overdue: false }).collect();
+    for task in tasks {
+        let Some(due) = task.due.filter(|due| (first..next).contains(due)) else { continue };
+        if task.status != TaskStatus::Completed {
+            days[due.day0() as usize].tasks.push(task.clone());
+        }
+    }
+    for day in &mut days {
+        day.tasks.sort_by(|a, b| b.priority.cmp(&a.priority).then_with(|| a.id.cmp(&b.id)));
+        day.overdue = day.date < today && !day.tasks.is_empty();
+    }
+    Calendar { month: first, today, week_start, days }
+}
+
+// The month as a grid `width` columns wide, a week to a row. Narrower terminals
+// first lose the ids, then the titles; past that the month is printed as a list.
+// `color` highlights today and paints the overdue markers red. Depends on
+// nothing but its arguments.
+pub fn layout(calendar: &Calendar, width: usize, color: bool) -> String {
+    let title = calendar.month.format("%B %Y").to_string();
+    let cell = width.saturating_sub(GAP.width() * 6) / 7;
+    if cell < MIN_CELL {
+        return agenda(calendar, &title, width);
+    }
+
+    let rule = vec!["─".repeat(cell); 7].join("┼");
+    let mut lines = vec![fit(&title, width)];
+    let weekdays: Vec<String> = (0..7)
+        .map(|offset| {
+            let day = (0..offset).fold(calendar.week_start, |day, _| day.succ());
+            pad(&fit(&day.to_string(), cell), cell)
+        })
+        .collect();
+    lines.push(weekdays.join(GAP).trim_end().to_string());
+
+    let blanks = calendar.month.weekday().days_since(calendar.week_start) as usize;
+    let mut slots: Vec<Option<&Day>> = vec![None; blanks];
+    slots.extend(calendar.days.iter().map(Some));
+    slots.resize(slots.len().div_ceil(7) * 7, None);
+    for week in slots.chunks(7) {
+        lines.push(rule.clone());
+        let cells: Vec<Vec<String>> =
+            week.iter().map(|day| day.map_or_else(Vec::new, |day| day_lines(day, calendar.today, cell, color))).collect();
+        let rows = cells.iter().map(Vec::len).max().unwrap_or(0);
+        for row in 0..rows {
+            let line: Vec<String> =
+                cells.iter().map(|lines| lines.get(row).cloned().unwrap_or_else(|| " ".repeat(cell))).collect();
+            lines.push(line.join(GAP).trim_end().to_string());
+        }
+    }
+    lines.join("\n")
+}
+
+// Each line exactly `width` columns wide once escape codes are left out
+fn day_lines(day: &Day, today: NaiveDate, width: usize, color: bool) -> Vec<String> {
+    let mut lines = vec![day_heading(day, today, width, color)];
+    if width < MIN_TITLE_CELL {
+        if !day.tasks.is_empty() {
+            lines.push(pad(&fit(&format!("+{}", day.tasks.len()), width), width));
+        }
+        return lines;
+    }
+    for task in day.tasks.iter().take(TITLES_PER_DAY) {
+        let text = match width >= ID_CELL {
+            true => format!("#{} {}", task.id, task.title),
+            false => task.title.clone(),
+        };
+        lines.push(pad(&fit(&text, width), width));
+    }
+    if day.tasks.len() > TITLES_PER_DAY {
+        lines.push(pad(&format!("+{}", day.tasks.len() - TITLES_PER_DAY), width));
+    }
+    lines
+}
+
+// "14", "[15]" for today, and the overdue marker after either when there is room
+fn day_heading(day: &Day, today: NaiveDate, width: usize, color: bool) -> String {
+    let number = match day.date == today {
+        true => format!("[{}]", day.date.day()),
+        false => day.date.day().to_string(),
+    };
+    let marker = if day.overdue && number.width() < width { OVERDUE_MARKER } else { "" };
+    let rest = " ".repeat(width - number.width() - marker.width());
+    match color {
+        true => format!("{}{}{}", highlight(&number, day.date == today), red(marker), rest),
+        false => format!("{}{}{}", number, marker, rest),
+    }
+}
+
+fn highlight(text: &str, on: bool) -> String {
+    if !on {
+        return text.to_string();
+    }
+    format!("\x1b[7m{}\x1b[0m", text)
+}
+
+fn red(text: &str) -> String {
+    if text.is_empty() {
+        return String::new();
+    }
+    format!("\x1b[31m{}\x1b[0m", text)
+}
+
+// Too narrow for a grid: the days that have tasks, one task to a line
+fn agenda(calendar: &Calendar, title: &str, width: usize) -> String {
+    let mut lines = vec![fit(title, width)];
+    for day in calendar.days.iter().filter(|day| !day.tasks.is_empty()) {
+        let mut date = format!("{:>2}", day.date.day());
+        if day.date == calendar.today {
+            date = format!("[{}]", day.date.day());
+        }
+        if day.overdue {
+            date.push_str(OVERDUE_MARKER);
+        }
+        for (i, task) in day.tasks.iter().enumerate() {
+            let lead = if i == 0 { pad(&date, 5) } else { " ".repeat(5) };
+            lines.push(fit(&format!("{}#{} {}", lead, task.id, task.title), width));
+        }
+    }
+    if lines.len() == 1 {
+        lines.push(fit(&msg!("calendar_empty"), width));
+    }
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Priority;
+
+    fn date(month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2024, month, day).unwrap()
+    }
+
+    fn sample() -> Vec<Task> {
+        let rows = [
+            ("Pay rent", Priority::High, date(5, 1), TaskStatus::Pending),
+            ("Dentist", Priority::Medium, date(5, 8), TaskStatus::Pending),
+            ("Report", Priority::Critical, date(5, 15), TaskStatus::InProgress),
+            ("Review the quarterly numbers", Priority::Low, date(5, 15), TaskStatus::Pending),
+            ("Book flights", Priority::Medium, date(5, 15), TaskStatus::Pending),
+            ("Call mum", Priority::Low, date(5, 15), TaskStatus::Pending),
+            ("Shipped", Priority::High, date(5, 20), TaskStatus::Completed),
+            ("Next month", Priority::High, date(6, 1), TaskStatus::Pending),
+        ];
+        rows.into_iter()
+            .enumerate()
+            .map(|(i, (title, priority, due, status))| {
+                let mut task = Task::new(i as u32 + 1, title.to_string(), String::new(), priority);
+                task.due = Some(due);
+                task.status = status;
+                task
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_build_keeps_open_tasks_due_in_the_month() {
+        let tasks = sample();
+        let calendar = build(&tasks, date(5, 20), date(5, 10), Weekday::Mon);
+        assert_eq!((calendar.month, calendar.days.len()), (date(5, 1), 31));
+        let ids = |day: u32| calendar.days[day as usize - 1].tasks.iter().map(|task| task.id).collect::<Vec<_>>();
+        assert_eq!(ids(15), vec![3, 5, 4, 6]);
+        assert!(ids(20).is_empty());
+        let overdue: Vec<u32> = calendar.days.iter().filter(|day| day.overdue).map(|day| day.date.day()).collect();
+        assert_eq!(overdue, vec![1, 8]);
+    }
+
+    #[test]
+    fn test_month_grid() {
+        let tasks = sample();
+        let expected = "\
+May 2024
+Mon       │Tue       │Wed       │Thu       │Fri       │Sat       │Sun
+──────────┼──────────┼──────────┼──────────┼──────────┼──────────┼──────────
+          │          │1●        │2         │3         │4         │5
+          │          │Pay rent  │          │          │          │
+──────────┼──────────┼──────────┼──────────┼──────────┼──────────┼──────────
+6         │7         │8●        │9         │[10]      │11        │12
+          │          │Dentist   │          │          │          │
+──────────┼──────────┼──────────┼──────────┼──────────┼──────────┼──────────
+13        │14        │15        │16        │17        │18        │19
+          │          │Report    │          │          │          │
+          │          │Book flig…│          │          │          │
+          │          │+2        │          │          │          │
+──────────┼──────────┼──────────┼──────────┼──────────┼──────────┼──────────
+20        │21        │22        │23        │24        │25        │26
+──────────┼──────────┼──────────┼──────────┼──────────┼──────────┼──────────
+27        │28        │29        │30        │31        │          │";
+        assert_eq!(layout(&build(&tasks, date(5, 1), date(5, 10), Weekday::Mon), 76, false), expected);
+    }
+
+    #[test]
+    fn test_weeks_start_on_the_configured_day() {
+        let tasks = sample();
+        let text = layout(&build(&tasks, date(5, 1), date(5, 10), Weekday::Sun), 76, false);
+        let lines: Vec<&str> = text.lines().collect();
+        assert!(lines[1].starts_with("Sun       │Mon"));
+        assert!(lines[3].starts_with("          │          │          │1●"));
+    }
+
+    #[test]
+    fn test_narrow_terminals_shrink_cells_then_list() {
+        let tasks = sample();
+        let calendar = build(&tasks, date(5, 1), date(5, 15), Weekday::Mon);
+        let wide = layout(&calendar, 100, false);
+        assert!(wide.contains("#3 Report"));
+        let titles = layout(&calendar, 60, false);
+        assert!(titles.contains("Report") && !titles.contains("#3"));
+        let counts = layout(&calendar, 34, false);
+        assert!(counts.contains("[15]") && counts.contains("+4  │") && !counts.contains("Report"));
+        let list = layout(&calendar, 30, false);
+        assert_eq!(list.lines().take(3).collect::<Vec<_>>(), vec!["May 2024", " 1●  #1 Pay rent", " 8●  #2 Dentist"]);
+        assert!(list.contains("[15] #3 Report\n     #5 Book flights"));
+
+        for width in [10, 33, 34, 40, 59, 60, 76, 83, 84, 120] {
+            for line in layout(&calendar, width, false).lines() {
+                assert!(line.width() <= width, "{} columns wide at width {}: {:?}", line.width(), width, line);
+            }
+        }
+    }
+
+    #[test]
+    fn test_color_highlights_today_and_overdue_days() {
+        let tasks = sample();
+        let text = layout(&build(&tasks, date(5, 1), date(5, 10), Weekday::Mon), 76, true);
+        assert!(text.contains("\x1b[7m[10]\x1b[0m      │"));
+        assert!(text.contains("8\x1b[31m●\x1b[0m        │"));
+        let empty = build(&[], date(2, 1), date(5, 10), Weekday::Mon);
+        assert_eq!(layout(&empty, 30, false), "February 2024\nNo tasks due this month.");
+    }
+}
@@ -0,0 +1,2453 @@
+use std::fs;
+use std::io::{self, BufRead, IsTerminal, Write};
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Local, NaiveDate, Utc, Weekday};
+use log::{debug, error, info, warn};
+use serde_json::json;
+
+use crate::audit;
+use crate::clipboard::{self, Clipboard, CopyTarget};
+use crate::command::{
+    self, Command, CommandOutput, ListView, ParseError, RunMode, execute, execute_in, resolve_task_ref, view_summaries,
+};
+use crate::config::{self, Config};
+#[cfg(unix)]
+use crate::control::ControlSocket;
+use crate::dates::DueSpec;
+use crate::diagnostics::SizeReport;
+use crate::focus::{Focus, FocusStep, format_elapsed};
+use crate::github::{self, IssueQuery};
+use crate::import;
+use crate::jira;
+use crate::msg;
+use crate::notify;
+use crate::overdue::OverdueWatch;
+use crate::pomodoro::{self, DEFAULT_MINUTES, Interrupt, SystemClock};
+use crate::render::{self, DisplayMode, OutputFormat, OutputLevel, Renderer, Tone, format_task, format_title_matches, route};
+use crate::review::Choice as ReviewChoice;
+use crate::rpc::{self, RpcSession};
+use crate::session::{Scope, Session};
+use crate::hooks::{HookFailure, Hooks, ShellRunner};
+use crate::merge::{self, Conflict, Resolution};
+use crate::migrate::Migrated;
+use crate::storage::{FileStorage, SavedState, Storage};
+use crate::summary;
+use crate::transfer::{self, Destination, TransferRequest};
+use crate::sync::{self, ConflictPolicy, Direction, Side};
+use crate::triage::{Answers, Choice, Triage};
+#[cfg(all(feature = "tui", unix))]
+use crate::tui::{self, Action, Screen, TuiState};
+#[cfg(all(feature = "tui", unix))]
+use crate::TaskEdit;
+use crate::validation;
+use crate::watch::{self, Watcher};
+use crate::webhook::{self, WebhookNotifier, WebhookWorker};
+#[cfg(feature = "server")]
+use crate::server::ApiServer;
+use crate::{Priority, SharedTaskManager, Snapshot, Task, TaskError, TaskManager, TaskStatus};
+
+// Left out of dry runs: they reach other machines, run on their own, or manage the file
+const NOT_IN_DRY_RUN: [&str; 11] =
+    ["rollback", "serve", "webhook", "sync", "pomodoro", "triage", "focus", "convert-store", "save", "notify", "tui"];
+
+// CLI Interface
+pub struct Cli {
+    task_manager: TaskManager,
+    storage: Arc<dyn Storage>,
+    // Changes since the last save; zero when the file is up to date
+    unsaved: Arc<AtomicUsize>,
+    autosave: Autosave,
+    format: OutputFormat,
+    config: Config,
+    // Taken automatically before bulk commands, consumed by `rollback`
+    checkpoint: Option<Snapshot>,
+    // Where `run` listens for commands from other processes, if anywhere
+    socket_path: Option<PathBuf>,
+    // Set while a command at a terminal has the manager checked out of the socket's lock
+    socket_busy: Arc<AtomicBool>,
+    // The task `focus` narrowed the prompt to, until the user leaves it
+    focus: Option<Focus>,
+    // Dry run (`--dry-run`, `set dry-run on`) previews changes instead of making them
+    mode: RunMode,
+    level: OutputLevel,
+    // Someone at a terminal can be asked before a completed task is changed
+    interactive: bool,
+    // The prompt's active view and sort, None outside the REPL
+    session: Option<Session>,
+    // Where the session is kept between runs; None keeps it in memory
+    session_path: Option<PathBuf>,
+    // Scripts being run by `source` inside one another, and whether one of them
+    // has a restore point open that the rest join
+    source_depth: usize,
+    in_transaction: bool,
+    // Hooks that failed during the current command, reported once it is done
+    hook_failures: Arc<Mutex<Vec<HookFailure>>>,
+    // The tasks as the data file held them at the last load or save, to merge
+    // another process's saves against
+    base: Vec<Task>,
+    // The file holds a save a failed merge left unmerged
+    disk_ahead: bool,
+    // --on-conflict: how to settle tasks changed here and in the file without asking
+    on_conflict: Option<Resolution>,
+    // Posts webhooks in the background; waits for them when the CLI is dropped
+    webhooks: Option<WebhookWorker>,
+}
+
+impl Cli {
+    pub fn new(storage: Box<dyn Storage>, config: Config) -> Self {
+        // Large files report progress on stderr, overwriting one line
+        let mut reported = false;
+        let mut progress = |count: usize| {
+            eprint!("\r{}", msg!("loading_progress", count = count));
+            reported = true;
+        };
+        let loaded = storage.load_manager(&mut progress);
+        if reported {
+            eprintln!();
+        }
+        let mut migrated = false;
+        let task_manager = match loaded {
+            Ok(manager) => manager,
+            // An older or hand-written file still loads, saying what was changed to read it
+            Err(e) => match storage.load_lenient() {
+                Ok(Migrated { state, coercions }) => {
+                    info!("read the data file leniently with {} coercions", coercions.len());
+                    eprintln!("{}", msg!("load_migrated", count = coercions.len()));
+                    for coercion in &coercions {
+                        eprintln!("  {}", coercion);
+                    }
+                    migrated = true;
+                    TaskManager::from_state(state)
+                }
+                Err(_) => {
+                    warn!("load failed, starting with an empty list: {}", e);
+                    eprintln!("{}", msg!("load_failed", error = e));
+                    TaskManager::new()
+                }
+            },
+        };
+        // Keep the tasks rather than start empty: only the clashing keys are unusable
+        if let Err(e) = task_manager.check_keys() {
+            warn!("{}", e);
+            eprintln!("{}", msg!("keys_conflict", error = e));
+        }
+        // One line, not the list: `check` has the details and `check --fix` the repairs
+        let issues = task_manager.health();
+        if !issues.is_empty() {
+            warn!("the task list breaks {} invariants", issues.len());
+            eprintln!("{}", msg!("health_warning", count = issues.len()));
+        }
+
+        let mut cli = Cli {
+            task_manager,
+            storage: Arc::from(storage),
+            unsaved: Arc::new(AtomicUsize::new(0)),
+            autosave: Autosave::new(&config),
+            format: config.format,
+            config,
+            checkpoint: None,
+            socket_path: None,
+            socket_busy: Arc::default(),
+            focus: None,
+            mode: RunMode::Apply,
+            level: OutputLevel::Normal,
+            interactive: false,
+            session: None,
+            session_path: None,
+            source_depth: 0,
+            in_transaction: false,
+            hook_failures: Arc::default(),
+            base: Vec::new(),
+            disk_ahead: false,
+            on_conflict: None,
+            webhooks: None,
+        };
+        cli.base = cli.task_manager.list_tasks().into_iter().cloned().collect();
+
+        // A migrated file is rewritten in the current format on the next save
+        if migrated {
+            cli.unsaved.store(1, Ordering::SeqCst);
+        }
+
+        let user = cli.config.user.clone().unwrap_or_else(audit::current_user);
+        cli.task_manager.enable_audit(user.clone(), cli.config.audit_limit);
+        cli.task_manager.set_user(user);
+        cli.task_manager.set_protect_others(cli.config.protect_others_tasks, false);
+        cli.task_manager.set_rules(validation::rules_from_config(&cli.config));
+        cli.task_manager.set_urgent_days(cli.config.urgent_days);
+        cli.task_manager.set_default_estimate(cli.config.default_estimate);
+        cli.task_manager.set_week_start(cli.config.first_day_of_week.parse().unwrap_or(Weekday::Mon));
+        cli.task_manager.set_ignore_accents(cli.config.ignore_accents);
+        cli.task_manager.set_guard_completed(cli.config.guard_completed);
+        cli.task_manager.set_strict_tags(cli.config.strict_tags);
+        cli.task_manager.set_workflow(cli.config.workflow.clone());
+        cli.task_manager.set_limits(cli.config.limits);
+        if cli.config.sync_url.is_some() {
+            cli.task_manager.enable_change_queue();
+        }
+        if let Some(notifier) = cli.webhook_notifier() {
+            let worker = WebhookWorker::start(webhook::POST_TIMEOUT);
+            cli.task_manager.subscribe(notifier.in_background(&worker).subscriber());
+            cli.webhooks = Some(worker);
+        } else if !cli.config.webhooks.is_empty() {
+            warn!("webhooks are configured but this build cannot send them");
+        }
+        if !cli.config.hooks.is_empty() {
+            let timeout = Duration::from_secs(cli.config.hook_timeout_secs);
+            let hooks = Hooks::new(cli.config.hooks.clone(), Arc::new(ShellRunner), timeout);
+            cli.hook_failures = hooks.failures();
+            cli.task_manager.subscribe(hooks.subscriber());
+        }
+
+        // Autosave: every change is counted, and saved once the Autosave policy says so
+        let unsaved = Arc::clone(&cli.unsaved);
+        cli.task_manager.subscribe(Box::new(move |_, _| {
+            unsaved.fetch_add(1, Ordering::SeqCst);
+        }));
+        cli
+    }
+
+    // Save when anything changed since the last save; a failed save keeps the changes counted
+    fn save_if_dirty(&mut self) -> bool {
+        let changes = self.unsaved.swap(0, Ordering::SeqCst);
+        if changes == 0 {
+            return true;
+        }
+        match self.write() {
+            Ok(()) => true,
+            Err(e) => {
+                self.unsaved.fetch_add(changes, Ordering::SeqCst);
+                error!("save failed: {}", e);
+                eprintln!("{}", msg!("save_failed", error = e));
+                false
+            }
+        }
+    }
+
+    // Save the list, first merging in whatever another process saved since
+    // this one loaded or last saved it
+    fn write(&mut self) -> Result<(), TaskError> {
+        if self.disk_ahead || self.storage.changed_on_disk() {
+            self.disk_ahead = true;
+            self.merge_from_disk()?;
+            self.disk_ahead = false;
+        }
+        let state = self.task_manager.to_state();
+        self.storage.save(&state)?;
+        self.base = state.tasks;
+        Ok(())
+    }
+
+    // Read the file another process saved and merge its tasks into this
+    // session's. Tasks both changed are settled by --on-conflict, else by
+    // asking; with nobody to ask nothing is saved. Everything besides the
+    // tasks is kept as this session has it.
+    fn merge_from_disk(&mut self) -> Result<(), TaskError> {
+        let theirs = self.storage.load()?;
+        let mine = self.task_manager.to_state();
+        let (fixed, interactive) = (self.on_conflict, self.interactive);
+        let mut resolve = |conflict: &Conflict| fixed.or_else(|| if interactive { ask_resolution(conflict) } else { None });
+        let next_id = mine.next_id.max(theirs.next_id);
+        let merged = merge::merge(&self.base, &mine.tasks, &theirs.tasks, next_id, &mut resolve).map_err(|unresolved| {
+            let ids: Vec<String> = unresolved.iter().map(|conflict| format!("#{}", conflict.id)).collect();
+            TaskError::Storage(msg!("merge_unresolved", ids = ids.join(", ")))
+        })?;
+        info!("merged a save by another process: {} tasks taken from it", merged.taken);
+        if merged.taken > 0 {
+            eprintln!("{}", msg!("merge_taken", count = merged.taken));
+        }
+        for (old, new) in &merged.renumbered {
+            eprintln!("{}", msg!("merge_renumbered", old = old, new = new));
+        }
+        self.base = theirs.tasks;
+        self.task_manager.reload(SavedState { tasks: merged.tasks, next_id: merged.next_id, ..mine });
+        Ok(())
+    }
+
+    // --on-conflict: settle every task changed both here and in the file this way
+    pub fn set_on_conflict(&mut self, resolution: Resolution) {
+        self.on_conflict = Some(resolution);
+    }
+
+    // On the way out, so a journaled data file is left as a single snapshot
+    fn compact_storage(&self) {
+        if self.mode == RunMode::DryRun {
+            return;
+        }
+        if let Err(e) = self.storage.compact() {
+            error!("compaction failed: {}", e);
+            eprintln!("{}", msg!("save_failed", error = e));
+        }
+    }
+
+    // Accept commands on a unix socket while `run` is going
+    pub fn listen_on(&mut self, path: PathBuf) {
+        self.socket_path = Some(path);
+    }
+
+    // Keep the prompt's active view and sort in `path` between runs
+    pub fn remember_session(&mut self, path: PathBuf) {
+        self.session_path = Some(path);
+    }
+
+    pub fn run(&mut self) {
+        self.interactive = io::stdin().is_terminal();
+        self.run_from(io::stdin().lock());
+    }
+
+    // The REPL over any line source; ends at quit, exit or the end of the input
+    fn run_from(&mut self, mut lines: impl BufRead) {
+        println!("{}", msg!("banner_title"));
+        println!("{}", msg!("banner_welcome"));
+        if !self.config.quiet_start {
+            println!("{}\n", summary::layout(&summary::build(&self.task_manager, Local::now().date_naive())));
+        } else {
+            // The summary counts the inbox too, so this line is only for a quiet start
+            let inbox = self.task_manager.inbox().len();
+            if inbox > 0 {
+                println!("{}", msg!("inbox_summary", count = inbox));
+            }
+        }
+        if self.config.views_on_startup {
+            let counts: Vec<String> = view_summaries(&self.task_manager)
+                .into_iter()
+                .map(|view| msg!("view_count", name = view.name, count = view.count))
+                .collect();
+            if !counts.is_empty() {
+                println!("{}", msg!("views_summary", counts = counts.join(", ")));
+            }
+        }
+        self.resume_session();
+        if self.config.notify_on_startup && self.mode == RunMode::Apply {
+            self.send_reminders(false);
+            self.save_if_dirty();
+        }
+
+        let mut overdue = OverdueWatch::new(&self.task_manager, Local::now().date_naive());
+        let socket = self.open_socket();
+        loop {
+            // Checked before each prompt, so a task that fell due over lunch is told of
+            let today = Local::now().date_naive();
+            let alerts = match &socket {
+                Some((_, shared)) => shared.with_read(|mgr| overdue_alerts(&mut overdue, mgr, today)),
+                None => overdue_alerts(&mut overdue, &self.task_manager, today),
+            };
+            if !alerts.is_empty() && self.format == OutputFormat::Human {
+                if self.config.bell {
+                    print!("\x07");
+                }
+                for alert in alerts {
+                    println!("{}", render::paint(&alert, Tone::Bad));
+                }
+            }
+            if self.mode == RunMode::DryRun {
+                print!("[dry-run] ");
+            }
+            match (self.focus, self.session.as_ref().and_then(|session| session.scope.as_ref())) {
+                (Some(focus), _) => print!("#{}> ", focus.id),
+                (None, Some(scope)) => print!("({})> ", scope.label()),
+                (None, None) => print!("> "),
+            }
+            io::stdout().flush().unwrap();
+
+            let mut input = String::new();
+            match lines.read_line(&mut input) {
+                Ok(0) => break,
+                Ok(_) => {}
+                Err(_) => {
+                    eprintln!("{}", msg!("input_error"));
+                    continue;
+                }
+            }
+            let idle = self.autosave.idle_before(Instant::now());
+
+            let input = input.trim();
+            if input.is_empty() {
+                continue;
+            }
+
+            if input == "quit" || input == "exit" {
+                println!("{}", msg!("goodbye"));
+                break;
+            }
+
+            match &socket {
+                // At a terminal any command may stop to ask something (which task a
+                // title meant, whether to change a completed one), so the manager is
+                // checked out of the lock for it and clients are told the session is
+                // busy rather than left waiting on a person
+                Some((_, shared)) if self.interactive => {
+                    self.task_manager = shared.with_write(|mgr| {
+                        self.socket_busy.store(true, Ordering::SeqCst);
+                        std::mem::take(mgr)
+                    });
+                    self.handle_command(input);
+                    self.autosave_after_command(idle);
+                    shared.with_write(|mgr| {
+                        *mgr = std::mem::take(&mut self.task_manager);
+                        self.socket_busy.store(false, Ordering::SeqCst);
+                    });
+                }
+                // Piped input never waits on a person, so clients just wait for the lock
+                Some((_, shared)) => shared.with_write(|mgr| {
+                    std::mem::swap(mgr, &mut self.task_manager);
+                    self.handle_command(input);
+                    self.autosave_after_command(idle);
+                    std::mem::swap(mgr, &mut self.task_manager);
+                }),
+                None => {
+                    self.handle_command(input);
+                    self.autosave_after_command(idle);
+                }
+            }
+        }
+
+        if let Some((listener, shared)) = socket {
+            drop(listener);
+            self.task_manager = shared.with_write(std::mem::take);
+        }
+        self.save_if_dirty();
+        self.compact_storage();
+    }
+
+    fn autosave_after_command(&mut self, idle: bool) {
+        if idle || self.autosave.is_due(self.unsaved.load(Ordering::SeqCst)) {
+            self.save_if_dirty();
+        }
+        self.autosave.command_finished(Instant::now());
+    }
+
+    // save: write the file now, whether or not anything changed
+    fn save_now(&mut self) -> bool {
+        let unsaved = self.unsaved.swap(0, Ordering::SeqCst);
+        match self.write() {
+            Ok(()) => {
+                self.confirm(msg!("saved"));
+                true
+            }
+            Err(e) => {
+                self.unsaved.fetch_add(unsaved, Ordering::SeqCst);
+                error!("save failed: {}", e);
+                eprintln!("{}", msg!("save_failed", error = e));
+                false
+            }
+        }
+    }
+
+    // With --socket the manager moves into a shared wrapper for the session: the
+    // listener's threads use it directly and the REPL swaps it in per command
+    #[cfg(unix)]
+    fn open_socket(&mut self) -> Option<(ControlSocket, SharedTaskManager)> {
+        let path = self.socket_path.clone()?;
+        let shared = SharedTaskManager::new(std::mem::take(&mut self.task_manager));
+        let (storage, unsaved) = (Arc::clone(&self.storage), Arc::clone(&self.unsaved));
+        let after = Arc::new(move |manager: &TaskManager| {
+            if let Err(e) = save_if_changed(storage.as_ref(), &unsaved, manager) {
+                error!("save failed: {}", e);
+            }
+        });
+        match ControlSocket::bind(&path, shared.clone(), self.config.clone(), after, Arc::clone(&self.socket_busy)) {
+            Ok(socket) => {
+                println!("{}", msg!("socket_listening", path = path.display()));
+                Some((socket, shared))
+            }
+            Err(e) => {
+                eprintln!("{}", self.format.renderer().render_task_error(&e));
+                self.task_manager = shared.with_write(std::mem::take);
+                None
+            }
+        }
+    }
+
+    #[cfg(not(unix))]
+    fn open_socket(&mut self) -> Option<((), SharedTaskManager)> {
+        if self.socket_path.is_some() {
+            eprintln!("{}", msg!("socket_unavailable"));
+        }
+        None
+    }
+
+    // --rpc: answer JSON-RPC requests on stdin until it closes, saving after each one.
+    // Nothing else may be printed to stdout here, so save failures only go to the log.
+    pub fn run_rpc(&mut self) {
+        let session = RpcSession::new(&mut self.task_manager, self.config.clone());
+        let (storage, unsaved) = (&self.storage, &self.unsaved);
+        let result = rpc::run(&session, &mut self.task_manager, io::stdin().lock(), io::stdout().lock(), |manager| {
+            if let Err(e) = save_if_changed(storage.as_ref(), unsaved, manager) {
+                error!("save failed: {}", e);
+            }
+        });
+        if let Err(e) = result {
+            error!("rpc session ended: {}", e);
+        }
+        if let Err(e) = self.storage.compact() {
+            error!("compaction failed: {}", e);
+        }
+    }
+
+    // Run a single command given on the command line; returns false on failure
+    pub fn run_once(&mut self, tokens: &[String]) -> bool {
+        self.task_manager.begin_undo_step(&tokens.join(" "));
+        let ok = match expand_quick_add(tokens, Local::now().date_naive(), &self.config.holidays) {
+            // Nobody to ask which task an ambiguous title means
+            Ok(tokens) => match resolve_task_ref(&tokens, &self.task_manager, &mut |_, _| None) {
+                Ok(tokens) => self.execute_tokens(&tokens),
+                Err(e) => {
+                    eprintln!("{}", self.format.renderer().render_task_error(&e));
+                    false
+                }
+            },
+            Err(e) => {
+                eprintln!("{}", self.format.renderer().render_parse_error(&e));
+                false
+            }
+        };
+        self.task_manager.finish_undo_step();
+        let saved = self.save_if_dirty();
+        self.compact_storage();
+        ok && saved
+    }
+
+    // Whatever one command changes is taken back by a single `undo`
+    fn handle_command(&mut self, input: &str) {
+        self.task_manager.begin_undo_step(input);
+        self.dispatch_command(input);
+        self.task_manager.finish_undo_step();
+    }
+
+    // Returns false when the command failed
+    fn dispatch_command(&mut self, input: &str) -> bool {
+        let tokens: Vec<String> = input.split_whitespace().map(String::from).collect();
+        if let Some(focus) = self.focus {
+            self.focus_command(focus, &tokens);
+            return true;
+        }
+        let tokens = match expand_quick_add(&tokens, Local::now().date_naive(), &self.config.holidays) {
+            Ok(tokens) => tokens,
+            Err(e) => {
+                eprintln!("{}", self.format.renderer().render_parse_error(&e));
+                return false;
+            }
+        };
+        let tokens = match resolve_task_ref(&tokens, &self.task_manager, &mut choose_task) {
+            Ok(tokens) => tokens,
+            Err(e) => {
+                eprintln!("{}", self.format.renderer().render_task_error(&e));
+                return false;
+            }
+        };
+        // Only the interactive prompt can hold a focus, so it is not in execute_tokens
+        match tokens.first().map(String::as_str) {
+            Some("focus") if self.mode == RunMode::Apply => {
+                self.enter_focus(&tokens[1..]);
+                self.focus.is_some()
+            }
+            // Standard input is where the prompt reads its commands
+            Some("add") if tokens.iter().any(|token| token == "--stdin") => {
+                eprintln!("{}", msg!("add_stdin_one_shot"));
+                false
+            }
+            _ => self.execute_tokens(&tokens),
+        }
+    }
+
+    // source <file> [--transactional]: run each line of the file as if typed at
+    // the prompt; blank lines and lines starting with '#' are skipped. In a
+    // transaction the first failing line stops the script and puts the tasks
+    // back as they were before it began; otherwise the rest still runs. A script
+    // sourced from inside a transaction joins it rather than opening its own.
+    fn source(&mut self, args: &[String]) -> bool {
+        let (path, transactional) = match args {
+            [path] => (path, self.config.source_transactional),
+            [flag, path] | [path, flag] if flag == "--transactional" => (path, true),
+            _ => {
+                eprintln!("{}", msg!("usage_source"));
+                return false;
+            }
+        };
+        if self.source_depth >= MAX_SOURCE_DEPTH {
+            eprintln!("{}", msg!("source_too_deep", path = path, depth = MAX_SOURCE_DEPTH));
+            return false;
+        }
+        let script = match fs::read_to_string(path) {
+            Ok(script) => script,
+            Err(e) => {
+                eprintln!("{}", msg!("source_unreadable", path = path, error = e));
+                return false;
+            }
+        };
+        let restore_point = (transactional && !self.in_transaction).then(|| self.task_manager.snapshot());
+        if restore_point.is_some() {
+            self.in_transaction = true;
+        }
+        self.source_depth += 1;
+        let mut failed = None;
+        for (number, line) in script.lines().enumerate().map(|(index, line)| (index + 1, line.trim())) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if line == "quit" || line == "exit" {
+                break;
+            }
+            if !self.dispatch_command(line) {
+                eprintln!("{}", msg!("source_line_failed", path = path, line = number));
+                failed.get_or_insert(number);
+                if self.in_transaction {
+                    break;
+                }
+            }
+        }
+        self.source_depth -= 1;
+        if restore_point.is_some() {
+            self.in_transaction = false;
+        }
+        match (failed, restore_point) {
+            (None, _) => true,
+            (Some(line), Some(snapshot)) => {
+                self.task_manager.restore(snapshot);
+                eprintln!("{}", msg!("source_rolled_back", path = path, line = line));
+                false
+            }
+            (Some(_), None) => false,
+        }
+    }
+
+    fn enter_focus(&mut self, args: &[String]) {
+        let id = match args {
+            [arg] => match arg.parse::<u32>() {
+                Ok(id) => id,
+                Err(_) => return eprintln!("{}", msg!("parse_invalid_id")),
+            },
+            _ => return eprintln!("{}", msg!("usage_focus")),
+        };
+        let renderer = self.format.renderer();
+        let status = match self.task_manager.get_task(id) {
+            Ok(task) => task.status.clone(),
+            Err(e) => return eprintln!("{}", renderer.render_task_error(&e)),
+        };
+        // Focusing on a task starts it
+        if status == TaskStatus::Pending
+            && let Err(e) = self.task_manager.update_task_status(id, TaskStatus::InProgress)
+        {
+            return eprintln!("{}", renderer.render_task_error(&e));
+        }
+        if matches!(self.format, OutputFormat::Human) && io::stdout().is_terminal() {
+            print!("\x1b[2J\x1b[H");
+        }
+        println!("{}", msg!("focus_header", id = id));
+        if let Ok(task) = self.task_manager.get_task(id) {
+            println!("{}", format_task(task, DisplayMode::Verbose).trim_end());
+        }
+        println!("{}", msg!("focus_hint"));
+        self.focus = Some(Focus::new(id));
+    }
+
+    fn focus_command(&mut self, focus: Focus, tokens: &[String]) {
+        match focus.step(tokens) {
+            FocusStep::Run(command) => {
+                self.run_command(command);
+            }
+            FocusStep::RunAndLeave(command) => {
+                if self.run_command(command) {
+                    self.leave_focus();
+                }
+            }
+            FocusStep::Leave => self.leave_focus(),
+            FocusStep::Rejected => println!("{}", msg!("focus_rejected", id = focus.id)),
+            FocusStep::Invalid(e) => eprintln!("{}", self.format.renderer().render_parse_error(&e)),
+        }
+    }
+
+    fn leave_focus(&mut self) {
+        if let Some(focus) = self.focus.take() {
+            println!("{}", msg!("focus_left", id = focus.id, elapsed = format_elapsed(focus.elapsed())));
+        }
+    }
+
+    fn execute_tokens(&mut self, tokens: &[String]) -> bool {
+        // Commands that talk to the outside world or keep state of their own
+        if self.mode == RunMode::DryRun
+            && let Some(command) = tokens.first().filter(|command| NOT_IN_DRY_RUN.contains(&command.as_str()))
+        {
+            eprintln!("{}", msg!("dry_run_unavailable", command = command));
+            return false;
+        }
+        // `<command> --watch` is `watch <command>`
+        if tokens.len() > 1 && tokens.iter().any(|token| token == "--watch") {
+            let command: Vec<String> = tokens.iter().filter(|token| *token != "--watch").cloned().collect();
+            return self.watch(&command);
+        }
+        match tokens.first().map(String::as_str) {
+            Some("set") => return self.apply_setting(&tokens[1..]),
+            Some("config") => {
+                self.show_config();
+                return true;
+            }
+            Some("rollback") => return self.rollback(),
+            Some("source") => return self.source(&tokens[1..]),
+            Some("serve") => return self.serve(&tokens[1..]),
+            Some("webhook") => return self.webhook(&tokens[1..]),
+            Some("import") if tokens.get(1).is_none_or(|source| source != "tags") => return self.import(&tokens[1..]),
+            Some("sync") => return self.sync(&tokens[1..]),
+            Some("copy") => return self.copy(&tokens[1..]),
+            Some("pomodoro") => return self.pomodoro(&tokens[1..]),
+            Some("triage") if tokens.len() == 1 => return self.triage(),
+            Some("tui") if tokens.len() == 1 => return self.tui(),
+            Some("review") if !tokens.iter().any(|token| token == "--list") => return self.review(&tokens[1..]),
+            Some("watch") => return self.watch(&tokens[1..]),
+            Some("convert-store") => return self.convert_store(&tokens[1..]),
+            Some("save") if tokens.len() == 1 => return self.save_now(),
+            Some("clear-view") if tokens.len() == 1 => return self.clear_view(),
+            // Answered here rather than by execute, which has no storage to describe
+            Some("debug") if tokens[1..] == ["info"] => {
+                let report = SizeReport::gather(&self.task_manager, Some(self.storage.as_ref()));
+                println!("{}", self.format.renderer().render(&CommandOutput::Size(report)));
+                return true;
+            }
+            Some("notify") if tokens.len() == 1 => {
+                self.send_reminders(true);
+                return true;
+            }
+            _ => {}
+        }
+
+        let renderer = self.format.renderer();
+
+        // Bare `add` walks the user through the fields interactively
+        let command = if tokens == ["add"] {
+            match prompt_add_command(&self.config, Local::now().date_naive(), &mut Terminal) {
+                Some(command) => command,
+                None => return false,
+            }
+        } else {
+            match Command::parse(tokens) {
+                Ok(command) => command.with_defaults(&self.config),
+                Err(ParseError::Empty) => return true,
+                Err(e) => {
+                    eprintln!("{}", renderer.render_parse_error(&e));
+                    return false;
+                }
+            }
+        };
+
+        // `add --stdin` takes its lines from standard input
+        let command = match command {
+            Command::AddLines { priority, due, tags, parent, .. } => match io::read_to_string(io::stdin()) {
+                Ok(text) => Command::AddLines { text, priority, due, tags, parent },
+                Err(e) => {
+                    eprintln!("{}", msg!("input_error"));
+                    warn!("reading stdin failed: {}", e);
+                    return false;
+                }
+            },
+            other => other,
+        };
+        // Bulk changes list what they would touch and ask first, unless --yes or confirm = false said not to
+        let command = match command {
+            Command::Bulk { filter, change, confirmed: false } if self.config.confirm => {
+                let command = Command::Bulk { filter, change, confirmed: false };
+                match confirm_bulk(command, &mut self.task_manager, renderer, &mut Terminal) {
+                    Some(command) => command,
+                    None => return true,
+                }
+            }
+            Command::Bulk { filter, change, .. } => Command::Bulk { filter, change, confirmed: true },
+            other => other,
+        };
+        let Some(session) = &mut self.session else { return self.run_command(command) };
+        let before = session.clone();
+
+        // At the prompt `list` stays inside the active view and keeps the last sort
+        let (command, scope) = match command {
+            Command::List { mode, inbox, sort, columns, .. } => {
+                if sort.is_some() {
+                    session.sort = tokens.iter().skip_while(|token| *token != "--sort").nth(1).cloned();
+                }
+                let sort = sort.or_else(|| session.sort_key());
+                (Command::List { mode, inbox, sort, scope: session.scope.clone(), columns }, None)
+            }
+            Command::View { name, extra } => {
+                let scope = Scope::View { name: name.to_lowercase(), extra: tokens[2..].join(" ") };
+                (Command::View { name, extra }, Some(scope))
+            }
+            Command::Filter { keyword, sort, show_score } => {
+                let scope = Scope::Filter { query: keyword.clone() };
+                (Command::Filter { keyword, sort, show_score }, Some(scope))
+            }
+            other => (other, None),
+        };
+        let ok = self.run_command(command);
+        if ok
+            && let Some(scope) = scope
+            && let Some(session) = &mut self.session
+        {
+            session.scope = Some(scope);
+        }
+        if self.session.as_ref() != Some(&before) {
+            self.save_session();
+        }
+        ok
+    }
+
+    // Pick up the view active when the prompt was last left: straight away with
+    // `resume_view = true`, otherwise if the user says so
+    fn resume_session(&mut self) {
+        let mut session = match &self.session_path {
+            Some(path) => Session::load(path, &self.task_manager),
+            None => Session::default(),
+        };
+        if let Some(scope) = &session.scope {
+            let count = scope.open_count(&self.task_manager);
+            if self.config.resume_view {
+                println!("{}", msg!("view_resumed", view = scope.label(), count = count));
+            } else if !(self.interactive && ask_resume(scope, count, &mut Terminal)) {
+                session.scope = None;
+            }
+        }
+        self.session = Some(session);
+    }
+
+    fn save_session(&self) {
+        if let (Some(session), Some(path)) = (&self.session, &self.session_path)
+            && let Err(e) = session.save(path)
+        {
+            warn!("could not save the session to {}: {}", path.display(), e);
+        }
+    }
+
+    // clear-view: `list` shows every task again
+    fn clear_view(&mut self) -> bool {
+        match self.session.as_mut().and_then(|session| session.scope.take()) {
+            Some(scope) => {
+                self.save_session();
+                self.confirm(msg!("view_cleared", view = scope.label()));
+                true
+            }
+            None => {
+                println!("{}", msg!("no_active_view"));
+                false
+            }
+        }
+    }
+
+    fn run_command(&mut self, command: Command) -> bool {
+        let renderer = self.format.renderer();
+        if command.is_bulk() && self.mode == RunMode::Apply {
+            self.checkpoint = Some(self.task_manager.snapshot());
+        }
+
+        let started = Instant::now();
+        let result = if self.interactive {
+            execute_confirming(self.mode, command, &mut self.task_manager, &mut Terminal)
+        } else {
+            execute_in(self.mode, command, &mut self.task_manager)
+        };
+        let changed = result.as_ref().ok().and_then(CommandOutput::changed_task);
+        let changed = changed.and_then(|id| self.task_manager.get_task(id).ok());
+        route(renderer, &result, self.level, changed, started.elapsed()).print();
+        for truncation in self.task_manager.take_truncations() {
+            eprintln!("{}", msg!("limit_truncated", what = truncation));
+        }
+        self.report_hook_failures();
+        // Said once per period, the first time a command takes the count to the goal
+        if let Some(progress) = self.task_manager.celebrate_goal(Local::now().date_naive())
+            && self.format == OutputFormat::Human
+        {
+            self.confirm(msg!("goal_reached", done = progress.done, period = progress.period.phrase()));
+        }
+        result.is_ok()
+    }
+
+    // Hooks never fail the command that set them off, but say when they went wrong
+    fn report_hook_failures(&self) {
+        let failures = std::mem::take(&mut *self.hook_failures.lock().unwrap_or_else(PoisonError::into_inner));
+        for failure in failures {
+            eprintln!(
+                "{}",
+                msg!(
+                    "hook_failed",
+                    event = failure.event,
+                    command = failure.command,
+                    id = failure.task_id,
+                    reason = failure.reason
+                )
+            );
+        }
+    }
+
+    // A note that something worked, which --quiet leaves out
+    fn confirm(&self, text: String) {
+        if self.level != OutputLevel::Quiet {
+            println!("{}", text);
+        }
+    }
+
+    fn rollback(&mut self) -> bool {
+        match self.checkpoint.take() {
+            Some(snapshot) => {
+                let count = snapshot.len();
+                self.task_manager.restore(snapshot);
+                self.confirm(msg!("rollback_done", count = count));
+                true
+            }
+            None => {
+                println!("{}", msg!("rollback_nothing"));
+                false
+            }
+        }
+    }
+
+    // `[retention]`, once a day before the first command: a line on stderr when it
+    // archived or purged anything, and a save so the day it ran is kept even when
+    // it had nothing to do. A dry run leaves it for the next real one.
+    pub fn apply_retention(&mut self) {
+        if self.config.retention.is_off() || self.mode != RunMode::Apply {
+            return;
+        }
+        let now = Utc::now();
+        let today = now.with_timezone(&Local).date_naive();
+        let Some(plan) = self.task_manager.apply_retention(&self.config.retention, now, today) else {
+            return;
+        };
+        if !plan.is_empty() {
+            info!("retention archived {:?} and purged {:?}", plan.archive, plan.purge);
+            eprintln!("{}", plan.summary());
+        }
+        self.unsaved.fetch_add(1, Ordering::SeqCst);
+        self.save_if_dirty();
+    }
+
+    // Raise a reminder for each task that is due and not yet reminded about today;
+    // `report` also prints a summary line
+    fn send_reminders(&mut self, report: bool) {
+        let now = Local::now().naive_local();
+        let due: Vec<Task> = notify::due_reminders(self.task_manager.list_tasks(), now, self.config.notify_within_hours)
+            .into_iter()
+            .cloned()
+            .collect();
+        let notifier = notify::default_notifier();
+        let mut sent = notify::send_offset_reminders(&mut self.task_manager, now, notifier.as_ref()).len();
+        if due.is_empty() && sent == 0 {
+            if report {
+                println!("{}", msg!("nothing_due"));
+            }
+            return;
+        }
+
+        for task in &due {
+            let (summary, body) = notify::reminder_text(task, now);
+            match notifier.notify(&summary, &body) {
+                Ok(()) => {
+                    let _ = self.task_manager.mark_notified(task.id, now.date());
+                    sent += 1;
+                }
+                Err(e) => warn!("reminder for task {} failed: {}", task.id, e),
+            }
+        }
+        if report {
+            self.confirm(msg!("reminders_sent", count = sent));
+        }
+    }
+
+    fn webhook_notifier(&self) -> Option<WebhookNotifier> {
+        if self.config.webhooks.is_empty() {
+            return None;
+        }
+        Some(WebhookNotifier::new(self.config.webhooks.clone(), webhook::default_client()?))
+    }
+
+    // webhook test: post a sample payload to every configured webhook
+    fn webhook(&self, args: &[String]) -> bool {
+        if args != ["test"] {
+            eprintln!("{}", msg!("usage_webhook"));
+            return false;
+        }
+        if self.config.webhooks.is_empty() {
+            eprintln!("{}", msg!("webhook_none"));
+            return false;
+        }
+        let Some(notifier) = self.webhook_notifier() else {
+            eprintln!("{}", msg!("webhook_unavailable"));
+            return false;
+        };
+        let mut ok = true;
+        for (url, result) in notifier.send_test() {
+            match result {
+                Ok(()) => self.confirm(msg!("webhook_sent", url = url)),
+                Err(e) => {
+                    eprintln!("{}", msg!("webhook_failed", url = url, error = e));
+                    ok = false;
+                }
+            }
+        }
+        ok
+    }
+
+    // import github <owner/repo> [--label <name>] [--state <state>] | import jira <path>:
+    // upsert one task per issue, undoable with `rollback`
+    fn import(&mut self, args: &[String]) -> bool {
+        let fetched = match args {
+            [source, rest @ ..] if source == "tasks" => return self.import_tasks(rest),
+            [source, path] if source == "jira" => fs::read_to_string(path)
+                .map_err(|e| TaskError::Storage(format!("{}: {}", path, e)))
+                .and_then(|text| jira::parse_csv(&text))
+                .map(|import| ("jira", import.rows, import.warnings)),
+            _ => {
+                let Some(query) = parse_github_import(args) else {
+                    eprintln!("{}", msg!("usage_import"));
+                    return false;
+                };
+                let Some(client) = github::default_client() else {
+                    eprintln!("{}", msg!("import_unavailable"));
+                    return false;
+                };
+                github::fetch_issues(client.as_ref(), &query).map(|rows| ("github", rows, Vec::new()))
+            }
+        };
+
+        let renderer = self.format.renderer();
+        match fetched {
+            Ok((source, rows, _)) if self.mode == RunMode::DryRun => {
+                let priority = &self.config.default_priority;
+                let (_, changes) = self.task_manager.rehearse(|mgr| import::apply(mgr, source, rows, priority));
+                println!("{}", renderer.render(&CommandOutput::DryRun { changes, files: Vec::new() }));
+                true
+            }
+            Ok((source, rows, warnings)) => {
+                self.checkpoint = Some(self.task_manager.snapshot());
+                let mut report = import::apply(&mut self.task_manager, source, rows, &self.config.default_priority);
+                report.warnings.splice(0..0, warnings);
+                println!("{}", renderer.render(&CommandOutput::Imported(report)));
+                true
+            }
+            Err(e) => {
+                eprintln!("{}", renderer.render_task_error(&e));
+                false
+            }
+        }
+    }
+
+    // import tasks <path> [query...] [--move] [--duplicates skip|rename] [--yes]:
+    // bring in tasks from another list's data file, read without changing it
+    // unless --move, after showing them and asking
+    fn import_tasks(&mut self, args: &[String]) -> bool {
+        let renderer = self.format.renderer();
+        let request = match TransferRequest::parse(args) {
+            Ok(request) => request,
+            Err(e) => {
+                eprintln!("{}", renderer.render_parse_error(&e));
+                return false;
+            }
+        };
+        let source = FileStorage::new(&request.path);
+        let picked = fs::metadata(&request.path)
+            .map_err(|e| TaskError::Storage(format!("{}: {}", request.path.display(), e)))
+            .and_then(|_| transfer::pick(&source, &request.filter, self.task_manager.user()));
+        let picked = match picked {
+            Ok(picked) => picked,
+            Err(e) => {
+                eprintln!("{}", renderer.render_task_error(&e));
+                return false;
+            }
+        };
+        let name = transfer::source_name(&request.path);
+        let preview = CommandOutput::Tasks { view: ListView::Source(name.clone()), tasks: picked.tasks.clone(), mode: None };
+        if picked.tasks.is_empty() {
+            println!("{}", renderer.render(&preview));
+            return true;
+        }
+        if self.mode == RunMode::DryRun {
+            let (moving, on_duplicate) = (request.moving, request.on_duplicate);
+            let (_, changes) =
+                self.task_manager.rehearse(|mgr| transfer::copy_into(mgr, &picked.tasks, moving, on_duplicate, &name));
+            let files = if moving { vec![request.path.clone()] } else { Vec::new() };
+            println!("{}", renderer.render(&CommandOutput::DryRun { changes, files }));
+            return true;
+        }
+        if !request.confirmed && self.config.confirm {
+            println!("{}", renderer.render(&preview));
+            let count = picked.tasks.len();
+            let question = match request.moving {
+                true => msg!("import_tasks_move_confirm", count = count, source = name),
+                false => msg!("import_tasks_confirm", count = count),
+            };
+            if !matches!(Terminal.ask(&question).unwrap_or_default().to_lowercase().as_str(), "y" | "yes") {
+                println!("{}", msg!("import_tasks_cancelled"));
+                return true;
+            }
+        }
+        // Rolling back a move would lose the tasks, which are gone from the source by then
+        if !request.moving {
+            self.checkpoint = Some(self.task_manager.snapshot());
+        }
+        match transfer::run(self, picked, &source, &request) {
+            Ok(transfer) => {
+                println!("{}", renderer.render(&CommandOutput::TasksImported(transfer)));
+                true
+            }
+            Err(e) => {
+                eprintln!("{}", renderer.render_task_error(&e));
+                false
+            }
+        }
+    }
+
+    // sync [push|pull|status]: exchange changes with the `serve` instance at sync_url
+    fn sync(&mut self, args: &[String]) -> bool {
+        let direction = match args {
+            [status] if status == "status" => {
+                let state = self.task_manager.sync_state();
+                let output = CommandOutput::SyncStatus {
+                    pending: state.pending.len(),
+                    last_pull: state.last_pull,
+                    last_push: state.last_push,
+                };
+                println!("{}", self.format.renderer().render(&output));
+                return true;
+            }
+            [] => Direction::Both,
+            [direction] if direction == "push" => Direction::Push,
+            [direction] if direction == "pull" => Direction::Pull,
+            _ => {
+                eprintln!("{}", msg!("usage_sync"));
+                return false;
+            }
+        };
+        let Some(url) = self.config.sync_url.clone() else {
+            eprintln!("{}", msg!("sync_no_url"));
+            return false;
+        };
+        let Some(transport) = sync::default_transport(&url, self.config.api_token.clone()) else {
+            eprintln!("{}", msg!("sync_unavailable"));
+            return false;
+        };
+
+        let policy = self.config.sync_conflicts;
+        let mut resolve = |local: &Task, _remote: &Task| match policy {
+            ConflictPolicy::PreferLocal => Side::Local,
+            ConflictPolicy::PreferRemote => Side::Remote,
+            ConflictPolicy::Ask => loop {
+                match get_input(&msg!("sync_ask", title = local.title)).to_lowercase().as_str() {
+                    "l" | "local" => break Side::Local,
+                    "r" | "remote" => break Side::Remote,
+                    _ => {}
+                }
+            },
+        };
+        let renderer = self.format.renderer();
+        let result = sync::run(&mut self.task_manager, transport.as_ref(), direction, &mut resolve, Utc::now());
+        // The watermarks or the queue changed even if no task did
+        self.unsaved.fetch_add(1, Ordering::SeqCst);
+        match result {
+            Ok(report) => {
+                println!("{}", renderer.render(&CommandOutput::Synced(report)));
+                true
+            }
+            Err(e) => {
+                eprintln!("{}", renderer.render_task_error(&e));
+                false
+            }
+        }
+    }
+
+    // copy <id> [--full|--url]: put the title, detail view or first link on the clipboard,
+    // printing it instead when there is no clipboard
+    fn copy(&self, args: &[String]) -> bool {
+        let Some((id, target)) = parse_copy(args) else {
+            eprintln!("{}", msg!("usage_copy"));
+            return false;
+        };
+        let renderer = self.format.renderer();
+        let task = match self.task_manager.get_task(id) {
+            Ok(task) => task,
+            Err(e) => {
+                eprintln!("{}", renderer.render_task_error(&e));
+                return false;
+            }
+        };
+        let Some(text) = clipboard::copy_text(task, target) else {
+            eprintln!("{}", msg!("copy_no_link", id = id));
+            return false;
+        };
+        let mut system = clipboard::system_clipboard();
+        let copied = clipboard::deliver(&text, system.as_deref_mut().map(|clipboard| clipboard as &mut dyn Clipboard));
+        let output = CommandOutput::Copied { id, text, clipboard: copied };
+        route(renderer, &Ok(output), self.level, None, Duration::ZERO).print();
+        true
+    }
+
+    // pomodoro <id> [minutes]: timed rounds on one task, each logged on it, until
+    // the user takes a break, marks it done or stops a round with Ctrl+C
+    fn pomodoro(&mut self, args: &[String]) -> bool {
+        let Some((id, minutes)) = parse_pomodoro(args) else {
+            eprintln!("{}", msg!("usage_pomodoro"));
+            return false;
+        };
+        let renderer = self.format.renderer();
+        loop {
+            let (title, status) = match self.task_manager.get_task(id) {
+                Ok(task) => (task.title.clone(), task.status.clone()),
+                Err(e) => {
+                    eprintln!("{}", renderer.render_task_error(&e));
+                    return false;
+                }
+            };
+            if status != TaskStatus::InProgress
+                && let Err(e) = self.task_manager.update_task_status(id, TaskStatus::InProgress)
+            {
+                eprintln!("{}", renderer.render_task_error(&e));
+                return false;
+            }
+            println!("{}", msg!("pomodoro_started", id = id, title = title, minutes = minutes));
+
+            let interrupt = Interrupt::catch();
+            let length = Duration::from_secs(minutes * 60);
+            let session = pomodoro::countdown(&SystemClock, length, &interrupt.flag, &mut |left| {
+                print!("\r{}", msg!("pomodoro_left", minutes = left.as_secs().div_ceil(60)));
+                let _ = io::stdout().flush();
+            });
+            drop(interrupt);
+            println!();
+
+            let (completed, worked) = (session.completed, Duration::from_secs(session.seconds));
+            if let Err(e) = self.task_manager.log_session(id, session) {
+                eprintln!("{}", renderer.render_task_error(&e));
+                return false;
+            }
+            if !completed {
+                println!("{}", msg!("pomodoro_stopped", elapsed = format_elapsed(worked)));
+                return true;
+            }
+            let body = format!("#{} {}", id, title);
+            if let Err(e) = notify::default_notifier().notify(&msg!("pomodoro_done"), &body) {
+                warn!("pomodoro notification failed: {}", e);
+            }
+            match get_input(&msg!("pomodoro_next")).to_lowercase().as_str() {
+                "a" | "another" => continue,
+                "d" | "done" => return self.run_command(Command::Update { id, status: TaskStatus::Completed, note: None }),
+                _ => {
+                    println!("{}", msg!("pomodoro_break", id = id));
+                    return true;
+                }
+            }
+        }
+    }
+
+    // convert-store <json|msgpack>: rewrite the data file in place; later saves in
+    // this session keep the new format
+    // Walk the inbox one task at a time; anything skipped, or left when quitting, stays for next time
+    fn triage(&mut self) -> bool {
+        let ids: Vec<u32> = self.task_manager.inbox().iter().map(|task| task.id).collect();
+        if ids.is_empty() {
+            println!("{}", msg!("inbox_empty"));
+            return true;
+        }
+        let renderer = self.format.renderer();
+        let today = Local::now().date_naive();
+        let mut triaged = 0;
+        for (done, &id) in ids.iter().enumerate() {
+            let Ok(task) = self.task_manager.get_task(id) else { continue };
+            println!("{}", msg!("triage_header", n = done + 1, count = ids.len()));
+            println!("{}", format_task(task, DisplayMode::Compact));
+            let choice = loop {
+                match read_input(&msg!("prompt_triage_choice")).map(|input| Choice::parse(&input)) {
+                    Some(Some(choice)) => break choice,
+                    Some(None) => println!("{}", msg!("triage_choice_hint")),
+                    None => break Choice::Quit,
+                }
+            };
+            match choice {
+                Choice::Triage => {
+                    let answers = Answers {
+                        priority: get_input(&msg!("prompt_triage_priority")),
+                        due: get_input(&msg!("prompt_triage_due")),
+                        tags: get_input(&msg!("prompt_triage_tags")),
+                        project: get_input(&msg!("prompt_triage_project")),
+                    };
+                    let result = match Triage::from_answers(&answers, today, &self.config.holidays) {
+                        Ok(triage) => triage.apply(&mut self.task_manager, id),
+                        Err(e) => {
+                            eprintln!("{}", renderer.render_parse_error(&e));
+                            continue;
+                        }
+                    };
+                    match result {
+                        Ok(()) => triaged += 1,
+                        Err(e) => eprintln!("{}", renderer.render_task_error(&e)),
+                    }
+                }
+                Choice::Skip => {}
+                Choice::Delete => {
+                    if let Err(e) = self.task_manager.delete_task(id) {
+                        eprintln!("{}", renderer.render_task_error(&e));
+                    }
+                }
+                Choice::Quit => break,
+            }
+        }
+        println!("{}", msg!("triage_done", count = triaged, left = self.task_manager.inbox().len()));
+        true
+    }
+
+    // review [--days n]: go through open tasks nobody has touched in a while, oldest first
+    fn review(&mut self, args: &[String]) -> bool {
+        let days = match command::parse_review(args) {
+            Ok((days, _)) => days,
+            Err(e) => {
+                eprintln!("{}", self.format.renderer().render_parse_error(&e));
+                return false;
+            }
+        };
+        let now = Utc::now();
+        let ids: Vec<u32> = self.task_manager.stale_tasks(days, now).iter().map(|task| task.id).collect();
+        if ids.is_empty() {
+            println!("{}", msg!("nothing_stale", days = days));
+            return true;
+        }
+        let reviewed = review_tasks(&mut self.task_manager, &ids, now, self.format.renderer(), &mut Terminal);
+        let left = self.task_manager.stale_tasks(days, Utc::now()).len();
+        println!("{}", msg!("review_done", count = reviewed, left = left));
+        true
+    }
+
+    fn convert_store(&mut self, args: &[String]) -> bool {
+        let [format] = args else {
+            eprintln!("{}", msg!("usage_convert_store"));
+            return false;
+        };
+        let format = match config::parse_store_format(format) {
+            Ok(format) => format,
+            Err(e) => {
+                eprintln!("{}", msg!("convert_store_invalid", error = e));
+                return false;
+            }
+        };
+        // A journal must hold every change before it is folded into the new file
+        self.save_if_dirty();
+        match self.storage.convert(&self.task_manager.to_state(), format) {
+            Ok(true) => {
+                self.confirm(msg!("store_converted", path = self.config.data_path.display(), format = format));
+                if self.config.store_format != format {
+                    println!("{}", msg!("store_format_hint", format = format));
+                }
+                true
+            }
+            Ok(false) => {
+                eprintln!("{}", msg!("store_not_converted"));
+                false
+            }
+            Err(e) => {
+                error!("conversion failed: {}", e);
+                eprintln!("{}", msg!("save_failed", error = e));
+                false
+            }
+        }
+    }
+
+    // serve [--port <n>]: block answering HTTP requests until the process is stopped
+    #[cfg(feature = "server")]
+    fn serve(&mut self, args: &[String]) -> bool {
+        let port = match args {
+            [] => 8080,
+            [flag, port] if flag == "--port" => match port.parse::<u16>() {
+                Ok(port) => port,
+                Err(_) => {
+                    eprintln!("{}", msg!("usage_serve"));
+                    return false;
+                }
+            },
+            _ => {
+                eprintln!("{}", msg!("usage_serve"));
+                return false;
+            }
+        };
+
+        // The server works on a shared copy; it comes back when the server stops
+        let shared = SharedTaskManager::new(std::mem::take(&mut self.task_manager));
+        let result = ApiServer::bind(&format!("127.0.0.1:{}", port), shared.clone())
+            .map(|server| server.with_token(self.config.api_token.clone()));
+        let ok = match result {
+            Ok(server) => {
+                println!("{}", msg!("serving", port = server.port()));
+                server.run(self.storage.as_ref());
+                true
+            }
+            Err(e) => {
+                eprintln!("{}", self.format.renderer().render_task_error(&e));
+                false
+            }
+        };
+        self.task_manager = shared.with_write(std::mem::take);
+        ok
+    }
+
+    #[cfg(not(feature = "server"))]
+    fn serve(&mut self, _args: &[String]) -> bool {
+        eprintln!("{}", msg!("serve_unavailable"));
+        false
+    }
+
+    // watch [command]: run a command that shows tasks, `list` by default, again
+    // each time another process saves the data file, until Ctrl+C
+    fn watch(&mut self, args: &[String]) -> bool {
+        let tokens = if args.is_empty() { vec!["list".to_string()] } else { args.to_vec() };
+        let renderer = self.format.renderer();
+        let command = match Command::parse(&tokens) {
+            Ok(command) if command.only_shows() => command.with_defaults(&self.config),
+            Ok(_) => {
+                eprintln!("{}", msg!("watch_shows_only", command = tokens[0]));
+                return false;
+            }
+            Err(e) => {
+                eprintln!("{}", renderer.render_parse_error(&e));
+                return false;
+            }
+        };
+        // At the prompt `list` stays inside the active view, as it does unwatched
+        let command = match (command, &self.session) {
+            (Command::List { mode, inbox, sort, columns, .. }, Some(session)) => Command::List {
+                mode,
+                inbox,
+                sort: sort.or_else(|| session.sort_key()),
+                scope: session.scope.clone(),
+                columns,
+            },
+            (command, _) => command,
+        };
+        let files = self.storage.files();
+        if files.is_empty() {
+            eprintln!("{}", msg!("watch_no_file"));
+            return false;
+        }
+        // Reloading would lose anything not yet written
+        self.save_if_dirty();
+
+        let interrupt = Interrupt::catch();
+        let mut watcher = Watcher::new(files);
+        let mut failed: Option<TaskError> = None;
+        print!("\x1b[?25l");
+        loop {
+            let output = execute(command.clone(), &mut self.task_manager);
+            print!("\x1b[2J\x1b[H");
+            println!("{}", msg!("watch_header", command = tokens.join(" "), time = Local::now().format("%H:%M:%S")));
+            if let Some(e) = failed.take() {
+                println!("{}", msg!("watch_reload_failed", error = e));
+            }
+            println!();
+            match &output {
+                Ok(output) => println!("{}", renderer.render(output)),
+                Err(e) => println!("{}", renderer.render_task_error(e)),
+            }
+            let _ = io::stdout().flush();
+            if !watcher.wait(&interrupt.flag) {
+                break;
+            }
+            if let Err(e) = self.reload() {
+                warn!("reload failed, showing the list as it was: {}", e);
+                failed = Some(e);
+            }
+        }
+        print!("\x1b[?25h");
+        println!();
+        true
+    }
+
+    // Read the data file again after another process saved it; one caught half
+    // written gets a second try
+    fn reload(&mut self) -> Result<(), TaskError> {
+        let state = self.storage.load().or_else(|e| {
+            debug!("reload failed, trying again: {}", e);
+            std::thread::sleep(watch::RETRY_AFTER);
+            self.storage.load()
+        })?;
+        self.base = state.tasks.clone();
+        self.task_manager.reload(state);
+        Ok(())
+    }
+
+    // tui: the list full-screen until q; each change is its own undo step
+    #[cfg(all(feature = "tui", unix))]
+    fn tui(&mut self) -> bool {
+        let screen = match Screen::open() {
+            Ok(screen) => screen,
+            Err(e) => {
+                eprintln!("{}", msg!("tui_failed", error = e));
+                return false;
+            }
+        };
+        let result = self.tui_loop(&screen);
+        // Back on the normal screen before anything is printed
+        drop(screen);
+        match result {
+            Ok(()) => true,
+            Err(e) => {
+                eprintln!("{}", msg!("tui_failed", error = e));
+                false
+            }
+        }
+    }
+
+    #[cfg(all(feature = "tui", unix))]
+    fn tui_loop(&mut self, screen: &Screen) -> io::Result<()> {
+        let renderer = OutputFormat::Human.renderer();
+        let mut state = TuiState::new(&self.task_manager);
+        loop {
+            let (width, height) = screen.size();
+            screen.draw(&tui::frame(&state, &self.task_manager, width, height))?;
+            for key in screen.read_keys()? {
+                let result = match state.handle_key(key, &self.task_manager) {
+                    Action::Stay => continue,
+                    Action::Quit => return Ok(()),
+                    Action::Run { line, command } => {
+                        self.task_manager.begin_undo_step(&line);
+                        execute(command, &mut self.task_manager)
+                            .map(|output| renderer.render(&output).lines().next().unwrap_or_default().to_string())
+                    }
+                    Action::Priority { line, id, priority } => {
+                        self.task_manager.begin_undo_step(&line);
+                        let edit = TaskEdit { priority: Some(priority.clone()), ..TaskEdit::default() };
+                        self.task_manager.apply_edit(id, edit).map(|()| msg!("tui_priority", id = id, priority = priority))
+                    }
+                };
+                self.task_manager.finish_undo_step();
+                state.report(result.unwrap_or_else(|e| renderer.render_task_error(&e)));
+                state.refresh(&self.task_manager);
+                self.autosave_after_command(false);
+            }
+        }
+    }
+
+    #[cfg(not(all(feature = "tui", unix)))]
+    fn tui(&mut self) -> bool {
+        eprintln!("{}", msg!("tui_unavailable"));
+        false
+    }
+
+    pub fn set_mode(&mut self, mode: RunMode) {
+        self.mode = mode;
+    }
+
+    // --quiet also drops the hint under an error
+    pub fn set_level(&mut self, level: OutputLevel) {
+        self.level = level;
+        render::set_hints(level != OutputLevel::Quiet);
+    }
+
+    // --force: change completed tasks without asking
+    pub fn set_force(&mut self) {
+        self.task_manager.set_guard_completed(false);
+        self.task_manager.set_protect_others(self.config.protect_others_tasks, true);
+    }
+
+    // --truncate: store a task over the `[limits]` cut down to fit, with a warning
+    pub fn set_truncate(&mut self) {
+        self.task_manager.set_truncate_to_limits(true);
+    }
+
+    // Session settings: `set format <human|json>`, `set dry-run <on|off>`
+    fn apply_setting(&mut self, args: &[String]) -> bool {
+        match args {
+            [key, value] if key == "dry-run" && (value == "on" || value == "off") => {
+                self.mode = if value == "on" { RunMode::DryRun } else { RunMode::Apply };
+                self.confirm(if value == "on" { msg!("dry_run_on") } else { msg!("dry_run_off") });
+                true
+            }
+            [key, value] if key == "format" => match OutputFormat::from_str(value) {
+                Ok(format) => {
+                    self.format = format;
+                    true
+                }
+                Err(_) => {
+                    eprintln!("{}", msg!("invalid_format"));
+                    false
+                }
+            },
+            _ => {
+                eprintln!("{}", msg!("usage_set"));
+                false
+            }
+        }
+    }
+
+    fn show_config(&self) {
+        let settings = self.config.describe();
+        if self.format == OutputFormat::Json {
+            let data: serde_json::Map<String, serde_json::Value> = settings
+                .iter()
+                .map(|(key, value, source)| {
+                    (key.to_string(), json!({ "value": value, "source": source.to_string() }))
+                })
+                .collect();
+            println!("{}", json!({ "ok": true, "data": data }));
+            return;
+        }
+
+        println!("{}", msg!("config_header"));
+        match &self.config.path {
+            Some(path) => println!("{}", msg!("config_file", path = path.display())),
+            None => println!("{}", msg!("config_no_file")),
+        }
+        for (key, value, source) in settings {
+            println!("  {:<18} = {:<20} ({})", key, value, source);
+        }
+    }
+}
+
+// `import tasks --move` saves this session's list before touching the other one
+impl Destination for Cli {
+    fn manager(&mut self) -> &mut TaskManager {
+        &mut self.task_manager
+    }
+
+    fn save(&mut self) -> Result<(), TaskError> {
+        let unsaved = self.unsaved.swap(0, Ordering::SeqCst);
+        self.write().inspect_err(|_| {
+            self.unsaved.fetch_add(unsaved, Ordering::SeqCst);
+        })
+    }
+}
+
+// Save when anything changed since the last save; a failed save keeps the changes
+// counted. For the socket and RPC sessions, which do not merge.
+fn save_if_changed(storage: &dyn Storage, unsaved: &AtomicUsize, manager: &TaskManager) -> Result<(), TaskError> {
+    let changes = unsaved.swap(0, Ordering::SeqCst);
+    if changes == 0 {
+        return Ok(());
+    }
+    storage.save(&manager.to_state()).inspect_err(|_| {
+        unsaved.fetch_add(changes, Ordering::SeqCst);
+    })
+}
+
+// When the REPL writes its changes. With no delay every command saves as it
+// finishes. Otherwise a command's changes wait until the session goes quiet for
+// the delay, enough changes pile up, or it exits, so piped input is written in
+// batches rather than once per line. Checked as lines arrive; there is no timer.
+struct Autosave {
+    delay: Duration,
+    max_changes: usize,
+    last_command: Instant,
+}
+
+impl Autosave {
+    fn new(config: &Config) -> Self {
+        Autosave {
+            delay: Duration::from_millis(config.autosave_delay_ms),
+            max_changes: config.autosave_max_changes,
+            last_command: Instant::now(),
+        }
+    }
+
+    // Whether the wait for a line that arrived at `now` counts as going quiet
+    fn idle_before(&self, now: Instant) -> bool {
+        now.duration_since(self.last_command) >= self.delay
+    }
+
+    fn is_due(&self, unsaved: usize) -> bool {
+        self.delay.is_zero() || unsaved >= self.max_changes
+    }
+
+    fn command_finished(&mut self, now: Instant) {
+        self.last_command = now;
+    }
+}
+
+// Where the interactive flows read answers and print notes
+trait Prompter {
+    // None once the input is closed
+    fn ask(&mut self, prompt: &str) -> Option<String>;
+    fn say(&mut self, line: &str);
+}
+
+struct Terminal;
+
+impl Prompter for Terminal {
+    fn ask(&mut self, prompt: &str) -> Option<String> {
+        read_input(prompt)
+    }
+
+    fn say(&mut self, line: &str) {
+        println!("{}", line);
+    }
+}
+
+// Bare `add`: ask for each field, asking again while a required one is blank.
+// None when the input closes part way through.
+fn prompt_add_command(config: &Config, today: NaiveDate, io: &mut dyn Prompter) -> Option<Command> {
+    let required = config.required;
+    io.say(&msg!("add_header"));
+
+    let title = io.ask(&msg!("prompt_title"))?;
+    let description = loop {
+        let description = io.ask(&msg!("prompt_description"))?;
+        if !(required.description && description.is_empty()) {
+            break description;
+        }
+        io.say(&msg!("field_required"));
+    };
+
+    io.say(&msg!("prompt_priority_hint"));
+    let priority_input = io.ask(&msg!("prompt_priority"))?;
+    let priority = match Priority::from_str(&priority_input) {
+        Ok(p) => p,
+        Err(_) => {
+            let default = config.default_priority.clone();
+            io.say(&msg!("invalid_priority_default", priority = default));
+            default
+        }
+    };
+
+    let due = if required.due_date {
+        loop {
+            let answer = io.ask(&msg!("prompt_due"))?;
+            if answer.is_empty() {
+                io.say(&msg!("field_required"));
+                continue;
+            }
+            match DueSpec::parse(&answer).map(|spec| spec.resolve(today, &config.holidays)) {
+                Some(Ok(date)) => break Some(date),
+                _ => io.say(&msg!("invalid_due_answer")),
+            }
+        }
+    } else {
+        None
+    };
+
+    let tags = if required.tags {
+        loop {
+            let answer = io.ask(&msg!("prompt_tags"))?;
+            let tags: Vec<String> = answer.split([',', ' ']).filter(|tag| !tag.is_empty()).map(String::from).collect();
+            if !tags.is_empty() {
+                break tags;
+            }
+            io.say(&msg!("field_required"));
+        }
+    } else {
+        Vec::new()
+    };
+
+    Some(Command::Add {
+        title,
+        description,
+        priority: Some(priority),
+        due,
+        tags,
+        parent: None,
+        required: Some(required),
+        energy: None,
+        fields: Vec::new(),
+    })
+}
+
+// What a quick-add word starting with each sigil sets
+// Scripts `source` runs inside one another before it refuses, so a script
+// sourcing itself stops
+const MAX_SOURCE_DEPTH: usize = 16;
+
+const QUICK_SIGILS: [char; 5] = ['!', '#', '@', '^', '+'];
+
+// The custom field `@context` sets
+const CONTEXT_FIELD: &str = "context";
+
+fn is_quick_word(word: &str) -> bool {
+    word.trim_start_matches('\\').starts_with(QUICK_SIGILS)
+}
+
+// An `add` line with inline metadata, turned into the flags it stands for so
+// it parses like any other: `add Fix login !high #backend @work ^friday +auth`
+// becomes `add Fix login --priority high --tag backend --field context work
+// --due <that friday> --parent auth`. `!`, `@`, `^` and `+` may each be given
+// once; a backslash keeps a sigil in the title (`\#1` is "#1"). A bad word
+// fails the whole line, so no task is made half set up. Other lines are
+// returned as they are.
+fn expand_quick_add(tokens: &[String], today: NaiveDate, holidays: &[NaiveDate]) -> Result<Vec<String>, ParseError> {
+    let Some((add, args)) = tokens.split_first().filter(|(add, args)| *add == "add" && args.iter().any(|word| is_quick_word(word)))
+    else {
+        return Ok(tokens.to_vec());
+    };
+    let mut words = vec![add.clone()];
+    let mut flags: Vec<String> = Vec::new();
+    let mut given = Vec::new();
+    for word in args {
+        if let Some(literal) = word.strip_prefix('\\').filter(|rest| is_quick_word(rest)) {
+            words.push(literal.to_string());
+            continue;
+        }
+        let Some(sigil) = word.chars().next().filter(|c| QUICK_SIGILS.contains(c)) else {
+            words.push(word.clone());
+            continue;
+        };
+        let value = &word[sigil.len_utf8()..];
+        if value.is_empty() || (sigil != '#' && given.contains(&sigil)) {
+            return Err(ParseError::InvalidToken(word.clone()));
+        }
+        given.push(sigil);
+        match sigil {
+            '!' => {
+                let priority = Priority::from_str(value).map_err(|_| ParseError::InvalidPriority(value.to_string()))?;
+                flags.extend(["--priority".to_string(), priority.to_string()]);
+            }
+            '#' => flags.extend(["--tag".to_string(), value.to_string()]),
+            '@' => flags.extend(["--field".to_string(), CONTEXT_FIELD.to_string(), value.to_string()]),
+            '^' => {
+                let due = DueSpec::parse(value)
+                    .and_then(|spec| spec.resolve(today, holidays).ok())
+                    .ok_or_else(|| ParseError::InvalidDate(value.to_string()))?;
+                flags.extend(["--due".to_string(), due.to_string()]);
+            }
+            _ => flags.extend(["--parent".to_string(), value.to_string()]),
+        }
+    }
+    words.extend(flags);
+    Ok(words)
+}
+
+// Show what an unconfirmed bulk command found and ask whether to go ahead: the
+// confirmed command on yes, None when nothing matched or the answer was anything else
+fn confirm_bulk(
+    command: Command,
+    mgr: &mut TaskManager,
+    renderer: &dyn Renderer,
+    io: &mut dyn Prompter,
+) -> Option<Command> {
+    let preview = match execute(command.clone(), mgr) {
+        Ok(preview) => preview,
+        Err(e) => {
+            io.say(&renderer.render_task_error(&e));
+            return None;
+        }
+    };
+    io.say(&renderer.render(&preview));
+    let CommandOutput::BulkPreview { tasks, .. } = &preview else { return None };
+    let Command::Bulk { filter, change, .. } = command else { return None };
+    if tasks.is_empty() {
+        return None;
+    }
+    match io.ask(&msg!("bulk_confirm", count = tasks.len()))?.to_lowercase().as_str() {
+        "y" | "yes" => Some(Command::Bulk { filter, change, confirmed: true }),
+        _ => {
+            io.say(&msg!("bulk_cancelled"));
+            None
+        }
+    }
+}
+
+// A command refused because it would change a completed task, or someone else's
+// protected one, runs again with the guard off once the user says so
+fn execute_confirming(
+    mode: RunMode,
+    command: Command,
+    mgr: &mut TaskManager,
+    io: &mut dyn Prompter,
+) -> Result<CommandOutput, TaskError> {
+    match execute_in(mode, command.clone(), mgr) {
+        Err(TaskError::TaskCompleted { id }) => {
+            let answer = io.ask(&msg!("completed_confirm", id = id)).unwrap_or_default().to_lowercase();
+            if !matches!(answer.as_str(), "y" | "yes") {
+                return Err(TaskError::TaskCompleted { id });
+            }
+            mgr.set_guard_completed(false);
+            let result = execute_in(mode, command, mgr);
+            mgr.set_guard_completed(true);
+            result
+        }
+        // Never asked with --force, which lets the change through already
+        Err(TaskError::NotOwner { id, owner }) => {
+            let answer = io.ask(&msg!("not_owner_confirm", id = id, owner = owner)).unwrap_or_default().to_lowercase();
+            if !matches!(answer.as_str(), "y" | "yes") {
+                return Err(TaskError::NotOwner { id, owner });
+            }
+            mgr.set_protect_others(true, true);
+            let result = execute_in(mode, command, mgr);
+            mgr.set_protect_others(true, false);
+            result
+        }
+        result => result,
+    }
+}
+
+// Empty or yes resumes, like the [Y/n] says
+// Ask what to do with each stale task in turn, making each change as it is
+// chosen; returns how many were dealt with before the end or a quit
+fn review_tasks(mgr: &mut TaskManager, ids: &[u32], now: DateTime<Utc>, renderer: &dyn Renderer, io: &mut dyn Prompter) -> usize {
+    let mut reviewed = 0;
+    for (done, &id) in ids.iter().enumerate() {
+        let Ok(task) = mgr.get_task(id) else { continue };
+        io.say(&msg!("review_header", n = done + 1, count = ids.len()));
+        io.say(&render::format_stale(task, now));
+        let choice = loop {
+            match io.ask(&msg!("prompt_review_choice")).map(|input| ReviewChoice::parse(&input)) {
+                Some(Some(choice)) => break choice,
+                Some(None) => io.say(&msg!("review_choice_hint")),
+                None => break ReviewChoice::Quit,
+            }
+        };
+        match choice {
+            ReviewChoice::Quit => break,
+            ReviewChoice::Skip => {}
+            choice => match choice.apply(mgr, id, now.date_naive()) {
+                Ok(()) => reviewed += 1,
+                Err(e) => io.say(&renderer.render_task_error(&e)),
+            },
+        }
+    }
+    reviewed
+}
+
+fn ask_resume(scope: &Scope, count: usize, io: &mut dyn Prompter) -> bool {
+    let Some(answer) = io.ask(&msg!("resume_view", view = scope.label(), count = count)) else { return false };
+    matches!(answer.to_lowercase().as_str(), "" | "y" | "yes")
+}
+
+// A line for each task that has become overdue since the last prompt
+fn overdue_alerts(watch: &mut OverdueWatch, manager: &TaskManager, today: NaiveDate) -> Vec<String> {
+    watch
+        .check(manager, today)
+        .into_iter()
+        .filter_map(|id| manager.get_task(id).ok())
+        .filter_map(|task| task.due.map(|due| msg!("overdue_alert", id = task.id, title = task.title, due = due)))
+        .collect()
+}
+
+fn get_input(prompt: &str) -> String {
+    read_input(prompt).unwrap_or_default()
+}
+
+// Which copy of a task changed here and in the file to keep; None once stdin is closed
+fn ask_resolution(conflict: &Conflict) -> Option<Resolution> {
+    let side = |copy: &Option<Task>| if copy.is_some() { msg!("merge_edited") } else { msg!("merge_deleted") };
+    let prompt =
+        msg!("merge_ask", id = conflict.id, title = conflict.title, mine = side(&conflict.mine), theirs = side(&conflict.theirs));
+    loop {
+        if let Ok(resolution) = read_input(&prompt)?.parse() {
+            return Some(resolution);
+        }
+    }
+}
+
+// None once stdin is closed
+// Ask which of the tasks a title could mean was meant
+fn choose_task(text: &str, matches: &[(u32, String)]) -> Option<u32> {
+    println!("{}{}", msg!("error_ambiguous_title", text = text), format_title_matches(matches));
+    read_input(&msg!("choose_task"))?.parse().ok()
+}
+
+fn read_input(prompt: &str) -> Option<String> {
+    print!("{}", prompt);
+    io::stdout().flush().unwrap();
+    let mut input = String::new();
+    match io::stdin().read_line(&mut input).unwrap() {
+        0 => None,
+        _ => Some(input.trim().to_string()),
+    }
+}
+
+fn parse_pomodoro(args: &[String]) -> Option<(u32, u64)> {
+    match args {
+        [id] => Some((id.parse().ok()?, DEFAULT_MINUTES)),
+        [id, minutes] => Some((id.parse().ok()?, minutes.parse().ok().filter(|&minutes| minutes > 0)?)),
+        _ => None,
+    }
+}
+
+fn parse_copy(args: &[String]) -> Option<(u32, CopyTarget)> {
+    let (id, flags) = args.split_first()?;
+    let target = match flags {
+        [] => CopyTarget::Title,
+        [flag] if flag == "--full" => CopyTarget::Full,
+        [flag] if flag == "--url" => CopyTarget::Url,
+        _ => return None,
+    };
+    Some((id.parse().ok()?, target))
+}
+
+fn parse_github_import(args: &[String]) -> Option<IssueQuery> {
+    let (source, rest) = args.split_first()?;
+    let (repo, options) = rest.split_first()?;
+    let (owner, name) = repo.split_once('/')?;
+    if source != "github" || owner.is_empty() || name.is_empty() || name.contains('/') {
+        return None;
+    }
+    let mut query = IssueQuery { repo: repo.clone(), label: None, state: "open".to_string() };
+    for pair in options.chunks(2) {
+        match pair {
+            [flag, label] if flag == "--label" => query.label = Some(label.clone()),
+            [flag, state] if flag == "--state" && ["open", "closed", "all"].contains(&state.as_str()) => {
+                query.state = state.clone()
+            }
+            _ => return None,
+        }
+    }
+    Some(query)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::command::execute;
+    use crate::render::HumanRenderer;
+    use crate::storage::{FileStorage, InMemoryStorage, SavedState};
+
+    // Counts the writes that actually reach storage
+    struct CountingStorage {
+        saves: Arc<AtomicUsize>,
+    }
+
+    impl Storage for CountingStorage {
+        fn load(&self) -> Result<SavedState, TaskError> {
+            Ok(SavedState::default())
+        }
+
+        fn save(&self, _state: &SavedState) -> Result<(), TaskError> {
+            self.saves.fetch_add(1, Ordering::SeqCst);
+            Ok(())
+        }
+    }
+
+    // Run `script` through the REPL and return how many times it saved
+    fn count_saves(config: Config, script: &str) -> usize {
+        let saves = Arc::new(AtomicUsize::new(0));
+        let mut cli = Cli::new(Box::new(CountingStorage { saves: Arc::clone(&saves) }), config);
+        cli.run_from(Cursor::new(script.to_string()));
+        saves.load(Ordering::SeqCst)
+    }
+
+    fn autosave_config(delay_ms: u64, max_changes: usize) -> Config {
+        let mut config = Config::default();
+        config.autosave_delay_ms = delay_ms;
+        config.autosave_max_changes = max_changes;
+        config
+    }
+
+    fn adds(count: usize) -> String {
+        (1..=count).map(|i| format!("add Task{}\n", i)).collect()
+    }
+
+    // Answers the prompts in order and records everything asked and said
+    struct Scripted {
+        answers: std::collections::VecDeque<&'static str>,
+        transcript: Vec<String>,
+    }
+
+    impl Prompter for Scripted {
+        fn ask(&mut self, prompt: &str) -> Option<String> {
+            self.transcript.push(prompt.to_string());
+            self.answers.pop_front().map(String::from)
+        }
+
+        fn say(&mut self, line: &str) {
+            self.transcript.push(line.to_string());
+        }
+    }
+
+    fn run_add(config: &Config, answers: &[&'static str]) -> (Option<Command>, Vec<String>) {
+        let mut script = Scripted { answers: answers.iter().copied().collect(), transcript: Vec::new() };
+        let today = NaiveDate::from_ymd_opt(2024, 7, 1).unwrap();
+        let command = prompt_add_command(config, today, &mut script);
+        (command, script.transcript)
+    }
+
+    #[test]
+    fn test_interactive_add_without_requirements() {
+        let (command, transcript) = run_add(&Config::default(), &["Water plants", "", ""]);
+        assert_eq!(transcript, vec![
+            msg!("add_header"),
+            msg!("prompt_title"),
+            msg!("prompt_description"),
+            msg!("prompt_priority_hint"),
+            msg!("prompt_priority"),
+            msg!("invalid_priority_default", priority = Priority::Medium),
+        ]);
+        assert!(matches!(command, Some(Command::Add { priority: Some(Priority::Medium), due: None, .. })));
+    }
+
+    #[test]
+    fn test_interactive_add_asks_again_for_required_fields() {
+        let mut config = Config::default();
+        config.default_priority = Priority::High;
+        config.required = validation::RequiredFields { due_date: true, tags: true, description: true };
+        let answers = ["Ship release", "", "tag and publish", "", "", "soon", "2 days", "", "release, ops"];
+        let (command, transcript) = run_add(&config, &answers);
+        assert_eq!(transcript, vec![
+            msg!("add_header"),
+            msg!("prompt_title"),
+            msg!("prompt_description"),
+            msg!("field_required"),
+            msg!("prompt_description"),
+            msg!("prompt_priority_hint"),
+            msg!("prompt_priority"),
+            msg!("invalid_priority_default", priority = Priority::High),
+            msg!("prompt_due"),
+            msg!("field_required"),
+            msg!("prompt_due"),
+            msg!("invalid_due_answer"),
+            msg!("prompt_due"),
+            msg!("prompt_tags"),
+            msg!("field_required"),
+            msg!("prompt_tags"),
+        ]);
+
+        let mut manager = TaskManager::new();
+        let Some(CommandOutput::Added { id, .. }) = command.map(|command| execute(command, &mut manager).unwrap()) else {
+            panic!("the add flow did not finish");
+        };
+        let task = manager.get_task(id).unwrap();
+        assert_eq!((task.description.as_str(), &task.priority), ("tag and publish", &Priority::High));
+        assert_eq!(task.due, NaiveDate::from_ymd_opt(2024, 7, 3));
+        assert_eq!(task.tags, vec!["release".into(), "ops".into()]);
+    }
+
+    #[test]
+    fn test_interactive_add_stops_when_input_closes() {
+        let mut config = Config::default();
+        config.required.description = true;
+        assert_eq!(run_add(&config, &["Half typed", ""]).0, None);
+    }
+
+    #[test]
+    fn test_bulk_commands_ask_before_changing_anything() {
+        let mut manager = TaskManager::new();
+        for title in ["Login form", "Login api", "Invoices"] {
+            manager.add_task(title.to_string(), String::new(), Priority::Low).unwrap();
+        }
+        manager.add_tag_to_task(2, "auth".to_string()).unwrap();
+        let renderer = OutputFormat::Human.renderer();
+        let tag_all = |args: &str| Command::parse(&args.split(' ').map(String::from).collect::<Vec<_>>()).unwrap();
+
+        let mut declined = Scripted { answers: ["n"].into_iter().collect(), transcript: Vec::new() };
+        assert_eq!(confirm_bulk(tag_all("tag-all login auth"), &mut manager, renderer, &mut declined), None);
+        assert_eq!(declined.transcript.last(), Some(&msg!("bulk_cancelled")));
+        assert!(manager.get_task(1).unwrap().tags.is_empty());
+
+        // Nothing found: said so, and nothing asked
+        let mut unasked = Scripted { answers: Default::default(), transcript: Vec::new() };
+        assert_eq!(confirm_bulk(tag_all("tag-all groceries auth"), &mut manager, renderer, &mut unasked), None);
+        assert_eq!(unasked.transcript, vec![msg!("bulk_no_match")]);
+
+        let mut accepted = Scripted { answers: ["y"].into_iter().collect(), transcript: Vec::new() };
+        let command = confirm_bulk(tag_all("tag-all login auth"), &mut manager, renderer, &mut accepted).unwrap();
+        assert!(accepted.transcript.contains(&msg!("bulk_confirm", count = 2)));
+        let outcome = execute(command, &mut manager).unwrap();
+        assert_eq!(renderer.render(&outcome), msg!("bulk_tagged", changed = 1, unchanged = 1));
+        assert_eq!(manager.get_tasks_by_tags(&["auth".to_string()]).len(), 2);
+    }
+
+    #[test]
+    fn test_changing_a_completed_task_asks_first() {
+        let mut manager = TaskManager::new();
+        manager.set_guard_completed(true);
+        let id = manager.add_task("Old report".to_string(), String::new(), Priority::Low).unwrap();
+        manager.update_task_status(id, TaskStatus::Completed).unwrap();
+        let tag = || Command::Tag { id, tag: "urgent".to_string() };
+
+        let mut declined = Scripted { answers: ["n"].into_iter().collect(), transcript: Vec::new() };
+        let result = execute_confirming(RunMode::Apply, tag(), &mut manager, &mut declined);
+        assert!(matches!(result, Err(TaskError::TaskCompleted { id: 1 })));
+        assert_eq!(declined.transcript, vec![msg!("completed_confirm", id = id)]);
+        assert!(manager.get_task(id).unwrap().tags.is_empty());
+
+        let mut accepted = Scripted { answers: ["y"].into_iter().collect(), transcript: Vec::new() };
+        execute_confirming(RunMode::Apply, tag(), &mut manager, &mut accepted).unwrap();
+        assert_eq!(manager.get_task(id).unwrap().tags.len(), 1);
+        // Only that once
+        assert!(execute(Command::Tag { id, tag: "later".to_string() }, &mut manager).is_err());
+    }
+
+    #[test]
+    fn test_one_shot_commands_need_force_for_completed_tasks() {
+        let mut cli = Cli::new(Box::new(InMemoryStorage::new()), Config::default());
+        let run = |cli: &mut Cli, line: &str| cli.run_once(&line.split(' ').map(String::from).collect::<Vec<_>>());
+        assert!(run(&mut cli, "add Old report"));
+        assert!(run(&mut cli, "update 1 completed"));
+        assert!(!run(&mut cli, "tag 1 urgent"));
+        assert!(cli.task_manager.get_task(1).unwrap().tags.is_empty());
+
+        cli.set_force();
+        assert!(run(&mut cli, "tag 1 urgent"));
+        assert_eq!(cli.task_manager.get_task(1).unwrap().tags.len(), 1);
+    }
+
+    #[test]
+    fn test_review_walks_stale_tasks_and_keeps_changes_on_quit() {
+        let now = Utc::now();
+        let mut mgr = TaskManager::new();
+        for (title, idle) in [("Renew passport", 20), ("Sort photos", 60), ("Fix bike", 30), ("Plan trip", 25)] {
+            let mut task = Task::new(0, title.to_string(), String::new(), Priority::Low);
+            task.updated_at = now - chrono::TimeDelta::days(idle);
+            mgr.create_task(task).unwrap();
+        }
+        let ids: Vec<u32> = mgr.stale_tasks(14, now).iter().map(|task| task.id).collect();
+        assert_eq!(ids, [2, 3, 4, 1]);
+
+        let mut script = Scripted { answers: ["c", "what", "k", "", "q"].into(), transcript: Vec::new() };
+        let reviewed = review_tasks(&mut mgr, &ids, now, &HumanRenderer, &mut script);
+        assert_eq!(reviewed, 2);
+        assert_eq!(script.transcript[0], "--- Review 1/4 ---");
+        assert!(script.transcript[1].ends_with("(untouched 60 days)"));
+        assert!(script.transcript.contains(&"Answer k, p, s, c, d, n or q.".to_string()));
+        // Quit on the last one: the first two changes stay, the rest are still stale
+        assert_eq!(mgr.get_task(2).unwrap().status, TaskStatus::Completed);
+        let left: Vec<u32> = mgr.stale_tasks(14, now).iter().map(|task| task.id).collect();
+        assert_eq!(left, [4, 1]);
+    }
+
+    #[test]
+    fn test_transactional_source_undoes_the_whole_script() {
+        let dir = std::env::temp_dir().join(format!("task-manager-cli-source-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let script = dir.join("setup.txt");
+        std::fs::write(&script, "add Book venue\n# the caterer next\nadd Call caterer\nupdate 99 done\nadd Send invites\n").unwrap();
+        let source = |cli: &mut Cli, args: &str| {
+            let mut tokens = vec!["source".to_string()];
+            tokens.extend(args.split(' ').map(String::from));
+            cli.run_once(&tokens)
+        };
+        let titles = |cli: &Cli| cli.task_manager.list_tasks().iter().map(|task| task.title.clone()).collect::<Vec<_>>();
+        let path = script.display().to_string();
+
+        let mut cli = Cli::new(Box::new(InMemoryStorage::new()), Config::default());
+        cli.run_once(&["add".to_string(), "Existing".to_string()]);
+        assert!(!source(&mut cli, &format!("{} --transactional", path)));
+        assert_eq!(titles(&cli), ["Existing"]);
+        assert!(!cli.in_transaction);
+        // The next task takes the id the rolled-back ones had
+        cli.run_once(&["add".to_string(), "After".to_string()]);
+        assert_eq!(cli.task_manager.get_task(2).unwrap().title, "After");
+
+        // Without a transaction the failing line is skipped and the rest stays
+        let mut cli = Cli::new(Box::new(InMemoryStorage::new()), Config::default());
+        assert!(!source(&mut cli, &path));
+        assert_eq!(titles(&cli), ["Book venue", "Call caterer", "Send invites"]);
+
+        // A nested script joins the outer transaction, so its failure undoes the outer lines too
+        let outer = dir.join("outer.txt");
+        std::fs::write(&outer, format!("add Budget\nsource {}\n", path)).unwrap();
+        let mut config = Config::default();
+        config.source_transactional = true;
+        let mut cli = Cli::new(Box::new(InMemoryStorage::new()), config);
+        assert!(!source(&mut cli, &outer.display().to_string()));
+        assert!(titles(&cli).is_empty());
+
+        // A script sourcing itself stops at the depth limit instead of running forever
+        std::fs::write(&outer, format!("source {}\n", outer.display())).unwrap();
+        assert!(!source(&mut cli, &outer.display().to_string()));
+        assert_eq!(cli.source_depth, 0);
+        assert!(!source(&mut cli, "missing.txt"));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_watch_reloads_what_another_process_saved() {
+        let run = |cli: &mut Cli, line: &str| cli.run_once(&line.split(' ').map(String::from).collect::<Vec<_>>());
+        let mut in_memory = Cli::new(Box::new(InMemoryStorage::new()), Config::default());
+        assert!(!run(&mut in_memory, "watch"));
+        assert!(!run(&mut in_memory, "add Buy milk --watch"));
+        assert_eq!(in_memory.task_manager.list_tasks().len(), 0);
+
+        let path = std::env::temp_dir().join(format!("task-manager-cli-watch-{}.json", std::process::id()));
+        let mut cli = Cli::new(Box::new(FileStorage::new(&path)), Config::default());
+        assert!(run(&mut cli, "add Write report"));
+        let mut other = Cli::new(Box::new(FileStorage::new(&path)), Config::default());
+        assert!(run(&mut other, "add Buy milk"));
+        assert!(cli.reload().is_ok());
+        assert_eq!(cli.task_manager.list_tasks().len(), 2);
+
+        // Half written, and still so on the second try: the list stays as it was
+        std::fs::write(&path, "{\"tasks\": [").unwrap();
+        assert!(cli.reload().is_err());
+        assert_eq!(cli.task_manager.list_tasks().len(), 2);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_save_merges_what_another_process_saved() {
+        let run = |cli: &mut Cli, line: &str| cli.run_once(&line.split(' ').map(String::from).collect::<Vec<_>>());
+        let titles = |state: &SavedState| {
+            state.tasks.iter().map(|task| (task.id, task.title.clone(), task.status.clone())).collect::<Vec<_>>()
+        };
+        let path = std::env::temp_dir().join(format!("task-manager-cli-merge-{}.json", std::process::id()));
+        let on_disk = || FileStorage::new(&path).load().unwrap();
+        let mut ours = Cli::new(Box::new(FileStorage::new(&path)), Config::default());
+        assert!(run(&mut ours, "add Report"));
+        assert!(run(&mut ours, "add Review"));
+
+        // Different tasks: both sides' changes survive, and our new task moves to a free id
+        let mut theirs = Cli::new(Box::new(FileStorage::new(&path)), Config::default());
+        assert!(run(&mut theirs, "update 2 completed"));
+        assert!(run(&mut theirs, "add Other"));
+        assert!(run(&mut ours, "add Deploy"));
+        let expected = [
+            (1, "Report".to_string(), TaskStatus::Pending),
+            (2, "Review".to_string(), TaskStatus::Completed),
+            (3, "Other".to_string(), TaskStatus::Pending),
+            (4, "Deploy".to_string(), TaskStatus::Pending),
+        ];
+        assert_eq!(titles(&on_disk()), expected);
+        assert_eq!(titles(&ours.task_manager.to_state()), expected);
+
+        // The same task: nobody to ask, so nothing is saved until a side is picked
+        assert!(run(&mut ours, "update 1 progress"));
+        assert!(!run(&mut theirs, "update 1 completed"));
+        assert_eq!(on_disk().tasks[0].status, TaskStatus::InProgress);
+        theirs.set_on_conflict(Resolution::Mine);
+        assert!(run(&mut theirs, "save"));
+        assert_eq!(titles(&on_disk()), titles(&theirs.task_manager.to_state()));
+        assert_eq!(on_disk().tasks[0].status, TaskStatus::Completed);
+        assert_eq!(on_disk().tasks.len(), 4);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_prompt_keeps_the_active_view_between_sessions() {
+        let path = std::env::temp_dir().join(format!("task-manager-cli-session-{}.json", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let mut config = Config::default();
+        config.resume_view = true;
+        let mut cli = Cli::new(Box::new(InMemoryStorage::new()), config);
+        cli.remember_session(path.clone());
+        let script = "add Fix login\ntag 1 backend\nadd Invoices\nview save backend tag:backend\nview nope\nview backend\nlist --sort title\nquit\n";
+        cli.run_from(Cursor::new(script));
+        let backend = Scope::View { name: "backend".to_string(), extra: String::new() };
+        let saved = Session { scope: Some(backend.clone()), sort: Some("title".to_string()) };
+        assert_eq!(Session::load(&path, &cli.task_manager), saved);
+
+        // The next session starts inside the view, until clear-view
+        cli.session = None;
+        cli.run_from(Cursor::new("clear-view\nquit\n"));
+        assert_eq!(Session::load(&path, &cli.task_manager), Session { scope: None, ..saved });
+        let _ = std::fs::remove_file(&path);
+
+        let ask = |answers: &[&'static str]| {
+            ask_resume(&backend, 14, &mut Scripted { answers: answers.iter().copied().collect(), transcript: Vec::new() })
+        };
+        assert!(ask(&[""]) && ask(&["Y"]));
+        assert!(!ask(&["n"]) && !ask(&[]));
+    }
+
+    #[test]
+    fn test_autosave_without_delay_saves_every_change() {
+        assert_eq!(count_saves(Config::default(), &format!("{}list\nquit\n", adds(5))), 5);
+    }
+
+    #[test]
+    fn test_burst_of_changes_is_saved_in_batches() {
+        let config = autosave_config(60_000, 10);
+        // Two full batches, then the remaining five when the input runs out
+        assert_eq!(count_saves(config.clone(), &adds(25)), 3);
+        assert_eq!(count_saves(config, &format!("{}quit\n", adds(3))), 1);
+    }
+
+    #[test]
+    fn test_save_command_writes_immediately() {
+        let config = autosave_config(60_000, 100);
+        assert_eq!(count_saves(config.clone(), "add One\nsave\nsave\nlist\nquit\n"), 2);
+        assert_eq!(count_saves(config, "add One\nsave\nadd Two\nquit\n"), 2);
+    }
+
+    #[test]
+    fn test_autosave_waits_for_quiet_or_a_full_batch() {
+        let config = autosave_config(500, 10);
+        let mut autosave = Autosave::new(&config);
+        let start = Instant::now();
+        autosave.command_finished(start);
+        assert!(!autosave.idle_before(start + Duration::from_millis(499)));
+        assert!(autosave.idle_before(start + Duration::from_millis(500)));
+        assert!(!autosave.is_due(9));
+        assert!(autosave.is_due(10));
+        assert!(Autosave::new(&Config::default()).is_due(1));
+    }
+
+    #[test]
+    fn test_focus_restricts_commands_to_the_focused_task() {
+        let saves = Arc::new(AtomicUsize::new(0));
+        let mut cli = Cli::new(Box::new(CountingStorage { saves }), Config::default());
+        let script = "add Write report\nadd Other\nfocus 1\ndelete 2\nnote first draft\nnote sent it\ndone\ndelete 2\n";
+        cli.run_from(Cursor::new(script.to_string()));
+
+        let report = cli.task_manager.get_task(1).unwrap();
+        assert_eq!(report.status, TaskStatus::Completed);
+        assert_eq!(report.description, "first draft\nsent it");
+        // Rejected while focusing, then allowed once `done` left focus mode
+        assert!(cli.focus.is_none());
+        assert!(cli.task_manager.get_task(2).is_err());
+    }
+
+    #[test]
+    fn test_pause_and_quit_focus_leave_focus_mode() {
+        let saves = Arc::new(AtomicUsize::new(0));
+        let mut cli = Cli::new(Box::new(CountingStorage { saves }), Config::default());
+        cli.run_from(Cursor::new("add One\nfocus 1\n".to_string()));
+        assert_eq!(cli.focus.map(|focus| focus.id), Some(1));
+        assert_eq!(cli.task_manager.get_task(1).unwrap().status, TaskStatus::InProgress);
+
+        cli.run_from(Cursor::new("pause\n".to_string()));
+        assert!(cli.focus.is_none());
+        assert_eq!(cli.task_manager.get_task(1).unwrap().status, TaskStatus::Pending);
+
+        cli.run_from(Cursor::new("focus 1\nquit-focus\nfocus 9\n".to_string()));
+        assert!(cli.focus.is_none());
+        assert_eq!(cli.task_manager.get_task(1).unwrap().status, TaskStatus::InProgress);
+    }
+
+    fn words(line: &str) -> Vec<String> {
+        line.split_whitespace().map(String::from).collect()
+    }
+
+    #[test]
+    fn test_expand_quick_add() {
+        // 2024-07-03 is a Wednesday
+        let today = NaiveDate::from_ymd_opt(2024, 7, 3).unwrap();
+        let expanded = [
+            (
+                "add Fix login bug !high #backend #bug @work ^friday +auth",
+                "add Fix login bug --priority High --tag backend --tag bug --field context work --due 2024-07-05 --parent auth",
+            ),
+            ("add Call mum ^tomorrow", "add Call mum --due 2024-07-04"),
+            ("add !c Pay rent ^2024-08-01", "add Pay rent --priority Critical --due 2024-08-01"),
+            ("add Ticket \\#notatag and \\@nobody", "add Ticket #notatag and @nobody"),
+            ("add Sort photos --desc from #summer trip", "add Sort photos --desc from trip --tag summer"),
+            ("add Review +12 !low", "add Review --parent 12 --priority Low"),
+            // Only a leading sigil counts
+            ("add Learn C# and a+b", "add Learn C# and a+b"),
+            ("add Plain title --tag home", "add Plain title --tag home"),
+            ("list #work", "list #work"),
+        ];
+        for (line, expected) in expanded {
+            assert_eq!(expand_quick_add(&words(line), today, &[]), Ok(words(expected)), "{}", line);
+        }
+
+        let failed = [
+            ("add Fix !urgent", ParseError::InvalidPriority("urgent".to_string())),
+            ("add Fix ^someday", ParseError::InvalidDate("someday".to_string())),
+            ("add Fix ^2024-02-30", ParseError::InvalidDate("2024-02-30".to_string())),
+            ("add Fix # now", ParseError::InvalidToken("#".to_string())),
+            ("add Fix ^", ParseError::InvalidToken("^".to_string())),
+            ("add Fix !high !low", ParseError::InvalidToken("!low".to_string())),
+            ("add Fix @home @work", ParseError::InvalidToken("@work".to_string())),
+            ("add Fix +a +b", ParseError::InvalidToken("+b".to_string())),
+        ];
+        for (line, error) in failed {
+            assert_eq!(expand_quick_add(&words(line), today, &[]), Err(error), "{}", line);
+        }
+    }
+
+    #[test]
+    fn test_quick_add_makes_the_whole_task_or_none() {
+        let mut cli = Cli::new(Box::new(InMemoryStorage::new()), Config::default());
+        cli.run_from(Cursor::new("add Auth\nadd Fix login !high #backend @work +auth\nadd Broken ^someday #x\n".to_string()));
+        let tasks = cli.task_manager.list_tasks();
+        assert_eq!(tasks.len(), 2);
+        let task = tasks[1];
+        assert_eq!((task.title.as_str(), &task.priority), ("Fix login", &Priority::High));
+        assert_eq!(task.tags, vec![Arc::from("backend")]);
+        assert_eq!(task.fields.get("context").map(String::as_str), Some("work"));
+        assert_eq!(task.parent.as_deref(), Some(tasks[0].uid.as_str()));
+    }
+}
@@ -0,0 +1,120 @@
+use crate::Task;
+use crate::render::{DisplayMode, format_task};
+
+// What `copy` puts on the clipboard
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CopyTarget {
+    Title,
+    // The verbose detail view, as `show` prints it
+    Full,
+    // The first link in the description
+    Url,
+}
+
+// Somewhere to put copied text
+pub trait Clipboard {
+    fn set_text(&mut self, text: &str) -> Result<(), String>;
+}
+
+#[cfg(feature = "clipboard")]
+pub struct SystemClipboard(arboard::Clipboard);
+
+#[cfg(feature = "clipboard")]
+impl Clipboard for SystemClipboard {
+    fn set_text(&mut self, text: &str) -> Result<(), String> {
+        self.0.set_text(text).map_err(|e| e.to_string())
+    }
+}
+
+// None when this build has no clipboard support or the desktop has no clipboard
+// to offer (a headless session, say)
+#[cfg(feature = "clipboard")]
+pub fn system_clipboard() -> Option<Box<dyn Clipboard>> {
+    match arboard::Clipboard::new() {
+        Ok(clipboard) => Some(Box::new(SystemClipboard(clipboard))),
+        Err(e) => {
+            log::warn!("no system clipboard: {}", e);
+            None
+        }
+    }
+}
+
+#[cfg(not(feature = "clipboard"))]
+pub fn system_clipboard() -> Option<Box<dyn Clipboard>> {
+    None
+}
+
+// None only for `Url` when the task has no link
+pub fn copy_text(task: &Task, target: CopyTarget) -> Option<String> {
+    match target {
+        CopyTarget::Title => Some(task.title.clone()),
+        CopyTarget::Full => Some(format_task(task, DisplayMode::Verbose).trim_end().to_string()),
+        CopyTarget::Url => first_link(&task.description),
+    }
+}
+
+// Put the text on the clipboard if there is one; false means the caller should
+// print it for copying by hand
+pub fn deliver(text: &str, clipboard: Option<&mut dyn Clipboard>) -> bool {
+    match clipboard.map(|clipboard| clipboard.set_text(text)) {
+        Some(Ok(())) => true,
+        Some(Err(e)) => {
+            log::warn!("copying to the clipboard failed: {}", e);
+            false
+        }
+        None => false,
+    }
+}
+
+fn first_link(text: &str) -> Option<String> {
+    text.split_whitespace()
+        .map(|word| word.trim_start_matches(['(', '<', '[', '"', '\'']))
+        .find(|word| word.starts_with("https://") || word.starts_with("http://"))
+        .map(|word| word.trim_end_matches(['.', ',', ';', ':', '!', '?', ')', '>', ']', '"', '\'']).to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Priority;
+
+    #[derive(Default)]
+    struct MemoryClipboard {
+        text: Option<String>,
+        fail: bool,
+    }
+
+    impl Clipboard for MemoryClipboard {
+        fn set_text(&mut self, text: &str) -> Result<(), String> {
+            if self.fail {
+                return Err("clipboard is locked".to_string());
+            }
+            self.text = Some(text.to_string());
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_copy_targets() {
+        let description = "Spec at (https://example.com/spec?id=4). Mirror: http://old.example.com".to_string();
+        let task = Task::new(3, "Write the spec".to_string(), description, Priority::High);
+        assert_eq!(copy_text(&task, CopyTarget::Title).as_deref(), Some("Write the spec"));
+        assert_eq!(copy_text(&task, CopyTarget::Url).as_deref(), Some("https://example.com/spec?id=4"));
+        let full = copy_text(&task, CopyTarget::Full).unwrap();
+        assert!(full.contains("Write the spec") && full.contains("Spec at"));
+
+        let plain = Task::new(4, "No links".to_string(), "see the wiki".to_string(), Priority::Low);
+        assert_eq!(copy_text(&plain, CopyTarget::Url), None);
+    }
+
+    #[test]
+    fn test_deliver_falls_back_when_copying_fails() {
+        let mut clipboard = MemoryClipboard::default();
+        assert!(deliver("Write the spec", Some(&mut clipboard)));
+        assert_eq!(clipboard.text.as_deref(), Some("Write the spec"));
+
+        let mut locked = MemoryClipboard { fail: true, ..MemoryClipboard::default() };
+        assert!(!deliver("Write the spec", Some(&mut locked)));
+        assert!(!deliver("Write the spec", None));
+    }
+}
@@ -0,0 +1,2826 @@
+use std::fmt;
+use std::fs;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::Duration;
+
+use chrono::{DateTime, Local, NaiveDate, Utc, Weekday};
+use serde::Serialize;
+
+use crate::audit::AuditEntry;
+use crate::board::{self, Board, DEFAULT_DONE_SHOWN};
+use crate::calendar::{self, Calendar};
+use crate::effort::{DEFAULT_FORECAST_WEEKS, EffortStats};
+use crate::config::Config;
+use crate::csv_export;
+use crate::dates::{self, DueSpec};
+use crate::goal::{Goal, GoalPeriod, GoalProgress};
+use crate::filter::ME;
+use crate::graph;
+use crate::health::{Issue, Repair};
+use crate::diagnostics::SizeReport;
+use crate::digest::{self, DEFAULT_DIGEST_LIMIT, Period};
+use crate::html;
+use crate::ics;
+use crate::import::ImportReport;
+use crate::matrix::{self, Matrix};
+use crate::msg;
+use crate::render::DisplayMode;
+use crate::report::ReportData;
+use crate::recurrence::{self, DEFAULT_PREVIEW_COUNT, MAX_PREVIEW_COUNT, Preview, RECUR_FIELD, Recurrence};
+use crate::review;
+use crate::roundtrip::{self, RoundTripFormat, RoundTripReport};
+use crate::schema::{self, FieldInfo};
+use crate::search;
+use crate::session::Scope;
+use crate::table::{self, COLUMN_NAMES, Column};
+use crate::summary::{self, Summary};
+use crate::sync::SyncReport;
+use crate::tags::{self, TagImport, TagRow};
+use crate::task::valid_field_key;
+use crate::timesheet::{DateRange, GroupKey, ReportPeriod, Timesheet};
+use crate::transfer::Transfer;
+use crate::tree::{self, TreeNode};
+use crate::validation::RequiredFields;
+use crate::{
+    CreatorStats, Energy, Placement, PlannedChange, Priority, SortKey, Task, TaskEdit, TaskError, TaskFilter, TaskManager, TaskStatus,
+};
+
+// What `recur-preview` reads its rule from
+#[derive(Debug, Clone, PartialEq)]
+pub enum RecurTarget {
+    // The task's `recur` field, counted from its due date or today
+    Task(u32),
+    // Counted from today
+    Rule(Recurrence),
+}
+
+// A fully parsed and validated user command
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    Help,
+    Add {
+        title: String,
+        description: String,
+        // None means "use the configured default"
+        priority: Option<Priority>,
+        due: Option<NaiveDate>,
+        tags: Vec<String>,
+        // Id of the task to file this one under
+        parent: Option<u32>,
+        // None means "use the configured [require] settings"
+        required: Option<RequiredFields>,
+        energy: Option<Energy>,
+        // Custom fields as (key, value)
+        fields: Vec<(String, String)>,
+    },
+    // `add --stdin`: a task per line of `text`, read by the CLI, each line
+    // `title` or `title | description | priority`; the flags apply to every task
+    AddLines {
+        text: String,
+        priority: Option<Priority>,
+        due: Option<NaiveDate>,
+        tags: Vec<String>,
+        parent: Option<u32>,
+    },
+    // `inbox` keeps only tasks waiting to be triaged
+    // `scope` narrows the list to the view or filter active at the prompt
+    // `columns` shows a table of just those instead of `mode`
+    List { mode: Option<DisplayMode>, inbox: bool, sort: Option<SortKey>, scope: Option<Scope>, columns: Option<Vec<Column>> },
+    Show { id: u32, mode: Option<DisplayMode> },
+    // `note` goes into the description with the change, for moves that need one
+    Update { id: u32, status: TaskStatus, note: Option<String> },
+    Tag { id: u32, tag: String },
+    // Append a line to the task's description
+    Note { id: u32, text: String },
+    // Set a custom field; None removes it
+    Field { id: u32, key: String, value: Option<String> },
+    // Set how much energy the task takes; None clears it
+    Energy { id: u32, energy: Option<Energy> },
+    // Open tasks doable at this energy level or below, most urgent first
+    CanDo { energy: Energy },
+    // Open tasks due within `days` or overdue, most pressed for time first
+    DueSoon { days: u32 },
+    // The one task to do next, as the startup summary picks it, optionally no
+    // harder than `energy`
+    Next { energy: Option<Energy> },
+    // Capture a task to triage later; None takes the configured default priority
+    Quick { title: String, priority: Option<Priority> },
+    // Add a reminder this long before the due date; None clears them all
+    Remind { id: u32, offset: Option<Duration> },
+    Wait { id: u32, who: String, follow_up: Option<NaiveDate> },
+    Unwait { id: u32 },
+    Waiting,
+    Pin { id: u32, pinned: bool },
+    // Link or unlink two tasks as related, on both of them
+    Relate { id: u32, other: u32, related: bool },
+    // Give each task a new priority, leaving the rest of it alone
+    SetPriority { ids: Vec<u32>, priority: Priority },
+    // Subtask and relation links as a Graphviz digraph
+    Graph,
+    // Pinned tasks only
+    Pinned,
+    // Tasks assigned to or created by the current user
+    Mine,
+    // Put `id` just before or after `other` in the manual order
+    Move { id: u32, place: Placement, other: u32 },
+    // Number the tasks of the project `id` is the top of as PREFIX-1, PREFIX-2, ...
+    Prefix { id: u32, prefix: String },
+    // None means "use the configured holidays"
+    Due { id: u32, when: DueSpec, holidays: Option<Vec<NaiveDate>> },
+    // The next `count` dates of a rule, or of the rule in a task's `recur` field;
+    // None means "use the configured business-day mode and holidays"
+    RecurPreview { target: RecurTarget, count: usize, business_days: Option<bool>, holidays: Option<Vec<NaiveDate>> },
+    Delete { id: u32 },
+    // Best matches first, unless `sort` asks for a fixed order; `show_score` prints
+    // each task's relevance
+    Filter { keyword: String, sort: Option<SortKey>, show_score: bool },
+    // Run a saved view's query, narrowed by any extra clauses
+    View { name: String, extra: TaskFilter },
+    // `force` replaces a view already saved under the name
+    ViewSave { name: String, query: String, force: bool },
+    ViewRename { from: String, to: String, force: bool },
+    ViewDelete { name: String },
+    ViewList,
+    // Make one change to every task the query finds. Unconfirmed, it only lists
+    // them, so the CLI can ask before running it again confirmed.
+    Bulk { filter: TaskFilter, change: BulkChange, confirmed: bool },
+    ByTags { tags: Vec<String> },
+    ByPriority { priority: Priority },
+    ByStatus { status: TaskStatus },
+    Stats,
+    // `stats --by user`: counts per creator
+    StatsByUser,
+    // `stats --weighted`: work weighed by estimate and a forecast from the last
+    // `weeks` weeks, forecast_weeks when None
+    StatsWeighted { weeks: Option<u32> },
+    // Groups of open tasks with titles alike enough to be the same task
+    Dedupe,
+    // Open and total task counts, for status bars and scripts
+    Count,
+    ClearCompleted,
+    // Take back the latest recorded command
+    Undo,
+    // Audit history, newest first; pages start at 1
+    Log { task_id: Option<u32>, page: usize },
+    Export { format: ExportFormat, path: PathBuf },
+    // Write the tag registry on its own, TOML or JSON by extension
+    ExportTags { path: PathBuf },
+    // Merge a tag registry file into ours; `replace` drops entries it lacks
+    ImportTags { path: PathBuf, replace: bool },
+    // Register what a tag means, replacing any earlier entry
+    TagDescribe { tag: String, description: String, color: Option<String> },
+    // Every tag with its task count; `long` adds registry descriptions, `unused`
+    // keeps registered tags no task carries
+    Tags { long: bool, unused: bool },
+    // Complete `target` tasks every period, replacing any goal before
+    GoalSet { target: u32, period: GoalPeriod },
+    GoalShow,
+    GoalClear,
+    // The plan for the coming day or week, written as an email body
+    Digest {
+        period: Period,
+        html: bool,
+        // Prefix mail headers so the output can go straight to sendmail
+        headers: bool,
+        // None means "use the configured limit"
+        limit: Option<usize>,
+        from: Option<String>,
+        to: Option<String>,
+    },
+    DebugIndex,
+    DebugInfo,
+    // The keys a stored task has; `json_schema` prints a JSON Schema of the
+    // whole data file instead
+    Schema { json_schema: bool },
+    // Write every task out in a format and read it back, reporting what changed
+    VerifyRoundTrip(RoundTripFormat),
+    // Look for broken invariants in the list; `fix` makes the safe repairs
+    Check { fix: bool },
+    // Side-by-side status columns, with this many completed cards; open columns
+    // follow `sort` when given
+    Board { done: usize, sort: Option<SortKey> },
+    // Open tasks in urgent/important quadrants
+    Matrix,
+    // Calendar grid of the open tasks due in the month of the date, this month
+    // when None; weeks start on `week_start`, Monday when None
+    Month { month: Option<NaiveDate>, week_start: Option<Weekday> },
+    // Open, overdue, due today, in progress, inbox and the task to do next
+    Summary,
+    // review --list: open tasks untouched for `days` days; plain `review` walks
+    // through them at the prompt instead
+    Review { days: u32 },
+    // Tracked time per tag or project; None means "use the configured week start"
+    Timesheet { group: GroupKey, period: ReportPeriod, csv: bool, week_start: Option<Weekday> },
+    // Subtask outline under one task, or under every top-level task
+    Tree { root: Option<u32> },
+}
+
+// What a bulk command does to each task it finds
+#[derive(Debug, Clone, PartialEq)]
+pub enum BulkChange {
+    Tag(String),
+    Priority(Priority),
+}
+
+impl BulkChange {
+    // Make the change through the usual mutation, so events fire as for one task;
+    // false when the task already had it
+    fn apply(&self, mgr: &mut TaskManager, id: u32) -> Result<bool, TaskError> {
+        let task = mgr.get_task(id)?;
+        match self {
+            BulkChange::Tag(tag) => {
+                let wanted = tag.trim().to_lowercase();
+                if task.tags.iter().any(|own| own.trim().to_lowercase() == wanted) {
+                    return Ok(false);
+                }
+                mgr.add_tag_to_task(id, tag.clone())?;
+            }
+            BulkChange::Priority(priority) => return mgr.update_priority(id, priority.clone()),
+        }
+        Ok(true)
+    }
+}
+
+// File formats the task list can be written out as
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ExportFormat {
+    // iCalendar VTODOs for tasks with a due date
+    Ics,
+    // A standalone HTML report of the whole list
+    Html,
+    // Every task, with a column per custom field key
+    Csv,
+    // Every task as the JSON API shows it
+    Json,
+}
+
+// Why a line of input could not be turned into a Command. Serialized like
+// TaskError, as {"error": "<variant>", ...}, with the word that could not be
+// read as `input` (`command` for an unknown command)
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "error")]
+pub enum ParseError {
+    Empty,
+    #[serde(serialize_with = "command_word")]
+    UnknownCommand(String),
+    // The usage text is the message
+    #[serde(serialize_with = "no_detail")]
+    Usage(&'static str),
+    #[serde(serialize_with = "input_word")]
+    InvalidId(String),
+    #[serde(serialize_with = "input_word")]
+    InvalidPriority(String),
+    #[serde(serialize_with = "input_word")]
+    InvalidStatus(String),
+    #[serde(serialize_with = "input_word")]
+    InvalidDate(String),
+    #[serde(serialize_with = "input_word")]
+    InvalidEnergy(String),
+    // A quick-add word with nothing after its sigil, or a sigil given twice
+    #[serde(serialize_with = "input_word")]
+    InvalidToken(String),
+    // A name in `--columns` that is not a table column
+    #[serde(serialize_with = "input_word")]
+    InvalidColumn(String),
+}
+
+fn input_word<S: serde::Serializer>(input: &str, serializer: S) -> Result<S::Ok, S::Error> {
+    #[derive(Serialize)]
+    struct Input<'a> {
+        input: &'a str,
+    }
+    Input { input }.serialize(serializer)
+}
+
+fn command_word<S: serde::Serializer>(command: &str, serializer: S) -> Result<S::Ok, S::Error> {
+    #[derive(Serialize)]
+    struct Unknown<'a> {
+        command: &'a str,
+    }
+    Unknown { command }.serialize(serializer)
+}
+
+fn no_detail<S: serde::Serializer>(_: &str, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_unit()
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseError::Empty => write!(f, "{}", msg!("parse_no_command")),
+            ParseError::UnknownCommand(_) => write!(f, "{}", msg!("parse_unknown_command")),
+            ParseError::Usage(key) => write!(f, "{}", msg!(key)),
+            ParseError::InvalidId(_) => write!(f, "{}", msg!("parse_invalid_id")),
+            ParseError::InvalidPriority(_) => write!(f, "{}", msg!("parse_invalid_priority")),
+            ParseError::InvalidStatus(_) => write!(f, "{}", msg!("parse_invalid_status")),
+            ParseError::InvalidDate(_) => write!(f, "{}", msg!("parse_invalid_date")),
+            ParseError::InvalidEnergy(_) => write!(f, "{}", msg!("parse_invalid_energy")),
+            ParseError::InvalidToken(_) => write!(f, "{}", msg!("parse_invalid_token")),
+            ParseError::InvalidColumn(_) => write!(f, "{}", msg!("parse_invalid_column", columns = COLUMN_NAMES.join(", "))),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+impl ParseError {
+    // The word that could not be read, for the errors about one
+    pub fn input(&self) -> Option<&str> {
+        match self {
+            ParseError::UnknownCommand(input)
+            | ParseError::InvalidId(input)
+            | ParseError::InvalidPriority(input)
+            | ParseError::InvalidStatus(input)
+            | ParseError::InvalidDate(input)
+            | ParseError::InvalidEnergy(input)
+            | ParseError::InvalidToken(input)
+            | ParseError::InvalidColumn(input) => Some(input),
+            ParseError::Empty | ParseError::Usage(_) => None,
+        }
+    }
+
+    // The valid word closest to a mistyped one: "hgih" gives "high"
+    pub fn suggestion(&self) -> Option<&'static str> {
+        match self {
+            ParseError::UnknownCommand(name) => search::did_you_mean(name, COMMAND_NAMES),
+            ParseError::InvalidPriority(input) => search::did_you_mean(input, &["low", "medium", "high", "critical"]),
+            ParseError::InvalidStatus(input) => search::did_you_mean(input, &["pending", "progress", "completed"]),
+            ParseError::InvalidEnergy(input) => {
+                search::did_you_mean(input, &["low", "medium", "high", "easy", "normal", "hard"])
+            }
+            ParseError::InvalidColumn(input) => search::did_you_mean(input, &COLUMN_NAMES),
+            _ => None,
+        }
+    }
+}
+
+// Message catalog keys for each command's usage text
+const ADD_USAGE: &str = "usage_add";
+const LIST_USAGE: &str = "usage_list";
+const SHOW_USAGE: &str = "usage_show";
+const UPDATE_USAGE: &str = "usage_update";
+const TAG_USAGE: &str = "usage_tag";
+const NOTE_USAGE: &str = "usage_note";
+const RECUR_PREVIEW_USAGE: &str = "usage_recur_preview";
+const DUE_USAGE: &str = "usage_due";
+const REMIND_USAGE: &str = "usage_remind";
+const QUICK_USAGE: &str = "usage_quick";
+const FIELD_USAGE: &str = "usage_field";
+const WAIT_USAGE: &str = "usage_wait";
+const UNWAIT_USAGE: &str = "usage_unwait";
+const PIN_USAGE: &str = "usage_pin";
+const UNPIN_USAGE: &str = "usage_unpin";
+const MOVE_USAGE: &str = "usage_move";
+const PREFIX_USAGE: &str = "usage_prefix";
+const RELATE_USAGE: &str = "usage_relate";
+const UNRELATE_USAGE: &str = "usage_unrelate";
+const DELETE_USAGE: &str = "usage_delete";
+const FILTER_USAGE: &str = "usage_filter";
+const TAGGED_USAGE: &str = "usage_tagged";
+const DEBUG_USAGE: &str = "usage_debug";
+const BOARD_USAGE: &str = "usage_board";
+const MONTH_USAGE: &str = "usage_month";
+const TREE_USAGE: &str = "usage_tree";
+const TIMESHEET_USAGE: &str = "usage_timesheet";
+const PRIORITY_USAGE: &str = "usage_priority";
+const STATUS_USAGE: &str = "usage_status";
+const LOG_USAGE: &str = "usage_log";
+const EXPORT_USAGE: &str = "usage_export";
+const DIGEST_USAGE: &str = "usage_digest";
+const VIEW_USAGE: &str = "usage_view";
+const TAG_ALL_USAGE: &str = "usage_tag_all";
+const SET_PRIORITY_USAGE: &str = "usage_set_priority";
+const PRIORITY_SET_USAGE: &str = "usage_priority_set";
+const REVIEW_USAGE: &str = "usage_review";
+const ENERGY_USAGE: &str = "usage_energy";
+const CAN_DO_USAGE: &str = "usage_can_do";
+const NEXT_USAGE: &str = "usage_next";
+const DUE_SOON_USAGE: &str = "usage_due_soon";
+const TAG_DESCRIBE_USAGE: &str = "usage_tag_describe";
+const TAGS_USAGE: &str = "usage_tags";
+const IMPORT_USAGE: &str = "usage_import";
+const SCHEMA_USAGE: &str = "usage_schema";
+const GOAL_USAGE: &str = "usage_goal";
+const VERIFY_ROUNDTRIP_USAGE: &str = "usage_verify_roundtrip";
+const CHECK_USAGE: &str = "usage_check";
+const STATS_USAGE: &str = "usage_stats";
+
+// Every command word, whether Command::parse or the CLI answers it; a saved view
+// cannot take one of these names
+pub const COMMAND_NAMES: &[&str] = &[
+    "help", "add", "list", "clear-view", "show", "update", "tag", "note", "field", "quick", "remind", "wait", "unwait", "waiting",
+    "pin", "unpin", "pinned", "mine", "relate", "unrelate", "graph", "move", "prefix", "due", "recur-preview", "delete", "filter", "view", "tag-all", "set-priority", "priority-set", "tagged",
+    "priority", "status", "tag-describe", "tags", "energy", "can-do", "due-soon", "next", "goal",
+    "board", "tree", "matrix", "month", "summary", "review", "timesheet", "stats", "dedupe", "count", "clear-completed", "undo", "log", "export", "digest", "debug", "schema",
+    "verify-roundtrip", "check",
+    "set", "config", "rollback", "source", "serve", "webhook", "import", "sync", "copy", "pomodoro", "triage", "tui", "watch", "convert-store",
+    "save", "notify", "focus", "quit", "exit",
+];
+
+// How far ahead `due-soon` looks without --days
+pub const DEFAULT_DUE_SOON_DAYS: u32 = 7;
+
+// Audit entries shown per page of `log`
+pub const LOG_PAGE_SIZE: usize = 20;
+
+impl Command {
+    // Commands touching many tasks at once; the CLI checkpoints before running them
+    pub fn is_bulk(&self) -> bool {
+        matches!(self, Command::ClearCompleted | Command::Bulk { confirmed: true, .. } | Command::Check { fix: true })
+    }
+
+    // Commands that only show tasks, which `watch` can run again and again
+    pub fn only_shows(&self) -> bool {
+        matches!(
+            self,
+            Command::List { .. }
+                | Command::Show { .. }
+                | Command::Waiting
+                | Command::Pinned
+                | Command::Mine
+                | Command::Graph
+                | Command::Filter { .. }
+                | Command::View { .. }
+                | Command::ViewList
+                | Command::ByTags { .. }
+                | Command::Tags { .. }
+                | Command::ByPriority { .. }
+                | Command::ByStatus { .. }
+                | Command::Stats
+                | Command::StatsByUser
+                | Command::StatsWeighted { .. }
+                | Command::Dedupe
+                | Command::Count
+                | Command::Log { .. }
+                | Command::Board { .. }
+                | Command::Matrix
+                | Command::Month { .. }
+                | Command::RecurPreview { .. }
+                | Command::Summary
+                | Command::GoalShow
+                | Command::CanDo { .. }
+                | Command::DueSoon { .. }
+                | Command::Next { .. }
+                | Command::Review { .. }
+                | Command::Timesheet { .. }
+                | Command::Tree { .. }
+                | Command::Check { fix: false }
+        )
+    }
+
+    // Fill in anything the user left out from the configuration
+    pub fn with_defaults(self, config: &Config) -> Command {
+        match self {
+            Command::Add { title, description, priority, due, tags, parent, required: None, energy, fields } => Command::Add {
+                title,
+                description,
+                priority: priority.or_else(|| Some(config.default_priority.clone())),
+                due,
+                tags,
+                parent,
+                required: Some(config.required),
+                energy,
+                fields,
+            },
+            Command::Digest { period, html, headers, limit, from, to } => Command::Digest {
+                period,
+                html,
+                headers,
+                limit: limit.or(Some(config.digest_limit)),
+                from: from.or_else(|| config.digest_from.clone()),
+                to: to.or_else(|| config.digest_to.clone()),
+            },
+            Command::AddLines { text, priority: None, due, tags, parent } => {
+                Command::AddLines { text, priority: Some(config.default_priority.clone()), due, tags, parent }
+            }
+            Command::Quick { title, priority: None } => {
+                Command::Quick { title, priority: Some(config.default_priority.clone()) }
+            }
+            // The configured table stands in for the compact list, not for a --format asked for
+            Command::List { mode: None, inbox, sort, scope, columns: None } if !config.list_columns.is_empty() => {
+                Command::List { mode: None, inbox, sort, scope, columns: Some(config.list_columns.clone()) }
+            }
+            Command::Due { id, when, holidays: None } => Command::Due { id, when, holidays: Some(config.holidays.clone()) },
+            Command::RecurPreview { target, count, business_days, holidays } => Command::RecurPreview {
+                target,
+                count,
+                business_days: business_days.or(Some(config.business_days)),
+                holidays: holidays.or_else(|| Some(config.holidays.clone())),
+            },
+            Command::Timesheet { group, period, csv, week_start: None } => Command::Timesheet {
+                group,
+                period,
+                csv,
+                week_start: config.first_day_of_week.parse().ok(),
+            },
+            Command::StatsWeighted { weeks: None } => Command::StatsWeighted { weeks: Some(config.forecast_weeks) },
+            Command::Month { month, week_start: None } => {
+                Command::Month { month, week_start: config.first_day_of_week.parse().ok() }
+            }
+            other => other,
+        }
+    }
+
+    pub fn parse(tokens: &[String]) -> Result<Command, ParseError> {
+        let (name, args) = tokens.split_first().ok_or(ParseError::Empty)?;
+
+        match name.as_str() {
+            "help" => Ok(Command::Help),
+            "add" => parse_add(args),
+            "list" => {
+                let (rest, mode) = take_display_mode(args, LIST_USAGE)?;
+                let (mut inbox, mut sort, mut columns) = (false, None, None);
+                let mut rest = rest.iter();
+                while let Some(arg) = rest.next() {
+                    match arg.as_str() {
+                        "--inbox" => inbox = true,
+                        "--columns" => {
+                            let names = rest.next().ok_or(ParseError::Usage(LIST_USAGE))?;
+                            columns = Some(table::parse_columns(names).map_err(ParseError::InvalidColumn)?);
+                        }
+                        "--sort" => {
+                            let key = rest.next().ok_or(ParseError::Usage(LIST_USAGE))?;
+                            sort = Some(SortKey::from_str(key).map_err(|_| ParseError::Usage(LIST_USAGE))?);
+                        }
+                        _ => return Err(ParseError::Usage(LIST_USAGE)),
+                    }
+                }
+                Ok(Command::List { mode, inbox, sort, scope: None, columns })
+            }
+            "show" => {
+                let (rest, mode) = take_display_mode(args, SHOW_USAGE)?;
+                Ok(Command::Show { id: parse_id(&rest, SHOW_USAGE)?, mode })
+            }
+            "update" => {
+                if args.len() < 2 {
+                    return Err(ParseError::Usage(UPDATE_USAGE));
+                }
+                let id = parse_id(args, UPDATE_USAGE)?;
+                let note = match &args[2..] {
+                    [] => None,
+                    [flag, text @ ..] if flag == "--note" && !text.is_empty() => Some(text.join(" ")),
+                    _ => return Err(ParseError::Usage(UPDATE_USAGE)),
+                };
+                Ok(Command::Update { id, status: parse_status(&args[1])?, note })
+            }
+            "tag" => {
+                if args.len() < 2 {
+                    return Err(ParseError::Usage(TAG_USAGE));
+                }
+                let id = parse_id(args, TAG_USAGE)?;
+                Ok(Command::Tag { id, tag: args[1..].join(" ") })
+            }
+            "note" => {
+                if args.len() < 2 {
+                    return Err(ParseError::Usage(NOTE_USAGE));
+                }
+                let id = parse_id(args, NOTE_USAGE)?;
+                Ok(Command::Note { id, text: args[1..].join(" ") })
+            }
+            "energy" => match args {
+                [_, level] if level == "none" => Ok(Command::Energy { id: parse_id(args, ENERGY_USAGE)?, energy: None }),
+                [_, level] => Ok(Command::Energy { id: parse_id(args, ENERGY_USAGE)?, energy: Some(parse_energy(level)?) }),
+                _ => Err(ParseError::Usage(ENERGY_USAGE)),
+            },
+            "field" => match args {
+                [_, key, flag] if flag == "--unset" => {
+                    Ok(Command::Field { id: parse_id(args, FIELD_USAGE)?, key: key.clone(), value: None })
+                }
+                [_, key, value @ ..] if !value.is_empty() => {
+                    Ok(Command::Field { id: parse_id(args, FIELD_USAGE)?, key: key.clone(), value: Some(value.join(" ")) })
+                }
+                _ => Err(ParseError::Usage(FIELD_USAGE)),
+            },
+            "quick" => {
+                if args.is_empty() {
+                    return Err(ParseError::Usage(QUICK_USAGE));
+                }
+                Ok(Command::Quick { title: args.join(" "), priority: None })
+            }
+            "remind" => {
+                let [_, offset] = args else {
+                    return Err(ParseError::Usage(REMIND_USAGE));
+                };
+                let id = parse_id(args, REMIND_USAGE)?;
+                let offset = match offset.as_str() {
+                    "none" => None,
+                    text => Some(dates::parse_offset(text).ok_or(ParseError::Usage(REMIND_USAGE))?),
+                };
+                Ok(Command::Remind { id, offset })
+            }
+            "wait" => {
+                let id = parse_id(args, WAIT_USAGE)?;
+                let (who, follow_up) = match args[1..].iter().position(|arg| arg == "until") {
+                    Some(at) => match &args[at + 2..] {
+                        [date] => (&args[1..at + 1], Some(parse_date(date)?)),
+                        _ => return Err(ParseError::Usage(WAIT_USAGE)),
+                    },
+                    None => (&args[1..], None),
+                };
+                if who.is_empty() {
+                    return Err(ParseError::Usage(WAIT_USAGE));
+                }
+                Ok(Command::Wait { id, who: who.join(" "), follow_up })
+            }
+            "unwait" => Ok(Command::Unwait { id: parse_id(args, UNWAIT_USAGE)? }),
+            "pin" => Ok(Command::Pin { id: parse_id(args, PIN_USAGE)?, pinned: true }),
+            "unpin" => Ok(Command::Pin { id: parse_id(args, UNPIN_USAGE)?, pinned: false }),
+            "move" => match args {
+                [_, place, other] => {
+                    let place = match place.as_str() {
+                        "before" => Placement::Before,
+                        "after" => Placement::After,
+                        _ => return Err(ParseError::Usage(MOVE_USAGE)),
+                    };
+                    let other = other.parse::<u32>().map_err(|_| ParseError::InvalidId(other.clone()))?;
+                    Ok(Command::Move { id: parse_id(args, MOVE_USAGE)?, place, other })
+                }
+                _ => Err(ParseError::Usage(MOVE_USAGE)),
+            },
+            "pinned" => Ok(Command::Pinned),
+            "relate" => parse_relate(args, true),
+            "unrelate" => parse_relate(args, false),
+            "graph" => Ok(Command::Graph),
+            "waiting" => Ok(Command::Waiting),
+            "mine" => Ok(Command::Mine),
+            "prefix" => match args {
+                [_, prefix] => Ok(Command::Prefix { id: parse_id(args, PREFIX_USAGE)?, prefix: prefix.clone() }),
+                _ => Err(ParseError::Usage(PREFIX_USAGE)),
+            },
+            "due" => {
+                if args.len() < 2 {
+                    return Err(ParseError::Usage(DUE_USAGE));
+                }
+                let id = parse_id(args, DUE_USAGE)?;
+                let text = args[1..].join(" ");
+                let when = DueSpec::parse(&text).ok_or(ParseError::InvalidDate(text))?;
+                Ok(Command::Due { id, when, holidays: None })
+            }
+            "recur-preview" => parse_recur_preview(args),
+            "delete" => Ok(Command::Delete { id: parse_id(args, DELETE_USAGE)? }),
+            "filter" => {
+                let (mut sort, mut show_score, mut words) = (None, false, Vec::new());
+                let mut rest = args.iter();
+                while let Some(arg) = rest.next() {
+                    match arg.as_str() {
+                        "--sort" => {
+                            let key = rest.next().ok_or(ParseError::Usage(FILTER_USAGE))?;
+                            sort = Some(SortKey::from_str(key).map_err(|_| ParseError::Usage(FILTER_USAGE))?);
+                        }
+                        "--debug-score" => show_score = true,
+                        _ => words.push(arg.as_str()),
+                    }
+                }
+                // A fixed order has no scores to show
+                if words.is_empty() || (sort.is_some() && show_score) {
+                    return Err(ParseError::Usage(FILTER_USAGE));
+                }
+                Ok(Command::Filter { keyword: words.join(" "), sort, show_score })
+            }
+            "view" => parse_view(args),
+            "tag-all" => parse_bulk(args, TAG_ALL_USAGE, |tag| Ok(BulkChange::Tag(tag.to_string()))),
+            "set-priority" => parse_bulk(args, SET_PRIORITY_USAGE, |level| Ok(BulkChange::Priority(parse_priority(level)?))),
+            "tagged" => {
+                if args.is_empty() {
+                    return Err(ParseError::Usage(TAGGED_USAGE));
+                }
+                Ok(Command::ByTags { tags: args.to_vec() })
+            }
+            "tag-describe" => parse_tag_describe(args),
+            "tags" => {
+                let (mut long, mut unused) = (false, false);
+                for arg in args {
+                    match arg.as_str() {
+                        "--long" => long = true,
+                        "--unused" => unused = true,
+                        _ => return Err(ParseError::Usage(TAGS_USAGE)),
+                    }
+                }
+                Ok(Command::Tags { long, unused })
+            }
+            // `priority 3 high` sets it; `priority high` lists by it
+            "priority" if args.len() == 2 && parse_id_spec(&args[0]).is_ok() => parse_priority_set(args),
+            "priority" => {
+                let level = args.first().ok_or(ParseError::Usage(PRIORITY_USAGE))?;
+                Ok(Command::ByPriority { priority: parse_priority(level)? })
+            }
+            "priority-set" => parse_priority_set(args),
+            "status" => {
+                let status = args.first().ok_or(ParseError::Usage(STATUS_USAGE))?;
+                Ok(Command::ByStatus { status: parse_status(status)? })
+            }
+            "board" => {
+                let (mut done, mut sort) = (DEFAULT_DONE_SHOWN, None);
+                let mut rest = args.iter();
+                while let Some(arg) = rest.next() {
+                    let value = rest.next().ok_or(ParseError::Usage(BOARD_USAGE))?;
+                    match arg.as_str() {
+                        "--done" => done = value.parse::<usize>().map_err(|_| ParseError::Usage(BOARD_USAGE))?,
+                        "--sort" => sort = Some(SortKey::from_str(value).map_err(|_| ParseError::Usage(BOARD_USAGE))?),
+                        _ => return Err(ParseError::Usage(BOARD_USAGE)),
+                    }
+                }
+                Ok(Command::Board { done, sort })
+            }
+            "tree" => match args {
+                [] => Ok(Command::Tree { root: None }),
+                [_] => Ok(Command::Tree { root: Some(parse_id(args, TREE_USAGE)?) }),
+                _ => Err(ParseError::Usage(TREE_USAGE)),
+            },
+            "matrix" => Ok(Command::Matrix),
+            "month" => match args {
+                [] => Ok(Command::Month { month: None, week_start: None }),
+                [month] => NaiveDate::parse_from_str(&format!("{}-01", month), "%Y-%m-%d")
+                    .map(|month| Command::Month { month: Some(month), week_start: None })
+                    .map_err(|_| ParseError::Usage(MONTH_USAGE)),
+                _ => Err(ParseError::Usage(MONTH_USAGE)),
+            },
+            "summary" => Ok(Command::Summary),
+            "goal" => parse_goal(args),
+            "next" => match args {
+                [] => Ok(Command::Next { energy: None }),
+                [flag, level] if flag == "--energy" => Ok(Command::Next { energy: Some(parse_energy(level)?) }),
+                _ => Err(ParseError::Usage(NEXT_USAGE)),
+            },
+            "can-do" => match args {
+                [level] => Ok(Command::CanDo { energy: parse_energy(level)? }),
+                _ => Err(ParseError::Usage(CAN_DO_USAGE)),
+            },
+            "due-soon" => match args {
+                [] => Ok(Command::DueSoon { days: DEFAULT_DUE_SOON_DAYS }),
+                [flag, days] if flag == "--days" => match days.parse::<u32>() {
+                    Ok(days) => Ok(Command::DueSoon { days }),
+                    Err(_) => Err(ParseError::Usage(DUE_SOON_USAGE)),
+                },
+                _ => Err(ParseError::Usage(DUE_SOON_USAGE)),
+            },
+            "review" => Ok(Command::Review { days: parse_review(args)?.0 }),
+            "timesheet" => parse_timesheet(args),
+            "stats" => match args {
+                [] => Ok(Command::Stats),
+                [flag, by] if flag == "--by" && by == "user" => Ok(Command::StatsByUser),
+                [flag] if flag == "--weighted" => Ok(Command::StatsWeighted { weeks: None }),
+                [flag, option, weeks] if flag == "--weighted" && option == "--weeks" => match weeks.parse::<u32>() {
+                    Ok(weeks) if weeks > 0 => Ok(Command::StatsWeighted { weeks: Some(weeks) }),
+                    _ => Err(ParseError::Usage(STATS_USAGE)),
+                },
+                _ => Err(ParseError::Usage(STATS_USAGE)),
+            },
+            "dedupe" => Ok(Command::Dedupe),
+            "count" => Ok(Command::Count),
+            "clear-completed" => Ok(Command::ClearCompleted),
+            "undo" => Ok(Command::Undo),
+            "log" => parse_log(args),
+            "export" => match args {
+                [format, path] if format == "ics" => {
+                    Ok(Command::Export { format: ExportFormat::Ics, path: PathBuf::from(path) })
+                }
+                [format, path] if format == "html" => {
+                    Ok(Command::Export { format: ExportFormat::Html, path: PathBuf::from(path) })
+                }
+                [format, path] if format == "csv" => {
+                    Ok(Command::Export { format: ExportFormat::Csv, path: PathBuf::from(path) })
+                }
+                [format, path] if format == "json" => {
+                    Ok(Command::Export { format: ExportFormat::Json, path: PathBuf::from(path) })
+                }
+                [tags, path] if tags == "tags" => Ok(Command::ExportTags { path: PathBuf::from(path) }),
+                _ => Err(ParseError::Usage(EXPORT_USAGE)),
+            },
+            // The CLI answers the other imports itself
+            "import" => match args {
+                [tags, path] if tags == "tags" => Ok(Command::ImportTags { path: PathBuf::from(path), replace: false }),
+                [tags, path, flag] if tags == "tags" && flag == "--replace" => {
+                    Ok(Command::ImportTags { path: PathBuf::from(path), replace: true })
+                }
+                _ => Err(ParseError::Usage(IMPORT_USAGE)),
+            },
+            "digest" => parse_digest(args),
+            "debug" => match args.first().map(String::as_str) {
+                Some("index") => Ok(Command::DebugIndex),
+                Some("info") => Ok(Command::DebugInfo),
+                _ => Err(ParseError::Usage(DEBUG_USAGE)),
+            },
+            "schema" => match args {
+                [] => Ok(Command::Schema { json_schema: false }),
+                [flag] if flag == "--json-schema" => Ok(Command::Schema { json_schema: true }),
+                _ => Err(ParseError::Usage(SCHEMA_USAGE)),
+            },
+            "verify-roundtrip" => match args {
+                [format] => format.parse().map(Command::VerifyRoundTrip).map_err(|_| ParseError::Usage(VERIFY_ROUNDTRIP_USAGE)),
+                _ => Err(ParseError::Usage(VERIFY_ROUNDTRIP_USAGE)),
+            },
+            "check" => match args {
+                [] => Ok(Command::Check { fix: false }),
+                [flag] if flag == "--fix" => Ok(Command::Check { fix: true }),
+                _ => Err(ParseError::Usage(CHECK_USAGE)),
+            },
+            other => Err(ParseError::UnknownCommand(other.to_string())),
+        }
+    }
+}
+
+// Pull an optional `--format <mode>` out of the arguments
+fn take_display_mode(args: &[String], usage: &'static str) -> Result<(Vec<String>, Option<DisplayMode>), ParseError> {
+    let mut rest = Vec::new();
+    let mut mode = None;
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        if arg == "--format" {
+            let value = iter.next().ok_or(ParseError::Usage(usage))?;
+            mode = Some(DisplayMode::from_str(value).map_err(|_| ParseError::Usage(usage))?);
+        } else {
+            rest.push(arg.clone());
+        }
+    }
+    Ok((rest, mode))
+}
+
+// (id, title) of an open task a title prefix could mean
+pub type TitleMatch = (u32, String);
+
+// Commands whose first argument is a task id
+const ID_COMMANDS: [&str; 19] = [
+    "show", "update", "tag", "note", "field", "remind", "wait", "unwait", "due", "delete", "tree", "log", "focus",
+    "copy", "pomodoro", "prefix", "pin", "unpin", "move",
+];
+
+// Put the task's id where a command expects one (or after `add --parent`) and was
+// given something else: a task key, so `show AUTH-7` runs as `show 12`, or the
+// start of an open task's title, quoted when it is several words (`show "buy m"`).
+// When the title could mean several tasks `choose` is asked to pick one of them;
+// returning None, as it does where nobody can answer, gives the AmbiguousTitle error.
+pub fn resolve_task_ref(
+    tokens: &[String],
+    mgr: &TaskManager,
+    choose: &mut dyn FnMut(&str, &[TitleMatch]) -> Option<u32>,
+) -> Result<Vec<String>, TaskError> {
+    let at = match tokens.first().map(String::as_str) {
+        Some(command) if ID_COMMANDS.contains(&command) => 1,
+        Some("add") => match tokens.iter().position(|token| token == "--parent") {
+            Some(flag) => flag + 1,
+            None => return Ok(tokens.to_vec()),
+        },
+        _ => return Ok(tokens.to_vec()),
+    };
+    let Some(arg) = tokens.get(at) else {
+        return Ok(tokens.to_vec());
+    };
+    if arg.starts_with('-') || arg.parse::<u32>().is_ok() {
+        return Ok(tokens.to_vec());
+    }
+    // Words from an opening quote to the closing one are a single reference
+    let end = if arg.starts_with('"') {
+        tokens[at..].iter().position(|token| token.len() > 1 && token.ends_with('"')).map_or(at, |n| at + n)
+    } else {
+        at
+    };
+    let text = tokens[at..=end].join(" ");
+    let text = text.trim_matches('"');
+    let id = match find_task_ref(mgr, text) {
+        Err(TaskError::AmbiguousTitle { text, matches }) => match choose(&text, &matches) {
+            Some(id) if matches.iter().any(|(candidate, _)| *candidate == id) => id,
+            _ => return Err(TaskError::AmbiguousTitle { text, matches }),
+        },
+        found => found?,
+    };
+    let mut resolved = tokens[..at].to_vec();
+    resolved.push(id.to_string());
+    resolved.extend_from_slice(&tokens[end + 1..]);
+    Ok(resolved)
+}
+
+// A task key, or else the start of the title of exactly one open task, ignoring
+// case; a title that matches in full wins over longer ones it is the start of
+fn find_task_ref(mgr: &TaskManager, text: &str) -> Result<u32, TaskError> {
+    if let Some(task) = mgr.find_by_key(text) {
+        return Ok(task.id);
+    }
+    let prefix = text.to_lowercase();
+    let open: Vec<&Task> = mgr
+        .list_tasks()
+        .into_iter()
+        .filter(|task| task.status != TaskStatus::Completed && task.title.to_lowercase().starts_with(&prefix))
+        .collect();
+    let exact: Vec<&Task> = open.iter().copied().filter(|task| task.title.to_lowercase() == prefix).collect();
+    match (open.as_slice(), exact.as_slice()) {
+        (_, [task]) | ([task], _) => Ok(task.id),
+        ([], _) => Err(TaskError::NoTitleMatch { text: text.to_string() }),
+        _ => Err(TaskError::AmbiguousTitle {
+            text: text.to_string(),
+            matches: open.iter().map(|task| (task.id, task.title.clone())).collect(),
+        }),
+    }
+}
+
+fn parse_id(args: &[String], usage: &'static str) -> Result<u32, ParseError> {
+    let arg = args.first().ok_or(ParseError::Usage(usage))?;
+    arg.parse::<u32>().map_err(|_| ParseError::InvalidId(arg.clone()))
+}
+
+// "3", "3,5,7" or "3-6,9": the ids in the order given, each once
+fn parse_id_spec(arg: &str) -> Result<Vec<u32>, ParseError> {
+    let invalid = || ParseError::InvalidId(arg.to_string());
+    let mut ids = Vec::new();
+    for part in arg.split(',') {
+        let (first, last) = match part.split_once('-') {
+            Some((first, last)) => (first.parse().map_err(|_| invalid())?, last.parse().map_err(|_| invalid())?),
+            None => {
+                let id = part.parse().map_err(|_| invalid())?;
+                (id, id)
+            }
+        };
+        if first > last {
+            return Err(invalid());
+        }
+        for id in first..=last {
+            if !ids.contains(&id) {
+                ids.push(id);
+            }
+        }
+    }
+    Ok(ids)
+}
+
+// priority-set <ids> <level>
+fn parse_priority_set(args: &[String]) -> Result<Command, ParseError> {
+    match args {
+        [ids, level] => Ok(Command::SetPriority { ids: parse_id_spec(ids)?, priority: parse_priority(level)? }),
+        _ => Err(ParseError::Usage(PRIORITY_SET_USAGE)),
+    }
+}
+
+fn parse_priority(arg: &str) -> Result<Priority, ParseError> {
+    Priority::from_str(arg).map_err(|_| ParseError::InvalidPriority(arg.to_string()))
+}
+
+fn parse_status(arg: &str) -> Result<TaskStatus, ParseError> {
+    TaskStatus::from_str(arg).map_err(|_| ParseError::InvalidStatus(arg.to_string()))
+}
+
+fn parse_energy(arg: &str) -> Result<Energy, ParseError> {
+    Energy::from_str(arg).map_err(|_| ParseError::InvalidEnergy(arg.to_string()))
+}
+
+fn parse_date(arg: &str) -> Result<NaiveDate, ParseError> {
+    NaiveDate::parse_from_str(arg, "%Y-%m-%d").map_err(|_| ParseError::InvalidDate(arg.to_string()))
+}
+
+// log [<task_id>] [--page <n>]
+fn parse_log(args: &[String]) -> Result<Command, ParseError> {
+    let mut task_id = None;
+    let mut page = 1;
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        if arg == "--page" {
+            let value = iter.next().ok_or(ParseError::Usage(LOG_USAGE))?;
+            page = match value.parse::<usize>() {
+                Ok(n) if n > 0 => n,
+                _ => return Err(ParseError::Usage(LOG_USAGE)),
+            };
+        } else if task_id.is_none() {
+            task_id = Some(parse_id(std::slice::from_ref(arg), LOG_USAGE)?);
+        } else {
+            return Err(ParseError::Usage(LOG_USAGE));
+        }
+    }
+    Ok(Command::Log { task_id, page })
+}
+
+// relate|unrelate <id> <other_id>
+fn parse_relate(args: &[String], related: bool) -> Result<Command, ParseError> {
+    let usage = if related { RELATE_USAGE } else { UNRELATE_USAGE };
+    match args {
+        [_, other] => Ok(Command::Relate {
+            id: parse_id(args, usage)?,
+            other: parse_id(std::slice::from_ref(other), usage)?,
+            related,
+        }),
+        _ => Err(ParseError::Usage(usage)),
+    }
+}
+
+// review [--days n] [--list]: the threshold and whether only to list
+pub fn parse_review(args: &[String]) -> Result<(u32, bool), ParseError> {
+    let mut days = review::DEFAULT_DAYS;
+    let mut list = false;
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--list" => list = true,
+            "--days" => {
+                days = match iter.next().map(|value| value.parse::<u32>()) {
+                    Some(Ok(n)) if n > 0 => n,
+                    _ => return Err(ParseError::Usage(REVIEW_USAGE)),
+                }
+            }
+            _ => return Err(ParseError::Usage(REVIEW_USAGE)),
+        }
+    }
+    Ok((days, list))
+}
+
+// tag-describe <tag> <description...> [--color <color>]
+fn parse_tag_describe(args: &[String]) -> Result<Command, ParseError> {
+    let (tag, rest) = args.split_first().ok_or(ParseError::Usage(TAG_DESCRIBE_USAGE))?;
+    let (mut words, mut color) = (Vec::new(), None);
+    let mut rest = rest.iter();
+    while let Some(arg) = rest.next() {
+        match arg.as_str() {
+            "--color" => color = Some(rest.next().ok_or(ParseError::Usage(TAG_DESCRIBE_USAGE))?.clone()),
+            _ => words.push(arg.as_str()),
+        }
+    }
+    if words.is_empty() {
+        return Err(ParseError::Usage(TAG_DESCRIBE_USAGE));
+    }
+    Ok(Command::TagDescribe { tag: tag.clone(), description: words.join(" "), color })
+}
+
+// recur-preview <id|rule> [count] [--business-days]; a rule of several words
+// may go unquoted, and a number after it is the count
+fn parse_recur_preview(args: &[String]) -> Result<Command, ParseError> {
+    let usage = || ParseError::Usage(RECUR_PREVIEW_USAGE);
+    let mut words: Vec<&str> = args.iter().map(String::as_str).filter(|arg| *arg != "--business-days").collect();
+    let business_days = (words.len() < args.len()).then_some(true);
+    let mut count = DEFAULT_PREVIEW_COUNT;
+    if let [_, .., last] = words.as_slice()
+        && let Ok(n) = last.parse::<usize>()
+    {
+        count = Some(n).filter(|&n| n > 0 && n <= MAX_PREVIEW_COUNT).ok_or_else(usage)?;
+        words.pop();
+    }
+    let target = match words.as_slice() {
+        [] => return Err(usage()),
+        [id] if id.parse::<u32>().is_ok() => RecurTarget::Task(parse_id(args, RECUR_PREVIEW_USAGE)?),
+        rule => RecurTarget::Rule(Recurrence::parse(&rule.join(" ")).ok_or_else(usage)?),
+    };
+    Ok(Command::RecurPreview { target, count, business_days, holidays: None })
+}
+
+// goal [show] | goal set <n> [--period week|day|month] | goal clear
+fn parse_goal(args: &[String]) -> Result<Command, ParseError> {
+    match args {
+        [] => Ok(Command::GoalShow),
+        [show] if show == "show" => Ok(Command::GoalShow),
+        [clear] if clear == "clear" => Ok(Command::GoalClear),
+        [set, target, rest @ ..] if set == "set" => {
+            let target = target.parse::<u32>().ok().filter(|n| *n > 0).ok_or(ParseError::Usage(GOAL_USAGE))?;
+            let period = match rest {
+                [] => GoalPeriod::Week,
+                [flag, period] if flag == "--period" => period.parse().map_err(|_| ParseError::Usage(GOAL_USAGE))?,
+                _ => return Err(ParseError::Usage(GOAL_USAGE)),
+            };
+            Ok(Command::GoalSet { target, period })
+        }
+        _ => Err(ParseError::Usage(GOAL_USAGE)),
+    }
+}
+
+// view <name> [clause...] | view save <name> <query...> [--force] | view rename <from> <to> [--force]
+// | view delete <name> | view list
+fn parse_view(args: &[String]) -> Result<Command, ParseError> {
+    let force = args.iter().any(|arg| arg == "--force");
+    let rest: Vec<&String> = args.iter().filter(|arg| *arg != "--force").collect();
+    match rest.as_slice() {
+        [list] if list.as_str() == "list" && !force => Ok(Command::ViewList),
+        [save, name, query @ ..] if save.as_str() == "save" && !query.is_empty() => {
+            let query = query.iter().map(|word| word.as_str()).collect::<Vec<_>>().join(" ");
+            TaskFilter::parse(&query)?;
+            Ok(Command::ViewSave { name: name.to_string(), query, force })
+        }
+        [rename, from, to] if rename.as_str() == "rename" => {
+            Ok(Command::ViewRename { from: from.to_string(), to: to.to_string(), force })
+        }
+        [delete, name] if delete.as_str() == "delete" && !force => Ok(Command::ViewDelete { name: name.to_string() }),
+        [name, extra @ ..] if !force && !["list", "save", "rename", "delete"].contains(&name.as_str()) => {
+            let extra = extra.iter().map(|word| word.as_str()).collect::<Vec<_>>().join(" ");
+            Ok(Command::View { name: name.to_string(), extra: TaskFilter::parse(&extra)? })
+        }
+        _ => Err(ParseError::Usage(VIEW_USAGE)),
+    }
+}
+
+// <command> <query...> <value> [--yes]: the query is a view's, and the last word
+// says what to change
+fn parse_bulk(
+    args: &[String],
+    usage: &'static str,
+    change: impl Fn(&str) -> Result<BulkChange, ParseError>,
+) -> Result<Command, ParseError> {
+    let confirmed = args.iter().any(|arg| arg == "--yes");
+    let rest: Vec<&str> = args.iter().map(String::as_str).filter(|arg| *arg != "--yes").collect();
+    let [query @ .., value] = rest.as_slice() else {
+        return Err(ParseError::Usage(usage));
+    };
+    // An empty query would change every task; `status:pending` and the like say so on purpose
+    if query.is_empty() {
+        return Err(ParseError::Usage(usage));
+    }
+    let filter = TaskFilter::parse(&query.join(" "))?;
+    Ok(Command::Bulk { filter, change: change(value)?, confirmed })
+}
+
+// digest [--period week|day] [--html] [--headers] [--limit <n>]
+fn parse_digest(args: &[String]) -> Result<Command, ParseError> {
+    let mut period = Period::Week;
+    let mut html = false;
+    let mut headers = false;
+    let mut limit = None;
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--period" => {
+                period = match iter.next().map(String::as_str) {
+                    Some("week") => Period::Week,
+                    Some("day") => Period::Day,
+                    _ => return Err(ParseError::Usage(DIGEST_USAGE)),
+                }
+            }
+            "--html" => html = true,
+            "--headers" => headers = true,
+            "--limit" => {
+                limit = match iter.next().map(|value| value.parse::<usize>()) {
+                    Some(Ok(n)) if n > 0 => Some(n),
+                    _ => return Err(ParseError::Usage(DIGEST_USAGE)),
+                }
+            }
+            _ => return Err(ParseError::Usage(DIGEST_USAGE)),
+        }
+    }
+    Ok(Command::Digest { period, html, headers, limit, from: None, to: None })
+}
+
+// timesheet [--by tag|project] [--period week|month|all] [--csv]
+fn parse_timesheet(args: &[String]) -> Result<Command, ParseError> {
+    let (mut group, mut period, mut csv) = (GroupKey::Tag, ReportPeriod::Week, false);
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--by" => {
+                group = match iter.next().map(String::as_str) {
+                    Some("tag") => GroupKey::Tag,
+                    Some("project") => GroupKey::Project,
+                    _ => return Err(ParseError::Usage(TIMESHEET_USAGE)),
+                }
+            }
+            "--period" => {
+                period = match iter.next().map(String::as_str) {
+                    Some("week") => ReportPeriod::Week,
+                    Some("month") => ReportPeriod::Month,
+                    Some("all") => ReportPeriod::All,
+                    _ => return Err(ParseError::Usage(TIMESHEET_USAGE)),
+                }
+            }
+            "--csv" => csv = true,
+            _ => return Err(ParseError::Usage(TIMESHEET_USAGE)),
+        }
+    }
+    Ok(Command::Timesheet { group, period, csv, week_start: None })
+}
+
+// add <title words...> [--desc <words...>] [--priority <level>] [--due <date>] [--tag <tag>]...
+fn parse_add(args: &[String]) -> Result<Command, ParseError> {
+    let mut title = Vec::new();
+    let mut description = Vec::new();
+    let mut priority = None;
+    let mut due = None;
+    let mut tags = Vec::new();
+    let mut parent = None;
+    let mut energy = None;
+    let mut fields = Vec::new();
+    let mut in_description = false;
+    let mut stdin = false;
+
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--desc" => in_description = true,
+            "--stdin" => stdin = true,
+            "--priority" => {
+                let level = iter.next().ok_or(ParseError::Usage(ADD_USAGE))?;
+                priority = Some(parse_priority(level)?);
+                in_description = false;
+            }
+            "--due" => {
+                let date = iter.next().ok_or(ParseError::Usage(ADD_USAGE))?;
+                due = Some(parse_date(date)?);
+                in_description = false;
+            }
+            "--tag" => {
+                let tag = iter.next().ok_or(ParseError::Usage(ADD_USAGE))?;
+                tags.push(tag.clone());
+                in_description = false;
+            }
+            "--parent" => {
+                let id = iter.next().ok_or(ParseError::Usage(ADD_USAGE))?;
+                parent = Some(parse_id(std::slice::from_ref(id), ADD_USAGE)?);
+                in_description = false;
+            }
+            "--energy" => {
+                let level = iter.next().ok_or(ParseError::Usage(ADD_USAGE))?;
+                energy = Some(parse_energy(level)?);
+                in_description = false;
+            }
+            "--field" => {
+                let (key, value) = iter.next().zip(iter.next()).ok_or(ParseError::Usage(ADD_USAGE))?;
+                if !valid_field_key(key) {
+                    return Err(ParseError::Usage(ADD_USAGE));
+                }
+                fields.push((key.clone(), value.clone()));
+                in_description = false;
+            }
+            word if in_description => description.push(word),
+            word => title.push(word),
+        }
+    }
+
+    if stdin {
+        if !title.is_empty() || !description.is_empty() || energy.is_some() || !fields.is_empty() {
+            return Err(ParseError::Usage(ADD_USAGE));
+        }
+        return Ok(Command::AddLines { text: String::new(), priority, due, tags, parent });
+    }
+    if title.is_empty() {
+        return Err(ParseError::Usage(ADD_USAGE));
+    }
+
+    Ok(Command::Add {
+        title: title.join(" "),
+        description: description.join(" "),
+        priority,
+        due,
+        tags,
+        parent,
+        required: None,
+        energy,
+        fields,
+    })
+}
+
+// Which query produced a task list, so renderers can title it
+#[derive(Debug, Clone, PartialEq)]
+pub enum ListView {
+    All,
+    Filter(String),
+    // A saved view, by name
+    View(String),
+    Tags(Vec<String>),
+    Priority(Priority),
+    Status(TaskStatus),
+    Inbox,
+    Pinned,
+    Mine,
+    // `can-do`: doable at this energy or below
+    Energy(Energy),
+    // `due-soon`: due within this many days
+    DueSoon(u32),
+    // `import tasks`: what would come in from another list, by its name
+    Source(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ViewSummary {
+    pub name: String,
+    pub query: String,
+    pub count: usize,
+}
+
+// The result of executing a command, rendered by the CLI layer
+#[derive(Debug, Clone, PartialEq)]
+pub enum CommandOutput {
+    Help,
+    // `similar` lists open tasks whose titles look like the new one's
+    Added { id: u32, similar: Vec<TitleMatch> },
+    // Ids of the tasks `add --stdin` created, and (line number, why) for each line it skipped
+    AddedLines { created: Vec<u32>, skipped: Vec<(usize, String)> },
+    Updated(u32),
+    Tagged(u32),
+    Noted(u32),
+    DueSet { id: u32, date: NaiveDate },
+    // `completed` warns that the task pinned is already done
+    Pinned { id: u32, pinned: bool, completed: bool },
+    // `changed` is false when the tasks already were (or were not) related
+    Related { id: u32, other: u32, related: bool, changed: bool },
+    // (id, old priority) of each task changed, and the tasks that already had it
+    PrioritySet { priority: Priority, changed: Vec<(u32, Priority)>, unchanged: Vec<u32> },
+    // DOT source for Graphviz
+    Graph(String),
+    Deleted(u32),
+    Cleared(Vec<u32>),
+    // `related` are the tasks it relates to from either side
+    Task { task: Box<Task>, mode: Option<DisplayMode>, related: Vec<Task> },
+    Tasks { view: ListView, tasks: Vec<Task>, mode: Option<DisplayMode> },
+    // `list --columns`
+    Table { view: ListView, tasks: Vec<Task>, columns: Vec<Column> },
+    // `filter` hits as (score, task), listed by relevance
+    Matches { keyword: String, hits: Vec<(u32, Task)>, show_score: bool },
+    Stats {
+        total: usize,
+        completed: usize,
+        in_progress: usize,
+        pending: usize,
+        // Untriaged tasks
+        inbox: usize,
+        pinned: usize,
+        goal: Option<GoalProgress>,
+    },
+    Count { open: usize, total: usize },
+    StatsByUser(Vec<CreatorStats>),
+    StatsWeighted(EffortStats),
+    RecurPreview(Preview),
+    // The tasks an unconfirmed bulk command would change
+    BulkPreview { change: BulkChange, tasks: Vec<Task> },
+    // A bulk command's outcome per task: changed, already so, or refused and why
+    BulkApplied { change: BulkChange, changed: Vec<u32>, unchanged: Vec<u32>, failed: Vec<(u32, String)> },
+    // Each group of open tasks that look alike
+    Duplicates(Vec<Vec<TitleMatch>>),
+    History {
+        task_id: Option<u32>,
+        entries: Vec<AuditEntry>,
+        // Per entry, whether `undo` can still take it back
+        undoable: Vec<bool>,
+        page: usize,
+        pages: usize,
+    },
+    // The command undone and the tasks it put back
+    Undone { label: String, ids: Vec<u32> },
+    Exported { path: PathBuf, count: usize },
+    // How many registry entries went to the file
+    TagsExported { path: PathBuf, count: usize },
+    TagsImported(TagImport),
+    TagDescribed(String),
+    GoalSet(GoalProgress),
+    // None when no goal is set
+    Goal(Option<GoalProgress>),
+    // false when there was no goal to clear
+    GoalCleared(bool),
+    Tags { rows: Vec<TagRow>, long: bool },
+    // `body` already carries the headers when they were asked for
+    Digest { subject: String, body: String },
+    // `clipboard` is false when the text could only be printed
+    Copied { id: u32, text: String, clipboard: bool },
+    Imported(ImportReport),
+    TasksImported(Transfer),
+    Synced(SyncReport),
+    SyncStatus { pending: usize, last_pull: Option<DateTime<Utc>>, last_push: Option<DateTime<Utc>> },
+    IndexCheck(Result<(usize, usize), String>),
+    Size(SizeReport),
+    Schema(Vec<FieldInfo>),
+    JsonSchema(serde_json::Value),
+    RoundTrip(RoundTripReport),
+    // What `check` found and, with --fix, what it repaired; None without --fix
+    Health { issues: Vec<Issue>, repairs: Option<Vec<Repair>> },
+    Board(Board),
+    Tree(Vec<TreeNode>),
+    Matrix(Matrix),
+    Month(Calendar),
+    Summary(Summary),
+    // None when nothing open fits
+    Next(Option<summary::Next>),
+    // Stale tasks, oldest first, as of `now`
+    Stale { days: u32, tasks: Vec<Task>, now: DateTime<Utc> },
+    Timesheet { sheet: Timesheet, csv: bool },
+    // Open tasks blocked on each person
+    Waiting { groups: Vec<(String, Vec<Task>)>, today: NaiveDate },
+    // `replaced` when a view of that name was overwritten
+    ViewSaved { name: String, replaced: bool },
+    ViewRenamed { from: String, to: String },
+    ViewDeleted(String),
+    // Each saved view with how many tasks it finds now
+    Views(Vec<ViewSummary>),
+    // What a command run in dry-run mode would have changed and written
+    DryRun { changes: Vec<PlannedChange>, files: Vec<PathBuf> },
+}
+
+impl CommandOutput {
+    // A note that a change was made, with nothing the user asked to see
+    pub fn is_confirmation(&self) -> bool {
+        if let CommandOutput::Added { similar, .. } = self {
+            return similar.is_empty();
+        }
+        matches!(
+            self,
+            CommandOutput::Updated(_)
+                | CommandOutput::Tagged(_)
+                | CommandOutput::Noted(_)
+                | CommandOutput::DueSet { .. }
+                | CommandOutput::Pinned { completed: false, .. }
+                | CommandOutput::Related { .. }
+                | CommandOutput::PrioritySet { .. }
+                | CommandOutput::Deleted(_)
+                | CommandOutput::Cleared(_)
+                | CommandOutput::Undone { .. }
+                | CommandOutput::Exported { .. }
+                | CommandOutput::TagsExported { .. }
+                | CommandOutput::TagDescribed(_)
+                | CommandOutput::GoalCleared(true)
+                | CommandOutput::Copied { clipboard: true, .. }
+                | CommandOutput::ViewSaved { .. }
+                | CommandOutput::ViewRenamed { .. }
+                | CommandOutput::ViewDeleted(_)
+        )
+    }
+
+    // The task a change was made to and is still there
+    pub fn changed_task(&self) -> Option<u32> {
+        match self {
+            CommandOutput::Added { id, .. }
+            | CommandOutput::Updated(id)
+            | CommandOutput::Tagged(id)
+            | CommandOutput::Noted(id)
+            | CommandOutput::DueSet { id, .. }
+            | CommandOutput::Pinned { id, .. }
+            | CommandOutput::Related { id, .. } => Some(*id),
+            _ => None,
+        }
+    }
+}
+
+// Whether commands make their changes or only report what they would be
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum RunMode {
+    #[default]
+    Apply,
+    DryRun,
+}
+
+// execute, except that in dry-run mode the command is checked and carried out
+// against the tasks and then undone, so its output lists what it would do. A
+// command that changes nothing gives its usual output.
+pub fn execute_in(mode: RunMode, cmd: Command, mgr: &mut TaskManager) -> Result<CommandOutput, TaskError> {
+    if mode == RunMode::Apply {
+        return execute(cmd, mgr);
+    }
+    if let Command::Export { path, .. } | Command::ExportTags { path } = cmd {
+        return Ok(CommandOutput::DryRun { changes: Vec::new(), files: vec![path] });
+    }
+    match mgr.rehearse(|mgr| execute(cmd, mgr)) {
+        (Ok(_), changes) if !changes.is_empty() => Ok(CommandOutput::DryRun { changes, files: Vec::new() }),
+        (result, _) => result,
+    }
+}
+
+pub fn execute(cmd: Command, mgr: &mut TaskManager) -> Result<CommandOutput, TaskError> {
+    match cmd {
+        Command::Help => Ok(CommandOutput::Help),
+        Command::Add { title, description, priority, due, tags, parent, required, energy, fields } => {
+            // Built whole so validation rules see the tags and due date
+            let mut task = Task::new(0, title, description, priority.unwrap_or(Priority::Medium));
+            task.due = due;
+            task.energy = energy;
+            task.fields.extend(fields);
+            if let Some(parent) = parent {
+                task.parent = Some(mgr.get_task(parent)?.uid.clone());
+            }
+            for tag in tags {
+                task.add_tag(tag);
+            }
+            let missing = required.unwrap_or_default().missing(&task);
+            if !missing.is_empty() {
+                return Err(TaskError::ValidationFailed(missing));
+            }
+            let id = mgr.create_task(task)?;
+            let similar = mgr.similar_open_tasks(id)?.iter().map(|task| (task.id, task.title.clone())).collect();
+            Ok(CommandOutput::Added { id, similar })
+        }
+        Command::AddLines { text, priority, due, tags, parent } => {
+            let parent = parent.map(|id| mgr.get_task(id).map(|task| task.uid.clone())).transpose()?;
+            let mut lines = Vec::new();
+            let mut tasks = Vec::new();
+            let mut skipped = Vec::new();
+            for (n, line) in text.lines().enumerate().map(|(n, line)| (n + 1, line.trim())) {
+                if line.is_empty() || line.starts_with('#') {
+                    continue;
+                }
+                let mut parts = line.splitn(3, '|').map(str::trim);
+                let title = parts.next().unwrap_or_default();
+                let description = parts.next().unwrap_or_default();
+                let line_priority = match parts.next().filter(|text| !text.is_empty()) {
+                    Some(text) => match Priority::from_str(text) {
+                        Ok(priority) => Some(priority),
+                        Err(_) => {
+                            skipped.push((n, msg!("line_bad_priority", priority = text)));
+                            continue;
+                        }
+                    },
+                    None => None,
+                };
+                if title.is_empty() {
+                    skipped.push((n, msg!("line_no_title")));
+                    continue;
+                }
+                let priority = line_priority.or_else(|| priority.clone()).unwrap_or(Priority::Medium);
+                let mut task = Task::new(0, title.to_string(), description.to_string(), priority);
+                task.due = due;
+                task.parent = parent.clone();
+                for tag in &tags {
+                    task.add_tag(tag.clone());
+                }
+                lines.push(n);
+                tasks.push(task);
+            }
+            let mut created = Vec::new();
+            for (n, outcome) in lines.into_iter().zip(mgr.add_tasks_bulk(tasks).outcomes) {
+                match outcome {
+                    Ok(id) => created.push(id),
+                    Err(TaskError::DuplicateTask { existing_id, .. }) => {
+                        skipped.push((n, msg!("line_duplicate", id = existing_id)))
+                    }
+                    Err(e) => skipped.push((n, e.to_string())),
+                }
+            }
+            skipped.sort();
+            Ok(CommandOutput::AddedLines { created, skipped })
+        }
+        Command::List { mode, inbox, sort, scope, columns } => {
+            let (view, tasks) = match scope {
+                _ if inbox => (ListView::Inbox, mgr.inbox()),
+                Some(scope @ Scope::View { .. }) => (ListView::View(scope.label()), scope.tasks(mgr)?),
+                Some(scope @ Scope::Filter { .. }) => (ListView::Filter(scope.label()), scope.tasks(mgr)?),
+                None => (ListView::All, mgr.list_tasks()),
+            };
+            let mut tasks: Vec<Task> = tasks.into_iter().cloned().collect();
+            sort.unwrap_or(SortKey::Id).sort(&mut tasks);
+            match columns {
+                Some(columns) => Ok(CommandOutput::Table { view, tasks, columns }),
+                None => Ok(CommandOutput::Tasks { view, tasks, mode }),
+            }
+        }
+        Command::Show { id, mode } => {
+            let task = Box::new(mgr.get_task(id)?.clone());
+            Ok(CommandOutput::Task { task, mode, related: mgr.related(id).into_iter().cloned().collect() })
+        }
+        Command::Update { id, status, note } => {
+            mgr.update_task_status_noted(id, status, note.as_deref())?;
+            Ok(CommandOutput::Updated(id))
+        }
+        Command::Tag { id, tag } => {
+            mgr.add_tag_to_task(id, tag)?;
+            Ok(CommandOutput::Tagged(id))
+        }
+        Command::Note { id, text } => {
+            let description = &mgr.get_task(id)?.description;
+            let description = if description.is_empty() { text } else { format!("{}\n{}", description, text) };
+            mgr.apply_edit(id, TaskEdit { description: Some(description), ..TaskEdit::default() })?;
+            Ok(CommandOutput::Noted(id))
+        }
+        Command::Quick { title, priority } => {
+            let mut task = Task::new(0, title, String::new(), priority.unwrap_or(Priority::Medium));
+            task.triaged = false;
+            Ok(CommandOutput::Added { id: mgr.create_task(task)?, similar: Vec::new() })
+        }
+        Command::Field { id, key, value } => {
+            mgr.set_field(id, &key, value)?;
+            Ok(CommandOutput::Updated(id))
+        }
+        Command::Energy { id, energy } => {
+            mgr.set_energy(id, energy)?;
+            Ok(CommandOutput::Updated(id))
+        }
+        Command::CanDo { energy } => {
+            let tasks = mgr.can_do(energy, Local::now().date_naive()).into_iter().cloned().collect();
+            Ok(CommandOutput::Tasks { view: ListView::Energy(energy), tasks, mode: None })
+        }
+        Command::Next { energy } => Ok(CommandOutput::Next(summary::next(mgr, Local::now().date_naive(), energy))),
+        Command::Remind { id, offset } => {
+            let mut offsets: Vec<Duration> = mgr.get_task(id)?.reminders.iter().map(|r| r.offset).collect();
+            match offset {
+                Some(offset) => offsets.push(offset),
+                None => offsets.clear(),
+            }
+            mgr.set_reminders(id, offsets)?;
+            let task = Box::new(mgr.get_task(id)?.clone());
+            Ok(CommandOutput::Task { task, mode: Some(DisplayMode::Normal), related: Vec::new() })
+        }
+        Command::Wait { id, who, follow_up } => {
+            mgr.wait_on(id, who, Local::now().date_naive(), follow_up)?;
+            Ok(CommandOutput::Updated(id))
+        }
+        Command::Unwait { id } => {
+            mgr.stop_waiting(id)?;
+            Ok(CommandOutput::Updated(id))
+        }
+        Command::Pin { id, pinned } => {
+            mgr.set_pinned(id, pinned)?;
+            let completed = pinned && mgr.get_task(id)?.status == TaskStatus::Completed;
+            Ok(CommandOutput::Pinned { id, pinned, completed })
+        }
+        Command::Pinned => Ok(task_list(ListView::Pinned, mgr.pinned())),
+        Command::Mine => {
+            let filter = TaskFilter { owner: Some(ME.to_string()), ..TaskFilter::default() };
+            Ok(task_list(ListView::Mine, mgr.find(&filter)))
+        }
+        Command::StatsByUser => Ok(CommandOutput::StatsByUser(mgr.stats_by_creator())),
+        Command::StatsWeighted { weeks } => {
+            Ok(CommandOutput::StatsWeighted(mgr.effort(Utc::now(), weeks.unwrap_or(DEFAULT_FORECAST_WEEKS))))
+        }
+        Command::Relate { id, other, related } => {
+            let changed = if related { mgr.relate(id, other)? } else { mgr.unrelate(id, other)? };
+            Ok(CommandOutput::Related { id, other, related, changed })
+        }
+        Command::SetPriority { ids, priority } => {
+            // Every id must be a task before any of them changes
+            for &id in &ids {
+                mgr.get_task(id)?;
+            }
+            let (mut changed, mut unchanged) = (Vec::new(), Vec::new());
+            for id in ids {
+                let from = mgr.get_task(id)?.priority.clone();
+                if mgr.update_priority(id, priority.clone())? {
+                    changed.push((id, from));
+                } else {
+                    unchanged.push(id);
+                }
+            }
+            Ok(CommandOutput::PrioritySet { priority, changed, unchanged })
+        }
+        Command::Graph => Ok(CommandOutput::Graph(graph::dot(mgr.list_tasks()))),
+        Command::DueSoon { days } => Ok(task_list(ListView::DueSoon(days), mgr.due_soon(days, Local::now().naive_local()))),
+        Command::Move { id, place, other } => {
+            mgr.move_task(id, place, other)?;
+            Ok(CommandOutput::Updated(id))
+        }
+        Command::Prefix { id, prefix } => {
+            mgr.set_key_prefix(id, &prefix)?;
+            Ok(CommandOutput::Updated(id))
+        }
+        Command::Waiting => {
+            let groups = mgr
+                .waiting()
+                .into_iter()
+                .map(|(who, tasks)| (who, tasks.into_iter().cloned().collect()))
+                .collect();
+            Ok(CommandOutput::Waiting { groups, today: Local::now().date_naive() })
+        }
+        Command::Due { id, when, holidays } => {
+            let date = when.resolve(Local::now().date_naive(), holidays.as_deref().unwrap_or_default())?;
+            mgr.apply_edit(id, TaskEdit { due: Some(date), ..TaskEdit::default() })?;
+            Ok(CommandOutput::DueSet { id, date })
+        }
+        Command::RecurPreview { target, count, business_days, holidays } => {
+            let today = Local::now().date_naive();
+            let (rule, from, task) = match target {
+                RecurTarget::Rule(rule) => (rule, today, None),
+                RecurTarget::Task(id) => {
+                    let task = mgr.get_task(id)?;
+                    let text = task.fields.get(RECUR_FIELD).ok_or_else(|| TaskError::Invalid {
+                        reason: format!("task {} has no recurrence rule; set one with: field {} {} \"every monday\"", id, id, RECUR_FIELD),
+                    })?;
+                    let rule = Recurrence::parse(text).ok_or_else(|| TaskError::Invalid {
+                        reason: format!("task {}'s {} field '{}' is not a recurrence rule", id, RECUR_FIELD, text),
+                    })?;
+                    (rule, task.due.unwrap_or(today), Some(id))
+                }
+            };
+            let holidays = business_days.unwrap_or(false).then(|| holidays.unwrap_or_default());
+            let preview = recurrence::preview(&rule, from, count, holidays.as_deref());
+            Ok(CommandOutput::RecurPreview(Preview { task, ..preview }))
+        }
+        Command::Delete { id } => {
+            mgr.delete_task(id)?;
+            Ok(CommandOutput::Deleted(id))
+        }
+        Command::Filter { keyword, sort: Some(sort), .. } => {
+            let mut tasks = mgr.find(&TaskFilter::query(&keyword));
+            sort.sort(&mut tasks);
+            Ok(task_list(ListView::Filter(keyword), tasks))
+        }
+        Command::Filter { keyword, sort: None, show_score } => {
+            let hits = mgr.search(&TaskFilter::query(&keyword));
+            let hits = hits.into_iter().map(|(score, task)| (score, task.clone())).collect();
+            Ok(CommandOutput::Matches { keyword, hits, show_score })
+        }
+        Command::View { name, extra } => {
+            let tasks = mgr.find_view(&name, &extra)?;
+            Ok(task_list(ListView::View(name.to_lowercase()), tasks))
+        }
+        Command::ViewSave { name, query, force } => {
+            check_not_a_command(&name)?;
+            let replaced = mgr.save_view(&name, &query, force)?;
+            Ok(CommandOutput::ViewSaved { name: name.to_lowercase(), replaced })
+        }
+        Command::ViewRename { from, to, force } => {
+            check_not_a_command(&to)?;
+            mgr.rename_view(&from, &to, force)?;
+            Ok(CommandOutput::ViewRenamed { from: from.to_lowercase(), to: to.to_lowercase() })
+        }
+        Command::ViewDelete { name } => {
+            mgr.delete_view(&name)?;
+            Ok(CommandOutput::ViewDeleted(name.to_lowercase()))
+        }
+        Command::ViewList => Ok(CommandOutput::Views(view_summaries(mgr))),
+        Command::Bulk { filter, change, confirmed: false } => {
+            let tasks = mgr.find(&filter).into_iter().cloned().collect();
+            Ok(CommandOutput::BulkPreview { change, tasks })
+        }
+        Command::Bulk { filter, change, confirmed: true } => {
+            let ids: Vec<u32> = mgr.find(&filter).iter().map(|task| task.id).collect();
+            let (mut changed, mut unchanged, mut failed) = (Vec::new(), Vec::new(), Vec::new());
+            for id in ids {
+                match change.apply(mgr, id) {
+                    Ok(true) => changed.push(id),
+                    Ok(false) => unchanged.push(id),
+                    Err(e) => failed.push((id, e.to_string())),
+                }
+            }
+            Ok(CommandOutput::BulkApplied { change, changed, unchanged, failed })
+        }
+        Command::ByTags { tags } => {
+            let tasks = mgr.get_tasks_by_tags(&tags);
+            Ok(task_list(ListView::Tags(tags), tasks))
+        }
+        Command::ByPriority { priority } => {
+            let tasks = mgr.get_tasks_by_priority(priority.clone());
+            Ok(task_list(ListView::Priority(priority), tasks))
+        }
+        Command::ByStatus { status } => {
+            let tasks = mgr.get_tasks_by_status(status.clone());
+            Ok(task_list(ListView::Status(status), tasks))
+        }
+        Command::Stats => {
+            let (total, completed, in_progress, pending) = mgr.get_statistics();
+            let (inbox, pinned) = (mgr.inbox().len(), mgr.pinned().len());
+            let goal = mgr.goal_progress(Local::now().date_naive());
+            Ok(CommandOutput::Stats { total, completed, in_progress, pending, inbox, pinned, goal })
+        }
+        Command::Dedupe => {
+            let clusters = mgr.similar_clusters().into_iter();
+            let clusters = clusters.map(|tasks| tasks.iter().map(|task| (task.id, task.title.clone())).collect());
+            Ok(CommandOutput::Duplicates(clusters.collect()))
+        }
+        Command::Count => {
+            let (total, completed, _, _) = mgr.get_statistics();
+            Ok(CommandOutput::Count { open: total - completed, total })
+        }
+        Command::ClearCompleted => Ok(CommandOutput::Cleared(mgr.clear_completed())),
+        Command::Undo => {
+            let entry = mgr.undo()?;
+            Ok(CommandOutput::Undone { label: entry.label, ids: entry.changes.iter().map(|change| change.id).collect() })
+        }
+        Command::Log { task_id, page } => {
+            let history = mgr.audit_history(task_id);
+            let pages = history.len().div_ceil(LOG_PAGE_SIZE).max(1);
+            let entries: Vec<AuditEntry> =
+                history.into_iter().skip(page.saturating_sub(1).saturating_mul(LOG_PAGE_SIZE)).take(LOG_PAGE_SIZE).collect();
+            let steps = mgr.undo_history();
+            let undoable = entries.iter().map(|entry| steps.iter().any(|step| step.covers(entry))).collect();
+            Ok(CommandOutput::History { task_id, entries, undoable, page, pages })
+        }
+        Command::Export { format, path } => {
+            let tasks = mgr.list_tasks();
+            let (contents, count) = match format {
+                ExportFormat::Ics => {
+                    let dated = tasks.iter().filter(|task| task.due.is_some()).count();
+                    (ics::to_ics(tasks, Utc::now()), dated)
+                }
+                ExportFormat::Html => {
+                    let report = ReportData::gather(tasks.iter().copied(), Utc::now());
+                    (html::render(&report), tasks.len())
+                }
+                ExportFormat::Csv => (csv_export::to_csv(&tasks), tasks.len()),
+                ExportFormat::Json => {
+                    let json = serde_json::to_string_pretty(&tasks).map_err(|e| TaskError::Storage(e.to_string()))?;
+                    (json, tasks.len())
+                }
+            };
+            fs::write(&path, contents).map_err(|e| TaskError::Storage(format!("{}: {}", path.display(), e)))?;
+            Ok(CommandOutput::Exported { path, count })
+        }
+        Command::ExportTags { path } => {
+            tags::write_file(&path, mgr.tag_registry())?;
+            Ok(CommandOutput::TagsExported { path, count: mgr.tag_registry().len() })
+        }
+        Command::ImportTags { path, replace } => {
+            let registry = tags::read_file(&path)?;
+            Ok(CommandOutput::TagsImported(mgr.import_tags(registry, replace)))
+        }
+        Command::TagDescribe { tag, description, color } => {
+            mgr.describe_tag(&tag, &description, color)?;
+            Ok(CommandOutput::TagDescribed(tag.trim().to_lowercase()))
+        }
+        Command::Tags { long, unused } => {
+            let mut rows = mgr.tag_rows();
+            if unused {
+                rows.retain(|row| row.count == 0 && row.info.is_some());
+            }
+            Ok(CommandOutput::Tags { rows, long })
+        }
+        Command::Digest { period, html, headers, limit, from, to } => {
+            let today = Local::now().date_naive();
+            let digest = digest::compose(mgr.list_tasks(), today, period, limit.unwrap_or(DEFAULT_DIGEST_LIMIT));
+            let mut body = if html { digest.to_html() } else { digest.to_text() };
+            if headers {
+                body = digest.to_message(&body, html, from.as_deref(), to.as_deref(), Utc::now());
+            }
+            Ok(CommandOutput::Digest { subject: digest.subject(), body })
+        }
+        Command::DebugIndex => Ok(CommandOutput::IndexCheck(mgr.verify_indexes())),
+        // The CLI answers with its storage filled in; elsewhere there is none to hand
+        Command::Timesheet { group, period, csv, week_start } => {
+            let range = DateRange::for_period(period, &Local::now(), week_start.unwrap_or(Weekday::Mon));
+            let rows = mgr.time_report(group, range);
+            let sheet = Timesheet { group, period, rows, total: mgr.time_total(range) };
+            Ok(CommandOutput::Timesheet { sheet, csv })
+        }
+        Command::Matrix => Ok(CommandOutput::Matrix(matrix::build(mgr, Local::now().date_naive()))),
+        Command::Month { month, week_start } => {
+            let today = Local::now().date_naive();
+            let calendar = calendar::build(mgr.list_tasks(), month.unwrap_or(today), today, week_start.unwrap_or(Weekday::Mon));
+            Ok(CommandOutput::Month(calendar))
+        }
+        Command::Summary => Ok(CommandOutput::Summary(summary::build(mgr, Local::now().date_naive()))),
+        Command::GoalSet { target, period } => {
+            mgr.set_goal(Some(Goal::new(target, period)));
+            Ok(CommandOutput::GoalSet(mgr.goal_progress(Local::now().date_naive()).expect("the goal was just set")))
+        }
+        Command::GoalShow => Ok(CommandOutput::Goal(mgr.goal_progress(Local::now().date_naive()))),
+        Command::GoalClear => {
+            let had = mgr.goal().is_some();
+            mgr.set_goal(None);
+            Ok(CommandOutput::GoalCleared(had))
+        }
+        Command::Review { days } => {
+            let now = Utc::now();
+            let tasks = mgr.stale_tasks(days, now).into_iter().cloned().collect();
+            Ok(CommandOutput::Stale { days, tasks, now })
+        }
+        Command::Tree { root } => {
+            if let Some(id) = root {
+                mgr.get_task(id)?;
+            }
+            Ok(CommandOutput::Tree(tree::build(&mgr.list_tasks(), root)))
+        }
+        Command::Board { done, sort } => Ok(CommandOutput::Board(board::build(mgr.list_tasks(), done, sort.as_ref()))),
+        Command::DebugInfo => Ok(CommandOutput::Size(SizeReport::gather(mgr, None))),
+        Command::Schema { json_schema: false } => Ok(CommandOutput::Schema(schema::task_fields())),
+        Command::Schema { json_schema: true } => Ok(CommandOutput::JsonSchema(schema::json_schema())),
+        Command::VerifyRoundTrip(format) => Ok(CommandOutput::RoundTrip(roundtrip::verify(mgr, format)?)),
+        Command::Check { fix } => {
+            let issues = mgr.health();
+            let repairs = fix.then(|| mgr.repair(Utc::now()));
+            Ok(CommandOutput::Health { issues, repairs })
+        }
+    }
+}
+
+fn check_not_a_command(name: &str) -> Result<(), TaskError> {
+    let name = name.to_lowercase();
+    if COMMAND_NAMES.contains(&name.as_str()) {
+        return Err(TaskError::Invalid { reason: format!("'{}' is a command and cannot name a view", name) });
+    }
+    Ok(())
+}
+
+// Every saved view and its current match count, for `view list` and the startup summary
+pub fn view_summaries(mgr: &TaskManager) -> Vec<ViewSummary> {
+    mgr.views()
+        .iter()
+        .map(|(name, query)| ViewSummary {
+            name: name.clone(),
+            query: query.clone(),
+            count: mgr.find_view(name, &TaskFilter::default()).map_or(0, |tasks| tasks.len()),
+        })
+        .collect()
+}
+
+fn task_list(view: ListView, tasks: Vec<&Task>) -> CommandOutput {
+    CommandOutput::Tasks {
+        view,
+        tasks: tasks.into_iter().cloned().collect(),
+        mode: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::TaskEvent;
+
+    fn tokens(input: &str) -> Vec<String> {
+        input.split_whitespace().map(String::from).collect()
+    }
+
+    fn parse(input: &str) -> Result<Command, ParseError> {
+        Command::parse(&tokens(input))
+    }
+
+    // Scripts match on these names, so each variant's are pinned
+    #[test]
+    fn test_parse_errors_serialize_with_stable_field_names() {
+        let serialized = |err: ParseError| serde_json::to_value(err).unwrap();
+        assert_eq!(serialized(ParseError::Empty), serde_json::json!({ "error": "Empty" }));
+        assert_eq!(
+            serialized(ParseError::UnknownCommand("frob".to_string())),
+            serde_json::json!({ "error": "UnknownCommand", "command": "frob" })
+        );
+        assert_eq!(serialized(ParseError::Usage("usage_add")), serde_json::json!({ "error": "Usage" }));
+        let inputs = [
+            (ParseError::InvalidId("x".to_string()), "InvalidId"),
+            (ParseError::InvalidPriority("x".to_string()), "InvalidPriority"),
+            (ParseError::InvalidStatus("x".to_string()), "InvalidStatus"),
+            (ParseError::InvalidDate("x".to_string()), "InvalidDate"),
+            (ParseError::InvalidEnergy("x".to_string()), "InvalidEnergy"),
+            (ParseError::InvalidToken("x".to_string()), "InvalidToken"),
+            (ParseError::InvalidColumn("x".to_string()), "InvalidColumn"),
+        ];
+        for (err, name) in inputs {
+            assert_eq!(serialized(err), serde_json::json!({ "error": name, "input": "x" }));
+        }
+    }
+
+    #[test]
+    fn test_parse_simple_commands() {
+        assert_eq!(parse("help"), Ok(Command::Help));
+        assert_eq!(parse("list"), Ok(Command::List { mode: None, inbox: false, sort: None, scope: None, columns: None }));
+        assert_eq!(
+            parse("list --format verbose"),
+            Ok(Command::List { mode: Some(DisplayMode::Verbose), inbox: false, sort: None, scope: None, columns: None })
+        );
+        assert_eq!(parse("list --inbox"), Ok(Command::List { mode: None, inbox: true, sort: None, scope: None, columns: None }));
+        assert_eq!(
+            parse("list --columns id,title,pri,age,updated"),
+            Ok(Command::List {
+                mode: None,
+                inbox: false,
+                sort: None,
+                scope: None,
+                columns: Some(vec![Column::Id, Column::Title, Column::Priority, Column::Age, Column::Updated]),
+            })
+        );
+        assert_eq!(parse("list --columns id,size"), Err(ParseError::InvalidColumn("size".to_string())));
+        assert_eq!(parse("list --columns"), Err(ParseError::Usage(LIST_USAGE)));
+        let mut config = Config::default();
+        config.list_columns = vec![Column::Id, Column::Title];
+        assert!(matches!(parse("list").unwrap().with_defaults(&config), Command::List { columns: Some(_), .. }));
+        assert!(matches!(parse("list --format normal").unwrap().with_defaults(&config), Command::List { columns: None, .. }));
+        assert_eq!(
+            parse("list --sort field:points --inbox"),
+            Ok(Command::List { mode: None, inbox: true, sort: Some(SortKey::Field("points".to_string())), scope: None, columns: None })
+        );
+        assert_eq!(parse("list --sort"), Err(ParseError::Usage(LIST_USAGE)));
+        assert_eq!(parse("list --sort colour"), Err(ParseError::Usage(LIST_USAGE)));
+        assert_eq!(parse("quick call the plumber"), Ok(Command::Quick { title: "call the plumber".to_string(), priority: None }));
+        assert_eq!(parse("quick"), Err(ParseError::Usage(QUICK_USAGE)));
+        assert_eq!(parse("list --format fancy"), Err(ParseError::Usage(LIST_USAGE)));
+        assert_eq!(parse("list everything"), Err(ParseError::Usage(LIST_USAGE)));
+        assert_eq!(parse("stats"), Ok(Command::Stats));
+        assert_eq!(parse("stats --by user"), Ok(Command::StatsByUser));
+        assert_eq!(parse("stats --by tag"), Err(ParseError::Usage(STATS_USAGE)));
+        assert_eq!(parse("stats --weighted"), Ok(Command::StatsWeighted { weeks: None }));
+        assert_eq!(parse("stats --weighted --weeks 8"), Ok(Command::StatsWeighted { weeks: Some(8) }));
+        assert_eq!(parse("stats --weighted --weeks 0"), Err(ParseError::Usage(STATS_USAGE)));
+        assert_eq!(parse("mine"), Ok(Command::Mine));
+        assert_eq!(parse("count"), Ok(Command::Count));
+        assert_eq!(parse("clear-completed"), Ok(Command::ClearCompleted));
+        assert!(parse("clear-completed").unwrap().is_bulk());
+        assert_eq!(parse("log"), Ok(Command::Log { task_id: None, page: 1 }));
+        assert_eq!(parse("log 4 --page 2"), Ok(Command::Log { task_id: Some(4), page: 2 }));
+        assert_eq!(parse("log --page 0"), Err(ParseError::Usage(LOG_USAGE)));
+        assert_eq!(parse("log x"), Err(ParseError::InvalidId("x".to_string())));
+        assert_eq!(parse("log 1 2"), Err(ParseError::Usage(LOG_USAGE)));
+        assert_eq!(parse("export ics cal.ics"), Ok(Command::Export { format: ExportFormat::Ics, path: PathBuf::from("cal.ics") }));
+        assert_eq!(parse("export html out.html"), Ok(Command::Export { format: ExportFormat::Html, path: PathBuf::from("out.html") }));
+        assert_eq!(parse("export pdf out.pdf"), Err(ParseError::Usage(EXPORT_USAGE)));
+        assert_eq!(parse(""), Err(ParseError::Empty));
+        assert_eq!(parse("frobnicate 1"), Err(ParseError::UnknownCommand("frobnicate".to_string())));
+    }
+
+    #[test]
+    fn test_parse_add() {
+        assert_eq!(parse("add Buy milk --desc two litres --priority h --due 2024-07-01 --tag home --tag errand"), Ok(Command::Add {
+            title: "Buy milk".to_string(),
+            description: "two litres".to_string(),
+            priority: Some(Priority::High),
+            due: NaiveDate::from_ymd_opt(2024, 7, 1),
+            tags: vec!["home".to_string(), "errand".to_string()],
+            parent: None,
+            required: None,
+            energy: None,
+            fields: Vec::new(),
+        }));
+        assert_eq!(parse("add Walk dog"), Ok(Command::Add {
+            title: "Walk dog".to_string(),
+            description: String::new(),
+            priority: None,
+            due: None,
+            tags: Vec::new(),
+            parent: None,
+            required: None,
+            energy: None,
+            fields: Vec::new(),
+        }));
+        assert!(matches!(parse("add Step one --parent 4"), Ok(Command::Add { parent: Some(4), .. })));
+        assert!(matches!(parse("add Reply to Sam --energy easy"), Ok(Command::Add { energy: Some(Energy::Low), .. })));
+        assert!(matches!(parse("add Call Ana --field context phone"), Ok(Command::Add { fields, .. }) if fields == [("context".to_string(), "phone".to_string())]));
+        assert_eq!(parse("add x --field Context phone"), Err(ParseError::Usage(ADD_USAGE)));
+        assert_eq!(parse("add x --field context"), Err(ParseError::Usage(ADD_USAGE)));
+        assert_eq!(parse("add x --energy mush"), Err(ParseError::InvalidEnergy("mush".to_string())));
+        assert_eq!(parse("add x --parent"), Err(ParseError::Usage(ADD_USAGE)));
+        assert_eq!(parse("add"), Err(ParseError::Usage(ADD_USAGE)));
+        assert_eq!(parse("add --desc only a description"), Err(ParseError::Usage(ADD_USAGE)));
+        assert_eq!(parse("add x --priority"), Err(ParseError::Usage(ADD_USAGE)));
+        assert_eq!(parse("add x --priority urgent"), Err(ParseError::InvalidPriority("urgent".to_string())));
+        assert_eq!(parse("add x --due tomorrow"), Err(ParseError::InvalidDate("tomorrow".to_string())));
+    }
+
+    #[test]
+    fn test_add_uses_configured_default_priority() {
+        let mut config = Config::default();
+        config.default_priority = Priority::High;
+        let cmd = parse("add Deploy").unwrap().with_defaults(&config);
+        assert!(matches!(cmd, Command::Add { priority: Some(Priority::High), .. }));
+        let explicit = parse("add Deploy --priority low").unwrap().with_defaults(&config);
+        assert!(matches!(explicit, Command::Add { priority: Some(Priority::Low), .. }));
+    }
+
+    #[test]
+    fn test_task_keys_stand_in_for_ids() {
+        let mut mgr = TaskManager::new();
+        let project = mgr.add_task("Auth".to_string(), String::new(), Priority::Medium).unwrap();
+        assert_eq!(parse("prefix 1 AUTH"), Ok(Command::Prefix { id: 1, prefix: "AUTH".to_string() }));
+        assert_eq!(parse("prefix 1"), Err(ParseError::Usage(PREFIX_USAGE)));
+        execute(parse("prefix 1 AUTH").unwrap(), &mut mgr).unwrap();
+        let login = execute(parse("add Login --parent 1").unwrap().with_defaults(&Config::default()), &mut mgr);
+        assert!(matches!(login, Ok(CommandOutput::Added { id: 2, .. })));
+
+        let resolve = |input: &str| resolve_task_ref(&tokens(input), &mgr, &mut |_, _| None).unwrap();
+        assert_eq!(resolve("update auth-1 completed"), tokens("update 2 completed"));
+        // Only where an id goes
+        assert_eq!(resolve("add AUTH-1 follow-up"), tokens("add AUTH-1 follow-up"));
+        assert_eq!(mgr.get_task(project).unwrap().key, None);
+    }
+
+    #[test]
+    fn test_title_prefixes_stand_in_for_ids() {
+        let mut mgr = TaskManager::new();
+        for title in ["Buy milk", "Buy mints", "Call bank", "Buy milk and eggs", "Call bank manager"] {
+            mgr.add_task(title.to_string(), String::new(), Priority::Medium).unwrap();
+        }
+        mgr.update_task_status(1, TaskStatus::Completed).unwrap();
+        let mut nobody = |_: &str, _: &[(u32, String)]| None;
+
+        // One open task starts with it, or has it as its whole title
+        let resolve = |input: &str| resolve_task_ref(&tokens(input), &mgr, &mut |_, _| None).unwrap();
+        assert_eq!(resolve(r#"tag "buy min" shop"#), tokens("tag 2 shop"));
+        assert_eq!(resolve(r#"add Eggs --parent "bUY MIN" --tag shop"#), tokens("add Eggs --parent 2 --tag shop"));
+        assert_eq!(resolve(r#"update "call bank" completed"#), tokens("update 3 completed"));
+        // None does
+        match resolve_task_ref(&tokens("delete walk"), &mgr, &mut nobody) {
+            Err(TaskError::NoTitleMatch { text }) => assert_eq!(text, "walk"),
+            other => panic!("unexpected result: {:?}", other),
+        }
+        // Several do: listed unless one of them is picked
+        match resolve_task_ref(&tokens("show buy"), &mgr, &mut nobody) {
+            Err(TaskError::AmbiguousTitle { text, matches }) => {
+                assert_eq!(text, "buy");
+                assert_eq!(matches, vec![(2, "Buy mints".to_string()), (4, "Buy milk and eggs".to_string())]);
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+        let mut pick_four = |_: &str, _: &[(u32, String)]| Some(4);
+        assert_eq!(resolve_task_ref(&tokens("show buy"), &mgr, &mut pick_four).unwrap(), tokens("show 4"));
+        let mut pick_other = |_: &str, _: &[(u32, String)]| Some(3);
+        assert!(resolve_task_ref(&tokens("show buy"), &mgr, &mut pick_other).is_err());
+        // Numbers and flags are left alone
+        assert_eq!(resolve("log --page 2"), tokens("log --page 2"));
+        assert_eq!(resolve("show 1"), tokens("show 1"));
+    }
+
+    #[test]
+    fn test_dry_run_reports_instead_of_changing() {
+        let mut mgr = TaskManager::new();
+        mgr.add_task("Buy milk".to_string(), String::new(), Priority::Medium).unwrap();
+        mgr.update_task_status(1, TaskStatus::Completed).unwrap();
+
+        match execute_in(RunMode::DryRun, Command::ClearCompleted, &mut mgr) {
+            Ok(CommandOutput::DryRun { changes, files }) => {
+                assert_eq!(changes.len(), 1);
+                assert_eq!((&changes[0].event, changes[0].title.as_str()), (&TaskEvent::Deleted(1), "Buy milk"));
+                assert!(files.is_empty());
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+        assert!(mgr.get_task(1).is_ok());
+        // Checks still apply, and read-only commands answer as usual
+        let mut dry_run = |command| execute_in(RunMode::DryRun, command, &mut mgr);
+        assert!(matches!(dry_run(Command::Delete { id: 9 }), Err(TaskError::TaskNotFound { id: 9 })));
+        assert!(matches!(dry_run(Command::Count), Ok(CommandOutput::Count { open: 0, total: 1 })));
+        let path = std::env::temp_dir().join(format!("task-manager-dry-run-{}.csv", std::process::id()));
+        let export = Command::Export { format: ExportFormat::Csv, path: path.clone() };
+        assert!(matches!(dry_run(export), Ok(CommandOutput::DryRun { files, .. }) if files == [path.clone()]));
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn test_add_lines_skips_comments_duplicates_and_bad_lines() {
+        let mut mgr = TaskManager::new();
+        mgr.add_task("Visit Lisbon".to_string(), String::new(), Priority::Medium).unwrap();
+        assert_eq!(
+            parse("add --stdin --priority low --tag someday"),
+            Ok(Command::AddLines {
+                text: String::new(),
+                priority: Some(Priority::Low),
+                due: None,
+                tags: vec!["someday".to_string()],
+                parent: None
+            })
+        );
+        assert_eq!(parse("add Title --stdin"), Err(ParseError::Usage(ADD_USAGE)));
+
+        let text = "# ideas\nLearn the cello\n\nRepaint the shed | green | high\nlearn the cello\n\
+                    Visit Lisbon\n | no title\nOrchard | | soon\n";
+        let Ok(Command::AddLines { priority, tags, .. }) = parse("add --stdin --priority low --tag someday") else {
+            panic!("add --stdin did not parse");
+        };
+        let cmd = Command::AddLines { text: text.to_string(), priority, due: None, tags, parent: None };
+        let Ok(CommandOutput::AddedLines { created, skipped }) = execute(cmd, &mut mgr) else {
+            panic!("add --stdin failed");
+        };
+        assert_eq!(created, vec![2, 3]);
+        let lines: Vec<usize> = skipped.iter().map(|(n, _)| *n).collect();
+        assert_eq!(lines, vec![5, 6, 7, 8]);
+        assert_eq!(skipped[1].1, "a task with this title already exists (ID: 1)");
+        let shed = mgr.get_task(3).unwrap();
+        assert_eq!((shed.description.as_str(), &shed.priority), ("green", &Priority::High));
+        assert_eq!(mgr.get_task(2).unwrap().priority, Priority::Low);
+        assert_eq!(mgr.get_task(2).unwrap().tags, vec!["someday".into()]);
+    }
+
+    #[test]
+    fn test_add_names_each_missing_required_field() {
+        let mut config = Config::default();
+        config.required = RequiredFields { due_date: true, tags: false, description: true };
+        let mut mgr = TaskManager::new();
+        let add = |text: &str| parse(text).unwrap().with_defaults(&config);
+        match execute(add("add Deploy"), &mut mgr) {
+            Err(TaskError::ValidationFailed(missing)) => {
+                assert_eq!(missing, vec!["a description is required", "a due date is required"])
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+        assert!(execute(add("add Deploy --desc to staging --due 2024-07-01"), &mut mgr).is_ok());
+        // Captures to triage later are exempt
+        assert!(execute(parse("quick Rollback plan").unwrap().with_defaults(&config), &mut mgr).is_ok());
+    }
+
+    #[test]
+    fn test_parse_digest() {
+        let digest = |period, html, headers, limit| Command::Digest { period, html, headers, limit, from: None, to: None };
+        assert_eq!(parse("digest"), Ok(digest(Period::Week, false, false, None)));
+        assert_eq!(parse("digest --period day --html --headers --limit 3"), Ok(digest(Period::Day, true, true, Some(3))));
+        assert_eq!(parse("digest --period month"), Err(ParseError::Usage(DIGEST_USAGE)));
+        assert_eq!(parse("digest --limit 0"), Err(ParseError::Usage(DIGEST_USAGE)));
+
+        let mut config = Config::default();
+        config.digest_limit = 8;
+        config.digest_to = Some("me@example.com".to_string());
+        let cmd = parse("digest --headers").unwrap().with_defaults(&config);
+        assert!(matches!(cmd, Command::Digest { limit: Some(8), to: Some(ref to), .. } if to == "me@example.com"));
+    }
+
+    #[test]
+    fn test_parse_id_commands() {
+        assert_eq!(parse("show 3"), Ok(Command::Show { id: 3, mode: None }));
+        assert_eq!(parse("show --format compact 3"), Ok(Command::Show { id: 3, mode: Some(DisplayMode::Compact) }));
+        assert_eq!(parse("show"), Err(ParseError::Usage(SHOW_USAGE)));
+        assert_eq!(parse("show abc"), Err(ParseError::InvalidId("abc".to_string())));
+        assert_eq!(parse("delete 7"), Ok(Command::Delete { id: 7 }));
+        assert_eq!(parse("delete"), Err(ParseError::Usage(DELETE_USAGE)));
+        assert_eq!(parse("delete -1"), Err(ParseError::InvalidId("-1".to_string())));
+    }
+
+    #[test]
+    fn test_parse_update_and_tag() {
+        assert_eq!(parse("update 2 progress"), Ok(Command::Update { id: 2, status: TaskStatus::InProgress, note: None }));
+        assert_eq!(parse("update 2"), Err(ParseError::Usage(UPDATE_USAGE)));
+        assert_eq!(parse("update x done"), Err(ParseError::InvalidId("x".to_string())));
+        assert_eq!(parse("update 2 done"), Err(ParseError::InvalidStatus("done".to_string())));
+        assert_eq!(parse("tag 4 needs review"), Ok(Command::Tag { id: 4, tag: "needs review".to_string() }));
+        assert_eq!(parse("tag 4"), Err(ParseError::Usage(TAG_USAGE)));
+    }
+
+    #[test]
+    fn test_parse_queries() {
+        assert_eq!(
+            parse("filter buy milk"),
+            Ok(Command::Filter { keyword: "buy milk".to_string(), sort: None, show_score: false })
+        );
+        assert_eq!(
+            parse("filter --debug-score report"),
+            Ok(Command::Filter { keyword: "report".to_string(), sort: None, show_score: true })
+        );
+        assert_eq!(
+            parse("filter report --sort id"),
+            Ok(Command::Filter { keyword: "report".to_string(), sort: Some(SortKey::Id), show_score: false })
+        );
+        assert_eq!(parse("filter"), Err(ParseError::Usage(FILTER_USAGE)));
+        assert_eq!(parse("filter --sort id"), Err(ParseError::Usage(FILTER_USAGE)));
+        assert_eq!(parse("filter x --sort id --debug-score"), Err(ParseError::Usage(FILTER_USAGE)));
+        assert_eq!(parse("tagged work home"), Ok(Command::ByTags { tags: vec!["work".to_string(), "home".to_string()] }));
+        assert_eq!(parse("tagged"), Err(ParseError::Usage(TAGGED_USAGE)));
+        assert_eq!(parse("debug index"), Ok(Command::DebugIndex));
+        assert_eq!(parse("debug info"), Ok(Command::DebugInfo));
+        assert_eq!(parse("schema"), Ok(Command::Schema { json_schema: false }));
+        assert_eq!(parse("goal"), Ok(Command::GoalShow));
+        assert_eq!(parse("goal set 10"), Ok(Command::GoalSet { target: 10, period: GoalPeriod::Week }));
+        assert_eq!(parse("goal set 3 --period day"), Ok(Command::GoalSet { target: 3, period: GoalPeriod::Day }));
+        assert_eq!(parse("goal clear"), Ok(Command::GoalClear));
+        for bad in ["goal set 0", "goal set ten", "goal set 5 --period year", "goal reset"] {
+            assert_eq!(parse(bad), Err(ParseError::Usage(GOAL_USAGE)), "{}", bad);
+        }
+        assert_eq!(parse("schema --json-schema"), Ok(Command::Schema { json_schema: true }));
+        assert_eq!(parse("schema --yaml"), Err(ParseError::Usage(SCHEMA_USAGE)));
+        assert_eq!(parse("verify-roundtrip csv"), Ok(Command::VerifyRoundTrip(RoundTripFormat::Csv)));
+        assert_eq!(parse("verify-roundtrip org"), Err(ParseError::Usage(VERIFY_ROUNDTRIP_USAGE)));
+        assert_eq!(parse("check"), Ok(Command::Check { fix: false }));
+        assert_eq!(parse("check --fix"), Ok(Command::Check { fix: true }));
+        assert_eq!(parse("check --all"), Err(ParseError::Usage(CHECK_USAGE)));
+        assert!(Command::Check { fix: true }.is_bulk() && !Command::Check { fix: false }.is_bulk());
+        assert_eq!(parse("board"), Ok(Command::Board { done: DEFAULT_DONE_SHOWN, sort: None }));
+        assert_eq!(parse("board --done 0"), Ok(Command::Board { done: 0, sort: None }));
+        assert_eq!(
+            parse("board --sort manual --done 1"),
+            Ok(Command::Board { done: 1, sort: Some(SortKey::Manual) })
+        );
+        assert_eq!(parse("board --done"), Err(ParseError::Usage(BOARD_USAGE)));
+        assert_eq!(parse("matrix"), Ok(Command::Matrix));
+        let may = NaiveDate::from_ymd_opt(2024, 5, 1);
+        assert_eq!(parse("month"), Ok(Command::Month { month: None, week_start: None }));
+        assert_eq!(parse("month 2024-05"), Ok(Command::Month { month: may, week_start: None }));
+        assert_eq!(parse("month 2024-13"), Err(ParseError::Usage(MONTH_USAGE)));
+        assert_eq!(parse("month May"), Err(ParseError::Usage(MONTH_USAGE)));
+        assert_eq!(parse("summary"), Ok(Command::Summary));
+        let timesheet = |group, period, csv| Ok(Command::Timesheet { group, period, csv, week_start: None });
+        assert_eq!(parse("timesheet"), timesheet(GroupKey::Tag, ReportPeriod::Week, false));
+        assert_eq!(parse("timesheet --by project --period all --csv"), timesheet(GroupKey::Project, ReportPeriod::All, true));
+        assert_eq!(parse("timesheet --period year"), Err(ParseError::Usage(TIMESHEET_USAGE)));
+        let mut config = Config::default();
+        config.first_day_of_week = "sunday".to_string();
+        let cmd = parse("timesheet").unwrap().with_defaults(&config);
+        assert!(matches!(cmd, Command::Timesheet { week_start: Some(Weekday::Sun), .. }));
+        assert_eq!(parse("note 4 called the bank"), Ok(Command::Note { id: 4, text: "called the bank".to_string() }));
+        assert_eq!(parse("note 4"), Err(ParseError::Usage(NOTE_USAGE)));
+        assert_eq!(parse("due 7 \"3 business days\""), Ok(Command::Due { id: 7, when: DueSpec::BusinessDays(3), holidays: None }));
+        assert_eq!(parse("due 7 someday"), Err(ParseError::InvalidDate("someday".to_string())));
+        assert_eq!(parse("due 7"), Err(ParseError::Usage(DUE_USAGE)));
+        let preview = |target, count, business_days| Ok(Command::RecurPreview { target, count, business_days, holidays: None });
+        let weekly = RecurTarget::Rule(Recurrence::Weeks { every: 2, on: vec![Weekday::Mon, Weekday::Thu] });
+        assert_eq!(parse("recur-preview 4"), preview(RecurTarget::Task(4), DEFAULT_PREVIEW_COUNT, None));
+        assert_eq!(parse("recur-preview 4 12 --business-days"), preview(RecurTarget::Task(4), 12, Some(true)));
+        assert_eq!(parse("recur-preview every 2 weeks on mon,thu 8"), preview(weekly.clone(), 8, None));
+        assert_eq!(parse("recur-preview \"every 2 weeks on mon,thu\""), preview(weekly, DEFAULT_PREVIEW_COUNT, None));
+        assert_eq!(parse("recur-preview daily 1000"), preview(RecurTarget::Rule(Recurrence::Days(1)), 1000, None));
+        for bad in ["recur-preview", "recur-preview sometimes", "recur-preview 4 0", "recur-preview every 3", "recur-preview daily 1001"] {
+            assert_eq!(parse(bad), Err(ParseError::Usage(RECUR_PREVIEW_USAGE)), "{}", bad);
+        }
+        let huge = parse("recur-preview every 1 days 1000000000");
+        assert_eq!(huge, Err(ParseError::Usage(RECUR_PREVIEW_USAGE)));
+        let mut config = Config::default();
+        config.business_days = true;
+        let cmd = parse("recur-preview daily").unwrap().with_defaults(&config);
+        assert!(matches!(cmd, Command::RecurPreview { business_days: Some(true), holidays: Some(_), .. }));
+        assert_eq!(parse("remind 7 2d"), Ok(Command::Remind { id: 7, offset: Some(Duration::from_secs(2 * 86_400)) }));
+        assert_eq!(parse("remind 7 none"), Ok(Command::Remind { id: 7, offset: None }));
+        assert_eq!(parse("remind 7 soon"), Err(ParseError::Usage(REMIND_USAGE)));
+        assert_eq!(parse("remind 1 1é"), Err(ParseError::Usage(REMIND_USAGE)));
+        assert_eq!(parse("remind 7"), Err(ParseError::Usage(REMIND_USAGE)));
+        assert_eq!(
+            parse("field 7 customer Acme Corp"),
+            Ok(Command::Field { id: 7, key: "customer".to_string(), value: Some("Acme Corp".to_string()) })
+        );
+        assert_eq!(parse("field 7 customer --unset"), Ok(Command::Field { id: 7, key: "customer".to_string(), value: None }));
+        assert_eq!(parse("field 7 customer"), Err(ParseError::Usage(FIELD_USAGE)));
+        let until = NaiveDate::from_ymd_opt(2024, 7, 10);
+        assert_eq!(
+            parse("wait 7 Alice Smith until 2024-07-10"),
+            Ok(Command::Wait { id: 7, who: "Alice Smith".to_string(), follow_up: until })
+        );
+        assert_eq!(parse("wait 7 Bob"), Ok(Command::Wait { id: 7, who: "Bob".to_string(), follow_up: None }));
+        assert_eq!(parse("wait 7 until 2024-07-10"), Err(ParseError::Usage(WAIT_USAGE)));
+        assert_eq!(parse("wait 7 Bob until"), Err(ParseError::Usage(WAIT_USAGE)));
+        assert_eq!(parse("wait 7 Bob until soon"), Err(ParseError::InvalidDate("soon".to_string())));
+        assert_eq!(parse("unwait 7"), Ok(Command::Unwait { id: 7 }));
+        assert_eq!(parse("waiting"), Ok(Command::Waiting));
+        assert_eq!(parse("tree"), Ok(Command::Tree { root: None }));
+        assert_eq!(parse("tree 3"), Ok(Command::Tree { root: Some(3) }));
+        assert_eq!(parse("tree 3 4"), Err(ParseError::Usage(TREE_USAGE)));
+        assert_eq!(parse("debug"), Err(ParseError::Usage(DEBUG_USAGE)));
+        assert_eq!(parse("priority critical"), Ok(Command::ByPriority { priority: Priority::Critical }));
+        assert_eq!(parse("priority"), Err(ParseError::Usage(PRIORITY_USAGE)));
+        assert_eq!(parse("priority 9"), Err(ParseError::InvalidPriority("9".to_string())));
+        let set = |ids: &[u32], priority| Ok(Command::SetPriority { ids: ids.to_vec(), priority });
+        assert_eq!(parse("priority 3 high"), set(&[3], Priority::High));
+        assert_eq!(parse("priority-set 3,5,7 c"), set(&[3, 5, 7], Priority::Critical));
+        assert_eq!(parse("priority-set 2-4,3,9 low"), set(&[2, 3, 4, 9], Priority::Low));
+        assert_eq!(parse("priority-set 4-2 low"), Err(ParseError::InvalidId("4-2".to_string())));
+        assert_eq!(parse("priority-set 3 urgent"), Err(ParseError::InvalidPriority("urgent".to_string())));
+        assert_eq!(parse("priority-set 3"), Err(ParseError::Usage(PRIORITY_SET_USAGE)));
+        // Two words whose first is no id is still the filter
+        assert_eq!(parse("priority high extra"), Ok(Command::ByPriority { priority: Priority::High }));
+        assert_eq!(parse("status completed"), Ok(Command::ByStatus { status: TaskStatus::Completed }));
+        assert_eq!(parse("status"), Err(ParseError::Usage(STATUS_USAGE)));
+        assert_eq!(parse("status later"), Err(ParseError::InvalidStatus("later".to_string())));
+    }
+
+    #[test]
+    fn test_log_pages_newest_first() {
+        let mut mgr = TaskManager::new();
+        mgr.enable_audit("bob".to_string(), 100);
+        for i in 0..25 {
+            execute(parse(&format!("add Task {}", i)).unwrap(), &mut mgr).unwrap();
+        }
+        match execute(parse("log --page 2").unwrap(), &mut mgr).unwrap() {
+            CommandOutput::History { entries, page, pages, .. } => {
+                assert_eq!((page, pages), (2, 2));
+                assert_eq!(entries.len(), 5);
+                assert_eq!(entries[0].task_id, Some(5));
+            }
+            other => panic!("unexpected output: {:?}", other),
+        }
+        match execute(parse("log 3").unwrap(), &mut mgr).unwrap() {
+            CommandOutput::History { entries, pages, .. } => {
+                assert_eq!(pages, 1);
+                assert_eq!(entries.len(), 1);
+                assert_eq!(entries[0].user, "bob");
+            }
+            other => panic!("unexpected output: {:?}", other),
+        }
+        // A page past the end is empty, however far past
+        match execute(parse(&format!("log --page {}", usize::MAX)).unwrap(), &mut mgr).unwrap() {
+            CommandOutput::History { entries, page, pages, .. } => {
+                assert_eq!((page, pages), (usize::MAX, 2));
+                assert!(entries.is_empty());
+            }
+            other => panic!("unexpected output: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_list_stays_inside_the_active_scope() {
+        let mut mgr = TaskManager::new();
+        for line in ["add Fix login", "add Invoices", "tag 1 backend", "view save backend tag:backend"] {
+            execute(parse(line).unwrap(), &mut mgr).unwrap();
+        }
+        let list = |scope| Command::List { mode: None, inbox: false, sort: None, scope: Some(scope), columns: None };
+        let ids = |output| match output {
+            CommandOutput::Tasks { view, tasks, .. } => (view, tasks.iter().map(|task| task.id).collect::<Vec<_>>()),
+            other => panic!("unexpected output: {:?}", other),
+        };
+        let backend = Scope::View { name: "backend".to_string(), extra: String::new() };
+        assert_eq!(ids(execute(list(backend), &mut mgr).unwrap()), (ListView::View("backend".to_string()), vec![1]));
+        let invoices = Scope::Filter { query: "invoice".to_string() };
+        assert_eq!(ids(execute(list(invoices), &mut mgr).unwrap()), (ListView::Filter("invoice".to_string()), vec![2]));
+        let gone = Scope::View { name: "frontend".to_string(), extra: String::new() };
+        assert!(execute(list(gone), &mut mgr).is_err());
+    }
+
+    #[test]
+    fn test_log_marks_what_undo_can_take_back() {
+        let mut mgr = TaskManager::new();
+        mgr.enable_audit("bob".to_string(), 100);
+        execute(parse("add Write report").unwrap(), &mut mgr).unwrap();
+        mgr.begin_undo_step("add Buy milk");
+        execute(parse("add Buy milk").unwrap(), &mut mgr).unwrap();
+        mgr.finish_undo_step();
+        match execute(parse("log").unwrap(), &mut mgr).unwrap() {
+            CommandOutput::History { undoable, .. } => assert_eq!(undoable, vec![true, false]),
+            other => panic!("unexpected output: {:?}", other),
+        }
+        assert_eq!(parse("undo"), Ok(Command::Undo));
+        let undone = execute(Command::Undo, &mut mgr).unwrap();
+        assert_eq!(undone, CommandOutput::Undone { label: "add Buy milk".to_string(), ids: vec![2] });
+        assert!(execute(Command::Undo, &mut mgr).is_err());
+    }
+
+    #[test]
+    fn test_execute_returns_structured_output() {
+        let mut mgr = TaskManager::new();
+        let added = execute(parse("add Write report --tag work").unwrap(), &mut mgr).unwrap();
+        assert_eq!(added, CommandOutput::Added { id: 1, similar: Vec::new() });
+        assert_eq!(mgr.get_task(1).unwrap().tags, ["work".into()]);
+
+        assert_eq!(execute(parse("update 1 completed").unwrap(), &mut mgr).unwrap(), CommandOutput::Updated(1));
+        match execute(parse("status completed").unwrap(), &mut mgr).unwrap() {
+            CommandOutput::Tasks { view, tasks, .. } => {
+                assert_eq!(view, ListView::Status(TaskStatus::Completed));
+                assert_eq!(tasks.len(), 1);
+            }
+            other => panic!("unexpected output: {:?}", other),
+        }
+        assert_eq!(execute(parse("stats").unwrap(), &mut mgr).unwrap(), CommandOutput::Stats {
+            total: 1,
+            completed: 1,
+            in_progress: 0,
+            pending: 0,
+            inbox: 0,
+            pinned: 0,
+            goal: None,
+        });
+        assert!(matches!(execute(parse("show 9").unwrap(), &mut mgr), Err(TaskError::TaskNotFound { id: 9 })));
+    }
+
+    #[test]
+    fn test_add_warns_about_similar_tasks_and_dedupe_groups_them() {
+        let mut mgr = TaskManager::new();
+        execute(parse("add Fix login bug").unwrap(), &mut mgr).unwrap();
+        execute(parse("add Buy milk").unwrap(), &mut mgr).unwrap();
+        let added = execute(parse("add fix the login bug").unwrap(), &mut mgr).unwrap();
+        assert_eq!(added, CommandOutput::Added { id: 3, similar: vec![(1, "Fix login bug".to_string())] });
+        // Still added, but worth a second look
+        assert!(!added.is_confirmation());
+        assert_eq!(mgr.get_task(3).unwrap().title, "fix the login bug");
+
+        assert_eq!(parse("dedupe"), Ok(Command::Dedupe));
+        let clusters = execute(Command::Dedupe, &mut mgr).unwrap();
+        assert_eq!(clusters, CommandOutput::Duplicates(vec![vec![
+            (1, "Fix login bug".to_string()),
+            (3, "fix the login bug".to_string()),
+        ]]));
+    }
+
+    #[test]
+    fn test_bulk_commands_change_every_match() {
+        assert_eq!(parse("tag-all status:pending backend urgent --yes"), Ok(Command::Bulk {
+            filter: TaskFilter { status: Some(TaskStatus::Pending), keyword: Some("backend".to_string()), ..TaskFilter::default() },
+            change: BulkChange::Tag("urgent".to_string()),
+            confirmed: true,
+        }));
+        assert_eq!(parse("tag-all urgent"), Err(ParseError::Usage(TAG_ALL_USAGE)));
+        assert_eq!(parse("set-priority tag:ops urgent"), Err(ParseError::InvalidPriority("urgent".to_string())));
+        assert_eq!(parse("set-priority status:done high"), Err(ParseError::InvalidStatus("done".to_string())));
+
+        let mut mgr = TaskManager::new();
+        for title in ["Deploy api", "Deploy docs", "Write notes"] {
+            execute(parse(&format!("add {} --priority low", title)).unwrap(), &mut mgr).unwrap();
+        }
+        execute(parse("update 2 progress").unwrap(), &mut mgr).unwrap();
+        execute(parse("field 2 points 3").unwrap(), &mut mgr).unwrap();
+        let preview = execute(parse("set-priority deploy high").unwrap(), &mut mgr).unwrap();
+        let CommandOutput::BulkPreview { tasks, .. } = preview else { panic!("unexpected output: {:?}", preview) };
+        assert_eq!(tasks.iter().map(|task| task.id).collect::<Vec<_>>(), vec![1, 2]);
+        // Only listed so far
+        assert_eq!(mgr.get_task(1).unwrap().priority, Priority::Low);
+
+        execute(parse("set-priority points:3 high --yes").unwrap(), &mut mgr).unwrap();
+        let applied = execute(parse("set-priority deploy high --yes").unwrap(), &mut mgr).unwrap();
+        assert_eq!(applied, CommandOutput::BulkApplied {
+            change: BulkChange::Priority(Priority::High),
+            changed: vec![1],
+            unchanged: vec![2],
+            failed: Vec::new(),
+        });
+        assert_eq!(mgr.get_task(3).unwrap().priority, Priority::Low);
+        assert!(parse("tag-all deploy x --yes").unwrap().is_bulk());
+    }
+
+    #[test]
+    fn test_quick_tasks_wait_in_the_inbox() {
+        let mut mgr = TaskManager::new();
+        let mut config = Config::default();
+        config.default_priority = Priority::Low;
+        execute(parse("add Planned --priority high").unwrap(), &mut mgr).unwrap();
+        let quick = parse("quick buy stamps").unwrap().with_defaults(&config);
+        assert_eq!(execute(quick, &mut mgr).unwrap(), CommandOutput::Added { id: 2, similar: Vec::new() });
+        assert_eq!(mgr.get_task(2).unwrap().priority, Priority::Low);
+
+        match execute(parse("list --inbox").unwrap(), &mut mgr).unwrap() {
+            CommandOutput::Tasks { view: ListView::Inbox, tasks, .. } => assert_eq!(tasks[0].title, "buy stamps"),
+            other => panic!("unexpected output: {:?}", other),
+        }
+        assert!(matches!(execute(Command::Stats, &mut mgr).unwrap(), CommandOutput::Stats { total: 2, inbox: 1, .. }));
+    }
+
+    #[test]
+    fn test_pinned_tasks_come_first() {
+        let mut mgr = TaskManager::new();
+        for title in ["Taxes", "Groceries", "Call mum"] {
+            mgr.add_task(title.to_string(), String::new(), Priority::Medium).unwrap();
+        }
+        assert_eq!(execute(parse("pin 3").unwrap(), &mut mgr).unwrap(), CommandOutput::Pinned {
+            id: 3,
+            pinned: true,
+            completed: false,
+        });
+        mgr.update_task_status(1, TaskStatus::Completed).unwrap();
+        // Pinning a finished task still works, with a warning
+        let done = execute(parse("pin 1").unwrap(), &mut mgr).unwrap();
+        assert_eq!(done, CommandOutput::Pinned { id: 1, pinned: true, completed: true });
+        assert!(!done.is_confirmation());
+
+        let ids = |output: CommandOutput| -> Vec<u32> {
+            let CommandOutput::Tasks { tasks, .. } = output else {
+                panic!("unexpected output: {:?}", output);
+            };
+            tasks.iter().map(|task| task.id).collect()
+        };
+        assert_eq!(ids(execute(parse("list").unwrap(), &mut mgr).unwrap()), vec![1, 3, 2]);
+        assert_eq!(ids(execute(parse("list --sort title").unwrap(), &mut mgr).unwrap()), vec![3, 1, 2]);
+        assert_eq!(ids(execute(parse("pinned").unwrap(), &mut mgr).unwrap()), vec![1, 3]);
+        execute(parse("unpin 1").unwrap(), &mut mgr).unwrap();
+        assert!(matches!(execute(Command::Stats, &mut mgr).unwrap(), CommandOutput::Stats { pinned: 1, .. }));
+    }
+
+    #[test]
+    fn test_move_sets_the_manual_order() {
+        let mut mgr = TaskManager::new();
+        for title in ["Taxes", "Groceries", "Call mum"] {
+            mgr.add_task(title.to_string(), String::new(), Priority::Medium).unwrap();
+        }
+        assert_eq!(parse("move 3 before 1"), Ok(Command::Move { id: 3, place: Placement::Before, other: 1 }));
+        assert_eq!(parse("move 3 above 1"), Err(ParseError::Usage(MOVE_USAGE)));
+        assert_eq!(parse("move 3 after x"), Err(ParseError::InvalidId("x".to_string())));
+        assert_eq!(parse("move 3"), Err(ParseError::Usage(MOVE_USAGE)));
+
+        assert_eq!(execute(parse("move 3 before 1").unwrap(), &mut mgr).unwrap(), CommandOutput::Updated(3));
+        execute(parse("move 1 after 2").unwrap(), &mut mgr).unwrap();
+        let CommandOutput::Tasks { tasks, .. } = execute(parse("list --sort manual").unwrap(), &mut mgr).unwrap() else {
+            panic!("expected a task list");
+        };
+        assert_eq!(tasks.iter().map(|task| task.id).collect::<Vec<_>>(), vec![3, 2, 1]);
+        let CommandOutput::Board(board) = execute(parse("board --sort manual").unwrap(), &mut mgr).unwrap() else {
+            panic!("expected a board");
+        };
+        assert_eq!(board.columns[0].tasks.iter().map(|task| task.id).collect::<Vec<_>>(), vec![3, 2, 1]);
+    }
+
+    #[test]
+    fn test_filter_lists_best_matches_first() {
+        let mut mgr = TaskManager::new();
+        for title in ["Read the report", "Report", "Reporting tool"] {
+            mgr.add_task(title.to_string(), String::new(), Priority::Medium).unwrap();
+        }
+        let CommandOutput::Matches { hits, .. } = execute(parse("filter report").unwrap(), &mut mgr).unwrap() else {
+            panic!("expected matches");
+        };
+        // In id order with their scores; the renderer ranks them
+        assert_eq!(hits.iter().map(|(score, task)| (task.id, *score)).collect::<Vec<_>>(), vec![
+            (1, 60),
+            (2, 100),
+            (3, 80)
+        ]);
+
+        let CommandOutput::Tasks { tasks, .. } = execute(parse("filter report --sort id").unwrap(), &mut mgr).unwrap()
+        else {
+            panic!("expected a task list");
+        };
+        assert_eq!(tasks.iter().map(|task| task.id).collect::<Vec<_>>(), vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn test_saved_views() {
+        let mut mgr = TaskManager::new();
+        let tasks = [
+            ("Fix login", Priority::Critical, "backend"),
+            ("Tune cache", Priority::Medium, "backend"),
+            ("Logo", Priority::Low, "design"),
+        ];
+        for (title, priority, tag) in tasks {
+            let id = mgr.add_task(title.to_string(), String::new(), priority).unwrap();
+            mgr.add_tag_to_task(id, tag.to_string()).unwrap();
+        }
+        mgr.add_tag_to_task(2, "blocked".to_string()).unwrap();
+        let ids = |output: CommandOutput| -> Vec<u32> {
+            let CommandOutput::Tasks { view: ListView::View(_), tasks, .. } = output else {
+                panic!("unexpected output: {:?}", output);
+            };
+            tasks.iter().map(|task| task.id).collect()
+        };
+
+        let save = parse("view save Backend status:pending tag:backend -tag:blocked").unwrap();
+        let saved = |replaced| CommandOutput::ViewSaved { name: "backend".to_string(), replaced };
+        assert_eq!(execute(save, &mut mgr).unwrap(), saved(false));
+        assert_eq!(ids(execute(parse("view backend").unwrap(), &mut mgr).unwrap()), vec![1]);
+        assert_eq!(ids(execute(parse("view backend priority:low").unwrap(), &mut mgr).unwrap()), Vec::<u32>::new());
+
+        // Overwriting needs --force, and command names are taken
+        let again = parse("view save backend tag:backend").unwrap();
+        assert!(matches!(execute(again, &mut mgr), Err(TaskError::Invalid { .. })));
+        let forced = parse("view save backend tag:backend --force").unwrap();
+        assert_eq!(execute(forced, &mut mgr).unwrap(), saved(true));
+        assert_eq!(ids(execute(parse("view backend").unwrap(), &mut mgr).unwrap()), vec![1, 2]);
+        assert!(matches!(execute(parse("view save stats tag:x").unwrap(), &mut mgr), Err(TaskError::Invalid { .. })));
+        assert_eq!(parse("view save mine priority:urgent"), Err(ParseError::InvalidPriority("urgent".to_string())));
+        assert_eq!(parse("view save mine"), Err(ParseError::Usage(VIEW_USAGE)));
+
+        execute(parse("view save design tag:design").unwrap(), &mut mgr).unwrap();
+        let onto = parse("view rename design backend").unwrap();
+        assert!(matches!(execute(onto, &mut mgr), Err(TaskError::Invalid { .. })));
+        execute(parse("view rename design art").unwrap(), &mut mgr).unwrap();
+        let Ok(CommandOutput::Views(views)) = execute(Command::ViewList, &mut mgr) else {
+            panic!("view list failed");
+        };
+        let listed: Vec<(&str, usize)> = views.iter().map(|view| (view.name.as_str(), view.count)).collect();
+        assert_eq!(listed, vec![("art", 1), ("backend", 2)]);
+
+        execute(parse("view delete art").unwrap(), &mut mgr).unwrap();
+        assert!(matches!(execute(parse("view art").unwrap(), &mut mgr), Err(TaskError::Invalid { .. })));
+        assert_eq!(mgr.views().keys().collect::<Vec<_>>(), vec!["backend"]);
+    }
+
+    #[test]
+    fn test_view_changes_are_previewed_in_dry_run() {
+        let mut mgr = TaskManager::new();
+        let output = execute_in(RunMode::DryRun, parse("view save open status:pending").unwrap(), &mut mgr).unwrap();
+        let CommandOutput::DryRun { changes, .. } = output else {
+            panic!("unexpected output: {:?}", output);
+        };
+        assert_eq!(changes[0].event, TaskEvent::ViewChanged { name: "open".to_string() });
+        assert!(mgr.views().is_empty());
+    }
+
+    #[test]
+    fn test_review_list_shows_stale_tasks() {
+        assert_eq!(parse("review --list"), Ok(Command::Review { days: review::DEFAULT_DAYS }));
+        assert_eq!(parse("review --days 30 --list"), Ok(Command::Review { days: 30 }));
+        assert_eq!(parse("review --days 0"), Err(ParseError::Usage(REVIEW_USAGE)));
+        assert_eq!(parse("review soon"), Err(ParseError::Usage(REVIEW_USAGE)));
+
+        let mut mgr = TaskManager::new();
+        let mut task = Task::new(0, "Sort photos".to_string(), String::new(), Priority::Low);
+        task.updated_at = Utc::now() - chrono::TimeDelta::days(20);
+        mgr.create_task(task).unwrap();
+        mgr.add_task("Fresh".to_string(), String::new(), Priority::Low).unwrap();
+        let CommandOutput::Stale { tasks, .. } = execute(parse("review --list").unwrap(), &mut mgr).unwrap() else {
+            panic!("expected the stale list")
+        };
+        assert_eq!(tasks.iter().map(|task| task.title.as_str()).collect::<Vec<_>>(), ["Sort photos"]);
+    }
+
+    #[test]
+    fn test_tag_registry_commands() {
+        assert_eq!(
+            parse("tag-describe backend Server side --color blue"),
+            Ok(Command::TagDescribe { tag: "backend".to_string(), description: "Server side".to_string(), color: Some("blue".to_string()) })
+        );
+        assert_eq!(parse("tag-describe backend"), Err(ParseError::Usage(TAG_DESCRIBE_USAGE)));
+        assert_eq!(parse("tags --unused --long"), Ok(Command::Tags { long: true, unused: true }));
+        assert_eq!(parse("tags --all"), Err(ParseError::Usage(TAGS_USAGE)));
+        assert_eq!(parse("import tags t.toml --replace"), Ok(Command::ImportTags { path: PathBuf::from("t.toml"), replace: true }));
+        assert_eq!(parse("import github"), Err(ParseError::Usage(IMPORT_USAGE)));
+
+        let mut mgr = TaskManager::new();
+        let id = mgr.add_task("Fix login".to_string(), String::new(), Priority::High).unwrap();
+        mgr.add_tag_to_task(id, "backend".to_string()).unwrap();
+        execute(parse("tag-describe Backend Server side").unwrap(), &mut mgr).unwrap();
+        execute(parse("tag-describe ops Deploys").unwrap(), &mut mgr).unwrap();
+        let CommandOutput::Tags { rows, .. } = execute(parse("tags --unused").unwrap(), &mut mgr).unwrap() else {
+            panic!("expected tags")
+        };
+        assert_eq!(rows.iter().map(|row| row.tag.as_str()).collect::<Vec<_>>(), ["ops"]);
+
+        let path = std::env::temp_dir().join(format!("task-manager-tags-{}.json", std::process::id()));
+        let export = Command::ExportTags { path: path.clone() };
+        assert_eq!(execute(export, &mut mgr).unwrap(), CommandOutput::TagsExported { path: path.clone(), count: 2 });
+        let mut other = TaskManager::new();
+        other.describe_tag("design", "Mockups", None).unwrap();
+        let CommandOutput::TagsImported(report) = execute(Command::ImportTags { path: path.clone(), replace: false }, &mut other).unwrap() else {
+            panic!("expected an import report")
+        };
+        assert_eq!(report.added, ["backend", "ops"]);
+        assert_eq!(other.tag_registry().len(), 3);
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_update_follows_the_workflow() {
+        use crate::render::{HumanRenderer, Renderer};
+        use crate::workflow::{Requirement, Workflow};
+
+        assert_eq!(
+            parse("update 1 pending --note customer found a regression"),
+            Ok(Command::Update { id: 1, status: TaskStatus::Pending, note: Some("customer found a regression".to_string()) })
+        );
+        assert_eq!(parse("update 1 pending --note"), Err(ParseError::Usage(UPDATE_USAGE)));
+
+        let mut workflow = Workflow::default();
+        workflow.allow(TaskStatus::Pending, vec![TaskStatus::InProgress]);
+        workflow.require(TaskStatus::Completed, TaskStatus::Pending, Requirement::Note);
+        let mut mgr = TaskManager::new();
+        mgr.set_workflow(workflow);
+        let id = mgr.add_task("Ship release".to_string(), String::new(), Priority::High).unwrap();
+
+        let err = execute(parse("update 1 completed").unwrap(), &mut mgr).unwrap_err();
+        assert_eq!(
+            HumanRenderer.render_task_error(&err),
+            "Error: Task cannot move from Pending to Completed. Allowed next: progress."
+        );
+        execute(parse("update 1 progress").unwrap(), &mut mgr).unwrap();
+        execute(parse("update 1 completed").unwrap(), &mut mgr).unwrap();
+        assert!(matches!(execute(parse("update 1 pending").unwrap(), &mut mgr), Err(TaskError::ValidationFailed(_))));
+        execute(parse("update 1 pending --note customer found a regression").unwrap(), &mut mgr).unwrap();
+        let task = mgr.get_task(id).unwrap();
+        assert_eq!((&task.status, task.description.as_str()), (&TaskStatus::Pending, "customer found a regression"));
+    }
+
+    #[test]
+    fn test_show_lists_related_tasks() {
+        assert_eq!(parse("relate 3 12"), Ok(Command::Relate { id: 3, other: 12, related: true }));
+        assert_eq!(parse("unrelate 3 12"), Ok(Command::Relate { id: 3, other: 12, related: false }));
+        assert_eq!(parse("relate 3"), Err(ParseError::Usage(RELATE_USAGE)));
+        assert_eq!(parse("relate 3 x"), Err(ParseError::InvalidId("x".to_string())));
+
+        let mut mgr = TaskManager::new();
+        for title in ["Launch", "Copy"] {
+            mgr.add_task(title.to_string(), String::new(), Priority::Medium).unwrap();
+        }
+        let output = execute(parse("relate 1 2").unwrap(), &mut mgr).unwrap();
+        assert_eq!(output, CommandOutput::Related { id: 1, other: 2, related: true, changed: true });
+        for id in [1, 2] {
+            let CommandOutput::Task { related, .. } = execute(parse(&format!("show {}", id)).unwrap(), &mut mgr).unwrap() else {
+                panic!("expected a task");
+            };
+            assert_eq!(related.iter().map(|task| task.id).collect::<Vec<_>>(), [3 - id]);
+        }
+        let CommandOutput::Graph(dot) = execute(parse("graph").unwrap(), &mut mgr).unwrap() else { panic!("expected a graph") };
+        assert!(dot.contains("1 -> 2 [dir=none, style=dashed];"));
+    }
+
+    #[test]
+    fn test_priority_set_changes_only_what_differs() {
+        let mut mgr = TaskManager::new();
+        for (title, priority) in [("Outage review", Priority::Medium), ("Patch", Priority::High), ("Docs", Priority::Low)] {
+            mgr.add_task(title.to_string(), String::new(), priority).unwrap();
+        }
+        // One unknown id and nothing changes
+        assert!(matches!(execute(parse("priority-set 1,9 high").unwrap(), &mut mgr), Err(TaskError::TaskNotFound { id: 9 })));
+        assert_eq!(mgr.get_task(1).unwrap().priority, Priority::Medium);
+
+        let output = execute(parse("priority-set 1-3 high").unwrap(), &mut mgr).unwrap();
+        assert_eq!(output, CommandOutput::PrioritySet {
+            priority: Priority::High,
+            changed: vec![(1, Priority::Medium), (3, Priority::Low)],
+            unchanged: vec![2],
+        });
+        assert!(output.is_confirmation());
+        assert!(mgr.list_tasks().iter().all(|task| task.priority == Priority::High));
+    }
+
+    #[test]
+    fn test_due_soon_lists_dated_open_tasks_most_pressed_first() {
+        assert_eq!(parse("due-soon"), Ok(Command::DueSoon { days: DEFAULT_DUE_SOON_DAYS }));
+        assert_eq!(parse("due-soon --days 3"), Ok(Command::DueSoon { days: 3 }));
+        assert_eq!(parse("due-soon 3"), Err(ParseError::Usage(DUE_SOON_USAGE)));
+        assert_eq!(parse("due-soon --days soon"), Err(ParseError::Usage(DUE_SOON_USAGE)));
+
+        let mut mgr = TaskManager::new();
+        let today = Local::now().date_naive();
+        for (title, due) in [("Slides", Some(5)), ("Report", Some(-1)), ("Budget", Some(12)), ("Someday", None)] {
+            let id = mgr.add_task(title.to_string(), String::new(), Priority::Medium).unwrap();
+            let due = due.map(|days| today + chrono::TimeDelta::days(days));
+            mgr.apply_edit(id, TaskEdit { due, ..TaskEdit::default() }).unwrap();
+        }
+        let output = execute(parse("due-soon").unwrap(), &mut mgr).unwrap();
+        let CommandOutput::Tasks { view, tasks, .. } = output else { panic!("unexpected output: {:?}", output) };
+        assert_eq!(view, ListView::DueSoon(7));
+        assert_eq!(tasks.iter().map(|task| task.title.as_str()).collect::<Vec<_>>(), ["Report", "Slides"]);
+        // Overdue tasks stay in however short the window
+        let CommandOutput::Tasks { tasks, .. } = execute(parse("due-soon --days 0").unwrap(), &mut mgr).unwrap() else {
+            panic!("expected tasks");
+        };
+        assert_eq!(tasks.iter().map(|task| task.id).collect::<Vec<_>>(), [2]);
+    }
+
+    #[test]
+    fn test_can_do_lists_what_fits_the_energy_left() {
+        assert_eq!(parse("energy 3 hard"), Ok(Command::Energy { id: 3, energy: Some(Energy::High) }));
+        assert_eq!(parse("energy 3 none"), Ok(Command::Energy { id: 3, energy: None }));
+        assert_eq!(parse("energy 3"), Err(ParseError::Usage(ENERGY_USAGE)));
+        assert_eq!(parse("can-do"), Err(ParseError::Usage(CAN_DO_USAGE)));
+        assert_eq!(parse("next --energy low"), Ok(Command::Next { energy: Some(Energy::Low) }));
+        assert_eq!(parse("next --energy"), Err(ParseError::Usage(NEXT_USAGE)));
+
+        let mut mgr = TaskManager::new();
+        let today = Local::now().date_naive();
+        for (title, priority, energy) in [
+            ("File receipts", Priority::Low, Some("easy")),
+            ("Reply to Sam", Priority::High, Some("low")),
+            ("Write design doc", Priority::Critical, Some("hard")),
+            ("Call bank", Priority::Medium, Some("medium")),
+            ("Water plants", Priority::Medium, None),
+            ("Chase invoice", Priority::High, Some("low")),
+            ("Sort photos", Priority::Medium, Some("low")),
+        ] {
+            let id = mgr.add_task(title.to_string(), String::new(), priority).unwrap();
+            if let Some(level) = energy {
+                execute(parse(&format!("energy {} {}", id, level)).unwrap(), &mut mgr).unwrap();
+            }
+        }
+        mgr.wait_on(6, "Finance".to_string(), today, None).unwrap();
+        mgr.snooze_task(7, today + chrono::TimeDelta::days(2)).unwrap();
+
+        let titles = |output: CommandOutput| match output {
+            CommandOutput::Tasks { tasks, .. } => tasks.into_iter().map(|task| task.title).collect::<Vec<_>>(),
+            other => panic!("expected tasks, got {:?}", other),
+        };
+        assert_eq!(titles(execute(parse("can-do low").unwrap(), &mut mgr).unwrap()), ["Reply to Sam", "File receipts"]);
+        assert_eq!(
+            titles(execute(parse("can-do medium").unwrap(), &mut mgr).unwrap()),
+            ["Reply to Sam", "Call bank", "File receipts"]
+        );
+        assert_eq!(titles(execute(parse("can-do high").unwrap(), &mut mgr).unwrap()).len(), 5);
+        let next = execute(parse("next --energy low").unwrap(), &mut mgr).unwrap();
+        assert!(matches!(next, CommandOutput::Next(Some(summary::Next { id: 2, .. }))));
+    }
+}
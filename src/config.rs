@@ -0,0 +1,1046 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
+
+use chrono::NaiveDate;
+use toml::{Table, Value};
+
+use crate::{Priority, TaskStatus, dates};
+use crate::audit::DEFAULT_AUDIT_LIMIT;
+use crate::digest::DEFAULT_DIGEST_LIMIT;
+use crate::effort::DEFAULT_FORECAST_WEEKS;
+use crate::hooks::{DEFAULT_HOOK_TIMEOUT_SECS, HookConfig, HookEvent};
+use crate::matrix::DEFAULT_URGENT_DAYS;
+use crate::pressure::DEFAULT_ESTIMATE;
+use crate::journal::DEFAULT_COMPACT_AFTER;
+use crate::render::OutputFormat;
+use crate::retention::{self, RetentionPolicy};
+use crate::storage::StoreFormat;
+use crate::sync::ConflictPolicy;
+use crate::table::{self, COLUMN_NAMES, Column};
+use crate::validation::{Limits, RequiredFields};
+use crate::webhook::{WebhookConfig, WebhookEvent};
+use crate::workflow::{Requirement, Workflow};
+
+const DEFAULT_AUTOSAVE_MAX_CHANGES: usize = 100;
+// notify_within_hours looks ahead a year at most
+const MAX_NOTIFY_WITHIN_HOURS: u32 = 366 * 24;
+
+const WEEKDAYS: [&str; 7] = ["monday", "tuesday", "wednesday", "thursday", "friday", "saturday", "sunday"];
+
+// Where the effective value of a setting came from
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ConfigSource {
+    Default,
+    File,
+    Flag,
+}
+
+impl fmt::Display for ConfigSource {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConfigSource::Default => write!(f, "default"),
+            ConfigSource::File => write!(f, "file"),
+            ConfigSource::Flag => write!(f, "flag"),
+        }
+    }
+}
+
+// User preferences; every key is optional in the file
+#[derive(Debug, Clone, PartialEq)]
+pub struct Config {
+    pub data_path: PathBuf,
+    // Append changes to a log beside the data file instead of rewriting it each time
+    pub journal: bool,
+    pub journal_compact_after: usize,
+    // Hold the REPL's changes until it has been quiet this long (0 saves after
+    // every command), or until this many have piled up
+    pub autosave_delay_ms: u64,
+    pub autosave_max_changes: usize,
+    // Encoding used when the data file is written
+    pub store_format: StoreFormat,
+    pub default_priority: Priority,
+    pub format: OutputFormat,
+    pub color: bool,
+    pub confirm: bool,
+    // strftime format for dates shown in lists, `show` and the HTML export
+    pub date_format: String,
+    // Print dates as YYYY-MM-DD instead of "in 3 days"
+    pub absolute_dates: bool,
+    pub first_day_of_week: String,
+    // Message catalog language such as "es"; falls back to $LANG when unset
+    pub language: Option<String>,
+    // Validation rules; all off by default
+    pub require_tag: bool,
+    pub require_due_for_priority: Option<Priority>,
+    pub max_open_tasks: Option<usize>,
+    // `[require]`: fields `add` will not go without
+    pub required: RequiredFields,
+    // Due-date reminders: checked at startup when enabled, and by `notify`
+    pub notify_on_startup: bool,
+    pub notify_within_hours: u32,
+    // Print how many tasks each saved view finds when the REPL starts
+    pub views_on_startup: bool,
+    // Leave out the summary of open, overdue and due tasks when the REPL starts
+    pub quiet_start: bool,
+    // Ring the terminal bell as well when a task becomes overdue at the prompt
+    pub bell: bool,
+    // `source` undoes the whole script when a line fails, as with --transactional
+    pub source_transactional: bool,
+    // Go back into the view active when the prompt was last left without asking
+    pub resume_view: bool,
+    // `filter` treats "cafe" and "café" alike; off to match diacritics exactly
+    pub ignore_accents: bool,
+    // Changing a completed task asks first at the prompt and fails elsewhere unless --force
+    pub guard_completed: bool,
+    // Tags must be in the tag registry (see `tag-describe`) before a task can carry them
+    pub strict_tags: bool,
+    // Who this process acts as, recorded on new tasks and in the audit log;
+    // None uses $USER. `--as <name>` overrides it.
+    pub user: Option<String>,
+    // Changing or deleting a task someone else created fails unless --force
+    pub protect_others_tasks: bool,
+    // `[transitions]` and `[[transition_requires]]`: the status changes allowed and
+    // what they need; every change is allowed by default
+    pub workflow: Workflow,
+    // `[limits]`: the most description, notes and tags one task may have; no limit by default
+    pub limits: Limits,
+    // `[retention]`: when completed tasks move to the archive and archived ones are
+    // purged, checked once a day at startup; off by default
+    pub retention: RetentionPolicy,
+    // How long the reminder daemon sleeps between checks
+    pub daemon_interval_minutes: u64,
+    // `[[webhooks]]` tables: url plus the events to post
+    pub webhooks: Vec<WebhookConfig>,
+    // `[[hooks]]` tables: an event plus the command to run on it
+    pub hooks: Vec<HookConfig>,
+    // Seconds a hook may run before it is killed
+    pub hook_timeout_secs: u64,
+    // Audit log entries kept before the oldest are dropped
+    pub audit_limit: usize,
+    // Base URL of the `serve` instance that `sync` talks to
+    pub sync_url: Option<String>,
+    // Bearer token `serve` requires and `sync` sends; no check when unset
+    pub api_token: Option<String>,
+    pub sync_conflicts: ConflictPolicy,
+    // Days `due <id> <n> business days` skips besides weekends
+    pub holidays: Vec<NaiveDate>,
+    // Business-day mode: `recur-preview` flags dates on weekends and holidays
+    pub business_days: bool,
+    // Days ahead a due date puts a task in the urgent half of `matrix`
+    pub urgent_days: u32,
+    // Columns `list` shows as a table; empty keeps the compact list
+    pub list_columns: Vec<Column>,
+    // How long a task without an `estimate` field takes, for the deadline pressure
+    // marker and `stats --weighted`
+    pub default_estimate: Duration,
+    // Weeks of completed work `stats --weighted` takes its pace from
+    pub forecast_weeks: u32,
+    // Items per `digest` section, and the addresses written with --headers
+    pub digest_limit: usize,
+    pub digest_from: Option<String>,
+    pub digest_to: Option<String>,
+    // The file the settings were read from, if any
+    pub path: Option<PathBuf>,
+    sources: BTreeMap<&'static str, ConfigSource>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            data_path: PathBuf::from("tasks.json"),
+            journal: false,
+            journal_compact_after: DEFAULT_COMPACT_AFTER,
+            autosave_delay_ms: 0,
+            autosave_max_changes: DEFAULT_AUTOSAVE_MAX_CHANGES,
+            store_format: StoreFormat::Json,
+            default_priority: Priority::Medium,
+            format: OutputFormat::Human,
+            color: true,
+            confirm: true,
+            date_format: "%Y-%m-%d".to_string(),
+            absolute_dates: false,
+            first_day_of_week: "monday".to_string(),
+            language: None,
+            require_tag: false,
+            require_due_for_priority: None,
+            max_open_tasks: None,
+            required: RequiredFields::default(),
+            notify_on_startup: false,
+            views_on_startup: false,
+            quiet_start: false,
+            bell: false,
+            source_transactional: false,
+            resume_view: false,
+            ignore_accents: true,
+            guard_completed: true,
+            strict_tags: false,
+            user: None,
+            protect_others_tasks: false,
+            workflow: Workflow::default(),
+            limits: Limits::default(),
+            retention: RetentionPolicy::default(),
+            notify_within_hours: 0,
+            daemon_interval_minutes: 5,
+            webhooks: Vec::new(),
+            hooks: Vec::new(),
+            hook_timeout_secs: DEFAULT_HOOK_TIMEOUT_SECS,
+            audit_limit: DEFAULT_AUDIT_LIMIT,
+            sync_url: None,
+            api_token: None,
+            sync_conflicts: ConflictPolicy::Ask,
+            holidays: Vec::new(),
+            business_days: false,
+            urgent_days: DEFAULT_URGENT_DAYS,
+            list_columns: Vec::new(),
+            default_estimate: DEFAULT_ESTIMATE,
+            forecast_weeks: DEFAULT_FORECAST_WEEKS,
+            digest_limit: DEFAULT_DIGEST_LIMIT,
+            digest_from: None,
+            digest_to: None,
+            path: None,
+            sources: BTreeMap::new(),
+        }
+    }
+}
+
+impl Config {
+    // $XDG_CONFIG_HOME/task-manager/config.toml, else ~/.config/task-manager/config.toml
+    pub fn default_path() -> Option<PathBuf> {
+        let base = match std::env::var_os("XDG_CONFIG_HOME") {
+            Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+            _ => PathBuf::from(std::env::var_os("HOME")?).join(".config"),
+        };
+        Some(base.join("task-manager").join("config.toml"))
+    }
+
+    // Load from an explicit path, or the default location when None.
+    // Never fails: problems are returned as warnings and the affected keys keep their defaults.
+    pub fn load(explicit: Option<&Path>) -> (Config, Vec<String>) {
+        let path = match explicit.map(Path::to_path_buf).or_else(Config::default_path) {
+            Some(path) => path,
+            None => return (Config::default(), Vec::new()),
+        };
+
+        match fs::read_to_string(&path) {
+            Ok(contents) => {
+                let (mut config, warnings) = Config::parse(&contents);
+                config.path = Some(path);
+                (config, warnings)
+            }
+            Err(e) if e.kind() == ErrorKind::NotFound && explicit.is_none() => (Config::default(), Vec::new()),
+            Err(e) => (Config::default(), vec![format!("could not read {}: {}", path.display(), e)]),
+        }
+    }
+
+    pub fn parse(contents: &str) -> (Config, Vec<String>) {
+        let mut config = Config::default();
+        let mut warnings = Vec::new();
+
+        let table = match contents.parse::<Table>() {
+            Ok(table) => table,
+            Err(e) => {
+                warnings.push(format!("config file is not valid TOML, using defaults: {}", e.message()));
+                return (config, warnings);
+            }
+        };
+
+        for (key, value) in &table {
+            // `[defaults]`, `[require]`, `[limits]` and `[retention]` group keys that are read as
+            // defaults.priority and so on
+            let entries: Vec<(String, &Value)> = match (key.as_str(), value.as_table()) {
+                ("defaults" | "require" | "limits" | "retention", Some(group)) => {
+                    group.iter().map(|(name, value)| (format!("{}.{}", key, name), value)).collect()
+                }
+                _ => vec![(key.clone(), value)],
+            };
+            for (key, value) in entries {
+                if let Err(e) = config.apply(&key, value) {
+                    warnings.push(format!("config key '{}': {}", key, e));
+                }
+            }
+        }
+        (config, warnings)
+    }
+
+    fn apply(&mut self, key: &str, value: &Value) -> Result<(), String> {
+        let key: &'static str = match key {
+            "data_path" => {
+                self.data_path = PathBuf::from(expect_str(value)?);
+                "data_path"
+            }
+            "journal" => {
+                self.journal = expect_bool(value)?;
+                "journal"
+            }
+            "journal_compact_after" => {
+                match expect_count(value)? {
+                    0 => return Err("expected at least 1 record".to_string()),
+                    limit => self.journal_compact_after = limit,
+                }
+                "journal_compact_after"
+            }
+            "autosave_delay_ms" => {
+                self.autosave_delay_ms = expect_count(value)? as u64;
+                "autosave_delay_ms"
+            }
+            "autosave_max_changes" => {
+                match expect_count(value)? {
+                    0 => return Err("expected at least 1 change".to_string()),
+                    limit => self.autosave_max_changes = limit,
+                }
+                "autosave_max_changes"
+            }
+            "store_format" => {
+                self.store_format = parse_store_format(expect_str(value)?)?;
+                "store_format"
+            }
+            "default_priority" | "defaults.priority" => {
+                self.default_priority = Priority::from_str(expect_str(value)?)
+                    .map_err(|_| "expected one of low, medium, high, critical".to_string())?;
+                "default_priority"
+            }
+            "format" => {
+                self.format = OutputFormat::from_str(expect_str(value)?)
+                    .map_err(|_| "expected human or json".to_string())?;
+                "format"
+            }
+            "color" => {
+                self.color = expect_bool(value)?;
+                "color"
+            }
+            "confirm" => {
+                self.confirm = expect_bool(value)?;
+                "confirm"
+            }
+            "date_format" => {
+                let format = expect_str(value)?;
+                dates::check_date_format(format)?;
+                self.date_format = format.to_string();
+                "date_format"
+            }
+            "absolute_dates" => {
+                self.absolute_dates = expect_bool(value)?;
+                "absolute_dates"
+            }
+            "first_day_of_week" => {
+                let day = expect_str(value)?.to_lowercase();
+                if !WEEKDAYS.contains(&day.as_str()) {
+                    return Err("expected a weekday name such as monday".to_string());
+                }
+                self.first_day_of_week = day;
+                "first_day_of_week"
+            }
+            "language" => {
+                self.language = Some(expect_str(value)?.to_lowercase());
+                "language"
+            }
+            "require_tag" => {
+                self.require_tag = expect_bool(value)?;
+                "require_tag"
+            }
+            "require_due_for_priority" => {
+                self.require_due_for_priority = Some(
+                    Priority::from_str(expect_str(value)?)
+                        .map_err(|_| "expected one of low, medium, high, critical".to_string())?,
+                );
+                "require_due_for_priority"
+            }
+            "max_open_tasks" => {
+                self.max_open_tasks = Some(expect_count(value)?);
+                "max_open_tasks"
+            }
+            "require.due_date" => {
+                self.required.due_date = expect_bool(value)?;
+                "require.due_date"
+            }
+            "require.tags" => {
+                self.required.tags = expect_bool(value)?;
+                "require.tags"
+            }
+            "require.description" => {
+                self.required.description = expect_bool(value)?;
+                "require.description"
+            }
+            "limits.description_chars" => {
+                self.limits.description_chars = Some(expect_count(value)?);
+                "limits.description_chars"
+            }
+            "limits.notes_per_task" => {
+                self.limits.notes_per_task = Some(expect_count(value)?);
+                "limits.notes_per_task"
+            }
+            "limits.tags_per_task" => {
+                self.limits.tags_per_task = Some(expect_count(value)?);
+                "limits.tags_per_task"
+            }
+            "retention.archive_completed_after" => {
+                self.retention.archive_completed_after = Some(expect_age(value)?);
+                "retention.archive_completed_after"
+            }
+            "retention.purge_archived_after" => {
+                self.retention.purge_archived_after = Some(expect_age(value)?);
+                "retention.purge_archived_after"
+            }
+            "notify_on_startup" => {
+                self.notify_on_startup = expect_bool(value)?;
+                "notify_on_startup"
+            }
+            "views_on_startup" => {
+                self.views_on_startup = expect_bool(value)?;
+                "views_on_startup"
+            }
+            "quiet_start" => {
+                self.quiet_start = expect_bool(value)?;
+                "quiet_start"
+            }
+            "bell" => {
+                self.bell = expect_bool(value)?;
+                "bell"
+            }
+            "source_transactional" => {
+                self.source_transactional = expect_bool(value)?;
+                "source_transactional"
+            }
+            "resume_view" => {
+                self.resume_view = expect_bool(value)?;
+                "resume_view"
+            }
+            "ignore_accents" => {
+                self.ignore_accents = expect_bool(value)?;
+                "ignore_accents"
+            }
+            "guard_completed" => {
+                self.guard_completed = expect_bool(value)?;
+                "guard_completed"
+            }
+            "strict_tags" => {
+                self.strict_tags = expect_bool(value)?;
+                "strict_tags"
+            }
+            "user" => {
+                self.user = Some(expect_str(value)?.trim().to_string()).filter(|user| !user.is_empty());
+                "user"
+            }
+            "protect_others_tasks" => {
+                self.protect_others_tasks = expect_bool(value)?;
+                "protect_others_tasks"
+            }
+            // pending = ["progress"] allows only that move out of pending
+            "transitions" => {
+                let table = value
+                    .as_table()
+                    .ok_or_else(|| format!("expected a table of status lists, found {}", value.type_str()))?;
+                for (from, to) in table {
+                    let to = to.as_array().ok_or_else(|| format!("{}: expected a list of statuses", from))?;
+                    let to = to.iter().map(|status| expect_str(status).and_then(parse_status)).collect::<Result<_, _>>()?;
+                    self.workflow.allow(parse_status(from)?, to);
+                }
+                "transitions"
+            }
+            "transition_requires" => {
+                let entries = value
+                    .as_array()
+                    .ok_or_else(|| format!("expected a list of tables, found {}", value.type_str()))?;
+                for entry in entries {
+                    let (from, to, requirement) = parse_transition_requirement(entry)?;
+                    self.workflow.require(from, to, requirement);
+                }
+                "transition_requires"
+            }
+            "notify_within_hours" => {
+                match expect_count(value)? {
+                    hours if hours > MAX_NOTIFY_WITHIN_HOURS as usize => {
+                        return Err(format!("expected at most {} hours (a year)", MAX_NOTIFY_WITHIN_HOURS));
+                    }
+                    hours => self.notify_within_hours = hours as u32,
+                }
+                "notify_within_hours"
+            }
+            "daemon_interval_minutes" => {
+                match expect_count(value)? {
+                    0 => return Err("expected at least 1 minute".to_string()),
+                    minutes if (minutes as u64).checked_mul(60).is_none() => {
+                        return Err(format!("expected at most {} minutes", u64::MAX / 60));
+                    }
+                    minutes => self.daemon_interval_minutes = minutes as u64,
+                }
+                "daemon_interval_minutes"
+            }
+            "webhooks" => {
+                let entries = value
+                    .as_array()
+                    .ok_or_else(|| format!("expected a list of tables, found {}", value.type_str()))?;
+                self.webhooks = entries.iter().map(parse_webhook).collect::<Result<_, _>>()?;
+                "webhooks"
+            }
+            "hooks" => {
+                let entries = value
+                    .as_array()
+                    .ok_or_else(|| format!("expected a list of tables, found {}", value.type_str()))?;
+                self.hooks = entries.iter().map(parse_hook).collect::<Result<_, _>>()?;
+                "hooks"
+            }
+            "hook_timeout_secs" => {
+                match expect_count(value)? {
+                    0 => return Err("expected at least 1 second".to_string()),
+                    secs => self.hook_timeout_secs = secs as u64,
+                }
+                "hook_timeout_secs"
+            }
+            "audit_limit" => {
+                match expect_count(value)? {
+                    0 => return Err("expected at least 1 entry".to_string()),
+                    limit => self.audit_limit = limit,
+                }
+                "audit_limit"
+            }
+            "sync_url" => {
+                self.sync_url = Some(expect_str(value)?.to_string());
+                "sync_url"
+            }
+            "api_token" => {
+                self.api_token = Some(expect_str(value)?.to_string());
+                "api_token"
+            }
+            "sync_conflicts" => {
+                self.sync_conflicts = ConflictPolicy::from_str(expect_str(value)?)
+                    .map_err(|_| "expected prefer-local, prefer-remote or ask".to_string())?;
+                "sync_conflicts"
+            }
+            // Lenient: good dates are kept and the rest reported
+            "holidays" => {
+                let entries = value
+                    .as_array()
+                    .ok_or_else(|| format!("expected a list of YYYY-MM-DD dates, found {}", value.type_str()))?;
+                let (dates, bad): (Vec<_>, Vec<_>) = entries
+                    .iter()
+                    .map(|entry| entry.as_str().and_then(|date| NaiveDate::parse_from_str(date, "%Y-%m-%d").ok()).ok_or(entry))
+                    .partition(Result::is_ok);
+                self.holidays = dates.into_iter().flatten().collect();
+                self.sources.insert("holidays", ConfigSource::File);
+                if !bad.is_empty() {
+                    let bad: Vec<String> = bad.into_iter().filter_map(Result::err).map(Value::to_string).collect();
+                    return Err(format!("ignored entries that are not YYYY-MM-DD dates: {}", bad.join(", ")));
+                }
+                "holidays"
+            }
+            "business_days" => {
+                self.business_days = expect_bool(value)?;
+                "business_days"
+            }
+            "urgent_days" => {
+                self.urgent_days = u32::try_from(expect_count(value)?).map_err(|_| "number too large".to_string())?;
+                "urgent_days"
+            }
+            "list_columns" => {
+                let names = expect_str(value)?;
+                self.list_columns = match names.trim() {
+                    "" => Vec::new(),
+                    names => table::parse_columns(names).map_err(|bad| {
+                        format!("unknown column '{}'; expected some of {}", bad, COLUMN_NAMES.join(", "))
+                    })?,
+                };
+                "list_columns"
+            }
+            "default_estimate" => {
+                let text = expect_str(value)?;
+                self.default_estimate =
+                    dates::parse_offset(text).ok_or_else(|| format!("expected a length like 2d, 4h or 30m, got '{}'", text))?;
+                "default_estimate"
+            }
+            "forecast_weeks" => {
+                match u32::try_from(expect_count(value)?).map_err(|_| "number too large".to_string())? {
+                    0 => return Err("expected at least 1 week".to_string()),
+                    weeks => self.forecast_weeks = weeks,
+                }
+                "forecast_weeks"
+            }
+            "digest_limit" => {
+                match expect_count(value)? {
+                    0 => return Err("expected at least 1 item".to_string()),
+                    limit => self.digest_limit = limit,
+                }
+                "digest_limit"
+            }
+            "digest_from" => {
+                self.digest_from = Some(expect_str(value)?.to_string());
+                "digest_from"
+            }
+            "digest_to" => {
+                self.digest_to = Some(expect_str(value)?.to_string());
+                "digest_to"
+            }
+            _ => return Err("unknown key, ignored".to_string()),
+        };
+        self.sources.insert(key, ConfigSource::File);
+        Ok(())
+    }
+
+    // Record that a command-line flag overrode a setting
+    pub fn set_from_flag(&mut self, key: &'static str) {
+        self.sources.insert(key, ConfigSource::Flag);
+    }
+
+    pub fn source(&self, key: &str) -> ConfigSource {
+        self.sources.get(key).copied().unwrap_or(ConfigSource::Default)
+    }
+
+    // Directory searched for user message catalogs (<language>.toml)
+    pub fn locale_dir(&self) -> Option<PathBuf> {
+        let config_file = self.path.clone().or_else(Config::default_path)?;
+        Some(config_file.parent()?.join("locales"))
+    }
+
+    // Every effective setting as (key, value, source), for the `config` command
+    pub fn describe(&self) -> Vec<(&'static str, String, ConfigSource)> {
+        let format = match self.format {
+            OutputFormat::Human => "human",
+            OutputFormat::Json => "json",
+        };
+        let limit = |limit: Option<usize>| limit.map_or_else(|| "(off)".to_string(), |n| n.to_string());
+        let age = |age: Option<Duration>| age.map_or_else(|| "(off)".to_string(), retention::format_age);
+        let entries = [
+            ("data_path", self.data_path.display().to_string()),
+            ("journal", self.journal.to_string()),
+            ("journal_compact_after", self.journal_compact_after.to_string()),
+            ("autosave_delay_ms", self.autosave_delay_ms.to_string()),
+            ("autosave_max_changes", self.autosave_max_changes.to_string()),
+            ("store_format", self.store_format.to_string()),
+            ("default_priority", self.default_priority.to_string().to_lowercase()),
+            ("format", format.to_string()),
+            ("color", self.color.to_string()),
+            ("confirm", self.confirm.to_string()),
+            ("date_format", self.date_format.clone()),
+            ("absolute_dates", self.absolute_dates.to_string()),
+            ("first_day_of_week", self.first_day_of_week.clone()),
+            ("language", self.language.clone().unwrap_or_else(|| "(from LANG)".to_string())),
+            ("require_tag", self.require_tag.to_string()),
+            (
+                "require_due_for_priority",
+                self.require_due_for_priority
+                    .as_ref()
+                    .map_or_else(|| "(off)".to_string(), |p| p.to_string().to_lowercase()),
+            ),
+            ("max_open_tasks", self.max_open_tasks.map_or_else(|| "(off)".to_string(), |n| n.to_string())),
+            ("require.due_date", self.required.due_date.to_string()),
+            ("require.tags", self.required.tags.to_string()),
+            ("require.description", self.required.description.to_string()),
+            ("limits.description_chars", limit(self.limits.description_chars)),
+            ("limits.notes_per_task", limit(self.limits.notes_per_task)),
+            ("limits.tags_per_task", limit(self.limits.tags_per_task)),
+            ("retention.archive_completed_after", age(self.retention.archive_completed_after)),
+            ("retention.purge_archived_after", age(self.retention.purge_archived_after)),
+            ("notify_on_startup", self.notify_on_startup.to_string()),
+            ("notify_within_hours", self.notify_within_hours.to_string()),
+            ("views_on_startup", self.views_on_startup.to_string()),
+            ("quiet_start", self.quiet_start.to_string()),
+            ("bell", self.bell.to_string()),
+            ("source_transactional", self.source_transactional.to_string()),
+            ("resume_view", self.resume_view.to_string()),
+            ("ignore_accents", self.ignore_accents.to_string()),
+            ("guard_completed", self.guard_completed.to_string()),
+            ("strict_tags", self.strict_tags.to_string()),
+            ("user", self.user.clone().unwrap_or_else(|| "($USER)".to_string())),
+            ("protect_others_tasks", self.protect_others_tasks.to_string()),
+            ("transitions", self.workflow.describe_allowed()),
+            ("transition_requires", self.workflow.describe_requirements()),
+            ("daemon_interval_minutes", self.daemon_interval_minutes.to_string()),
+            ("webhooks", self.webhooks.iter().map(|hook| hook.url.as_str()).collect::<Vec<_>>().join(", ")),
+            ("hooks", self.hooks.iter().map(|hook| format!("{}: {}", hook.event, hook.command)).collect::<Vec<_>>().join(", ")),
+            ("hook_timeout_secs", self.hook_timeout_secs.to_string()),
+            ("audit_limit", self.audit_limit.to_string()),
+            ("sync_url", self.sync_url.clone().unwrap_or_else(|| "(off)".to_string())),
+            ("api_token", self.api_token.as_ref().map_or("(off)", |_| "(set)").to_string()),
+            ("sync_conflicts", self.sync_conflicts.to_string()),
+            ("holidays", self.holidays.iter().map(NaiveDate::to_string).collect::<Vec<_>>().join(", ")),
+            ("business_days", self.business_days.to_string()),
+            ("urgent_days", self.urgent_days.to_string()),
+            ("list_columns", self.list_columns.iter().map(Column::to_string).collect::<Vec<_>>().join(",")),
+            ("default_estimate", dates::format_offset(self.default_estimate)),
+            ("forecast_weeks", self.forecast_weeks.to_string()),
+            ("digest_limit", self.digest_limit.to_string()),
+            ("digest_from", self.digest_from.clone().unwrap_or_else(|| "(unset)".to_string())),
+            ("digest_to", self.digest_to.clone().unwrap_or_else(|| "(unset)".to_string())),
+        ];
+        entries
+            .into_iter()
+            .map(|(key, value)| (key, value, self.source(key)))
+            .collect()
+    }
+}
+
+// A format this build can write; the `--store-format` flag goes through here too
+pub fn parse_store_format(value: &str) -> Result<StoreFormat, String> {
+    let format = StoreFormat::from_str(value)?;
+    if !format.is_available() {
+        return Err(format!("'{}' needs the 'msgpack' feature, which this build lacks", value));
+    }
+    Ok(format)
+}
+
+fn expect_str(value: &Value) -> Result<&str, String> {
+    value.as_str().ok_or_else(|| format!("expected a string, found {}", value.type_str()))
+}
+
+fn parse_webhook(value: &Value) -> Result<WebhookConfig, String> {
+    let table = value.as_table().ok_or("each webhook must be a table with url and events")?;
+    let url = table.get("url").ok_or("webhook is missing url").and_then(|url| expect_str(url).map_err(|_| "url must be a string"))?;
+    let events = match table.get("events") {
+        Some(events) => events
+            .as_array()
+            .ok_or("webhook events must be a list")?
+            .iter()
+            .map(|event| {
+                expect_str(event)
+                    .ok()
+                    .and_then(|name| WebhookEvent::from_str(name).ok())
+                    .ok_or("webhook events are completed and created_critical")
+            })
+            .collect::<Result<_, _>>()?,
+        None => vec![WebhookEvent::Completed, WebhookEvent::CreatedCritical],
+    };
+    Ok(WebhookConfig { url: url.to_string(), events })
+}
+
+// { event = "completed", command = "~/bin/log-done.sh" }
+fn parse_hook(value: &Value) -> Result<HookConfig, String> {
+    let table = value.as_table().ok_or("each hook must be a table with event and command")?;
+    let event = table
+        .get("event")
+        .ok_or("hook is missing event")?
+        .as_str()
+        .and_then(|name| HookEvent::from_str(name).ok())
+        .ok_or("hook events are created, completed, status_changed, updated, deleted and reminded")?;
+    let command = table.get("command").ok_or("hook is missing command")?.as_str().ok_or("command must be a string")?;
+    if command.trim().is_empty() {
+        return Err("hook command is empty".to_string());
+    }
+    Ok(HookConfig { event, command: command.to_string() })
+}
+
+fn parse_status(value: &str) -> Result<TaskStatus, String> {
+    TaskStatus::from_str(value).map_err(|_| format!("unknown status '{}', expected pending, progress or completed", value))
+}
+
+// { from = "completed", to = "pending", need = "note" }
+fn parse_transition_requirement(value: &Value) -> Result<(TaskStatus, TaskStatus, Requirement), String> {
+    let table = value.as_table().ok_or("each requirement must be a table with from, to and need")?;
+    let field = |name: &str| {
+        table.get(name).ok_or_else(|| format!("requirement is missing {}", name)).and_then(|value| expect_str(value))
+    };
+    let need = field("need")?;
+    let requirement = Requirement::from_str(need).map_err(|_| format!("unknown need '{}', expected note or assignee", need))?;
+    Ok((parse_status(field("from")?)?, parse_status(field("to")?)?, requirement))
+}
+
+fn expect_count(value: &Value) -> Result<usize, String> {
+    let n = value
+        .as_integer()
+        .ok_or_else(|| format!("expected a number, found {}", value.type_str()))?;
+    usize::try_from(n).map_err(|_| "expected a positive number".to_string())
+}
+
+fn expect_age(value: &Value) -> Result<Duration, String> {
+    let text = expect_str(value)?;
+    retention::parse_age(text).ok_or_else(|| format!("expected an age like 30d, 6w or 1y, got '{}'", text))
+}
+
+fn expect_bool(value: &Value) -> Result<bool, String> {
+    value.as_bool().ok_or_else(|| format!("expected true or false, found {}", value.type_str()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_defaults_when_empty() {
+        let (config, warnings) = Config::parse("");
+        assert_eq!(config, Config::default());
+        assert!(warnings.is_empty());
+        assert!(config.describe().iter().all(|(_, _, source)| *source == ConfigSource::Default));
+    }
+
+    #[test]
+    fn test_file_values_and_sources() {
+        let (config, warnings) = Config::parse(
+            "data_path = \"/tmp/t.json\"\ndefault_priority = \"high\"\ncolor = false\nfirst_day_of_week = \"Sunday\"\n",
+        );
+        assert!(warnings.is_empty());
+        assert_eq!(config.data_path, PathBuf::from("/tmp/t.json"));
+        assert_eq!(config.default_priority, Priority::High);
+        assert!(!config.color);
+        assert_eq!(config.first_day_of_week, "sunday");
+        assert_eq!(config.source("color"), ConfigSource::File);
+        assert_eq!(config.source("confirm"), ConfigSource::Default);
+
+        let (config, warnings) = Config::parse("audit_limit = 50\n");
+        assert!(warnings.is_empty());
+        assert_eq!(config.audit_limit, 50);
+        let (config, warnings) = Config::parse("audit_limit = -1\n");
+        assert_eq!(config.audit_limit, DEFAULT_AUDIT_LIMIT);
+        assert_eq!(warnings.len(), 1);
+        let (config, warnings) = Config::parse("audit_limit = 0\n");
+        assert_eq!(config.audit_limit, DEFAULT_AUDIT_LIMIT);
+        assert_eq!(warnings, ["config key 'audit_limit': expected at least 1 entry"]);
+
+        let (config, warnings) = Config::parse("sync_url = \"http://home:8080\"\nsync_conflicts = \"prefer-local\"\n");
+        assert!(warnings.is_empty());
+        assert_eq!(config.sync_url.as_deref(), Some("http://home:8080"));
+        assert_eq!(config.sync_conflicts, ConflictPolicy::PreferLocal);
+
+        let (config, warnings) = Config::parse("digest_limit = 3\ndigest_to = \"me@example.com\"\n");
+        assert!(warnings.is_empty());
+        assert_eq!((config.digest_limit, config.digest_to.as_deref()), (3, Some("me@example.com")));
+        let (config, warnings) = Config::parse("digest_limit = 0\n");
+        assert_eq!(config.digest_limit, DEFAULT_DIGEST_LIMIT);
+        assert_eq!(warnings.len(), 1);
+
+        let (config, warnings) = Config::parse("holidays = [\"2024-12-25\", \"xmas\", 12, \"2024-12-26\"]\n");
+        assert_eq!(config.holidays, vec![NaiveDate::from_ymd_opt(2024, 12, 25).unwrap(), NaiveDate::from_ymd_opt(2024, 12, 26).unwrap()]);
+        assert_eq!(warnings, vec!["config key 'holidays': ignored entries that are not YYYY-MM-DD dates: \"xmas\", 12"]);
+        assert_eq!(config.source("holidays"), ConfigSource::File);
+
+        assert!(!Config::default().business_days);
+        let (config, warnings) = Config::parse("business_days = true\n");
+        assert!(warnings.is_empty());
+        assert!(config.business_days);
+
+        assert_eq!(Config::default().urgent_days, DEFAULT_URGENT_DAYS);
+        let (config, warnings) = Config::parse("urgent_days = 0\n");
+        assert!(warnings.is_empty());
+        assert_eq!(config.urgent_days, 0);
+
+        assert!(Config::default().list_columns.is_empty());
+        let (config, warnings) = Config::parse("list_columns = \"id,title,age\"\n");
+        assert!(warnings.is_empty());
+        assert_eq!(config.list_columns, [Column::Id, Column::Title, Column::Age]);
+        let (config, warnings) = Config::parse("list_columns = \"id,size\"\n");
+        assert!(config.list_columns.is_empty());
+        assert_eq!(warnings, vec!["config key 'list_columns': unknown column 'size'; expected some of id, title, pri, status, due, tags, energy, age, updated"]);
+
+        assert_eq!(Config::default().default_estimate, DEFAULT_ESTIMATE);
+        let (config, warnings) = Config::parse("default_estimate = \"4h\"\n");
+        assert!(warnings.is_empty());
+        assert_eq!(config.default_estimate, Duration::from_secs(4 * 3600));
+        let (config, warnings) = Config::parse("default_estimate = \"soon\"\n");
+        assert_eq!(config.default_estimate, DEFAULT_ESTIMATE);
+        assert_eq!(warnings, vec!["config key 'default_estimate': expected a length like 2d, 4h or 30m, got 'soon'"]);
+
+        assert_eq!(Config::default().forecast_weeks, DEFAULT_FORECAST_WEEKS);
+        let (config, warnings) = Config::parse("forecast_weeks = 8
+");
+        assert!(warnings.is_empty());
+        assert_eq!(config.forecast_weeks, 8);
+        let (config, warnings) = Config::parse("forecast_weeks = 0
+");
+        assert_eq!(config.forecast_weeks, DEFAULT_FORECAST_WEEKS);
+        assert_eq!(warnings, vec!["config key 'forecast_weeks': expected at least 1 week"]);
+
+        assert!(Config::default().ignore_accents);
+        let (config, warnings) = Config::parse("ignore_accents = false\n");
+        assert!(warnings.is_empty());
+        assert!(!config.ignore_accents);
+
+        assert!(Config::default().guard_completed);
+        let (config, warnings) = Config::parse("guard_completed = false\n");
+        assert!(warnings.is_empty());
+        assert!(!config.guard_completed);
+
+        assert!(!Config::default().strict_tags);
+        let (config, warnings) = Config::parse("strict_tags = true\n");
+        assert!(warnings.is_empty());
+        assert!(config.strict_tags);
+
+        assert!(!Config::default().protect_others_tasks && Config::default().user.is_none());
+        let (config, warnings) = Config::parse("protect_others_tasks = true\nuser = \"deploy-bot\"\n");
+        assert!(warnings.is_empty());
+        assert!(config.protect_others_tasks);
+        assert_eq!(config.user.as_deref(), Some("deploy-bot"));
+
+        assert!(!Config::default().bell);
+        let (config, warnings) = Config::parse("bell = true\n");
+        assert!(warnings.is_empty());
+        assert!(config.bell);
+
+        assert!(!Config::default().source_transactional);
+        let (config, warnings) = Config::parse("source_transactional = true\n");
+        assert!(warnings.is_empty());
+        assert!(config.source_transactional);
+    }
+
+    #[test]
+    fn test_workflow_settings() {
+        let (config, warnings) = Config::parse(
+            "[transitions]\npending = [\"progress\"]\ncompleted = [\"pending\"]\n\n\
+             [[transition_requires]]\nfrom = \"completed\"\nto = \"pending\"\nneed = \"note\"\n",
+        );
+        assert!(warnings.is_empty(), "{:?}", warnings);
+        assert_eq!(config.workflow.next_statuses(&TaskStatus::Pending), [TaskStatus::InProgress]);
+        assert_eq!(config.workflow.requires(&TaskStatus::Completed, &TaskStatus::Pending), [Requirement::Note]);
+        assert_eq!(config.source("transitions"), ConfigSource::File);
+
+        let (config, warnings) = Config::parse("[transitions]\npending = [\"done\"]\n\n[[transition_requires]]\nfrom = \"pending\"\n");
+        assert_eq!(warnings.len(), 2);
+        assert!(config.workflow.is_permissive());
+    }
+
+    #[test]
+    fn test_journal_settings() {
+        let (config, warnings) = Config::parse("journal = true\njournal_compact_after = 50\n");
+        assert!(warnings.is_empty());
+        assert_eq!((config.journal, config.journal_compact_after), (true, 50));
+        let (config, warnings) = Config::parse("journal_compact_after = 0\n");
+        assert_eq!(config.journal_compact_after, DEFAULT_COMPACT_AFTER);
+        assert_eq!(warnings.len(), 1);
+    }
+
+    #[test]
+    fn test_autosave_settings() {
+        let (config, warnings) = Config::parse("autosave_delay_ms = 500\nautosave_max_changes = 20\n");
+        assert!(warnings.is_empty());
+        assert_eq!((config.autosave_delay_ms, config.autosave_max_changes), (500, 20));
+        let (config, warnings) = Config::parse("autosave_max_changes = 0\n");
+        assert_eq!(config.autosave_max_changes, DEFAULT_AUTOSAVE_MAX_CHANGES);
+        assert_eq!(warnings.len(), 1);
+    }
+
+    #[test]
+    fn test_defaults_and_require_tables() {
+        let (config, warnings) =
+            Config::parse("[defaults]\npriority = \"high\"\n\n[require]\ndue_date = true\ntags = false\ndescription = true\n");
+        assert!(warnings.is_empty(), "{:?}", warnings);
+        assert_eq!(config.default_priority, Priority::High);
+        assert_eq!(config.required, RequiredFields { due_date: true, tags: false, description: true });
+        assert_eq!(config.source("default_priority"), ConfigSource::File);
+        assert_eq!(config.source("require.due_date"), ConfigSource::File);
+
+        let (config, warnings) = Config::parse("[require]\nestimate = true\ndue_date = \"yes\"\n");
+        assert_eq!(config.required, RequiredFields::default());
+        assert!(warnings[0].contains("'require.due_date'") && warnings[1].contains("'require.estimate'"));
+    }
+
+    #[test]
+    fn test_limits_table() {
+        assert!(Config::default().limits.is_unlimited());
+        let (config, warnings) = Config::parse("[limits]\ndescription_chars = 500\nnotes_per_task = 20\ntags_per_task = 5\n");
+        assert!(warnings.is_empty(), "{:?}", warnings);
+        assert_eq!(config.limits, Limits { description_chars: Some(500), notes_per_task: Some(20), tags_per_task: Some(5) });
+        assert_eq!(config.source("limits.tags_per_task"), ConfigSource::File);
+
+        let (config, warnings) = Config::parse("[limits]\ndescription_chars = -1\nwords = 100\n");
+        assert!(config.limits.is_unlimited());
+        assert!(warnings[0].contains("'limits.description_chars'") && warnings[1].contains("'limits.words'"));
+    }
+
+    #[test]
+    fn test_retention_table() {
+        assert!(Config::default().retention.is_off());
+        let (config, warnings) = Config::parse("[retention]\narchive_completed_after = \"30d\"\npurge_archived_after = \"1y\"\n");
+        assert!(warnings.is_empty(), "{:?}", warnings);
+        assert_eq!(config.retention.archive_completed_after, Some(Duration::from_secs(30 * 86_400)));
+        assert_eq!(config.retention.purge_archived_after, Some(Duration::from_secs(365 * 86_400)));
+        let described: Vec<String> =
+            config.describe().into_iter().filter(|(key, ..)| key.starts_with("retention.")).map(|(_, value, _)| value).collect();
+        assert_eq!(described, ["30d", "1y"]);
+
+        let (config, warnings) = Config::parse("[retention]\narchive_completed_after = \"soon\"\npurge_archived_after = \"1é\"\n");
+        assert!(config.retention.is_off());
+        assert_eq!(
+            warnings,
+            [
+                "config key 'retention.archive_completed_after': expected an age like 30d, 6w or 1y, got 'soon'",
+                "config key 'retention.purge_archived_after': expected an age like 30d, 6w or 1y, got '1é'",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_store_format() {
+        let (config, warnings) = Config::parse("store_format = \"JSON\"\n");
+        assert!(warnings.is_empty());
+        assert_eq!(config.store_format, StoreFormat::Json);
+        let (config, warnings) = Config::parse("store_format = \"msgpack\"\n");
+        if cfg!(feature = "msgpack") {
+            assert_eq!((config.store_format, warnings.len()), (StoreFormat::MessagePack, 0));
+        } else {
+            assert_eq!(config.store_format, StoreFormat::Json);
+            assert!(warnings[0].contains("'msgpack' feature"));
+        }
+        assert_eq!(Config::parse("store_format = \"xml\"\n").1.len(), 1);
+    }
+
+    #[test]
+    fn test_bad_keys_warn_and_keep_defaults() {
+        let (config, warnings) = Config::parse("colour = true\ndefault_priority = \"urgent\"\nconfirm = 3\n");
+        assert_eq!(config, Config::default());
+        assert_eq!(warnings.len(), 3);
+        assert!(warnings[0].contains("'colour'") && warnings[0].contains("unknown key"));
+        assert!(warnings[1].contains("'confirm'") && warnings[1].contains("expected true or false"));
+        assert!(warnings[2].contains("'default_priority'"));
+
+        let (config, warnings) = Config::parse("date_format = \"%H:%M\"\n");
+        assert_eq!(config.date_format, "%Y-%m-%d");
+        assert_eq!(
+            warnings,
+            ["config key 'date_format': expected a date format like %Y-%m-%d or %d/%m/%Y, got '%H:%M'"]
+        );
+
+        let (config, warnings) = Config::parse("notify_within_hours = 8784\n");
+        assert_eq!((config.notify_within_hours, warnings.len()), (8784, 0));
+        let (config, warnings) = Config::parse("notify_within_hours = 4294967295\n");
+        assert_eq!(config.notify_within_hours, 0);
+        assert_eq!(warnings, ["config key 'notify_within_hours': expected at most 8784 hours (a year)"]);
+        let (config, warnings) = Config::parse("daemon_interval_minutes = 9223372036854775807\n");
+        assert_eq!(config.daemon_interval_minutes, 5);
+        assert_eq!(warnings, ["config key 'daemon_interval_minutes': expected at most 307445734561825860 minutes"]);
+    }
+
+    #[test]
+    fn test_webhook_tables() {
+        let (config, warnings) = Config::parse(
+            "[[webhooks]]\nurl = \"https://hooks.example/a\"\nevents = [\"completed\"]\n\n[[webhooks]]\nurl = \"https://hooks.example/b\"\n",
+        );
+        assert!(warnings.is_empty(), "{:?}", warnings);
+        assert_eq!(config.webhooks, vec![
+            WebhookConfig { url: "https://hooks.example/a".to_string(), events: vec![WebhookEvent::Completed] },
+            WebhookConfig {
+                url: "https://hooks.example/b".to_string(),
+                events: vec![WebhookEvent::Completed, WebhookEvent::CreatedCritical],
+            },
+        ]);
+
+        let (config, warnings) = Config::parse("webhooks = [{ url = \"x\", events = [\"deleted\"] }]\n");
+        assert!(config.webhooks.is_empty());
+        assert!(warnings[0].contains("completed and created_critical"));
+    }
+
+    #[test]
+    fn test_hook_tables() {
+        let (config, warnings) = Config::parse(
+            "hook_timeout_secs = 3\n\n[[hooks]]\nevent = \"completed\"\ncommand = \"/home/me/bin/log-done.sh\"\n",
+        );
+        assert!(warnings.is_empty(), "{:?}", warnings);
+        assert_eq!(config.hooks, vec![HookConfig { event: HookEvent::Completed, command: "/home/me/bin/log-done.sh".to_string() }]);
+        assert_eq!(config.hook_timeout_secs, 3);
+
+        let (config, warnings) = Config::parse("hooks = [{ event = \"finished\", command = \"x\" }]\nhook_timeout_secs = 0\n");
+        assert!(config.hooks.is_empty());
+        assert_eq!(config.hook_timeout_secs, DEFAULT_HOOK_TIMEOUT_SECS);
+        assert_eq!(warnings.len(), 2);
+        assert!(warnings.iter().any(|warning| warning.contains("hook events are created")));
+        assert!(warnings.iter().any(|warning| warning.contains("at least 1 second")));
+    }
+
+    #[test]
+    fn test_invalid_toml_falls_back_to_defaults() {
+        let (config, warnings) = Config::parse("color = \n");
+        assert_eq!(config, Config::default());
+        assert_eq!(warnings.len(), 1);
+    }
+}
@@ -0,0 +1,222 @@
+use std::fs;
+use std::io::{BufRead, BufReader, ErrorKind, Write};
+use std::os::unix::fs::FileTypeExt;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use log::{info, warn};
+use serde_json::{Value, json};
+
+use crate::command::{Command, execute, resolve_task_ref};
+use crate::config::Config;
+use crate::msg;
+use crate::render::{JsonRenderer, Renderer, parse_error_json, task_error_json};
+use crate::{SharedTaskManager, TaskError, TaskManager};
+
+// Called with the manager, still locked, after every command a client runs
+pub type AfterCommand = Arc<dyn Fn(&TaskManager) + Send + Sync>;
+
+// How long a client's command waits for the session to hand the manager back
+// before it is answered with a Busy error, and how often it looks
+const BUSY_WAIT: Duration = Duration::from_secs(1);
+const BUSY_POLL: Duration = Duration::from_millis(20);
+
+// `--socket <path>`: answers REPL command lines from local scripts against the
+// live session, one JSON document per line. Stopped and removed on drop.
+pub struct ControlSocket {
+    path: PathBuf,
+    stop: Arc<AtomicBool>,
+    accept: Option<JoinHandle<()>>,
+}
+
+impl ControlSocket {
+    // A socket file nobody answers on is left over from a crashed session and is
+    // replaced; one that still answers belongs to another session, and anything
+    // that is not a socket, such as the data file, is never touched. While
+    // `busy` is set the session has the manager checked out and clients are
+    // told so instead of waiting on it.
+    pub fn bind(
+        path: &Path,
+        manager: SharedTaskManager,
+        config: Config,
+        after: AfterCommand,
+        busy: Arc<AtomicBool>,
+    ) -> Result<ControlSocket, TaskError> {
+        let bind_error = |e: std::io::Error| TaskError::Storage(format!("{}: {}", path.display(), e));
+        match fs::symlink_metadata(path) {
+            Ok(metadata) => {
+                if !metadata.file_type().is_socket() {
+                    return Err(bind_error(std::io::Error::new(ErrorKind::AlreadyExists, "exists and is not a socket")));
+                }
+                if UnixStream::connect(path).is_ok() {
+                    return Err(bind_error(std::io::Error::new(ErrorKind::AddrInUse, "another session is listening")));
+                }
+                fs::remove_file(path).map_err(bind_error)?;
+            }
+            Err(e) if e.kind() == ErrorKind::NotFound => {}
+            Err(e) => return Err(bind_error(e)),
+        }
+        let listener = UnixListener::bind(path).map_err(bind_error)?;
+        info!("control socket listening on {}", path.display());
+
+        let stop = Arc::new(AtomicBool::new(false));
+        let stopping = Arc::clone(&stop);
+        let accept = thread::spawn(move || {
+            for stream in listener.incoming() {
+                if stopping.load(Ordering::SeqCst) {
+                    break;
+                }
+                match stream {
+                    Ok(stream) => {
+                        let (manager, config, after) = (manager.clone(), config.clone(), Arc::clone(&after));
+                        let busy = Arc::clone(&busy);
+                        thread::spawn(move || serve_client(stream, &manager, &config, after.as_ref(), &busy));
+                    }
+                    Err(e) => warn!("control socket accept failed: {}", e),
+                }
+            }
+        });
+        Ok(ControlSocket { path: path.to_path_buf(), stop, accept: Some(accept) })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for ControlSocket {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::SeqCst);
+        // Wake the accept loop so it sees the flag
+        let _ = UnixStream::connect(&self.path);
+        if let Some(accept) = self.accept.take() {
+            let _ = accept.join();
+        }
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+fn serve_client(
+    stream: UnixStream,
+    manager: &SharedTaskManager,
+    config: &Config,
+    after: &dyn Fn(&TaskManager),
+    busy: &AtomicBool,
+) {
+    let mut writer = match stream.try_clone() {
+        Ok(writer) => writer,
+        Err(e) => return warn!("control socket client dropped: {}", e),
+    };
+    for line in BufReader::new(stream).lines() {
+        let Ok(line) = line else { break };
+        if line.trim().is_empty() {
+            continue;
+        }
+        let started = Instant::now();
+        let reply = loop {
+            let reply = manager.with_write(|mgr| {
+                // What the lock holds while the session has the manager out is a stand-in
+                (!busy.load(Ordering::SeqCst)).then(|| {
+                    let reply = respond(mgr, config, &line);
+                    after(mgr);
+                    reply
+                })
+            });
+            match reply {
+                Some(reply) => break reply,
+                None if started.elapsed() < BUSY_WAIT => thread::sleep(BUSY_POLL),
+                None => break failure(json!({ "kind": "Busy", "message": msg!("socket_busy") })),
+            }
+        };
+        if writeln!(writer, "{}", reply).is_err() {
+            break;
+        }
+    }
+}
+
+// Run one command line the way the REPL would, rendered as JSON. Session
+// commands such as `set`, `sync` or interactive `add` are not available here.
+pub fn respond(manager: &mut TaskManager, config: &Config, line: &str) -> String {
+    let tokens: Vec<String> = line.split_whitespace().map(String::from).collect();
+    let tokens = match resolve_task_ref(&tokens, manager, &mut |_, _| None) {
+        Ok(tokens) => tokens,
+        Err(e) => return failure(task_error_json(&e)),
+    };
+    match Command::parse(&tokens) {
+        Ok(command) => match execute(command.with_defaults(config), manager) {
+            Ok(output) => JsonRenderer.render(&output),
+            Err(e) => failure(task_error_json(&e)),
+        },
+        Err(e) => failure(parse_error_json(&e)),
+    }
+}
+
+// Replies share one line with successes, so errors keep the {"ok": false} envelope
+fn failure(error: Value) -> String {
+    json!({ "ok": false, "error": error }).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Priority;
+    use serde_json::Value;
+    use std::sync::atomic::AtomicUsize;
+
+    #[test]
+    fn test_count_over_the_socket() {
+        let path = std::env::temp_dir().join(format!("task-manager-control-{}.sock", std::process::id()));
+        let idle = || Arc::new(AtomicBool::new(false));
+        // A file that is not a socket, say the data file, is left alone
+        fs::write(&path, "[]").unwrap();
+        let refused = ControlSocket::bind(&path, SharedTaskManager::new(TaskManager::new()), Config::default(), Arc::new(|_| {}), idle());
+        assert!(matches!(refused, Err(TaskError::Storage(_))));
+        assert_eq!(fs::read_to_string(&path).unwrap(), "[]");
+        fs::remove_file(&path).unwrap();
+        // A socket left over from a crashed session is replaced
+        drop(UnixListener::bind(&path).unwrap());
+        assert!(path.exists());
+
+        let manager = SharedTaskManager::new(TaskManager::new());
+        manager.add_task("Write docs".to_string(), String::new(), Priority::High).unwrap();
+        manager.add_task("Ship it".to_string(), String::new(), Priority::Low).unwrap();
+        manager.update_task_status(2, crate::TaskStatus::Completed).unwrap();
+        let commands = Arc::new(AtomicUsize::new(0));
+        let counted = Arc::clone(&commands);
+        let after: AfterCommand = Arc::new(move |_| {
+            counted.fetch_add(1, Ordering::SeqCst);
+        });
+        let busy = idle();
+        let socket = ControlSocket::bind(&path, manager.clone(), Config::default(), after, Arc::clone(&busy)).unwrap();
+
+        let mut stream = UnixStream::connect(socket.path()).unwrap();
+        writeln!(stream, "count\nadd Review PR\nfrobnicate").unwrap();
+        let mut replies = BufReader::new(stream).lines();
+        let mut next = || serde_json::from_str::<Value>(&replies.next().unwrap().unwrap()).unwrap();
+        assert_eq!(next(), serde_json::json!({ "ok": true, "data": { "open": 1, "total": 2 } }));
+        assert_eq!(next()["data"]["id"], 3);
+        assert_eq!(next()["error"]["kind"], "UnknownCommand");
+        assert_eq!(commands.load(Ordering::SeqCst), 3);
+        assert_eq!(manager.list_tasks().len(), 3);
+
+        // While the session has the manager checked out, clients hear so rather than hang
+        busy.store(true, Ordering::SeqCst);
+        let mut stream = UnixStream::connect(socket.path()).unwrap();
+        writeln!(stream, "count").unwrap();
+        let reply: Value = serde_json::from_str(&BufReader::new(stream).lines().next().unwrap().unwrap()).unwrap();
+        assert_eq!(reply["error"]["kind"], "Busy");
+        assert_eq!(commands.load(Ordering::SeqCst), 3);
+        busy.store(false, Ordering::SeqCst);
+
+        // A second session may not take over a live socket
+        let taken = ControlSocket::bind(&path, manager.clone(), Config::default(), Arc::new(|_| {}), idle());
+        assert!(matches!(taken, Err(TaskError::Storage(_))));
+
+        drop(socket);
+        assert!(!path.exists());
+    }
+}
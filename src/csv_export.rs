@@ -0,0 +1,115 @@
+use std::collections::BTreeSet;
+
+use chrono::NaiveDate;
+
+use crate::Task;
+
+const COLUMNS: [&str; 8] = ["id", "title", "description", "priority", "status", "tags", "due", "energy"];
+
+// One row per task, with a column for every custom field key any of them has;
+// a task without that field leaves the cell empty
+pub fn to_csv(tasks: &[&Task]) -> String {
+    let keys: BTreeSet<&str> = tasks.iter().flat_map(|task| task.fields.keys().map(String::as_str)).collect();
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    let header: Vec<&str> = COLUMNS.iter().copied().chain(keys.iter().copied()).collect();
+    writer.write_record(&header).expect("writing to memory cannot fail");
+    for task in tasks {
+        let mut row = vec![
+            task.id.to_string(),
+            task.title.clone(),
+            task.description.clone(),
+            task.priority.to_string(),
+            task.status.to_string(),
+            task.tags.join(";"),
+            task.due.map(|due| due.to_string()).unwrap_or_default(),
+            task.energy.map(|energy| energy.to_string()).unwrap_or_default(),
+        ];
+        row.extend(keys.iter().map(|key| task.fields.get(*key).cloned().unwrap_or_default()));
+        writer.write_record(&row).expect("writing to memory cannot fail");
+    }
+    String::from_utf8(writer.into_inner().expect("writing to memory cannot fail")).expect("csv of strings is utf-8")
+}
+
+// Tasks back from a file to_csv wrote. Only the exported columns come back:
+// everything else a task holds starts out as on a new task.
+pub fn from_csv(text: &str) -> Result<Vec<Task>, String> {
+    let mut reader = csv::Reader::from_reader(text.as_bytes());
+    let header = reader.headers().map_err(|e| e.to_string())?.clone();
+    if header.iter().take(COLUMNS.len()).ne(COLUMNS) {
+        return Err(format!("expected the columns {}", COLUMNS.join(",")));
+    }
+    let mut tasks = Vec::new();
+    for (line, record) in (2..).zip(reader.records()) {
+        let record = record.map_err(|e| e.to_string())?;
+        let cell = |column: usize| record.get(column).unwrap_or_default();
+        let bad = |column: usize| format!("line {}: bad {} '{}'", line, COLUMNS[column], cell(column));
+        let id = cell(0).parse().map_err(|_| bad(0))?;
+        let priority = cell(3).parse().map_err(|_| bad(3))?;
+        let mut task = Task::new(id, cell(1).to_string(), cell(2).to_string(), priority);
+        task.status = cell(4).parse().map_err(|_| bad(4))?;
+        for tag in cell(5).split(';').filter(|tag| !tag.is_empty()) {
+            task.add_tag(tag);
+        }
+        if !cell(6).is_empty() {
+            task.due = Some(NaiveDate::parse_from_str(cell(6), "%Y-%m-%d").map_err(|_| bad(6))?);
+        }
+        if !cell(7).is_empty() {
+            task.energy = Some(cell(7).parse().map_err(|_| bad(7))?);
+        }
+        for (column, key) in header.iter().enumerate().skip(COLUMNS.len()) {
+            if !cell(column).is_empty() {
+                task.fields.insert(key.to_string(), cell(column).to_string());
+            }
+        }
+        tasks.push(task);
+    }
+    Ok(tasks)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Energy, Priority, TaskStatus};
+
+    #[test]
+    fn test_a_column_per_field_key() {
+        let mut ticket = Task::new(1, "Fix login".to_string(), String::new(), Priority::High);
+        ticket.fields.insert("ticket".to_string(), "OPS-12".to_string());
+        ticket.add_tag("auth");
+        let mut story = Task::new(2, "Export, at last".to_string(), "CSV".to_string(), Priority::Low);
+        story.fields.insert("points".to_string(), "3".to_string());
+        story.energy = Some(Energy::Low);
+
+        assert_eq!(
+            to_csv(&[&ticket, &story]),
+            "id,title,description,priority,status,tags,due,energy,points,ticket\n\
+             1,Fix login,,High,Pending,auth,,,,OPS-12\n\
+             2,\"Export, at last\",CSV,Low,Pending,,,Low,3,\n"
+        );
+    }
+
+    #[test]
+    fn test_read_back_what_was_written() {
+        let mut task = Task::new(4, "Plan, then build".to_string(), "two\nlines".to_string(), Priority::Critical);
+        task.status = TaskStatus::InProgress;
+        task.add_tag("work");
+        task.add_tag("q3");
+        task.due = NaiveDate::from_ymd_opt(2024, 7, 1);
+        task.energy = Some(Energy::High);
+        task.fields.insert("points".to_string(), "5".to_string());
+        let plain = Task::new(9, "Plain".to_string(), String::new(), Priority::Low);
+
+        let back = from_csv(&to_csv(&[&task, &plain])).unwrap();
+        assert_eq!(back.len(), 2);
+        let first = &back[0];
+        assert_eq!((first.id, first.title.as_str(), first.description.as_str()), (4, "Plan, then build", "two\nlines"));
+        assert_eq!((&first.priority, &first.status, first.due, first.energy), (&Priority::Critical, &TaskStatus::InProgress, task.due, Some(Energy::High)));
+        assert_eq!(first.tags, task.tags);
+        assert_eq!(first.fields, task.fields);
+        assert!(back[1].fields.is_empty() && back[1].tags.is_empty());
+
+        assert_eq!(from_csv("id,name\n1,x\n").unwrap_err(), "expected the columns id,title,description,priority,status,tags,due,energy");
+        let bad = "id,title,description,priority,status,tags,due,energy\n1,x,,Urgent,Pending,,,\n";
+        assert_eq!(from_csv(bad).unwrap_err(), "line 2: bad priority 'Urgent'");
+    }
+}
@@ -0,0 +1,261 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+use chrono::{Local, NaiveDateTime};
+use log::{error, info, warn};
+
+use crate::config::Config;
+use crate::msg;
+use crate::notify::{self, Notifier};
+use crate::journal;
+use crate::storage::{self, Storage};
+use crate::{TaskError, TaskManager};
+
+const DAEMON_USAGE: &str = "usage_daemon";
+
+// How often the sleeping loop checks for SIGTERM
+const STOP_POLL: Duration = Duration::from_secs(1);
+
+// The pid file sits next to the data file: tasks.json -> tasks.pid
+pub fn pid_path(data_path: &Path) -> PathBuf {
+    data_path.with_extension("pid")
+}
+
+// Raise every reminder due at `now` and record it on the task; returns the ids reminded
+pub fn tick(manager: &mut TaskManager, now: NaiveDateTime, within_hours: u32, notifier: &dyn Notifier) -> Vec<u32> {
+    let due: Vec<(u32, (String, String))> = notify::due_reminders(manager.list_tasks(), now, within_hours)
+        .into_iter()
+        .map(|task| (task.id, notify::reminder_text(task, now)))
+        .collect();
+
+    let mut reminded = Vec::new();
+    for (id, (summary, body)) in due {
+        match notifier.notify(&summary, &body) {
+            Ok(()) => {
+                let _ = manager.mark_notified(id, now.date());
+                reminded.push(id);
+            }
+            Err(e) => warn!("reminder for task {} failed: {}", id, e),
+        }
+    }
+    for id in notify::send_offset_reminders(manager, now, notifier) {
+        if !reminded.contains(&id) {
+            reminded.push(id);
+        }
+    }
+    reminded
+}
+
+// `daemon [--interval <minutes>]` or `daemon --stop`; returns the process exit code
+pub fn main(args: &[String], config: &Config) -> i32 {
+    let pid_file = pid_path(&config.data_path);
+    let minutes = match args {
+        [] => config.daemon_interval_minutes,
+        [flag] if flag == "--stop" => return report(stop(&pid_file).map(|pid| msg!("daemon_stopped", pid = pid))),
+        [flag, minutes] if flag == "--interval" => match minutes.parse::<u64>() {
+            Ok(minutes) => minutes,
+            Err(_) => return report(Err(msg!(DAEMON_USAGE))),
+        },
+        _ => return report(Err(msg!(DAEMON_USAGE))),
+    };
+    let Some(interval) = interval(minutes) else {
+        return report(Err(msg!(DAEMON_USAGE)));
+    };
+
+    let stop_flag = Arc::new(AtomicBool::new(false));
+    for signal in [signal_hook::consts::SIGTERM, signal_hook::consts::SIGINT] {
+        if let Err(e) = signal_hook::flag::register(signal, Arc::clone(&stop_flag)) {
+            return report(Err(e.to_string()));
+        }
+    }
+    let daemon = Daemon {
+        storage: storage::file_storage(config),
+        data_path: config.data_path.clone(),
+        pid_file,
+        interval,
+        within_hours: config.notify_within_hours,
+        stop: stop_flag,
+    };
+    report(daemon.run(notify::default_notifier().as_ref()).map(|()| msg!("daemon_exited")))
+}
+
+// A wake-up interval of `minutes`; None for zero or too many to count in seconds
+fn interval(minutes: u64) -> Option<Duration> {
+    minutes.checked_mul(60).filter(|_| minutes > 0).map(Duration::from_secs)
+}
+
+fn report(result: Result<String, String>) -> i32 {
+    match result {
+        Ok(message) => {
+            println!("{}", message);
+            0
+        }
+        Err(message) => {
+            eprintln!("{}", message);
+            1
+        }
+    }
+}
+
+// Send SIGTERM to the daemon named in the pid file
+pub fn stop(pid_file: &Path) -> Result<i32, String> {
+    let pid = read_pid(pid_file).ok_or_else(|| msg!("daemon_not_running"))?;
+    // SAFETY: kill has no memory-safety preconditions
+    if unsafe { libc::kill(pid, libc::SIGTERM) } != 0 {
+        let _ = fs::remove_file(pid_file);
+        return Err(msg!("daemon_not_running"));
+    }
+    Ok(pid)
+}
+
+fn read_pid(pid_file: &Path) -> Option<i32> {
+    fs::read_to_string(pid_file).ok()?.trim().parse().ok()
+}
+
+fn is_alive(pid: i32) -> bool {
+    // SAFETY: signal 0 only checks that the process exists
+    unsafe { libc::kill(pid, 0) == 0 }
+}
+
+struct Daemon {
+    storage: Box<dyn Storage>,
+    data_path: PathBuf,
+    pid_file: PathBuf,
+    interval: Duration,
+    within_hours: u32,
+    stop: Arc<AtomicBool>,
+}
+
+impl Daemon {
+    fn run(&self, notifier: &dyn Notifier) -> Result<(), String> {
+        if let Some(pid) = read_pid(&self.pid_file).filter(|&pid| is_alive(pid)) {
+            return Err(msg!("daemon_already_running", pid = pid));
+        }
+        fs::write(&self.pid_file, std::process::id().to_string())
+            .map_err(|e| format!("{}: {}", self.pid_file.display(), e))?;
+        println!("{}", msg!("daemon_started", pid = std::process::id(), minutes = self.interval.as_secs() / 60));
+        info!("daemon started, checking every {:?}", self.interval);
+
+        let result = self.watch(notifier).and_then(|()| self.storage.compact());
+        let _ = fs::remove_file(&self.pid_file);
+        info!("daemon stopped");
+        result.map_err(|e| e.to_string())
+    }
+
+    fn watch(&self, notifier: &dyn Notifier) -> Result<(), TaskError> {
+        let mut loaded_at: Option<[Option<SystemTime>; 2]> = None;
+        let mut manager = TaskManager::new();
+
+        while !self.stop.load(Ordering::SeqCst) {
+            // Another process may have edited the file since the last wake-up
+            let modified = self.modified();
+            if loaded_at != Some(modified) {
+                manager = TaskManager::from_state(self.storage.load()?);
+                loaded_at = Some(modified);
+                info!("reloaded {} tasks", manager.list_tasks().len());
+            }
+
+            let reminded = tick(&mut manager, Local::now().naive_local(), self.within_hours, notifier);
+            if !reminded.is_empty() {
+                info!("reminded about tasks {:?}", reminded);
+                match self.storage.save(&manager.to_state()) {
+                    Ok(()) => loaded_at = Some(self.modified()),
+                    Err(e) => error!("could not record reminders: {}", e),
+                }
+            }
+
+            self.sleep();
+        }
+        Ok(())
+    }
+
+    // The data file and, when journaled, its log: either changing means another
+    // process has saved
+    fn modified(&self) -> [Option<SystemTime>; 2] {
+        [self.data_path.clone(), journal::log_path(&self.data_path)]
+            .map(|path| fs::metadata(path).and_then(|m| m.modified()).ok())
+    }
+
+    // Sleep for one interval, waking early when asked to stop
+    fn sleep(&self) {
+        let mut slept = Duration::ZERO;
+        while slept < self.interval && !self.stop.load(Ordering::SeqCst) {
+            thread::sleep(STOP_POLL);
+            slept += STOP_POLL;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Priority;
+    use chrono::NaiveDate;
+    use std::cell::RefCell;
+
+    #[derive(Default)]
+    struct Recorder {
+        shown: RefCell<Vec<String>>,
+    }
+
+    impl Notifier for Recorder {
+        fn notify(&self, summary: &str, body: &str) -> Result<(), String> {
+            self.shown.borrow_mut().push(format!("{}: {}", summary, body));
+            Ok(())
+        }
+    }
+
+    fn at(day: u32, hour: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2024, 7, day).unwrap().and_hms_opt(hour, 0, 0).unwrap()
+    }
+
+    #[test]
+    fn test_tick_fires_once_per_day() {
+        let mut manager = TaskManager::new();
+        let id = manager.add_task("Pay rent".to_string(), String::new(), Priority::High).unwrap();
+        manager.apply_edit(id, crate::TaskEdit { due: NaiveDate::from_ymd_opt(2024, 7, 2), ..Default::default() }).unwrap();
+        let recorder = Recorder::default();
+
+        assert!(tick(&mut manager, at(1, 9), 0, &recorder).is_empty());
+        assert_eq!(tick(&mut manager, at(1, 9), 24, &recorder), vec![id]);
+        assert!(tick(&mut manager, at(1, 21), 24, &recorder).is_empty());
+        assert_eq!(tick(&mut manager, at(2, 8), 0, &recorder), vec![id]);
+        assert_eq!(*recorder.shown.borrow(), vec!["Due 2024-07-02: #1 Pay rent", "Due today: #1 Pay rent"]);
+        assert_eq!(manager.get_task(id).unwrap().last_notified, NaiveDate::from_ymd_opt(2024, 7, 2));
+    }
+
+    #[test]
+    fn test_offset_reminders_fire_once() {
+        let mut manager = TaskManager::new();
+        let id = manager.add_task("File taxes".to_string(), String::new(), Priority::High).unwrap();
+        manager.apply_edit(id, crate::TaskEdit { due: NaiveDate::from_ymd_opt(2024, 7, 5), ..Default::default() }).unwrap();
+        manager.set_reminders(id, vec![std::time::Duration::from_secs(2 * 86_400)]).unwrap();
+        let recorder = Recorder::default();
+
+        assert!(tick(&mut manager, at(2, 23), 0, &recorder).is_empty());
+        assert_eq!(tick(&mut manager, at(3, 0), 0, &recorder), vec![id]);
+        assert!(tick(&mut manager, at(3, 9), 0, &recorder).is_empty());
+        assert_eq!(*recorder.shown.borrow(), vec!["Due 2024-07-05 (2d reminder): #1 File taxes"]);
+        assert!(manager.get_task(id).unwrap().reminders[0].fired);
+    }
+
+    #[test]
+    fn test_stop_without_daemon() {
+        let pid_file = std::env::temp_dir().join(format!("task-manager-daemon-{}.pid", std::process::id()));
+        let _ = fs::remove_file(&pid_file);
+        assert!(stop(&pid_file).is_err());
+        assert_eq!(pid_path(Path::new("/data/tasks.json")), PathBuf::from("/data/tasks.pid"));
+    }
+
+    #[test]
+    fn test_intervals_that_do_not_fit_are_refused() {
+        assert_eq!(interval(5), Some(Duration::from_secs(300)));
+        assert_eq!((interval(0), interval(u64::MAX)), (None, None));
+        let args = ["--interval".to_string(), u64::MAX.to_string()];
+        assert_eq!(main(&args, &Config::default()), 1);
+    }
+}
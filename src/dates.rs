@@ -0,0 +1,291 @@
+use std::fmt::Write;
+use std::sync::OnceLock;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use chrono::{DateTime, Datelike, Days, Local, NaiveDate, Utc, Weekday};
+
+use crate::TaskError;
+use crate::msg;
+
+// Beyond this many days either way, dates are shown as they are
+const RELATIVE_DAYS: i64 = 30;
+
+// Set once at startup from `absolute_dates`
+static ABSOLUTE_DATES: AtomicBool = AtomicBool::new(false);
+
+pub fn set_absolute_dates(absolute: bool) {
+    ABSOLUTE_DATES.store(absolute, Ordering::Relaxed);
+}
+
+// Set once at startup from `date_format`; YYYY-MM-DD until then
+static DATE_FORMAT: OnceLock<String> = OnceLock::new();
+
+pub fn set_date_format(format: &str) {
+    let _ = DATE_FORMAT.set(format.to_string());
+}
+
+// A date written out for people to read, in the configured format
+pub fn format_date(date: NaiveDate) -> String {
+    match DATE_FORMAT.get() {
+        Some(format) => date.format(format).to_string(),
+        None => date.to_string(),
+    }
+}
+
+// A strftime format that can write a date; time fields such as %H cannot
+pub fn check_date_format(format: &str) -> Result<(), String> {
+    let sample = NaiveDate::from_ymd_opt(2024, 1, 31).expect("valid date");
+    let mut out = String::new();
+    match write!(out, "{}", sample.format(format)) {
+        Ok(()) if !format.trim().is_empty() => Ok(()),
+        _ => Err(format!("expected a date format like %Y-%m-%d or %d/%m/%Y, got '{}'", format)),
+    }
+}
+
+// "today", "tomorrow", "in 5 days", "3 days ago", or the date itself once it is
+// more than a month away
+pub fn humanize(date: NaiveDate, today: NaiveDate) -> String {
+    relative(date, today).unwrap_or_else(|| format_date(date))
+}
+
+fn relative(date: NaiveDate, today: NaiveDate) -> Option<String> {
+    let text = match (date - today).num_days() {
+        0 => msg!("date_today"),
+        1 => msg!("date_tomorrow"),
+        -1 => msg!("date_yesterday"),
+        days if days.abs() > RELATIVE_DAYS => return None,
+        days if days > 0 => msg!("date_in_days", count = days),
+        days => msg!("date_days_ago", count = -days),
+    };
+    Some(text)
+}
+
+// A due date as the UI shows it, relative to today unless absolute dates are configured
+pub fn show_date(date: NaiveDate) -> String {
+    if ABSOLUTE_DATES.load(Ordering::Relaxed) {
+        return format_date(date);
+    }
+    humanize(date, Local::now().date_naive())
+}
+
+// Timestamps go by their UTC day, the same day their absolute form names, and
+// fall back to that form where a due date would show the bare date
+pub fn show_time(time: DateTime<Utc>, absolute_format: &str) -> String {
+    if ABSOLUTE_DATES.load(Ordering::Relaxed) {
+        return time.format(absolute_format).to_string();
+    }
+    relative(time.date_naive(), Utc::now().date_naive()).unwrap_or_else(|| time.format(absolute_format).to_string())
+}
+
+// A due date as typed: a calendar date, or a count of days from today
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DueSpec {
+    On(NaiveDate),
+    Days(i64),
+    // Counted over weekdays that are not holidays
+    BusinessDays(i64),
+    // The next such day, today included
+    Next(Weekday),
+}
+
+impl DueSpec {
+    // "2024-07-01", "today", "tomorrow", a weekday such as "friday" or "fri",
+    // "3 days" or "3 business days"
+    pub fn parse(text: &str) -> Option<DueSpec> {
+        let text = text.trim().trim_matches('"').trim().to_lowercase();
+        match text.as_str() {
+            "today" => return Some(DueSpec::Days(0)),
+            "tomorrow" => return Some(DueSpec::Days(1)),
+            _ => {}
+        }
+        if let Ok(date) = NaiveDate::parse_from_str(&text, "%Y-%m-%d") {
+            return Some(DueSpec::On(date));
+        }
+        if let Ok(day) = text.parse::<Weekday>() {
+            return Some(DueSpec::Next(day));
+        }
+        let words: Vec<&str> = text.split_whitespace().collect();
+        let (count, business, unit) = match words.as_slice() {
+            [count, unit] => (count, false, unit),
+            [count, "business", unit] => (count, true, unit),
+            _ => return None,
+        };
+        let count: i64 = count.parse().ok()?;
+        match (business, *unit) {
+            (false, "day" | "days") => Some(DueSpec::Days(count)),
+            (true, "day" | "days") => Some(DueSpec::BusinessDays(count)),
+            _ => None,
+        }
+    }
+
+    pub fn resolve(self, today: NaiveDate, holidays: &[NaiveDate]) -> Result<NaiveDate, TaskError> {
+        match self {
+            DueSpec::On(date) => Ok(date),
+            DueSpec::Days(days) => {
+                let days = u64::try_from(days).map_err(|_| TaskError::InvalidInput)?;
+                today.checked_add_days(Days::new(days)).ok_or(TaskError::InvalidInput)
+            }
+            DueSpec::BusinessDays(days) => add_business_days(today, days, holidays),
+            DueSpec::Next(day) => Ok(today + Days::new(u64::from(day.days_since(today.weekday())))),
+        }
+    }
+}
+
+// A reminder offset: "2d", "4h" or "30m"
+pub fn parse_offset(text: &str) -> Option<Duration> {
+    parse_suffixed(text, &[('d', 86_400), ('h', 3_600), ('m', 60)])
+}
+
+// A positive count followed by one of `units`, each with its length in seconds
+pub fn parse_suffixed(text: &str, units: &[(char, u64)]) -> Option<Duration> {
+    let (split, unit) = text.char_indices().next_back()?;
+    let &(_, unit_secs) = units.iter().find(|&&(suffix, _)| suffix == unit)?;
+    let count: u64 = text[..split].parse().ok().filter(|&count| count > 0)?;
+    count.checked_mul(unit_secs).map(Duration::from_secs)
+}
+
+// The largest of d, h or m that says it exactly
+pub fn format_offset(offset: Duration) -> String {
+    let minutes = offset.as_secs() / 60;
+    match minutes {
+        m if m % 1440 == 0 => format!("{}d", m / 1440),
+        m if m % 60 == 0 => format!("{}h", m / 60),
+        m => format!("{}m", m),
+    }
+}
+
+pub fn is_business_day(date: NaiveDate, holidays: &[NaiveDate]) -> bool {
+    !matches!(date.weekday(), Weekday::Sat | Weekday::Sun) && !holidays.contains(&date)
+}
+
+// `date` itself when it is a business day, else the first one after it
+pub fn next_business_day(date: NaiveDate, holidays: &[NaiveDate]) -> NaiveDate {
+    let mut date = date;
+    while !is_business_day(date, holidays) {
+        date = date.succ_opt().expect("dates this far out are not reachable");
+    }
+    date
+}
+
+// The `n`th business day after `start`. A start on a weekend or holiday counts
+// from there, so one business day after a Saturday is the Monday; zero days
+// rolls `start` forward to a business day.
+pub fn add_business_days(start: NaiveDate, n: i64, holidays: &[NaiveDate]) -> Result<NaiveDate, TaskError> {
+    if n < 0 {
+        return Err(TaskError::InvalidInput);
+    }
+    let mut date = start;
+    for _ in 0..n {
+        date = next_business_day(date.succ_opt().ok_or(TaskError::InvalidInput)?, holidays);
+    }
+    Ok(next_business_day(date, holidays))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn day(month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2024, month, day).unwrap()
+    }
+
+    #[test]
+    fn test_humanize() {
+        let today = day(7, 10);
+        let cases = [
+            (day(7, 10), "today"),
+            (day(7, 11), "tomorrow"),
+            (day(7, 9), "yesterday"),
+            (day(7, 15), "in 5 days"),
+            (day(7, 7), "3 days ago"),
+            (day(8, 9), "in 30 days"),
+            (day(8, 10), "2024-08-10"),
+            (day(6, 10), "30 days ago"),
+            (day(6, 9), "2024-06-09"),
+        ];
+        for (date, expected) in cases {
+            assert_eq!(humanize(date, today), expected);
+        }
+    }
+
+    #[test]
+    fn test_date_formats_must_write_a_date() {
+        assert_eq!(check_date_format("%d/%m/%Y"), Ok(()));
+        assert_eq!(check_date_format("%e %b %Y"), Ok(()));
+        for bad in ["%Y-%m-%d %H:%M", "%Q", "", " "] {
+            assert!(check_date_format(bad).is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn test_reminder_offsets() {
+        for (text, minutes) in [("2d", 2880), ("4h", 240), ("30m", 30), ("90m", 90)] {
+            let offset = parse_offset(text).unwrap();
+            assert_eq!(offset.as_secs(), minutes * 60);
+            assert_eq!(format_offset(offset), if text == "90m" { "90m" } else { text });
+        }
+        assert_eq!(format_offset(Duration::from_secs(86_400 * 7)), "7d");
+        for bad in ["", "d", "2", "2w", "-2d", "0h", "1.5h", "1é", "é", "2дн"] {
+            assert_eq!(parse_offset(bad), None, "{}", bad);
+        }
+    }
+
+    #[test]
+    fn test_add_business_days() {
+        // 2024-07-05 is a Friday and 2024-07-04 a holiday in the list
+        let holidays = [day(7, 4), day(7, 15)];
+        let cases = [
+            (day(7, 1), 0, day(7, 1)),
+            (day(7, 1), 3, day(7, 5)),
+            (day(7, 3), 1, day(7, 5)),
+            (day(7, 5), 1, day(7, 8)),
+            (day(7, 5), 3, day(7, 10)),
+            // Starting on a weekend
+            (day(7, 6), 0, day(7, 8)),
+            (day(7, 6), 1, day(7, 8)),
+            (day(7, 7), 2, day(7, 9)),
+            // The Monday after is a holiday too
+            (day(7, 12), 1, day(7, 16)),
+            (day(7, 1), 10, day(7, 17)),
+        ];
+        for (start, n, expected) in cases {
+            assert_eq!(add_business_days(start, n, &holidays).ok(), Some(expected), "{} + {}", start, n);
+        }
+        assert!(matches!(add_business_days(day(7, 1), -1, &holidays), Err(TaskError::InvalidInput)));
+    }
+
+    #[test]
+    fn test_parse_due_spec() {
+        let cases = [
+            ("2024-07-01", Some(DueSpec::On(day(7, 1)))),
+            ("today", Some(DueSpec::Days(0))),
+            ("Tomorrow", Some(DueSpec::Days(1))),
+            ("3 days", Some(DueSpec::Days(3))),
+            ("1 day", Some(DueSpec::Days(1))),
+            ("\"3 business days\"", Some(DueSpec::BusinessDays(3))),
+            ("-2 business days", Some(DueSpec::BusinessDays(-2))),
+            ("Friday", Some(DueSpec::Next(Weekday::Fri))),
+            ("tue", Some(DueSpec::Next(Weekday::Tue))),
+            ("3 weeks", None),
+            ("soon", None),
+            ("business days", None),
+        ];
+        for (text, expected) in cases {
+            assert_eq!(DueSpec::parse(text), expected, "{}", text);
+        }
+    }
+
+    #[test]
+    fn test_resolve_due_spec() {
+        let today = day(7, 5);
+        assert_eq!(DueSpec::Days(3).resolve(today, &[]).ok(), Some(day(7, 8)));
+        assert_eq!(DueSpec::BusinessDays(3).resolve(today, &[day(7, 9)]).ok(), Some(day(7, 11)));
+        assert!(matches!(DueSpec::BusinessDays(-3).resolve(today, &[]), Err(TaskError::InvalidInput)));
+        assert!(matches!(DueSpec::Days(-1).resolve(today, &[]), Err(TaskError::InvalidInput)));
+        // 2024-07-05 is a Friday
+        assert_eq!(DueSpec::Next(Weekday::Fri).resolve(today, &[]).ok(), Some(today));
+        assert_eq!(DueSpec::Next(Weekday::Mon).resolve(today, &[]).ok(), Some(day(7, 8)));
+        assert_eq!(DueSpec::Next(Weekday::Thu).resolve(today, &[]).ok(), Some(day(7, 11)));
+    }
+}
@@ -0,0 +1,171 @@
+use serde::Serialize;
+
+use crate::storage::{Storage, StorageReport};
+use crate::validation::note_count;
+use crate::{Task, TaskManager};
+
+// Tasks `debug info` lists as the largest
+const LARGEST: usize = 5;
+
+// One of the largest tasks, and the `[limits]` keys it is over
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LargeTask {
+    pub id: u32,
+    pub title: String,
+    pub description_chars: usize,
+    pub notes: usize,
+    pub tags: usize,
+    pub over: Vec<&'static str>,
+}
+
+// Everything `debug info` prints, for pasting into a bug report
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SizeReport {
+    pub version: &'static str,
+    pub tasks: usize,
+    // Distinct tags, compared case-insensitively, and task ids filed under them
+    pub tags: usize,
+    pub tag_links: usize,
+    pub titles_indexed: usize,
+    pub title_bytes: usize,
+    pub description_bytes: usize,
+    // Heap the tasks hold, not counting shared tag text; an estimate
+    pub task_heap_bytes: usize,
+    pub audit_entries: usize,
+    // By description length, then notes and tags; tasks already over a limit
+    // were stored before it was set or with --truncate off
+    pub largest: Vec<LargeTask>,
+    // None when made without the session's storage, as over the socket
+    pub storage: Option<StorageReport>,
+}
+
+impl SizeReport {
+    // One pass over the tasks and nothing copied, so it stays quick on large lists
+    pub fn gather(manager: &TaskManager, storage: Option<&dyn Storage>) -> SizeReport {
+        let (mut title_bytes, mut description_bytes, mut task_heap_bytes) = (0, 0, 0);
+        let tasks = manager.list_tasks();
+        for task in &tasks {
+            title_bytes += task.title.len();
+            description_bytes += task.description.len();
+            task_heap_bytes += task.heap_bytes();
+        }
+        let (tags, tag_links, titles_indexed) = manager.index_sizes();
+        SizeReport {
+            version: env!("CARGO_PKG_VERSION"),
+            tasks: tasks.len(),
+            tags,
+            tag_links,
+            titles_indexed,
+            title_bytes,
+            description_bytes,
+            task_heap_bytes,
+            audit_entries: manager.audit_len(),
+            largest: largest(&tasks, manager),
+            storage: storage.map(Storage::report),
+        }
+    }
+}
+
+fn largest(tasks: &[&Task], manager: &TaskManager) -> Vec<LargeTask> {
+    let limits = manager.limits();
+    let mut sized: Vec<LargeTask> = tasks
+        .iter()
+        .map(|task| {
+            let (description_chars, notes, tags) = (task.description.chars().count(), note_count(&task.description), task.tags.len());
+            let over = [
+                ("limits.description_chars", limits.description_chars, description_chars),
+                ("limits.notes_per_task", limits.notes_per_task, notes),
+                ("limits.tags_per_task", limits.tags_per_task, tags),
+            ]
+            .into_iter()
+            .filter(|(_, limit, size)| limit.is_some_and(|limit| *size > limit))
+            .map(|(key, _, _)| key)
+            .collect();
+            LargeTask { id: task.id, title: task.title.clone(), description_chars, notes, tags, over }
+        })
+        .collect();
+    sized.sort_by(|a, b| (b.description_chars, b.notes, b.tags, a.id).cmp(&(a.description_chars, a.notes, a.tags, b.id)));
+    sized.truncate(LARGEST);
+    sized
+}
+
+// 1536 -> "1.5 KiB"
+pub fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", size, UNITS[unit])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Priority;
+    use crate::storage::{FileStorage, StoreFormat};
+    use crate::validation::Limits;
+
+    #[test]
+    fn test_report_counts_tasks_tags_and_text() {
+        let mut manager = TaskManager::new();
+        let mut task = Task::new(0, "Write".to_string(), "Draft the report".to_string(), Priority::High);
+        task.add_tag("work");
+        task.add_tag("Q3");
+        manager.create_task(task).unwrap();
+        let mut task = Task::new(0, "Send".to_string(), String::new(), Priority::Low);
+        task.add_tag("WORK");
+        manager.create_task(task).unwrap();
+
+        let report = SizeReport::gather(&manager, None);
+        assert_eq!((report.tasks, report.tags, report.tag_links, report.titles_indexed), (2, 2, 3, 2));
+        assert_eq!((report.title_bytes, report.description_bytes), (9, 16));
+        assert!(report.task_heap_bytes >= 25);
+        assert_eq!(report.storage, None);
+        assert_eq!(report.largest.iter().map(|task| task.id).collect::<Vec<_>>(), [1, 2]);
+    }
+
+    #[test]
+    fn test_largest_tasks_name_the_limits_they_are_over() {
+        let mut manager = TaskManager::new();
+        for (title, description) in [("Short", "one line"), ("Long", "first\nsecond\nthird note here"), ("Empty", "")] {
+            manager.create_task(Task::new(0, title.to_string(), description.to_string(), Priority::Low)).unwrap();
+        }
+        // Limits set after the tasks were stored
+        manager.set_limits(Limits { description_chars: Some(20), notes_per_task: Some(2), tags_per_task: None });
+        let largest = SizeReport::gather(&manager, None).largest;
+        assert_eq!(largest.iter().map(|task| task.id).collect::<Vec<_>>(), [2, 1, 3]);
+        assert_eq!((largest[0].description_chars, largest[0].notes), (28, 3));
+        assert_eq!(largest[0].over, ["limits.description_chars", "limits.notes_per_task"]);
+        assert!(largest[1].over.is_empty());
+    }
+
+    #[test]
+    fn test_report_describes_the_data_file() {
+        let path = std::env::temp_dir().join(format!("task-manager-diagnostics-{}.json", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let storage = FileStorage::new(&path);
+        let manager = TaskManager::new();
+        let before = SizeReport::gather(&manager, Some(&storage)).storage.unwrap();
+        assert_eq!((before.format, before.bytes_on_disk, before.last_save_ms), (Some(StoreFormat::Json), None, None));
+
+        storage.save(&manager.to_state()).unwrap();
+        let after = SizeReport::gather(&manager, Some(&storage)).storage.unwrap();
+        assert_eq!(after.path.as_deref(), Some(path.as_path()));
+        assert_eq!(after.bytes_on_disk, Some(std::fs::metadata(&path).unwrap().len()));
+        assert!(after.last_save_ms.is_some());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_format_bytes() {
+        assert_eq!(format_bytes(512), "512 B");
+        assert_eq!(format_bytes(1536), "1.5 KiB");
+        assert_eq!(format_bytes(20 * 1024 * 1024), "20.0 MiB");
+    }
+}
@@ -0,0 +1,82 @@
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+use crate::Task;
+
+// One field that differs between two versions of a task, as stored
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FieldChange {
+    pub field: String,
+    // Null when the field is left out of that version
+    pub before: Value,
+    pub after: Value,
+}
+
+// Every stored field that differs between two versions of the same task.
+// Fields are compared in their serialized form, so a field added to Task is
+// compared without being listed here.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TaskDiff {
+    pub id: u32,
+    pub changes: Vec<FieldChange>,
+}
+
+fn fields(task: &Task) -> Map<String, Value> {
+    match serde_json::to_value(task) {
+        Ok(Value::Object(map)) => map,
+        _ => unreachable!("a task serializes to an object"),
+    }
+}
+
+impl TaskDiff {
+    // Changed fields in alphabetical order
+    pub fn between(before: &Task, after: &Task) -> TaskDiff {
+        let (before, after) = (fields(before), fields(after));
+        let mut names: Vec<&String> = before.keys().chain(after.keys()).collect();
+        names.sort();
+        names.dedup();
+        let changes = names
+            .into_iter()
+            .filter(|name| before.get(*name) != after.get(*name))
+            .map(|name| FieldChange {
+                field: name.clone(),
+                before: before.get(name).cloned().unwrap_or(Value::Null),
+                after: after.get(name).cloned().unwrap_or(Value::Null),
+            })
+            .collect();
+        TaskDiff { id: before["id"].as_u64().unwrap_or_default() as u32, changes }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    pub fn fields(&self) -> impl Iterator<Item = &str> {
+        self.changes.iter().map(|change| change.field.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Priority, schema};
+    use serde_json::json;
+
+    #[test]
+    fn test_changed_fields_only() {
+        let task = schema::sample_task();
+        assert!(TaskDiff::between(&task, &task.clone()).is_empty());
+
+        let mut edited = task.clone();
+        edited.title = "Write the report".to_string();
+        edited.pinned = false;
+        edited.key = None;
+        let diff = TaskDiff::between(&task, &edited);
+        assert_eq!(diff.id, 7);
+        assert_eq!(diff.fields().collect::<Vec<_>>(), ["key", "pinned", "title"]);
+        assert_eq!(diff.changes[0], FieldChange { field: "key".to_string(), before: json!("AUTH-1"), after: Value::Null });
+
+        let other = Task::new(7, "Write report".to_string(), "Q3 numbers".to_string(), Priority::High);
+        assert!(TaskDiff::between(&task, &other).fields().any(|field| field == "uid"));
+    }
+}
@@ -0,0 +1,276 @@
+use std::fmt::Write;
+
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+
+use crate::html;
+use crate::msg;
+use crate::render::pin_marker;
+use crate::{Task, TaskStatus, pinned_first};
+
+// Items listed per section when the config does not say otherwise
+pub const DEFAULT_DIGEST_LIMIT: usize = 5;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Period {
+    Day,
+    Week,
+}
+
+impl Period {
+    pub fn days(self) -> i64 {
+        match self {
+            Period::Day => 1,
+            Period::Week => 7,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SectionKind {
+    Overdue,
+    Due,
+    InProgress,
+    Completed,
+}
+
+// One heading of the digest: the first `limit` tasks and how many were left out
+#[derive(Debug, Clone, PartialEq)]
+pub struct Section {
+    pub kind: SectionKind,
+    pub tasks: Vec<Task>,
+    pub more: usize,
+}
+
+impl Section {
+    fn new(kind: SectionKind, mut tasks: Vec<Task>, limit: usize) -> Section {
+        let more = tasks.len().saturating_sub(limit);
+        tasks.truncate(limit);
+        Section { kind, tasks, more }
+    }
+
+    pub fn total(&self) -> usize {
+        self.tasks.len() + self.more
+    }
+
+    fn heading(&self, period: Period) -> String {
+        match (self.kind, period) {
+            (SectionKind::Overdue, _) => msg!("digest_overdue"),
+            (SectionKind::Due, Period::Day) => msg!("digest_due_today"),
+            (SectionKind::Due, Period::Week) => msg!("digest_due_week"),
+            (SectionKind::InProgress, _) => msg!("digest_in_progress"),
+            (SectionKind::Completed, _) => msg!("digest_completed"),
+        }
+    }
+}
+
+// The plan for a period starting on `date`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Digest {
+    pub date: NaiveDate,
+    pub period: Period,
+    pub open: usize,
+    // Overdue, due, in progress, completed; empty ones included
+    pub sections: Vec<Section>,
+}
+
+// Pure over the task set and the reference date: the period runs from `date` for
+// `period.days()` days, and "recently completed" covers the same span before it
+pub fn compose<'a>(tasks: impl IntoIterator<Item = &'a Task>, date: NaiveDate, period: Period, limit: usize) -> Digest {
+    let end = date + Duration::days(period.days());
+    let since = date - Duration::days(period.days());
+    let tasks: Vec<&Task> = tasks.into_iter().collect();
+    let open: Vec<&Task> = tasks.iter().copied().filter(|task| task.status != TaskStatus::Completed).collect();
+    let pick = |filter: &dyn Fn(&Task) -> bool, from: &[&Task]| -> Vec<Task> {
+        from.iter().copied().filter(|task| filter(task)).cloned().collect()
+    };
+
+    let mut overdue = pick(&|task| task.due.is_some_and(|due| due < date), &open);
+    overdue.sort_by(|a, b| pinned_first(a, b).then(a.due.cmp(&b.due)).then_with(|| b.priority.cmp(&a.priority)));
+    let mut due = pick(&|task| task.due.is_some_and(|due| due >= date && due < end), &open);
+    due.sort_by(|a, b| pinned_first(a, b).then(a.due.cmp(&b.due)).then_with(|| b.priority.cmp(&a.priority)));
+    let mut in_progress = pick(&|task| task.status == TaskStatus::InProgress, &open);
+    in_progress.sort_by(|a, b| pinned_first(a, b).then(b.priority.cmp(&a.priority)).then_with(|| a.id.cmp(&b.id)));
+    let mut completed = pick(
+        &|task| {
+            let day = task.updated_at.date_naive();
+            task.status == TaskStatus::Completed && day >= since && day < date
+        },
+        &tasks,
+    );
+    completed.sort_by(|a, b| b.updated_at.cmp(&a.updated_at).then_with(|| a.id.cmp(&b.id)));
+
+    Digest {
+        date,
+        period,
+        open: open.len(),
+        sections: vec![
+            Section::new(SectionKind::Overdue, overdue, limit),
+            Section::new(SectionKind::Due, due, limit),
+            Section::new(SectionKind::InProgress, in_progress, limit),
+            Section::new(SectionKind::Completed, completed, limit),
+        ],
+    }
+}
+
+impl Digest {
+    pub fn subject(&self) -> String {
+        match self.period {
+            Period::Day => msg!("digest_subject_day", date = self.date),
+            Period::Week => msg!("digest_subject_week", date = self.date),
+        }
+    }
+
+    fn count(&self, kind: SectionKind) -> usize {
+        self.sections.iter().find(|section| section.kind == kind).map_or(0, Section::total)
+    }
+
+    fn greeting(&self) -> String {
+        msg!(
+            "digest_greeting",
+            open = self.open,
+            overdue = self.count(SectionKind::Overdue),
+            due = self.count(SectionKind::Due),
+            in_progress = self.count(SectionKind::InProgress)
+        )
+    }
+
+    // Non-empty sections only
+    fn shown(&self) -> impl Iterator<Item = &Section> {
+        self.sections.iter().filter(|section| !section.tasks.is_empty())
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut out = self.greeting();
+        out.push('\n');
+        for section in self.shown() {
+            write!(out, "\n{}\n", section.heading(self.period)).unwrap();
+            for task in &section.tasks {
+                writeln!(out, "  - {}", text_line(task)).unwrap();
+            }
+            if section.more > 0 {
+                writeln!(out, "  {}", msg!("digest_more", count = section.more)).unwrap();
+            }
+        }
+        out
+    }
+
+    // An HTML body built from the report's table and escaping
+    pub fn to_html(&self) -> String {
+        let mut out = format!("<!DOCTYPE html>\n<html>\n<body>\n<p>{}</p>\n", html::escape(&self.greeting()));
+        for section in self.shown() {
+            writeln!(out, "<h2>{}</h2>", html::escape(&section.heading(self.period))).unwrap();
+            writeln!(out, "{}", html::task_table(&section.tasks, "")).unwrap();
+            if section.more > 0 {
+                writeln!(out, "<p>{}</p>", html::escape(&msg!("digest_more", count = section.more))).unwrap();
+            }
+        }
+        out.push_str("</body>\n</html>\n");
+        out
+    }
+
+    // RFC 2822 style headers, a blank line and the body, ready for `sendmail -t`
+    pub fn to_message(
+        &self,
+        body: &str,
+        html: bool,
+        from: Option<&str>,
+        to: Option<&str>,
+        now: DateTime<Utc>,
+    ) -> String {
+        let mut out = String::new();
+        if let Some(from) = from {
+            writeln!(out, "From: {}", from).unwrap();
+        }
+        if let Some(to) = to {
+            writeln!(out, "To: {}", to).unwrap();
+        }
+        writeln!(out, "Subject: {}", self.subject()).unwrap();
+        writeln!(out, "Date: {}", now.to_rfc2822()).unwrap();
+        writeln!(out, "MIME-Version: 1.0").unwrap();
+        let content_type = if html { "text/html" } else { "text/plain" };
+        writeln!(out, "Content-Type: {}; charset=utf-8", content_type).unwrap();
+        out.push('\n');
+        out.push_str(body);
+        out
+    }
+}
+
+fn text_line(task: &Task) -> String {
+    let title = format!("{}{}", pin_marker(task.pinned), task.title);
+    match task.due {
+        Some(due) => msg!("digest_item_due", id = task.id, title = title, priority = task.priority, date = due),
+        None => msg!("digest_item", id = task.id, title = title, priority = task.priority),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Priority;
+    use chrono::TimeZone;
+
+    fn day(d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2024, 7, d).unwrap()
+    }
+
+    fn task(id: u32, title: &str, due: Option<NaiveDate>, status: TaskStatus) -> Task {
+        let mut task = Task::new(id, title.to_string(), String::new(), Priority::Medium);
+        task.due = due;
+        task.status = status;
+        task
+    }
+
+    #[test]
+    fn test_week_sections_and_caps() {
+        let monday = day(8);
+        let mut tasks = vec![
+            task(1, "Late report", Some(day(3)), TaskStatus::Pending),
+            task(2, "Dentist", Some(day(10)), TaskStatus::Pending),
+            task(3, "Next month", Some(day(20)), TaskStatus::Pending),
+            task(4, "Refactor", None, TaskStatus::InProgress),
+            task(5, "Shipped", None, TaskStatus::Completed),
+            task(6, "Long ago", None, TaskStatus::Completed),
+        ];
+        tasks[4].updated_at = Utc.with_ymd_and_hms(2024, 7, 5, 16, 0, 0).unwrap();
+        tasks[5].updated_at = Utc.with_ymd_and_hms(2024, 6, 1, 9, 0, 0).unwrap();
+        for id in 7..=9 {
+            tasks.push(task(id, &format!("Errand {}", id), Some(day(9)), TaskStatus::Pending));
+        }
+
+        let digest = compose(&tasks, monday, Period::Week, 2);
+        let ids = |kind: SectionKind| -> Vec<u32> {
+            digest.sections.iter().find(|s| s.kind == kind).unwrap().tasks.iter().map(|t| t.id).collect()
+        };
+        assert_eq!(ids(SectionKind::Overdue), vec![1]);
+        assert_eq!(ids(SectionKind::Due), vec![7, 8]);
+        assert_eq!(digest.sections[1].more, 2);
+        assert_eq!(ids(SectionKind::InProgress), vec![4]);
+        assert_eq!(ids(SectionKind::Completed), vec![5]);
+        assert_eq!(digest.open, 7);
+
+        let text = digest.to_text();
+        assert!(text.starts_with("7 open tasks: 1 overdue, 4 due, 1 in progress."));
+        assert!(text.contains("Due this week\n  - #7 Errand 7 (Medium, due 2024-07-09)"));
+        assert!(text.contains("...and 2 more"));
+        assert!(!text.contains("Next month"));
+    }
+
+    #[test]
+    fn test_day_period_and_headers() {
+        let tasks = [
+            task(1, "Today", Some(day(8)), TaskStatus::Pending),
+            task(2, "Tomorrow", Some(day(9)), TaskStatus::Pending),
+        ];
+        let digest = compose(&tasks, day(8), Period::Day, DEFAULT_DIGEST_LIMIT);
+        let body = digest.to_text();
+        assert!(body.contains("Due today\n  - #1 Today"));
+        assert!(!body.contains("Tomorrow"));
+
+        let now = Utc.with_ymd_and_hms(2024, 7, 8, 7, 0, 0).unwrap();
+        let message = digest.to_message(&body, false, Some("me@example.com"), Some("me@example.com"), now);
+        assert!(message.starts_with("From: me@example.com\nTo: me@example.com\nSubject: Task digest for 2024-07-08\n"));
+        assert!(message.contains("Date: Mon, 8 Jul 2024 07:00:00 +0000\n"));
+        assert!(message.contains("Content-Type: text/plain; charset=utf-8\n\n2 open tasks"));
+        assert!(digest.to_html().contains("<h2>Due today</h2>"));
+    }
+}
@@ -0,0 +1,217 @@
+use std::time::Duration;
+
+use chrono::{DateTime, TimeDelta, Utc};
+use serde::Serialize;
+
+use crate::msg;
+use crate::pressure;
+use crate::{Task, TaskStatus};
+
+// Weeks of completed work the forecast pace is taken from when the config does not say
+pub const DEFAULT_FORECAST_WEEKS: u32 = 4;
+
+// The estimated work in a set of tasks. Tasks without an estimate of their own
+// count the default one, kept apart so the report can say how much is guessed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct Effort {
+    pub tasks: usize,
+    // Tasks with their own estimate, and its total in minutes
+    pub estimated: usize,
+    pub estimated_minutes: u64,
+    // The default estimate's minutes for each of the rest
+    pub default_minutes: u64,
+}
+
+impl Effort {
+    fn add(&mut self, task: &Task, default: Duration) {
+        self.tasks += 1;
+        match pressure::estimate(task) {
+            Some(estimate) => {
+                self.estimated += 1;
+                self.estimated_minutes += estimate.as_secs() / 60;
+            }
+            None => self.default_minutes += default.as_secs() / 60,
+        }
+    }
+
+    pub fn minutes(&self) -> u64 {
+        self.estimated_minutes + self.default_minutes
+    }
+
+    // Share of the tasks with their own estimate, in percent; None without tasks
+    pub fn coverage(&self) -> Option<f64> {
+        (self.tasks > 0).then(|| self.estimated as f64 * 100.0 / self.tasks as f64)
+    }
+}
+
+// When the open work runs out at the recent completion pace
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(tag = "kind", rename_all = "kebab-case")]
+pub enum Forecast {
+    // Nothing is open
+    Done,
+    // Nothing was completed in the window, so there is no pace
+    NoHistory,
+    // Whole weeks, rounded up
+    Weeks { weeks: u64 },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct EffortStats {
+    pub open: Effort,
+    pub completed: Effort,
+    // The window the pace is taken over, and the minutes completed a week in it
+    pub weeks: u32,
+    pub pace_minutes: u64,
+    pub forecast: Forecast,
+}
+
+impl EffortStats {
+    // Completed share of all estimated work, in percent; None with no work at all
+    pub fn completion_rate(&self) -> Option<f64> {
+        let total = self.open.minutes() + self.completed.minutes();
+        (total > 0).then(|| self.completed.minutes() as f64 * 100.0 / total as f64)
+    }
+
+    pub fn lines(&self) -> Vec<String> {
+        let mut lines = vec![
+            msg!("effort_header"),
+            msg!("effort_open", effort = hours(self.open.minutes()), count = self.open.tasks),
+            msg!("effort_completed", effort = hours(self.completed.minutes()), count = self.completed.tasks),
+        ];
+        if let Some(rate) = self.completion_rate() {
+            lines.push(msg!("effort_completion_rate", rate = format!("{:.1}", rate)));
+        }
+        if let Some(coverage) = self.open.coverage() {
+            lines.push(msg!("effort_coverage", percent = format!("{:.0}", coverage)));
+        }
+        let guessed = self.open.tasks - self.open.estimated;
+        if guessed > 0 {
+            lines.push(msg!("effort_defaulted", count = guessed, effort = hours(self.open.default_minutes)));
+        }
+        lines.push(match self.forecast {
+            Forecast::Done => msg!("forecast_done"),
+            Forecast::NoHistory => msg!("forecast_no_history", weeks = self.weeks),
+            Forecast::Weeks { weeks } => {
+                msg!("forecast_weeks", pace = hours(self.pace_minutes), window = self.weeks, count = weeks)
+            }
+        });
+        lines
+    }
+}
+
+// "45m", "4h" or "12.5h"
+pub fn hours(minutes: u64) -> String {
+    match minutes {
+        m if m < 60 => format!("{}m", m),
+        m if m % 60 == 0 => format!("{}h", m / 60),
+        m => format!("{:.1}h", m as f64 / 60.0),
+    }
+}
+
+// Minutes completed a week over the `weeks` weeks before `now`, from each
+// completed task's completion time and effort
+pub fn pace(completions: impl IntoIterator<Item = (DateTime<Utc>, u64)>, now: DateTime<Utc>, weeks: u32) -> u64 {
+    if weeks == 0 {
+        return 0;
+    }
+    // A window reaching past the start of time takes in all history
+    let since = TimeDelta::try_weeks(i64::from(weeks))
+        .and_then(|window| now.checked_sub_signed(window))
+        .unwrap_or(DateTime::<Utc>::MIN_UTC);
+    let done: u64 = completions.into_iter().filter(|(at, _)| *at > since && *at <= now).map(|(_, minutes)| minutes).sum();
+    done / u64::from(weeks)
+}
+
+// Pure over the open minutes and the weekly pace
+pub fn forecast(open_minutes: u64, pace_minutes: u64) -> Forecast {
+    match (open_minutes, pace_minutes) {
+        (0, _) => Forecast::Done,
+        (_, 0) => Forecast::NoHistory,
+        (open, pace) => Forecast::Weeks { weeks: open.div_ceil(pace) },
+    }
+}
+
+pub fn build<'a>(tasks: impl IntoIterator<Item = &'a Task>, now: DateTime<Utc>, default: Duration, weeks: u32) -> EffortStats {
+    let (mut open, mut completed) = (Effort::default(), Effort::default());
+    let mut completions = Vec::new();
+    for task in tasks {
+        if task.status != TaskStatus::Completed {
+            open.add(task, default);
+            continue;
+        }
+        let before = completed.minutes();
+        completed.add(task, default);
+        completions.push((task.completed_at.unwrap_or(task.updated_at), completed.minutes() - before));
+    }
+    let pace_minutes = pace(completions, now, weeks);
+    EffortStats { open, completed, weeks, pace_minutes, forecast: forecast(open.minutes(), pace_minutes) }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+    use crate::Priority;
+
+    fn now() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 7, 31, 12, 0, 0).unwrap()
+    }
+
+    fn task(id: u32, estimate: Option<&str>, completed_days_ago: Option<i64>) -> Task {
+        let mut task = Task::new(id, format!("Task {}", id), String::new(), Priority::Medium);
+        if let Some(estimate) = estimate {
+            task.fields.insert(pressure::ESTIMATE_FIELD.to_string(), estimate.to_string());
+        }
+        if let Some(days) = completed_days_ago {
+            task.status = TaskStatus::Completed;
+            task.completed_at = Some(now() - TimeDelta::days(days));
+        }
+        task
+    }
+
+    #[test]
+    fn test_forecast_edge_cases() {
+        assert_eq!(forecast(0, 0), Forecast::Done);
+        assert_eq!(forecast(0, 240), Forecast::Done);
+        assert_eq!(forecast(600, 0), Forecast::NoHistory);
+        assert_eq!(forecast(1440, 240), Forecast::Weeks { weeks: 6 });
+        assert_eq!(forecast(1441, 240), Forecast::Weeks { weeks: 7 });
+        assert_eq!(forecast(1, 240), Forecast::Weeks { weeks: 1 });
+    }
+
+    #[test]
+    fn test_pace_counts_only_the_window() {
+        let at = |days: i64, minutes| (now() - TimeDelta::days(days), minutes);
+        assert_eq!(pace([at(1, 600), at(20, 360), at(29, 1000)], now(), 4), 240);
+        assert_eq!(pace([at(1, 600)], now(), 0), 0);
+        assert_eq!(pace(Vec::new(), now(), 4), 0);
+        assert_eq!(pace([at(1, 600), at(100_000, 600)], now(), u32::MAX), 0);
+        assert_eq!(pace([at(1, 6_000_000_000_000)], now(), u32::MAX), 1396);
+    }
+
+    #[test]
+    fn test_build_weighs_tasks_by_estimate() {
+        let tasks = [
+            task(1, Some("2d"), None),
+            task(2, Some("30m"), None),
+            task(3, None, None),
+            task(4, Some("8h"), Some(3)),
+            task(5, None, Some(10)),
+            // Too long ago for the pace, still in the completed total
+            task(6, Some("4h"), Some(60)),
+        ];
+        let stats = build(&tasks, now(), Duration::from_secs(2 * 3600), 2);
+        assert_eq!(stats.open, Effort { tasks: 3, estimated: 2, estimated_minutes: 2910, default_minutes: 120 });
+        assert_eq!(stats.completed.minutes(), 8 * 60 + 120 + 4 * 60);
+        assert_eq!(stats.pace_minutes, (8 * 60 + 120) / 2);
+        assert_eq!(stats.forecast, Forecast::Weeks { weeks: 11 });
+        assert_eq!(format!("{:.1}", stats.completion_rate().unwrap()), "21.7");
+        assert_eq!(format!("{:.0}", stats.open.coverage().unwrap()), "67");
+
+        let empty = build(&[], now(), Duration::from_secs(3600), 4);
+        assert_eq!((empty.forecast, empty.completion_rate(), empty.open.coverage()), (Forecast::Done, None, None));
+        assert_eq!((hours(45), hours(240), hours(750)), ("45m".to_string(), "4h".to_string(), "12.5h".to_string()));
+    }
+}
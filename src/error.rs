@@ -0,0 +1,190 @@
+use std::fmt;
+
+use serde::{Serialize, Serializer};
+
+use crate::TaskStatus;
+
+/// Why a TaskManager call failed. More kinds of failure may be added, so
+/// matches need a wildcard arm.
+///
+/// ```
+/// use task_manager::{TaskError, TaskManager};
+///
+/// match TaskManager::new().get_task(9) {
+///     Err(TaskError::TaskNotFound { id }) => assert_eq!(id, 9),
+///     other => panic!("unexpected {:?}", other),
+/// }
+/// assert_eq!(TaskError::TaskNotFound { id: 9 }.to_string(), "Task 9 not found");
+/// ```
+///
+/// Serializes as one flat object named by its variant, which scripts match on:
+///
+/// ```
+/// use task_manager::TaskError;
+///
+/// let err = TaskError::DuplicateTask { title: "Buy milk".to_string(), existing_id: 3 };
+/// let json = serde_json::to_value(&err).unwrap();
+/// assert_eq!(json, serde_json::json!({ "error": "DuplicateTask", "title": "Buy milk", "existing_id": 3 }));
+/// ```
+#[derive(Debug, Serialize)]
+#[serde(tag = "error")]
+#[non_exhaustive]
+pub enum TaskError {
+    TaskNotFound { id: u32 },
+    // No open task's title starts with the text given in place of an id
+    NoTitleMatch {
+        #[serde(rename = "title")]
+        text: String,
+    },
+    // Several open tasks' titles start with it: (id, title) of each
+    AmbiguousTitle {
+        #[serde(rename = "title")]
+        text: String,
+        #[serde(serialize_with = "title_matches")]
+        matches: Vec<(u32, String)>,
+    },
+    InvalidInput,
+    // Input that is well-formed but cannot apply, and why
+    Invalid { reason: String },
+    DuplicateTask { title: String, existing_id: u32 },
+    // The change would alter a completed task while the guard is on
+    TaskCompleted { id: u32 },
+    // The task was created by `owner` and others' tasks are protected
+    NotOwner { id: u32, owner: String },
+    // The configured workflow does not allow this status change; `allowed` lists
+    // where the task may go instead
+    InvalidTransition { from: TaskStatus, to: TaskStatus, allowed: Vec<TaskStatus> },
+    #[serde(serialize_with = "detail")]
+    Storage(String),
+    // A remote service such as the GitHub API failed or refused the request
+    #[serde(serialize_with = "detail")]
+    Remote(String),
+    // A file handed to `import` could not be read as the format it claims
+    #[serde(serialize_with = "detail")]
+    Import(String),
+    // Every active validation rule the task broke
+    #[serde(serialize_with = "violations")]
+    ValidationFailed(Vec<String>),
+}
+
+// The variants holding bare values get a named field, since the variant's name
+// is written into the same object as its fields
+
+fn detail<S: Serializer>(detail: &str, serializer: S) -> Result<S::Ok, S::Error> {
+    #[derive(Serialize)]
+    struct Detail<'a> {
+        detail: &'a str,
+    }
+    Detail { detail }.serialize(serializer)
+}
+
+fn violations<S: Serializer>(violations: &[String], serializer: S) -> Result<S::Ok, S::Error> {
+    #[derive(Serialize)]
+    struct Violations<'a> {
+        violations: &'a [String],
+    }
+    Violations { violations }.serialize(serializer)
+}
+
+fn title_matches<S: Serializer>(matches: &[(u32, String)], serializer: S) -> Result<S::Ok, S::Error> {
+    #[derive(Serialize)]
+    struct Match<'a> {
+        id: u32,
+        title: &'a str,
+    }
+    serializer.collect_seq(matches.iter().map(|(id, title)| Match { id: *id, title }))
+}
+
+impl fmt::Display for TaskError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TaskError::TaskNotFound { id } => write!(f, "Task {} not found", id),
+            TaskError::NoTitleMatch { text } => write!(f, "No open task starts with '{}'", text),
+            TaskError::AmbiguousTitle { text, matches } => {
+                let ids: Vec<String> = matches.iter().map(|(id, _)| id.to_string()).collect();
+                write!(f, "Several open tasks start with '{}' (IDs: {})", text, ids.join(", "))
+            }
+            TaskError::InvalidInput => write!(f, "Invalid input provided"),
+            TaskError::Invalid { reason } => write!(f, "Invalid input: {}", reason),
+            TaskError::DuplicateTask { existing_id, .. } => {
+                write!(f, "Task with this title already exists (ID: {})", existing_id)
+            }
+            TaskError::TaskCompleted { id } => write!(f, "Task {} is completed", id),
+            TaskError::NotOwner { id, owner } => write!(f, "Task {} belongs to {}", id, owner),
+            TaskError::InvalidTransition { from, to, .. } => write!(f, "Cannot move a task from {} to {}", from, to),
+            TaskError::Storage(msg) => write!(f, "Storage error: {}", msg),
+            TaskError::Remote(msg) => write!(f, "Remote error: {}", msg),
+            TaskError::Import(msg) => write!(f, "Import error: {}", msg),
+            TaskError::ValidationFailed(violations) => write!(f, "Validation failed: {}", violations.join("; ")),
+        }
+    }
+}
+
+impl std::error::Error for TaskError {}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn serialized(err: TaskError) -> serde_json::Value {
+        serde_json::to_value(&err).expect("every variant serializes")
+    }
+
+    // Scripts match on these names, so each variant's are pinned
+    #[test]
+    fn test_every_variant_serializes_with_stable_field_names() {
+        assert_eq!(serialized(TaskError::TaskNotFound { id: 5 }), json!({ "error": "TaskNotFound", "id": 5 }));
+        assert_eq!(
+            serialized(TaskError::NoTitleMatch { text: "buy".to_string() }),
+            json!({ "error": "NoTitleMatch", "title": "buy" })
+        );
+        assert_eq!(
+            serialized(TaskError::AmbiguousTitle { text: "buy".to_string(), matches: vec![(1, "Buy milk".to_string()), (4, "Buy bread".to_string())] }),
+            json!({
+                "error": "AmbiguousTitle",
+                "title": "buy",
+                "matches": [{ "id": 1, "title": "Buy milk" }, { "id": 4, "title": "Buy bread" }],
+            })
+        );
+        assert_eq!(serialized(TaskError::InvalidInput), json!({ "error": "InvalidInput" }));
+        assert_eq!(
+            serialized(TaskError::Invalid { reason: "no tag".to_string() }),
+            json!({ "error": "Invalid", "reason": "no tag" })
+        );
+        assert_eq!(
+            serialized(TaskError::DuplicateTask { title: "Buy milk".to_string(), existing_id: 3 }),
+            json!({ "error": "DuplicateTask", "title": "Buy milk", "existing_id": 3 })
+        );
+        assert_eq!(serialized(TaskError::TaskCompleted { id: 2 }), json!({ "error": "TaskCompleted", "id": 2 }));
+        assert_eq!(
+            serialized(TaskError::NotOwner { id: 2, owner: "ana".to_string() }),
+            json!({ "error": "NotOwner", "id": 2, "owner": "ana" })
+        );
+        assert_eq!(
+            serialized(TaskError::InvalidTransition {
+                from: TaskStatus::Completed,
+                to: TaskStatus::InProgress,
+                allowed: vec![TaskStatus::Pending],
+            }),
+            json!({ "error": "InvalidTransition", "from": "Completed", "to": "InProgress", "allowed": ["Pending"] })
+        );
+        assert_eq!(
+            serialized(TaskError::Storage("disk full".to_string())),
+            json!({ "error": "Storage", "detail": "disk full" })
+        );
+        assert_eq!(
+            serialized(TaskError::Remote("HTTP 502".to_string())),
+            json!({ "error": "Remote", "detail": "HTTP 502" })
+        );
+        assert_eq!(
+            serialized(TaskError::Import("unequal lengths".to_string())),
+            json!({ "error": "Import", "detail": "unequal lengths" })
+        );
+        assert_eq!(
+            serialized(TaskError::ValidationFailed(vec!["title too long".to_string()])),
+            json!({ "error": "ValidationFailed", "violations": ["title too long"] })
+        );
+    }
+}
@@ -0,0 +1,149 @@
+use std::io::{self, Write};
+use std::sync::mpsc::{Receiver, RecvTimeoutError, SyncSender, TrySendError, sync_channel};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::Duration;
+
+use log::warn;
+use serde_json::json;
+
+use crate::{Subscriber, TaskEvent, TaskManager};
+
+// Messages a subscriber may fall behind by before it is dropped
+const BUFFER: usize = 256;
+
+// Fans each task event out to every open `GET /events` stream. Publishing never
+// blocks: a stream whose buffer is full is cut off instead of holding up the
+// mutation that raised the event.
+#[derive(Default)]
+pub struct EventHub {
+    clients: Mutex<Vec<SyncSender<String>>>,
+}
+
+impl EventHub {
+    pub fn new() -> Arc<EventHub> {
+        Arc::new(EventHub::default())
+    }
+
+    // A manager subscriber that publishes every event to this hub
+    pub fn subscriber(self: &Arc<Self>) -> Subscriber {
+        let hub = Arc::clone(self);
+        Box::new(move |event, manager| hub.publish(message(event, manager)))
+    }
+
+    // Each receiver gets every message published from now on, until it lags too far
+    pub fn subscribe(&self) -> Receiver<String> {
+        let (sender, receiver) = sync_channel(BUFFER);
+        self.clients.lock().unwrap_or_else(PoisonError::into_inner).push(sender);
+        receiver
+    }
+
+    pub fn publish(&self, message: String) {
+        self.clients.lock().unwrap_or_else(PoisonError::into_inner).retain(|client| {
+            match client.try_send(message.clone()) {
+                Ok(()) => true,
+                Err(TrySendError::Full(_)) => {
+                    warn!("dropping an event stream that stopped reading");
+                    false
+                }
+                Err(TrySendError::Disconnected(_)) => false,
+            }
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.clients.lock().unwrap_or_else(PoisonError::into_inner).len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+// One SSE message: `event:` is created, updated, deleted or restored, and `data:`
+// the task as the rest of the API shows it (just the id once it is gone); a
+// saved view changing is a `view` event naming it
+pub fn message(event: &TaskEvent, manager: &TaskManager) -> String {
+    let (kind, id) = match event {
+        TaskEvent::Added(id) => ("created", Some(*id)),
+        TaskEvent::StatusChanged { id, .. }
+        | TaskEvent::PriorityChanged { id, .. }
+        | TaskEvent::Tagged { id, .. }
+        | TaskEvent::Edited(id)
+        | TaskEvent::Notified(id) => ("updated", Some(*id)),
+        TaskEvent::Deleted(id) => ("deleted", Some(*id)),
+        TaskEvent::Restored => ("restored", None),
+        TaskEvent::ViewChanged { name } => return format!("event: view\ndata: {}\n\n", json!({ "name": name })),
+        TaskEvent::TagDescribed { tag } => return format!("event: tag\ndata: {}\n\n", json!({ "tag": tag })),
+        TaskEvent::GoalChanged => return "event: goal\ndata: {}\n\n".to_string(),
+        TaskEvent::RetentionApplied { archived, purged } => {
+            return format!("event: retention\ndata: {}\n\n", json!({ "archived": archived, "purged": purged }));
+        }
+        // The change itself comes as its own event
+        TaskEvent::Overridden { id, owner } => {
+            return format!("event: override\ndata: {}\n\n", json!({ "id": id, "owner": owner }));
+        }
+    };
+    let data = match id {
+        Some(id) => manager.get_task(id).map_or_else(|_| json!({ "id": id }), |task| json!(task)),
+        None => json!({}),
+    };
+    format!("event: {}\ndata: {}\n\n", kind, data)
+}
+
+// Write an HTTP response head and then the messages as they arrive, with a
+// comment line whenever `heartbeat` passes quietly. Returns once the hub drops
+// the stream or the client goes away.
+pub fn stream(writer: &mut dyn Write, messages: &Receiver<String>, heartbeat: Duration) -> io::Result<()> {
+    write!(
+        writer,
+        "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nCache-Control: no-cache\r\nConnection: close\r\n\r\n"
+    )?;
+    writer.flush()?;
+    loop {
+        match messages.recv_timeout(heartbeat) {
+            Ok(message) => writer.write_all(message.as_bytes())?,
+            Err(RecvTimeoutError::Timeout) => writer.write_all(b": heartbeat\n\n")?,
+            Err(RecvTimeoutError::Disconnected) => return Ok(()),
+        }
+        writer.flush()?;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Priority;
+
+    #[test]
+    fn test_every_subscriber_gets_every_event() {
+        let hub = EventHub::new();
+        let mut manager = TaskManager::new();
+        manager.subscribe(hub.subscriber());
+        let (first, second) = (hub.subscribe(), hub.subscribe());
+
+        let id = manager.add_task("Deploy".to_string(), String::new(), Priority::High).unwrap();
+        manager.delete_task(id).unwrap();
+        for receiver in [&first, &second] {
+            let created = receiver.try_recv().unwrap();
+            assert!(created.starts_with("event: created\ndata: {"));
+            assert!(created.contains("\"title\":\"Deploy\"") && created.ends_with("}\n\n"));
+            assert_eq!(receiver.try_recv().unwrap(), "event: deleted\ndata: {\"id\":1}\n\n");
+        }
+    }
+
+    #[test]
+    fn test_slow_and_closed_streams_are_dropped() {
+        let hub = EventHub::new();
+        let slow = hub.subscribe();
+        drop(hub.subscribe());
+        hub.publish("event: created\ndata: {}\n\n".to_string());
+        assert_eq!(hub.len(), 1);
+
+        for _ in 0..BUFFER {
+            hub.publish(": filler\n\n".to_string());
+        }
+        assert!(hub.is_empty());
+        // What was already buffered is still delivered before the stream ends
+        assert_eq!(slow.iter().count(), BUFFER);
+    }
+}
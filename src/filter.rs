@@ -0,0 +1,347 @@
+use std::borrow::Borrow;
+use std::cmp::Ordering;
+use std::str::FromStr;
+
+use crate::command::ParseError;
+use crate::search::{self, SearchText};
+use crate::task::valid_field_key;
+use crate::{Energy, Priority, Task, TaskError, TaskStatus};
+
+/// A combined query for TaskManager::find: every criterion that is set must hold.
+///
+/// ```
+/// use task_manager::{Priority, TaskFilter, TaskManager};
+///
+/// let mut manager = TaskManager::new();
+/// manager.add_task("Fix login".to_string(), String::new(), Priority::High).unwrap();
+/// manager.add_task("Fix typo".to_string(), String::new(), Priority::Low).unwrap();
+/// let filter = TaskFilter { priority: Some(Priority::High), ..TaskFilter::keyword("fix") };
+/// assert_eq!(manager.find(&filter).len(), 1);
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TaskFilter {
+    pub status: Option<TaskStatus>,
+    pub priority: Option<Priority>,
+    pub energy: Option<Energy>,
+    // The task must carry every one of these (case-insensitive)
+    pub tags: Vec<String>,
+    // ...and none of these
+    pub without_tags: Vec<String>,
+    // Case-insensitive text in the title, description or a tag, as `filter` matches
+    pub keyword: Option<String>,
+    // (key, value) custom fields the task must have, values compared case-insensitively
+    pub fields: Vec<(String, String)>,
+    // The user the task's `assignee` field or creator must be; ME until the
+    // manager puts in who is asking
+    pub owner: Option<String>,
+}
+
+// The `owner` of a `mine` clause
+pub const ME: &str = "me";
+
+impl TaskFilter {
+    /// Tasks with the text in their title, description, a tag or a field.
+    ///
+    /// ```
+    /// # use task_manager::TaskFilter;
+    /// assert_eq!(TaskFilter::keyword("tax").keyword.as_deref(), Some("tax"));
+    /// ```
+    pub fn keyword(keyword: impl Into<String>) -> Self {
+        TaskFilter { keyword: Some(keyword.into()), ..TaskFilter::default() }
+    }
+
+    /// `filter` text: each `key:value` word with a valid field key matches that
+    /// custom field, and the remaining words are the keyword.
+    ///
+    /// ```
+    /// # use task_manager::TaskFilter;
+    /// let filter = TaskFilter::query("sprint:12 login");
+    /// assert_eq!(filter.fields, [("sprint".to_string(), "12".to_string())]);
+    /// assert_eq!(filter.keyword.as_deref(), Some("login"));
+    /// ```
+    pub fn query(text: &str) -> Self {
+        let mut fields = Vec::new();
+        let mut words = Vec::new();
+        for word in text.split_whitespace() {
+            match word.split_once(':') {
+                Some((key, value)) if valid_field_key(key) && !value.is_empty() => {
+                    fields.push((key.to_string(), value.to_string()))
+                }
+                _ => words.push(word),
+            }
+        }
+        let keyword = if words.is_empty() && !fields.is_empty() { None } else { Some(words.join(" ")) };
+        TaskFilter { keyword, fields, ..TaskFilter::default() }
+    }
+
+    /// A saved view's query: `status:<status>`, `priority:<level>`, `energy:<level>`,
+    /// `tag:<tag>` and `-tag:<tag>` clauses, custom `key:value` fields, `mine`, and
+    /// the rest as the keyword.
+    ///
+    /// ```
+    /// # use task_manager::{TaskFilter, TaskStatus};
+    /// let filter = TaskFilter::parse("status:pending tag:work -tag:later report").unwrap();
+    /// assert_eq!(filter.status, Some(TaskStatus::Pending));
+    /// assert_eq!((filter.tags, filter.without_tags), (vec!["work".to_string()], vec!["later".to_string()]));
+    /// assert!(TaskFilter::parse("priority:urgent").is_err());
+    /// ```
+    pub fn parse(text: &str) -> Result<Self, ParseError> {
+        let mut filter = TaskFilter::default();
+        let mut words = Vec::new();
+        for word in text.split_whitespace() {
+            match word.split_once(':') {
+                Some(("status", status)) => {
+                    let parsed = TaskStatus::from_str(status);
+                    filter.status = Some(parsed.map_err(|_| ParseError::InvalidStatus(status.to_string()))?);
+                }
+                Some(("priority", level)) => {
+                    let parsed = Priority::from_str(level);
+                    filter.priority = Some(parsed.map_err(|_| ParseError::InvalidPriority(level.to_string()))?);
+                }
+                Some(("energy", level)) => {
+                    let parsed = Energy::from_str(level);
+                    filter.energy = Some(parsed.map_err(|_| ParseError::InvalidEnergy(level.to_string()))?);
+                }
+                Some(("tag", tag)) if !tag.is_empty() => filter.tags.push(tag.to_string()),
+                Some(("-tag", tag)) if !tag.is_empty() => filter.without_tags.push(tag.to_string()),
+                Some((key, value)) if valid_field_key(key) && !value.is_empty() => {
+                    filter.fields.push((key.to_string(), value.to_string()))
+                }
+                None if word.eq_ignore_ascii_case("mine") => filter.owner = Some(ME.to_string()),
+                _ => words.push(word),
+            }
+        }
+        if !words.is_empty() {
+            filter.keyword = Some(words.join(" "));
+        }
+        Ok(filter)
+    }
+
+    /// Every criterion, tags included, checked against one task. The keyword is
+    /// matched case- and accent-insensitively, as `filter` does by default.
+    ///
+    /// ```
+    /// # use task_manager::{Priority, Task, TaskFilter};
+    /// let mut task = Task::new(1, "Résumé".to_string(), String::new(), Priority::Low);
+    /// task.add_tag("jobs");
+    /// assert!(TaskFilter::parse("tag:jobs resume").unwrap().matches(&task));
+    /// ```
+    pub fn matches(&self, task: &Task) -> bool {
+        let keyword = self.keyword.as_deref().is_none_or(|keyword| {
+            SearchText::new(task, true).contains(&search::fold(keyword, true))
+        });
+        keyword && self.tags.iter().all(|tag| has_tag(task, tag)) && self.matches_fields(task)
+    }
+
+    // Everything but the tags, which the manager answers from its index, and the
+    // keyword, which it looks up in its folded search text
+    pub(crate) fn matches_fields(&self, task: &Task) -> bool {
+        self.status.as_ref().is_none_or(|status| task.status == *status)
+            && self.priority.as_ref().is_none_or(|priority| task.priority == *priority)
+            && self.energy.is_none_or(|energy| task.energy == Some(energy))
+            && self.fields.iter().all(|(key, value)| task.fields.get(key).is_some_and(|v| v.eq_ignore_ascii_case(value)))
+            && !self.without_tags.iter().any(|tag| has_tag(task, tag))
+            && self.owner.as_deref().is_none_or(|owner| is_owner(task, owner))
+    }
+}
+
+// Assigned to `user` or created by them
+fn is_owner(task: &Task, user: &str) -> bool {
+    let assignee = task.fields.get("assignee").map(String::as_str);
+    [assignee, task.created_by.as_deref()].into_iter().flatten().any(|who| who.trim().eq_ignore_ascii_case(user))
+}
+
+fn has_tag(task: &Task, tag: &str) -> bool {
+    let tag = tag.trim().to_lowercase();
+    task.tags.iter().any(|t| t.trim().to_lowercase() == tag)
+}
+
+/// The order `list --sort` puts tasks in; ties keep id order.
+///
+/// ```
+/// use task_manager::SortKey;
+///
+/// assert_eq!("priority".parse::<SortKey>().unwrap(), SortKey::Priority);
+/// assert_eq!("field:points".parse::<SortKey>().unwrap(), SortKey::Field("points".to_string()));
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub enum SortKey {
+    Id,
+    Title,
+    // Most urgent first
+    Priority,
+    // Soonest first, undated last
+    Due,
+    // A custom field, numerically when both values are numbers; tasks without it last
+    Field(String),
+    // The order set with `move`
+    Manual,
+}
+
+impl FromStr for SortKey {
+    type Err = TaskError;
+
+    fn from_str(s: &str) -> Result<SortKey, TaskError> {
+        match s.to_lowercase().as_str() {
+            "id" => Ok(SortKey::Id),
+            "title" => Ok(SortKey::Title),
+            "priority" => Ok(SortKey::Priority),
+            "due" => Ok(SortKey::Due),
+            "manual" => Ok(SortKey::Manual),
+            other => match other.strip_prefix("field:") {
+                Some(key) if valid_field_key(key) => Ok(SortKey::Field(key.to_string())),
+                _ => Err(TaskError::InvalidInput),
+            },
+        }
+    }
+}
+
+impl SortKey {
+    /// Pinned tasks first, then by the key.
+    ///
+    /// ```
+    /// # use task_manager::{Priority, SortKey, Task};
+    /// let mut tasks = vec![
+    ///     Task::new(1, "Low".to_string(), String::new(), Priority::Low),
+    ///     Task::new(2, "High".to_string(), String::new(), Priority::High),
+    /// ];
+    /// SortKey::Priority.sort(&mut tasks);
+    /// assert_eq!(tasks[0].id(), 2);
+    /// ```
+    pub fn sort<T: Borrow<Task>>(&self, tasks: &mut [T]) {
+        tasks.sort_by(|a, b| {
+            let (a, b) = (a.borrow(), b.borrow());
+            pinned_first(a, b).then(self.compare(a, b)).then(a.id.cmp(&b.id))
+        });
+    }
+
+    fn compare(&self, a: &Task, b: &Task) -> Ordering {
+        match self {
+            SortKey::Id => a.id.cmp(&b.id),
+            SortKey::Title => a.title.to_lowercase().cmp(&b.title.to_lowercase()),
+            SortKey::Priority => b.priority.cmp(&a.priority),
+            SortKey::Due => present_first(a.due, b.due, |a, b| a.cmp(&b)),
+            SortKey::Field(key) => {
+                present_first(a.fields.get(key).map(String::as_str), b.fields.get(key).map(String::as_str), compare_values)
+            }
+            SortKey::Manual => a.rank.cmp(&b.rank),
+        }
+    }
+}
+
+/// The leading key of every task ordering: pinned tasks before the rest.
+///
+/// ```
+/// use std::cmp::Ordering;
+/// use task_manager::{Priority, Task, pinned_first};
+///
+/// let task = Task::new(1, "A".to_string(), String::new(), Priority::Low);
+/// assert_eq!(pinned_first(&task, &task), Ordering::Equal);
+/// ```
+pub fn pinned_first(a: &Task, b: &Task) -> Ordering {
+    b.pinned.cmp(&a.pinned)
+}
+
+fn present_first<T>(a: Option<T>, b: Option<T>, compare: impl Fn(T, T) -> Ordering) -> Ordering {
+    match (a, b) {
+        (Some(a), Some(b)) => compare(a, b),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => Ordering::Equal,
+    }
+}
+
+// Numbers in numeric order ("9" before "10"), then everything else as text
+fn compare_values(a: &str, b: &str) -> Ordering {
+    match (a.trim().parse::<f64>(), b.trim().parse::<f64>()) {
+        (Ok(x), Ok(y)) => x.total_cmp(&y),
+        (Ok(_), Err(_)) => Ordering::Less,
+        (Err(_), Ok(_)) => Ordering::Greater,
+        (Err(_), Err(_)) => a.to_lowercase().cmp(&b.to_lowercase()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn task(id: u32, points: Option<&str>) -> Task {
+        let mut task = Task::new(id, format!("Task {}", id), String::new(), Priority::Medium);
+        if let Some(points) = points {
+            task.fields.insert("points".to_string(), points.to_string());
+        }
+        task
+    }
+
+    #[test]
+    fn test_query_splits_fields_from_the_keyword() {
+        let filter = TaskFilter::query("customer:acme invoice points:3");
+        assert_eq!(filter.keyword.as_deref(), Some("invoice"));
+        assert_eq!(filter.fields, vec![
+            ("customer".to_string(), "acme".to_string()),
+            ("points".to_string(), "3".to_string())
+        ]);
+        assert_eq!(TaskFilter::query("customer:acme").keyword, None);
+        // Not a field key, or no value: plain text
+        let text = TaskFilter::query("Re:budget at 10:30 note:");
+        assert_eq!((text.keyword.as_deref(), text.fields), (Some("Re:budget at 10:30 note:"), Vec::new()));
+    }
+
+    #[test]
+    fn test_parse_view_query() {
+        let filter = TaskFilter::parse("status:pending tag:backend -tag:blocked customer:acme login").unwrap();
+        assert_eq!(filter.status, Some(TaskStatus::Pending));
+        assert_eq!((filter.tags, filter.without_tags), (vec!["backend".to_string()], vec!["blocked".to_string()]));
+        assert_eq!(filter.fields, vec![("customer".to_string(), "acme".to_string())]);
+        assert_eq!(filter.keyword.as_deref(), Some("login"));
+        assert_eq!(TaskFilter::parse("priority:urgent"), Err(ParseError::InvalidPriority("urgent".to_string())));
+        assert_eq!(TaskFilter::parse("status:later"), Err(ParseError::InvalidStatus("later".to_string())));
+
+        let easy = TaskFilter::parse("energy:easy").unwrap();
+        assert_eq!(easy.energy, Some(Energy::Low));
+        let mut low = task(1, None);
+        low.energy = Some(Energy::Low);
+        assert!(easy.matches(&low) && !easy.matches(&task(2, None)));
+        assert_eq!(TaskFilter::parse("energy:mush"), Err(ParseError::InvalidEnergy("mush".to_string())));
+
+        let mine = TaskFilter::parse("Mine status:pending").unwrap();
+        assert_eq!((mine.owner.as_deref(), mine.keyword), (Some(ME), None));
+        let mut assigned = task(3, None);
+        assigned.fields.insert("assignee".to_string(), "Ana".to_string());
+        let ana = TaskFilter { owner: Some("ana".to_string()), ..TaskFilter::default() };
+        assert!(ana.matches(&assigned) && !ana.matches(&task(4, None)));
+    }
+
+    #[test]
+    fn test_matches_checks_tags_both_ways() {
+        let mut backend = task(1, None);
+        backend.add_tag("Backend");
+        let mut blocked = backend.clone();
+        blocked.add_tag("blocked");
+        let filter = TaskFilter::parse("tag:backend -tag:BLOCKED").unwrap();
+        assert!(filter.matches(&backend));
+        assert!(!filter.matches(&blocked));
+        assert!(!filter.matches(&task(2, None)));
+    }
+
+    #[test]
+    fn test_sort_by_field_is_numeric_aware() {
+        let mut tasks =
+            vec![task(1, Some("10")), task(2, None), task(3, Some("9")), task(4, Some("large")), task(5, Some("9"))];
+        SortKey::from_str("field:points").unwrap().sort(&mut tasks);
+        assert_eq!(tasks.iter().map(|task| task.id).collect::<Vec<_>>(), vec![3, 5, 1, 4, 2]);
+        assert!(SortKey::from_str("field:Story Points").is_err());
+        assert_eq!(SortKey::from_str("Due").unwrap(), SortKey::Due);
+    }
+
+    #[test]
+    fn test_pinned_tasks_lead_any_sort() {
+        let mut tasks = vec![task(1, Some("1")), task(2, Some("2")), task(3, Some("3")), task(4, None)];
+        tasks[2].pinned = true;
+        tasks[3].pinned = true;
+        SortKey::from_str("field:points").unwrap().sort(&mut tasks);
+        assert_eq!(tasks.iter().map(|task| task.id).collect::<Vec<_>>(), vec![3, 4, 1, 2]);
+        SortKey::Id.sort(&mut tasks);
+        assert_eq!(tasks.iter().map(|task| task.id).collect::<Vec<_>>(), vec![3, 4, 1, 2]);
+    }
+}
@@ -0,0 +1,108 @@
+use std::time::{Duration, Instant};
+
+use crate::TaskStatus;
+use crate::command::{Command, ParseError};
+
+// Focus mode: the REPL works on one task until the user leaves it
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Focus {
+    pub id: u32,
+    pub started: Instant,
+}
+
+// What a line typed while focusing asks for
+#[derive(Debug, Clone, PartialEq)]
+pub enum FocusStep {
+    Run(Command),
+    // Run the command, then return to the normal prompt
+    RunAndLeave(Command),
+    Leave,
+    // Not one of the focus commands, or aimed at another task
+    Rejected,
+    Invalid(ParseError),
+}
+
+impl Focus {
+    pub fn new(id: u32) -> Focus {
+        Focus { id, started: Instant::now() }
+    }
+
+    // The short forms name the focused task implicitly; they and any full command
+    // go through the normal parser, and only commands on this task are let through
+    pub fn step(&self, tokens: &[String]) -> FocusStep {
+        let (name, args) = match tokens.split_first() {
+            Some((name, args)) => (name.as_str(), args),
+            None => return FocusStep::Rejected,
+        };
+        let id = self.id.to_string();
+        let expanded: Vec<String> = match name {
+            "quit-focus" if args.is_empty() => return FocusStep::Leave,
+            "done" if args.is_empty() => vec!["update".into(), id, "completed".into()],
+            "pause" if args.is_empty() => vec!["update".into(), id, "pending".into()],
+            "note" if args.first() != Some(&id) => ["note".to_string(), id].into_iter().chain(args.iter().cloned()).collect(),
+            _ => tokens.to_vec(),
+        };
+        let command = match Command::parse(&expanded) {
+            Ok(command) => command,
+            Err(ParseError::UnknownCommand(_)) => return FocusStep::Rejected,
+            Err(e) => return FocusStep::Invalid(e),
+        };
+        match command {
+            Command::Update { id, ref status, .. } if id == self.id => match status {
+                TaskStatus::InProgress => FocusStep::Run(command),
+                _ => FocusStep::RunAndLeave(command),
+            },
+            Command::Note { id, .. } | Command::Show { id, .. } if id == self.id => FocusStep::Run(command),
+            _ => FocusStep::Rejected,
+        }
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.started.elapsed()
+    }
+}
+
+// "1h 05m", "12m 30s" or "45s"
+pub fn format_elapsed(elapsed: Duration) -> String {
+    let seconds = elapsed.as_secs();
+    match (seconds / 3600, seconds / 60 % 60, seconds % 60) {
+        (0, 0, s) => format!("{}s", s),
+        (0, m, s) => format!("{}m {:02}s", m, s),
+        (h, m, _) => format!("{}h {:02}m", h, m),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn step(input: &str) -> FocusStep {
+        let tokens: Vec<String> = input.split_whitespace().map(String::from).collect();
+        Focus::new(14).step(&tokens)
+    }
+
+    #[test]
+    fn test_focus_commands_act_on_the_focused_task() {
+        assert_eq!(step("done"), FocusStep::RunAndLeave(Command::Update { id: 14, status: TaskStatus::Completed, note: None }));
+        assert_eq!(step("pause"), FocusStep::RunAndLeave(Command::Update { id: 14, status: TaskStatus::Pending, note: None }));
+        assert_eq!(step("quit-focus"), FocusStep::Leave);
+        assert_eq!(step("note rang the supplier"), FocusStep::Run(Command::Note { id: 14, text: "rang the supplier".into() }));
+        assert_eq!(step("note 14 rang them"), FocusStep::Run(Command::Note { id: 14, text: "rang them".into() }));
+        assert_eq!(step("show 14"), FocusStep::Run(Command::Show { id: 14, mode: None }));
+        assert_eq!(step("note"), FocusStep::Invalid(ParseError::Usage("usage_note")));
+    }
+
+    #[test]
+    fn test_everything_else_is_rejected() {
+        for input in ["list", "add Something else", "update 3 completed", "delete 14", "show 2", "frobnicate", "done 3"] {
+            assert_eq!(step(input), FocusStep::Rejected, "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_format_elapsed() {
+        assert_eq!(format_elapsed(Duration::from_secs(45)), "45s");
+        assert_eq!(format_elapsed(Duration::from_secs(750)), "12m 30s");
+        assert_eq!(format_elapsed(Duration::from_secs(3900)), "1h 05m");
+    }
+}
@@ -0,0 +1,301 @@
+use serde::Deserialize;
+
+use crate::import::ImportedTask;
+use crate::msg;
+use crate::{Priority, TaskError, TaskStatus};
+
+const API_ROOT: &str = "https://api.github.com";
+
+// One page of an API answer; `next` comes from the Link header
+#[derive(Debug, Clone, Default)]
+pub struct ApiResponse {
+    pub status: u16,
+    pub body: String,
+    pub next: Option<String>,
+}
+
+// The HTTP layer, kept behind a trait so tests can replay canned pages
+pub trait GithubClient {
+    // Err only for transport failures; HTTP error statuses come back as a response
+    fn get(&self, url: &str) -> Result<ApiResponse, String>;
+}
+
+// Which issues of which repository to fetch
+#[derive(Debug, Clone, PartialEq)]
+pub struct IssueQuery {
+    pub repo: String,
+    pub label: Option<String>,
+    // open, closed or all, as the API spells them
+    pub state: String,
+}
+
+impl IssueQuery {
+    fn url(&self) -> String {
+        let mut url = format!("{}/repos/{}/issues?state={}&per_page=100", API_ROOT, self.repo, self.state);
+        if let Some(label) = &self.label {
+            url.push_str("&labels=");
+            url.push_str(&encode(label));
+        }
+        url
+    }
+}
+
+#[derive(Deserialize)]
+struct Issue {
+    number: u64,
+    title: String,
+    body: Option<String>,
+    state: String,
+    #[serde(default)]
+    labels: Vec<Label>,
+    // Present when the "issue" is really a pull request
+    pull_request: Option<serde_json::Value>,
+}
+
+#[derive(Deserialize)]
+struct Label {
+    name: String,
+}
+
+// Every issue matching the query, following pagination to the last page
+pub fn fetch_issues(client: &dyn GithubClient, query: &IssueQuery) -> Result<Vec<ImportedTask>, TaskError> {
+    let mut tasks = Vec::new();
+    let mut url = Some(query.url());
+    while let Some(page) = url {
+        let response = client.get(&page).map_err(TaskError::Remote)?;
+        if response.status != 200 {
+            return Err(api_error(response.status, &query.repo, &response.body));
+        }
+        let issues: Vec<Issue> = serde_json::from_str(&response.body)
+            .map_err(|e| TaskError::Remote(msg!("github_bad_response", error = e)))?;
+        tasks.extend(issues.into_iter().filter(|issue| issue.pull_request.is_none()).map(|issue| to_task(&query.repo, issue)));
+        url = response.next;
+    }
+    Ok(tasks)
+}
+
+fn to_task(repo: &str, issue: Issue) -> ImportedTask {
+    let tags: Vec<String> = issue.labels.into_iter().map(|label| label.name).collect();
+    ImportedTask {
+        external_id: format!("github:{}#{}", repo, issue.number),
+        title: issue.title,
+        description: issue.body.unwrap_or_default(),
+        priority: tags.iter().find_map(|tag| priority_from_label(tag)),
+        tags,
+        status: if issue.state == "closed" { TaskStatus::Completed } else { TaskStatus::Pending },
+        key: None,
+    }
+}
+
+// "P0".."P3" style labels, optionally written "priority: P1" or "priority/p1"
+pub fn priority_from_label(label: &str) -> Option<Priority> {
+    let lower = label.trim().to_lowercase();
+    let level = ["priority:", "priority/", "priority-"]
+        .iter()
+        .find_map(|prefix| lower.strip_prefix(prefix))
+        .unwrap_or(&lower)
+        .trim();
+    match level.strip_prefix('p')?.parse::<u32>().ok()? {
+        0 => Some(Priority::Critical),
+        1 => Some(Priority::High),
+        2 => Some(Priority::Medium),
+        _ => Some(Priority::Low),
+    }
+}
+
+fn api_error(status: u16, repo: &str, body: &str) -> TaskError {
+    // GitHub puts a human-readable reason in {"message": ...}
+    let detail = serde_json::from_str::<serde_json::Value>(body)
+        .ok()
+        .and_then(|value| value["message"].as_str().map(String::from))
+        .unwrap_or_default();
+    TaskError::Remote(match status {
+        401 => msg!("github_unauthorized"),
+        403 => msg!("github_forbidden", detail = detail),
+        404 => msg!("github_not_found", repo = repo),
+        _ => msg!("github_failed", status = status, detail = detail),
+    })
+}
+
+// Percent-encode a query value; labels often contain spaces or colons
+fn encode(value: &str) -> String {
+    let mut out = String::new();
+    for byte in value.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => out.push(byte as char),
+            _ => out.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    out
+}
+
+// The rel="next" target of a Link header
+pub fn next_link(header: &str) -> Option<String> {
+    header.split(',').find_map(|part| {
+        let (target, params) = part.split_once(';')?;
+        params
+            .split(';')
+            .any(|param| param.trim() == "rel=\"next\"")
+            .then(|| target.trim().trim_start_matches('<').trim_end_matches('>').to_string())
+    })
+}
+
+// Real client, authenticated with GITHUB_TOKEN when it is set
+#[cfg(feature = "http-client")]
+pub struct HttpGithubClient {
+    agent: ureq::Agent,
+    token: Option<String>,
+}
+
+#[cfg(feature = "http-client")]
+impl HttpGithubClient {
+    pub fn from_env() -> Self {
+        HttpGithubClient {
+            agent: ureq::AgentBuilder::new().timeout(std::time::Duration::from_secs(15)).build(),
+            token: std::env::var("GITHUB_TOKEN").ok().filter(|token| !token.is_empty()),
+        }
+    }
+}
+
+#[cfg(feature = "http-client")]
+impl GithubClient for HttpGithubClient {
+    fn get(&self, url: &str) -> Result<ApiResponse, String> {
+        let mut request = self
+            .agent
+            .get(url)
+            .set("Accept", "application/vnd.github+json")
+            .set("User-Agent", "Task-Manager");
+        if let Some(token) = &self.token {
+            request = request.set("Authorization", &format!("Bearer {}", token));
+        }
+        let response = match request.call() {
+            Ok(response) => response,
+            Err(ureq::Error::Status(_, response)) => response,
+            Err(e) => return Err(e.to_string()),
+        };
+        let status = response.status();
+        let next = response.header("Link").and_then(next_link);
+        let body = response.into_string().map_err(|e| e.to_string())?;
+        Ok(ApiResponse { status, body, next })
+    }
+}
+
+// The client used by the CLI, if this build can make HTTP requests
+#[cfg(feature = "http-client")]
+pub fn default_client() -> Option<Box<dyn GithubClient>> {
+    Some(Box::new(HttpGithubClient::from_env()))
+}
+
+#[cfg(not(feature = "http-client"))]
+pub fn default_client() -> Option<Box<dyn GithubClient>> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::import;
+    use crate::TaskManager;
+    use std::cell::RefCell;
+    use std::collections::HashMap;
+
+    // Replays canned pages by URL and remembers what was asked for
+    #[derive(Default)]
+    struct Replay {
+        pages: HashMap<String, ApiResponse>,
+        requested: RefCell<Vec<String>>,
+    }
+
+    impl Replay {
+        fn page(mut self, url: &str, status: u16, body: &str, next: Option<&str>) -> Self {
+            let response = ApiResponse { status, body: body.to_string(), next: next.map(String::from) };
+            self.pages.insert(url.to_string(), response);
+            self
+        }
+    }
+
+    impl GithubClient for Replay {
+        fn get(&self, url: &str) -> Result<ApiResponse, String> {
+            self.requested.borrow_mut().push(url.to_string());
+            self.pages.get(url).cloned().ok_or_else(|| format!("unexpected request {}", url))
+        }
+    }
+
+    fn query() -> IssueQuery {
+        IssueQuery { repo: "acme/widgets".to_string(), label: None, state: "all".to_string() }
+    }
+
+    const FIRST: &str = "https://api.github.com/repos/acme/widgets/issues?state=all&per_page=100";
+    const SECOND: &str = "https://api.github.com/repos/acme/widgets/issues?state=all&per_page=100&page=2";
+
+    const PAGE_ONE: &str = r#"[
+        {"number": 12, "title": "Crash on save", "body": "Stack trace attached", "state": "open",
+         "labels": [{"name": "bug"}, {"name": "P1"}]},
+        {"number": 13, "title": "Bump version", "body": null, "state": "open", "labels": [],
+         "pull_request": {"url": "https://api.github.com/repos/acme/widgets/pulls/13"}}
+    ]"#;
+    const PAGE_TWO: &str = r#"[
+        {"number": 7, "title": "Docs typo", "body": "", "state": "closed", "labels": [{"name": "priority: p3"}]}
+    ]"#;
+
+    fn replay() -> Replay {
+        Replay::default().page(FIRST, 200, PAGE_ONE, Some(SECOND)).page(SECOND, 200, PAGE_TWO, None)
+    }
+
+    #[test]
+    fn test_fetch_follows_pages_and_skips_pull_requests() {
+        let client = replay();
+        let tasks = fetch_issues(&client, &query()).unwrap();
+        assert_eq!(*client.requested.borrow(), vec![FIRST, SECOND]);
+        assert_eq!(tasks.len(), 2);
+        assert_eq!(tasks[0].external_id, "github:acme/widgets#12");
+        assert_eq!(tasks[0].description, "Stack trace attached");
+        assert_eq!(tasks[0].tags, vec!["bug", "P1"]);
+        assert_eq!(tasks[0].priority, Some(Priority::High));
+        assert_eq!(tasks[1].priority, Some(Priority::Low));
+        assert_eq!(tasks[1].status, TaskStatus::Completed);
+    }
+
+    #[test]
+    fn test_reimport_updates_existing_tasks() {
+        let mut manager = TaskManager::new();
+        let first = import::apply(&mut manager, "github", fetch_issues(&replay(), &query()).unwrap(), &Priority::Medium);
+        assert_eq!(first.created.len(), 2);
+
+        let renamed = PAGE_ONE.replace("Crash on save", "Crash on save (regression)").replace("\"open\"", "\"closed\"");
+        let client = Replay::default().page(FIRST, 200, &renamed, Some(SECOND)).page(SECOND, 200, PAGE_TWO, None);
+        let second = import::apply(&mut manager, "github", fetch_issues(&client, &query()).unwrap(), &Priority::Medium);
+        assert!(second.created.is_empty());
+        assert_eq!(second.updated.len(), 2);
+        assert_eq!(manager.list_tasks().len(), 2);
+        let task = manager.get_task(first.created[0]).unwrap();
+        assert_eq!(task.title, "Crash on save (regression)");
+        assert_eq!(task.status, TaskStatus::Completed);
+    }
+
+    #[test]
+    fn test_api_errors_are_explained() {
+        let client = Replay::default().page(FIRST, 404, r#"{"message": "Not Found"}"#, None);
+        let err = fetch_issues(&client, &query()).unwrap_err();
+        assert!(matches!(&err, TaskError::Remote(message) if message.contains("acme/widgets")));
+
+        let client = Replay::default().page(FIRST, 403, r#"{"message": "API rate limit exceeded"}"#, None);
+        let err = fetch_issues(&client, &query()).unwrap_err();
+        assert!(err.to_string().contains("API rate limit exceeded"));
+    }
+
+    #[test]
+    fn test_priority_labels_and_links() {
+        assert_eq!(priority_from_label("P0"), Some(Priority::Critical));
+        assert_eq!(priority_from_label("priority/p2"), Some(Priority::Medium));
+        assert_eq!(priority_from_label("P5"), Some(Priority::Low));
+        assert_eq!(priority_from_label("performance"), None);
+
+        let header = "<https://api.github.com/x?page=2>; rel=\"next\", <https://api.github.com/x?page=5>; rel=\"last\"";
+        assert_eq!(next_link(header).as_deref(), Some("https://api.github.com/x?page=2"));
+        assert_eq!(next_link("<https://api.github.com/x?page=1>; rel=\"prev\""), None);
+
+        let labelled = IssueQuery { label: Some("good first issue".to_string()), ..query() };
+        assert!(labelled.url().ends_with("&labels=good%20first%20issue"));
+    }
+}
@@ -0,0 +1,177 @@
+use std::fmt;
+use std::str::FromStr;
+
+use chrono::{Datelike, Days, Local, Months, NaiveDate, Weekday};
+use serde::{Deserialize, Serialize};
+
+use crate::msg;
+use crate::{Task, TaskError, TaskStatus};
+
+// Cells in the progress bar
+pub const BAR_WIDTH: usize = 10;
+
+// The span a goal counts completions over; weeks start on the configured day
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GoalPeriod {
+    Day,
+    Week,
+    Month,
+}
+
+impl GoalPeriod {
+    // The period holding `today`, as a half-open range of days
+    pub fn bounds(self, today: NaiveDate, week_start: Weekday) -> (NaiveDate, NaiveDate) {
+        match self {
+            GoalPeriod::Day => (today, today + Days::new(1)),
+            GoalPeriod::Week => {
+                let start = today - Days::new(u64::from(today.weekday().days_since(week_start)));
+                (start, start + Days::new(7))
+            }
+            GoalPeriod::Month => {
+                let start = today.with_day(1).expect("every month has a first day");
+                (start, start + Months::new(1))
+            }
+        }
+    }
+
+    // "today", "this week" or "this month"
+    pub fn phrase(self) -> String {
+        match self {
+            GoalPeriod::Day => msg!("goal_period_day"),
+            GoalPeriod::Week => msg!("goal_period_week"),
+            GoalPeriod::Month => msg!("goal_period_month"),
+        }
+    }
+}
+
+impl FromStr for GoalPeriod {
+    type Err = TaskError;
+
+    fn from_str(s: &str) -> Result<GoalPeriod, TaskError> {
+        match s.to_lowercase().as_str() {
+            "day" | "daily" => Ok(GoalPeriod::Day),
+            "week" | "weekly" => Ok(GoalPeriod::Week),
+            "month" | "monthly" => Ok(GoalPeriod::Month),
+            _ => Err(TaskError::InvalidInput),
+        }
+    }
+}
+
+impl fmt::Display for GoalPeriod {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GoalPeriod::Day => write!(f, "day"),
+            GoalPeriod::Week => write!(f, "week"),
+            GoalPeriod::Month => write!(f, "month"),
+        }
+    }
+}
+
+// "Complete `target` tasks every `period`", saved with the tasks
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Goal {
+    pub target: u32,
+    pub period: GoalPeriod,
+    // First day of the period the goal was last met in, so reaching it is
+    // congratulated once
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub celebrated: Option<NaiveDate>,
+}
+
+impl Goal {
+    pub fn new(target: u32, period: GoalPeriod) -> Goal {
+        Goal { target, period, celebrated: None }
+    }
+}
+
+// How far the current period has come
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct GoalProgress {
+    pub target: u32,
+    pub period: GoalPeriod,
+    pub done: u32,
+    // First day of the period and the day after its last
+    pub start: NaiveDate,
+    pub end: NaiveDate,
+}
+
+impl GoalProgress {
+    pub fn is_met(&self) -> bool {
+        self.done >= self.target
+    }
+
+    // "██████░░░░", full once the goal is met
+    pub fn bar(&self) -> String {
+        let filled = if self.target == 0 {
+            BAR_WIDTH
+        } else {
+            (self.done.min(self.target) as usize * BAR_WIDTH) / self.target as usize
+        };
+        format!("{}{}", "█".repeat(filled), "░".repeat(BAR_WIDTH - filled))
+    }
+
+    // "Goal: 6/10 this week ██████░░░░"
+    pub fn line(&self) -> String {
+        msg!("goal_progress", done = self.done, target = self.target, period = self.period.phrase(), bar = self.bar())
+    }
+}
+
+// The day a completed task was completed, in local time. Tasks completed
+// before completion times were kept count by their last change.
+pub fn completion_day(task: &Task) -> Option<NaiveDate> {
+    if task.status != TaskStatus::Completed {
+        return None;
+    }
+    Some(task.completed_at.unwrap_or(task.updated_at).with_timezone(&Local).date_naive())
+}
+
+// Pure over the completion days, the goal and today
+pub fn progress(
+    goal: &Goal,
+    completions: impl IntoIterator<Item = NaiveDate>,
+    today: NaiveDate,
+    week_start: Weekday,
+) -> GoalProgress {
+    let (start, end) = goal.period.bounds(today, week_start);
+    let done = completions.into_iter().filter(|day| *day >= start && *day < end).count();
+    GoalProgress { target: goal.target, period: goal.period, done: done as u32, start, end }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2024, month, day).unwrap()
+    }
+
+    #[test]
+    fn test_period_bounds_follow_the_week_start() {
+        // 2024-07-10 is a Wednesday
+        let today = date(7, 10);
+        assert_eq!(GoalPeriod::Week.bounds(today, Weekday::Mon), (date(7, 8), date(7, 15)));
+        assert_eq!(GoalPeriod::Week.bounds(today, Weekday::Sun), (date(7, 7), date(7, 14)));
+        assert_eq!(GoalPeriod::Week.bounds(date(7, 7), Weekday::Mon), (date(7, 1), date(7, 8)));
+        assert_eq!(GoalPeriod::Day.bounds(today, Weekday::Mon), (today, date(7, 11)));
+        assert_eq!(GoalPeriod::Month.bounds(date(2, 29), Weekday::Mon), (date(2, 1), date(3, 1)));
+        assert_eq!(GoalPeriod::Month.bounds(date(12, 31), Weekday::Mon).1, NaiveDate::from_ymd_opt(2025, 1, 1).unwrap());
+    }
+
+    #[test]
+    fn test_progress_counts_completions_in_the_period() {
+        let goal = Goal::new(10, GoalPeriod::Week);
+        let completions = [date(7, 5), date(7, 7), date(7, 8), date(7, 8), date(7, 9), date(7, 10), date(7, 15)];
+        let week = progress(&goal, completions, date(7, 10), Weekday::Mon);
+        assert_eq!((week.done, week.start, week.end), (4, date(7, 8), date(7, 15)));
+        assert!(!week.is_met());
+        assert_eq!(week.bar(), "████░░░░░░");
+        assert_eq!(week.line(), "Goal: 4/10 this week ████░░░░░░");
+
+        // A Sunday start takes in the 7th as well
+        assert_eq!(progress(&goal, completions, date(7, 10), Weekday::Sun).done, 5);
+        let daily = progress(&Goal::new(2, GoalPeriod::Day), completions, date(7, 8), Weekday::Mon);
+        assert!(daily.is_met());
+        assert_eq!(daily.bar(), "██████████");
+    }
+}
@@ -0,0 +1,106 @@
+use std::collections::{BTreeSet, HashMap};
+use std::fmt::Write;
+
+use crate::{Task, TaskStatus};
+
+// A string as a quoted DOT identifier
+fn quote(text: &str) -> String {
+    let mut out = String::with_capacity(text.len() + 2);
+    out.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => {}
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+// The tasks linked to another as a Graphviz digraph: an arrow from each
+// project to its subtasks and a dashed line between related tasks, drawn
+// once per pair. Tasks without links are left out; completed ones are grey.
+pub fn dot<'a>(tasks: impl IntoIterator<Item = &'a Task>) -> String {
+    let tasks: Vec<&Task> = tasks.into_iter().collect();
+    let by_uid: HashMap<&str, u32> = tasks.iter().map(|task| (task.uid.as_str(), task.id)).collect();
+    let ids: BTreeSet<u32> = tasks.iter().map(|task| task.id).collect();
+
+    let subtasks: BTreeSet<(u32, u32)> = tasks
+        .iter()
+        .filter_map(|task| task.parent.as_deref().and_then(|uid| by_uid.get(uid)).map(|&parent| (parent, task.id)))
+        .collect();
+    // A link recorded on one side only is still drawn
+    let related: BTreeSet<(u32, u32)> = tasks
+        .iter()
+        .flat_map(|task| task.relates_to.iter().map(move |&other| (task.id.min(other), task.id.max(other))))
+        .filter(|(a, b)| a != b && ids.contains(a) && ids.contains(b))
+        .collect();
+    let linked: BTreeSet<u32> = subtasks.iter().chain(&related).flat_map(|&(a, b)| [a, b]).collect();
+
+    let mut out = String::from("digraph tasks {\n  node [shape=box];\n");
+    for task in tasks.iter().filter(|task| linked.contains(&task.id)) {
+        let label = quote(&format!("#{} {}", task.id, task.title));
+        let style = if task.status == TaskStatus::Completed { ", color=gray, fontcolor=gray" } else { "" };
+        writeln!(out, "  {} [label={}{}];", task.id, label, style).unwrap();
+    }
+    for (parent, child) in &subtasks {
+        writeln!(out, "  {} -> {};", parent, child).unwrap();
+    }
+    for (a, b) in &related {
+        writeln!(out, "  {} -> {} [dir=none, style=dashed];", a, b).unwrap();
+    }
+    out.push('}');
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Priority;
+
+    #[test]
+    fn test_quote_escapes_what_dot_would_misread() {
+        assert_eq!(quote("plain"), "\"plain\"");
+        assert_eq!(quote("say \"hi\"\\now\r\nthen"), "\"say \\\"hi\\\"\\\\now\\nthen\"");
+    }
+
+    #[test]
+    fn test_dot_draws_subtasks_and_relations() {
+        let mut tasks: Vec<Task> = ["Launch", "Write \"copy\"", "Design", "Unlinked", "Retro"]
+            .into_iter()
+            .enumerate()
+            .map(|(i, title)| Task::new(i as u32 + 1, title.to_string(), String::new(), Priority::Medium))
+            .collect();
+        let launch = tasks[0].uid.clone();
+        tasks[1].parent = Some(launch.clone());
+        tasks[2].parent = Some(launch);
+        // Both sides, one side only, a missing task and a self-link
+        tasks[1].relates_to = vec![3, 5];
+        tasks[2].relates_to = vec![2];
+        tasks[4].relates_to = vec![3, 5, 9];
+        tasks[4].status = TaskStatus::Completed;
+
+        assert_eq!(
+            dot(&tasks),
+            [
+                "digraph tasks {",
+                "  node [shape=box];",
+                "  1 [label=\"#1 Launch\"];",
+                "  2 [label=\"#2 Write \\\"copy\\\"\"];",
+                "  3 [label=\"#3 Design\"];",
+                "  5 [label=\"#5 Retro\", color=gray, fontcolor=gray];",
+                "  1 -> 2;",
+                "  1 -> 3;",
+                "  2 -> 3 [dir=none, style=dashed];",
+                "  2 -> 5 [dir=none, style=dashed];",
+                "  3 -> 5 [dir=none, style=dashed];",
+                "}",
+            ]
+            .join("\n")
+        );
+        assert_eq!(dot(&tasks[3..4]), "digraph tasks {\n  node [shape=box];\n}");
+    }
+}
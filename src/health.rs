@@ -0,0 +1,355 @@
+use std::collections::{BTreeMap, HashSet};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::storage::SavedState;
+use crate::task::new_uid;
+use crate::{Task, msg};
+
+// A task `check --fix` took out of the list because it could not be repaired,
+// kept in the data file with the reason
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TrashedTask {
+    pub task: Task,
+    pub note: String,
+    pub trashed_at: DateTime<Utc>,
+}
+
+// One broken invariant of a saved list, naming the task it is on
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Issue {
+    // Several tasks have the id; only the last of them loads
+    DuplicateId { id: u32, count: usize },
+    // The task has the uid of one before it
+    DuplicateUid { id: u32, uid: String },
+    // The next task added would take an id already in use
+    NextIdTooLow { next_id: u32, highest: u32 },
+    // The parent uid is no task's, or the task's own
+    DanglingParent { id: u32, parent: String },
+    DanglingRelation { id: u32, other: u32 },
+    EmptyTitle { id: u32 },
+}
+
+impl Issue {
+    pub fn describe(&self) -> String {
+        match self {
+            Issue::DuplicateId { id, count } => msg!("issue_duplicate_id", id = id, count = count),
+            Issue::DuplicateUid { id, uid } => msg!("issue_duplicate_uid", id = id, uid = uid),
+            Issue::NextIdTooLow { next_id, highest } => msg!("issue_next_id", next_id = next_id, highest = highest),
+            Issue::DanglingParent { id, parent } => msg!("issue_dangling_parent", id = id, parent = parent),
+            Issue::DanglingRelation { id, other } => msg!("issue_dangling_relation", id = id, other = other),
+            Issue::EmptyTitle { id } => msg!("issue_empty_title", id = id),
+        }
+    }
+}
+
+// One change `check --fix` made
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Repair {
+    Renumbered { from: u32, to: u32, title: String },
+    NewUid { id: u32 },
+    NextIdRaised { from: u32, to: u32 },
+    ParentDropped { id: u32, parent: String },
+    RelationDropped { id: u32, other: u32 },
+    // Moved to the trash
+    Quarantined { id: u32, reason: String },
+}
+
+impl Repair {
+    pub fn describe(&self) -> String {
+        match self {
+            Repair::Renumbered { from, to, title } => msg!("repair_renumbered", from = from, to = to, title = title),
+            Repair::NewUid { id } => msg!("repair_new_uid", id = id),
+            Repair::NextIdRaised { from, to } => msg!("repair_next_id", from = from, to = to),
+            Repair::ParentDropped { id, parent } => msg!("repair_parent", id = id, parent = parent),
+            Repair::RelationDropped { id, other } => msg!("repair_relation", id = id, other = other),
+            Repair::Quarantined { id, reason } => msg!("repair_quarantined", id = id, reason = reason),
+        }
+    }
+}
+
+// Every broken invariant, check by check
+pub fn check(state: &SavedState) -> Vec<Issue> {
+    [duplicate_ids(state), duplicate_uids(state), next_id(state), dangling_parents(state), dangling_relations(state), empty_titles(state)]
+        .concat()
+}
+
+// Make the repairs each check asks for, in an order where one never undoes
+// another: ids and uids are made unique before the tasks that cannot be
+// repaired go to the trash, and links into the trash are dropped after
+pub fn repair(state: &mut SavedState, now: DateTime<Utc>) -> Vec<Repair> {
+    let mut repairs = fix_duplicate_ids(state);
+    repairs.extend(fix_duplicate_uids(state));
+    repairs.extend(quarantine_empty_titles(state, now));
+    repairs.extend(fix_dangling_parents(state));
+    repairs.extend(fix_dangling_relations(state));
+    repairs.extend(fix_next_id(state));
+    repairs
+}
+
+pub fn duplicate_ids(state: &SavedState) -> Vec<Issue> {
+    let mut counts: BTreeMap<u32, usize> = BTreeMap::new();
+    for task in &state.tasks {
+        *counts.entry(task.id).or_default() += 1;
+    }
+    counts.into_iter().filter(|&(_, count)| count > 1).map(|(id, count)| Issue::DuplicateId { id, count }).collect()
+}
+
+// All but the last task with an id move to new ones, since the last is the
+// one that loaded
+pub fn fix_duplicate_ids(state: &mut SavedState) -> Vec<Repair> {
+    let mut next = state.tasks.iter().map(|task| task.id + 1).max().unwrap_or(1).max(state.next_id);
+    let mut seen = HashSet::new();
+    let shadowed: Vec<bool> = state.tasks.iter().rev().map(|task| !seen.insert(task.id)).collect();
+    let mut repairs = Vec::new();
+    // New ids go out in file order
+    for (task, _) in state.tasks.iter_mut().zip(shadowed.into_iter().rev()).filter(|(_, shadowed)| *shadowed) {
+        repairs.push(Repair::Renumbered { from: task.id, to: next, title: task.title.clone() });
+        task.id = next;
+        next += 1;
+    }
+    if !repairs.is_empty() {
+        state.next_id = next;
+    }
+    repairs
+}
+
+pub fn duplicate_uids(state: &SavedState) -> Vec<Issue> {
+    let mut seen = HashSet::new();
+    state
+        .tasks
+        .iter()
+        .filter(|task| !seen.insert(task.uid.as_str()))
+        .map(|task| Issue::DuplicateUid { id: task.id, uid: task.uid.clone() })
+        .collect()
+}
+
+// The first task keeps the uid; the others get new ones
+pub fn fix_duplicate_uids(state: &mut SavedState) -> Vec<Repair> {
+    let mut seen = HashSet::new();
+    let mut repairs = Vec::new();
+    for task in &mut state.tasks {
+        if !seen.insert(task.uid.clone()) {
+            task.uid = new_uid();
+            seen.insert(task.uid.clone());
+            repairs.push(Repair::NewUid { id: task.id });
+        }
+    }
+    repairs
+}
+
+pub fn next_id(state: &SavedState) -> Vec<Issue> {
+    match state.tasks.iter().map(|task| task.id).max() {
+        Some(highest) if state.next_id <= highest => vec![Issue::NextIdTooLow { next_id: state.next_id, highest }],
+        _ => Vec::new(),
+    }
+}
+
+pub fn fix_next_id(state: &mut SavedState) -> Vec<Repair> {
+    let Some(highest) = state.tasks.iter().map(|task| task.id).max().filter(|&highest| state.next_id <= highest) else {
+        return Vec::new();
+    };
+    let from = std::mem::replace(&mut state.next_id, highest + 1);
+    vec![Repair::NextIdRaised { from, to: highest + 1 }]
+}
+
+fn is_dangling_parent(task: &Task, uids: &HashSet<&str>) -> bool {
+    task.parent.as_deref().is_some_and(|parent| parent == task.uid || !uids.contains(parent))
+}
+
+pub fn dangling_parents(state: &SavedState) -> Vec<Issue> {
+    let uids: HashSet<&str> = state.tasks.iter().map(|task| task.uid.as_str()).collect();
+    state
+        .tasks
+        .iter()
+        .filter(|task| is_dangling_parent(task, &uids))
+        .map(|task| Issue::DanglingParent { id: task.id, parent: task.parent.clone().unwrap_or_default() })
+        .collect()
+}
+
+// The task stays, as a top-level one
+pub fn fix_dangling_parents(state: &mut SavedState) -> Vec<Repair> {
+    let uids: HashSet<String> = state.tasks.iter().map(|task| task.uid.clone()).collect();
+    let uids: HashSet<&str> = uids.iter().map(String::as_str).collect();
+    let mut repairs = Vec::new();
+    for task in &mut state.tasks {
+        if is_dangling_parent(task, &uids) {
+            let parent = task.parent.take().unwrap_or_default();
+            repairs.push(Repair::ParentDropped { id: task.id, parent });
+        }
+    }
+    repairs
+}
+
+pub fn dangling_relations(state: &SavedState) -> Vec<Issue> {
+    let ids: HashSet<u32> = state.tasks.iter().map(|task| task.id).collect();
+    state
+        .tasks
+        .iter()
+        .flat_map(|task| {
+            let ids = &ids;
+            task.relates_to
+                .iter()
+                .filter(move |&&other| other == task.id || !ids.contains(&other))
+                .map(move |&other| Issue::DanglingRelation { id: task.id, other })
+        })
+        .collect()
+}
+
+pub fn fix_dangling_relations(state: &mut SavedState) -> Vec<Repair> {
+    let ids: HashSet<u32> = state.tasks.iter().map(|task| task.id).collect();
+    let mut repairs = Vec::new();
+    for task in &mut state.tasks {
+        let id = task.id;
+        task.relates_to.retain(|&other| {
+            let keep = other != id && ids.contains(&other);
+            if !keep {
+                repairs.push(Repair::RelationDropped { id, other });
+            }
+            keep
+        });
+    }
+    repairs
+}
+
+pub fn empty_titles(state: &SavedState) -> Vec<Issue> {
+    state.tasks.iter().filter(|task| task.title.trim().is_empty()).map(|task| Issue::EmptyTitle { id: task.id }).collect()
+}
+
+// A task without a title cannot be shown or looked up by name, and guessing
+// one would put words in the user's mouth, so it goes to the trash whole
+pub fn quarantine_empty_titles(state: &mut SavedState, now: DateTime<Utc>) -> Vec<Repair> {
+    let reason = msg!("trash_note_empty_title");
+    let (empty, kept): (Vec<Task>, Vec<Task>) =
+        std::mem::take(&mut state.tasks).into_iter().partition(|task| task.title.trim().is_empty());
+    state.tasks = kept;
+    empty
+        .into_iter()
+        .map(|task| {
+            let id = task.id;
+            state.trash.push(TrashedTask { task, note: reason.clone(), trashed_at: now });
+            Repair::Quarantined { id, reason: reason.clone() }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+    use crate::Priority;
+
+    fn state(titles: &[(u32, &str)], next_id: u32) -> SavedState {
+        let tasks = titles
+            .iter()
+            .map(|&(id, title)| Task::new(id, title.to_string(), String::new(), Priority::Medium))
+            .collect();
+        SavedState { tasks, next_id, ..SavedState::default() }
+    }
+
+    fn now() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 7, 10, 9, 0, 0).unwrap()
+    }
+
+    #[test]
+    fn test_a_sound_list_has_no_issues() {
+        let mut sound = state(&[(1, "Launch"), (2, "Copy")], 3);
+        sound.tasks[1].parent = Some(sound.tasks[0].uid.clone());
+        sound.tasks[0].relates_to = vec![2];
+        assert!(check(&sound).is_empty());
+        assert!(repair(&mut sound.clone(), now()).is_empty());
+    }
+
+    #[test]
+    fn test_duplicate_ids_are_renumbered_keeping_the_one_that_loaded() {
+        let mut broken = state(&[(1, "First copy"), (2, "Review"), (1, "Second copy"), (1, "Third copy")], 3);
+        assert_eq!(duplicate_ids(&broken), [Issue::DuplicateId { id: 1, count: 3 }]);
+        assert_eq!(
+            fix_duplicate_ids(&mut broken),
+            [
+                Repair::Renumbered { from: 1, to: 3, title: "First copy".to_string() },
+                Repair::Renumbered { from: 1, to: 4, title: "Second copy".to_string() },
+            ]
+        );
+        let ids: Vec<(u32, &str)> = broken.tasks.iter().map(|task| (task.id, task.title.as_str())).collect();
+        assert_eq!(ids, [(3, "First copy"), (2, "Review"), (4, "Second copy"), (1, "Third copy")]);
+        assert_eq!(broken.next_id, 5);
+        assert!(duplicate_ids(&broken).is_empty());
+    }
+
+    #[test]
+    fn test_duplicate_uids_get_new_ones() {
+        let mut broken = state(&[(1, "Report"), (2, "Copied by hand")], 3);
+        broken.tasks[1].uid = broken.tasks[0].uid.clone();
+        assert_eq!(duplicate_uids(&broken), [Issue::DuplicateUid { id: 2, uid: broken.tasks[0].uid.clone() }]);
+        let first = broken.tasks[0].uid.clone();
+        assert_eq!(fix_duplicate_uids(&mut broken), [Repair::NewUid { id: 2 }]);
+        assert_eq!(broken.tasks[0].uid, first);
+        assert!(duplicate_uids(&broken).is_empty());
+    }
+
+    #[test]
+    fn test_next_id_is_raised_past_every_task() {
+        let mut broken = state(&[(1, "Report"), (7, "Review")], 7);
+        assert_eq!(next_id(&broken), [Issue::NextIdTooLow { next_id: 7, highest: 7 }]);
+        assert_eq!(fix_next_id(&mut broken), [Repair::NextIdRaised { from: 7, to: 8 }]);
+        assert!(next_id(&broken).is_empty() && fix_next_id(&mut broken).is_empty());
+        // Ahead of the tasks is fine: deleted ones are not handed out again
+        assert!(next_id(&state(&[(1, "Report")], 40)).is_empty());
+    }
+
+    #[test]
+    fn test_dangling_links_are_dropped() {
+        let mut broken = state(&[(1, "Launch"), (2, "Copy"), (3, "Design")], 4);
+        let own = broken.tasks[0].uid.clone();
+        broken.tasks[0].parent = Some(own.clone());
+        broken.tasks[1].parent = Some("gone".to_string());
+        broken.tasks[2].parent = Some(broken.tasks[1].uid.clone());
+        broken.tasks[1].relates_to = vec![1, 2, 9];
+        assert_eq!(
+            check(&broken),
+            [
+                Issue::DanglingParent { id: 1, parent: own.clone() },
+                Issue::DanglingParent { id: 2, parent: "gone".to_string() },
+                Issue::DanglingRelation { id: 2, other: 2 },
+                Issue::DanglingRelation { id: 2, other: 9 },
+            ]
+        );
+        assert_eq!(
+            fix_dangling_parents(&mut broken),
+            [Repair::ParentDropped { id: 1, parent: own }, Repair::ParentDropped { id: 2, parent: "gone".to_string() }]
+        );
+        assert_eq!(
+            fix_dangling_relations(&mut broken),
+            [Repair::RelationDropped { id: 2, other: 2 }, Repair::RelationDropped { id: 2, other: 9 }]
+        );
+        assert_eq!(broken.tasks[1].relates_to, [1]);
+        assert!(broken.tasks[2].parent.is_some());
+        assert!(check(&broken).is_empty());
+    }
+
+    #[test]
+    fn test_tasks_without_a_title_go_to_the_trash_and_links_to_them_after() {
+        let mut broken = state(&[(1, "Launch"), (2, "  "), (3, "Design")], 4);
+        broken.tasks[1].description = "kept in the trash".to_string();
+        broken.tasks[2].parent = Some(broken.tasks[1].uid.clone());
+        broken.tasks[0].relates_to = vec![2];
+        assert_eq!(empty_titles(&broken), [Issue::EmptyTitle { id: 2 }]);
+
+        let repairs = repair(&mut broken, now());
+        assert_eq!(repairs.len(), 3);
+        assert!(matches!(&repairs[0], Repair::Quarantined { id: 2, .. }));
+        assert_eq!(repairs[1], Repair::ParentDropped { id: 3, parent: broken.trash[0].task.uid.clone() });
+        assert_eq!(repairs[2], Repair::RelationDropped { id: 1, other: 2 });
+        // Nothing is lost: the task is in the trash whole, with when and why
+        assert_eq!(broken.tasks.iter().map(|task| task.id).collect::<Vec<_>>(), [1, 3]);
+        assert_eq!(broken.trash[0].task.description, "kept in the trash");
+        assert_eq!(broken.trash[0].trashed_at, now());
+        assert!(!broken.trash[0].note.is_empty());
+        assert!(check(&broken).is_empty());
+    }
+}
@@ -0,0 +1,304 @@
+use std::fmt;
+use std::io::Write;
+use std::process::{Command, Stdio};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use log::{debug, warn};
+use serde_json::json;
+
+use crate::{Subscriber, TaskError, TaskEvent, TaskManager, TaskStatus};
+
+// How long a hook may run before it is killed
+pub const DEFAULT_HOOK_TIMEOUT_SECS: u64 = 10;
+
+// How often a running hook is checked on
+const POLL: Duration = Duration::from_millis(10);
+
+// Which changes a hook runs on
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum HookEvent {
+    Created,
+    // A task moved to Completed
+    Completed,
+    // Any other status change
+    StatusChanged,
+    // Tagged, or its title, description, priority or due date edited
+    Updated,
+    Deleted,
+    // A due-date reminder was raised
+    Reminded,
+}
+
+impl HookEvent {
+    pub fn name(self) -> &'static str {
+        match self {
+            HookEvent::Created => "created",
+            HookEvent::Completed => "completed",
+            HookEvent::StatusChanged => "status_changed",
+            HookEvent::Updated => "updated",
+            HookEvent::Deleted => "deleted",
+            HookEvent::Reminded => "reminded",
+        }
+    }
+
+    // Which hook event, if any, a task event amounts to
+    fn of(event: &TaskEvent) -> Option<(HookEvent, u32)> {
+        match event {
+            TaskEvent::Added(id) => Some((HookEvent::Created, *id)),
+            TaskEvent::StatusChanged { id, from, to: TaskStatus::Completed } if *from != TaskStatus::Completed => {
+                Some((HookEvent::Completed, *id))
+            }
+            TaskEvent::StatusChanged { id, from, to } if from != to => Some((HookEvent::StatusChanged, *id)),
+            TaskEvent::PriorityChanged { id, .. } | TaskEvent::Tagged { id, .. } | TaskEvent::Edited(id) => {
+                Some((HookEvent::Updated, *id))
+            }
+            TaskEvent::Deleted(id) => Some((HookEvent::Deleted, *id)),
+            TaskEvent::Notified(id) => Some((HookEvent::Reminded, *id)),
+            _ => None,
+        }
+    }
+}
+
+impl FromStr for HookEvent {
+    type Err = TaskError;
+
+    fn from_str(s: &str) -> Result<HookEvent, TaskError> {
+        match s {
+            "created" => Ok(HookEvent::Created),
+            "completed" => Ok(HookEvent::Completed),
+            "status_changed" => Ok(HookEvent::StatusChanged),
+            "updated" => Ok(HookEvent::Updated),
+            "deleted" => Ok(HookEvent::Deleted),
+            "reminded" => Ok(HookEvent::Reminded),
+            _ => Err(TaskError::InvalidInput),
+        }
+    }
+}
+
+impl fmt::Display for HookEvent {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+// One `[[hooks]]` entry from the config file
+#[derive(Debug, Clone, PartialEq)]
+pub struct HookConfig {
+    pub event: HookEvent,
+    // Run through the shell, so it may carry arguments and pipes
+    pub command: String,
+}
+
+// A hook that did not finish cleanly
+#[derive(Debug, Clone, PartialEq)]
+pub struct HookFailure {
+    pub event: HookEvent,
+    pub command: String,
+    pub task_id: u32,
+    pub reason: String,
+}
+
+// Starts a hook's command; swapped out in tests to record invocations and
+// pretend to fail or hang
+pub trait CommandRunner: Send + Sync {
+    fn run(&self, command: &str, env: &[(&'static str, String)], input: &str, timeout: Duration) -> Result<(), String>;
+}
+
+// Runs hooks through `sh -c` (`cmd /C` on Windows). The hook's output is
+// dropped and its errors go to our stderr.
+pub struct ShellRunner;
+
+#[cfg(unix)]
+fn shell(command: &str) -> Command {
+    let mut shell = Command::new("sh");
+    shell.arg("-c").arg(command);
+    shell
+}
+
+#[cfg(not(unix))]
+fn shell(command: &str) -> Command {
+    let mut shell = Command::new("cmd");
+    shell.arg("/C").arg(command);
+    shell
+}
+
+impl CommandRunner for ShellRunner {
+    fn run(&self, command: &str, env: &[(&'static str, String)], input: &str, timeout: Duration) -> Result<(), String> {
+        let mut child = shell(command)
+            .envs(env.iter().map(|(key, value)| (key, value)))
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .spawn()
+            .map_err(|e| e.to_string())?;
+        // Written from the side, so a hook that never reads its input cannot hold us up
+        if let Some(mut stdin) = child.stdin.take() {
+            let input = input.to_string();
+            thread::spawn(move || {
+                let _ = stdin.write_all(input.as_bytes());
+            });
+        }
+        let deadline = Instant::now() + timeout;
+        loop {
+            match child.try_wait().map_err(|e| e.to_string())? {
+                Some(status) if status.success() => return Ok(()),
+                Some(status) => return Err(format!("exited with {}", status)),
+                None if Instant::now() >= deadline => {
+                    let _ = child.kill();
+                    let _ = child.wait();
+                    return Err(format!("timed out after {}s", timeout.as_secs_f64()));
+                }
+                None => thread::sleep(POLL),
+            }
+        }
+    }
+}
+
+// Runs the configured hooks from the TaskEvent hook, one after another. A hook
+// that fails or times out is logged and kept for the CLI to report; the change
+// that raised the event stands either way.
+pub struct Hooks {
+    hooks: Vec<HookConfig>,
+    runner: Arc<dyn CommandRunner>,
+    timeout: Duration,
+    failures: Arc<Mutex<Vec<HookFailure>>>,
+}
+
+impl Hooks {
+    pub fn new(hooks: Vec<HookConfig>, runner: Arc<dyn CommandRunner>, timeout: Duration) -> Self {
+        Hooks { hooks, runner, timeout, failures: Arc::default() }
+    }
+
+    // Where failures collect, to be taken after each command
+    pub fn failures(&self) -> Arc<Mutex<Vec<HookFailure>>> {
+        Arc::clone(&self.failures)
+    }
+
+    pub fn subscriber(self) -> Subscriber {
+        Box::new(move |event, manager| {
+            if let Some((kind, id)) = HookEvent::of(event) {
+                self.fire(kind, id, manager);
+            }
+        })
+    }
+
+    // The task goes on stdin as JSON (just its id once deleted), with TASK_ID,
+    // TASK_TITLE and EVENT in the environment
+    fn fire(&self, event: HookEvent, id: u32, manager: &TaskManager) {
+        let task = manager.get_task(id).ok();
+        let input = task.map_or_else(|| json!({ "id": id }), |task| json!(task)).to_string();
+        let title = task.map(|task| task.title.clone()).unwrap_or_default();
+        let env = [("TASK_ID", id.to_string()), ("TASK_TITLE", title), ("EVENT", event.name().to_string())];
+        for hook in self.hooks.iter().filter(|hook| hook.event == event) {
+            debug!("running {} hook for task {}: {}", event, id, hook.command);
+            if let Err(reason) = self.runner.run(&hook.command, &env, &input, self.timeout) {
+                warn!("{} hook '{}' failed for task {}: {}", event, hook.command, id, reason);
+                let failure = HookFailure { event, command: hook.command.clone(), task_id: id, reason };
+                self.failures.lock().unwrap_or_else(PoisonError::into_inner).push(failure);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Priority;
+
+    #[derive(Debug, Clone, PartialEq)]
+    struct Invocation {
+        command: String,
+        env: Vec<(&'static str, String)>,
+        input: serde_json::Value,
+    }
+
+    // Records invocations instead of running them; commands named "fail" or
+    // "hang" act as if they failed or ran past the timeout
+    #[derive(Default)]
+    struct Recorder {
+        invocations: Mutex<Vec<Invocation>>,
+    }
+
+    impl CommandRunner for Recorder {
+        fn run(&self, command: &str, env: &[(&'static str, String)], input: &str, timeout: Duration) -> Result<(), String> {
+            let input = serde_json::from_str(input).unwrap();
+            self.invocations.lock().unwrap().push(Invocation { command: command.to_string(), env: env.to_vec(), input });
+            match command {
+                "fail" => Err("exited with exit status: 1".to_string()),
+                "hang" => Err(format!("timed out after {}s", timeout.as_secs_f64())),
+                _ => Ok(()),
+            }
+        }
+    }
+
+    fn hook(event: HookEvent, command: &str) -> HookConfig {
+        HookConfig { event, command: command.to_string() }
+    }
+
+    #[test]
+    fn test_matching_hooks_get_the_task() {
+        let recorder = Arc::new(Recorder::default());
+        let hooks = Hooks::new(
+            vec![hook(HookEvent::Completed, "log-done.sh"), hook(HookEvent::Deleted, "gone.sh")],
+            recorder.clone(),
+            Duration::from_secs(1),
+        );
+        let mut manager = TaskManager::new();
+        manager.subscribe(hooks.subscriber());
+
+        let id = manager.add_task("Ship release".to_string(), String::new(), Priority::High).unwrap();
+        manager.update_task_status(id, TaskStatus::InProgress).unwrap();
+        manager.update_task_status(id, TaskStatus::Completed).unwrap();
+        manager.delete_task(id).unwrap();
+
+        let invocations = recorder.invocations.lock().unwrap();
+        let commands: Vec<&str> = invocations.iter().map(|run| run.command.as_str()).collect();
+        assert_eq!(commands, ["log-done.sh", "gone.sh"]);
+        let done = &invocations[0];
+        assert_eq!(done.env, [
+            ("TASK_ID", "1".to_string()),
+            ("TASK_TITLE", "Ship release".to_string()),
+            ("EVENT", "completed".to_string()),
+        ]);
+        assert_eq!((done.input["title"].as_str(), done.input["status"].as_str()), (Some("Ship release"), Some("Completed")));
+        assert_eq!(invocations[1].input, json!({ "id": 1 }));
+    }
+
+    #[test]
+    fn test_failures_are_kept_and_the_change_stands() {
+        let recorder = Arc::new(Recorder::default());
+        let hooks = Hooks::new(
+            vec![hook(HookEvent::Created, "fail"), hook(HookEvent::Created, "hang"), hook(HookEvent::Created, "ok")],
+            recorder.clone(),
+            Duration::from_millis(1500),
+        );
+        let failures = hooks.failures();
+        let mut manager = TaskManager::new();
+        manager.subscribe(hooks.subscriber());
+
+        let id = manager.add_task("Water plants".to_string(), String::new(), Priority::Low).unwrap();
+        assert!(manager.get_task(id).is_ok());
+        // A failing hook does not stop the ones after it
+        assert_eq!(recorder.invocations.lock().unwrap().len(), 3);
+        let failures = failures.lock().unwrap();
+        let reasons: Vec<&str> = failures.iter().map(|failure| failure.reason.as_str()).collect();
+        assert_eq!(reasons, ["exited with exit status: 1", "timed out after 1.5s"]);
+        assert_eq!((failures[0].event, failures[0].task_id), (HookEvent::Created, id));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_shell_runner() {
+        let timeout = Duration::from_secs(5);
+        let env = [("TASK_ID", "4".to_string())];
+        assert_eq!(ShellRunner.run("read line && [ \"$line\" = '{}' ] && [ \"$TASK_ID\" = 4 ]", &env, "{}\n", timeout), Ok(()));
+        assert!(ShellRunner.run("exit 3", &env, "", timeout).unwrap_err().contains('3'));
+
+        let started = Instant::now();
+        let result = ShellRunner.run("sleep 5", &env, "", Duration::from_millis(100));
+        assert_eq!(result, Err("timed out after 0.1s".to_string()));
+        assert!(started.elapsed() < Duration::from_secs(4));
+    }
+}
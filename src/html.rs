@@ -0,0 +1,194 @@
+use std::fmt::Write;
+
+use crate::dates;
+use crate::render::pin_marker;
+use crate::report::ReportData;
+use crate::{Priority, Task};
+
+// Self-contained page: inline style and script, no external assets.
+// {{name}} placeholders are filled by `fill`.
+const PAGE: &str = r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+<meta charset="utf-8">
+<title>{{title}}</title>
+<style>
+body { font-family: system-ui, sans-serif; margin: 2em auto; max-width: 60em; color: #222; }
+.cards { display: flex; gap: 1em; flex-wrap: wrap; }
+.card { border: 1px solid #ddd; border-radius: 6px; padding: 0.8em 1.2em; min-width: 7em; }
+.card .value { font-size: 1.8em; font-weight: bold; }
+table { border-collapse: collapse; width: 100%; margin-top: 1em; }
+th, td { text-align: left; padding: 0.4em 0.6em; border-bottom: 1px solid #eee; }
+th { cursor: pointer; user-select: none; }
+.priority { font-weight: bold; }
+.priority-critical { color: #b71c1c; }
+.priority-high { color: #e65100; }
+.priority-medium { color: #1565c0; }
+.priority-low { color: #607d8b; }
+footer { margin-top: 2em; color: #888; font-size: 0.9em; }
+</style>
+</head>
+<body>
+<h1>{{title}}</h1>
+<div class="cards">
+{{cards}}
+</div>
+<h2>Open tasks</h2>
+{{open}}
+<details>
+<summary>Completed tasks ({{completed_count}})</summary>
+{{done}}
+</details>
+<footer>Generated {{generated_at}}</footer>
+<script>
+document.querySelectorAll("table.sortable th").forEach(function (th) {
+  th.addEventListener("click", function () {
+    var body = th.closest("table").tBodies[0];
+    var column = th.cellIndex;
+    var ascending = th.dataset.order !== "asc";
+    th.dataset.order = ascending ? "asc" : "desc";
+    var key = function (row) { var cell = row.cells[column]; return cell.dataset.sort || cell.textContent; };
+    Array.from(body.rows)
+      .sort(function (a, b) { return key(a).localeCompare(key(b), undefined, { numeric: true }) * (ascending ? 1 : -1); })
+      .forEach(function (row) { body.appendChild(row); });
+  });
+});
+</script>
+</body>
+</html>
+"#;
+
+pub fn render(report: &ReportData) -> String {
+    fill(
+        PAGE,
+        &[
+            ("title", "Task report".to_string()),
+            ("cards", summary_cards(report)),
+            ("open", task_table(&report.open, "No open tasks.")),
+            ("completed_count", report.done.len().to_string()),
+            ("done", task_table(&report.done, "No completed tasks.")),
+            ("generated_at", escape(&report.generated_at.format("%Y-%m-%d %H:%M UTC").to_string())),
+        ],
+    )
+}
+
+// Replace each {{name}} with an already escaped value, in one pass so that
+// braces inside the values are never expanded
+fn fill(template: &str, values: &[(&str, String)]) -> String {
+    let mut out = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        out.push_str(&rest[..start]);
+        let after = &rest[start + 2..];
+        let end = after.find("}}").expect("unterminated placeholder");
+        let name = &after[..end];
+        let value = values.iter().find(|(key, _)| *key == name).map(|(_, value)| value.as_str());
+        out.push_str(value.expect("unknown placeholder"));
+        rest = &after[end + 2..];
+    }
+    out.push_str(rest);
+    out
+}
+
+pub fn summary_cards(report: &ReportData) -> String {
+    let cards = [
+        ("Total", report.total.to_string()),
+        ("Pending", report.pending.to_string()),
+        ("In progress", report.in_progress.to_string()),
+        ("Completed", report.completed.to_string()),
+        ("Done", format!("{}%", report.completion_rate())),
+    ];
+    let mut out = String::new();
+    for (label, value) in cards {
+        writeln!(out, r#"<div class="card"><div class="value">{}</div>{}</div>"#, value, label).unwrap();
+    }
+    out
+}
+
+pub fn task_table(tasks: &[Task], empty: &str) -> String {
+    if tasks.is_empty() {
+        return format!("<p>{}</p>", escape(empty));
+    }
+    let mut out = String::from(
+        "<table class=\"sortable\">\n<thead><tr><th>ID</th><th>Title</th><th>Priority</th><th>Status</th>\
+         <th>Due</th><th>Tags</th></tr></thead>\n<tbody>\n",
+    );
+    for task in tasks {
+        let due = task.due.map(|due| format!(r#"<td data-sort="{}">{}</td>"#, due, escape(&dates::format_date(due))));
+        let priority = format!(
+            r#"<td class="priority priority-{}" data-sort="{}">{}</td>"#,
+            task.priority.to_string().to_lowercase(),
+            priority_rank(&task.priority),
+            task.priority
+        );
+        writeln!(
+            out,
+            "<tr><td>{}</td><td>{}</td>{}<td>{}</td>{}<td>{}</td></tr>",
+            task.id,
+            escape(&format!("{}{}", pin_marker(task.pinned), task.title)),
+            priority,
+            task.status,
+            due.as_deref().unwrap_or("<td></td>"),
+            escape(&task.tags.join(", "))
+        )
+        .unwrap();
+    }
+    out.push_str("</tbody>\n</table>");
+    out
+}
+
+// Sorts most urgent first when ascending
+fn priority_rank(priority: &Priority) -> u8 {
+    match priority {
+        Priority::Critical => 1,
+        Priority::High => 2,
+        Priority::Medium => 3,
+        Priority::Low => 4,
+    }
+}
+
+pub fn escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    #[test]
+    fn test_user_content_is_escaped() {
+        let mut task = Task::new(1, "<script>alert('x')</script> {{done}}".to_string(), String::new(), Priority::High);
+        task.add_tag("a&b".to_string());
+        let page = render(&ReportData::gather([&task], Utc::now()));
+        assert!(!page.contains("<script>alert"));
+        assert!(page.contains("&lt;script&gt;alert(&#39;x&#39;)&lt;/script&gt;"));
+        assert!(page.contains("a&amp;b"));
+        assert_eq!(page.matches("{{").count(), 1);
+    }
+
+    #[test]
+    fn test_both_tables_sort_by_their_own_columns() {
+        let open = Task::new(1, "Open".to_string(), String::new(), Priority::Low);
+        let mut done = Task::new(2, "Done".to_string(), String::new(), Priority::High);
+        done.update_status(crate::TaskStatus::Completed);
+        let page = render(&ReportData::gather([&open, &done], Utc::now()));
+        assert_eq!(page.matches("<table class=\"sortable\">").count(), 2);
+        assert_eq!(page.matches("<th>").count(), 12);
+        // The column comes from the header's place in its own row, not its place
+        // among every header on the page
+        assert!(page.contains("forEach(function (th) {"));
+        assert!(page.contains("var column = th.cellIndex;"));
+    }
+}
@@ -0,0 +1,182 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::sync::{Arc, OnceLock, RwLock};
+
+use toml::Table;
+
+use crate::TaskError;
+
+const ENGLISH: &str = include_str!("../locales/en.toml");
+const SPANISH: &str = include_str!("../locales/es.toml");
+
+// A set of translated messages keyed by identifier, backed by English for missing keys
+#[derive(Debug, Clone, Default)]
+pub struct Catalog {
+    messages: HashMap<String, String>,
+}
+
+impl Catalog {
+    pub fn from_toml(contents: &str) -> Result<Catalog, TaskError> {
+        let table = contents
+            .parse::<Table>()
+            .map_err(|e| TaskError::Storage(format!("invalid message catalog: {}", e.message())))?;
+        let messages = table
+            .into_iter()
+            .filter_map(|(key, value)| value.as_str().map(|text| (key, text.to_string())))
+            .collect();
+        Ok(Catalog { messages })
+    }
+
+    pub fn from_file(path: &Path) -> Result<Catalog, TaskError> {
+        let contents = fs::read_to_string(path)
+            .map_err(|e| TaskError::Storage(format!("{}: {}", path.display(), e)))?;
+        Catalog::from_toml(&contents)
+    }
+
+    // Built-in catalog for a language code such as "es"
+    pub fn builtin(language: &str) -> Option<Catalog> {
+        match language {
+            "en" => Some(Catalog::default()),
+            "es" => Catalog::from_toml(SPANISH).ok(),
+            _ => None,
+        }
+    }
+
+    // Look up `key`, substituting {name} placeholders; unknown keys render as the key itself
+    pub fn format(&self, key: &str, args: &[(&str, String)]) -> String {
+        let template = self
+            .messages
+            .get(key)
+            .or_else(|| english().messages.get(key))
+            .map(String::as_str)
+            .unwrap_or(key);
+        // One pass over the template, so braces inside a value are left alone
+        let mut text = String::with_capacity(template.len());
+        let mut rest = template;
+        while let Some(open) = rest.find('{') {
+            text.push_str(&rest[..open]);
+            let after = &rest[open + 1..];
+            let value = after
+                .find('}')
+                .and_then(|close| args.iter().find(|(name, _)| *name == &after[..close]).map(|(_, value)| (close, value)));
+            match value {
+                Some((close, value)) => {
+                    text.push_str(value);
+                    rest = &after[close + 1..];
+                }
+                None => {
+                    text.push('{');
+                    rest = after;
+                }
+            }
+        }
+        text.push_str(rest);
+        text
+    }
+}
+
+fn english() -> &'static Catalog {
+    static ENGLISH_CATALOG: OnceLock<Catalog> = OnceLock::new();
+    ENGLISH_CATALOG.get_or_init(|| Catalog::from_toml(ENGLISH).expect("embedded English catalog is valid"))
+}
+
+fn active() -> &'static RwLock<Arc<Catalog>> {
+    static ACTIVE: OnceLock<RwLock<Arc<Catalog>>> = OnceLock::new();
+    ACTIVE.get_or_init(|| RwLock::new(Arc::new(Catalog::default())))
+}
+
+// Make `catalog` the one used by msg! for the rest of the process
+pub fn install(catalog: Catalog) {
+    *active().write().unwrap_or_else(|e| e.into_inner()) = Arc::new(catalog);
+}
+
+pub fn message(key: &str, args: &[(&str, String)]) -> String {
+    let catalog = Arc::clone(&active().read().unwrap_or_else(|e| e.into_inner()));
+    catalog.format(key, args)
+}
+
+// "es_ES.UTF-8" -> "es"
+pub fn language_from_locale(locale: &str) -> Option<String> {
+    let code: String = locale.chars().take_while(|c| c.is_ascii_alphabetic()).collect();
+    if code.is_empty() || code == "C" || code == "POSIX" {
+        None
+    } else {
+        Some(code.to_lowercase())
+    }
+}
+
+// Pick a catalog for `language`: a user file in `locale_dir` wins over the built-in one
+pub fn resolve(language: &str, locale_dir: Option<&Path>) -> Result<Option<Catalog>, TaskError> {
+    if let Some(dir) = locale_dir {
+        let path = dir.join(format!("{}.toml", language));
+        if path.exists() {
+            return Catalog::from_file(&path).map(Some);
+        }
+    }
+    Ok(Catalog::builtin(language))
+}
+
+// msg!("task_added", id = 3) looks up a message in the active catalog
+#[macro_export]
+macro_rules! msg {
+    ($key:expr) => {
+        $crate::i18n::message($key, &[])
+    };
+    ($key:expr, $($name:ident = $value:expr),+ $(,)?) => {
+        $crate::i18n::message($key, &[$((stringify!($name), $value.to_string())),+])
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_english_default_and_placeholders() {
+        let catalog = Catalog::default();
+        assert_eq!(catalog.format("task_added", &[("id", "7".to_string())]), "Task added successfully with ID: 7");
+        assert_eq!(catalog.format("no_such_key", &[]), "no_such_key");
+        assert_eq!(msg!("error_task_not_found", id = 4), "Task 4 not found");
+    }
+
+    #[test]
+    fn test_placeholders_in_values_are_not_substituted() {
+        let catalog = Catalog::default();
+        let args = [("id", "5".to_string()), ("title", "Rename {id} to {title}".to_string())];
+        assert_eq!(catalog.format("reminder_body", &args), "#5 Rename {id} to {title}");
+        assert_eq!(msg!("webhook_completed", id = 2, title = "{id}"), "Completed task #2: {id}");
+        // Braces that are not a known placeholder stay as written
+        assert_eq!(catalog.format("{open {id}", &[("id", "1".to_string())]), "{open 1");
+    }
+
+    #[test]
+    fn test_spanish_catalog_overrides_and_falls_back() {
+        let catalog = Catalog::builtin("es").unwrap();
+        assert_eq!(catalog.format("task_added", &[("id", "3".to_string())]), "Tarea añadida con el ID: 3");
+        assert_eq!(catalog.format("goodbye", &[]), "¡Adiós!");
+        assert_eq!(catalog.format("reminder_due", &[("date", "2024-07-02".to_string())]), "Vence el 2024-07-02");
+        // Not translated in the sample, so English is used
+        assert_eq!(catalog.format("stats_header", &[]), "=== Task Statistics ===");
+        assert_eq!(catalog.format("usage_delete", &[]), "Usage: delete <task_id>");
+    }
+
+    #[test]
+    fn test_language_from_locale() {
+        assert_eq!(language_from_locale("es_ES.UTF-8"), Some("es".to_string()));
+        assert_eq!(language_from_locale("de"), Some("de".to_string()));
+        assert_eq!(language_from_locale("C"), None);
+        assert_eq!(language_from_locale(""), None);
+    }
+
+    #[test]
+    fn test_user_catalog_file_wins_over_builtin() {
+        let dir = std::env::temp_dir().join(format!("task-manager-locales-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("es.toml"), "goodbye = \"Hasta luego\"\n").unwrap();
+        let catalog = resolve("es", Some(&dir)).unwrap().unwrap();
+        assert_eq!(catalog.format("goodbye", &[]), "Hasta luego");
+        assert!(resolve("xx", Some(&dir)).unwrap().is_none());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
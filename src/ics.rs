@@ -0,0 +1,145 @@
+use chrono::{DateTime, Utc};
+
+use crate::{Priority, Task, TaskStatus};
+
+// Content lines longer than this many octets are folded (RFC 5545 §3.1)
+const MAX_LINE_OCTETS: usize = 75;
+
+// One VTODO per task that has a due date; `now` stamps every entry
+pub fn to_ics<'a>(tasks: impl IntoIterator<Item = &'a Task>, now: DateTime<Utc>) -> String {
+    let mut out = String::new();
+    push_line(&mut out, "BEGIN:VCALENDAR");
+    push_line(&mut out, "VERSION:2.0");
+    push_line(&mut out, "PRODID:-//Task Manager//EN");
+    for task in tasks {
+        let Some(due) = task.due else { continue };
+        push_line(&mut out, "BEGIN:VTODO");
+        push_line(&mut out, &format!("UID:{}", escape(&task.uid)));
+        push_line(&mut out, &format!("DTSTAMP:{}", now.format("%Y%m%dT%H%M%SZ")));
+        push_line(&mut out, &format!("CREATED:{}", task.created_at.format("%Y%m%dT%H%M%SZ")));
+        push_line(&mut out, &format!("LAST-MODIFIED:{}", task.updated_at.format("%Y%m%dT%H%M%SZ")));
+        push_line(&mut out, &format!("SUMMARY:{}", escape(&task.title)));
+        if !task.description.is_empty() {
+            push_line(&mut out, &format!("DESCRIPTION:{}", escape(&task.description)));
+        }
+        push_line(&mut out, &format!("DUE;VALUE=DATE:{}", due.format("%Y%m%d")));
+        push_line(&mut out, &format!("PRIORITY:{}", ical_priority(&task.priority)));
+        push_line(&mut out, &format!("STATUS:{}", ical_status(&task.status)));
+        if !task.tags.is_empty() {
+            let categories: Vec<String> = task.tags.iter().map(|tag| escape(tag)).collect();
+            push_line(&mut out, &format!("CATEGORIES:{}", categories.join(",")));
+        }
+        push_line(&mut out, "END:VTODO");
+    }
+    push_line(&mut out, "END:VCALENDAR");
+    out
+}
+
+// iCal priorities run 1 (highest) to 9 (lowest); 0 means undefined
+fn ical_priority(priority: &Priority) -> u8 {
+    match priority {
+        Priority::Critical => 1,
+        Priority::High => 3,
+        Priority::Medium => 5,
+        Priority::Low => 9,
+    }
+}
+
+fn ical_status(status: &TaskStatus) -> &'static str {
+    match status {
+        TaskStatus::Pending => "NEEDS-ACTION",
+        TaskStatus::InProgress => "IN-PROCESS",
+        TaskStatus::Completed => "COMPLETED",
+    }
+}
+
+// TEXT value escaping: backslash, semicolon, comma and newlines
+fn escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' => out.push_str("\\\\"),
+            ';' => out.push_str("\\;"),
+            ',' => out.push_str("\\,"),
+            '\n' => out.push_str("\\n"),
+            '\r' => {}
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+// Append a content line, folding it into CRLF + space continuations without splitting a character
+fn push_line(out: &mut String, line: &str) {
+    let mut width = 0;
+    for c in line.chars() {
+        if width + c.len_utf8() > MAX_LINE_OCTETS {
+            out.push_str("\r\n ");
+            // The leading space counts toward the continuation line's length
+            width = 1;
+        }
+        out.push(c);
+        width += c.len_utf8();
+    }
+    out.push_str("\r\n");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{NaiveDate, TimeZone};
+
+    fn dated_task() -> Task {
+        let mut task = Task::new(3, "Renew passport, maybe; soon".to_string(), "Bring photo\nand form".to_string(), Priority::High);
+        task.uid = "0b7e7a52-1b44-4c1f-9a4f-5d8e50f1c3aa".to_string();
+        task.due = NaiveDate::from_ymd_opt(2024, 8, 15);
+        task.add_tag("admin".to_string());
+        task.add_tag("travel".to_string());
+        task.update_status(TaskStatus::InProgress);
+        task
+    }
+
+    // Undo folding and split into (name with params, value) pairs
+    fn parse(ics: &str) -> Vec<(String, String)> {
+        ics.replace("\r\n ", "")
+            .split("\r\n")
+            .filter(|line| !line.is_empty())
+            .map(|line| {
+                let (name, value) = line.split_once(':').unwrap();
+                (name.to_string(), value.to_string())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_vtodo_fields() {
+        let undated = Task::new(4, "Someday".to_string(), String::new(), Priority::Low);
+        let now = Utc.with_ymd_and_hms(2024, 7, 1, 12, 0, 0).unwrap();
+        let tasks = [dated_task(), undated];
+        let ics = to_ics(&tasks, now);
+        let fields = parse(&ics);
+        let get = |name: &str| fields.iter().find(|(n, _)| n == name).map(|(_, v)| v.as_str());
+
+        assert_eq!(fields.first().unwrap().1, "VCALENDAR");
+        assert_eq!(fields.iter().filter(|(n, v)| n == "BEGIN" && v == "VTODO").count(), 1);
+        assert_eq!(get("UID"), Some("0b7e7a52-1b44-4c1f-9a4f-5d8e50f1c3aa"));
+        assert_eq!(get("DTSTAMP"), Some("20240701T120000Z"));
+        assert_eq!(get("SUMMARY"), Some("Renew passport\\, maybe\\; soon"));
+        assert_eq!(get("DESCRIPTION"), Some("Bring photo\\nand form"));
+        assert_eq!(get("DUE;VALUE=DATE"), Some("20240815"));
+        assert_eq!(get("PRIORITY"), Some("3"));
+        assert_eq!(get("STATUS"), Some("IN-PROCESS"));
+        assert_eq!(get("CATEGORIES"), Some("admin,travel"));
+        assert!(ics.ends_with("END:VCALENDAR\r\n"));
+    }
+
+    #[test]
+    fn test_long_lines_fold_at_75_octets() {
+        let mut task = dated_task();
+        task.title = "é".repeat(100);
+        let ics = to_ics([&task], Utc::now());
+        assert!(ics.split("\r\n").all(|line| line.len() <= MAX_LINE_OCTETS));
+        let summary = parse(&ics).into_iter().find(|(n, _)| n == "SUMMARY").unwrap().1;
+        assert_eq!(summary, task.title);
+    }
+}
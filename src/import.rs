@@ -0,0 +1,254 @@
+use std::collections::{HashMap, HashSet};
+
+use serde::Serialize;
+
+use crate::manager::normalize_title;
+use crate::{Priority, Task, TaskEdit, TaskError, TaskManager, TaskStatus};
+
+// A task as read from an outside source, before it is matched against the list
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImportedTask {
+    // Stable key in the source, stored on the task so re-imports update it
+    pub external_id: String,
+    pub title: String,
+    pub description: String,
+    // None keeps the current priority on update and uses the default on create
+    pub priority: Option<Priority>,
+    pub tags: Vec<String>,
+    pub status: TaskStatus,
+    // Short source key, appended to the title when another task already has it;
+    // None rejects the clashing row instead
+    pub key: Option<String>,
+}
+
+// What happened to each row of an import
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct ImportReport {
+    pub source: String,
+    pub created: Vec<u32>,
+    pub updated: Vec<u32>,
+    // (row label, reason) for rows that were rejected
+    pub skipped: Vec<(String, String)>,
+    // Rows that went in, but not quite as the source had them
+    pub warnings: Vec<String>,
+}
+
+// Create or update one task per row; a bad row is reported and the rest still go in.
+// New rows are gathered and added in bulk, flushed early whenever a later row
+// updates one of them, so the outcome is the same as taking the rows one by one.
+// Rows without a priority are created at `default_priority`.
+pub fn apply(manager: &mut TaskManager, source: &str, rows: Vec<ImportedTask>, default_priority: &Priority) -> ImportReport {
+    let mut report = ImportReport { source: source.to_string(), ..ImportReport::default() };
+    let mut known: HashMap<String, u32> = HashMap::new();
+    for task in manager.list_tasks() {
+        if let Some(external_id) = &task.external_id {
+            known.entry(external_id.clone()).or_insert(task.id);
+        }
+    }
+    let mut new_rows: Vec<ImportedTask> = Vec::new();
+    let mut new_ids: HashSet<String> = HashSet::new();
+    for row in rows {
+        if new_ids.contains(&row.external_id) {
+            create_all(manager, std::mem::take(&mut new_rows), default_priority, &mut known, &mut report);
+            new_ids.clear();
+        }
+        match known.get(&row.external_id) {
+            Some(&id) => {
+                let label = row.external_id.clone();
+                match update(manager, id, row) {
+                    Ok(()) => report.updated.push(id),
+                    Err(e) => report.skipped.push((label, e.to_string())),
+                }
+            }
+            None => {
+                new_ids.insert(row.external_id.clone());
+                new_rows.push(row);
+            }
+        }
+    }
+    create_all(manager, new_rows, default_priority, &mut known, &mut report);
+    // Rows cut down to the limits with --truncate; rows over them otherwise are skipped
+    report.warnings.extend(manager.take_truncations());
+    report
+}
+
+fn create_all(
+    manager: &mut TaskManager,
+    rows: Vec<ImportedTask>,
+    default_priority: &Priority,
+    known: &mut HashMap<String, u32>,
+    report: &mut ImportReport,
+) {
+    // A title already taken, in the list or earlier in this batch, gets the source key
+    let mut claimed = HashSet::new();
+    let tasks = rows
+        .iter()
+        .map(|row| {
+            let taken = manager.find_by_title(&row.title).is_some() || claimed.contains(&normalize_title(&row.title));
+            let title = if taken && row.key.is_some() { keyed_title(row) } else { row.title.clone() };
+            claimed.insert(normalize_title(&title));
+            build(row, title, default_priority)
+        })
+        .collect();
+    let result = manager.add_tasks_bulk(tasks);
+    for (row, outcome) in rows.into_iter().zip(result.outcomes) {
+        match outcome {
+            Ok(id) => {
+                known.insert(row.external_id, id);
+                report.created.push(id);
+            }
+            Err(e) => report.skipped.push((row.external_id, e.to_string())),
+        }
+    }
+}
+
+fn build(row: &ImportedTask, title: String, default_priority: &Priority) -> Task {
+    let priority = row.priority.clone().unwrap_or_else(|| default_priority.clone());
+    let mut task = Task::new(0, title, row.description.clone(), priority);
+    task.external_id = Some(row.external_id.clone());
+    for tag in &row.tags {
+        task.add_tag(tag.clone());
+    }
+    task.update_status(row.status.clone());
+    task
+}
+
+fn update(manager: &mut TaskManager, id: u32, row: ImportedTask) -> Result<(), TaskError> {
+    let edit = |title: String| TaskEdit {
+        title: Some(title),
+        description: Some(row.description.clone()),
+        priority: row.priority.clone(),
+        ..TaskEdit::default()
+    };
+    match manager.apply_edit(id, edit(row.title.clone())) {
+        Err(TaskError::DuplicateTask { .. }) if row.key.is_some() => manager.apply_edit(id, edit(keyed_title(&row)))?,
+        result => result?,
+    }
+    for tag in &row.tags {
+        manager.add_tag_to_task(id, tag.clone())?;
+    }
+    let current = manager.get_task(id)?.status.clone();
+    // "Not started" upstream does not undo local progress on an open task
+    let keep = current == row.status || (current == TaskStatus::InProgress && row.status == TaskStatus::Pending);
+    if !keep {
+        manager.update_task_status(id, row.status)?;
+    }
+    Ok(())
+}
+
+fn keyed_title(row: &ImportedTask) -> String {
+    format!("{} ({})", row.title, row.key.as_deref().unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::validation::Limits;
+
+    fn row(id: &str, title: &str) -> ImportedTask {
+        ImportedTask {
+            external_id: id.to_string(),
+            title: title.to_string(),
+            description: String::new(),
+            priority: None,
+            tags: vec!["imported".to_string()],
+            status: TaskStatus::Pending,
+            key: None,
+        }
+    }
+
+    #[test]
+    fn test_reimport_updates_instead_of_duplicating() {
+        let mut manager = TaskManager::new();
+        let first = apply(&mut manager, "test", vec![row("x#1", "First"), row("x#2", "Second")], &Priority::Medium);
+        assert_eq!(first.created, vec![1, 2]);
+
+        let mut changed = row("x#1", "First, renamed");
+        changed.priority = Some(Priority::High);
+        changed.status = TaskStatus::Completed;
+        let second = apply(&mut manager, "test", vec![changed, row("x#3", "Third")], &Priority::Medium);
+        assert_eq!(second.updated, vec![1]);
+        assert_eq!(second.created, vec![3]);
+
+        let task = manager.get_task(1).unwrap();
+        assert_eq!(task.title, "First, renamed");
+        assert_eq!(task.priority, Priority::High);
+        assert_eq!(task.status, TaskStatus::Completed);
+        assert_eq!(manager.list_tasks().len(), 3);
+    }
+
+    #[test]
+    fn test_repeated_row_updates_the_task_created_earlier() {
+        let mut manager = TaskManager::new();
+        let mut again = row("x#1", "First");
+        again.status = TaskStatus::Completed;
+        let rows = vec![row("x#1", "First"), row("x#2", "Second"), again, row("x#3", "Third")];
+        let report = apply(&mut manager, "test", rows, &Priority::Medium);
+        assert_eq!(report.created, vec![1, 2, 3]);
+        assert_eq!(report.updated, vec![1]);
+        assert!(report.skipped.is_empty());
+        assert_eq!(manager.get_task(1).unwrap().status, TaskStatus::Completed);
+    }
+
+    #[test]
+    fn test_rejected_rows_are_reported() {
+        let mut manager = TaskManager::new();
+        manager.add_task("Taken".to_string(), String::new(), Priority::Low).unwrap();
+        let report = apply(&mut manager, "test", vec![row("x#1", "taken"), row("x#2", "Fine")], &Priority::Medium);
+        assert_eq!(report.created, vec![2]);
+        assert_eq!(report.skipped.len(), 1);
+        assert_eq!(report.skipped[0].0, "x#1");
+        assert!(report.skipped[0].1.contains("already exists"));
+    }
+
+    #[test]
+    fn test_clashing_titles_take_the_source_key() {
+        let mut manager = TaskManager::new();
+        let mut first = row("x#1", "Same");
+        first.key = Some("X-1".to_string());
+        let mut second = row("x#2", "Same");
+        second.key = Some("X-2".to_string());
+        let report = apply(&mut manager, "test", vec![first.clone(), second.clone()], &Priority::Medium);
+        assert_eq!(report.created, vec![1, 2]);
+        assert_eq!(manager.get_task(2).unwrap().title, "Same (X-2)");
+
+        // Re-importing keeps the keyed title rather than failing
+        let report = apply(&mut manager, "test", vec![first, second], &Priority::Medium);
+        assert_eq!(report.updated, vec![1, 2]);
+        assert_eq!(manager.get_task(2).unwrap().title, "Same (X-2)");
+    }
+
+    #[test]
+    fn test_default_priority_only_fills_new_rows() {
+        let mut manager = TaskManager::new();
+        let mut ranked = row("x#1", "Ranked");
+        ranked.priority = Some(Priority::Low);
+        apply(&mut manager, "test", vec![ranked, row("x#2", "Unranked")], &Priority::High);
+        assert_eq!(manager.get_task(1).unwrap().priority, Priority::Low);
+        assert_eq!(manager.get_task(2).unwrap().priority, Priority::High);
+
+        // An update without a priority keeps the one the task has
+        manager.apply_edit(2, TaskEdit { priority: Some(Priority::Medium), ..TaskEdit::default() }).unwrap();
+        apply(&mut manager, "test", vec![row("x#2", "Unranked")], &Priority::Critical);
+        assert_eq!(manager.get_task(2).unwrap().priority, Priority::Medium);
+    }
+
+    #[test]
+    fn test_rows_over_the_limits_are_reported() {
+        let mut manager = TaskManager::new();
+        manager.set_limits(Limits { description_chars: Some(10), notes_per_task: None, tags_per_task: None });
+        let mut long = row("x#1", "Long");
+        long.description = "far more than ten characters".to_string();
+        let rows = vec![long, row("x#2", "Short")];
+        let report = apply(&mut manager, "test", rows.clone(), &Priority::Medium);
+        assert_eq!(report.created, vec![1]);
+        assert_eq!(report.skipped[0].0, "x#1");
+        assert!(report.skipped[0].1.contains("limits.description_chars"));
+
+        manager.set_truncate_to_limits(true);
+        let report = apply(&mut manager, "test", rows, &Priority::Medium);
+        assert_eq!((report.created, report.updated), (vec![2], vec![1]));
+        assert_eq!(manager.get_task(2).unwrap().description, "far more…");
+        assert_eq!(report.warnings, ["task 2: cut the description from 28 to 10 characters (limits.description_chars)"]);
+    }
+}
@@ -0,0 +1,171 @@
+use crate::import::ImportedTask;
+use crate::msg;
+use crate::{Priority, TaskError, TaskStatus};
+
+// A Jira CSV export mapped onto local tasks
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct JiraImport {
+    pub rows: Vec<ImportedTask>,
+    // Values that had no local equivalent and were defaulted
+    pub warnings: Vec<String>,
+}
+
+// Parse an export; "Issue key" and "Summary" are required, everything else is optional
+pub fn parse_csv(text: &str) -> Result<JiraImport, TaskError> {
+    let mut reader = csv::ReaderBuilder::new().flexible(true).from_reader(text.as_bytes());
+    let headers = reader.headers().map_err(csv_error)?.clone();
+    let columns = |name: &str| -> Vec<usize> {
+        headers.iter().enumerate().filter(|(_, header)| header.trim() == name).map(|(i, _)| i).collect()
+    };
+    let required = |name: &str| {
+        columns(name).first().copied().ok_or_else(|| TaskError::Import(msg!("jira_missing_column", column = name)))
+    };
+    let key_column = required("Issue key")?;
+    let summary_column = required("Summary")?;
+    let priority_column = columns("Priority").first().copied();
+    let status_column = columns("Status").first().copied();
+    let description_column = columns("Description").first().copied();
+    // Jira writes one "Labels" column per label on the busiest issue
+    let label_columns = columns("Labels");
+
+    let mut import = JiraImport::default();
+    for record in reader.records() {
+        let record = record.map_err(csv_error)?;
+        let field = |column: Option<usize>| column.and_then(|i| record.get(i)).unwrap_or("").trim();
+        let key = field(Some(key_column)).to_string();
+        if key.is_empty() {
+            continue;
+        }
+
+        let priority = match field(priority_column) {
+            "" => None,
+            name => {
+                let priority = map_priority(name);
+                if priority.is_none() {
+                    import.warnings.push(msg!("jira_unknown_priority", key = key, priority = name));
+                }
+                priority
+            }
+        };
+        // A blank status is one Jira left out, not one it made up
+        let status = match field(status_column) {
+            "" => TaskStatus::Pending,
+            name => map_status(name).unwrap_or_else(|| {
+                import.warnings.push(msg!("jira_unknown_status", key = key, status = name));
+                TaskStatus::Pending
+            }),
+        };
+
+        let mut tags = vec![key.clone()];
+        for column in &label_columns {
+            for label in field(Some(*column)).split_whitespace() {
+                if !tags.iter().any(|tag| tag == label) {
+                    tags.push(label.to_string());
+                }
+            }
+        }
+
+        import.rows.push(ImportedTask {
+            external_id: format!("jira:{}", key),
+            title: field(Some(summary_column)).to_string(),
+            description: field(description_column).to_string(),
+            priority,
+            tags,
+            status,
+            key: Some(key),
+        });
+    }
+    Ok(import)
+}
+
+fn csv_error(e: csv::Error) -> TaskError {
+    TaskError::Import(msg!("jira_bad_csv", error = e))
+}
+
+// The five stock Jira priorities plus the older Blocker..Trivial scheme
+pub fn map_priority(name: &str) -> Option<Priority> {
+    match name.trim().to_lowercase().as_str() {
+        "highest" | "blocker" | "critical" => Some(Priority::Critical),
+        "high" | "major" => Some(Priority::High),
+        "medium" | "normal" => Some(Priority::Medium),
+        "low" | "lowest" | "minor" | "trivial" => Some(Priority::Low),
+        _ => None,
+    }
+}
+
+// Common workflow status names; None for anything custom
+pub fn map_status(name: &str) -> Option<TaskStatus> {
+    match name.trim().to_lowercase().as_str() {
+        "to do" | "todo" | "open" | "new" | "backlog" | "selected for development" | "reopened" => {
+            Some(TaskStatus::Pending)
+        }
+        "in progress" | "in development" | "in review" | "code review" | "in testing" => Some(TaskStatus::InProgress),
+        "done" | "closed" | "resolved" | "complete" | "completed" => Some(TaskStatus::Completed),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::TaskManager;
+    use crate::import;
+
+    const EXPORT: &str = include_str!("../tests/fixtures/jira_export.csv");
+
+    #[test]
+    fn test_fixture_maps_fields() {
+        let import = parse_csv(EXPORT).unwrap();
+        assert_eq!(import.rows.len(), 5);
+
+        let login = &import.rows[0];
+        assert_eq!(login.external_id, "jira:WEB-101");
+        assert_eq!(login.priority, Some(Priority::Critical));
+        assert_eq!(login.status, TaskStatus::InProgress);
+        assert_eq!(login.tags, vec!["WEB-101", "frontend", "auth"]);
+        assert!(login.description.contains("\nReproduced on Chrome"));
+
+        assert_eq!(import.rows[1].tags, vec!["WEB-102", "copy", "ux"]);
+        assert_eq!(import.rows[2].status, TaskStatus::Pending);
+        assert_eq!(import.rows[3].title, "Migrate CI to the new runners, phase 1");
+        assert_eq!(import.rows[3].status, TaskStatus::Completed);
+        assert_eq!(import.rows[4].priority, Some(Priority::Low));
+
+        // "Awaiting Triage" is a custom status
+        assert_eq!(import.rows[4].status, TaskStatus::Pending);
+        assert_eq!(import.warnings.len(), 1);
+        assert!(import.warnings[0].contains("WEB-105"));
+    }
+
+    #[test]
+    fn test_duplicate_summaries_get_the_issue_key() {
+        let mut manager = TaskManager::new();
+        let report = import::apply(&mut manager, "jira", parse_csv(EXPORT).unwrap().rows, &Priority::Medium);
+        assert_eq!(report.created.len(), 5);
+        assert!(report.skipped.is_empty());
+        let titles: Vec<String> = manager.list_tasks().iter().map(|task| task.title.clone()).collect();
+        assert!(titles.contains(&"Login page times out behind proxy".to_string()));
+        assert!(titles.contains(&"Login page times out behind proxy (WEB-103)".to_string()));
+
+        let again = import::apply(&mut manager, "jira", parse_csv(EXPORT).unwrap().rows, &Priority::Medium);
+        assert_eq!(again.updated.len(), 5);
+        assert_eq!(manager.list_tasks().len(), 5);
+    }
+
+    #[test]
+    fn test_blank_or_missing_status_is_pending_without_a_warning() {
+        let import = parse_csv("Issue key,Summary,Status\nA-1,Blank,\nA-2,Short row\nA-3,Spaces,  \n").unwrap();
+        assert!(import.rows.iter().all(|row| row.status == TaskStatus::Pending));
+        assert!(import.warnings.is_empty(), "{:?}", import.warnings);
+        let import = parse_csv("Issue key,Summary\nA-1,No status column\n").unwrap();
+        assert_eq!((import.rows[0].status.clone(), import.warnings.len()), (TaskStatus::Pending, 0));
+    }
+
+    #[test]
+    fn test_missing_columns_are_reported() {
+        let err = parse_csv("Key,Title\nA-1,Something\n").unwrap_err();
+        assert!(matches!(&err, TaskError::Import(message) if message.contains("Issue key")));
+        assert_eq!(map_priority("Blocker"), Some(Priority::Critical));
+        assert_eq!(map_status("code review"), Some(TaskStatus::InProgress));
+    }
+}
@@ -0,0 +1,481 @@
+use std::collections::{BTreeMap, HashSet};
+use std::fs::{self, OpenOptions};
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, PoisonError};
+
+use chrono::NaiveDate;
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+
+use crate::audit::AuditEntry;
+use crate::goal::Goal;
+use crate::health::TrashedTask;
+use crate::retention::ArchivedTask;
+use crate::storage::{self, FileStorage, SaveTimer, SavedState, Storage, StorageReport, StoreFormat};
+use crate::sync::SyncState;
+use crate::tags::TagRegistry;
+use crate::undo::UndoEntry;
+use crate::{Task, TaskError};
+
+// Log records written before the snapshot is rewritten when the config does not say otherwise
+pub const DEFAULT_COMPACT_AFTER: usize = 1000;
+
+// One line of the change log
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+enum Record {
+    // The task as it is now, added or changed
+    Put { task: Box<Task> },
+    Delete { id: u32 },
+    NextId { next_id: u32 },
+    Audit { entries: Vec<AuditEntry> },
+    Sync { sync: SyncState },
+    // Every saved view, replacing the ones before
+    Views { views: BTreeMap<String, String> },
+    // Drop the `dropped` oldest undo steps, keep the next `kept` and add `added` after them
+    Undo { dropped: usize, kept: usize, added: Vec<UndoEntry> },
+    // The whole tag registry, replacing the one before
+    Tags { tags: TagRegistry },
+    // The completion goal, None once cleared
+    Goal { goal: Option<Goal> },
+    // Everything in the trash, replacing what was there
+    Trash { trash: Vec<TrashedTask> },
+    // Everything in the archive, replacing what was there
+    Archive { archive: Vec<ArchivedTask> },
+    RetentionRun { day: Option<NaiveDate> },
+}
+
+// The log kept next to a data file: tasks.json -> tasks.log
+pub fn log_path(data_path: &Path) -> PathBuf {
+    data_path.with_extension("log")
+}
+
+// `journal = true`: the data file is a snapshot and each save appends only what
+// changed to the log beside it as NDJSON. The snapshot is rewritten, and the log
+// emptied, once the log grows past `compact_after` records and on exit.
+pub struct JournalStorage {
+    snapshot: FileStorage,
+    log_path: PathBuf,
+    compact_after: usize,
+    // What snapshot plus log add up to, so a save can work out the difference;
+    // None until the first load
+    written: Mutex<Option<Written>>,
+    timer: SaveTimer,
+}
+
+struct Written {
+    tasks: BTreeMap<u32, Task>,
+    next_id: u32,
+    audit: Vec<AuditEntry>,
+    sync: SyncState,
+    views: BTreeMap<String, String>,
+    undo: Vec<UndoEntry>,
+    tags: TagRegistry,
+    goal: Option<Goal>,
+    trash: Vec<TrashedTask>,
+    archive: Vec<ArchivedTask>,
+    retention_run: Option<NaiveDate>,
+    // Lines in the log
+    records: usize,
+}
+
+impl Written {
+    fn new(state: SavedState, records: usize) -> Written {
+        Written {
+            tasks: state.tasks.into_iter().map(|task| (task.id, task)).collect(),
+            next_id: state.next_id,
+            audit: state.audit,
+            sync: state.sync,
+            views: state.views,
+            undo: state.undo,
+            tags: state.tags,
+            goal: state.goal,
+            trash: state.trash,
+            archive: state.archive,
+            retention_run: state.retention_run,
+            records,
+        }
+    }
+
+    fn to_state(&self) -> SavedState {
+        SavedState {
+            tasks: self.tasks.values().cloned().collect(),
+            next_id: self.next_id,
+            audit: self.audit.clone(),
+            sync: self.sync.clone(),
+            views: self.views.clone(),
+            undo: self.undo.clone(),
+            tags: self.tags.clone(),
+            goal: self.goal.clone(),
+            trash: self.trash.clone(),
+            archive: self.archive.clone(),
+            retention_run: self.retention_run,
+        }
+    }
+
+    // `known` holds the audit entries already in, so replayed ones are skipped
+    fn apply(&mut self, record: Record, known: &mut HashSet<AuditEntry>) {
+        match record {
+            Record::Put { task } => {
+                self.tasks.insert(task.id, *task);
+            }
+            Record::Delete { id } => {
+                self.tasks.remove(&id);
+            }
+            Record::NextId { next_id } => self.next_id = next_id,
+            // A crash between rewriting the snapshot and removing the log replays
+            // entries the snapshot already has
+            Record::Audit { entries } => {
+                for entry in entries {
+                    if known.insert(entry.clone()) {
+                        self.audit.push(entry);
+                    }
+                }
+            }
+            Record::Sync { sync } => self.sync = sync,
+            Record::Views { views } => self.views = views,
+            Record::Tags { tags } => self.tags = tags,
+            Record::Goal { goal } => self.goal = goal,
+            Record::Trash { trash } => self.trash = trash,
+            Record::Archive { archive } => self.archive = archive,
+            Record::RetentionRun { day } => self.retention_run = day,
+            Record::Undo { dropped, kept, added } => {
+                self.undo.drain(..dropped.min(self.undo.len()));
+                self.undo.truncate(kept);
+                self.undo.extend(added);
+            }
+        }
+    }
+
+    // The records that turn this into `state`
+    fn changes(&self, state: &SavedState) -> Vec<Record> {
+        let kept: HashSet<u32> = state.tasks.iter().map(|task| task.id).collect();
+        let mut records: Vec<Record> = self
+            .tasks
+            .keys()
+            .filter(|id| !kept.contains(id))
+            .map(|&id| Record::Delete { id })
+            .collect();
+        records.extend(
+            state
+                .tasks
+                .iter()
+                .filter(|task| self.tasks.get(&task.id) != Some(*task))
+                .map(|task| Record::Put { task: Box::new(task.clone()) }),
+        );
+        if state.next_id != self.next_id {
+            records.push(Record::NextId { next_id: state.next_id });
+        }
+        // New entries follow the last one written; when the limit has trimmed even
+        // that one away, the whole log goes and replay skips what it already has
+        let added = match self.audit.last().and_then(|last| state.audit.iter().rposition(|entry| entry == last)) {
+            Some(position) => &state.audit[position + 1..],
+            None => &state.audit[..],
+        };
+        if !added.is_empty() {
+            records.push(Record::Audit { entries: added.to_vec() });
+        }
+        if state.sync != self.sync {
+            records.push(Record::Sync { sync: state.sync.clone() });
+        }
+        if state.views != self.views {
+            records.push(Record::Views { views: state.views.clone() });
+        }
+        if state.tags != self.tags {
+            records.push(Record::Tags { tags: state.tags.clone() });
+        }
+        if state.goal != self.goal {
+            records.push(Record::Goal { goal: state.goal.clone() });
+        }
+        if state.trash != self.trash {
+            records.push(Record::Trash { trash: state.trash.clone() });
+        }
+        if state.archive != self.archive {
+            records.push(Record::Archive { archive: state.archive.clone() });
+        }
+        if state.retention_run != self.retention_run {
+            records.push(Record::RetentionRun { day: state.retention_run });
+        }
+        // Steps come off the front past the limit and off the back when undone
+        if state.undo != self.undo {
+            let dropped = state.undo.first().and_then(|first| self.undo.iter().position(|entry| entry == first));
+            let dropped = dropped.unwrap_or(self.undo.len());
+            let kept = self.undo[dropped..].iter().zip(&state.undo).take_while(|(a, b)| a == b).count();
+            records.push(Record::Undo { dropped, kept, added: state.undo[kept..].to_vec() });
+        }
+        records
+    }
+}
+
+impl JournalStorage {
+    pub fn new(data_path: impl Into<PathBuf>, compact_after: usize) -> Self {
+        let data_path = data_path.into();
+        JournalStorage {
+            log_path: log_path(&data_path),
+            snapshot: FileStorage::new(data_path),
+            compact_after,
+            written: Mutex::new(None),
+            timer: SaveTimer::default(),
+        }
+    }
+
+    pub fn with_format(mut self, format: StoreFormat) -> Self {
+        self.snapshot = self.snapshot.with_format(format);
+        self
+    }
+
+    fn log_error(&self, e: impl std::fmt::Display) -> TaskError {
+        TaskError::Storage(format!("{}: {}", self.log_path.display(), e))
+    }
+
+    // Records in the log, in order. A final line that does not parse is what a
+    // crash mid-append leaves behind: it is dropped and cut from the file so the
+    // next append starts clean. Damage anywhere else is an error.
+    fn read_log(&self) -> Result<Vec<Record>, TaskError> {
+        let contents = match fs::read(&self.log_path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(self.log_error(e)),
+        };
+        let mut records = Vec::new();
+        let mut offset = 0;
+        for (number, line) in contents.split_inclusive(|&byte| byte == b'\n').enumerate() {
+            let last = offset + line.len() == contents.len();
+            match serde_json::from_slice::<Record>(line) {
+                Ok(record) if line.ends_with(b"\n") => records.push(record),
+                Err(e) if !last => return Err(self.log_error(format!("line {}: {}", number + 1, e))),
+                result => {
+                    let reason = result.err().map_or_else(|| "no line ending".to_string(), |e| e.to_string());
+                    warn!("dropping torn record at the end of {}: {}", self.log_path.display(), reason);
+                    OpenOptions::new()
+                        .write(true)
+                        .open(&self.log_path)
+                        .and_then(|file| file.set_len(offset as u64))
+                        .map_err(|e| self.log_error(e))?;
+                }
+            }
+            offset += line.len();
+        }
+        Ok(records)
+    }
+
+    fn append(&self, records: &[Record]) -> Result<(), TaskError> {
+        let mut lines = String::new();
+        for record in records {
+            lines.push_str(&serde_json::to_string(record).map_err(|e| TaskError::Storage(e.to_string()))?);
+            lines.push('\n');
+        }
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.log_path)
+            .and_then(|mut file| file.write_all(lines.as_bytes()))
+            .map_err(|e| self.log_error(e))
+    }
+
+    fn replay(&self) -> Result<Written, TaskError> {
+        let mut written = Written::new(self.snapshot.load()?, 0);
+        let records = self.read_log()?;
+        written.records = records.len();
+        let mut known: HashSet<AuditEntry> = written.audit.iter().cloned().collect();
+        for record in records {
+            written.apply(record, &mut known);
+        }
+        info!("replayed {} records from {}", written.records, self.log_path.display());
+        Ok(written)
+    }
+
+    // The log always runs up to the state being written, so if it outlives a crash
+    // here, replaying it over the new snapshot lands on that same state
+    fn rewrite(&self, state: &SavedState) -> Result<(), TaskError> {
+        self.snapshot.save(state)?;
+        self.remove_log()
+    }
+
+    fn remove_log(&self) -> Result<(), TaskError> {
+        match fs::remove_file(&self.log_path) {
+            Ok(()) => {}
+            Err(e) if e.kind() == ErrorKind::NotFound => {}
+            Err(e) => return Err(self.log_error(e)),
+        }
+        info!("compacted {} into the snapshot", self.log_path.display());
+        Ok(())
+    }
+
+    // Append what changed since the last save, compacting once the log is long enough
+    fn save_changes(&self, state: &SavedState) -> Result<(), TaskError> {
+        let mut guard = self.written.lock().unwrap_or_else(PoisonError::into_inner);
+        let written = match guard.as_mut() {
+            Some(written) => written,
+            None => guard.insert(self.replay()?),
+        };
+        let records = written.changes(state);
+        if records.is_empty() {
+            return Ok(());
+        }
+        self.append(&records)?;
+        *written = Written::new(state.clone(), written.records + records.len());
+        if written.records > self.compact_after {
+            self.rewrite(state)?;
+            written.records = 0;
+        } else {
+            info!("appended {} records to {}", records.len(), self.log_path.display());
+        }
+        Ok(())
+    }
+}
+
+impl Storage for JournalStorage {
+    fn load(&self) -> Result<SavedState, TaskError> {
+        let written = self.replay()?;
+        let state = written.to_state();
+        *self.written.lock().unwrap_or_else(PoisonError::into_inner) = Some(written);
+        Ok(state)
+    }
+
+    fn save(&self, state: &SavedState) -> Result<(), TaskError> {
+        self.timer.time(|| self.save_changes(state))
+    }
+
+    fn compact(&self) -> Result<(), TaskError> {
+        let mut written = self.written.lock().unwrap_or_else(PoisonError::into_inner);
+        match written.as_mut() {
+            Some(w) if w.records > 0 => {
+                self.rewrite(&w.to_state())?;
+                w.records = 0;
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    // The snapshot's report, counting the log and timing saves of either kind
+    fn report(&self) -> StorageReport {
+        let snapshot = self.snapshot.report();
+        let bytes_on_disk = match (snapshot.bytes_on_disk, storage::file_size(&self.log_path)) {
+            (None, None) => None,
+            (data, log) => Some(data.unwrap_or(0) + log.unwrap_or(0)),
+        };
+        StorageReport {
+            bytes_on_disk,
+            last_save_ms: self.timer.last_ms(),
+            ..snapshot
+        }
+    }
+
+    fn files(&self) -> Vec<PathBuf> {
+        vec![self.snapshot.path().to_path_buf(), self.log_path.clone()]
+    }
+
+    // Compaction in the new format: the snapshot holds everything afterwards
+    fn convert(&self, state: &SavedState, format: StoreFormat) -> Result<bool, TaskError> {
+        let mut written = self.written.lock().unwrap_or_else(PoisonError::into_inner);
+        self.snapshot.convert(state, format)?;
+        self.remove_log()?;
+        *written = Some(Written::new(state.clone(), 0));
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Priority, TaskManager, TaskStatus};
+
+    fn temp_data_path(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("task-manager-journal-{}-{}.json", name, std::process::id()));
+        let _ = fs::remove_file(&path);
+        let _ = fs::remove_file(log_path(&path));
+        path
+    }
+
+    fn cleanup(path: &Path) {
+        let _ = fs::remove_file(path);
+        let _ = fs::remove_file(log_path(path));
+    }
+
+    // A session that saves after every change, the way autosave does
+    fn session(storage: &dyn Storage) -> TaskManager {
+        let mut manager = TaskManager::from_state(storage.load().unwrap());
+        manager.enable_audit("tester".to_string(), 100);
+        let a = manager.add_task("Write docs".to_string(), "draft".to_string(), Priority::High).unwrap();
+        storage.save(&manager.to_state()).unwrap();
+        let b = manager.add_task("Ship it".to_string(), String::new(), Priority::Low).unwrap();
+        storage.save(&manager.to_state()).unwrap();
+        manager.add_tag_to_task(a, "docs".to_string()).unwrap();
+        storage.save(&manager.to_state()).unwrap();
+        manager.update_task_status(b, TaskStatus::Completed).unwrap();
+        storage.save(&manager.to_state()).unwrap();
+        manager.delete_task(b).unwrap();
+        storage.save(&manager.to_state()).unwrap();
+        manager.save_view("docs", "tag:docs status:pending", false).unwrap();
+        storage.save(&manager.to_state()).unwrap();
+        for title in ["Spare", "Extra"] {
+            manager.begin_undo_step(&format!("add {}", title));
+            manager.add_task(title.to_string(), String::new(), Priority::Low).unwrap();
+            manager.finish_undo_step();
+            storage.save(&manager.to_state()).unwrap();
+        }
+        manager.undo().unwrap();
+        storage.save(&manager.to_state()).unwrap();
+        manager
+    }
+
+    #[test]
+    fn test_recovers_from_a_crash_mid_append() {
+        let path = temp_data_path("crash");
+        let storage = JournalStorage::new(&path, 100);
+        let manager = session(&storage);
+        assert!(!path.exists(), "nothing but the log is written before compaction");
+
+        // Killed while writing the next record: the storage is never compacted
+        let mut log = OpenOptions::new().append(true).open(log_path(&path)).unwrap();
+        log.write_all(br#"{"op":"put","task":{"id":3,"title":"Half"#).unwrap();
+        drop(storage);
+
+        let recovered = JournalStorage::new(&path, 100);
+        let state = recovered.load().unwrap();
+        assert_eq!(state, manager.to_state());
+
+        // The torn line is gone, so later appends still replay
+        let mut manager = TaskManager::from_state(state);
+        manager.add_task("After the crash".to_string(), String::new(), Priority::Medium).unwrap();
+        recovered.save(&manager.to_state()).unwrap();
+        assert_eq!(JournalStorage::new(&path, 100).load().unwrap(), manager.to_state());
+
+        // Damage before the last line is not a crash artefact and is reported
+        let contents = fs::read_to_string(log_path(&path)).unwrap();
+        fs::write(log_path(&path), format!("garbage\n{}", contents)).unwrap();
+        assert!(matches!(JournalStorage::new(&path, 100).load(), Err(TaskError::Storage(_))));
+        cleanup(&path);
+    }
+
+    #[test]
+    fn test_compaction_keeps_the_same_state() {
+        let path = temp_data_path("compacted");
+        let storage = JournalStorage::new(&path, 3);
+        let manager = session(&storage);
+        let log_lines = fs::read_to_string(log_path(&path)).map_or(0, |log| log.lines().count());
+        assert!(path.exists() && log_lines <= 3);
+        assert_eq!(JournalStorage::new(&path, 3).load().unwrap(), manager.to_state());
+        cleanup(&path);
+
+        // Everything still in the log, then folded into the snapshot on exit
+        let path = temp_data_path("replayed");
+        let storage = JournalStorage::new(&path, 100);
+        let manager = session(&storage);
+        let replayed = JournalStorage::new(&path, 100).load().unwrap();
+        assert_eq!(replayed, manager.to_state());
+        let log = fs::read_to_string(log_path(&path)).unwrap();
+        storage.compact().unwrap();
+        assert!(!log_path(&path).exists());
+        assert_eq!(FileStorage::new(&path).load().unwrap(), replayed);
+
+        // Killed before the log was removed: its audit entries are not added twice
+        fs::write(log_path(&path), log).unwrap();
+        let twice = JournalStorage::new(&path, 100).load().unwrap();
+        assert_eq!(twice.audit, replayed.audit);
+        cleanup(&path);
+    }
+}
@@ -0,0 +1,102 @@
+//! A task list with tags, priorities, due dates and a change history, behind the
+//! `Task-Manager` command line.
+//!
+//! The items at the crate root are the stable API and follow semantic versioning:
+//! [`TaskManager`] holds the list and makes every change, [`Task`] is read through
+//! its getters, and [`TaskFilter`] and [`SortKey`] pick and order tasks. Enums
+//! that will grow, such as [`TaskError`], [`TaskStatus`] and [`TaskEvent`], are
+//! `#[non_exhaustive]`. The public modules are the command line's own building
+//! blocks and may change in any release.
+//!
+//! ```
+//! use task_manager::{Priority, TaskFilter, TaskManager, TaskStatus};
+//!
+//! let mut manager = TaskManager::new();
+//! let id = manager.add_task("Write report".to_string(), String::new(), Priority::try_from("high")?)?;
+//! manager.add_tag_to_task(id, "work".to_string())?;
+//! manager.update_task_status(id, TaskStatus::InProgress)?;
+//!
+//! let open = manager.find(&TaskFilter::parse("tag:work status:progress")?);
+//! assert_eq!(open[0].title(), "Write report");
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+
+pub mod audit;
+pub mod board;
+pub mod calendar;
+pub mod cli;
+pub mod clipboard;
+pub mod command;
+pub mod config;
+pub mod csv_export;
+#[cfg(unix)]
+pub mod control;
+#[cfg(unix)]
+pub mod daemon;
+pub mod dates;
+pub mod diagnostics;
+pub mod diff;
+pub mod digest;
+pub mod effort;
+mod error;
+#[cfg(feature = "server")]
+pub mod events;
+mod filter;
+pub mod focus;
+pub mod github;
+pub mod goal;
+pub mod graph;
+pub mod health;
+pub mod hooks;
+pub mod html;
+pub mod i18n;
+pub mod ics;
+pub mod import;
+pub mod jira;
+pub mod journal;
+pub mod logging;
+mod manager;
+pub mod matrix;
+pub mod merge;
+pub mod metrics;
+pub mod migrate;
+pub mod notify;
+pub mod overdue;
+pub mod pomodoro;
+pub mod pressure;
+pub mod recurrence;
+pub mod render;
+pub mod report;
+pub mod retention;
+pub mod review;
+pub mod roundtrip;
+pub mod rpc;
+pub mod schema;
+pub mod search;
+#[cfg(feature = "server")]
+pub mod server;
+pub mod session;
+pub mod shared;
+pub mod storage;
+pub mod summary;
+pub mod sync;
+pub mod table;
+pub mod tags;
+mod task;
+pub mod timesheet;
+pub mod tree;
+pub mod transfer;
+pub mod triage;
+#[cfg(feature = "tui")]
+pub mod tui;
+pub mod undo;
+pub mod validation;
+pub mod watch;
+pub mod webhook;
+pub mod workflow;
+
+pub use error::TaskError;
+pub use filter::{SortKey, TaskFilter, pinned_first};
+pub use manager::{BulkResult, CreatorStats, Placement, PlannedChange, Snapshot, Subscriber, TaskEdit, TaskEvent, TaskManager};
+pub use shared::SharedTaskManager;
+pub use task::{Energy, KeyPrefix, Priority, Reminder, Task, TaskStatus, Waiting, WorkSession};
@@ -0,0 +1,91 @@
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use log::{LevelFilter, Log, Metadata, Record};
+
+use crate::TaskError;
+
+// Appends one line per record to a file; never writes to stdout
+struct FileLogger {
+    file: Mutex<File>,
+    level: LevelFilter,
+}
+
+impl Log for FileLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.level
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let secs = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        let mut file = self.file.lock().unwrap_or_else(|e| e.into_inner());
+        // Logging must never take the CLI down, so write errors are dropped
+        let _ = writeln!(file, "{} {:<5} {}: {}", secs, record.level(), record.target(), record.args());
+    }
+
+    fn flush(&self) {
+        let _ = self.file.lock().unwrap_or_else(|e| e.into_inner()).flush();
+    }
+}
+
+pub fn parse_level(s: &str) -> Result<LevelFilter, TaskError> {
+    s.parse::<LevelFilter>().map_err(|_| TaskError::InvalidInput)
+}
+
+// Install the process-wide file logger; can only succeed once per process
+pub fn init_file_logger(path: &Path, level: LevelFilter) -> Result<(), TaskError> {
+    let file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .map_err(|e| TaskError::Storage(format!("{}: {}", path.display(), e)))?;
+    log::set_boxed_logger(Box::new(FileLogger { file: Mutex::new(file), level }))
+        .map_err(|e| TaskError::Storage(e.to_string()))?;
+    log::set_max_level(level);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::Cli;
+    use crate::config::Config;
+    use crate::storage::InMemoryStorage;
+    use std::fs;
+
+    #[test]
+    fn test_scripted_session_writes_log_lines() {
+        let path = std::env::temp_dir().join(format!("task-manager-log-{}.log", std::process::id()));
+        let _ = fs::remove_file(&path);
+        init_file_logger(&path, LevelFilter::Debug).unwrap();
+
+        let mut cli = Cli::new(Box::new(InMemoryStorage::new()), Config::default());
+        let script = ["add Log me", "tag 1 audit", "update 1 completed", "delete 1"];
+        for line in script {
+            let tokens: Vec<String> = line.split_whitespace().map(String::from).collect();
+            assert!(cli.run_once(&tokens));
+        }
+        log::logger().flush();
+
+        let contents = fs::read_to_string(&path).unwrap();
+        for expected in [
+            "DEBUG task_manager::manager: added task 1",
+            "DEBUG task_manager::manager: tagged task 1 with 'audit'",
+            "DEBUG task_manager::manager: task 1 status Pending -> Completed",
+            "DEBUG task_manager::manager: deleted task 1",
+            "INFO  task_manager::storage: saved 0 tasks to memory",
+        ] {
+            assert!(contents.contains(expected), "missing {:?} in log:\n{}", expected, contents);
+        }
+        fs::remove_file(&path).unwrap();
+    }
+}
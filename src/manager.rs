@@ -0,0 +1,3726 @@
+use std::borrow::Cow;
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::ops::RangeBounds;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::time::Duration;
+
+use chrono::{DateTime, Days, NaiveDate, NaiveDateTime, TimeDelta, Utc, Weekday};
+use log::debug;
+use serde::{Deserialize, Serialize};
+
+use crate::audit::{AuditEntry, AuditLog};
+use crate::effort::{self, EffortStats};
+use crate::goal::{self, Goal, GoalProgress};
+use crate::filter::ME;
+use crate::health::{self, Issue, Repair, TrashedTask};
+use crate::matrix::{DEFAULT_URGENT_DAYS, Quadrant};
+use crate::pressure::{self, Pressure};
+use crate::retention::{self, ArchivedTask, RetentionPlan, RetentionPolicy};
+use crate::search::{self, SearchText};
+use crate::storage::SavedState;
+use crate::sync::{ChangeRecorder, SyncState};
+use crate::tags::{TagImport, TagInfo, TagRegistry, TagRow};
+use crate::task::{MAX_FIELDS, valid_field_key, valid_key_prefix};
+use crate::timesheet::{DateRange, GroupKey, UNTAGGED};
+use crate::undo::{UndoEntry, UndoStack};
+use crate::validation::{self, Limits, ValidationContext, ValidationRule};
+use crate::workflow::Workflow;
+use crate::{Energy, KeyPrefix, Priority, Reminder, Task, TaskError, TaskFilter, TaskStatus, Waiting, WorkSession};
+
+// Candidate lists at least this long are searched in parallel when built with `parallel`
+#[cfg(feature = "parallel")]
+const PARALLEL_THRESHOLD: usize = 10_000;
+
+// Space left between neighbouring ranks in the manual order
+pub(crate) const RANK_GAP: u64 = 1024;
+
+/// Change notifications emitted by TaskManager after a mutation succeeds. More
+/// kinds of change may be added, so matches need a wildcard arm.
+///
+/// ```
+/// use std::sync::{Arc, Mutex};
+/// use task_manager::{Priority, TaskEvent, TaskManager};
+///
+/// let events = Arc::new(Mutex::new(Vec::new()));
+/// let seen = Arc::clone(&events);
+/// let mut manager = TaskManager::new();
+/// manager.subscribe(Box::new(move |event, _| seen.lock().unwrap().push(event.clone())));
+/// let id = manager.add_task("Plan".to_string(), String::new(), Priority::Low).unwrap();
+/// assert_eq!(events.lock().unwrap()[0], TaskEvent::Added(id));
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[non_exhaustive]
+pub enum TaskEvent {
+    Added(u32),
+    StatusChanged { id: u32, from: TaskStatus, to: TaskStatus },
+    // Through update_priority; apply_edit reports a priority change as Edited
+    PriorityChanged { id: u32, from: Priority, to: Priority },
+    Tagged { id: u32, tag: String },
+    Deleted(u32),
+    // Title, description, priority or due date changed through apply_edit
+    Edited(u32),
+    // A due-date reminder was raised for the task
+    Notified(u32),
+    // The whole task list was replaced from a snapshot
+    Restored,
+    // A tag's registry entry was added, changed or removed
+    TagDescribed { tag: String },
+    // A saved view was stored, renamed (once for each name) or deleted
+    ViewChanged { name: String },
+    // The completion goal was set, cleared or reached
+    GoalChanged,
+    // Someone else's task was changed or deleted past `protect_others_tasks`;
+    // the change itself follows as its own event
+    Overridden { id: u32, owner: String },
+    // The retention policy moved these completed tasks to the archive and
+    // purged these archived ones for good
+    RetentionApplied { archived: Vec<u32>, purged: Vec<u32> },
+}
+
+impl TaskEvent {
+    /// The task the event is about; Restored is about the whole list.
+    ///
+    /// ```
+    /// # use task_manager::TaskEvent;
+    /// assert_eq!(TaskEvent::Deleted(3).task_id(), Some(3));
+    /// assert_eq!(TaskEvent::Restored.task_id(), None);
+    /// ```
+    pub fn task_id(&self) -> Option<u32> {
+        match self {
+            TaskEvent::Added(id)
+            | TaskEvent::StatusChanged { id, .. }
+            | TaskEvent::PriorityChanged { id, .. }
+            | TaskEvent::Tagged { id, .. }
+            | TaskEvent::Deleted(id)
+            | TaskEvent::Edited(id)
+            | TaskEvent::Notified(id)
+            | TaskEvent::Overridden { id, .. } => Some(*id),
+            TaskEvent::Restored
+            | TaskEvent::ViewChanged { .. }
+            | TaskEvent::TagDescribed { .. }
+            | TaskEvent::GoalChanged
+            | TaskEvent::RetentionApplied { .. } => None,
+        }
+    }
+}
+
+/// A change a rehearsal made and took back, with the title of the task it touched.
+///
+/// ```
+/// use task_manager::{Priority, TaskEvent, TaskManager};
+///
+/// let mut manager = TaskManager::new();
+/// let (_, planned) = manager.rehearse(|manager| manager.add_task("Maybe".to_string(), String::new(), Priority::Low));
+/// assert_eq!((&planned[0].event, planned[0].title.as_str()), (&TaskEvent::Added(1), "Maybe"));
+/// assert!(manager.list_tasks().is_empty());
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlannedChange {
+    pub event: TaskEvent,
+    pub title: String,
+}
+
+/// `stats --by user`: the tasks one user created, by status.
+///
+/// ```
+/// use task_manager::{Priority, TaskManager};
+///
+/// let mut manager = TaskManager::new();
+/// manager.set_user("ana".to_string());
+/// manager.add_task("Mine".to_string(), String::new(), Priority::Low).unwrap();
+/// let stats = manager.stats_by_creator();
+/// assert_eq!((stats[0].user.as_deref(), stats[0].pending), (Some("ana"), 1));
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct CreatorStats {
+    // None for tasks added before creators were recorded
+    pub user: Option<String>,
+    pub total: usize,
+    pub completed: usize,
+    pub in_progress: usize,
+    pub pending: usize,
+}
+
+/// A deep copy of the task list taken before a risky operation.
+///
+/// ```
+/// use task_manager::{Priority, TaskManager};
+///
+/// let mut manager = TaskManager::new();
+/// manager.add_task("Keep".to_string(), String::new(), Priority::Low).unwrap();
+/// let snapshot = manager.snapshot();
+/// assert_eq!(snapshot.len(), 1);
+/// manager.add_task("Drop".to_string(), String::new(), Priority::Low).unwrap();
+/// manager.restore(snapshot);
+/// assert_eq!(manager.list_tasks().len(), 1);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Snapshot {
+    tasks: BTreeMap<u32, Task>,
+    next_id: u32,
+}
+
+impl Snapshot {
+    /// Tasks in the snapshot.
+    ///
+    /// ```
+    /// # use task_manager::TaskManager;
+    /// assert_eq!(TaskManager::new().snapshot().len(), 0);
+    /// ```
+    pub fn len(&self) -> usize {
+        self.tasks.len()
+    }
+
+    /// ```
+    /// # use task_manager::TaskManager;
+    /// assert!(TaskManager::new().snapshot().is_empty());
+    /// ```
+    pub fn is_empty(&self) -> bool {
+        self.tasks.is_empty()
+    }
+}
+
+/// Fields to change on an existing task; None leaves a field as it is.
+///
+/// ```
+/// use task_manager::{Priority, TaskEdit, TaskManager};
+///
+/// let mut manager = TaskManager::new();
+/// let id = manager.add_task("Draft".to_string(), String::new(), Priority::Low).unwrap();
+/// let edit = TaskEdit { title: Some("Final".to_string()), ..TaskEdit::default() };
+/// manager.apply_edit(id, edit).unwrap();
+/// assert_eq!(manager.get_task(id).unwrap().title(), "Final");
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TaskEdit {
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub priority: Option<Priority>,
+    pub due: Option<NaiveDate>,
+}
+
+/// Which side of the other task `move_task` puts a task on.
+///
+/// ```
+/// use task_manager::{Priority, Placement, TaskManager};
+///
+/// let mut manager = TaskManager::new();
+/// let first = manager.add_task("First".to_string(), String::new(), Priority::Low).unwrap();
+/// let second = manager.add_task("Second".to_string(), String::new(), Priority::Low).unwrap();
+/// manager.move_task(second, Placement::Before, first).unwrap();
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Placement {
+    Before,
+    After,
+}
+
+/// What add_tasks_bulk did with each task, in the order they were given.
+///
+/// ```
+/// use task_manager::{Priority, Task, TaskManager};
+///
+/// let mut manager = TaskManager::new();
+/// let task = |title: &str| Task::new(0, title.to_string(), String::new(), Priority::Low);
+/// let result = manager.add_tasks_bulk(vec![task("One"), task("one"), task("Two")]);
+/// assert_eq!(result.created(), vec![1, 2]);
+/// assert!(result.outcomes[1].is_err());
+/// ```
+#[derive(Debug, Default)]
+pub struct BulkResult {
+    pub outcomes: Vec<Result<u32, TaskError>>,
+}
+
+impl BulkResult {
+    /// Ids of the tasks that went in.
+    ///
+    /// ```
+    /// # use task_manager::BulkResult;
+    /// assert!(BulkResult::default().created().is_empty());
+    /// ```
+    pub fn created(&self) -> Vec<u32> {
+        self.outcomes.iter().filter_map(|outcome| outcome.as_ref().ok().copied()).collect()
+    }
+}
+
+// Task id -> the task's folded search text
+type SearchIndex = Arc<HashMap<u32, SearchText>>;
+
+/// Called with each event and the manager as it is after the change.
+///
+/// ```
+/// use task_manager::{Subscriber, TaskManager};
+///
+/// let log: Subscriber = Box::new(|event, manager| println!("{:?}: {} tasks", event, manager.list_tasks().len()));
+/// TaskManager::new().subscribe(log);
+/// ```
+pub type Subscriber = Box<dyn Fn(&TaskEvent, &TaskManager) + Send + Sync>;
+
+/// The task list with its indexes, rules and history. Every change goes through
+/// its methods, which check it and tell subscribers.
+///
+/// ```
+/// use task_manager::{Priority, TaskManager, TaskStatus};
+///
+/// let mut manager = TaskManager::new();
+/// let id = manager.add_task("Write report".to_string(), String::new(), Priority::High).unwrap();
+/// manager.add_tag_to_task(id, "work".to_string()).unwrap();
+/// manager.update_task_status(id, TaskStatus::Completed).unwrap();
+/// assert_eq!(manager.get_statistics(), (1, 1, 0, 0));
+/// ```
+pub struct TaskManager {
+    tasks: BTreeMap<u32, Task>,
+    next_id: u32,
+    // Rank the next new task gets, past every rank in use
+    next_rank: u64,
+    // Normalized tag -> ids of tasks carrying it
+    tag_index: HashMap<String, BTreeSet<u32>>,
+    // Normalized title -> id, for duplicate detection
+    title_index: HashMap<String, u32>,
+    // Every tag spelling seen, shared by the tasks carrying it
+    tag_names: TagInterner,
+    // Kept in step with `tasks` so statistics never walk the list
+    counts: TaskCounts,
+    // Bumped by every change, so cached views know when they are stale
+    generation: u64,
+    // Ids in priority order and the generation they were sorted at
+    priority_view: Mutex<Option<(u64, Arc<[u32]>)>>,
+    // Each task's folded search text and the generation it was built at
+    search_index: Mutex<Option<(u64, SearchIndex)>>,
+    // Whether search folds away diacritics as well as case
+    ignore_accents: bool,
+    // Shared with the recording subscriber; not part of snapshots
+    audit: Arc<Mutex<AuditLog>>,
+    // Checked before a new task is stored
+    rules: Vec<Box<dyn ValidationRule>>,
+    // Days ahead a due date makes a task urgent for `quadrant`
+    urgent_days: u32,
+    // How long a task without its own estimate takes, for `pressure` and `effort`
+    default_estimate: Duration,
+    // Watermarks and queued changes for sync; shared with the queue recorder
+    sync: Arc<Mutex<SyncState>>,
+    // Saved view name -> the query it runs
+    views: BTreeMap<String, String>,
+    // What recent commands overwrote, so `undo` can put it back; saved with the tasks
+    undo: UndoStack,
+    // Agreed meanings of tags, by lowercase tag
+    tag_registry: TagRegistry,
+    // Refuse tags the registry does not know
+    strict_tags: bool,
+    // Tasks to complete each day, week or month; saved with the tasks
+    goal: Option<Goal>,
+    // The first day of a goal's week
+    week_start: Weekday,
+    // Tasks `check --fix` took out of the list; saved with the tasks
+    trash: Vec<TrashedTask>,
+    // Completed tasks the retention policy took out of the list, and the day it
+    // last ran; saved with the tasks
+    archive: Vec<ArchivedTask>,
+    retention_run: Option<NaiveDate>,
+    // Tasks loaded under an id a later task in the file also had, kept until
+    // `check --fix` renumbers them so a save does not lose them
+    shadowed: Vec<Task>,
+    // Refuse changes to completed tasks other than reopening and deleting them
+    guard_completed: bool,
+    // Who is making the changes; recorded as the creator of new tasks. None
+    // leaves them without one.
+    user: Option<String>,
+    // Refuse changes to tasks another user created, unless `force_others`
+    protect_others: bool,
+    force_others: bool,
+    // Which status changes are allowed and what they need
+    workflow: Workflow,
+    // How big one task may get
+    limits: Limits,
+    // Cut a task over the limits down to fit instead of refusing it
+    truncate_to_limits: bool,
+    // What was cut since the caller last asked; not part of snapshots
+    truncations: Vec<String>,
+    subscribers: Vec<Subscriber>,
+}
+
+fn tracked_in(task: &Task, range: DateRange) -> Duration {
+    task.sessions
+        .iter()
+        .map(|session| range.overlap(session.started_at, Duration::from_secs(session.seconds)))
+        .sum()
+}
+
+// View names are spelled like field keys: lowercase letters, digits and dashes
+fn checked_view_name(name: &str) -> Result<String, TaskError> {
+    let name = name.trim().to_lowercase();
+    if !valid_field_key(&name) {
+        return Err(TaskError::Invalid {
+            reason: format!("view name '{}' must be letters, digits and dashes starting with a letter", name),
+        });
+    }
+    Ok(name)
+}
+
+fn view_taken(name: &str) -> TaskError {
+    TaskError::Invalid { reason: format!("view '{}' already exists; use --force to replace it", name) }
+}
+
+fn no_such_view(name: &str) -> TaskError {
+    TaskError::Invalid { reason: format!("no view named '{}'", name) }
+}
+
+// Follow parent links to the top; a task in a parent cycle stops where the loop closes
+fn project_of<'a>(task: &'a Task, by_uid: &HashMap<&str, &'a Task>) -> &'a Task {
+    let mut top = task;
+    let mut seen = HashSet::new();
+    while let Some(parent) = top.parent.as_deref().and_then(|uid| by_uid.get(uid)) {
+        if !seen.insert(top.id) {
+            break;
+        }
+        top = parent;
+    }
+    top
+}
+
+fn has_key(task: &Task, key: &str) -> bool {
+    task.key.iter().chain(&task.old_keys).any(|own| own.eq_ignore_ascii_case(key))
+}
+
+// Tags are matched case-insensitively
+fn normalize_tag(tag: &str) -> String {
+    tag.trim().to_lowercase()
+}
+
+// Hands out one shared allocation per distinct tag, so memory grows with the
+// number of different tags rather than with how often they are used, and two
+// equal tags compare by pointer. Tags stay interned after their last task goes.
+#[derive(Default)]
+struct TagInterner(HashSet<Arc<str>>);
+
+impl TagInterner {
+    fn intern(&mut self, tag: &str) -> Arc<str> {
+        if let Some(interned) = self.0.get(tag) {
+            return Arc::clone(interned);
+        }
+        let tag: Arc<str> = Arc::from(tag);
+        self.0.insert(Arc::clone(&tag));
+        tag
+    }
+
+    // Swap a task's own tag strings, fresh from a file or another list, for the shared ones
+    fn intern_tags(&mut self, task: &mut Task) {
+        for tag in &mut task.tags {
+            *tag = self.intern(tag);
+        }
+    }
+}
+
+// Tasks per status and per priority
+#[derive(Debug, Clone, Default, PartialEq)]
+struct TaskCounts {
+    by_status: [usize; 3],
+    by_priority: [usize; 4],
+}
+
+impl TaskCounts {
+    fn tally<'a>(tasks: impl IntoIterator<Item = &'a Task>) -> TaskCounts {
+        let mut counts = TaskCounts::default();
+        for task in tasks {
+            counts.add(task);
+        }
+        counts
+    }
+
+    fn add(&mut self, task: &Task) {
+        self.by_status[status_slot(&task.status)] += 1;
+        self.by_priority[priority_slot(&task.priority)] += 1;
+    }
+
+    fn remove(&mut self, task: &Task) {
+        self.by_status[status_slot(&task.status)] -= 1;
+        self.by_priority[priority_slot(&task.priority)] -= 1;
+    }
+}
+
+fn status_slot(status: &TaskStatus) -> usize {
+    match status {
+        TaskStatus::Pending => 0,
+        TaskStatus::InProgress => 1,
+        TaskStatus::Completed => 2,
+    }
+}
+
+fn priority_slot(priority: &Priority) -> usize {
+    match priority {
+        Priority::Low => 0,
+        Priority::Medium => 1,
+        Priority::High => 2,
+        Priority::Critical => 3,
+    }
+}
+
+// Titles differing only in case or surrounding whitespace count as duplicates
+pub(crate) fn normalize_title(title: &str) -> String {
+    title.trim().to_lowercase()
+}
+
+impl TaskManager {
+    /// An empty list with the default settings.
+    ///
+    /// ```
+    /// # use task_manager::TaskManager;
+    /// assert!(TaskManager::new().list_tasks().is_empty());
+    /// ```
+    pub fn new() -> Self {
+        TaskManager {
+            tasks: BTreeMap::new(),
+            next_id: 1,
+            next_rank: RANK_GAP,
+            tag_index: HashMap::new(),
+            title_index: HashMap::new(),
+            tag_names: TagInterner::default(),
+            counts: TaskCounts::default(),
+            generation: 0,
+            priority_view: Mutex::new(None),
+            search_index: Mutex::new(None),
+            ignore_accents: true,
+            audit: Arc::new(Mutex::new(AuditLog::default())),
+            rules: Vec::new(),
+            urgent_days: DEFAULT_URGENT_DAYS,
+            default_estimate: pressure::DEFAULT_ESTIMATE,
+            sync: Arc::new(Mutex::new(SyncState::default())),
+            views: BTreeMap::new(),
+            undo: UndoStack::default(),
+            tag_registry: TagRegistry::new(),
+            strict_tags: false,
+            goal: None,
+            week_start: Weekday::Mon,
+            trash: Vec::new(),
+            archive: Vec::new(),
+            retention_run: None,
+            shadowed: Vec::new(),
+            guard_completed: false,
+            user: None,
+            protect_others: false,
+            force_others: false,
+            workflow: Workflow::default(),
+            limits: Limits::default(),
+            truncate_to_limits: false,
+            truncations: Vec::new(),
+            subscribers: Vec::new(),
+        }
+    }
+
+    /// Rebuild a manager from persisted state.
+    ///
+    /// ```
+    /// use task_manager::{Priority, TaskManager};
+    ///
+    /// let mut manager = TaskManager::new();
+    /// manager.add_task("Saved".to_string(), String::new(), Priority::Low).unwrap();
+    /// let loaded = TaskManager::from_state(manager.to_state());
+    /// assert_eq!(loaded.get_task(1).unwrap().title(), "Saved");
+    /// ```
+    pub fn from_state(mut state: SavedState) -> Self {
+        let mut manager = TaskManager::new();
+        for task in std::mem::take(&mut state.tasks) {
+            manager.insert_loaded(task);
+        }
+        manager.finish_load(state);
+        manager
+    }
+
+    // Add one task read from storage, indexing it on the way in so loading is a
+    // single pass. Only a hand-edited file repeats an id; the later task wins,
+    // the earlier one waits in `shadowed` and the indexes are rebuilt.
+    pub(crate) fn insert_loaded(&mut self, mut task: Task) {
+        self.generation += 1;
+        self.tag_names.intern_tags(&mut task);
+        let id = task.id;
+        self.next_id = self.next_id.max(id + 1);
+        self.next_rank = self.next_rank.max(task.rank + RANK_GAP);
+        self.counts.add(&task);
+        if let Some(replaced) = self.tasks.insert(id, task) {
+            self.counts.remove(&replaced);
+            self.shadowed.push(replaced);
+            self.tag_index = self.build_tag_index();
+            self.title_index = self.build_title_index();
+            return;
+        }
+        let task = &self.tasks[&id];
+        for tag in &task.tags {
+            self.tag_index.entry(normalize_tag(tag)).or_default().insert(id);
+        }
+        // Same winner as build_title_index when titles collide: the highest id
+        let indexed = self.title_index.entry(normalize_title(&task.title)).or_insert(id);
+        *indexed = (*indexed).max(id);
+    }
+
+    // The rest of the saved state, once every task is in; `rest.tasks` is not read
+    pub(crate) fn finish_load(&mut self, rest: SavedState) {
+        self.next_id = self.next_id.max(rest.next_id);
+        // Kept whole: enable_audit trims to the configured limit, which may be larger
+        self.audit = Arc::new(Mutex::new(AuditLog::new(rest.audit, usize::MAX)));
+        self.sync = Arc::new(Mutex::new(rest.sync));
+        self.views = rest.views;
+        self.undo = UndoStack::new(rest.undo);
+        self.tag_registry = rest.tags;
+        self.goal = rest.goal;
+        self.trash = rest.trash;
+        self.archive = rest.archive;
+        self.retention_run = rest.retention_run;
+        debug!("loaded and indexed {} tasks", self.tasks.len());
+    }
+
+    // Take in what another process saved, keeping this manager's settings and
+    // subscribers. Nothing is emitted: the change is already on disk.
+    pub fn reload(&mut self, state: SavedState) {
+        let loaded = TaskManager::from_state(state);
+        self.audit_log().replace(loaded.audit_log().entries().to_vec());
+        *self.sync.lock().unwrap_or_else(PoisonError::into_inner) = loaded.sync_state();
+        self.tasks = loaded.tasks;
+        self.next_id = loaded.next_id;
+        self.tag_names = loaded.tag_names;
+        self.views = loaded.views;
+        self.undo = loaded.undo;
+        self.tag_registry = loaded.tag_registry;
+        self.goal = loaded.goal;
+        self.trash = loaded.trash;
+        self.archive = loaded.archive;
+        self.retention_run = loaded.retention_run;
+        self.shadowed = loaded.shadowed;
+        self.reindex();
+        self.generation += 1;
+    }
+
+    /// Everything a storage backend writes out.
+    ///
+    /// ```
+    /// # use task_manager::{Priority, TaskManager};
+    /// let mut manager = TaskManager::new();
+    /// manager.add_task("Saved".to_string(), String::new(), Priority::Low).unwrap();
+    /// assert_eq!(manager.to_state().tasks.len(), 1);
+    /// ```
+    pub fn to_state(&self) -> SavedState {
+        SavedState {
+            tasks: self.shadowed.iter().chain(self.list_tasks()).cloned().collect(),
+            next_id: self.next_id,
+            audit: self.audit_log().entries().to_vec(),
+            sync: self.sync_state(),
+            views: self.views.clone(),
+            undo: self.undo.entries().to_vec(),
+            tags: self.tag_registry.clone(),
+            goal: self.goal.clone(),
+            trash: self.trash.clone(),
+            archive: self.archive.clone(),
+            retention_run: self.retention_run,
+        }
+    }
+
+    // What `check` finds wrong with the list as it would be saved
+    pub fn health(&self) -> Vec<Issue> {
+        let state = SavedState {
+            tasks: self.shadowed.iter().chain(self.tasks.values()).cloned().collect(),
+            next_id: self.next_id,
+            ..SavedState::default()
+        };
+        health::check(&state)
+    }
+
+    // Make every repair `check --fix` can, moving what cannot be repaired to the trash
+    pub fn repair(&mut self, now: DateTime<Utc>) -> Vec<Repair> {
+        let mut state = SavedState {
+            tasks: self.shadowed.iter().chain(self.tasks.values()).cloned().collect(),
+            next_id: self.next_id,
+            ..SavedState::default()
+        };
+        let repairs = health::repair(&mut state, now);
+        if repairs.is_empty() {
+            return repairs;
+        }
+        let tasks: BTreeMap<u32, Task> = state.tasks.into_iter().map(|task| (task.id, task)).collect();
+        let touched: BTreeSet<u32> = self.tasks.keys().chain(tasks.keys()).copied().collect();
+        let changed: Vec<u32> = touched.into_iter().filter(|id| self.tasks.get(id) != tasks.get(id)).collect();
+        for id in changed {
+            self.remember_before(id);
+        }
+        self.shadowed.clear();
+        self.trash.extend(state.trash);
+        self.put_back(Snapshot { tasks, next_id: state.next_id });
+        self.emit(TaskEvent::Restored);
+        repairs
+    }
+
+    pub fn trash(&self) -> &[TrashedTask] {
+        &self.trash
+    }
+
+    pub fn archive(&self) -> &[ArchivedTask] {
+        &self.archive
+    }
+
+    // Apply `policy` unless it already ran `today`: old completed tasks move to
+    // the archive and old archived ones are purged. None when it was not due;
+    // the day is recorded either way, so a list with nothing to do is not
+    // looked at again until tomorrow.
+    pub fn apply_retention(&mut self, policy: &RetentionPolicy, now: DateTime<Utc>, today: NaiveDate) -> Option<RetentionPlan> {
+        if !retention::is_due(self.retention_run, today) {
+            return None;
+        }
+        self.retention_run = Some(today);
+        let plan = retention::plan(self.tasks.values(), &self.archive, now, policy);
+        if plan.is_empty() {
+            return Some(plan);
+        }
+        self.archive.retain(|archived| !plan.purge.contains(&archived.task.id));
+        for id in &plan.archive {
+            if let Some(task) = self.tasks.remove(id) {
+                self.archive.push(ArchivedTask { task, archived_at: now });
+            }
+        }
+        self.reindex();
+        self.generation += 1;
+        debug!("retention archived {} and purged {} tasks", plan.archive.len(), plan.purge.len());
+        self.emit(TaskEvent::RetentionApplied { archived: plan.archive.clone(), purged: plan.purge.clone() });
+        // No task keeps a link to one that left the list, as after a delete
+        let linked: Vec<u32> = self
+            .tasks
+            .values()
+            .filter(|task| task.relates_to.iter().any(|other| plan.archive.contains(other)))
+            .map(|task| task.id)
+            .collect();
+        for id in linked {
+            let task = self.tasks.get_mut(&id).expect("read from the map");
+            task.relates_to.retain(|other| !plan.archive.contains(other));
+            task.touch();
+            self.emit(TaskEvent::Edited(id));
+        }
+        Some(plan)
+    }
+
+    fn audit_log(&self) -> MutexGuard<'_, AuditLog> {
+        self.audit.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    // Start recording every change as made by the manager's user, or by `user`
+    // while it has none, keeping at most `limit` entries
+    pub fn enable_audit(&mut self, user: String, limit: usize) {
+        self.audit_log().set_limit(limit);
+        let audit = Arc::clone(&self.audit);
+        self.subscribe(Box::new(move |event, mgr| {
+            let entry = AuditEntry::from_event(event, mgr.user().unwrap_or(&user));
+            audit.lock().unwrap_or_else(PoisonError::into_inner).record(entry);
+        }));
+    }
+
+    pub fn audit_len(&self) -> usize {
+        self.audit_log().entries().len()
+    }
+
+    // Recorded changes newest first, for one task or all of them
+    pub fn audit_history(&self, task_id: Option<u32>) -> Vec<AuditEntry> {
+        self.audit_log().history(task_id)
+    }
+
+    /// A copy of the task list to [`restore`](TaskManager::restore) later.
+    ///
+    /// ```
+    /// # use task_manager::TaskManager;
+    /// assert!(TaskManager::new().snapshot().is_empty());
+    /// ```
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            tasks: self.tasks.clone(),
+            next_id: self.next_id,
+        }
+    }
+
+    /// Replace all tasks with the snapshot's, rebuilding the indexes.
+    ///
+    /// ```
+    /// # use task_manager::{Priority, TaskManager};
+    /// let mut manager = TaskManager::new();
+    /// let empty = manager.snapshot();
+    /// manager.add_task("Gone".to_string(), String::new(), Priority::Low).unwrap();
+    /// manager.restore(empty);
+    /// assert!(manager.list_tasks().is_empty());
+    /// ```
+    pub fn restore(&mut self, snapshot: Snapshot) {
+        self.put_back(snapshot);
+        debug!("restored snapshot of {} tasks", self.tasks.len());
+        self.emit(TaskEvent::Restored);
+    }
+
+    fn put_back(&mut self, snapshot: Snapshot) {
+        self.tasks = snapshot.tasks;
+        self.next_id = snapshot.next_id;
+        self.reindex();
+        self.generation += 1;
+    }
+
+    // Rebuild everything derived from the tasks after they were replaced wholesale
+    fn reindex(&mut self) {
+        self.next_rank = self.tasks.values().map(|task| task.rank + RANK_GAP).fold(RANK_GAP, u64::max);
+        self.counts = TaskCounts::tally(self.tasks.values());
+        self.tag_index = self.build_tag_index();
+        self.title_index = self.build_title_index();
+    }
+
+    // Run `f` for real, then undo every change it made and report them instead.
+    // Subscribers, the ones that save, audit, queue for sync and post webhooks
+    // included, hear of none of it.
+    pub fn rehearse<T>(&mut self, f: impl FnOnce(&mut TaskManager) -> T) -> (T, Vec<PlannedChange>) {
+        let subscribers = std::mem::take(&mut self.subscribers);
+        let before = self.snapshot();
+        let (views, undo, tags, goal) = (self.views.clone(), self.undo.clone(), self.tag_registry.clone(), self.goal.clone());
+        let (trash, shadowed) = (self.trash.clone(), self.shadowed.clone());
+        let (archive, retention_run) = (self.archive.clone(), self.retention_run);
+        let truncations = self.truncations.len();
+        let planned: Arc<Mutex<Vec<PlannedChange>>> = Arc::default();
+        let recorder = Arc::clone(&planned);
+        self.subscribe(Box::new(move |event, mgr| {
+            let title = event.task_id().and_then(|id| mgr.tasks.get(&id)).map(|task| task.title.clone());
+            let mut planned = recorder.lock().unwrap_or_else(PoisonError::into_inner);
+            // Several steps of one change, such as handing out keys, can tell the same thing twice
+            if !planned.iter().any(|change| change.event == *event) {
+                planned.push(PlannedChange { event: event.clone(), title: title.unwrap_or_default() });
+            }
+        }));
+        let result = f(self);
+        self.subscribers = subscribers;
+        let mut planned = std::mem::take(&mut *planned.lock().unwrap_or_else(PoisonError::into_inner));
+        // A deleted task is gone by the time its event comes
+        for change in planned.iter_mut().filter(|change| change.title.is_empty()) {
+            if let Some(task) = change.event.task_id().and_then(|id| before.tasks.get(&id)) {
+                change.title = task.title.clone();
+            }
+        }
+        self.put_back(before);
+        self.views = views;
+        self.undo = undo;
+        self.tag_registry = tags;
+        self.goal = goal;
+        self.trash = trash;
+        self.shadowed = shadowed;
+        self.archive = archive;
+        self.retention_run = retention_run;
+        // Nothing was stored, so nothing was cut
+        self.truncations.truncate(truncations);
+        (result, planned)
+    }
+
+    // Start recording what the next command changes, as one step for `undo`
+    pub fn begin_undo_step(&mut self, label: &str) {
+        self.undo.begin(label);
+    }
+
+    pub fn finish_undo_step(&mut self) {
+        self.undo.finish(&self.tasks);
+    }
+
+    // Steps `undo` can still take back, oldest first
+    pub fn undo_history(&self) -> &[UndoEntry] {
+        self.undo.entries()
+    }
+
+    // Take back the latest step. One the tasks have moved on from since, through
+    // another command or an edit to the data file, is dropped untouched, so the
+    // step before it comes next.
+    pub fn undo(&mut self) -> Result<UndoEntry, TaskError> {
+        let entry = self.undo.pop().ok_or_else(|| TaskError::Invalid { reason: "nothing to undo".to_string() })?;
+        self.check_undo(&entry)?;
+        for change in &entry.changes {
+            match &change.before {
+                Some(task) => self.tasks.insert(change.id, task.clone()),
+                None => self.tasks.remove(&change.id),
+            };
+        }
+        self.reindex();
+        for change in &entry.changes {
+            self.emit(match (&change.before, &change.after) {
+                (None, _) => TaskEvent::Deleted(change.id),
+                (Some(_), None) => TaskEvent::Added(change.id),
+                (Some(_), Some(_)) => TaskEvent::Edited(change.id),
+            });
+        }
+        Ok(entry)
+    }
+
+    fn check_undo(&self, entry: &UndoEntry) -> Result<(), TaskError> {
+        let stale = |id: u32| TaskError::Invalid {
+            reason: format!("'{}' can no longer be undone: task {} has changed since", entry.label, id),
+        };
+        let ids: HashSet<u32> = entry.changes.iter().map(|change| change.id).collect();
+        for change in &entry.changes {
+            if self.tasks.get(&change.id) != change.after.as_ref() {
+                return Err(stale(change.id));
+            }
+            // A task given its old title since would end up with a twin
+            let taken = change.before.as_ref().and_then(|task| self.title_index.get(&normalize_title(&task.title)));
+            if let Some(&other) = taken.filter(|other| !ids.contains(other)) {
+                return Err(stale(other));
+            }
+        }
+        Ok(())
+    }
+
+    /// Register a callback invoked for every event, in registration order.
+    ///
+    /// ```
+    /// # use task_manager::{Priority, TaskManager};
+    /// let mut manager = TaskManager::new();
+    /// manager.subscribe(Box::new(|event, _| assert!(event.task_id().is_some())));
+    /// manager.add_task("Heard".to_string(), String::new(), Priority::Low).unwrap();
+    /// ```
+    pub fn subscribe(&mut self, subscriber: Subscriber) {
+        self.subscribers.push(subscriber);
+    }
+
+    // Every mutation that changes a task ends here, which also retires cached views
+    fn emit(&mut self, event: TaskEvent) {
+        self.generation += 1;
+        for subscriber in &self.subscribers {
+            subscriber(&event, self);
+        }
+    }
+
+    pub fn set_rules(&mut self, rules: Vec<Box<dyn ValidationRule>>) {
+        self.rules = rules;
+    }
+
+    pub fn set_urgent_days(&mut self, days: u32) {
+        self.urgent_days = days;
+    }
+
+    pub fn set_default_estimate(&mut self, estimate: Duration) {
+        self.default_estimate = estimate;
+    }
+
+    pub fn set_week_start(&mut self, day: Weekday) {
+        self.week_start = day;
+    }
+
+    pub fn set_guard_completed(&mut self, guard: bool) {
+        self.guard_completed = guard;
+    }
+
+    pub fn set_user(&mut self, user: String) {
+        self.user = Some(user);
+    }
+
+    pub fn user(&self) -> Option<&str> {
+        self.user.as_deref()
+    }
+
+    // `force` lets changes to others' tasks through, each one audited
+    pub fn set_protect_others(&mut self, protect: bool, force: bool) {
+        self.protect_others = protect;
+        self.force_others = force;
+    }
+
+    pub fn set_workflow(&mut self, workflow: Workflow) {
+        self.workflow = workflow;
+    }
+
+    pub fn workflow(&self) -> &Workflow {
+        &self.workflow
+    }
+
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.limits = limits;
+    }
+
+    pub fn limits(&self) -> &Limits {
+        &self.limits
+    }
+
+    pub fn set_truncate_to_limits(&mut self, truncate: bool) {
+        self.truncate_to_limits = truncate;
+    }
+
+    // "task 4: cut the description ..." for each task stored cut down since the last call
+    pub fn take_truncations(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.truncations)
+    }
+
+    // With --truncate, cut `task` down to the limits and remember what went;
+    // otherwise `validate` refuses it
+    fn fit_limits(&mut self, task: &mut Task) {
+        if self.truncate_to_limits {
+            let cut = self.limits.truncate(task);
+            self.truncations.extend(cut.into_iter().map(|what| format!("task {}: {}", task.id, what)));
+        }
+    }
+
+    // Whether "cafe" finds "café"; the search text is folded again on the next search
+    pub fn set_ignore_accents(&mut self, ignore: bool) {
+        self.ignore_accents = ignore;
+        *self.search_index.get_mut().unwrap_or_else(PoisonError::into_inner) = None;
+    }
+
+    // Where `task` falls in the Eisenhower matrix on `today`. Tasks without a due
+    // date are never urgent.
+    pub fn quadrant(&self, task: &Task, today: NaiveDate) -> Quadrant {
+        let important = task.priority >= Priority::High;
+        let urgent = task.due.is_some_and(|due| (due - today).num_days() <= i64::from(self.urgent_days));
+        Quadrant::classify(important, urgent)
+    }
+
+    // Open and completed work weighed by estimate, with a forecast from the
+    // completions in the `weeks` weeks before `now`
+    pub fn effort(&self, now: DateTime<Utc>, weeks: u32) -> EffortStats {
+        effort::build(self.tasks.values(), now, self.default_estimate, weeks)
+    }
+
+    // How pressed `task` is for time at `now`; None when it has no due date or is done
+    pub fn pressure(&self, task: &Task, now: NaiveDateTime) -> Option<Pressure> {
+        pressure::pressure(task, now, self.default_estimate)
+    }
+
+    // Check `task` against the active rules as if it were about to be stored
+    pub fn validate(&self, task: &Task) -> Result<(), TaskError> {
+        // Read from the counters, leaving out the stored copy of `task` itself
+        let [pending, in_progress, _] = self.counts.by_status;
+        let stored_open = self.tasks.get(&task.id).is_some_and(|t| t.status != TaskStatus::Completed);
+        let open_tasks = pending + in_progress - usize::from(stored_open);
+        let mut violations = validation::check_all(&self.rules, task, &ValidationContext { open_tasks });
+        violations.extend(self.limits.check(task));
+        if violations.is_empty() {
+            Ok(())
+        } else {
+            Err(TaskError::ValidationFailed(violations))
+        }
+    }
+
+    /// Add a task and return its id. Titles must be unique, ignoring case.
+    ///
+    /// ```
+    /// # use task_manager::{Priority, TaskError, TaskManager};
+    /// let mut manager = TaskManager::new();
+    /// assert_eq!(manager.add_task("Plan".to_string(), String::new(), Priority::Low).unwrap(), 1);
+    /// let again = manager.add_task("plan".to_string(), String::new(), Priority::Low);
+    /// assert!(matches!(again, Err(TaskError::DuplicateTask { existing_id: 1, .. })));
+    /// ```
+    pub fn add_task(&mut self, title: String, description: String, priority: Priority) -> Result<u32, TaskError> {
+        self.create_task(Task::new(0, title, description, priority))
+    }
+
+    /// Store a fully built task under the next free id; its own id is ignored.
+    ///
+    /// ```
+    /// # use chrono::NaiveDate;
+    /// # use task_manager::{Priority, Task, TaskManager};
+    /// let mut manager = TaskManager::new();
+    /// let due = NaiveDate::from_ymd_opt(2024, 5, 1).unwrap();
+    /// let mut task = Task::new(99, "Pay rent".to_string(), String::new(), Priority::High).with_due(due);
+    /// task.add_tag("home");
+    /// let id = manager.create_task(task).unwrap();
+    /// assert_eq!((id, manager.get_task(id).unwrap().due()), (1, Some(due)));
+    /// ```
+    pub fn create_task(&mut self, task: Task) -> Result<u32, TaskError> {
+        for tag in &task.tags {
+            self.check_tag(tag)?;
+        }
+        let id = self.insert_new(task)?;
+        self.assign_keys(&[id]);
+        Ok(id)
+    }
+
+    /// Store many new tasks in one pass, each as create_task would: titles are checked
+    /// against the list and the tasks before them, and a rejected task does not stop
+    /// the rest. Subscribers still hear about every task added.
+    ///
+    /// ```
+    /// # use task_manager::{Priority, Task, TaskManager};
+    /// let mut manager = TaskManager::new();
+    /// let tasks = vec![Task::new(0, "A".to_string(), String::new(), Priority::Low)];
+    /// assert_eq!(manager.add_tasks_bulk(tasks).created(), vec![1]);
+    /// ```
+    pub fn add_tasks_bulk(&mut self, tasks: Vec<Task>) -> BulkResult {
+        let outcomes: Vec<Result<u32, TaskError>> = tasks.into_iter().map(|task| self.insert_new(task)).collect();
+        let result = BulkResult { outcomes };
+        self.assign_keys(&result.created());
+        debug!("added {} tasks in bulk", result.created().len());
+        result
+    }
+
+    fn insert_new(&mut self, mut task: Task) -> Result<u32, TaskError> {
+        // Check for duplicate titles
+        let key = normalize_title(&task.title);
+        if let Some(&existing_id) = self.title_index.get(&key) {
+            return Err(TaskError::DuplicateTask { title: task.title, existing_id });
+        }
+
+        let id = self.next_id;
+        task.id = id;
+        // A task copied from elsewhere keeps its creator
+        if task.created_by.is_none() {
+            task.created_by = self.user.clone();
+        }
+        self.fit_limits(&mut task);
+        self.validate(&task)?;
+        // New tasks go to the end of the manual order
+        task.rank = self.next_rank;
+        self.next_rank += RANK_GAP;
+        self.tag_names.intern_tags(&mut task);
+        for tag in &task.tags {
+            self.tag_index.entry(normalize_tag(tag)).or_default().insert(id);
+        }
+        self.counts.add(&task);
+        self.remember_before(id);
+        self.tasks.insert(id, task);
+        self.title_index.insert(key, id);
+        self.next_id += 1;
+        debug!("added task {}", id);
+        self.emit(TaskEvent::Added(id));
+        Ok(id)
+    }
+
+    // Store a copy of a task from another list, matched by uid: an existing task keeps
+    // its id and takes every other field, timestamps included; an unknown uid is added
+    pub fn upsert_task(&mut self, mut task: Task) -> Result<u32, TaskError> {
+        let Some(id) = self.find_by_uid(&task.uid).map(|existing| existing.id) else {
+            return self.create_task(task);
+        };
+        let key = normalize_title(&task.title);
+        if let Some(&existing_id) = self.title_index.get(&key).filter(|&&existing| existing != id) {
+            return Err(TaskError::DuplicateTask { title: task.title, existing_id });
+        }
+        task.id = id;
+        // Like the id, the place in the manual order belongs to this list
+        task.rank = self.tasks[&id].rank;
+        self.fit_limits(&mut task);
+        self.validate(&task)?;
+        self.tag_names.intern_tags(&mut task);
+
+        self.counts.add(&task);
+        // The other list is where this copy was changed, so it is stored as it comes
+        self.remember_before(id);
+        let old = self.tasks.insert(id, task).expect("id was just looked up");
+        self.counts.remove(&old);
+        let old_key = normalize_title(&old.title);
+        if key != old_key {
+            self.unindex_title(&old_key, id);
+            self.title_index.insert(key, id);
+        }
+        self.unindex_tags(id, &old.tags);
+        for tag in &self.tasks[&id].tags {
+            self.tag_index.entry(normalize_tag(tag)).or_default().insert(id);
+        }
+        debug!("replaced task {} from a synced copy", id);
+        self.emit(TaskEvent::Edited(id));
+        Ok(id)
+    }
+
+    pub fn find_by_uid(&self, uid: &str) -> Option<&Task> {
+        self.tasks.values().find(|task| task.uid == uid)
+    }
+
+    pub fn sync_state(&self) -> SyncState {
+        self.sync.lock().unwrap_or_else(PoisonError::into_inner).clone()
+    }
+
+    pub fn update_sync_state<R>(&mut self, f: impl FnOnce(&mut SyncState) -> R) -> R {
+        f(&mut self.sync.lock().unwrap_or_else(PoisonError::into_inner))
+    }
+
+    pub fn views(&self) -> &BTreeMap<String, String> {
+        &self.views
+    }
+
+    // Store `query` under `name`, returning whether it replaced a view of that
+    // name, which only `force` allows
+    pub fn save_view(&mut self, name: &str, query: &str, force: bool) -> Result<bool, TaskError> {
+        let name = checked_view_name(name)?;
+        TaskFilter::parse(query).map_err(|e| TaskError::Invalid { reason: e.to_string() })?;
+        if self.views.contains_key(&name) && !force {
+            return Err(view_taken(&name));
+        }
+        let replaced = self.views.insert(name.clone(), query.trim().to_string()).is_some();
+        self.emit(TaskEvent::ViewChanged { name });
+        Ok(replaced)
+    }
+
+    // Renaming onto an existing view replaces it, again only with `force`
+    pub fn rename_view(&mut self, from: &str, to: &str, force: bool) -> Result<(), TaskError> {
+        let (from, to) = (from.to_lowercase(), checked_view_name(to)?);
+        if !self.views.contains_key(&from) {
+            return Err(no_such_view(&from));
+        }
+        if from != to && self.views.contains_key(&to) && !force {
+            return Err(view_taken(&to));
+        }
+        let query = self.views.remove(&from).unwrap_or_default();
+        self.views.insert(to.clone(), query);
+        self.emit(TaskEvent::ViewChanged { name: from });
+        self.emit(TaskEvent::ViewChanged { name: to });
+        Ok(())
+    }
+
+    pub fn delete_view(&mut self, name: &str) -> Result<(), TaskError> {
+        let name = name.to_lowercase();
+        self.views.remove(&name).ok_or_else(|| no_such_view(&name))?;
+        self.emit(TaskEvent::ViewChanged { name });
+        Ok(())
+    }
+
+    // The tasks a saved view finds that also match `extra`
+    pub fn find_view(&self, name: &str, extra: &TaskFilter) -> Result<Vec<&Task>, TaskError> {
+        let name = name.to_lowercase();
+        let query = self.views.get(&name).ok_or_else(|| no_such_view(&name))?;
+        let filter = TaskFilter::parse(query).map_err(|e| TaskError::Invalid { reason: e.to_string() })?;
+        let extra = self.for_user(extra);
+        Ok(self.find(&filter).into_iter().filter(|task| extra.matches(task)).collect())
+    }
+
+    pub fn goal(&self) -> Option<&Goal> {
+        self.goal.as_ref()
+    }
+
+    // Replace the goal, or drop it with None
+    pub fn set_goal(&mut self, goal: Option<Goal>) {
+        if self.goal != goal {
+            self.goal = goal;
+            self.emit(TaskEvent::GoalChanged);
+        }
+    }
+
+    // Completions so far in the goal's period holding `today`
+    pub fn goal_progress(&self, today: NaiveDate) -> Option<GoalProgress> {
+        let goal = self.goal.as_ref()?;
+        let completions = self.tasks.values().filter_map(goal::completion_day);
+        Some(goal::progress(goal, completions, today, self.week_start))
+    }
+
+    // The progress, the first time the goal is met in its period; later calls
+    // in the same period return None
+    pub fn celebrate_goal(&mut self, today: NaiveDate) -> Option<GoalProgress> {
+        let progress = self.goal_progress(today).filter(GoalProgress::is_met)?;
+        let goal = self.goal.as_mut()?;
+        if goal.celebrated == Some(progress.start) {
+            return None;
+        }
+        goal.celebrated = Some(progress.start);
+        self.emit(TaskEvent::GoalChanged);
+        Some(progress)
+    }
+
+    pub fn tag_registry(&self) -> &TagRegistry {
+        &self.tag_registry
+    }
+
+    pub fn set_strict_tags(&mut self, strict: bool) {
+        self.strict_tags = strict;
+    }
+
+    // Register what a tag means, replacing anything said about it before
+    pub fn describe_tag(&mut self, tag: &str, description: &str, color: Option<String>) -> Result<(), TaskError> {
+        let tag = normalize_tag(tag);
+        if tag.is_empty() || tag.contains(char::is_whitespace) {
+            return Err(TaskError::Invalid { reason: format!("'{}' is not a tag", tag) });
+        }
+        let info = TagInfo { description: description.trim().to_string(), color };
+        if self.tag_registry.insert(tag.clone(), info.clone()).as_ref() != Some(&info) {
+            self.emit(TaskEvent::TagDescribed { tag });
+        }
+        Ok(())
+    }
+
+    // Take in a shared registry: entries are added or updated and, unless
+    // `replace`, the ones the file does not mention stay
+    pub fn import_tags(&mut self, registry: TagRegistry, replace: bool) -> TagImport {
+        let mut report = TagImport::default();
+        if replace {
+            report.removed = self.tag_registry.keys().filter(|tag| !registry.contains_key(*tag)).cloned().collect();
+            for tag in &report.removed {
+                self.tag_registry.remove(tag);
+            }
+        }
+        for (tag, info) in registry {
+            match self.tag_registry.insert(tag.clone(), info.clone()) {
+                None => report.added.push(tag),
+                Some(old) if old != info => report.updated.push(tag),
+                Some(_) => {}
+            }
+        }
+        let changed: Vec<String> = report.added.iter().chain(&report.updated).chain(&report.removed).cloned().collect();
+        for tag in changed {
+            self.emit(TaskEvent::TagDescribed { tag });
+        }
+        report
+    }
+
+    // Every tag in use or registered, alphabetically, with how many tasks carry it
+    pub fn tag_rows(&self) -> Vec<TagRow> {
+        let mut tags: BTreeSet<&str> = self.tag_index.keys().map(String::as_str).collect();
+        tags.extend(self.tag_registry.keys().map(String::as_str));
+        tags.into_iter()
+            .map(|tag| TagRow {
+                tag: tag.to_string(),
+                count: self.tag_index.get(tag).map_or(0, BTreeSet::len),
+                info: self.tag_registry.get(tag).cloned(),
+            })
+            .collect()
+    }
+
+    // With strict tags, a tag the registry does not know, and the registered
+    // ones it might have been meant as
+    fn check_tag(&self, tag: &str) -> Result<(), TaskError> {
+        let key = normalize_tag(tag);
+        if !self.strict_tags || self.tag_registry.contains_key(&key) {
+            return Ok(());
+        }
+        let close: Vec<&str> =
+            self.tag_registry.keys().filter(|known| search::is_similar(known, &key)).map(String::as_str).collect();
+        let reason = match close.as_slice() {
+            [] => format!("tag '{}' is not in the tag registry", key),
+            close => format!("tag '{}' is not in the tag registry; did you mean {}?", key, close.join(", ")),
+        };
+        Err(TaskError::Invalid { reason })
+    }
+
+    // Queue every later change for the next `sync push`
+    pub fn enable_change_queue(&mut self) {
+        let sync = Arc::clone(&self.sync);
+        let recorder = Mutex::new(ChangeRecorder::new(self));
+        self.subscribe(Box::new(move |event, manager| {
+            let mut state = sync.lock().unwrap_or_else(PoisonError::into_inner);
+            recorder.lock().unwrap_or_else(PoisonError::into_inner).record(event, manager, &mut state);
+        }));
+    }
+
+    /// ```
+    /// # use task_manager::{TaskError, TaskManager};
+    /// assert!(matches!(TaskManager::new().get_task(4), Err(TaskError::TaskNotFound { id: 4 })));
+    /// ```
+    pub fn get_task(&self, id: u32) -> Result<&Task, TaskError> {
+        self.tasks.get(&id).ok_or(TaskError::TaskNotFound { id })
+    }
+
+    fn get_task_mut(&mut self, id: u32) -> Result<&mut Task, TaskError> {
+        self.before_change(id)?;
+        self.tasks.get_mut(&id).ok_or(TaskError::TaskNotFound { id })
+    }
+
+    // Every change made on purpose to a stored task comes here first: a completed
+    // task is refused while the guard is on, so is someone else's while they are
+    // protected, and the undo step is told
+    fn before_change(&mut self, id: u32) -> Result<(), TaskError> {
+        if self.guard_completed && self.tasks.get(&id).is_some_and(|task| task.status == TaskStatus::Completed) {
+            return Err(TaskError::TaskCompleted { id });
+        }
+        self.check_owner(id)?;
+        self.remember_before(id);
+        Ok(())
+    }
+
+    // The creator of a task another user made, while such tasks are protected;
+    // tasks from before creators were recorded belong to nobody
+    fn other_owner(&self, id: u32) -> Option<&str> {
+        let user = self.user.as_deref().filter(|_| self.protect_others)?;
+        let owner = self.tasks.get(&id)?.created_by.as_deref()?;
+        (owner != user).then_some(owner)
+    }
+
+    fn check_owner(&mut self, id: u32) -> Result<(), TaskError> {
+        let Some(owner) = self.other_owner(id).map(str::to_string) else {
+            return Ok(());
+        };
+        if !self.force_others {
+            return Err(TaskError::NotOwner { id, owner });
+        }
+        self.emit(TaskEvent::Overridden { id, owner });
+        Ok(())
+    }
+
+    // The part of before_change every change needs, new and deleted tasks and
+    // bookkeeping such as ranks and key counters included: an open undo step
+    // keeps the task as it was
+    fn remember_before(&mut self, id: u32) {
+        if self.undo.wants(id) {
+            let before = self.tasks.get(&id).cloned();
+            self.undo.remember(id, before);
+        }
+    }
+
+    /// Apply every field of `edit` or none of them: the result must pass the rules.
+    ///
+    /// ```
+    /// # use task_manager::{Priority, TaskEdit, TaskManager};
+    /// let mut manager = TaskManager::new();
+    /// let id = manager.add_task("Draft".to_string(), String::new(), Priority::Low).unwrap();
+    /// manager.apply_edit(id, TaskEdit { priority: Some(Priority::High), ..TaskEdit::default() }).unwrap();
+    /// assert_eq!(manager.get_task(id).unwrap().priority(), &Priority::High);
+    /// ```
+    pub fn apply_edit(&mut self, id: u32, edit: TaskEdit) -> Result<(), TaskError> {
+        let mut task = self.get_task(id)?.clone();
+        let old_key = normalize_title(&task.title);
+        if let Some(title) = edit.title {
+            let key = normalize_title(&title);
+            if let Some(&existing_id) = self.title_index.get(&key).filter(|&&existing| existing != id) {
+                return Err(TaskError::DuplicateTask { title, existing_id });
+            }
+            task.title = title;
+        }
+        if let Some(description) = edit.description {
+            task.description = description;
+        }
+        if let Some(priority) = edit.priority {
+            task.priority = priority;
+        }
+        if edit.due.is_some() && edit.due != task.due {
+            task.due = edit.due;
+            // Reminders count back from the new date, so each may fire again
+            for reminder in &mut task.reminders {
+                reminder.fired = false;
+            }
+        }
+        self.fit_limits(&mut task);
+        self.validate(&task)?;
+
+        task.touch();
+        self.before_change(id)?;
+        let key = normalize_title(&task.title);
+        self.counts.add(&task);
+        if let Some(old) = self.tasks.insert(id, task) {
+            self.counts.remove(&old);
+        }
+        if key != old_key {
+            self.unindex_title(&old_key, id);
+            self.title_index.insert(key, id);
+        }
+        debug!("edited task {}", id);
+        self.emit(TaskEvent::Edited(id));
+        Ok(())
+    }
+
+    // Change just the priority, keeping the old one in the event so the audit
+    // log shows what it was raised or lowered from. Returns false when the task
+    // already had it.
+    pub fn update_priority(&mut self, id: u32, priority: Priority) -> Result<bool, TaskError> {
+        let mut task = self.get_task(id)?.clone();
+        if task.priority == priority {
+            return Ok(false);
+        }
+        let from = std::mem::replace(&mut task.priority, priority.clone());
+        self.validate(&task)?;
+
+        task.touch();
+        self.before_change(id)?;
+        self.counts.add(&task);
+        if let Some(old) = self.tasks.insert(id, task) {
+            self.counts.remove(&old);
+        }
+        debug!("task {} priority {:?} -> {:?}", id, from, priority);
+        self.emit(TaskEvent::PriorityChanged { id, from, to: priority });
+        Ok(true)
+    }
+
+    // Remember that a reminder went out on `date`
+    pub fn mark_notified(&mut self, id: u32, date: NaiveDate) -> Result<(), TaskError> {
+        self.get_task_mut(id)?.last_notified = Some(date);
+        debug!("reminded about task {}", id);
+        self.emit(TaskEvent::Notified(id));
+        Ok(())
+    }
+
+    // Replace the task's reminders; an empty list clears them. Only tasks with a
+    // due date can have any.
+    pub fn set_reminders(&mut self, id: u32, mut offsets: Vec<Duration>) -> Result<(), TaskError> {
+        let task = self.get_task_mut(id)?;
+        if task.due.is_none() && !offsets.is_empty() {
+            return Err(TaskError::Invalid { reason: format!("task {} has no due date to remind before", id) });
+        }
+        offsets.sort_unstable_by(|a, b| b.cmp(a));
+        offsets.dedup();
+        let fired: Vec<Duration> = task.reminders.iter().filter(|r| r.fired).map(|r| r.offset).collect();
+        task.reminders = offsets
+            .into_iter()
+            .map(|offset| Reminder { offset, fired: fired.contains(&offset) })
+            .collect();
+        task.touch();
+        self.emit(TaskEvent::Edited(id));
+        Ok(())
+    }
+
+    // Note that the reminder `offset` before the due date has gone out
+    pub fn mark_reminder_fired(&mut self, id: u32, offset: Duration) -> Result<(), TaskError> {
+        let task = self.get_task_mut(id)?;
+        let reminder = task.reminders.iter_mut().find(|r| r.offset == offset).ok_or(TaskError::InvalidInput)?;
+        reminder.fired = true;
+        self.emit(TaskEvent::Notified(id));
+        Ok(())
+    }
+
+    // Mark the task as blocked on `who` from `since`; waiting again replaces the earlier entry
+    pub fn wait_on(&mut self, id: u32, who: String, since: NaiveDate, follow_up: Option<NaiveDate>) -> Result<(), TaskError> {
+        let task = self.get_task_mut(id)?;
+        if task.status == TaskStatus::Completed {
+            return Err(TaskError::Invalid { reason: format!("task {} is already completed", id) });
+        }
+        task.waiting_on = Some(Waiting { who, since, follow_up });
+        task.touch();
+        self.emit(TaskEvent::Edited(id));
+        Ok(())
+    }
+
+    // Returns what the task was waiting on, if anything
+    pub fn stop_waiting(&mut self, id: u32) -> Result<Option<Waiting>, TaskError> {
+        let task = self.get_task_mut(id)?;
+        let waiting = task.waiting_on.take();
+        if waiting.is_some() {
+            task.touch();
+            self.emit(TaskEvent::Edited(id));
+        }
+        Ok(waiting)
+    }
+
+    // Open tasks nobody has changed in `days` days, oldest first; snoozed ones
+    // stay out until their day comes
+    pub fn stale_tasks(&self, days: u32, now: DateTime<Utc>) -> Vec<&Task> {
+        // A cutoff before the start of time leaves nothing stale
+        let Some(cutoff) = TimeDelta::try_days(i64::from(days)).and_then(|days| now.checked_sub_signed(days)) else {
+            return Vec::new();
+        };
+        let today = now.date_naive();
+        let mut stale: Vec<&Task> = self
+            .tasks
+            .values()
+            .filter(|task| task.status != TaskStatus::Completed && task.updated_at < cutoff)
+            .filter(|task| task.snoozed_until.is_none_or(|until| until <= today))
+            .collect();
+        stale.sort_by_key(|task| (task.updated_at, task.id));
+        stale
+    }
+
+    // Mark the task as looked at without changing anything else, so it leaves `review`
+    pub fn touch_task(&mut self, id: u32) -> Result<(), TaskError> {
+        self.get_task_mut(id)?.touch();
+        self.emit(TaskEvent::Edited(id));
+        Ok(())
+    }
+
+    pub fn snooze_task(&mut self, id: u32, until: NaiveDate) -> Result<(), TaskError> {
+        self.get_task_mut(id)?.snoozed_until = Some(until);
+        self.emit(TaskEvent::Edited(id));
+        Ok(())
+    }
+
+    // None clears it
+    pub fn set_energy(&mut self, id: u32, energy: Option<Energy>) -> Result<(), TaskError> {
+        let task = self.get_task_mut(id)?;
+        task.energy = energy;
+        task.touch();
+        self.emit(TaskEvent::Edited(id));
+        Ok(())
+    }
+
+    // Open tasks to pick up with this much energy left: not waiting on anyone, not
+    // snoozed and needing no more than that, most urgent first. A task without an
+    // energy level could need anything, so only `high` takes it in.
+    pub fn can_do(&self, energy: Energy, today: NaiveDate) -> Vec<&Task> {
+        let mut tasks: Vec<&Task> = self
+            .tasks
+            .values()
+            .filter(|task| task.status != TaskStatus::Completed && task.waiting_on.is_none())
+            .filter(|task| task.snoozed_until.is_none_or(|until| until <= today))
+            .filter(|task| task.energy.unwrap_or(Energy::High) <= energy)
+            .collect();
+        tasks.sort_by_key(|task| (Reverse(task.priority.clone()), task.due.is_none(), task.due, task.id));
+        tasks
+    }
+
+    // Open tasks due within `days` of `now` or overdue, most pressed first, then
+    // by due date
+    pub fn due_soon(&self, days: u32, now: NaiveDateTime) -> Vec<&Task> {
+        let until = now.date().checked_add_days(Days::new(u64::from(days))).unwrap_or(NaiveDate::MAX);
+        let mut tasks: Vec<(Pressure, &Task)> = self
+            .tasks
+            .values()
+            .filter(|task| task.due.is_some_and(|due| due <= until))
+            .filter_map(|task| self.pressure(task, now).map(|pressure| (pressure, task)))
+            .collect();
+        tasks.sort_by_key(|(pressure, task)| (Reverse(*pressure), task.due, task.id));
+        tasks.into_iter().map(|(_, task)| task).collect()
+    }
+
+    // Open tasks blocked on someone, grouped by person, longest waiting first within each
+    pub fn waiting(&self) -> Vec<(String, Vec<&Task>)> {
+        let mut by_person: BTreeMap<&str, Vec<&Task>> = BTreeMap::new();
+        for task in self.tasks.values().filter(|task| task.status != TaskStatus::Completed) {
+            if let Some(waiting) = &task.waiting_on {
+                by_person.entry(waiting.who.as_str()).or_default().push(task);
+            }
+        }
+        by_person
+            .into_iter()
+            .map(|(who, mut tasks)| {
+                tasks.sort_by_key(|task| task.waiting_on.as_ref().map(|waiting| waiting.since));
+                (who.to_string(), tasks)
+            })
+            .collect()
+    }
+
+    // File the task under `parent`, or make it top-level again; a task cannot end up under itself
+    pub fn set_parent(&mut self, id: u32, parent: Option<u32>) -> Result<(), TaskError> {
+        let uid = self.get_task(id)?.uid.clone();
+        let parent_uid = match parent {
+            Some(parent) => {
+                let by_uid: HashMap<&str, &Task> = self.tasks.values().map(|task| (task.uid.as_str(), task)).collect();
+                let mut next = Some(self.get_task(parent)?.uid.as_str());
+                while let Some(above) = next {
+                    if above == uid {
+                        return Err(TaskError::Invalid { reason: format!("task {} is already above task {}", id, parent) });
+                    }
+                    next = by_uid.get(above).and_then(|task| task.parent.as_deref());
+                }
+                Some(self.get_task(parent)?.uid.clone())
+            }
+            None => None,
+        };
+        let task = self.get_task_mut(id)?;
+        task.parent = parent_uid;
+        task.touch();
+        self.emit(TaskEvent::Edited(id));
+        let mut moved = vec![id];
+        moved.extend(self.descendants(id));
+        self.assign_keys(&moved);
+        Ok(())
+    }
+
+    // Give the project whose top task is `id` a key prefix and number every task in
+    // it that has no key there yet. Changing the prefix renumbers the tasks, each
+    // keeping its old key as an alias.
+    pub fn set_key_prefix(&mut self, id: u32, prefix: &str) -> Result<(), TaskError> {
+        let prefix = prefix.trim().to_uppercase();
+        if !valid_key_prefix(&prefix) {
+            return Err(TaskError::Invalid {
+                reason: format!("key prefix '{}' must be up to ten letters and digits starting with a letter", prefix),
+            });
+        }
+        let project = self.get_task(id)?;
+        if project.parent.is_some() {
+            return Err(TaskError::Invalid {
+                reason: format!("task {} is a subtask; a prefix goes on the top task of a project", id),
+            });
+        }
+        let mut others = self.tasks.values().filter(|task| task.id != id);
+        if let Some(other) = others.find(|task| task.key_prefix.as_ref().is_some_and(|kp| kp.prefix == prefix)) {
+            return Err(TaskError::Invalid {
+                reason: format!("prefix {} is already used by task {}", prefix, other.id),
+            });
+        }
+        let next = match &project.key_prefix {
+            Some(current) if current.prefix == prefix => current.next,
+            _ => 1,
+        };
+        let task = self.get_task_mut(id)?;
+        task.key_prefix = Some(KeyPrefix { prefix, next });
+        task.touch();
+        self.emit(TaskEvent::Edited(id));
+        let members = self.descendants(id);
+        self.assign_keys(&members);
+        Ok(())
+    }
+
+    // A task by its key or one of its old keys, ignoring case. None when no task or
+    // more than one has it, which only a hand-edited file can cause.
+    pub fn find_by_key(&self, key: &str) -> Option<&Task> {
+        let mut found = self.tasks.values().filter(|task| has_key(task, key));
+        match (found.next(), found.next()) {
+            (Some(task), None) => Some(task),
+            _ => None,
+        }
+    }
+
+    // Keys and prefixes are unique when handed out; this finds the duplicates
+    // edits to the data file by hand can leave behind
+    pub fn check_keys(&self) -> Result<(), TaskError> {
+        let mut keys: BTreeMap<String, BTreeSet<u32>> = BTreeMap::new();
+        let mut prefixes: BTreeMap<String, BTreeSet<u32>> = BTreeMap::new();
+        for task in self.tasks.values() {
+            for key in task.key.iter().chain(&task.old_keys) {
+                keys.entry(key.to_uppercase()).or_default().insert(task.id);
+            }
+            if let Some(kp) = &task.key_prefix {
+                prefixes.entry(kp.prefix.to_uppercase()).or_default().insert(task.id);
+            }
+        }
+        let duplicates = |kind: &str, owners: BTreeMap<String, BTreeSet<u32>>| -> Vec<String> {
+            owners
+                .into_iter()
+                .filter(|(_, ids)| ids.len() > 1)
+                .map(|(name, ids)| {
+                    let ids: Vec<String> = ids.iter().map(u32::to_string).collect();
+                    format!("{} {} is on tasks {}", kind, name, ids.join(", "))
+                })
+                .collect()
+        };
+        let mut problems = duplicates("key", keys);
+        problems.extend(duplicates("prefix", prefixes));
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(TaskError::Invalid { reason: format!("duplicate task keys: {}", problems.join("; ")) })
+        }
+    }
+
+    // Ids of the tasks below `id` at any depth, in id order
+    fn descendants(&self, id: u32) -> Vec<u32> {
+        let Some(top) = self.tasks.get(&id) else {
+            return Vec::new();
+        };
+        let mut children: HashMap<&str, Vec<&Task>> = HashMap::new();
+        for task in self.tasks.values() {
+            if let Some(parent) = task.parent.as_deref() {
+                children.entry(parent).or_default().push(task);
+            }
+        }
+        let mut found = BTreeSet::new();
+        let mut pending = vec![top.uid.as_str()];
+        while let Some(uid) = pending.pop() {
+            for child in children.get(uid).into_iter().flatten() {
+                if child.id != id && found.insert(child.id) {
+                    pending.push(child.uid.as_str());
+                }
+            }
+        }
+        found.into_iter().collect()
+    }
+
+    // Bring each task's key in line with the project it is in now. A task whose key
+    // is from another project, or that had none, gets the next number in its
+    // project when that has a prefix; a key it loses stays on as an old key.
+    fn assign_keys(&mut self, ids: &[u32]) {
+        let wanted: Vec<(u32, u32, Option<String>)> = {
+            let by_uid: HashMap<&str, &Task> = self.tasks.values().map(|task| (task.uid.as_str(), task)).collect();
+            ids.iter()
+                .filter_map(|id| self.tasks.get(id))
+                .map(|task| {
+                    let project = project_of(task, &by_uid);
+                    let prefix = project.key_prefix.as_ref().filter(|_| project.id != task.id);
+                    (task.id, project.id, prefix.map(|kp| kp.prefix.clone()))
+                })
+                .collect()
+        };
+        for (id, project, prefix) in wanted {
+            let current = self.tasks.get(&id).and_then(|task| task.key.as_deref());
+            let fits = match (current, prefix.as_deref()) {
+                (Some(key), Some(prefix)) => key.rsplit_once('-').is_some_and(|(start, _)| start == prefix),
+                (None, None) => true,
+                _ => false,
+            };
+            if fits {
+                continue;
+            }
+            let key = prefix.map(|prefix| self.next_key(project, &prefix));
+            self.remember_before(id);
+            let Some(task) = self.tasks.get_mut(&id) else {
+                continue;
+            };
+            if let Some(old) = task.key.take().filter(|old| !task.old_keys.contains(old)) {
+                task.old_keys.push(old);
+            }
+            task.key = key;
+            task.touch();
+            self.emit(TaskEvent::Edited(id));
+        }
+    }
+
+    // Take the project's next number, stepping over any a hand edit already used
+    fn next_key(&mut self, project: u32, prefix: &str) -> String {
+        self.remember_before(project);
+        loop {
+            let Some(kp) = self.tasks.get_mut(&project).and_then(|task| task.key_prefix.as_mut()) else {
+                return format!("{}-1", prefix);
+            };
+            let key = format!("{}-{}", prefix, kp.next);
+            kp.next += 1;
+            if !self.tasks.values().any(|task| has_key(task, &key)) {
+                self.emit(TaskEvent::Edited(project));
+                return key;
+            }
+        }
+    }
+
+    pub fn mark_triaged(&mut self, id: u32) -> Result<(), TaskError> {
+        let task = self.get_task_mut(id)?;
+        task.triaged = true;
+        task.touch();
+        self.emit(TaskEvent::Edited(id));
+        Ok(())
+    }
+
+    pub fn set_pinned(&mut self, id: u32, pinned: bool) -> Result<(), TaskError> {
+        let task = self.get_task_mut(id)?;
+        if task.pinned == pinned {
+            return Ok(());
+        }
+        task.pinned = pinned;
+        task.touch();
+        self.emit(TaskEvent::Edited(id));
+        Ok(())
+    }
+
+    // Link two tasks as related, each listing the other. Returns false when they
+    // already were.
+    pub fn relate(&mut self, id: u32, other: u32) -> Result<bool, TaskError> {
+        self.check_link(id, other)?;
+        if self.tasks[&id].relates_to.contains(&other) && self.tasks[&other].relates_to.contains(&id) {
+            return Ok(false);
+        }
+        for (from, to) in [(id, other), (other, id)] {
+            let task = self.tasks.get_mut(&from).expect("checked above");
+            if let Err(at) = task.relates_to.binary_search(&to) {
+                task.relates_to.insert(at, to);
+                task.touch();
+                self.emit(TaskEvent::Edited(from));
+            }
+        }
+        Ok(true)
+    }
+
+    // Take the link out of both tasks. Returns false when there was none.
+    pub fn unrelate(&mut self, id: u32, other: u32) -> Result<bool, TaskError> {
+        self.check_link(id, other)?;
+        let mut removed = false;
+        for (from, to) in [(id, other), (other, id)] {
+            let task = self.tasks.get_mut(&from).expect("checked above");
+            if task.relates_to.contains(&to) {
+                task.relates_to.retain(|&linked| linked != to);
+                task.touch();
+                self.emit(TaskEvent::Edited(from));
+                removed = true;
+            }
+        }
+        Ok(removed)
+    }
+
+    // Both tasks exist, are different and may be changed; tells the undo step
+    // about both before either changes
+    fn check_link(&mut self, id: u32, other: u32) -> Result<(), TaskError> {
+        self.get_task(id)?;
+        self.get_task(other)?;
+        if id == other {
+            return Err(TaskError::Invalid { reason: format!("task {} cannot relate to itself", id) });
+        }
+        self.before_change(id)?;
+        self.before_change(other)
+    }
+
+    // Tasks related to `id` from either side, by id. A link only one side
+    // records, as a hand-edited file may have, still shows on both.
+    pub fn related(&self, id: u32) -> Vec<&Task> {
+        let own = self.tasks.get(&id).map_or(&[][..], |task| task.relates_to.as_slice());
+        self.tasks
+            .values()
+            .filter(|task| task.id != id && (own.contains(&task.id) || task.relates_to.contains(&id)))
+            .collect()
+    }
+
+    pub fn pinned(&self) -> Vec<&Task> {
+        self.tasks.values().filter(|task| task.pinned).collect()
+    }
+
+    // Put a task just before or after another in the manual order. The new rank is
+    // halfway between the neighbours; when they sit next to each other the whole
+    // order is spaced out again first.
+    pub fn move_task(&mut self, id: u32, place: Placement, other: u32) -> Result<(), TaskError> {
+        self.get_task(id)?;
+        self.get_task(other)?;
+        if id == other {
+            return Err(TaskError::Invalid { reason: format!("cannot move task {} relative to itself", id) });
+        }
+        let rank = match self.rank_beside(id, place, other) {
+            Some(rank) => rank,
+            None => {
+                self.renumber_ranks();
+                self.rank_beside(id, place, other).expect("renumbered ranks leave room between neighbours")
+            }
+        };
+        self.next_rank = self.next_rank.max(rank + RANK_GAP);
+        let task = self.get_task_mut(id)?;
+        task.rank = rank;
+        task.touch();
+        debug!("moved task {} {:?} task {}", id, place, other);
+        self.emit(TaskEvent::Edited(id));
+        Ok(())
+    }
+
+    // Ids in manual order; equal ranks (tasks saved before there was one) keep id order
+    fn manual_order(&self) -> Vec<u32> {
+        let mut tasks: Vec<&Task> = self.tasks.values().collect();
+        tasks.sort_by_key(|task| (task.rank, task.id));
+        tasks.into_iter().map(|task| task.id).collect()
+    }
+
+    // A free rank between `other` and its neighbour on the `place` side, leaving out
+    // the task being moved; None when the two ranks have nothing between them
+    fn rank_beside(&self, id: u32, place: Placement, other: u32) -> Option<u64> {
+        let order: Vec<u32> = self.manual_order().into_iter().filter(|&each| each != id).collect();
+        let at = order.iter().position(|&each| each == other)?;
+        let rank = |index: usize| self.tasks[&order[index]].rank;
+        let (lower, upper) = match place {
+            Placement::Before if at == 0 => (0, rank(at)),
+            Placement::Before => (rank(at - 1), rank(at)),
+            Placement::After if at + 1 == order.len() => return Some(rank(at) + RANK_GAP),
+            Placement::After => (rank(at), rank(at + 1)),
+        };
+        (upper > lower + 1).then(|| lower + (upper - lower) / 2)
+    }
+
+    // Give every task a fresh rank RANK_GAP apart, keeping their order. The tasks
+    // themselves did not change, so they are not touched.
+    fn renumber_ranks(&mut self) {
+        let order = self.manual_order();
+        let mut changed = Vec::new();
+        for (index, id) in order.iter().enumerate() {
+            let rank = (index as u64 + 1) * RANK_GAP;
+            if self.tasks[id].rank != rank {
+                self.remember_before(*id);
+                self.tasks.get_mut(id).expect("ordered ids come from the map").rank = rank;
+                changed.push(*id);
+            }
+        }
+        self.next_rank = self.next_rank.max((order.len() as u64 + 1) * RANK_GAP);
+        debug!("renumbered the manual order of {} tasks", order.len());
+        for id in changed {
+            self.emit(TaskEvent::Edited(id));
+        }
+    }
+
+    // Open tasks captured with `quick` and not yet triaged, oldest first
+    pub fn inbox(&self) -> Vec<&Task> {
+        self.tasks.values().filter(|task| !task.triaged && task.status != TaskStatus::Completed).collect()
+    }
+
+    // Open tasks besides `id` whose titles look like its title, most alike first
+    pub fn similar_open_tasks(&self, id: u32) -> Result<Vec<&Task>, TaskError> {
+        let title = &self.get_task(id)?.title;
+        let mut similar: Vec<(f64, &Task)> = self
+            .open_tasks()
+            .filter(|task| task.id != id)
+            .map(|task| (search::similarity(title, &task.title), task))
+            .filter(|(similarity, _)| *similarity >= search::SIMILAR_THRESHOLD)
+            .collect();
+        similar.sort_by(|(a, _), (b, _)| b.total_cmp(a));
+        Ok(similar.into_iter().map(|(_, task)| task).collect())
+    }
+
+    // Groups of open tasks whose titles look alike, each in id order and the groups
+    // by their first id. Likeness chains: A like B and B like C puts all three together.
+    pub fn similar_clusters(&self) -> Vec<Vec<&Task>> {
+        let open: Vec<&Task> = self.open_tasks().collect();
+        // Each task's group, as the index of a task in it; merged by pointing one group's root at the other's
+        let mut group: Vec<usize> = (0..open.len()).collect();
+        fn root(group: &mut [usize], mut at: usize) -> usize {
+            while group[at] != at {
+                group[at] = group[group[at]];
+                at = group[at];
+            }
+            at
+        }
+        for i in 0..open.len() {
+            for j in i + 1..open.len() {
+                if search::is_similar(&open[i].title, &open[j].title) {
+                    let (a, b) = (root(&mut group, i), root(&mut group, j));
+                    group[a.max(b)] = a.min(b);
+                }
+            }
+        }
+        let mut clusters: BTreeMap<usize, Vec<&Task>> = BTreeMap::new();
+        for (i, task) in open.iter().enumerate() {
+            clusters.entry(root(&mut group, i)).or_default().push(task);
+        }
+        clusters.into_values().filter(|cluster| cluster.len() > 1).collect()
+    }
+
+    fn open_tasks(&self) -> impl Iterator<Item = &Task> {
+        self.tasks.values().filter(|task| task.status != TaskStatus::Completed)
+    }
+
+    // Record time worked on the task
+    pub fn log_session(&mut self, id: u32, session: WorkSession) -> Result<(), TaskError> {
+        let task = self.get_task_mut(id)?;
+        task.sessions.push(session);
+        task.touch();
+        self.emit(TaskEvent::Edited(id));
+        Ok(())
+    }
+
+    // Time tracked within `range`, summed per tag or per project (the top task of
+    // each parent chain), largest first. A task with several tags counts in full
+    // under each of them.
+    pub fn time_report(&self, group: GroupKey, range: DateRange) -> Vec<(String, Duration)> {
+        let by_uid: HashMap<&str, &Task> = match group {
+            GroupKey::Project => self.tasks.values().map(|task| (task.uid.as_str(), task)).collect(),
+            GroupKey::Tag => HashMap::new(),
+        };
+        let mut totals: HashMap<String, Duration> = HashMap::new();
+        for task in self.tasks.values() {
+            let time = tracked_in(task, range);
+            if time.is_zero() {
+                continue;
+            }
+            let groups = match group {
+                GroupKey::Tag if task.tags.is_empty() => vec![UNTAGGED.to_string()],
+                GroupKey::Tag => {
+                    let tags: BTreeSet<String> = task.tags.iter().map(|tag| normalize_tag(tag)).collect();
+                    tags.into_iter().collect()
+                }
+                GroupKey::Project => vec![project_of(task, &by_uid).title.clone()],
+            };
+            for name in groups {
+                *totals.entry(name).or_default() += time;
+            }
+        }
+        let mut rows: Vec<(String, Duration)> = totals.into_iter().collect();
+        rows.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        rows
+    }
+
+    // Time tracked within `range` on all tasks, each counted once
+    pub fn time_total(&self, range: DateRange) -> Duration {
+        self.tasks.values().map(|task| tracked_in(task, range)).sum()
+    }
+
+    /// Change the status as the workflow allows.
+    ///
+    /// ```
+    /// # use task_manager::{Priority, TaskManager, TaskStatus};
+    /// let mut manager = TaskManager::new();
+    /// let id = manager.add_task("Ship".to_string(), String::new(), Priority::Low).unwrap();
+    /// manager.update_task_status(id, TaskStatus::InProgress).unwrap();
+    /// assert_eq!(manager.get_task(id).unwrap().status(), &TaskStatus::InProgress);
+    /// ```
+    pub fn update_task_status(&mut self, id: u32, status: TaskStatus) -> Result<(), TaskError> {
+        self.update_task_status_noted(id, status, None)
+    }
+
+    // Change the status as the workflow allows, adding `note` to the description
+    // first; some moves, such as reopening, can be set to need one
+    pub fn update_task_status_noted(&mut self, id: u32, status: TaskStatus, note: Option<&str>) -> Result<(), TaskError> {
+        self.workflow.check(self.get_task(id)?, &status, note)?;
+        // Reopening is the way to change a completed task, so the guard lets it through
+        match status {
+            TaskStatus::Completed => self.before_change(id)?,
+            _ => self.remember_before(id),
+        }
+        let task = self.tasks.get_mut(&id).ok_or(TaskError::TaskNotFound { id })?;
+        let note = note.map(str::trim).filter(|note| !note.is_empty());
+        if let Some(note) = note {
+            task.description = if task.description.is_empty() { note.to_string() } else { format!("{}\n{}", task.description, note) };
+        }
+        let from = task.status.clone();
+        self.counts.remove(task);
+        task.update_status(status.clone());
+        self.counts.add(task);
+        debug!("task {} status {:?} -> {:?}", id, from, status);
+        if note.is_some() {
+            self.emit(TaskEvent::Edited(id));
+        }
+        self.emit(TaskEvent::StatusChanged { id, from, to: status });
+        Ok(())
+    }
+
+    // Set a custom field, or remove it when `value` is None
+    pub fn set_field(&mut self, id: u32, key: &str, value: Option<String>) -> Result<(), TaskError> {
+        if !valid_field_key(key) {
+            return Err(TaskError::Invalid {
+                reason: format!("field key '{}' must be lowercase letters, digits and dashes", key),
+            });
+        }
+        let task = self.get_task_mut(id)?;
+        match value {
+            Some(value) => {
+                if !task.fields.contains_key(key) && task.fields.len() >= MAX_FIELDS {
+                    return Err(TaskError::Invalid { reason: format!("a task holds at most {} fields", MAX_FIELDS) });
+                }
+                task.fields.insert(key.to_string(), value);
+            }
+            None => {
+                task.fields.remove(key);
+            }
+        }
+        task.touch();
+        self.emit(TaskEvent::Edited(id));
+        Ok(())
+    }
+
+    /// Tag a task; a tag it already carries is left alone.
+    ///
+    /// ```
+    /// # use task_manager::{Priority, TaskManager};
+    /// let mut manager = TaskManager::new();
+    /// let id = manager.add_task("Ship".to_string(), String::new(), Priority::Low).unwrap();
+    /// manager.add_tag_to_task(id, "release".to_string()).unwrap();
+    /// assert_eq!(manager.get_task(id).unwrap().tags().collect::<Vec<_>>(), ["release"]);
+    /// ```
+    pub fn add_tag_to_task(&mut self, id: u32, tag: String) -> Result<(), TaskError> {
+        let tags = self.get_task(id)?.tags.len();
+        self.check_tag(&tag)?;
+        let interned = self.tag_names.intern(&tag);
+        let carried = self.tasks[&id].tags.contains(&interned);
+        if let Some(over) = self.limits.extra_tag(tags).filter(|_| !carried) {
+            if !self.truncate_to_limits {
+                return Err(TaskError::ValidationFailed(vec![over]));
+            }
+            self.truncations.push(format!("task {}: dropped tag {} (limits.tags_per_task)", id, tag));
+            return Ok(());
+        }
+        if self.get_task_mut(id)?.add_tag(interned) {
+            self.tag_index.entry(normalize_tag(&tag)).or_default().insert(id);
+            debug!("tagged task {} with '{}'", id, tag);
+            self.emit(TaskEvent::Tagged { id, tag });
+        }
+        Ok(())
+    }
+
+    /// ```
+    /// # use task_manager::{Priority, TaskManager};
+    /// let mut manager = TaskManager::new();
+    /// let id = manager.add_task("Gone".to_string(), String::new(), Priority::Low).unwrap();
+    /// manager.delete_task(id).unwrap();
+    /// assert!(manager.get_task(id).is_err());
+    /// ```
+    pub fn delete_task(&mut self, id: u32) -> Result<(), TaskError> {
+        self.check_owner(id)?;
+        self.remember_before(id);
+        let task = self.tasks.remove(&id).ok_or(TaskError::TaskNotFound { id })?;
+        self.counts.remove(&task);
+        self.unindex_title(&normalize_title(&task.title), id);
+        self.unindex_tags(id, &task.tags);
+        debug!("deleted task {}", id);
+        self.emit(TaskEvent::Deleted(id));
+        // No task keeps a link to one that is gone
+        let linked: Vec<u32> =
+            self.tasks.values().filter(|other| other.relates_to.contains(&id)).map(|other| other.id).collect();
+        for other in linked {
+            self.remember_before(other);
+            let other_task = self.tasks.get_mut(&other).expect("read from the map");
+            other_task.relates_to.retain(|&linked| linked != id);
+            other_task.touch();
+            self.emit(TaskEvent::Edited(other));
+        }
+        Ok(())
+    }
+
+    pub fn find_by_title(&self, title: &str) -> Option<&Task> {
+        self.title_index
+            .get(&normalize_title(title))
+            .and_then(|id| self.tasks.get(id))
+    }
+
+    fn build_tag_index(&self) -> HashMap<String, BTreeSet<u32>> {
+        let mut index: HashMap<String, BTreeSet<u32>> = HashMap::new();
+        for task in self.tasks.values() {
+            for tag in &task.tags {
+                index.entry(normalize_tag(tag)).or_default().insert(task.id);
+            }
+        }
+        index
+    }
+
+    fn build_title_index(&self) -> HashMap<String, u32> {
+        self.tasks
+            .values()
+            .map(|task| (normalize_title(&task.title), task.id))
+            .collect()
+    }
+
+    // Drop `id` from the title index. A hand-edited file can hold twins whose
+    // titles differ only in case; the key then passes to the highest surviving
+    // one, as build_title_index would pick.
+    fn unindex_title(&mut self, key: &str, id: u32) {
+        if self.title_index.get(key) != Some(&id) {
+            return;
+        }
+        self.title_index.remove(key);
+        let twin = self.tasks.values().rev().find(|task| task.id != id && normalize_title(&task.title) == key);
+        if let Some(twin) = twin.map(|task| task.id) {
+            self.title_index.insert(key.to_string(), twin);
+        }
+    }
+
+    fn unindex_tags(&mut self, id: u32, tags: &[Arc<str>]) {
+        for tag in tags {
+            let key = normalize_tag(tag);
+            if let Some(ids) = self.tag_index.get_mut(&key) {
+                ids.remove(&id);
+                if ids.is_empty() {
+                    self.tag_index.remove(&key);
+                }
+            }
+        }
+    }
+
+    pub fn verify_title_index(&self) -> Result<usize, String> {
+        let rebuilt = self.build_title_index();
+        if rebuilt == self.title_index {
+            Ok(rebuilt.len())
+        } else {
+            Err(format!(
+                "title index out of sync: {} indexed, {} expected",
+                self.title_index.len(),
+                rebuilt.len()
+            ))
+        }
+    }
+
+    // (distinct tags, task ids filed under them, titles), for `debug info`
+    pub fn index_sizes(&self) -> (usize, usize, usize) {
+        let tagged = self.tag_index.values().map(BTreeSet::len).sum();
+        (self.tag_index.len(), tagged, self.title_index.len())
+    }
+
+    // Check every maintained index; returns (tag count, title count)
+    pub fn verify_indexes(&self) -> Result<(usize, usize), String> {
+        Ok((self.verify_tag_index()?, self.verify_title_index()?))
+    }
+
+    // Compare the maintained tag index against one rebuilt from scratch
+    pub fn verify_tag_index(&self) -> Result<usize, String> {
+        let rebuilt = self.build_tag_index();
+        if rebuilt == self.tag_index {
+            return Ok(rebuilt.len());
+        }
+        let mut tags: Vec<&String> = rebuilt.keys().chain(self.tag_index.keys()).collect();
+        tags.sort();
+        tags.dedup();
+        let mismatched: Vec<String> = tags
+            .into_iter()
+            .filter(|tag| rebuilt.get(*tag) != self.tag_index.get(*tag))
+            .cloned()
+            .collect();
+        Err(format!("tag index out of sync for: {}", mismatched.join(", ")))
+    }
+
+    // Tasks carrying every one of the given tags (case-insensitive)
+    pub fn get_tasks_by_tags(&self, tags: &[String]) -> Vec<&Task> {
+        let mut sets = Vec::with_capacity(tags.len());
+        for tag in tags {
+            match self.tag_index.get(&normalize_tag(tag)) {
+                Some(set) => sets.push(set),
+                None => return Vec::new(),
+            }
+        }
+        // Walk the rarest tag and check the others, without copying any set
+        sets.sort_by_key(|set| set.len());
+        let Some((smallest, rest)) = sets.split_first() else { return Vec::new() };
+        smallest
+            .iter()
+            .filter(|id| rest.iter().all(|set| set.contains(id)))
+            .filter_map(|id| self.tasks.get(id))
+            .collect()
+    }
+
+    /// Every task, in id order.
+    ///
+    /// ```
+    /// # use task_manager::{Priority, TaskManager};
+    /// let mut manager = TaskManager::new();
+    /// manager.add_task("B".to_string(), String::new(), Priority::Low).unwrap();
+    /// manager.add_task("A".to_string(), String::new(), Priority::Low).unwrap();
+    /// let titles: Vec<&str> = manager.list_tasks().iter().map(|task| task.title()).collect();
+    /// assert_eq!(titles, ["B", "A"]);
+    /// ```
+    pub fn list_tasks(&self) -> Vec<&Task> {
+        self.tasks.values().collect()
+    }
+
+    // Highest priority first, ties in id order. The order is sorted once and reused
+    // until the next change.
+    pub fn tasks_by_priority(&self) -> Vec<&Task> {
+        let ids = {
+            let mut cached = self.priority_view.lock().unwrap_or_else(PoisonError::into_inner);
+            match &*cached {
+                Some((generation, ids)) if *generation == self.generation => Arc::clone(ids),
+                _ => {
+                    let mut tasks = self.list_tasks();
+                    tasks.sort_by(|a, b| b.priority.cmp(&a.priority).then_with(|| a.id.cmp(&b.id)));
+                    let ids: Arc<[u32]> = tasks.iter().map(|task| task.id).collect();
+                    *cached = Some((self.generation, Arc::clone(&ids)));
+                    ids
+                }
+            }
+        };
+        ids.iter().map(|id| &self.tasks[id]).collect()
+    }
+
+    // Changes since the manager was created; equal values mean nothing changed in between
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    pub fn tasks_in_id_range(&self, range: impl RangeBounds<u32>) -> Vec<&Task> {
+        self.tasks.range(range).map(|(_, task)| task).collect()
+    }
+
+    /// Tasks with the text in their title, description, a tag or a field.
+    ///
+    /// ```
+    /// # use task_manager::{Priority, TaskManager};
+    /// let mut manager = TaskManager::new();
+    /// manager.add_task("Renew passport".to_string(), String::new(), Priority::Low).unwrap();
+    /// assert_eq!(manager.filter_tasks("PASSPORT").len(), 1);
+    /// ```
+    pub fn filter_tasks(&self, filter: &str) -> Vec<&Task> {
+        self.find(&TaskFilter::keyword(filter))
+    }
+
+    /// Tasks matching every criterion of the filter, in id order. Long candidate
+    /// lists are split across threads when built with the `parallel` feature.
+    ///
+    /// ```
+    /// # use task_manager::{Priority, TaskFilter, TaskManager};
+    /// let mut manager = TaskManager::new();
+    /// let id = manager.add_task("Deploy".to_string(), String::new(), Priority::High).unwrap();
+    /// manager.add_tag_to_task(id, "work".to_string()).unwrap();
+    /// let filter = TaskFilter::parse("tag:work priority:high").unwrap();
+    /// assert_eq!(manager.find(&filter).len(), 1);
+    /// ```
+    pub fn find(&self, filter: &TaskFilter) -> Vec<&Task> {
+        let filter = &*self.for_user(filter);
+        let candidates = self.candidates(filter);
+        let keyword = self.folded_keyword(filter);
+        #[cfg(feature = "parallel")]
+        if candidates.len() >= PARALLEL_THRESHOLD {
+            return find_in_parallel(candidates, filter, keyword.as_ref());
+        }
+        find_in_sequence(candidates, filter, keyword.as_ref())
+    }
+
+    // find, with each task's relevance to the filter's keyword, in id order
+    pub fn search(&self, filter: &TaskFilter) -> Vec<(u32, &Task)> {
+        let Some((words, index)) = self.folded_keyword(filter) else {
+            return self.find(filter).into_iter().map(|task| (0, task)).collect();
+        };
+        self.find(filter).into_iter().map(|task| (search::score(&index[&task.id], &words), task)).collect()
+    }
+
+    // find, always on the thread pool whatever the list size
+    #[cfg(feature = "parallel")]
+    pub fn find_par(&self, filter: &TaskFilter) -> Vec<&Task> {
+        let filter = &*self.for_user(filter);
+        find_in_parallel(self.candidates(filter), filter, self.folded_keyword(filter).as_ref())
+    }
+
+    // The filter with its `mine` clause naming this manager's user
+    fn for_user<'a>(&self, filter: &'a TaskFilter) -> Cow<'a, TaskFilter> {
+        match (filter.owner.as_deref(), &self.user) {
+            (Some(ME), Some(user)) => Cow::Owned(TaskFilter { owner: Some(user.clone()), ..filter.clone() }),
+            _ => Cow::Borrowed(filter),
+        }
+    }
+
+    // The filter's keyword folded the way the search text is, with the text to look it up in
+    fn folded_keyword(&self, filter: &TaskFilter) -> Option<(String, SearchIndex)> {
+        let keyword = filter.keyword.as_deref()?;
+        Some((search::fold(keyword, self.ignore_accents), self.search_index()))
+    }
+
+    // Every task's search text, folded once and reused until the next change
+    fn search_index(&self) -> SearchIndex {
+        let mut cached = self.search_index.lock().unwrap_or_else(PoisonError::into_inner);
+        match &*cached {
+            Some((generation, index)) if *generation == self.generation => Arc::clone(index),
+            _ => {
+                let index: SearchIndex = Arc::new(
+                    self.tasks.values().map(|task| (task.id, SearchText::new(task, self.ignore_accents))).collect(),
+                );
+                *cached = Some((self.generation, Arc::clone(&index)));
+                index
+            }
+        }
+    }
+
+    // Tags narrow the search through the index before anything else is checked
+    fn candidates(&self, filter: &TaskFilter) -> Vec<&Task> {
+        if filter.tags.is_empty() {
+            self.list_tasks()
+        } else {
+            self.get_tasks_by_tags(&filter.tags)
+        }
+    }
+
+    pub fn get_tasks_by_priority(&self, priority: Priority) -> Vec<&Task> {
+        self.tasks.values()
+            .filter(|task| task.priority == priority)
+            .collect()
+    }
+
+    pub fn get_tasks_by_status(&self, status: TaskStatus) -> Vec<&Task> {
+        self.tasks.values()
+            .filter(|task| task.status == status)
+            .collect()
+    }
+
+    // Delete every completed task, returning the removed ids
+    pub fn clear_completed(&mut self) -> Vec<u32> {
+        let mut ids: Vec<u32> = self
+            .get_tasks_by_status(TaskStatus::Completed)
+            .iter()
+            .map(|task| task.id)
+            .collect();
+        // The ids were just read from the map, so only a protected task stays
+        ids.retain(|&id| self.delete_task(id).is_ok());
+        ids
+    }
+
+    // get_statistics per creator, by name, with tasks nobody is recorded as creating last
+    pub fn stats_by_creator(&self) -> Vec<CreatorStats> {
+        let mut by_user: BTreeMap<(bool, Option<&str>), CreatorStats> = BTreeMap::new();
+        for task in self.tasks.values() {
+            let user = task.created_by.as_deref();
+            let stats = by_user
+                .entry((user.is_none(), user))
+                .or_insert_with(|| CreatorStats { user: user.map(str::to_string), ..CreatorStats::default() });
+            stats.total += 1;
+            match task.status {
+                TaskStatus::Completed => stats.completed += 1,
+                TaskStatus::InProgress => stats.in_progress += 1,
+                TaskStatus::Pending => stats.pending += 1,
+            }
+        }
+        by_user.into_values().collect()
+    }
+
+    /// (total, completed, in progress, pending), read from the maintained counters.
+    ///
+    /// ```
+    /// # use task_manager::TaskManager;
+    /// assert_eq!(TaskManager::new().get_statistics(), (0, 0, 0, 0));
+    /// ```
+    pub fn get_statistics(&self) -> (usize, usize, usize, usize) {
+        debug_assert_eq!(self.counts, TaskCounts::tally(self.tasks.values()), "task counters drifted");
+        let [pending, in_progress, completed] = self.counts.by_status;
+        (self.tasks.len(), completed, in_progress, pending)
+    }
+
+    pub fn count_with_priority(&self, priority: Priority) -> usize {
+        self.counts.by_priority[priority_slot(&priority)]
+    }
+}
+
+impl Default for TaskManager {
+    fn default() -> Self {
+        TaskManager::new()
+    }
+}
+
+fn find_in_sequence<'a>(
+    candidates: Vec<&'a Task>,
+    filter: &TaskFilter,
+    keyword: Option<&(String, SearchIndex)>,
+) -> Vec<&'a Task> {
+    candidates.into_iter().filter(|task| keeps(filter, task, keyword)).collect()
+}
+
+// A candidate passes every criterion but the tags, which chose the candidates
+fn keeps(filter: &TaskFilter, task: &Task, keyword: Option<&(String, SearchIndex)>) -> bool {
+    filter.matches_fields(task) && keyword.is_none_or(|(words, index)| index[&task.id].contains(words))
+}
+
+// Rayon splits the candidates into chunks and collects the matches back in their
+// original order, which is id order
+#[cfg(feature = "parallel")]
+fn find_in_parallel<'a>(
+    candidates: Vec<&'a Task>,
+    filter: &TaskFilter,
+    keyword: Option<&(String, SearchIndex)>,
+) -> Vec<&'a Task> {
+    use rayon::prelude::*;
+
+    candidates.into_par_iter().filter(|task| keeps(filter, task, keyword)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Priority, SortKey, TaskStatus};
+
+    #[test]
+    fn test_task_manager_add_task() {
+        let mut manager = TaskManager::new();
+        let result = manager.add_task("Test".to_string(), "Description".to_string(), Priority::Low);
+        assert!(result.is_ok());
+        assert_eq!(result.unwrap(), 1);
+    }
+
+    #[test]
+    fn test_bulk_add_reports_each_task() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let mut manager = TaskManager::new();
+        manager.add_task("Existing".to_string(), String::new(), Priority::Low).unwrap();
+        let added = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&added);
+        manager.subscribe(Box::new(move |event, _| {
+            if matches!(event, TaskEvent::Added(_)) {
+                counter.fetch_add(1, Ordering::SeqCst);
+            }
+        }));
+
+        let task = |title: &str| Task::new(99, title.to_string(), String::new(), Priority::High);
+        let result = manager.add_tasks_bulk(vec![task("One"), task(" existing "), task("Two"), task("ONE")]);
+        assert_eq!(result.created(), vec![2, 3]);
+        assert!(matches!(result.outcomes[1], Err(TaskError::DuplicateTask { existing_id: 1, .. })));
+        assert!(matches!(result.outcomes[3], Err(TaskError::DuplicateTask { existing_id: 2, .. })));
+        assert_eq!(added.load(Ordering::SeqCst), 2);
+        assert_eq!(manager.count_with_priority(Priority::High), 2);
+        assert_eq!(manager.verify_indexes(), Ok((0, 3)));
+    }
+
+    #[test]
+    fn test_duplicate_task_error() {
+        let mut manager = TaskManager::new();
+        manager.add_task("Test".to_string(), "Description".to_string(), Priority::Low).unwrap();
+        let result = manager.add_task("Test".to_string(), "Another Description".to_string(), Priority::High);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_task_filtering() {
+        let mut manager = TaskManager::new();
+        manager.add_task("Buy groceries".to_string(), "Milk and bread".to_string(), Priority::Medium).unwrap();
+        manager.add_task("Walk dog".to_string(), "Morning walk".to_string(), Priority::Low).unwrap();
+        
+        let filtered = manager.filter_tasks("dog");
+        assert_eq!(filtered.len(), 1);
+        assert_eq!(filtered[0].title, "Walk dog");
+    }
+
+    #[test]
+    fn test_find_combines_criteria() {
+        let mut manager = TaskManager::new();
+        let a = manager.add_task("Deploy API".to_string(), String::new(), Priority::High).unwrap();
+        let b = manager.add_task("Deploy docs".to_string(), String::new(), Priority::High).unwrap();
+        manager.add_task("Review API".to_string(), String::new(), Priority::Low).unwrap();
+        for id in [a, b] {
+            manager.add_tag_to_task(id, "Release".to_string()).unwrap();
+        }
+        manager.update_task_status(b, TaskStatus::InProgress).unwrap();
+
+        let filter = TaskFilter {
+            tags: vec!["release".to_string()],
+            keyword: Some("DEPLOY".to_string()),
+            ..TaskFilter::default()
+        };
+        let ids = |filter: &TaskFilter| manager.find(filter).iter().map(|task| task.id).collect::<Vec<u32>>();
+        assert_eq!(ids(&filter), vec![a, b]);
+        assert_eq!(ids(&TaskFilter { status: Some(TaskStatus::Pending), ..filter.clone() }), vec![a]);
+        assert_eq!(ids(&TaskFilter { priority: Some(Priority::Low), ..TaskFilter::default() }), vec![3]);
+        assert_eq!(ids(&TaskFilter { tags: vec!["missing".to_string()], ..TaskFilter::default() }), Vec::<u32>::new());
+        assert_eq!(ids(&TaskFilter::default()).len(), 3);
+    }
+
+    #[test]
+    fn test_filter_folds_accents_unless_turned_off() {
+        let mut manager = TaskManager::new();
+        let id = manager.add_task("Über dependency cleanup".to_string(), String::new(), Priority::Low).unwrap();
+        let ids = |manager: &TaskManager, words: &str| manager.filter_tasks(words).iter().map(|task| task.id).collect::<Vec<u32>>();
+        assert_eq!(ids(&manager, "uber"), vec![id]);
+        // The cached search text follows an edit
+        manager.apply_edit(id, TaskEdit { title: Some("Straße café".to_string()), ..TaskEdit::default() }).unwrap();
+        assert_eq!(ids(&manager, "uber"), Vec::<u32>::new());
+        assert_eq!(ids(&manager, "STRASSE CAFE"), vec![id]);
+
+        manager.set_ignore_accents(false);
+        assert_eq!(ids(&manager, "strasse cafe"), Vec::<u32>::new());
+        assert_eq!(ids(&manager, "STRASSE CAFÉ"), vec![id]);
+        let hits = manager.search(&TaskFilter::keyword("straße café"));
+        assert_eq!(hits.iter().map(|(score, task)| (*score, task.id)).collect::<Vec<_>>(), vec![(100, id)]);
+    }
+
+    #[test]
+    fn test_similar_open_tasks_and_clusters() {
+        let mut manager = TaskManager::new();
+        let mut add = |title: &str| manager.add_task(title.to_string(), String::new(), Priority::Low).unwrap();
+        let login = add("Fix login bug");
+        let milk = add("Buy milk");
+        let the_login = add("fix the login bug");
+        let typo = add("Fix the login bgu");
+        let done = add("Buy milk!");
+        let eggs = add("Buy eggs");
+        manager.update_task_status(done, TaskStatus::Completed).unwrap();
+
+        let ids = |tasks: Vec<&Task>| tasks.iter().map(|task| task.id).collect::<Vec<u32>>();
+        assert_eq!(ids(manager.similar_open_tasks(the_login).unwrap()), vec![typo, login]);
+        // Completed tasks are not suggested
+        assert_eq!(ids(manager.similar_open_tasks(milk).unwrap()), Vec::<u32>::new());
+        assert!(manager.similar_open_tasks(99).is_err());
+
+        let clusters: Vec<Vec<u32>> = manager.similar_clusters().into_iter().map(ids).collect();
+        assert_eq!(clusters, vec![vec![login, the_login, typo]]);
+        assert!(!clusters.iter().flatten().any(|id| [milk, eggs].contains(id)));
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn test_parallel_find_matches_sequential() {
+        // A fixed-seed generator keeps the dataset the same from run to run
+        let mut seed: u64 = 0x2545_f491_4f6c_dd1d;
+        let mut next = move |bound: u64| {
+            seed ^= seed << 13;
+            seed ^= seed >> 7;
+            seed ^= seed << 17;
+            seed % bound
+        };
+        let words = ["deploy", "Review", "ÉTÉ", "invoice", "ΟΔΟΣ", "backup", "Straße", "meeting"];
+        let statuses = [TaskStatus::Pending, TaskStatus::InProgress, TaskStatus::Completed];
+        let priorities = [Priority::Low, Priority::Medium, Priority::High, Priority::Critical];
+        let tasks = (1..=30_000)
+            .map(|id| {
+                let mut word = || words[next(words.len() as u64) as usize];
+                let (title, description) = (format!("{} {}", word(), word()), format!("{} notes", word()));
+                let mut task = Task::new(id, title, description, priorities[next(4) as usize].clone());
+                task.status = statuses[next(3) as usize].clone();
+                task.tags = (0..next(3)).map(|_| format!("tag{}", next(5)).into()).collect();
+                task
+            })
+            .collect();
+        let manager = TaskManager::from_state(SavedState { tasks, next_id: 30_001, ..SavedState::default() });
+
+        let filters = [
+            TaskFilter::default(),
+            TaskFilter::keyword("deploy"),
+            TaskFilter::keyword("οδος"),
+            TaskFilter { status: Some(TaskStatus::InProgress), keyword: Some("été".to_string()), ..TaskFilter::default() },
+            TaskFilter { priority: Some(Priority::Critical), tags: vec!["TAG1".to_string()], ..TaskFilter::default() },
+            TaskFilter { tags: vec!["tag2".to_string(), "tag4".to_string()], ..TaskFilter::default() },
+        ];
+        for filter in &filters {
+            let parallel = serde_json::to_string(&manager.find_par(filter)).unwrap();
+            let sequential = serde_json::to_string(&find_in_sequence(manager.candidates(filter), filter, manager.folded_keyword(filter).as_ref())).unwrap();
+            assert_eq!(parallel, sequential, "{:?}", filter);
+        }
+        assert!(manager.find_par(&filters[2]).len() > 1000);
+    }
+
+    #[test]
+    fn test_identical_tags_share_one_allocation() {
+        let mut manager = TaskManager::new();
+        let a = manager.add_task("Fix login".to_string(), String::new(), Priority::High).unwrap();
+        manager.add_tag_to_task(a, "backend".to_string()).unwrap();
+        let mut imported = Task::new(0, "Fix logout".to_string(), String::new(), Priority::Low);
+        imported.add_tag("backend".to_string());
+        let b = manager.create_task(imported).unwrap();
+        let tag = |manager: &TaskManager, id| Arc::clone(&manager.get_task(id).unwrap().tags[0]);
+        assert!(Arc::ptr_eq(&tag(&manager, a), &tag(&manager, b)));
+
+        // Still plain strings on disk, and shared again after loading
+        let json = serde_json::to_string(&manager.to_state()).unwrap();
+        assert!(json.contains(r#""tags":["backend"]"#));
+        let reloaded = TaskManager::from_state(serde_json::from_str(&json).unwrap());
+        assert!(Arc::ptr_eq(&tag(&reloaded, a), &tag(&reloaded, b)));
+        // The interner, the two tasks and the handle just taken
+        assert_eq!(Arc::strong_count(&tag(&reloaded, a)), 4);
+    }
+
+    #[test]
+    fn test_counters_match_brute_force_through_random_mutations() {
+        let mut seed: u64 = 0x9e37_79b9_7f4a_7c15;
+        let mut next = move |bound: u64| {
+            seed ^= seed << 13;
+            seed ^= seed >> 7;
+            seed ^= seed << 17;
+            seed % bound
+        };
+        let statuses = [TaskStatus::Pending, TaskStatus::InProgress, TaskStatus::Completed];
+        let priorities = [Priority::Low, Priority::Medium, Priority::High, Priority::Critical];
+        let mut manager = TaskManager::new();
+        let mut snapshot = manager.snapshot();
+
+        for step in 0..5_000 {
+            let ids: Vec<u32> = manager.list_tasks().iter().map(|task| task.id).collect();
+            let id = if ids.is_empty() { 0 } else { ids[next(ids.len() as u64) as usize] };
+            match next(10) {
+                0..=2 => {
+                    let priority = priorities[next(4) as usize].clone();
+                    manager.add_task(format!("Task {}", step), String::new(), priority).unwrap();
+                }
+                3 | 4 => {
+                    let _ = manager.update_task_status(id, statuses[next(3) as usize].clone());
+                }
+                5 => {
+                    let edit = TaskEdit { priority: Some(priorities[next(4) as usize].clone()), ..TaskEdit::default() };
+                    let _ = manager.apply_edit(id, edit);
+                }
+                6 => {
+                    let _ = manager.delete_task(id);
+                }
+                7 => {
+                    if let Ok(task) = manager.get_task(id) {
+                        let mut copy = task.clone();
+                        copy.status = statuses[next(3) as usize].clone();
+                        copy.priority = priorities[next(4) as usize].clone();
+                        manager.upsert_task(copy).unwrap();
+                    }
+                }
+                8 if next(20) == 0 => {
+                    manager.clear_completed();
+                }
+                8 => snapshot = manager.snapshot(),
+                _ if next(10) == 0 => manager.restore(snapshot.clone()),
+                _ => {}
+            }
+
+            let tasks = manager.list_tasks();
+            let with_status = |status: TaskStatus| tasks.iter().filter(|task| task.status == status).count();
+            let expected = (
+                tasks.len(),
+                with_status(TaskStatus::Completed),
+                with_status(TaskStatus::InProgress),
+                with_status(TaskStatus::Pending),
+            );
+            assert_eq!(manager.get_statistics(), expected, "after step {}", step);
+            for priority in &priorities {
+                let count = tasks.iter().filter(|task| task.priority == *priority).count();
+                assert_eq!(manager.count_with_priority(priority.clone()), count, "after step {}", step);
+            }
+        }
+    }
+
+    #[test]
+    fn test_cached_priority_view_matches_a_fresh_sort() {
+        let mut seed: u64 = 0x2545_f491_4f6c_dd1d;
+        let mut next = move |bound: u64| {
+            seed ^= seed << 13;
+            seed ^= seed >> 7;
+            seed ^= seed << 17;
+            seed % bound
+        };
+        let priorities = [Priority::Low, Priority::Medium, Priority::High, Priority::Critical];
+        let mut manager = TaskManager::new();
+        let mut snapshot = manager.snapshot();
+
+        for step in 0..3_000 {
+            let ids: Vec<u32> = manager.list_tasks().iter().map(|task| task.id).collect();
+            let id = if ids.is_empty() { 0 } else { ids[next(ids.len() as u64) as usize] };
+            let before = manager.generation();
+            match next(8) {
+                0..=2 => {
+                    let priority = priorities[next(4) as usize].clone();
+                    manager.add_task(format!("Task {}", step), String::new(), priority).unwrap();
+                }
+                3 => {
+                    let edit = TaskEdit { priority: Some(priorities[next(4) as usize].clone()), ..TaskEdit::default() };
+                    let _ = manager.apply_edit(id, edit);
+                }
+                4 => {
+                    let _ = manager.delete_task(id);
+                }
+                5 => {
+                    if let Ok(task) = manager.get_task(id) {
+                        let mut copy = task.clone();
+                        copy.priority = priorities[next(4) as usize].clone();
+                        manager.upsert_task(copy).unwrap();
+                    }
+                }
+                6 => snapshot = manager.snapshot(),
+                _ => manager.restore(snapshot.clone()),
+            }
+            if manager.list_tasks().iter().map(|task| task.id).ne(ids.iter().copied()) {
+                assert_ne!(manager.generation(), before, "after step {}", step);
+            }
+
+            let mut expected = manager.list_tasks();
+            expected.sort_by(|a, b| b.priority.cmp(&a.priority).then_with(|| a.id.cmp(&b.id)));
+            let expected: Vec<(u32, Priority)> = expected.iter().map(|task| (task.id, task.priority.clone())).collect();
+            // Read twice: the second answer comes from the cache
+            for _ in 0..2 {
+                let view: Vec<(u32, Priority)> =
+                    manager.tasks_by_priority().iter().map(|task| (task.id, task.priority.clone())).collect();
+                assert_eq!(view, expected, "after step {}", step);
+            }
+            let listed: Vec<u32> = manager.list_tasks().iter().map(|task| task.id).collect();
+            assert!(listed.is_sorted(), "after step {}", step);
+        }
+    }
+
+    #[test]
+    fn test_tag_index_tracks_mutations() {
+        let mut manager = TaskManager::new();
+        let a = manager.add_task("A".to_string(), String::new(), Priority::Low).unwrap();
+        let b = manager.add_task("B".to_string(), String::new(), Priority::Low).unwrap();
+        manager.add_tag_to_task(a, "Backend".to_string()).unwrap();
+        manager.add_tag_to_task(a, "urgent".to_string()).unwrap();
+        manager.add_tag_to_task(b, "backend".to_string()).unwrap();
+        assert!(manager.verify_tag_index().is_ok());
+
+        let ids = |tasks: Vec<&Task>| tasks.iter().map(|t| t.id).collect::<Vec<u32>>();
+        assert_eq!(ids(manager.get_tasks_by_tags(&["BACKEND".to_string()])), vec![a, b]);
+        assert_eq!(ids(manager.get_tasks_by_tags(&["backend".to_string(), "urgent".to_string()])), vec![a]);
+        assert!(manager.get_tasks_by_tags(&["missing".to_string()]).is_empty());
+
+        manager.delete_task(a).unwrap();
+        assert_eq!(manager.verify_tag_index(), Ok(1));
+        assert!(manager.get_tasks_by_tags(&["urgent".to_string()]).is_empty());
+
+        // Loading rebuilds the index automatically
+        let reloaded = TaskManager::from_state(manager.to_state());
+        assert_eq!(reloaded.verify_tag_index(), Ok(1));
+        assert_eq!(ids(reloaded.get_tasks_by_tags(&["backend".to_string()])), vec![b]);
+    }
+
+    #[test]
+    fn test_duplicate_detection_is_case_insensitive_and_reports_id() {
+        let mut manager = TaskManager::new();
+        let id = manager.add_task("Buy milk".to_string(), String::new(), Priority::Low).unwrap();
+        match manager.add_task("  buy MILK ".to_string(), String::new(), Priority::Low) {
+            Err(TaskError::DuplicateTask { existing_id, .. }) => assert_eq!(existing_id, id),
+            other => panic!("expected DuplicateTask, got {:?}", other),
+        }
+        assert_eq!(manager.find_by_title("BUY MILK").map(|t| t.id), Some(id));
+        manager.delete_task(id).unwrap();
+        assert!(manager.find_by_title("buy milk").is_none());
+        assert!(manager.add_task("Buy milk".to_string(), String::new(), Priority::Low).is_ok());
+    }
+
+    #[test]
+    fn test_title_index_survives_add_delete_sequences() {
+        let mut manager = TaskManager::new();
+        let mut seed: u32 = 12345;
+        let mut next = || {
+            seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+            (seed >> 16) % 40
+        };
+        for _ in 0..500 {
+            let n = next();
+            let title = format!("Task {}", n % 20);
+            if n < 25 {
+                let _ = manager.add_task(title, String::new(), Priority::Medium);
+            } else if let Some(id) = manager.find_by_title(&title).map(|t| t.id) {
+                manager.delete_task(id).unwrap();
+            }
+            assert!(manager.verify_indexes().is_ok());
+        }
+        let reloaded = TaskManager::from_state(manager.to_state());
+        assert_eq!(reloaded.verify_title_index(), Ok(manager.list_tasks().len()));
+    }
+
+    #[test]
+    fn test_restore_undoes_everything_since_snapshot() {
+        let mut manager = TaskManager::new();
+        for i in 0..10 {
+            let id = manager.add_task(format!("Task {}", i), String::new(), Priority::Medium).unwrap();
+            manager.add_tag_to_task(id, format!("tag{}", i % 3)).unwrap();
+        }
+        manager.update_task_status(2, TaskStatus::Completed).unwrap();
+        let before = manager.to_state();
+        let snapshot = manager.snapshot();
+        assert_eq!(snapshot.len(), 10);
+
+        for id in 1..=5 {
+            manager.delete_task(id).unwrap();
+        }
+        manager.update_task_status(7, TaskStatus::Completed).unwrap();
+        manager.clear_completed();
+        manager.add_task("Task 1".to_string(), String::new(), Priority::Low).unwrap();
+        manager.add_tag_to_task(6, "extra".to_string()).unwrap();
+
+        manager.restore(snapshot);
+        assert_eq!(manager.to_state(), before);
+        assert!(manager.verify_indexes().is_ok());
+        assert_eq!(manager.find_by_title("task 3").map(|t| t.id), Some(4));
+        assert!(manager.get_tasks_by_tags(&["extra".to_string()]).is_empty());
+        assert_eq!(manager.add_task("New".to_string(), String::new(), Priority::Low).unwrap(), 11);
+    }
+
+    #[test]
+    fn test_apply_edit_updates_title_index() {
+        let mut manager = TaskManager::new();
+        let id = manager.add_task("Draft".to_string(), String::new(), Priority::Low).unwrap();
+        manager.add_task("Other".to_string(), String::new(), Priority::Low).unwrap();
+
+        let rename = |title: &str| TaskEdit { title: Some(title.to_string()), ..TaskEdit::default() };
+        assert!(matches!(manager.apply_edit(id, rename("other")), Err(TaskError::DuplicateTask { existing_id: 2, .. })));
+        manager.apply_edit(id, rename("DRAFT")).unwrap();
+        manager
+            .apply_edit(id, TaskEdit { title: Some("Final".to_string()), priority: Some(Priority::High), ..TaskEdit::default() })
+            .unwrap();
+
+        assert_eq!(manager.find_by_title("final").map(|t| t.id), Some(id));
+        assert!(manager.find_by_title("draft").is_none());
+        assert!(manager.verify_indexes().is_ok());
+        assert_eq!(manager.get_task(id).unwrap().priority, Priority::High);
+        assert!(matches!(manager.apply_edit(9, TaskEdit::default()), Err(TaskError::TaskNotFound { id: 9 })));
+    }
+
+    #[test]
+    fn test_views_are_ordered_by_id() {
+        let mut manager = TaskManager::new();
+        for i in 0..20 {
+            let priority = if i % 2 == 0 { Priority::High } else { Priority::Low };
+            manager.add_task(format!("report {}", i), String::new(), priority).unwrap();
+        }
+        manager.delete_task(5).unwrap();
+
+        let ids = |tasks: Vec<&Task>| tasks.iter().map(|t| t.id).collect::<Vec<u32>>();
+        let filtered = ids(manager.filter_tasks("report"));
+        assert_eq!(filtered, (1..=20).filter(|id| *id != 5).collect::<Vec<u32>>());
+        assert_eq!(ids(manager.get_tasks_by_priority(Priority::High)), vec![1, 3, 7, 9, 11, 13, 15, 17, 19]);
+        assert_eq!(ids(manager.tasks_in_id_range(4..=7)), vec![4, 6, 7]);
+    }
+
+    #[test]
+    fn test_subscriber_receives_events_in_order() {
+        use std::sync::{Arc, Mutex};
+
+        let mut manager = TaskManager::new();
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let recorder = Arc::clone(&events);
+        manager.subscribe(Box::new(move |event, _| recorder.lock().unwrap().push(event.clone())));
+
+        let id = manager.add_task("Test".to_string(), "Description".to_string(), Priority::Low).unwrap();
+        manager.update_task_status(id, TaskStatus::InProgress).unwrap();
+        manager.add_tag_to_task(id, "work".to_string()).unwrap();
+        manager.add_tag_to_task(id, "work".to_string()).unwrap();
+        assert!(manager.update_task_status(99, TaskStatus::Completed).is_err());
+        manager.delete_task(id).unwrap();
+
+        assert_eq!(*events.lock().unwrap(), vec![
+            TaskEvent::Added(id),
+            TaskEvent::StatusChanged { id, from: TaskStatus::Pending, to: TaskStatus::InProgress },
+            TaskEvent::Tagged { id, tag: "work".to_string() },
+            TaskEvent::Deleted(id),
+        ]);
+    }
+
+    #[test]
+    fn test_quadrant_from_priority_and_due_date() {
+        let today = NaiveDate::from_ymd_opt(2024, 7, 8).unwrap();
+        let mut manager = TaskManager::new();
+        let task = |priority, due: Option<(u32, u32)>| {
+            let mut task = Task::new(1, "Task".to_string(), String::new(), priority);
+            task.due = due.map(|(month, day)| NaiveDate::from_ymd_opt(2024, month, day).unwrap());
+            task
+        };
+        // Overdue, due today and due on the last day of the window are all urgent
+        for due in [(6, 30), (7, 8), (7, 10)] {
+            assert_eq!(manager.quadrant(&task(Priority::High, Some(due)), today), Quadrant::DoNow);
+            assert_eq!(manager.quadrant(&task(Priority::Medium, Some(due)), today), Quadrant::Delegate);
+        }
+        assert_eq!(manager.quadrant(&task(Priority::Critical, Some((7, 11))), today), Quadrant::Schedule);
+        assert_eq!(manager.quadrant(&task(Priority::Critical, None), today), Quadrant::Schedule);
+        assert_eq!(manager.quadrant(&task(Priority::Low, None), today), Quadrant::Drop);
+
+        manager.set_urgent_days(0);
+        assert_eq!(manager.quadrant(&task(Priority::High, Some((7, 9))), today), Quadrant::Schedule);
+        assert_eq!(manager.quadrant(&task(Priority::High, Some((7, 8))), today), Quadrant::DoNow);
+    }
+
+    #[test]
+    fn test_due_soon_puts_the_most_pressed_first() {
+        let now = NaiveDate::from_ymd_opt(2024, 7, 8).unwrap().and_hms_opt(9, 0, 0).unwrap();
+        let mut manager = TaskManager::new();
+        let mut add = |title: &str, due: Option<u32>, estimate: Option<&str>| {
+            let id = manager.add_task(title.to_string(), String::new(), Priority::Medium).unwrap();
+            let due = due.map(|day| NaiveDate::from_ymd_opt(2024, 7, day).unwrap());
+            manager.apply_edit(id, TaskEdit { due, ..TaskEdit::default() }).unwrap();
+            if let Some(estimate) = estimate {
+                manager.set_field(id, pressure::ESTIMATE_FIELD, Some(estimate.to_string())).unwrap();
+            }
+            id
+        };
+        let slides = add("Slides", Some(12), None);
+        let report = add("Report", Some(14), Some("5d"));
+        let late = add("Late", Some(5), None);
+        add("Next month", Some(20), None);
+        add("Someday", None, None);
+        let done = add("Done", Some(9), None);
+        manager.update_task_status(done, TaskStatus::Completed).unwrap();
+
+        let ids = |manager: &TaskManager| manager.due_soon(7, now).iter().map(|task| task.id).collect::<Vec<_>>();
+        assert_eq!(ids(&manager), [late, report, slides]);
+        let task = manager.get_task(slides).unwrap();
+        assert_eq!(manager.pressure(task, now), Some(Pressure::Ahead));
+
+        // 111 hours left is less than a five-day estimate
+        manager.set_default_estimate(Duration::from_secs(5 * 86_400));
+        assert_eq!(manager.pressure(manager.get_task(slides).unwrap(), now), Some(Pressure::Critical));
+        assert_eq!(ids(&manager), [late, slides, report]);
+        // A window past the end of the calendar takes in every dated open task
+        assert_eq!(manager.due_soon(u32::MAX, now).len(), 4);
+    }
+
+    #[test]
+    fn test_logged_sessions_count_pomodoros_and_time() {
+        let mut manager = TaskManager::new();
+        let id = manager.add_task("Write".to_string(), String::new(), Priority::Medium).unwrap();
+        let session = |seconds, completed| WorkSession { started_at: chrono::Utc::now(), seconds, completed };
+        manager.log_session(id, session(1500, true)).unwrap();
+        manager.log_session(id, session(1500, true)).unwrap();
+        manager.log_session(id, session(420, false)).unwrap();
+        assert!(manager.log_session(99, session(60, true)).is_err());
+
+        let task = manager.get_task(id).unwrap();
+        assert_eq!(task.pomodoros(), 2);
+        assert_eq!(task.time_tracked().as_secs(), 3420);
+    }
+
+    #[test]
+    fn test_time_report_by_tag_and_project() {
+        use chrono::TimeZone;
+        let at = |day, hour| chrono::Utc.with_ymd_and_hms(2024, 7, day, hour, 0, 0).unwrap();
+        let hours = |n: u64| Duration::from_secs(n * 3600);
+        let mut manager = TaskManager::new();
+        let migration = manager.add_task("Migration".to_string(), String::new(), Priority::High).unwrap();
+        let schema = manager.add_task("Schema".to_string(), String::new(), Priority::High).unwrap();
+        let chores = manager.add_task("Chores".to_string(), String::new(), Priority::Low).unwrap();
+        let migration_uid = manager.get_task(migration).unwrap().uid.clone();
+        manager.get_task_mut(schema).unwrap().parent = Some(migration_uid);
+        manager.add_tag_to_task(schema, "db".to_string()).unwrap();
+        manager.add_tag_to_task(schema, "Work".to_string()).unwrap();
+        manager.add_tag_to_task(migration, "work".to_string()).unwrap();
+
+        let log = |manager: &mut TaskManager, id, started_at, n: u64| {
+            manager.log_session(id, WorkSession { started_at, seconds: n * 3600, completed: true }).unwrap()
+        };
+        // Straddles the start of the week: only the hour after midnight counts
+        log(&mut manager, schema, at(7, 23), 2);
+        log(&mut manager, schema, at(9, 10), 3);
+        log(&mut manager, migration, at(10, 9), 1);
+        log(&mut manager, chores, at(11, 20), 2);
+        // Before the range entirely
+        log(&mut manager, chores, at(1, 9), 5);
+
+        let week = DateRange::between(at(8, 0), at(15, 0));
+        assert_eq!(manager.time_report(GroupKey::Tag, week), vec![
+            ("work".to_string(), hours(5)),
+            ("db".to_string(), hours(4)),
+            (UNTAGGED.to_string(), hours(2)),
+        ]);
+        assert_eq!(manager.time_report(GroupKey::Project, week), vec![
+            ("Migration".to_string(), hours(5)),
+            ("Chores".to_string(), hours(2)),
+        ]);
+        // Schema's four hours appear under both of its tags but only once in the total
+        assert_eq!(manager.time_total(week), hours(7));
+        assert_eq!(manager.time_total(DateRange::all()), hours(13));
+    }
+
+    #[test]
+    fn test_reminders_need_a_due_date_and_rearm_when_it_moves() {
+        let mut manager = TaskManager::new();
+        let id = manager.add_task("Renew passport".to_string(), String::new(), Priority::High).unwrap();
+        let (days, hours) = (Duration::from_secs(2 * 86_400), Duration::from_secs(4 * 3_600));
+        assert!(matches!(manager.set_reminders(id, vec![days]), Err(TaskError::Invalid { .. })));
+
+        let due = |day| TaskEdit { due: chrono::NaiveDate::from_ymd_opt(2024, 7, day), ..Default::default() };
+        manager.apply_edit(id, due(10)).unwrap();
+        manager.set_reminders(id, vec![hours, days, hours]).unwrap();
+        let offsets = |manager: &TaskManager| {
+            manager.get_task(id).unwrap().reminders.iter().map(|r| (r.offset, r.fired)).collect::<Vec<_>>()
+        };
+        assert_eq!(offsets(&manager), vec![(days, false), (hours, false)]);
+
+        manager.mark_reminder_fired(id, days).unwrap();
+        manager.set_reminders(id, vec![days, hours]).unwrap();
+        assert_eq!(offsets(&manager), vec![(days, true), (hours, false)]);
+        manager.apply_edit(id, due(12)).unwrap();
+        assert_eq!(offsets(&manager), vec![(days, false), (hours, false)]);
+
+        manager.set_reminders(id, Vec::new()).unwrap();
+        assert!(offsets(&manager).is_empty());
+    }
+
+    #[test]
+    fn test_custom_fields_are_checked_and_capped() {
+        let mut manager = TaskManager::new();
+        let id = manager.add_task("Estimate".to_string(), String::new(), Priority::Medium).unwrap();
+        manager.set_field(id, "story-points", Some("5".to_string())).unwrap();
+        manager.set_field(id, "story-points", Some("8".to_string())).unwrap();
+        assert_eq!(manager.get_task(id).unwrap().fields["story-points"], "8");
+        assert!(matches!(manager.set_field(id, "Story Points", Some("1".to_string())), Err(TaskError::Invalid { .. })));
+
+        for n in 1..MAX_FIELDS {
+            manager.set_field(id, &format!("extra-{}", n), Some(n.to_string())).unwrap();
+        }
+        assert!(matches!(manager.set_field(id, "one-more", Some("x".to_string())), Err(TaskError::Invalid { .. })));
+        // Changing a field it already has is still fine at the cap
+        manager.set_field(id, "extra-1", Some("changed".to_string())).unwrap();
+
+        manager.set_field(id, "story-points", None).unwrap();
+        assert!(!manager.get_task(id).unwrap().fields.contains_key("story-points"));
+        assert_eq!(manager.filter_tasks("changed").len(), 1);
+        let by_field = TaskFilter { fields: vec![("extra-2".to_string(), "2".to_string())], ..TaskFilter::default() };
+        assert_eq!(manager.find(&by_field).len(), 1);
+    }
+
+    #[test]
+    fn test_waiting_groups_open_tasks_by_person() {
+        let mut manager = TaskManager::new();
+        let date = |day| NaiveDate::from_ymd_opt(2024, 7, day).unwrap();
+        let mut add = |title: &str, who: &str, since| {
+            let id = manager.add_task(title.to_string(), String::new(), Priority::Medium).unwrap();
+            manager.wait_on(id, who.to_string(), since, None).unwrap();
+            id
+        };
+        let contract = add("Contract", "Alice", date(5));
+        let budget = add("Budget", "Bob", date(3));
+        let invoice = add("Invoice", "Alice", date(1));
+        let shipped = add("Shipped", "Bob", date(2));
+        manager.update_task_status(shipped, TaskStatus::Completed).unwrap();
+
+        let groups: Vec<(String, Vec<u32>)> =
+            manager.waiting().into_iter().map(|(who, tasks)| (who, tasks.iter().map(|t| t.id).collect())).collect();
+        assert_eq!(groups, vec![("Alice".to_string(), vec![invoice, contract]), ("Bob".to_string(), vec![budget])]);
+        assert!(matches!(manager.wait_on(shipped, "Bob".to_string(), date(9), None), Err(TaskError::Invalid { .. })));
+
+        assert_eq!(manager.stop_waiting(contract).unwrap().map(|waiting| waiting.who), Some("Alice".to_string()));
+        assert_eq!(manager.stop_waiting(contract).unwrap(), None);
+        assert_eq!(manager.get_task(contract).unwrap().status, TaskStatus::Pending);
+    }
+
+    #[test]
+    fn test_project_keys_follow_tasks_between_projects() {
+        let mut manager = TaskManager::new();
+        let auth = manager.add_task("Auth".to_string(), String::new(), Priority::Medium).unwrap();
+        let login = manager.add_task("Login form".to_string(), String::new(), Priority::Medium).unwrap();
+        manager.set_parent(login, Some(auth)).unwrap();
+        assert_eq!(manager.get_task(login).unwrap().key, None);
+
+        manager.set_key_prefix(auth, "auth").unwrap();
+        assert_eq!(manager.get_task(login).unwrap().key.as_deref(), Some("AUTH-1"));
+        let mut reset = Task::new(0, "Password reset".to_string(), String::new(), Priority::Medium);
+        reset.parent = Some(manager.get_task(login).unwrap().uid.clone());
+        let reset = manager.create_task(reset).unwrap();
+        assert_eq!(manager.get_task(reset).unwrap().key.as_deref(), Some("AUTH-2"));
+        assert_eq!(manager.find_by_key("auth-2").map(|task| task.id), Some(reset));
+
+        let billing = manager.add_task("Billing".to_string(), String::new(), Priority::Medium).unwrap();
+        manager.set_key_prefix(billing, "BILL").unwrap();
+        assert!(matches!(manager.set_key_prefix(billing, "AUTH"), Err(TaskError::Invalid { .. })));
+        assert!(matches!(manager.set_key_prefix(reset, "RESET"), Err(TaskError::Invalid { .. })));
+        assert!(matches!(manager.set_key_prefix(billing, "9LIVES"), Err(TaskError::Invalid { .. })));
+
+        // The subtask moves along with its parent, and both answer to their old keys
+        manager.set_parent(login, Some(billing)).unwrap();
+        assert_eq!(manager.get_task(login).unwrap().key.as_deref(), Some("BILL-1"));
+        assert_eq!(manager.get_task(reset).unwrap().key.as_deref(), Some("BILL-2"));
+        assert_eq!(manager.find_by_key("AUTH-2").map(|task| task.id), Some(reset));
+
+        // Numbers are not handed out twice, even after a reload
+        let mut reloaded = TaskManager::from_state(manager.to_state());
+        let mut signup = Task::new(0, "Signup".to_string(), String::new(), Priority::Medium);
+        signup.parent = Some(reloaded.get_task(auth).unwrap().uid.clone());
+        let signup = reloaded.create_task(signup).unwrap();
+        assert_eq!(reloaded.get_task(signup).unwrap().key.as_deref(), Some("AUTH-3"));
+        assert!(reloaded.check_keys().is_ok());
+    }
+
+    #[test]
+    fn test_duplicate_keys_from_hand_edits_are_reported() {
+        let mut first = Task::new(1, "One".to_string(), String::new(), Priority::Medium);
+        first.key = Some("AUTH-7".to_string());
+        let mut second = Task::new(2, "Two".to_string(), String::new(), Priority::Medium);
+        second.old_keys = vec!["auth-7".to_string()];
+        let mut third = Task::new(3, "Three".to_string(), String::new(), Priority::Medium);
+        third.key_prefix = Some(KeyPrefix { prefix: "OPS".to_string(), next: 1 });
+        let mut fourth = Task::new(4, "Four".to_string(), String::new(), Priority::Medium);
+        fourth.key_prefix = Some(KeyPrefix { prefix: "OPS".to_string(), next: 4 });
+        let tasks = vec![first, second, third, fourth];
+        let manager = TaskManager::from_state(SavedState { tasks, next_id: 5, ..SavedState::default() });
+
+        match manager.check_keys() {
+            Err(TaskError::Invalid { reason }) => assert_eq!(
+                reason,
+                "duplicate task keys: key AUTH-7 is on tasks 1, 2; prefix OPS is on tasks 3, 4"
+            ),
+            other => panic!("unexpected result: {:?}", other),
+        }
+        // A clashing key stands for neither task
+        assert!(manager.find_by_key("AUTH-7").is_none());
+    }
+
+    #[test]
+    fn test_rehearsal_reports_changes_and_undoes_them() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let mut manager = TaskManager::new();
+        let milk = manager.add_task("Buy milk".to_string(), String::new(), Priority::Low).unwrap();
+        manager.update_task_status(milk, TaskStatus::Completed).unwrap();
+        let heard = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&heard);
+        manager.subscribe(Box::new(move |_, _| {
+            counter.fetch_add(1, Ordering::SeqCst);
+        }));
+        let before = manager.snapshot();
+
+        let (cleared, planned) = manager.rehearse(|mgr| {
+            let added = mgr.add_task("Buy bread".to_string(), String::new(), Priority::Low).unwrap();
+            mgr.delete_task(milk).unwrap();
+            added
+        });
+        assert_eq!(planned, vec![
+            PlannedChange { event: TaskEvent::Added(cleared), title: "Buy bread".to_string() },
+            PlannedChange { event: TaskEvent::Deleted(milk), title: "Buy milk".to_string() },
+        ]);
+        assert_eq!(manager.snapshot(), before);
+        assert_eq!(heard.load(Ordering::SeqCst), 0);
+        assert!(manager.find_by_title("Buy bread").is_none());
+        assert!(manager.verify_indexes().is_ok());
+        // The id it handed out is handed out again for real
+        assert_eq!(manager.add_task("Buy bread".to_string(), String::new(), Priority::Low).unwrap(), cleared);
+        assert_eq!(heard.load(Ordering::SeqCst), 1);
+    }
+
+    fn manual_ids(manager: &TaskManager) -> Vec<u32> {
+        let mut tasks: Vec<&Task> = manager.list_tasks();
+        SortKey::Manual.sort(&mut tasks);
+        tasks.into_iter().map(|task| task.id).collect()
+    }
+
+    #[test]
+    fn test_relations_are_kept_on_both_tasks() {
+        let mut manager = TaskManager::new();
+        for title in ["Launch", "Copy", "Design"] {
+            manager.add_task(title.to_string(), String::new(), Priority::Medium).unwrap();
+        }
+        assert!(manager.relate(1, 2).unwrap());
+        assert!(manager.relate(3, 1).unwrap());
+        assert!(!manager.relate(2, 1).unwrap());
+        assert_eq!(manager.get_task(1).unwrap().relates_to, [2, 3]);
+        assert_eq!(manager.get_task(2).unwrap().relates_to, [1]);
+        assert!(matches!(manager.relate(1, 1), Err(TaskError::Invalid { .. })));
+        assert!(matches!(manager.relate(1, 9), Err(TaskError::TaskNotFound { id: 9 })));
+
+        assert!(manager.unrelate(2, 1).unwrap());
+        assert!(!manager.unrelate(2, 1).unwrap());
+        assert!(manager.get_task(2).unwrap().relates_to.is_empty());
+        let related = |manager: &TaskManager, id| manager.related(id).iter().map(|task| task.id).collect::<Vec<_>>();
+        assert_eq!(related(&manager, 1), [3]);
+
+        // A one-sided link shows from both ends, and deleting a task unlinks it everywhere
+        manager.tasks.get_mut(&2).unwrap().relates_to = vec![3];
+        assert_eq!(related(&manager, 3), [1, 2]);
+        manager.delete_task(3).unwrap();
+        assert!(manager.get_task(1).unwrap().relates_to.is_empty());
+        assert!(manager.get_task(2).unwrap().relates_to.is_empty());
+    }
+
+    #[test]
+    fn test_priority_change_is_audited_and_undoable() {
+        let mut manager = TaskManager::new();
+        manager.enable_audit("ana".to_string(), 100);
+        let id = manager.add_task("Outage review".to_string(), String::new(), Priority::Medium).unwrap();
+        let before = manager.get_task(id).unwrap().clone();
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let seen = Arc::clone(&events);
+        manager.subscribe(Box::new(move |event, _| seen.lock().unwrap().push(event.clone())));
+
+        manager.begin_undo_step("priority-set 1 critical");
+        assert!(manager.update_priority(id, Priority::Critical).unwrap());
+        manager.finish_undo_step();
+        assert!(!manager.update_priority(id, Priority::Critical).unwrap());
+        let task = manager.get_task(id).unwrap();
+        assert_eq!(task.priority, Priority::Critical);
+        assert!(task.updated_at >= before.updated_at);
+        assert_eq!(manager.count_with_priority(Priority::Critical), 1);
+        assert_eq!(
+            *events.lock().unwrap(),
+            [TaskEvent::PriorityChanged { id, from: Priority::Medium, to: Priority::Critical }]
+        );
+        let last = manager.audit_history(Some(id)).remove(0);
+        assert_eq!((last.action.as_str(), last.detail.as_str()), ("priority", "Medium -> Critical"));
+
+        manager.undo().unwrap();
+        assert_eq!(*manager.get_task(id).unwrap(), before);
+        assert_eq!(manager.count_with_priority(Priority::Critical), 0);
+        assert!(matches!(manager.update_priority(9, Priority::Low), Err(TaskError::TaskNotFound { id: 9 })));
+    }
+
+    #[test]
+    fn test_others_tasks_are_protected_and_overrides_audited() {
+        let mut manager = TaskManager::new();
+        manager.enable_audit("unused".to_string(), 100);
+        manager.set_protect_others(true, false);
+        manager.set_user("ana".to_string());
+        let hers = manager.add_task("Quarterly plan".to_string(), String::new(), Priority::Medium).unwrap();
+        manager.update_task_status(hers, TaskStatus::InProgress).unwrap();
+        assert_eq!(manager.get_task(hers).unwrap().created_by.as_deref(), Some("ana"));
+
+        manager.set_user("bob".to_string());
+        let his = manager.add_task("Fix the build".to_string(), String::new(), Priority::High).unwrap();
+        let refused = |result: Result<(), TaskError>| {
+            matches!(result, Err(TaskError::NotOwner { id, owner }) if id == hers && owner == "ana")
+        };
+        assert!(refused(manager.update_task_status(hers, TaskStatus::Completed)));
+        assert!(refused(manager.add_tag_to_task(hers, "urgent".to_string())));
+        assert!(refused(manager.delete_task(hers)));
+        assert_eq!(manager.get_task(hers).unwrap().status, TaskStatus::InProgress);
+        manager.update_task_status(his, TaskStatus::Completed).unwrap();
+        // `mine` is what bob created or is assigned
+        let mine = TaskFilter { owner: Some(ME.to_string()), ..TaskFilter::default() };
+        assert_eq!(manager.find(&mine).iter().map(|task| task.id).collect::<Vec<_>>(), [his]);
+        let mut assigned = manager.get_task(hers).unwrap().clone();
+        assigned.fields.insert("assignee".to_string(), "Bob".to_string());
+        manager.upsert_task(assigned).unwrap();
+        assert_eq!(manager.find(&mine).len(), 2);
+
+        // --force lets bob through, and the log says whose task he changed
+        manager.set_protect_others(true, true);
+        manager.delete_task(hers).unwrap();
+        let history = manager.audit_history(Some(hers));
+        let actions: Vec<(&str, &str, &str)> =
+            history.iter().map(|entry| (entry.user.as_str(), entry.action.as_str(), entry.detail.as_str())).collect();
+        assert_eq!(actions[..2], [("bob", "deleted", ""), ("bob", "override", "task of ana")]);
+        assert_eq!(actions.last(), Some(&("ana", "added", "")));
+
+        // Unprotected, anyone changes anything and nothing extra is logged
+        manager.set_user("ana".to_string());
+        manager.set_protect_others(false, false);
+        manager.update_task_status(his, TaskStatus::Pending).unwrap();
+        assert_eq!(manager.audit_history(Some(his))[0].action, "status");
+    }
+
+    #[test]
+    fn test_stats_by_creator() {
+        let mut manager = TaskManager::new();
+        manager.create_task(Task::new(0, "From an old file".to_string(), String::new(), Priority::Low)).unwrap();
+        for (user, title) in [("zoe", "Draft"), ("ana", "Review"), ("zoe", "Ship")] {
+            manager.set_user(user.to_string());
+            manager.add_task(title.to_string(), String::new(), Priority::Medium).unwrap();
+        }
+        manager.update_task_status(4, TaskStatus::Completed).unwrap();
+        let stats = manager.stats_by_creator();
+        let rows: Vec<(Option<&str>, usize, usize, usize)> =
+            stats.iter().map(|row| (row.user.as_deref(), row.total, row.completed, row.pending)).collect();
+        assert_eq!(rows, [(Some("ana"), 1, 0, 1), (Some("zoe"), 2, 1, 1), (None, 1, 0, 1)]);
+    }
+
+    #[test]
+    fn test_twin_titles_from_a_file_keep_the_index_in_step() {
+        let mut state = SavedState { next_id: 5, ..SavedState::default() };
+        for (id, title) in [(1, "Buy milk"), (2, "buy milk"), (3, "Call mum"), (4, "CALL MUM")] {
+            state.tasks.push(Task::new(id, title.to_string(), String::new(), Priority::Medium));
+        }
+        let mut manager = TaskManager::from_state(state);
+        assert_eq!(manager.find_by_title("buy milk").map(|t| t.id), Some(2));
+
+        // Deleting the twin the key does not point at leaves it alone
+        manager.delete_task(1).unwrap();
+        assert_eq!(manager.find_by_title("buy milk").map(|t| t.id), Some(2));
+        assert!(manager.verify_indexes().is_ok());
+
+        // Deleting or renaming the one it does hands the key to the other
+        manager.delete_task(4).unwrap();
+        assert_eq!(manager.find_by_title("call mum").map(|t| t.id), Some(3));
+        assert!(manager.verify_indexes().is_ok());
+        manager.delete_task(2).unwrap();
+        assert!(manager.find_by_title("buy milk").is_none());
+        assert!(manager.verify_indexes().is_ok());
+
+        let mut state = manager.to_state();
+        state.tasks.push(Task::new(5, "call Mum".to_string(), String::new(), Priority::Medium));
+        let mut manager = TaskManager::from_state(state);
+        let rename = TaskEdit { title: Some("Ring mum".to_string()), ..TaskEdit::default() };
+        manager.apply_edit(5, rename).unwrap();
+        assert_eq!(manager.find_by_title("call mum").map(|t| t.id), Some(3));
+        assert!(manager.verify_indexes().is_ok());
+    }
+
+    #[test]
+    fn test_synced_copies_update_the_indexes_in_place() {
+        let mut state = SavedState { next_id: 4, ..SavedState::default() };
+        for (id, title) in [(1, "Buy milk"), (2, "BUY MILK"), (3, "Report")] {
+            let mut task = Task::new(id, title.to_string(), String::new(), Priority::Medium);
+            task.add_tag("home");
+            state.tasks.push(task);
+        }
+        let mut manager = TaskManager::from_state(state);
+
+        // The twin the key does not point at is renamed without taking it along
+        let mut copy = manager.get_task(1).unwrap().clone();
+        copy.title = "Buy bread".to_string();
+        copy.tags = vec!["shop".into()];
+        manager.upsert_task(copy).unwrap();
+        assert_eq!(manager.find_by_title("buy milk").map(|t| t.id), Some(2));
+        assert_eq!(manager.find_by_title("buy bread").map(|t| t.id), Some(1));
+        assert_eq!(manager.get_tasks_by_tags(&["home".to_string()]).len(), 2);
+        assert_eq!(manager.get_tasks_by_tags(&["shop".to_string()]).len(), 1);
+        assert!(manager.verify_indexes().is_ok());
+
+        let mut copy = manager.get_task(3).unwrap().clone();
+        copy.tags.clear();
+        manager.upsert_task(copy).unwrap();
+        assert_eq!(manager.find_by_title("report").map(|t| t.id), Some(3));
+        assert_eq!(manager.verify_indexes(), Ok((2, 3)));
+    }
+
+    #[test]
+    fn test_a_hand_edited_list_keeps_its_duplicates_until_repaired() {
+        let mut state = SavedState { next_id: 2, ..SavedState::default() };
+        for title in ["Kept from the first copy", "", "Loads"] {
+            state.tasks.push(Task::new(1, title.to_string(), String::new(), Priority::Medium));
+        }
+        state.tasks.push(Task::new(2, "Design".to_string(), String::new(), Priority::Medium));
+        let mut manager = TaskManager::from_state(state);
+        assert_eq!(manager.get_task(1).unwrap().title, "Loads");
+        assert_eq!(
+            manager.health(),
+            [Issue::DuplicateId { id: 1, count: 3 }, Issue::EmptyTitle { id: 1 }]
+        );
+        // Saving before the repair loses nothing
+        assert_eq!(manager.to_state().tasks.len(), 4);
+
+        let now = Utc::now();
+        let repairs = manager.repair(now);
+        assert_eq!(repairs.len(), 3, "{:?}", repairs);
+        assert!(manager.health().is_empty());
+        assert!(manager.repair(now).is_empty());
+        assert_eq!(manager.get_task(3).unwrap().title, "Kept from the first copy");
+        assert_eq!(manager.trash().len(), 1);
+        assert_eq!(manager.trash()[0].task.id, 4);
+        assert_eq!(manager.list_tasks().len(), 3);
+        assert_eq!(manager.add_task("Next".to_string(), String::new(), Priority::Low).unwrap(), 5);
+
+        let reloaded = TaskManager::from_state(manager.to_state());
+        assert_eq!(reloaded.trash(), manager.trash());
+        assert_eq!(reloaded.to_state().tasks.len(), 4);
+    }
+
+    #[test]
+    fn test_retention_archives_then_purges_once_a_day() {
+        let mut manager = TaskManager::new();
+        manager.enable_audit("ana".to_string(), 100);
+        let done = manager.add_task("Old report".to_string(), String::new(), Priority::Low).unwrap();
+        let open = manager.add_task("Follow up".to_string(), String::new(), Priority::Low).unwrap();
+        manager.relate(open, done).unwrap();
+        manager.update_task_status(done, TaskStatus::Completed).unwrap();
+        let policy = RetentionPolicy {
+            archive_completed_after: retention::parse_age("30d"),
+            purge_archived_after: retention::parse_age("1y"),
+        };
+        let day = |days: u64| NaiveDate::from_ymd_opt(2024, 7, 1).unwrap() + Days::new(days);
+        let later = |days: i64| Utc::now() + TimeDelta::days(days);
+
+        let plan = manager.apply_retention(&policy, later(31), day(31)).unwrap();
+        assert_eq!((plan.archive, plan.purge), (vec![done], vec![]));
+        assert_eq!(manager.list_tasks().len(), 1);
+        assert_eq!(manager.archive()[0].task.title, "Old report");
+        // The link to it goes with it, so the list stays sound
+        assert!(manager.get_task(open).unwrap().relates_to.is_empty());
+        assert!(manager.health().is_empty());
+        let entry = manager.audit_history(None).into_iter().find(|entry| entry.action == "retention").unwrap();
+        assert_eq!(entry.detail, format!("archived #{}; purged none", done));
+        assert!(manager.apply_retention(&policy, later(400), day(31)).is_none());
+
+        let mut reloaded = TaskManager::from_state(manager.to_state());
+        assert_eq!(reloaded.archive(), manager.archive());
+        assert!(reloaded.apply_retention(&policy, later(400), day(31)).is_none());
+        let plan = reloaded.apply_retention(&policy, later(400), day(32)).unwrap();
+        assert_eq!((plan.archive, plan.purge), (vec![], vec![done]));
+        assert!(reloaded.archive().is_empty());
+        assert_eq!(reloaded.add_task("New".to_string(), String::new(), Priority::Low).unwrap(), 3);
+    }
+
+    #[test]
+    fn test_moves_between_the_same_neighbours_renumber_the_order() {
+        let mut manager = TaskManager::new();
+        let ids: Vec<u32> = (1..=14)
+            .map(|n| manager.add_task(format!("Task {}", n), String::new(), Priority::Medium).unwrap())
+            .collect();
+        // New tasks join the end
+        assert_eq!(manual_ids(&manager), ids);
+        let (first, second) = (ids[0], ids[1]);
+
+        // Each task moved in right after the first halves the gap behind it, so
+        // the gap between the first two runs out before the tasks do
+        let mut expected = vec![first];
+        for &id in ids[2..].iter().rev() {
+            manager.move_task(id, Placement::After, first).unwrap();
+            expected.insert(1, id);
+        }
+        expected.push(second);
+        assert_eq!(manual_ids(&manager), expected);
+        let mut ranks: Vec<u64> = expected.iter().map(|id| manager.get_task(*id).unwrap().rank).collect();
+        ranks.dedup();
+        assert_eq!(ranks.len(), expected.len(), "every task keeps a rank of its own");
+        assert!(manager.get_task(second).unwrap().rank > RANK_GAP * 2, "the order was spaced out again");
+
+        manager.move_task(second, Placement::Before, first).unwrap();
+        assert_eq!(manual_ids(&manager)[..2], [second, first]);
+        let added = manager.add_task("Last".to_string(), String::new(), Priority::Medium).unwrap();
+        assert_eq!(manual_ids(&manager).last(), Some(&added));
+        assert!(manager.move_task(first, Placement::After, first).is_err());
+        assert!(matches!(manager.move_task(first, Placement::After, 99), Err(TaskError::TaskNotFound { id: 99 })));
+    }
+
+    #[test]
+    fn test_deleting_tasks_keeps_the_manual_order() {
+        let mut manager = TaskManager::new();
+        let ids: Vec<u32> = (1..=5)
+            .map(|n| manager.add_task(format!("Task {}", n), String::new(), Priority::Medium).unwrap())
+            .collect();
+        manager.move_task(ids[4], Placement::Before, ids[1]).unwrap();
+        manager.move_task(ids[0], Placement::After, ids[3]).unwrap();
+        assert_eq!(manual_ids(&manager), vec![ids[4], ids[1], ids[2], ids[3], ids[0]]);
+
+        manager.delete_task(ids[2]).unwrap();
+        manager.delete_task(ids[4]).unwrap();
+        assert_eq!(manual_ids(&manager), vec![ids[1], ids[3], ids[0]]);
+        // A neighbour that is gone leaves no hole to fall into
+        manager.move_task(ids[0], Placement::Before, ids[3]).unwrap();
+        assert_eq!(manual_ids(&manager), vec![ids[1], ids[0], ids[3]]);
+    }
+
+    #[test]
+    fn test_guard_refuses_changes_to_completed_tasks() {
+        let mut manager = TaskManager::new();
+        manager.set_guard_completed(true);
+        let id = manager.add_task("Old report".to_string(), String::new(), Priority::Low).unwrap();
+        let other = manager.add_task("Other".to_string(), String::new(), Priority::Low).unwrap();
+        manager.update_task_status(id, TaskStatus::Completed).unwrap();
+        let before = manager.get_task(id).unwrap().clone();
+
+        let refused = |result: Result<(), TaskError>| matches!(result, Err(TaskError::TaskCompleted { id: 1 }));
+        assert!(refused(manager.add_tag_to_task(id, "urgent".to_string())));
+        assert!(refused(manager.apply_edit(id, TaskEdit { priority: Some(Priority::High), ..TaskEdit::default() })));
+        assert!(refused(manager.set_field(id, "owner", Some("ana".to_string()))));
+        assert!(refused(manager.set_pinned(id, true)));
+        assert!(refused(manager.update_task_status(id, TaskStatus::Completed)));
+        assert_eq!(manager.get_task(id).unwrap(), &before);
+        // Moving another task past it may renumber it, which is bookkeeping
+        manager.move_task(other, Placement::Before, id).unwrap();
+
+        // Reopening and deleting are always allowed
+        manager.update_task_status(id, TaskStatus::Pending).unwrap();
+        manager.add_tag_to_task(id, "urgent".to_string()).unwrap();
+        manager.update_task_status(id, TaskStatus::Completed).unwrap();
+        manager.delete_task(id).unwrap();
+
+        manager.update_task_status(other, TaskStatus::Completed).unwrap();
+        manager.set_guard_completed(false);
+        manager.add_tag_to_task(other, "archived".to_string()).unwrap();
+    }
+
+    fn step<T>(manager: &mut TaskManager, label: &str, f: impl FnOnce(&mut TaskManager) -> T) -> T {
+        manager.begin_undo_step(label);
+        let result = f(manager);
+        manager.finish_undo_step();
+        result
+    }
+
+    fn reloaded(manager: &TaskManager) -> TaskManager {
+        let json = serde_json::to_string(&manager.to_state()).unwrap();
+        TaskManager::from_state(serde_json::from_str(&json).unwrap())
+    }
+
+    #[test]
+    fn test_undo_survives_a_reload() {
+        let mut manager = TaskManager::new();
+        let report = manager.add_task("Write report".to_string(), String::new(), Priority::Low).unwrap();
+        let milk = manager.add_task("Buy milk".to_string(), String::new(), Priority::Low).unwrap();
+        let before: Vec<Task> = manager.list_tasks().into_iter().cloned().collect();
+
+        let added = step(&mut manager, "add Call mom", |mgr| {
+            mgr.add_task("Call mom".to_string(), String::new(), Priority::High).unwrap()
+        });
+        step(&mut manager, "update 1 completed", |mgr| {
+            mgr.add_tag_to_task(report, "work".to_string()).unwrap();
+            mgr.update_task_status(report, TaskStatus::Completed).unwrap();
+        });
+        step(&mut manager, "delete 2", |mgr| mgr.delete_task(milk).unwrap());
+        // Nothing changed, so nothing to undo
+        step(&mut manager, "list", |mgr| mgr.list_tasks().len());
+        assert_eq!(manager.undo_history().len(), 3);
+
+        let mut manager = reloaded(&manager);
+        assert_eq!(manager.undo().unwrap().label, "delete 2");
+        assert_eq!(manager.get_task(milk).unwrap().title, "Buy milk");
+        let mut manager = reloaded(&manager);
+        assert_eq!(manager.undo().unwrap().changes.len(), 1);
+        assert_eq!(manager.undo().unwrap().label, "add Call mom");
+        assert!(manager.get_task(added).is_err());
+
+        let after: Vec<Task> = manager.list_tasks().into_iter().cloned().collect();
+        assert_eq!(after, before);
+        assert!(manager.verify_indexes().is_ok());
+        assert_eq!(manager.find_by_title("Buy milk").map(|task| task.id), Some(milk));
+        assert!(matches!(manager.undo(), Err(TaskError::Invalid { .. })));
+    }
+
+    #[test]
+    fn test_undo_refuses_steps_the_file_moved_on_from() {
+        let mut manager = TaskManager::new();
+        let id = manager.add_task("Write report".to_string(), String::new(), Priority::Low).unwrap();
+        step(&mut manager, "add Buy milk", |mgr| {
+            mgr.add_task("Buy milk".to_string(), String::new(), Priority::Low).unwrap()
+        });
+        step(&mut manager, "update 1 completed", |mgr| mgr.update_task_status(id, TaskStatus::Completed).unwrap());
+
+        // Someone edited the data file by hand in between
+        let mut state = manager.to_state();
+        state.tasks[0].title = "Write the report".to_string();
+        let mut manager = TaskManager::from_state(state);
+        let before = manager.to_state().tasks;
+        assert!(matches!(manager.undo(), Err(TaskError::Invalid { reason }) if reason.contains("task 1")));
+        assert_eq!(manager.to_state().tasks, before);
+        // The stale step is gone and the one before it still works
+        assert_eq!(manager.undo().unwrap().label, "add Buy milk");
+        assert_eq!(manager.list_tasks().len(), 1);
+    }
+
+    #[test]
+    fn test_reload_keeps_settings_and_tells_no_one() {
+        let mut manager = TaskManager::new();
+        manager.add_task("Write report".to_string(), String::new(), Priority::Low).unwrap();
+        manager.set_urgent_days(9);
+        let heard = Arc::new(Mutex::new(0));
+        let counter = Arc::clone(&heard);
+        manager.subscribe(Box::new(move |_, _| *counter.lock().unwrap() += 1));
+
+        // Another process added a tagged task and saved
+        let mut other = TaskManager::from_state(manager.to_state());
+        let id = other.add_task("Buy milk".to_string(), String::new(), Priority::High).unwrap();
+        other.add_tag_to_task(id, "errand".to_string()).unwrap();
+        manager.reload(other.to_state());
+
+        assert_eq!(*heard.lock().unwrap(), 0);
+        assert_eq!(manager.urgent_days, 9);
+        assert_eq!(manager.find_by_title("buy milk").map(|task| task.id), Some(id));
+        assert_eq!(manager.get_statistics().0, 2);
+        assert!(manager.verify_indexes().is_ok());
+        // Still wired up for changes made here
+        manager.delete_task(id).unwrap();
+        assert_eq!(*heard.lock().unwrap(), 1);
+    }
+
+    #[test]
+    fn test_stale_tasks_oldest_first() {
+        let now = Utc::now();
+        let mut manager = TaskManager::new();
+        for (title, idle) in [("Fresh", 2), ("Old", 40), ("Older", 90), ("Done", 60), ("Snoozed", 30), ("Edge", 14)] {
+            let mut task = Task::new(0, title.to_string(), String::new(), Priority::Low);
+            task.updated_at = now - TimeDelta::days(idle);
+            manager.create_task(task).unwrap();
+        }
+        let done = manager.find_by_title("Done").unwrap().id;
+        manager.tasks.get_mut(&done).unwrap().status = TaskStatus::Completed;
+        let snoozed = manager.find_by_title("Snoozed").unwrap().id;
+        manager.snooze_task(snoozed, now.date_naive() + TimeDelta::days(3)).unwrap();
+
+        let titles = |manager: &TaskManager, days, now| {
+            manager.stale_tasks(days, now).iter().map(|task| task.title.clone()).collect::<Vec<_>>()
+        };
+        assert_eq!(titles(&manager, 14, now), ["Older", "Old"]);
+        assert_eq!(titles(&manager, 60, now), ["Older"]);
+        // The snooze runs out
+        assert_eq!(titles(&manager, 14, now + TimeDelta::days(3)), ["Older", "Old", "Snoozed", "Edge"]);
+
+        let old = manager.find_by_title("Old").unwrap().id;
+        manager.touch_task(old).unwrap();
+        assert_eq!(titles(&manager, 14, now), ["Older"]);
+        assert!(titles(&manager, u32::MAX, now).is_empty());
+    }
+
+    #[test]
+    fn test_goal_is_saved_and_celebrated_once() {
+        use crate::goal::GoalPeriod;
+
+        let today = chrono::Local::now().date_naive();
+        let mut manager = TaskManager::new();
+        let events = Arc::new(Mutex::new(0));
+        let counter = Arc::clone(&events);
+        manager.subscribe(Box::new(move |event, _| {
+            if *event == TaskEvent::GoalChanged {
+                *counter.lock().unwrap() += 1;
+            }
+        }));
+        assert_eq!(manager.goal_progress(today), None);
+        manager.set_goal(Some(Goal::new(2, GoalPeriod::Day)));
+        for title in ["Write", "Review", "Ship"] {
+            manager.add_task(title.to_string(), String::new(), Priority::Medium).unwrap();
+        }
+
+        manager.update_task_status(1, TaskStatus::Completed).unwrap();
+        assert_eq!(manager.goal_progress(today).map(|progress| progress.done), Some(1));
+        assert_eq!(manager.celebrate_goal(today), None);
+        manager.update_task_status(2, TaskStatus::Completed).unwrap();
+        assert_eq!(manager.celebrate_goal(today).map(|progress| progress.done), Some(2));
+        // Going over, or dipping under and back, in the same period says nothing more
+        manager.update_task_status(3, TaskStatus::Completed).unwrap();
+        manager.update_task_status(3, TaskStatus::Pending).unwrap();
+        manager.update_task_status(3, TaskStatus::Completed).unwrap();
+        assert_eq!(manager.celebrate_goal(today), None);
+        assert_eq!(*events.lock().unwrap(), 2);
+
+        let reloaded = TaskManager::from_state(manager.to_state());
+        assert_eq!(reloaded.goal().and_then(|goal| goal.celebrated), Some(today));
+        // Same goal set again counts as no change
+        manager.set_goal(reloaded.goal().cloned());
+        manager.set_goal(None);
+        assert_eq!(*events.lock().unwrap(), 3);
+    }
+
+    #[test]
+    fn test_tag_registry_describes_imports_and_guards_tags() {
+        let mut manager = TaskManager::new();
+        let id = manager.add_task("Fix login".to_string(), String::new(), Priority::High).unwrap();
+        manager.add_tag_to_task(id, "backend".to_string()).unwrap();
+        manager.describe_tag("Backend", "Server code", Some("blue".to_string())).unwrap();
+        manager.describe_tag("ops", "Deploys", None).unwrap();
+        assert!(manager.describe_tag("two words", "Nope", None).is_err());
+        let rows: Vec<(String, usize)> = manager.tag_rows().into_iter().map(|row| (row.tag, row.count)).collect();
+        assert_eq!(rows, [("backend".to_string(), 1), ("ops".to_string(), 0)]);
+
+        let mut shared = TagRegistry::new();
+        shared.insert("ops".to_string(), TagInfo { description: "Deploys and on-call".to_string(), color: None });
+        shared.insert("design".to_string(), TagInfo { description: "Mockups".to_string(), color: None });
+        let report = manager.import_tags(shared.clone(), false);
+        assert_eq!((report.added, report.updated, report.removed), (vec!["design".to_string()], vec!["ops".to_string()], vec![]));
+        assert_eq!(manager.tag_registry().len(), 3);
+        let report = manager.import_tags(shared, true);
+        assert_eq!(report.removed, ["backend"]);
+        assert_eq!(manager.tag_registry().keys().collect::<Vec<_>>(), ["design", "ops"]);
+
+        // Only with strict tags on does the registry limit what tasks carry
+        manager.add_tag_to_task(id, "urgent".to_string()).unwrap();
+        manager.set_strict_tags(true);
+        manager.add_tag_to_task(id, "Design".to_string()).unwrap();
+        let Err(TaskError::Invalid { reason }) = manager.add_tag_to_task(id, "desgn".to_string()) else {
+            panic!("expected an unknown tag to be refused")
+        };
+        assert!(reason.contains("did you mean design?"), "{}", reason);
+        let mut task = Task::new(0, "Plan".to_string(), String::new(), Priority::Low);
+        task.tags.push("misc".into());
+        assert!(matches!(manager.create_task(task), Err(TaskError::Invalid { .. })));
+    }
+
+    #[test]
+    fn test_limits_refuse_or_truncate() {
+        let mut manager = TaskManager::new();
+        manager.set_limits(Limits { description_chars: Some(12), notes_per_task: Some(2), tags_per_task: Some(2) });
+        let long = Task::new(0, "Write up".to_string(), "notes from the whole meeting".to_string(), Priority::Low);
+        let Err(TaskError::ValidationFailed(reasons)) = manager.create_task(long.clone()) else {
+            panic!("expected the long description to be refused")
+        };
+        assert_eq!(reasons, ["the description has 28 characters; limits.description_chars allows 12"]);
+
+        let id = manager.add_task("Call".to_string(), "first".to_string(), Priority::Low).unwrap();
+        let edit = |text: &str| TaskEdit { description: Some(text.to_string()), ..TaskEdit::default() };
+        assert!(manager.apply_edit(id, edit("first\nsecond\nthird")).is_err());
+        for tag in ["home", "phone"] {
+            manager.add_tag_to_task(id, tag.to_string()).unwrap();
+        }
+        assert!(matches!(manager.add_tag_to_task(id, "later".to_string()), Err(TaskError::ValidationFailed(_))));
+        // One it already carries still fits
+        manager.add_tag_to_task(id, "home".to_string()).unwrap();
+        assert!(manager.take_truncations().is_empty());
+
+        manager.set_truncate_to_limits(true);
+        let cut = manager.create_task(long).unwrap();
+        assert_eq!(manager.get_task(cut).unwrap().description, "notes from…");
+        manager.apply_edit(id, edit("first\nsecond\nthird")).unwrap();
+        assert_eq!(manager.get_task(id).unwrap().description, "first\nsecond");
+        manager.add_tag_to_task(id, "later".to_string()).unwrap();
+        assert_eq!(manager.get_task(id).unwrap().tags.len(), 2);
+        assert_eq!(manager.take_truncations(), [
+            "task 2: cut the description from 28 to 12 characters (limits.description_chars)",
+            "task 1: kept the first 2 of 3 notes (limits.notes_per_task)",
+            "task 1: dropped tag later (limits.tags_per_task)",
+        ]);
+        // A rehearsal stores nothing, so it cuts nothing either
+        manager.rehearse(|mgr| mgr.apply_edit(id, edit("a\nb\nc"))).0.unwrap();
+        assert!(manager.take_truncations().is_empty());
+    }
+}
@@ -0,0 +1,180 @@
+use std::fmt;
+
+use chrono::NaiveDate;
+use serde::Serialize;
+use unicode_width::UnicodeWidthStr;
+
+use crate::board::{MIN_SIDE_BY_SIDE_WIDTH, fit, pad};
+use crate::dates;
+use crate::msg;
+use crate::render::{pin_marker, priority_glyph};
+use crate::{Task, TaskManager, TaskStatus};
+
+// Days ahead a due date still counts as urgent when the config does not say
+pub const DEFAULT_URGENT_DAYS: u32 = 2;
+
+const GAP: &str = " │ ";
+
+// Eisenhower quadrants: important is High or Critical priority, urgent is overdue
+// or due within the manager's urgent window
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Quadrant {
+    DoNow,
+    Schedule,
+    Delegate,
+    Drop,
+}
+
+impl Quadrant {
+    // Grid order: the important row first, the urgent column on the left
+    pub const ALL: [Quadrant; 4] = [Quadrant::DoNow, Quadrant::Schedule, Quadrant::Delegate, Quadrant::Drop];
+
+    pub fn classify(important: bool, urgent: bool) -> Quadrant {
+        match (important, urgent) {
+            (true, true) => Quadrant::DoNow,
+            (true, false) => Quadrant::Schedule,
+            (false, true) => Quadrant::Delegate,
+            (false, false) => Quadrant::Drop,
+        }
+    }
+}
+
+impl fmt::Display for Quadrant {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Quadrant::DoNow => msg!("matrix_do_now"),
+            Quadrant::Schedule => msg!("matrix_schedule"),
+            Quadrant::Delegate => msg!("matrix_delegate"),
+            Quadrant::Drop => msg!("matrix_drop"),
+        };
+        f.write_str(&name)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Matrix {
+    pub quadrants: Vec<Cell>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Cell {
+    pub quadrant: Quadrant,
+    pub tasks: Vec<Task>,
+}
+
+// Open tasks by quadrant, each soonest due first, then most important, then by id
+pub fn build(manager: &TaskManager, today: NaiveDate) -> Matrix {
+    let mut cells: Vec<Cell> = Quadrant::ALL.iter().map(|&quadrant| Cell { quadrant, tasks: Vec::new() }).collect();
+    for task in manager.list_tasks() {
+        if task.status == TaskStatus::Completed {
+            continue;
+        }
+        let quadrant = manager.quadrant(task, today);
+        cells[Quadrant::ALL.iter().position(|&q| q == quadrant).expect("every quadrant has a cell")]
+            .tasks
+            .push(task.clone());
+    }
+    for cell in &mut cells {
+        cell.tasks.sort_by(|a, b| {
+            (a.due.is_none(), a.due)
+                .cmp(&(b.due.is_none(), b.due))
+                .then_with(|| b.priority.cmp(&a.priority))
+                .then_with(|| a.id.cmp(&b.id))
+        });
+    }
+    Matrix { quadrants: cells }
+}
+
+// The 2x2 grid as text `width` columns wide, or the quadrants one under another
+// on narrow terminals. Depends on nothing but its arguments.
+pub fn layout(matrix: &Matrix, width: usize) -> String {
+    if width < MIN_SIDE_BY_SIDE_WIDTH || matrix.quadrants.len() != 4 {
+        let sections: Vec<String> = matrix.quadrants.iter().map(|cell| cell_lines(cell, width).join("\n")).collect();
+        return sections.join("\n\n");
+    }
+    let half = (width - GAP.width()) / 2;
+    let cells: Vec<Vec<String>> = matrix.quadrants.iter().map(|cell| cell_lines(cell, half)).collect();
+    let row = |left: &[String], right: &[String]| {
+        (0..left.len().max(right.len()))
+            .map(|i| {
+                let left = pad(left.get(i).map_or("", String::as_str), half);
+                format!("{}{}{}", left, GAP, right.get(i).map_or("", String::as_str)).trim_end().to_string()
+            })
+            .collect::<Vec<_>>()
+    };
+    let mut lines = row(&cells[0], &cells[1]);
+    lines.push(format!("{}─┼─{}", "─".repeat(half), "─".repeat(half)));
+    lines.extend(row(&cells[2], &cells[3]));
+    lines.join("\n")
+}
+
+fn cell_lines(cell: &Cell, width: usize) -> Vec<String> {
+    let mut lines = vec![fit(&format!("{} ({})", cell.quadrant, cell.tasks.len()), width)];
+    lines.extend(cell.tasks.iter().map(|task| fit(&task_line(task), width)));
+    if cell.tasks.is_empty() {
+        lines.push(fit(&msg!("board_empty"), width));
+    }
+    lines
+}
+
+fn task_line(task: &Task) -> String {
+    let line = format!("#{} {} {}{}", task.id, priority_glyph(&task.priority), pin_marker(task.pinned), task.title);
+    match task.due {
+        Some(date) => format!("{} {}", line, msg!("matrix_due", date = dates::show_date(date))),
+        None => line,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Priority, TaskEdit};
+
+    fn date(day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2024, 7, day).unwrap()
+    }
+
+    fn sample() -> TaskManager {
+        let mut manager = TaskManager::new();
+        let tasks = [
+            ("Fix the outage", Priority::Critical, Some(date(8))),
+            ("Plan next quarter", Priority::High, None),
+            ("Answer the survey", Priority::Low, Some(date(9))),
+            ("Sort old photos", Priority::Low, None),
+            ("Renew passport", Priority::High, Some(date(1))),
+            ("Done already", Priority::High, Some(date(8))),
+        ];
+        for (title, priority, due) in tasks {
+            let id = manager.add_task(title.to_string(), String::new(), priority).unwrap();
+            manager.apply_edit(id, TaskEdit { due, ..TaskEdit::default() }).unwrap();
+        }
+        manager.update_task_status(6, TaskStatus::Completed).unwrap();
+        manager
+    }
+
+    #[test]
+    fn test_matrix_grid_snapshot() {
+        let matrix = build(&sample(), date(8));
+        assert_eq!(
+            layout(&matrix, 90),
+            "\
+Do now (2)                                  │ Schedule (1)
+#5 !! Renew passport (due 2024-07-01)       │ #2 !! Plan next quarter
+#1 !!! Fix the outage (due 2024-07-08)      │
+────────────────────────────────────────────┼────────────────────────────────────────────
+Delegate (1)                                │ Drop? (1)
+#3 · Answer the survey (due 2024-07-09)     │ #4 · Sort old photos"
+        );
+    }
+
+    #[test]
+    fn test_narrow_matrix_stacks_and_empty_quadrants_say_so() {
+        let mut manager = TaskManager::new();
+        manager.add_task("Sort old photos".to_string(), String::new(), Priority::Low).unwrap();
+        assert_eq!(
+            layout(&build(&manager, date(8)), 30),
+            "Do now (0)\n(empty)\n\nSchedule (0)\n(empty)\n\nDelegate (0)\n(empty)\n\nDrop? (1)\n#1 · Sort old photos"
+        );
+    }
+}
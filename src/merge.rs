@@ -0,0 +1,273 @@
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::fmt;
+use std::str::FromStr;
+
+use crate::Task;
+
+// Which copy wins a task both this session and another process changed
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Resolution {
+    // This session's copy, or its deletion
+    Mine,
+    // The file's copy, or its deletion
+    Theirs,
+    // The task as it was when the list was loaded
+    Skip,
+}
+
+impl FromStr for Resolution {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Resolution, ()> {
+        match s.trim().to_lowercase().as_str() {
+            "m" | "mine" => Ok(Resolution::Mine),
+            "t" | "theirs" => Ok(Resolution::Theirs),
+            "s" | "skip" => Ok(Resolution::Skip),
+            _ => Err(()),
+        }
+    }
+}
+
+impl fmt::Display for Resolution {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Resolution::Mine => write!(f, "mine"),
+            Resolution::Theirs => write!(f, "theirs"),
+            Resolution::Skip => write!(f, "skip"),
+        }
+    }
+}
+
+// A task both sides changed since the list was loaded; a copy is None where
+// that side deleted the task, or where it did not exist yet
+#[derive(Debug, Clone, PartialEq)]
+pub struct Conflict {
+    pub id: u32,
+    pub title: String,
+    pub base: Option<Task>,
+    pub mine: Option<Task>,
+    pub theirs: Option<Task>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Merged {
+    // By id
+    pub tasks: Vec<Task>,
+    pub next_id: u32,
+    // Tasks that came out different from this session's copy, deletions included
+    pub taken: usize,
+    // How each conflict was settled, by id
+    pub resolved: Vec<(u32, Resolution)>,
+    // (old, new) for tasks added here whose id the other process used meanwhile
+    pub renumbered: Vec<(u32, u32)>,
+}
+
+// Three-way merge of the tasks by uid: `base` as the list was loaded, `mine`
+// as this session has it and `theirs` as another process saved it. A side
+// changed a task when it added or deleted it or its updated_at moved. A task
+// only one side changed takes that side's copy; when both changed it to
+// different copies `resolve` picks, and a None leaves it unresolved. Err holds
+// every unresolved conflict. Tasks added here get fresh ids at or past
+// `next_id` where the other side added one with the same id.
+pub fn merge(
+    base: &[Task],
+    mine: &[Task],
+    theirs: &[Task],
+    next_id: u32,
+    resolve: &mut dyn FnMut(&Conflict) -> Option<Resolution>,
+) -> Result<Merged, Vec<Conflict>> {
+    let by_uid = |tasks: &'_ [Task]| -> HashMap<String, Task> {
+        tasks.iter().map(|task| (task.uid.clone(), task.clone())).collect()
+    };
+    let (base_by_uid, mine_by_uid, theirs_by_uid) = (by_uid(base), by_uid(mine), by_uid(theirs));
+    // By id, so conflicts come up in list order
+    let uids: BTreeSet<(u32, &String)> = [&base_by_uid, &mine_by_uid, &theirs_by_uid]
+        .into_iter()
+        .flat_map(|tasks| tasks.iter().map(|(uid, task)| (task.id, uid)))
+        .collect();
+    let mut listed = HashSet::new();
+    let uids: Vec<&String> = uids.into_iter().map(|(_, uid)| uid).filter(|uid| listed.insert(*uid)).collect();
+
+    let mut merged = Merged::default();
+    let mut unresolved = Vec::new();
+    // Each task kept, and whether its copy is this session's
+    let mut kept: Vec<(Task, bool)> = Vec::new();
+    for uid in uids {
+        let (b, m, t) = (base_by_uid.get(uid), mine_by_uid.get(uid), theirs_by_uid.get(uid));
+        let changed = |side: Option<&Task>| match (b, side) {
+            (Some(b), Some(side)) => b.updated_at != side.updated_at,
+            (None, None) => false,
+            _ => true,
+        };
+        let (result, ours) = match (changed(m), changed(t)) {
+            (false, _) => (t, false),
+            (true, false) => (m, true),
+            (true, true) if m == t => (m, true),
+            (true, true) => {
+                let Some(any) = m.or(t) else { continue };
+                let conflict = Conflict {
+                    id: any.id,
+                    title: any.title.clone(),
+                    base: b.cloned(),
+                    mine: m.cloned(),
+                    theirs: t.cloned(),
+                };
+                let Some(resolution) = resolve(&conflict) else {
+                    unresolved.push(conflict);
+                    continue;
+                };
+                merged.resolved.push((conflict.id, resolution));
+                match resolution {
+                    Resolution::Mine => (m, true),
+                    Resolution::Theirs => (t, false),
+                    Resolution::Skip => (b, false),
+                }
+            }
+        };
+        if result != m {
+            merged.taken += 1;
+        }
+        if let Some(task) = result {
+            kept.push((task.clone(), ours && b.is_none() && t.is_none()));
+        }
+    }
+    if !unresolved.is_empty() {
+        return Err(unresolved);
+    }
+
+    // Tasks added here move out of the way of ids the other side took
+    kept.sort_by_key(|(task, added_here)| (*added_here, task.id));
+    let mut used: HashSet<u32> = HashSet::new();
+    let mut next = kept.iter().map(|(task, _)| task.id + 1).max().unwrap_or(1).max(next_id);
+    let mut renumber: HashMap<u32, u32> = HashMap::new();
+    for (task, added_here) in &mut kept {
+        if *added_here && used.contains(&task.id) {
+            renumber.insert(task.id, next);
+            merged.renumbered.push((task.id, next));
+            task.id = next;
+            next += 1;
+        }
+        used.insert(task.id);
+    }
+    merged.tasks = kept
+        .into_iter()
+        .map(|(mut task, added_here)| {
+            if added_here {
+                for other in &mut task.relates_to {
+                    *other = renumber.get(other).copied().unwrap_or(*other);
+                }
+                task.relates_to.sort_unstable();
+            }
+            task
+        })
+        .collect();
+    merged.tasks.sort_by_key(|task| task.id);
+    merged.next_id = next;
+    Ok(merged)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, TimeZone, Utc};
+
+    use super::*;
+    use crate::Priority;
+
+    fn task(id: u32, title: &str) -> Task {
+        let mut task = Task::new(id, title.to_string(), String::new(), Priority::Medium);
+        task.updated_at = Utc.with_ymd_and_hms(2024, 7, 1, 9, 0, 0).unwrap();
+        task
+    }
+
+    // The task as a side saved it after changing the title
+    fn edited(task: &Task, title: &str, minutes: i64) -> Task {
+        Task { title: title.to_string(), updated_at: task.updated_at + Duration::minutes(minutes), ..task.clone() }
+    }
+
+    fn titles(merged: &Merged) -> Vec<(u32, &str)> {
+        merged.tasks.iter().map(|task| (task.id, task.title.as_str())).collect()
+    }
+
+    fn never(conflict: &Conflict) -> Option<Resolution> {
+        panic!("unexpected conflict on #{}", conflict.id)
+    }
+
+    #[test]
+    fn test_disjoint_edits_merge_without_asking() {
+        let base = vec![task(1, "Report"), task(2, "Review"), task(3, "Deploy")];
+        let mut added = task(4, "Added here");
+        added.relates_to = vec![1, 4];
+        let mine = vec![edited(&base[0], "Report v2", 5), base[1].clone(), base[2].clone(), added];
+        // They edited #2, deleted #3 and added their own #4
+        let theirs = vec![base[0].clone(), edited(&base[1], "Review notes", 7), task(4, "Added there")];
+
+        let merged = merge(&base, &mine, &theirs, 5, &mut never).unwrap();
+        assert_eq!(
+            titles(&merged),
+            [(1, "Report v2"), (2, "Review notes"), (4, "Added there"), (5, "Added here")]
+        );
+        assert_eq!(merged.renumbered, [(4, 5)]);
+        assert_eq!(merged.tasks[3].relates_to, [1, 5]);
+        assert_eq!(merged.next_id, 6);
+        // #2's edit, #3's deletion and their #4
+        assert_eq!(merged.taken, 3);
+        assert!(merged.resolved.is_empty());
+
+        // Nothing changed elsewhere: the merge is this session's list
+        let merged = merge(&base, &mine[..3], &base, 4, &mut never).unwrap();
+        assert_eq!(merged.tasks, mine[..3]);
+        assert_eq!((merged.taken, merged.next_id), (0, 4));
+    }
+
+    #[test]
+    fn test_same_task_edits_are_resolved_per_task() {
+        let base = vec![task(1, "Report"), task(2, "Review")];
+        let mine = vec![edited(&base[0], "Mine", 5), edited(&base[1], "Same", 5)];
+        // The same copy on both sides is not a conflict
+        let theirs = vec![edited(&base[0], "Theirs", 9), edited(&base[1], "Same", 5)];
+
+        let pick = |resolution| {
+            let mut asked = Vec::new();
+            let merged = merge(&base, &mine, &theirs, 3, &mut |conflict| {
+                asked.push((conflict.id, conflict.mine.clone().unwrap().title, conflict.theirs.clone().unwrap().title));
+                Some(resolution)
+            })
+            .unwrap();
+            assert_eq!(asked, [(1, "Mine".to_string(), "Theirs".to_string())]);
+            assert_eq!(merged.resolved, [(1, resolution)]);
+            merged
+        };
+        assert_eq!(titles(&pick(Resolution::Mine)), [(1, "Mine"), (2, "Same")]);
+        assert_eq!(titles(&pick(Resolution::Theirs)), [(1, "Theirs"), (2, "Same")]);
+        assert_eq!(titles(&pick(Resolution::Skip)), [(1, "Report"), (2, "Same")]);
+
+        let unresolved = merge(&base, &mine, &theirs, 3, &mut |_| None).unwrap_err();
+        assert_eq!(unresolved.len(), 1);
+        assert_eq!((unresolved[0].id, unresolved[0].title.as_str()), (1, "Mine"));
+    }
+
+    #[test]
+    fn test_delete_against_edit_is_a_conflict() {
+        let base = vec![task(1, "Report"), task(2, "Review"), task(3, "Deploy")];
+        // We deleted #1 and edited #2; they edited #1 and deleted #2. Nobody touched #3.
+        let mine = vec![edited(&base[1], "Review v2", 5), base[2].clone()];
+        let theirs = vec![edited(&base[0], "Report v2", 5), base[2].clone()];
+
+        let mut conflicts = Vec::new();
+        let merged = merge(&base, &mine, &theirs, 4, &mut |conflict| {
+            conflicts.push((conflict.id, conflict.mine.is_some(), conflict.theirs.is_some()));
+            Some(Resolution::Mine)
+        })
+        .unwrap();
+        assert_eq!(conflicts, [(1, false, true), (2, true, false)]);
+        assert_eq!(titles(&merged), [(2, "Review v2"), (3, "Deploy")]);
+
+        let merged = merge(&base, &mine, &theirs, 4, &mut |_| Some(Resolution::Theirs)).unwrap();
+        assert_eq!(titles(&merged), [(1, "Report v2"), (3, "Deploy")]);
+        assert_eq!(merged.taken, 2);
+
+        // A deletion of a task the other side left alone just goes through
+        let merged = merge(&base, &base[1..], &base, 4, &mut never).unwrap();
+        assert_eq!(titles(&merged), [(2, "Review"), (3, "Deploy")]);
+    }
+}
@@ -0,0 +1,98 @@
+use std::fmt::Write;
+
+use chrono::NaiveDate;
+
+use crate::{Priority, Task, TaskStatus};
+
+// Content-Type of the Prometheus text exposition format
+pub const CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+const STATUSES: [(TaskStatus, &str); 3] = [
+    (TaskStatus::Pending, "pending"),
+    (TaskStatus::InProgress, "in_progress"),
+    (TaskStatus::Completed, "completed"),
+];
+
+const PRIORITIES: [(Priority, &str); 4] = [
+    (Priority::Low, "low"),
+    (Priority::Medium, "medium"),
+    (Priority::High, "high"),
+    (Priority::Critical, "critical"),
+];
+
+// Gauges over the task list plus the server's command counter. Every series is
+// written even when zero, so dashboards never see one disappear.
+pub fn render<'a>(tasks: impl IntoIterator<Item = &'a Task>, today: NaiveDate, commands: u64) -> String {
+    let tasks: Vec<&Task> = tasks.into_iter().collect();
+    let mut out = String::new();
+
+    family(&mut out, "tasks_total", "gauge", "Number of tasks.");
+    writeln!(out, "tasks_total {}", tasks.len()).unwrap();
+
+    family(&mut out, "tasks_by_status", "gauge", "Number of tasks in each status.");
+    for (status, label) in &STATUSES {
+        let count = tasks.iter().filter(|task| task.status == *status).count();
+        writeln!(out, "tasks_by_status{{status=\"{}\"}} {}", label_value(label), count).unwrap();
+    }
+
+    family(&mut out, "tasks_by_priority", "gauge", "Number of tasks at each priority.");
+    for (priority, label) in &PRIORITIES {
+        let count = tasks.iter().filter(|task| task.priority == *priority).count();
+        writeln!(out, "tasks_by_priority{{priority=\"{}\"}} {}", label_value(label), count).unwrap();
+    }
+
+    family(&mut out, "tasks_overdue", "gauge", "Open tasks whose due date has passed.");
+    let overdue = tasks
+        .iter()
+        .filter(|task| task.status != TaskStatus::Completed && task.due.is_some_and(|due| due < today))
+        .count();
+    writeln!(out, "tasks_overdue {}", overdue).unwrap();
+
+    family(&mut out, "task_commands_total", "counter", "API commands handled since the server started.");
+    writeln!(out, "task_commands_total {}", commands).unwrap();
+    out
+}
+
+fn family(out: &mut String, name: &str, kind: &str, help: &str) {
+    writeln!(out, "# HELP {} {}", name, help).unwrap();
+    writeln!(out, "# TYPE {} {}", name, kind).unwrap();
+}
+
+// Escape a label value as the exposition format requires: backslash, quote and newline
+pub fn label_value(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '\\' => out.push_str("\\\\"),
+            '"' => out.push_str("\\\""),
+            '\n' => out.push_str("\\n"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_counts_and_escaping() {
+        let today = NaiveDate::from_ymd_opt(2024, 7, 8).unwrap();
+        let mut tasks = vec![
+            Task::new(1, "Late".to_string(), String::new(), Priority::Critical),
+            Task::new(2, "Done late".to_string(), String::new(), Priority::Low),
+        ];
+        tasks[0].due = NaiveDate::from_ymd_opt(2024, 7, 1);
+        tasks[1].due = NaiveDate::from_ymd_opt(2024, 7, 1);
+        tasks[1].update_status(TaskStatus::Completed);
+
+        let text = render(&tasks, today, 7);
+        assert!(text.contains("\ntasks_total 2\n"));
+        assert!(text.contains("tasks_by_status{status=\"completed\"} 1\n"));
+        assert!(text.contains("tasks_by_priority{priority=\"high\"} 0\n"));
+        assert!(text.contains("\ntasks_overdue 1\n"));
+        assert!(text.ends_with("# TYPE task_commands_total counter\ntask_commands_total 7\n"));
+        assert_eq!(label_value("a\"b\\c\nd"), "a\\\"b\\\\c\\nd");
+    }
+}
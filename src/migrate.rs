@@ -0,0 +1,329 @@
+use std::fmt;
+use std::str::FromStr;
+
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
+use serde::Serialize;
+use serde_json::{Map, Value, json};
+
+use crate::storage::SavedState;
+use crate::{Energy, Priority};
+
+// One change the lenient loader made to read a file written in an older or
+// hand-written shape
+#[derive(Debug, Clone, PartialEq)]
+pub struct Coercion {
+    // The task's id as the file gives it, or its place in the list when it has none
+    pub task: Option<u64>,
+    pub field: String,
+    pub from: String,
+    pub to: String,
+}
+
+impl fmt::Display for Coercion {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.task {
+            Some(id) => write!(f, "task {}: {} {} read as {}", id, self.field, self.from, self.to),
+            None => write!(f, "{} {} read as {}", self.field, self.from, self.to),
+        }
+    }
+}
+
+// A file read leniently, with every coercion it took
+#[derive(Debug)]
+pub struct Migrated {
+    pub state: SavedState,
+    pub coercions: Vec<Coercion>,
+}
+
+// Read a JSON data file the strict loader refused: a bare list of tasks, tasks
+// missing fields the current format requires, statuses like "in_progress" or
+// "done", priorities as numbers 1 to 4 or lowercase words, tags as one
+// comma-separated string, timestamps as Unix seconds or plain dates. Fails only
+// on what cannot be read at all, such as a task without a title.
+pub fn decode_lenient(contents: &[u8]) -> Result<Migrated, String> {
+    let value: Value = serde_json::from_slice(contents).map_err(|e| e.to_string())?;
+    let mut coercions = Vec::new();
+    let mut file = match value {
+        Value::Array(tasks) => {
+            coercions.push(coercion(None, "file", "a bare list of tasks", "{\"tasks\": [...]}"));
+            Map::from_iter([("tasks".to_string(), Value::Array(tasks))])
+        }
+        Value::Object(file) => file,
+        other => return Err(format!("expected an object or a list of tasks, found {}", type_name(&other))),
+    };
+    let Some(Value::Array(tasks)) = file.get_mut("tasks") else {
+        return Err("no \"tasks\" list".to_string());
+    };
+
+    for (n, task) in tasks.iter_mut().enumerate() {
+        let Value::Object(task) = task else {
+            return Err(format!("task {} in the list is not an object", n + 1));
+        };
+        migrate_task(task, n, &mut coercions)?;
+    }
+    // Tasks that came without an id take the ones after the highest given
+    let mut next = tasks.iter().filter_map(|task| task["id"].as_u64()).max().unwrap_or(0) + 1;
+    for task in tasks.iter_mut().filter(|task| !task["id"].is_u64()) {
+        let title = task["title"].as_str().unwrap_or_default().to_string();
+        coercions.push(coercion(None, "id", &format!("missing on '{}'", title), &next.to_string()));
+        task["id"] = json!(next);
+        next += 1;
+    }
+    match file.get("next_id").and_then(Value::as_u64) {
+        Some(given) if given >= next => {}
+        given => {
+            let from = given.map_or_else(|| "missing".to_string(), |given| given.to_string());
+            coercions.push(coercion(None, "next_id", &from, &next.to_string()));
+            file.insert("next_id".to_string(), json!(next));
+        }
+    }
+
+    let state = serde_json::from_value(Value::Object(file)).map_err(|e| e.to_string())?;
+    Ok(Migrated { state, coercions })
+}
+
+fn coercion(task: Option<u64>, field: &str, from: &str, to: &str) -> Coercion {
+    Coercion { task, field: field.to_string(), from: from.to_string(), to: to.to_string() }
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "a boolean",
+        Value::Number(_) => "a number",
+        Value::String(_) => "a string",
+        Value::Array(_) => "a list",
+        Value::Object(_) => "an object",
+    }
+}
+
+fn migrate_task(task: &mut Map<String, Value>, n: usize, coercions: &mut Vec<Coercion>) -> Result<(), String> {
+    // Ids written as text count as given
+    if let Some(id) = task.get("id").and_then(Value::as_str).and_then(|id| id.trim().parse::<u64>().ok()) {
+        task.insert("id".to_string(), json!(id));
+    }
+    let label = task.get("id").and_then(Value::as_u64).or(Some(n as u64 + 1));
+    let mut note = |field: &str, from: &Value, to: &Value| coercions.push(coercion(label, field, &from.to_string(), &to.to_string()));
+
+    match task.get("title") {
+        Some(Value::String(_)) => {}
+        Some(title @ (Value::Number(_) | Value::Bool(_))) => {
+            let text = json!(title.to_string());
+            note("title", &title.clone(), &text);
+            task.insert("title".to_string(), text);
+        }
+        _ => return Err(format!("task {} has no title", label.unwrap_or_default())),
+    }
+
+    for (field, default) in [("description", json!("")), ("tags", json!([]))] {
+        if task.get(field).is_none_or(Value::is_null) {
+            note(field, &json!("missing"), &default);
+            task.insert(field.to_string(), default);
+        }
+    }
+    if let Some(Value::String(tags)) = task.get("tags") {
+        let from = json!(tags);
+        let list: Vec<&str> = tags.split(',').map(str::trim).filter(|tag| !tag.is_empty()).collect();
+        let to = json!(list);
+        note("tags", &from, &to);
+        task.insert("tags".to_string(), to);
+    }
+
+    let priority = match task.get("priority") {
+        None | Some(Value::Null) => Some(json!("Medium")),
+        Some(Value::Number(n)) => Some(match n.as_u64() {
+            Some(1) => json!("Low"),
+            Some(2) => json!("Medium"),
+            Some(3) => json!("High"),
+            Some(4) => json!("Critical"),
+            _ => return Err(format!("task {}: priority {} is not 1 to 4", label.unwrap_or_default(), n)),
+        }),
+        Some(Value::String(word)) => canonical::<Priority>(word),
+        Some(_) => None,
+    };
+    if let Some(to) = priority {
+        note("priority", task.get("priority").unwrap_or(&json!("missing")), &to);
+        task.insert("priority".to_string(), to);
+    }
+
+    // The earliest files marked finished tasks with `"completed": true`
+    let done = task.remove("completed").and_then(|done| done.as_bool());
+    let status = match (task.get("status"), done) {
+        (None | Some(Value::Null), done) => Some(json!(if done == Some(true) { "Completed" } else { "Pending" })),
+        (Some(Value::String(word)), _) => legacy_status(word).filter(|to| to != word).map(|to| json!(to)),
+        _ => None,
+    };
+    if let Some(to) = status {
+        let from = match (task.get("status"), done) {
+            (Some(from), _) if !from.is_null() => from.clone(),
+            (_, Some(done)) => json!(format!("completed: {}", done)),
+            _ => json!("missing"),
+        };
+        note("status", &from, &to);
+        task.insert("status".to_string(), to);
+    }
+
+    if let Some(Value::String(word)) = task.get("energy")
+        && let Some(to) = canonical::<Energy>(word)
+    {
+        note("energy", &json!(word), &to);
+        task.insert("energy".to_string(), to);
+    }
+
+    for field in ["due", "last_notified", "snoozed_until"] {
+        if let Some(Value::String(text)) = task.get(field)
+            && NaiveDate::parse_from_str(text, "%Y-%m-%d").is_err()
+            && let Some(date) = parse_timestamp(text).map(|at| at.date_naive())
+        {
+            let to = json!(date);
+            note(field, &json!(text), &to);
+            task.insert(field.to_string(), to);
+        }
+    }
+    for field in ["created_at", "updated_at"] {
+        let to = match task.get(field) {
+            Some(Value::Number(n)) => n.as_i64().and_then(|secs| DateTime::from_timestamp(secs, 0)),
+            Some(Value::String(text)) if DateTime::parse_from_rfc3339(text).is_err() => parse_timestamp(text),
+            _ => None,
+        };
+        if let Some(to) = to {
+            let to = json!(to);
+            note(field, &task[field].clone(), &to);
+            task.insert(field.to_string(), to);
+        }
+    }
+    Ok(())
+}
+
+// `word` as the current format writes it, when it reads but is written differently:
+// "high" for High
+fn canonical<T: FromStr + Serialize>(word: &str) -> Option<Value> {
+    let value = serde_json::to_value(word.parse::<T>().ok()?).ok()?;
+    (value != word).then_some(value)
+}
+
+// Status words from older files and other tools, as the current format writes them
+fn legacy_status(word: &str) -> Option<&'static str> {
+    let folded: String = word.chars().filter(|c| c.is_alphanumeric()).collect::<String>().to_lowercase();
+    match folded.as_str() {
+        "pending" | "todo" | "open" | "new" => Some("Pending"),
+        "inprogress" | "progress" | "doing" | "started" | "active" => Some("InProgress"),
+        "completed" | "complete" | "done" | "closed" | "finished" => Some("Completed"),
+        _ => None,
+    }
+}
+
+// "2024-07-01 09:30:00", "2024-07-01T09:30:00" or a plain date, read as UTC
+fn parse_timestamp(text: &str) -> Option<DateTime<Utc>> {
+    if let Ok(at) = DateTime::parse_from_rfc3339(text) {
+        return Some(at.with_timezone(&Utc));
+    }
+    ["%Y-%m-%d %H:%M:%S", "%Y-%m-%dT%H:%M:%S"]
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(text, format).ok())
+        .or_else(|| NaiveDate::parse_from_str(text, "%Y-%m-%d").ok().and_then(|date| date.and_hms_opt(0, 0, 0)))
+        .map(|at| at.and_utc())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Task, TaskStatus};
+
+    fn load(contents: &str) -> Migrated {
+        decode_lenient(contents.as_bytes()).unwrap()
+    }
+
+    // The task a fixture should load to; what the file leaves out (uid and
+    // timestamps) is taken from the loaded one
+    fn expected(loaded: &Task, id: u32, title: &str, description: &str, priority: Priority, status: TaskStatus) -> Task {
+        let mut task = Task::new(id, title.to_string(), description.to_string(), priority);
+        task.status = status;
+        task.uid = loaded.uid.clone();
+        task.created_at = loaded.created_at;
+        task.updated_at = loaded.updated_at;
+        task
+    }
+
+    #[test]
+    fn test_bare_list() {
+        let migrated = load(include_str!("../tests/fixtures/legacy_bare_list.json"));
+        let tasks = &migrated.state.tasks;
+        let mut taxes = expected(&tasks[1], 2, "File taxes", "Before the 15th", Priority::High, TaskStatus::Completed);
+        taxes.tags = vec!["home".into()];
+        assert_eq!(tasks[0], expected(&tasks[0], 1, "Buy milk", "", Priority::Low, TaskStatus::Pending));
+        assert_eq!(tasks[1], taxes);
+        assert_eq!(migrated.state.next_id, 3);
+        let fields: Vec<&str> = migrated.coercions.iter().map(|c| c.field.as_str()).collect();
+        assert_eq!(fields, ["file", "next_id"]);
+    }
+
+    #[test]
+    fn test_snake_case_statuses_and_lowercase_priorities() {
+        let migrated = load(include_str!("../tests/fixtures/legacy_statuses.json"));
+        let tasks = &migrated.state.tasks;
+        assert_eq!(tasks[0], expected(&tasks[0], 1, "Draft spec", "", Priority::High, TaskStatus::InProgress));
+        assert_eq!(tasks[1], expected(&tasks[1], 2, "Review PR", "", Priority::Medium, TaskStatus::Completed));
+        assert_eq!(tasks[2], expected(&tasks[2], 3, "Plan sprint", "", Priority::Low, TaskStatus::Pending));
+        let lines: Vec<String> = migrated.coercions.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            lines,
+            [
+                "task 1: priority \"high\" read as \"High\"",
+                "task 1: status \"in_progress\" read as \"InProgress\"",
+                "task 2: priority \"medium\" read as \"Medium\"",
+                "task 2: status \"done\" read as \"Completed\"",
+                "task 3: status \"todo\" read as \"Pending\"",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_integer_priorities() {
+        let migrated = load(include_str!("../tests/fixtures/legacy_int_priority.json"));
+        let priorities: Vec<Priority> = migrated.state.tasks.iter().map(|task| task.priority.clone()).collect();
+        assert_eq!(priorities, [Priority::Low, Priority::Medium, Priority::High, Priority::Critical]);
+        assert_eq!(migrated.coercions.len(), 4);
+        assert_eq!(migrated.coercions[3].to_string(), "task 4: priority 4 read as \"Critical\"");
+
+        let out_of_range = r#"[{ "id": 1, "title": "Odd", "priority": 9 }]"#;
+        assert_eq!(decode_lenient(out_of_range.as_bytes()).unwrap_err(), "task 1: priority 9 is not 1 to 4");
+    }
+
+    #[test]
+    fn test_missing_fields_and_completed_flag() {
+        let migrated = load(include_str!("../tests/fixtures/legacy_minimal.json"));
+        let tasks = &migrated.state.tasks;
+        assert_eq!(tasks[0], expected(&tasks[0], 3, "Call the bank", "", Priority::Medium, TaskStatus::Pending));
+        // Tasks without an id follow the highest one given
+        assert_eq!(tasks[1], expected(&tasks[1], 4, "Renew passport", "", Priority::Medium, TaskStatus::Completed));
+        let mut numbered = expected(&tasks[2], 5, "2024", "", Priority::Medium, TaskStatus::Pending);
+        numbered.tags = vec!["admin".into(), "travel".into()];
+        assert_eq!(tasks[2], numbered);
+        assert_eq!(migrated.state.next_id, 6);
+
+        let lines: Vec<String> = migrated.coercions.iter().map(ToString::to_string).collect();
+        assert!(lines.contains(&"task 2: status \"completed: true\" read as \"Completed\"".to_string()));
+        assert!(lines.contains(&"task 3: tags \"admin, travel\" read as [\"admin\",\"travel\"]".to_string()));
+        assert!(lines.contains(&"id missing on 'Renew passport' read as 4".to_string()));
+        assert!(lines.contains(&"next_id missing read as 6".to_string()));
+
+        let untitled = r#"{ "tasks": [{ "id": 1, "description": "no title" }] }"#;
+        assert_eq!(decode_lenient(untitled.as_bytes()).unwrap_err(), "task 1 has no title");
+        assert!(decode_lenient(b"\"tasks\"").is_err());
+    }
+
+    #[test]
+    fn test_timestamps() {
+        let migrated = load(include_str!("../tests/fixtures/legacy_timestamps.json"));
+        let task = &migrated.state.tasks[0];
+        let mut invoice = expected(task, 1, "Send invoice", "March hours", Priority::Medium, TaskStatus::Pending);
+        invoice.tags = vec!["work".into()];
+        invoice.due = NaiveDate::from_ymd_opt(2024, 7, 5);
+        invoice.created_at = DateTime::from_timestamp(1719826200, 0).unwrap();
+        invoice.updated_at = "2024-07-02T08:00:00Z".parse().unwrap();
+        assert_eq!(*task, invoice);
+        let fields: Vec<&str> = migrated.coercions.iter().map(|c| c.field.as_str()).collect();
+        assert_eq!(fields, ["due", "created_at", "updated_at"]);
+    }
+}
@@ -0,0 +1,192 @@
+use std::io::{self, Write};
+
+use chrono::{Duration, NaiveDate, NaiveDateTime, NaiveTime};
+
+use crate::dates::{format_date, format_offset};
+use crate::{Task, TaskManager, TaskStatus, msg};
+
+// Open tasks due by `now + within_hours` (overdue ones included) that have not
+// already been reminded about on `now`'s date
+pub fn due_reminders<'a>(
+    tasks: impl IntoIterator<Item = &'a Task>,
+    now: NaiveDateTime,
+    within_hours: u32,
+) -> Vec<&'a Task> {
+    let today = now.date();
+    // Past the end of the calendar everything due is in range
+    let horizon = Duration::try_hours(i64::from(within_hours))
+        .and_then(|within| now.checked_add_signed(within))
+        .map_or(NaiveDate::MAX, |horizon| horizon.date());
+    tasks
+        .into_iter()
+        .filter(|task| task.status != TaskStatus::Completed)
+        .filter(|task| task.due.is_some_and(|due| due <= horizon))
+        .filter(|task| task.last_notified != Some(today))
+        .collect()
+}
+
+// When the reminder `offset` before `due` goes out; a date is due from its start
+pub fn reminder_at(due: NaiveDate, offset: std::time::Duration) -> NaiveDateTime {
+    let offset = Duration::from_std(offset).unwrap_or(Duration::MAX);
+    due.and_time(NaiveTime::MIN).checked_sub_signed(offset).unwrap_or(NaiveDateTime::MIN)
+}
+
+// Each reminder offset on an open task that has come round by `now` and not fired
+pub fn offset_reminders<'a>(
+    tasks: impl IntoIterator<Item = &'a Task>,
+    now: NaiveDateTime,
+) -> Vec<(&'a Task, std::time::Duration)> {
+    tasks
+        .into_iter()
+        .filter(|task| task.status != TaskStatus::Completed)
+        .flat_map(|task| {
+            let due = task.due;
+            task.reminders
+                .iter()
+                .filter(move |reminder| !reminder.fired && due.is_some_and(|due| reminder_at(due, reminder.offset) <= now))
+                .map(move |reminder| (task, reminder.offset))
+        })
+        .collect()
+}
+
+// Raise every offset reminder due at `now` and mark it fired; returns the tasks reminded
+pub fn send_offset_reminders(manager: &mut TaskManager, now: NaiveDateTime, notifier: &dyn Notifier) -> Vec<u32> {
+    let due: Vec<(u32, std::time::Duration, (String, String))> = offset_reminders(manager.list_tasks(), now)
+        .into_iter()
+        .map(|(task, offset)| (task.id, offset, offset_reminder_text(task, offset)))
+        .collect();
+    let mut reminded = Vec::new();
+    for (id, offset, (summary, body)) in due {
+        match notifier.notify(&summary, &body) {
+            Ok(()) => {
+                let _ = manager.mark_reminder_fired(id, offset);
+                reminded.push(id);
+            }
+            Err(e) => log::warn!("reminder for task {} failed: {}", id, e),
+        }
+    }
+    reminded
+}
+
+pub fn offset_reminder_text(task: &Task, offset: std::time::Duration) -> (String, String) {
+    let due = task.due.expect("reminders are only raised for tasks with a due date");
+    (
+        msg!("reminder_due_offset", date = format_date(due), offset = format_offset(offset)),
+        msg!("reminder_body", id = task.id, title = task.title),
+    )
+}
+
+// Notification text for one task: (summary, body)
+pub fn reminder_text(task: &Task, now: NaiveDateTime) -> (String, String) {
+    let due = task.due.expect("reminders are only raised for tasks with a due date");
+    let summary = if due < now.date() {
+        msg!("reminder_overdue", date = format_date(due))
+    } else if due == now.date() {
+        msg!("reminder_due_today")
+    } else {
+        msg!("reminder_due", date = format_date(due))
+    };
+    (summary, msg!("reminder_body", id = task.id, title = task.title))
+}
+
+// Somewhere to show a reminder
+pub trait Notifier {
+    fn notify(&self, summary: &str, body: &str) -> Result<(), String>;
+}
+
+// Rings the terminal bell and prints a banner
+pub struct TerminalNotifier;
+
+impl Notifier for TerminalNotifier {
+    fn notify(&self, summary: &str, body: &str) -> Result<(), String> {
+        let mut out = io::stdout();
+        writeln!(out, "\x07*** {}: {} ***", summary, body).map_err(|e| e.to_string())
+    }
+}
+
+// Desktop notifications, falling back to the terminal when none can be shown
+#[cfg(feature = "desktop-notify")]
+pub struct DesktopNotifier;
+
+#[cfg(feature = "desktop-notify")]
+impl Notifier for DesktopNotifier {
+    fn notify(&self, summary: &str, body: &str) -> Result<(), String> {
+        let shown = notify_rust::Notification::new()
+            .appname("Task Manager")
+            .summary(summary)
+            .body(body)
+            .show();
+        match shown {
+            Ok(_) => Ok(()),
+            Err(e) => {
+                log::warn!("desktop notification failed, using the terminal: {}", e);
+                TerminalNotifier.notify(summary, body)
+            }
+        }
+    }
+}
+
+#[cfg(feature = "desktop-notify")]
+pub fn default_notifier() -> Box<dyn Notifier> {
+    Box::new(DesktopNotifier)
+}
+
+#[cfg(not(feature = "desktop-notify"))]
+pub fn default_notifier() -> Box<dyn Notifier> {
+    Box::new(TerminalNotifier)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Priority;
+    use chrono::NaiveDate;
+
+    fn task(id: u32, due: Option<(i32, u32, u32)>) -> Task {
+        let mut task = Task::new(id, format!("Task {}", id), String::new(), Priority::Medium);
+        task.due = due.and_then(|(y, m, d)| NaiveDate::from_ymd_opt(y, m, d));
+        task
+    }
+
+    fn at(y: i32, m: u32, d: u32, hour: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap().and_hms_opt(hour, 0, 0).unwrap()
+    }
+
+    #[test]
+    fn test_due_today_and_overdue_only() {
+        let mut done = task(4, Some((2024, 7, 1)));
+        done.update_status(TaskStatus::Completed);
+        let tasks = vec![
+            task(1, Some((2024, 7, 1))),
+            task(2, Some((2024, 6, 20))),
+            task(3, Some((2024, 7, 2))),
+            done,
+            task(5, None),
+        ];
+        let ids = |found: Vec<&Task>| found.iter().map(|t| t.id).collect::<Vec<_>>();
+        assert_eq!(ids(due_reminders(&tasks, at(2024, 7, 1, 9), 0)), vec![1, 2]);
+        // 9:00 + 20h reaches into the next day
+        assert_eq!(ids(due_reminders(&tasks, at(2024, 7, 1, 9), 20)), vec![1, 2, 3]);
+        assert_eq!(ids(due_reminders(&tasks, at(2024, 7, 1, 9), u32::MAX)), vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn test_already_notified_today_is_skipped() {
+        let mut reminded = task(1, Some((2024, 7, 1)));
+        reminded.last_notified = NaiveDate::from_ymd_opt(2024, 7, 1);
+        let tasks = vec![reminded];
+        assert!(due_reminders(&tasks, at(2024, 7, 1, 18), 0).is_empty());
+        // A new day fires again
+        assert_eq!(due_reminders(&tasks, at(2024, 7, 2, 8), 0).len(), 1);
+    }
+
+    #[test]
+    fn test_reminder_text_names_the_task() {
+        let overdue = task(7, Some((2024, 6, 30)));
+        assert_eq!(
+            reminder_text(&overdue, at(2024, 7, 1, 9)),
+            ("Overdue since 2024-06-30".to_string(), "#7 Task 7".to_string())
+        );
+        assert_eq!(reminder_text(&task(8, Some((2024, 7, 1))), at(2024, 7, 1, 9)).0, "Due today");
+    }
+}
@@ -0,0 +1,111 @@
+use std::collections::{BTreeSet, HashSet};
+
+use chrono::NaiveDate;
+
+use crate::{TaskManager, TaskStatus};
+
+// Spots open tasks that become overdue while the REPL is open. Tasks not yet
+// overdue wait in due-date order, so a check only looks at the ones that have
+// just crossed over; the list is read again from the tasks after they change.
+pub struct OverdueWatch {
+    upcoming: BTreeSet<(NaiveDate, u32)>,
+    // Alerted about this session, so never again
+    alerted: HashSet<u32>,
+    // The day of the last check and the manager generation `upcoming` was read at
+    checked: NaiveDate,
+    generation: u64,
+}
+
+impl OverdueWatch {
+    // Tasks overdue already on `today` are in the startup summary and do not alert
+    pub fn new(manager: &TaskManager, today: NaiveDate) -> Self {
+        let mut watch = OverdueWatch { upcoming: BTreeSet::new(), alerted: HashSet::new(), checked: today, generation: 0 };
+        watch.read(manager);
+        watch
+    }
+
+    // Open tasks that were not overdue at the last check and are not yet alerted
+    fn read(&mut self, manager: &TaskManager) {
+        self.upcoming = manager
+            .list_tasks()
+            .into_iter()
+            .filter(|task| task.status != TaskStatus::Completed && !self.alerted.contains(&task.id))
+            .filter_map(|task| task.due.filter(|due| *due >= self.checked).map(|due| (due, task.id)))
+            .collect();
+        self.generation = manager.generation();
+    }
+
+    // Tasks that became overdue since the last check, by due date. Costs as
+    // much as the tasks that crossed over unless the tasks changed since.
+    pub fn check(&mut self, manager: &TaskManager, today: NaiveDate) -> Vec<u32> {
+        if manager.generation() != self.generation {
+            self.read(manager);
+        }
+        let mut overdue = Vec::new();
+        while let Some(&(due, id)) = self.upcoming.first() {
+            if due >= today {
+                break;
+            }
+            self.upcoming.pop_first();
+            if self.alerted.insert(id) {
+                overdue.push(id);
+            }
+        }
+        self.checked = self.checked.max(today);
+        overdue
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Priority, TaskEdit};
+
+    fn date(day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2024, 7, day).unwrap()
+    }
+
+    fn add(manager: &mut TaskManager, title: &str, due: Option<NaiveDate>) -> u32 {
+        let id = manager.add_task(title.to_string(), String::new(), Priority::Medium).unwrap();
+        if due.is_some() {
+            manager.apply_edit(id, TaskEdit { due, ..TaskEdit::default() }).unwrap();
+        }
+        id
+    }
+
+    #[test]
+    fn test_alerts_once_when_the_day_turns() {
+        let mut manager = TaskManager::new();
+        let late = add(&mut manager, "Already late", Some(date(1)));
+        let today = add(&mut manager, "Due today", Some(date(3)));
+        let tomorrow = add(&mut manager, "Due tomorrow", Some(date(4)));
+        add(&mut manager, "Undated", None);
+        let mut watch = OverdueWatch::new(&manager, date(3));
+
+        // Overdue from the start, and nothing has crossed yet
+        assert!(watch.check(&manager, date(3)).is_empty());
+        assert_eq!(watch.check(&manager, date(4)), [today]);
+        assert!(watch.check(&manager, date(4)).is_empty());
+        assert_eq!(watch.check(&manager, date(6)), [tomorrow]);
+        assert!(watch.check(&manager, date(7)).is_empty());
+        assert!(!watch.alerted.contains(&late));
+    }
+
+    #[test]
+    fn test_changed_tasks_are_read_again() {
+        let mut manager = TaskManager::new();
+        let report = add(&mut manager, "Report", Some(date(5)));
+        let done = add(&mut manager, "Done early", Some(date(4)));
+        let mut watch = OverdueWatch::new(&manager, date(3));
+
+        manager.update_task_status(done, TaskStatus::Completed).unwrap();
+        let added = add(&mut manager, "Added later", Some(date(4)));
+        manager.apply_edit(report, TaskEdit { due: Some(date(10)), ..TaskEdit::default() }).unwrap();
+        assert_eq!(watch.check(&manager, date(6)), [added]);
+
+        // Moving an alerted task's due date does not alert it again
+        manager.apply_edit(added, TaskEdit { due: Some(date(8)), ..TaskEdit::default() }).unwrap();
+        assert_eq!(watch.check(&manager, date(11)), [report]);
+        assert_eq!(watch.upcoming.len(), 0);
+    }
+}
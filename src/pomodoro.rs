@@ -0,0 +1,150 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+use chrono::Utc;
+
+use crate::WorkSession;
+
+pub const DEFAULT_MINUTES: u64 = 25;
+
+// How often the remaining time is redrawn
+const REDRAW_EVERY: Duration = Duration::from_secs(60);
+
+// How often the countdown looks at the stop flag between redraws
+const STOP_POLL: Duration = Duration::from_millis(250);
+
+// Where the countdown gets the time, so tests can run it without sleeping
+pub trait Clock {
+    fn now(&self) -> Instant;
+    fn sleep(&self, duration: Duration);
+}
+
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    fn sleep(&self, duration: Duration) {
+        std::thread::sleep(duration);
+    }
+}
+
+// Count `length` down, calling `redraw` with the time left at the start and once
+// a minute after. Setting `stop` ends it early; either way the session covers the
+// time actually spent.
+pub fn countdown(clock: &dyn Clock, length: Duration, stop: &AtomicBool, redraw: &mut dyn FnMut(Duration)) -> WorkSession {
+    let started_at = Utc::now();
+    let start = clock.now();
+    let mut next_redraw = start;
+    let (elapsed, completed) = loop {
+        let now = clock.now();
+        let elapsed = now - start;
+        if elapsed >= length {
+            break (length, true);
+        }
+        if stop.load(Ordering::SeqCst) {
+            break (elapsed, false);
+        }
+        if now >= next_redraw {
+            redraw(length - elapsed);
+            next_redraw += REDRAW_EVERY;
+        }
+        clock.sleep(STOP_POLL.min(length - elapsed).min(next_redraw - now));
+    };
+    WorkSession { started_at, seconds: elapsed.as_secs(), completed }
+}
+
+// Raised by Ctrl+C for as long as it is alive; the usual Ctrl+C comes back when dropped
+pub struct Interrupt {
+    pub flag: Arc<AtomicBool>,
+    #[cfg(unix)]
+    id: Option<signal_hook::SigId>,
+}
+
+impl Interrupt {
+    #[cfg(unix)]
+    pub fn catch() -> Interrupt {
+        let flag = Arc::new(AtomicBool::new(false));
+        let id = signal_hook::flag::register(signal_hook::consts::SIGINT, Arc::clone(&flag))
+            .map_err(|e| log::warn!("cannot catch Ctrl+C, it will end the program: {}", e))
+            .ok();
+        Interrupt { flag, id }
+    }
+
+    #[cfg(not(unix))]
+    pub fn catch() -> Interrupt {
+        Interrupt { flag: Arc::new(AtomicBool::new(false)) }
+    }
+}
+
+#[cfg(unix)]
+impl Drop for Interrupt {
+    fn drop(&mut self) {
+        if let Some(id) = self.id.take() {
+            signal_hook::low_level::unregister(id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::{Cell, RefCell};
+
+    use super::*;
+
+    // Time moves only when the countdown sleeps; `stop_at` raises the flag once reached
+    struct FakeClock {
+        start: Instant,
+        now: Cell<Instant>,
+        sleeps: RefCell<Vec<Duration>>,
+        stop_at: Option<(Duration, &'static AtomicBool)>,
+    }
+
+    impl FakeClock {
+        fn new(stop_at: Option<(Duration, &'static AtomicBool)>) -> FakeClock {
+            let start = Instant::now();
+            FakeClock { start, now: Cell::new(start), sleeps: RefCell::new(Vec::new()), stop_at }
+        }
+    }
+
+    impl Clock for FakeClock {
+        fn now(&self) -> Instant {
+            self.now.get()
+        }
+
+        fn sleep(&self, duration: Duration) {
+            self.sleeps.borrow_mut().push(duration);
+            self.now.set(self.now.get() + duration);
+            if let Some((at, flag)) = self.stop_at
+                && self.now.get() - self.start >= at
+            {
+                flag.store(true, Ordering::SeqCst);
+            }
+        }
+    }
+
+    #[test]
+    fn test_full_countdown_redraws_every_minute() {
+        let clock = FakeClock::new(None);
+        let mut shown = Vec::new();
+        let session = countdown(&clock, Duration::from_secs(25 * 60), &AtomicBool::new(false), &mut |left| {
+            shown.push(left.as_secs() / 60)
+        });
+        assert_eq!(shown, (1..=25).rev().collect::<Vec<u64>>());
+        assert_eq!((session.seconds, session.completed), (25 * 60, true));
+        assert_eq!(clock.now() - clock.start, Duration::from_secs(25 * 60));
+        assert!(clock.sleeps.borrow().iter().all(|&sleep| sleep <= STOP_POLL));
+    }
+
+    #[test]
+    fn test_stopping_early_records_the_partial_interval() {
+        static STOP: AtomicBool = AtomicBool::new(false);
+        let clock = FakeClock::new(Some((Duration::from_secs(7 * 60 + 30), &STOP)));
+        let session = countdown(&clock, Duration::from_secs(25 * 60), &STOP, &mut |_| {});
+        assert!(!session.completed);
+        assert_eq!(session.seconds, 7 * 60 + 30);
+    }
+}
@@ -0,0 +1,102 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use chrono::{Days, NaiveDateTime};
+use serde::Serialize;
+
+use crate::{Task, TaskStatus, dates};
+
+// Custom field holding how long a task takes, as "2d", "4h" or "30m"
+pub const ESTIMATE_FIELD: &str = "estimate";
+
+pub const DEFAULT_ESTIMATE: Duration = Duration::from_secs(86_400);
+
+// The estimate list markers use for tasks without their own, set at startup
+static DEFAULT_ESTIMATE_SECS: AtomicU64 = AtomicU64::new(86_400);
+
+pub fn set_default_estimate(estimate: Duration) {
+    DEFAULT_ESTIMATE_SECS.store(estimate.as_secs(), Ordering::Relaxed);
+}
+
+pub fn default_estimate() -> Duration {
+    Duration::from_secs(DEFAULT_ESTIMATE_SECS.load(Ordering::Relaxed))
+}
+
+// How the time left before a task is due compares with how long it takes,
+// least pressing first
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Pressure {
+    // More than twice the estimate left
+    Ahead,
+    // Less than twice the estimate left
+    Tight,
+    // Less than the estimate left, or overdue
+    Critical,
+}
+
+// The task's own estimate when it has a readable one
+pub fn estimate(task: &Task) -> Option<Duration> {
+    task.fields.get(ESTIMATE_FIELD).and_then(|text| dates::parse_offset(text))
+}
+
+// Pure over the task, the clock and the estimate for tasks without one. A
+// task is due by the end of its due day; open tasks without a due date feel
+// no pressure.
+pub fn pressure(task: &Task, now: NaiveDateTime, default: Duration) -> Option<Pressure> {
+    if task.status == TaskStatus::Completed {
+        return None;
+    }
+    let deadline = (task.due? + Days::new(1)).and_hms_opt(0, 0, 0)?;
+    let estimate = chrono::Duration::from_std(estimate(task).unwrap_or(default)).ok()?;
+    let left = deadline - now;
+    Some(if left < estimate {
+        Pressure::Critical
+    } else if left < estimate * 2 {
+        Pressure::Tight
+    } else {
+        Pressure::Ahead
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Priority;
+    use chrono::NaiveDate;
+
+    fn at(day: u32, hour: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2024, 7, day).unwrap().and_hms_opt(hour, 0, 0).unwrap()
+    }
+
+    fn due(day: u32, estimate: Option<&str>) -> Task {
+        let mut task = Task::new(1, "Report".to_string(), String::new(), Priority::Medium);
+        task.due = NaiveDate::from_ymd_opt(2024, 7, day);
+        if let Some(estimate) = estimate {
+            task.fields.insert(ESTIMATE_FIELD.to_string(), estimate.to_string());
+        }
+        task
+    }
+
+    #[test]
+    fn test_pressure_compares_time_left_with_the_estimate() {
+        let day = Duration::from_secs(86_400);
+        // Due by the end of the 10th: 60 hours left at noon on the 8th
+        let task = due(10, None);
+        assert_eq!(pressure(&task, at(8, 12), day), Some(Pressure::Ahead));
+        assert_eq!(pressure(&task, at(9, 6), day), Some(Pressure::Tight));
+        assert_eq!(pressure(&task, at(10, 12), day), Some(Pressure::Critical));
+        assert_eq!(pressure(&task, at(12, 0), day), Some(Pressure::Critical));
+
+        // The task's own estimate wins; an unreadable one falls back
+        assert_eq!(pressure(&due(10, Some("4h")), at(10, 18), day), Some(Pressure::Tight));
+        assert_eq!(pressure(&due(10, Some("3d")), at(8, 12), day), Some(Pressure::Critical));
+        assert_eq!(pressure(&due(10, Some("soon")), at(8, 12), day), Some(Pressure::Ahead));
+
+        let mut done = due(10, None);
+        done.status = TaskStatus::Completed;
+        assert_eq!(pressure(&done, at(12, 0), day), None);
+        assert_eq!(pressure(&Task::new(2, "Undated".to_string(), String::new(), Priority::Low), at(8, 0), day), None);
+        assert!(Pressure::Critical > Pressure::Tight && Pressure::Tight > Pressure::Ahead);
+    }
+}
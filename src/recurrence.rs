@@ -0,0 +1,396 @@
+use std::fmt;
+
+use chrono::{Datelike, Days, Months, NaiveDate, Weekday};
+use serde::Serialize;
+
+use crate::dates;
+use crate::msg;
+
+// The custom field a task keeps its recurrence rule in, such as "every 2 weeks on mon,thu"
+pub const RECUR_FIELD: &str = "recur";
+
+// Dates `recur-preview` lists without a count
+pub const DEFAULT_PREVIEW_COUNT: usize = 5;
+// The most it lists at once
+pub const MAX_PREVIEW_COUNT: usize = 1000;
+
+// How often a task comes back. Parts a rule leaves out come from the date it
+// counts from: "monthly" after 2024-01-31 keeps to the 31st.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Recurrence {
+    Days(u32),
+    // On these weekdays, Monday first, every `every` weeks; empty means the
+    // weekday of the start
+    Weeks { every: u32, on: Vec<Weekday> },
+    // On this day of the month; months too short for it use their last day
+    Months { every: u32, day: Option<u32> },
+    // On this (month, day); February 29 falls on the 28th outside leap years
+    Years { every: u32, on: Option<(u32, u32)> },
+}
+
+impl Recurrence {
+    // "daily", "weekly", "monthly", "yearly", "every <n> days|weeks|months|years",
+    // "every mon,thu" or "every weekday", with "on mon,fri" after a weekly rule,
+    // "on the 31st" after a monthly one and "on 02-29" after a yearly one
+    pub fn parse(text: &str) -> Option<Recurrence> {
+        let text = text.trim().trim_matches('"').to_lowercase();
+        let words: Vec<&str> = text.split_whitespace().collect();
+        let (every, unit, rest) = match words.as_slice() {
+            ["daily", rest @ ..] => (1, "day", rest),
+            ["weekly", rest @ ..] => (1, "week", rest),
+            ["monthly", rest @ ..] => (1, "month", rest),
+            ["yearly" | "annually", rest @ ..] => (1, "year", rest),
+            ["every", count, unit, rest @ ..] if count.parse::<u32>().is_ok() => {
+                (count.parse().ok().filter(|&count| count > 0)?, *unit, rest)
+            }
+            ["every", unit, rest @ ..] if unit_name(unit).is_some() => (1, *unit, rest),
+            ["every", days @ ..] => return Some(Recurrence::Weeks { every: 1, on: weekdays(&days.join(" "))? }),
+            _ => return None,
+        };
+        let on = match rest {
+            [] => None,
+            ["on", spec @ ..] if !spec.is_empty() => Some(spec.join(" ")),
+            _ => return None,
+        };
+        match (unit_name(unit)?, on.as_deref()) {
+            ("day", None) => Some(Recurrence::Days(every)),
+            ("day", Some(_)) => None,
+            ("week", None) => Some(Recurrence::Weeks { every, on: Vec::new() }),
+            ("week", Some(on)) => Some(Recurrence::Weeks { every, on: weekdays(on)? }),
+            ("month", None) => Some(Recurrence::Months { every, day: None }),
+            ("month", Some(on)) => Some(Recurrence::Months { every, day: Some(day_of_month(on)?) }),
+            (_, None) => Some(Recurrence::Years { every, on: None }),
+            (_, Some(on)) => Some(Recurrence::Years { every, on: Some(month_day(on)?) }),
+        }
+    }
+
+    // The dates the rule lands on after `start`, which is also what it counts
+    // from. Ends only past the last date chrono can hold.
+    pub fn occurrences(&self, start: NaiveDate) -> Occurrences<'_> {
+        Occurrences { rule: self, start, last: start, step: 0 }
+    }
+
+    // The first `n` dates after `date`
+    pub fn occurrences_after(&self, date: NaiveDate, n: usize) -> Vec<NaiveDate> {
+        self.occurrences(date).take(n).collect()
+    }
+}
+
+impl fmt::Display for Recurrence {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let every = |f: &mut fmt::Formatter, every: u32, one: &str, unit: &str| match every {
+            1 => write!(f, "{}", one),
+            n => write!(f, "every {} {}s", n, unit),
+        };
+        match self {
+            Recurrence::Days(n) => every(f, *n, "daily", "day"),
+            Recurrence::Weeks { every: n, on } => {
+                every(f, *n, "weekly", "week")?;
+                if !on.is_empty() {
+                    let days: Vec<String> = on.iter().map(Weekday::to_string).collect();
+                    write!(f, " on {}", days.join(", "))?;
+                }
+                Ok(())
+            }
+            Recurrence::Months { every: n, day } => {
+                every(f, *n, "monthly", "month")?;
+                match day {
+                    Some(day) => write!(f, " on the {}", ordinal(*day)),
+                    None => Ok(()),
+                }
+            }
+            Recurrence::Years { every: n, on } => {
+                every(f, *n, "yearly", "year")?;
+                match on {
+                    Some((month, day)) => write!(f, " on {:02}-{:02}", month, day),
+                    None => Ok(()),
+                }
+            }
+        }
+    }
+}
+
+pub struct Occurrences<'a> {
+    rule: &'a Recurrence,
+    start: NaiveDate,
+    // The date handed out last, `start` before the first
+    last: NaiveDate,
+    // Months and years: periods past the start's already tried
+    step: u32,
+}
+
+impl Iterator for Occurrences<'_> {
+    type Item = NaiveDate;
+
+    fn next(&mut self) -> Option<NaiveDate> {
+        // A rule built by hand with a zero interval never moves on
+        if let Recurrence::Days(0)
+        | Recurrence::Weeks { every: 0, .. }
+        | Recurrence::Months { every: 0, .. }
+        | Recurrence::Years { every: 0, .. } = self.rule
+        {
+            return None;
+        }
+        let next = match self.rule {
+            Recurrence::Days(every) => self.last.checked_add_days(Days::new(u64::from(*every)))?,
+            Recurrence::Weeks { every, on } => self.next_weekly(*every, on)?,
+            Recurrence::Months { every, day } => {
+                let first = self.start.with_day(1)?;
+                let day = day.unwrap_or(self.start.day());
+                self.next_periodic(|step| {
+                    let month = first.checked_add_months(Months::new(step.checked_mul(*every)?))?;
+                    on_day(month.year(), month.month(), day)
+                })?
+            }
+            Recurrence::Years { every, on } => {
+                let (month, day) = on.unwrap_or((self.start.month(), self.start.day()));
+                let year = self.start.year();
+                self.next_periodic(|step| {
+                    let years = i32::try_from(step.checked_mul(*every)?).ok()?;
+                    on_day(year.checked_add(years)?, month, day)
+                })?
+            }
+        };
+        self.last = next;
+        Some(next)
+    }
+}
+
+impl Occurrences<'_> {
+    // Weeks are counted Monday to Sunday from the start's week
+    fn next_weekly(&self, every: u32, on: &[Weekday]) -> Option<NaiveDate> {
+        let days = if on.is_empty() { vec![self.start.weekday()] } else { on.to_vec() };
+        let first_week = self.start.week(Weekday::Mon).first_day();
+        let mut date = self.last;
+        // Far enough to cross the weeks skipped between two that count
+        for _ in 0..7 * u64::from(every) {
+            date = date.succ_opt()?;
+            let weeks = (date.week(Weekday::Mon).first_day() - first_week).num_days() / 7;
+            if days.contains(&date.weekday()) && weeks % i64::from(every) == 0 {
+                return Some(date);
+            }
+        }
+        None
+    }
+
+    // The date in the start's own month or year, then in each period after it,
+    // skipping any not past the last date handed out
+    fn next_periodic(&mut self, date_at: impl Fn(u32) -> Option<NaiveDate>) -> Option<NaiveDate> {
+        loop {
+            let date = date_at(self.step)?;
+            self.step = self.step.checked_add(1)?;
+            if date > self.last {
+                return Some(date);
+            }
+        }
+    }
+}
+
+fn unit_name(word: &str) -> Option<&'static str> {
+    match word {
+        "day" | "days" => Some("day"),
+        "week" | "weeks" => Some("week"),
+        "month" | "months" => Some("month"),
+        "year" | "years" => Some("year"),
+        _ => None,
+    }
+}
+
+// "mon,thu", "monday and friday" or "weekdays", Monday first
+fn weekdays(text: &str) -> Option<Vec<Weekday>> {
+    let mut days = Vec::new();
+    for word in text.split([',', ' ']).filter(|word| !word.is_empty() && *word != "and") {
+        match word {
+            "weekday" | "weekdays" => days.extend([Weekday::Mon, Weekday::Tue, Weekday::Wed, Weekday::Thu, Weekday::Fri]),
+            day => days.push(day.parse().ok()?),
+        }
+    }
+    days.sort_by_key(Weekday::num_days_from_monday);
+    days.dedup();
+    (!days.is_empty()).then_some(days)
+}
+
+// "31", "31st" or "the 3rd"
+fn day_of_month(text: &str) -> Option<u32> {
+    let text = text.strip_prefix("the ").unwrap_or(text);
+    let digits = text.trim_end_matches(|c: char| c.is_ascii_alphabetic());
+    digits.parse().ok().filter(|day| (1..=31).contains(day))
+}
+
+// "02-29"; any day some year has
+fn month_day(text: &str) -> Option<(u32, u32)> {
+    let date = NaiveDate::parse_from_str(&format!("2000-{}", text), "%Y-%m-%d").ok()?;
+    Some((date.month(), date.day()))
+}
+
+fn ordinal(day: u32) -> String {
+    let suffix = match (day % 10, day % 100) {
+        (_, 11..=13) => "th",
+        (1, _) => "st",
+        (2, _) => "nd",
+        (3, _) => "rd",
+        _ => "th",
+    };
+    format!("{}{}", day, suffix)
+}
+
+// `day` of the month, or its last day when the month is shorter
+fn on_day(year: i32, month: u32, day: u32) -> Option<NaiveDate> {
+    let first = NaiveDate::from_ymd_opt(year, month, 1)?;
+    let last = first.checked_add_months(Months::new(1)).and_then(|next| next.pred_opt()).map_or(31, |last| last.day());
+    NaiveDate::from_ymd_opt(year, month, day.min(last))
+}
+
+// Why a previewed date is not a business day
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum OffDay {
+    Weekend,
+    Holiday,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Occurrence {
+    pub date: NaiveDate,
+    // Set in business-day mode only
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub off: Option<OffDay>,
+}
+
+// `recur-preview`: the dates a rule would produce from `from`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Preview {
+    pub rule: String,
+    pub from: NaiveDate,
+    // The task the rule was read from, when it was
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub task: Option<u32>,
+    pub dates: Vec<Occurrence>,
+}
+
+// `holidays` is None outside business-day mode, when no date is flagged
+pub fn preview(rule: &Recurrence, from: NaiveDate, count: usize, holidays: Option<&[NaiveDate]>) -> Preview {
+    let dates = rule
+        .occurrences_after(from, count)
+        .into_iter()
+        .map(|date| {
+            let off = holidays.and_then(|holidays| match date.weekday() {
+                Weekday::Sat | Weekday::Sun => Some(OffDay::Weekend),
+                _ if !dates::is_business_day(date, holidays) => Some(OffDay::Holiday),
+                _ => None,
+            });
+            Occurrence { date, off }
+        })
+        .collect();
+    Preview { rule: rule.to_string(), from, task: None, dates }
+}
+
+impl Preview {
+    pub fn lines(&self) -> Vec<String> {
+        let mut lines = vec![match self.task {
+            Some(id) => msg!("recur_preview_task", count = self.dates.len(), id = id, rule = self.rule, from = self.from),
+            None => msg!("recur_preview_header", count = self.dates.len(), rule = self.rule, from = self.from),
+        }];
+        for occurrence in &self.dates {
+            let date = occurrence.date.format("%a %Y-%m-%d").to_string();
+            lines.push(match occurrence.off {
+                Some(OffDay::Weekend) => msg!("recur_preview_weekend", date = date),
+                Some(OffDay::Holiday) => msg!("recur_preview_holiday", date = date),
+                None => format!("  {}", date),
+            });
+        }
+        lines
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    fn next(rule: &str, from: NaiveDate, n: usize) -> Vec<NaiveDate> {
+        Recurrence::parse(rule).unwrap_or_else(|| panic!("'{}' parses", rule)).occurrences_after(from, n)
+    }
+
+    #[test]
+    fn test_parse_rules() {
+        assert_eq!(Recurrence::parse("daily"), Some(Recurrence::Days(1)));
+        assert_eq!(Recurrence::parse("every 3 days"), Some(Recurrence::Days(3)));
+        assert_eq!(
+            Recurrence::parse("Every 2 weeks on thu, mon"),
+            Some(Recurrence::Weeks { every: 2, on: vec![Weekday::Mon, Weekday::Thu] })
+        );
+        assert_eq!(Recurrence::parse("every monday and friday"), Some(Recurrence::Weeks { every: 1, on: vec![Weekday::Mon, Weekday::Fri] }));
+        assert_eq!(Recurrence::parse("every weekday").map(|rule| rule.to_string()), Some("weekly on Mon, Tue, Wed, Thu, Fri".to_string()));
+        assert_eq!(Recurrence::parse("monthly on the 31st"), Some(Recurrence::Months { every: 1, day: Some(31) }));
+        assert_eq!(Recurrence::parse("every 3 months"), Some(Recurrence::Months { every: 3, day: None }));
+        assert_eq!(Recurrence::parse("yearly on 02-29"), Some(Recurrence::Years { every: 1, on: Some((2, 29)) }));
+        for bad in ["", "sometimes", "every 0 days", "every 2", "daily on mon", "monthly on the 32nd", "yearly on 02-30", "every funday"] {
+            assert_eq!(Recurrence::parse(bad), None, "{}", bad);
+        }
+        assert_eq!(Recurrence::Months { every: 2, day: Some(22) }.to_string(), "every 2 months on the 22nd");
+    }
+
+    #[test]
+    fn test_monthly_on_the_31st_across_short_months() {
+        assert_eq!(
+            next("monthly on the 31st", date(2023, 12, 31), 6),
+            vec![date(2024, 1, 31), date(2024, 2, 29), date(2024, 3, 31), date(2024, 4, 30), date(2024, 5, 31), date(2024, 6, 30)]
+        );
+        // The day comes from the start and is kept after a short month
+        assert_eq!(next("monthly", date(2023, 1, 31), 3), vec![date(2023, 2, 28), date(2023, 3, 31), date(2023, 4, 30)]);
+        // Later in the start's own month still counts
+        assert_eq!(next("monthly on 31", date(2024, 4, 10), 2), vec![date(2024, 4, 30), date(2024, 5, 31)]);
+        assert_eq!(next("every 3 months", date(2024, 11, 30), 3), vec![date(2025, 2, 28), date(2025, 5, 30), date(2025, 8, 30)]);
+    }
+
+    #[test]
+    fn test_leap_years() {
+        assert_eq!(next("yearly", date(2024, 2, 29), 5), vec![
+            date(2025, 2, 28),
+            date(2026, 2, 28),
+            date(2027, 2, 28),
+            date(2028, 2, 29),
+            date(2029, 2, 28)
+        ]);
+        assert_eq!(next("yearly on 02-29", date(2023, 6, 1), 2), vec![date(2024, 2, 29), date(2025, 2, 28)]);
+        assert_eq!(next("every 4 years", date(1896, 2, 29), 2), vec![date(1900, 2, 28), date(1904, 2, 29)]);
+        assert_eq!(next("every 2 days", date(2024, 2, 27), 2), vec![date(2024, 2, 29), date(2024, 3, 2)]);
+    }
+
+    #[test]
+    fn test_weekly_rules_with_several_weekdays() {
+        // 2024-05-01 is a Wednesday
+        assert_eq!(
+            next("every mon,wed,fri", date(2024, 5, 1), 5),
+            vec![date(2024, 5, 3), date(2024, 5, 6), date(2024, 5, 8), date(2024, 5, 10), date(2024, 5, 13)]
+        );
+        // Every other week, counted from the start's week
+        assert_eq!(
+            next("every 2 weeks on tue,thu", date(2024, 5, 1), 4),
+            vec![date(2024, 5, 2), date(2024, 5, 14), date(2024, 5, 16), date(2024, 5, 28)]
+        );
+        assert_eq!(next("weekly", date(2024, 5, 1), 2), vec![date(2024, 5, 8), date(2024, 5, 15)]);
+        assert_eq!(next("every 3 weeks", date(2024, 5, 1), 2), vec![date(2024, 5, 22), date(2024, 6, 12)]);
+    }
+
+    #[test]
+    fn test_preview_flags_off_days_in_business_day_mode() {
+        let rule = Recurrence::parse("every 2 days").unwrap();
+        let christmas = date(2024, 12, 25);
+        let flags = |holidays: Option<&[NaiveDate]>| {
+            preview(&rule, date(2024, 12, 19), 4, holidays).dates.into_iter().map(|day| (day.date.day(), day.off)).collect::<Vec<_>>()
+        };
+        assert_eq!(flags(Some(&[christmas])), vec![
+            (21, Some(OffDay::Weekend)),
+            (23, None),
+            (25, Some(OffDay::Holiday)),
+            (27, None)
+        ]);
+        assert!(flags(None).iter().all(|(_, off)| off.is_none()));
+        assert_eq!(Recurrence::Days(1).occurrences(NaiveDate::MAX).next(), None);
+    }
+}
@@ -0,0 +1,1560 @@
+use std::fmt::{Display, Write};
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use chrono::{DateTime, Local, NaiveDate, Utc};
+use serde::Serialize;
+use serde_json::{Value, json};
+use unicode_width::UnicodeWidthStr;
+
+use crate::audit::AuditEntry;
+use crate::board;
+use crate::calendar;
+use crate::command::{BulkChange, CommandOutput, ListView, ParseError, ViewSummary};
+use crate::dates;
+use crate::diagnostics::{SizeReport, format_bytes};
+use crate::focus::format_elapsed;
+use crate::health::{Issue, Repair};
+use crate::import::ImportReport;
+use crate::matrix;
+use crate::msg;
+use crate::pressure::{self, Pressure};
+use crate::schema::FieldInfo;
+use crate::search;
+use crate::review;
+use crate::roundtrip::RoundTripReport;
+use crate::summary;
+use crate::sync::{Side, SyncReport};
+use crate::table;
+use crate::tags::{TagInfo, TagRow};
+use crate::transfer::Transfer;
+use crate::tree;
+use crate::workflow;
+use crate::{CreatorStats, Energy, PlannedChange, Priority, Task, TaskError, TaskEvent, TaskStatus};
+
+// How much of a task to show
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DisplayMode {
+    // One line: id, priority glyph, title, status, tag count
+    Compact,
+    Normal,
+    // Normal plus timestamps
+    Verbose,
+}
+
+impl FromStr for DisplayMode {
+    type Err = TaskError;
+
+    fn from_str(s: &str) -> Result<DisplayMode, TaskError> {
+        match s.to_lowercase().as_str() {
+            "compact" => Ok(DisplayMode::Compact),
+            "normal" => Ok(DisplayMode::Normal),
+            "verbose" => Ok(DisplayMode::Verbose),
+            _ => Err(TaskError::InvalidInput),
+        }
+    }
+}
+
+pub fn priority_glyph(priority: &Priority) -> &'static str {
+    match priority {
+        Priority::Low => "·",
+        Priority::Medium => "!",
+        Priority::High => "!!",
+        Priority::Critical => "!!!",
+    }
+}
+
+// Set once at startup: `color` is on and stdout is a terminal
+static COLOR: AtomicBool = AtomicBool::new(false);
+
+pub fn set_color(color: bool) {
+    COLOR.store(color, Ordering::Relaxed);
+}
+
+// How worrying a number or word is, shown in colour when colour is on
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Tone {
+    Plain,
+    Good,
+    Warn,
+    Bad,
+}
+
+pub fn paint(text: &str, tone: Tone) -> String {
+    let code = match tone {
+        Tone::Good => "32",
+        Tone::Warn => "33",
+        Tone::Bad => "31",
+        Tone::Plain => return text.to_string(),
+    };
+    if !COLOR.load(Ordering::Relaxed) {
+        return text.to_string();
+    }
+    format!("\x1b[{}m{}\x1b[0m", code, text)
+}
+
+// Off with --quiet: errors leave out the hint line
+static HINTS: AtomicBool = AtomicBool::new(true);
+
+pub fn set_hints(hints: bool) {
+    HINTS.store(hints, Ordering::Relaxed);
+}
+
+// An error as the human format prints it: what went wrong, the word behind it
+// when the message does not quote it already, and what to try next
+#[derive(Debug, Clone, PartialEq)]
+pub struct ErrorBlock {
+    pub message: String,
+    pub input: Option<String>,
+    pub hint: Option<String>,
+}
+
+impl ErrorBlock {
+    pub fn render(&self, hints: bool) -> String {
+        let mut out = paint(&self.message, Tone::Bad);
+        if let Some(input) = &self.input {
+            write!(out, "\n{}", msg!("error_input", input = input)).unwrap();
+        }
+        if let Some(hint) = self.hint.as_ref().filter(|_| hints) {
+            write!(out, "\n{}", paint(&msg!("error_hint", hint = hint), Tone::Warn)).unwrap();
+        }
+        out
+    }
+}
+
+pub fn task_error_block(err: &TaskError) -> ErrorBlock {
+    let error = match err {
+        TaskError::TaskNotFound { id } => msg!("error_task_not_found", id = id),
+        TaskError::NoTitleMatch { text } => msg!("error_no_title_match", text = text),
+        TaskError::AmbiguousTitle { text, matches } => {
+            let mut out = msg!("error_ambiguous_title", text = text);
+            out.push_str(&format_title_matches(matches));
+            out
+        }
+        TaskError::InvalidInput => msg!("error_invalid_input"),
+        TaskError::Invalid { reason } => msg!("error_invalid", reason = reason),
+        TaskError::DuplicateTask { existing_id, .. } => msg!("error_duplicate_task", id = existing_id),
+        TaskError::TaskCompleted { id } => msg!("error_task_completed", id = id),
+        TaskError::NotOwner { id, owner } => msg!("error_not_owner", id = id, owner = owner),
+        TaskError::InvalidTransition { from, to, allowed } if allowed.is_empty() => {
+            msg!("error_invalid_transition_stuck", from = from, to = to)
+        }
+        TaskError::InvalidTransition { from, to, allowed } => {
+            let allowed: Vec<&str> = allowed.iter().map(workflow::status_word).collect();
+            msg!("error_invalid_transition", from = from, to = to, allowed = allowed.join(", "))
+        }
+        TaskError::Storage(detail) => msg!("error_storage", detail = detail),
+        TaskError::Remote(detail) => msg!("error_remote", detail = detail),
+        TaskError::Import(detail) => msg!("error_import", detail = detail),
+        TaskError::ValidationFailed(violations) => {
+            let mut out = msg!("error_validation");
+            for violation in violations {
+                write!(out, "\n  - {}", violation).unwrap();
+            }
+            out
+        }
+    };
+    // The rest already say what to do, or list the choices
+    let hint = match err {
+        TaskError::TaskNotFound { .. } => Some(msg!("hint_task_not_found")),
+        TaskError::InvalidInput => Some(msg!("hint_help")),
+        TaskError::DuplicateTask { existing_id, .. } => Some(msg!("hint_duplicate_task", id = existing_id)),
+        TaskError::Storage(_) => Some(msg!("hint_storage")),
+        TaskError::Remote(_) => Some(msg!("hint_remote")),
+        TaskError::Import(_) => Some(msg!("hint_import")),
+        TaskError::ValidationFailed(_) => Some(msg!("hint_validation")),
+        _ => None,
+    };
+    let input = match err {
+        TaskError::DuplicateTask { title, .. } => Some(title.clone()),
+        _ => None,
+    };
+    ErrorBlock { message: msg!("error", error = error), input, hint }
+}
+
+pub fn parse_error_block(err: &ParseError) -> ErrorBlock {
+    let hint = match (err, err.suggestion()) {
+        (_, Some(word)) => Some(msg!("hint_did_you_mean", word = word)),
+        (ParseError::Empty | ParseError::UnknownCommand(_), None) => Some(msg!("hint_help")),
+        (ParseError::InvalidId(_), None) => Some(msg!("hint_invalid_id")),
+        _ => None,
+    };
+    ErrorBlock { message: err.to_string(), input: err.input().map(str::to_string), hint }
+}
+
+// Put in front of a pinned task's title, wherever tasks are drawn
+pub fn pin_marker(pinned: bool) -> &'static str {
+    if pinned { "★ " } else { "" }
+}
+
+// A small bar after the status in list lines; nothing when the level is unset
+pub fn energy_marker(energy: Option<Energy>) -> &'static str {
+    match energy {
+        None => "",
+        Some(Energy::Low) => " ▁",
+        Some(Energy::Medium) => " ▄",
+        Some(Energy::High) => " █",
+    }
+}
+
+// A dot after the status in list lines, fuller and redder as the due date
+// closes in on the estimate; nothing for undated or completed tasks
+pub fn pressure_marker(pressure: Option<Pressure>) -> String {
+    match pressure {
+        None => String::new(),
+        Some(Pressure::Ahead) => format!(" {}", paint("○", Tone::Good)),
+        Some(Pressure::Tight) => format!(" {}", paint("◐", Tone::Warn)),
+        Some(Pressure::Critical) => format!(" {}", paint("●", Tone::Bad)),
+    }
+}
+
+pub fn status_glyph(status: &TaskStatus) -> &'static str {
+    match status {
+        TaskStatus::Pending => "○",
+        TaskStatus::InProgress => "◐",
+        TaskStatus::Completed => "●",
+    }
+}
+
+pub fn format_task(task: &Task, mode: DisplayMode) -> String {
+    match mode {
+        DisplayMode::Compact => {
+            let title = match &task.key {
+                Some(key) => format!("{}{} {}", pin_marker(task.pinned), key, task.title),
+                None => format!("{}{}", pin_marker(task.pinned), task.title),
+            };
+            let pressure = pressure::pressure(task, Local::now().naive_local(), pressure::default_estimate());
+            let mut line = format!(
+                "#{:<4} {:<3} {}  [{}]{}{}",
+                task.id,
+                priority_glyph(&task.priority),
+                title,
+                task.status,
+                energy_marker(task.energy),
+                pressure_marker(pressure)
+            );
+            if !task.tags.is_empty() {
+                line.push_str(&format!("  {}", msg!("task_tag_count", count = task.tags.len())));
+            }
+            line
+        }
+        DisplayMode::Normal => {
+            let mut out = format!(
+                "{}\n{}\n{}\n",
+                msg!(
+                    "task_line",
+                    id = task.id,
+                    title = format!("{}{}", pin_marker(task.pinned), task.title),
+                    priority = task.priority,
+                    status = task.status
+                ),
+                msg!("task_description", description = task.description),
+                msg!("task_tags", tags = task.tags.join(", "))
+            );
+            if let Some(due) = task.due {
+                writeln!(out, "{}", msg!("task_due", date = dates::show_date(due))).unwrap();
+            }
+            if let Some(energy) = task.energy {
+                writeln!(out, "{}", msg!("task_energy", energy = energy)).unwrap();
+            }
+            match (&task.key, task.old_keys.join(", ")) {
+                (Some(key), old) if old.is_empty() => writeln!(out, "{}", msg!("task_key", key = key)).unwrap(),
+                (Some(key), old) => writeln!(out, "{}", msg!("task_key_was", key = key, old = old)).unwrap(),
+                (None, old) if !old.is_empty() => writeln!(out, "{}", msg!("task_old_keys", old = old)).unwrap(),
+                (None, _) => {}
+            }
+            if let Some(kp) = &task.key_prefix {
+                writeln!(out, "{}", msg!("task_key_prefix", prefix = kp.prefix)).unwrap();
+            }
+            if !task.fields.is_empty() {
+                let fields: Vec<String> = task.fields.iter().map(|(key, value)| format!("{}={}", key, value)).collect();
+                writeln!(out, "{}", msg!("task_fields", fields = fields.join(", "))).unwrap();
+            }
+            if !task.reminders.is_empty() {
+                let reminders: Vec<String> = task
+                    .reminders
+                    .iter()
+                    .map(|reminder| {
+                        let state = if reminder.fired { msg!("reminder_sent") } else { msg!("reminder_pending") };
+                        msg!("reminder_item", offset = dates::format_offset(reminder.offset), state = state)
+                    })
+                    .collect();
+                writeln!(out, "{}", msg!("task_reminders", reminders = reminders.join(", "))).unwrap();
+            }
+            if let Some(waiting) = &task.waiting_on {
+                let since = dates::show_date(waiting.since);
+                let line = match waiting.follow_up {
+                    Some(date) => msg!("task_waiting_until", who = waiting.who, since = since, date = dates::show_date(date)),
+                    None => msg!("task_waiting", who = waiting.who, since = since),
+                };
+                writeln!(out, "{}", line).unwrap();
+            }
+            if !task.sessions.is_empty() {
+                let tracked = format_elapsed(task.time_tracked());
+                writeln!(out, "{}", msg!("task_pomodoros", count = task.pomodoros(), time = tracked)).unwrap();
+            }
+            out
+        }
+        DisplayMode::Verbose => format!(
+            "{}{}\n{}\n",
+            format_task(task, DisplayMode::Normal),
+            msg!("task_created", time = dates::show_time(task.created_at, TIMESTAMP_FORMAT)),
+            msg!("task_updated_at", time = dates::show_time(task.updated_at, TIMESTAMP_FORMAT))
+        ),
+    }
+}
+
+// One "would ..." line per change, under a reminder that none was made
+fn render_dry_run(changes: &[PlannedChange], files: &[PathBuf]) -> String {
+    let mut lines = vec![msg!("dry_run_header")];
+    for change in changes {
+        let title = &change.title;
+        lines.push(match &change.event {
+            TaskEvent::Added(id) => msg!("would_add", id = id, title = title),
+            TaskEvent::StatusChanged { id, from, to } => {
+                msg!("would_move", id = id, title = title, from = from, to = to)
+            }
+            TaskEvent::PriorityChanged { id, from, to } => {
+                msg!("would_reprioritize", id = id, title = title, from = from, to = to)
+            }
+            TaskEvent::Tagged { id, tag } => msg!("would_tag", id = id, title = title, tag = tag),
+            TaskEvent::Deleted(id) => msg!("would_delete", id = id, title = title),
+            TaskEvent::Edited(id) => msg!("would_edit", id = id, title = title),
+            TaskEvent::Notified(id) => msg!("would_notify", id = id, title = title),
+            TaskEvent::Restored => msg!("would_restore"),
+            TaskEvent::ViewChanged { name } => msg!("would_change_view", name = name),
+            TaskEvent::TagDescribed { tag } => msg!("would_describe_tag", tag = tag),
+            TaskEvent::GoalChanged => msg!("would_change_goal"),
+            TaskEvent::Overridden { id, owner } => msg!("would_override", id = id, title = title, owner = owner),
+            TaskEvent::RetentionApplied { archived, purged } => {
+                msg!("would_apply_retention", archived = archived.len(), purged = purged.len())
+            }
+        });
+    }
+    lines.extend(files.iter().map(|path| msg!("would_write", path = path.display())));
+    if lines.len() == 1 {
+        lines.push(msg!("would_do_nothing"));
+    }
+    lines.join("\n")
+}
+
+// One "#id title" line for each task a title prefix could mean
+pub fn format_title_matches(matches: &[(u32, String)]) -> String {
+    matches.iter().map(|(id, title)| format!("\n  #{:<4} {}", id, title)).collect()
+}
+
+// Each person, then the tasks waiting on them and for how long
+fn format_waiting(groups: &[(String, Vec<Task>)], today: NaiveDate) -> String {
+    let mut out = String::new();
+    for (who, tasks) in groups {
+        writeln!(out, "{}", who).unwrap();
+        for task in tasks {
+            let Some(waiting) = &task.waiting_on else { continue };
+            let days = waiting.days(today);
+            let state = if waiting.follow_up_due(today) {
+                msg!("waiting_follow_up", who = who, days = days)
+            } else {
+                msg!("waiting_days", days = days)
+            };
+            writeln!(out, "  #{:<4} {}  {}", task.id, task.title, state).unwrap();
+        }
+    }
+    out
+}
+
+// The compact line and how long the task has sat untouched, as `review` shows it
+pub fn format_stale(task: &Task, now: DateTime<Utc>) -> String {
+    format!("{}  {}", format_task(task, DisplayMode::Compact), msg!("stale_idle", days = review::idle_days(task, now)))
+}
+
+// One line per saved view: name, current match count, query
+fn format_views(views: &[ViewSummary]) -> String {
+    let width = views.iter().map(|view| view.name.len()).max().unwrap_or(0);
+    let mut out = String::new();
+    for view in views {
+        writeln!(out, "{:<width$}  {:>4}  {}", view.name, view.count, view.query, width = width).unwrap();
+    }
+    out.trim_end().to_string()
+}
+
+// One line per tag: name and task count, then with `long` what the registry says
+// about it
+fn format_tags(rows: &[TagRow], long: bool) -> String {
+    let width = rows.iter().map(|row| row.tag.width()).max().unwrap_or(0);
+    let mut out = String::new();
+    for row in rows {
+        write!(out, "{}  {:>4}", board::pad(&row.tag, width), row.count).unwrap();
+        match row.info.as_ref().filter(|_| long) {
+            Some(TagInfo { description, color: Some(color) }) => write!(out, "  {} ({})", description, color).unwrap(),
+            Some(TagInfo { description, color: None }) => write!(out, "  {}", description).unwrap(),
+            None if long => write!(out, "  {}", msg!("tag_unregistered")).unwrap(),
+            None => {}
+        }
+        out.push('\n');
+    }
+    out.trim_end().to_string()
+}
+
+// One key per line: name, JSON type, whether a task must have it, and its
+// format or allowed values
+fn format_schema(fields: &[FieldInfo]) -> String {
+    let name_width = fields.iter().map(|field| field.name.width()).max().unwrap_or(0);
+    let kind_width = fields.iter().map(|field| field.kind.width()).max().unwrap_or(0);
+    let mut out = msg!("schema_header");
+    for field in fields {
+        let required = if field.required { msg!("schema_required") } else { msg!("schema_optional") };
+        write!(out, "\n{}  {}  {}", board::pad(&field.name, name_width), board::pad(field.kind, kind_width), required).unwrap();
+        if let Some(detail) = &field.detail {
+            write!(out, "  {}", detail).unwrap();
+        }
+    }
+    write!(out, "\n{}", msg!("schema_footer")).unwrap();
+    out
+}
+
+fn task_ids(ids: &[u32]) -> String {
+    ids.iter().map(|id| format!("#{}", id)).collect::<Vec<_>>().join(", ")
+}
+
+// One line per changed field, with how the format loses it
+fn format_roundtrip(report: &RoundTripReport) -> String {
+    let format = report.format;
+    if report.is_lossless() {
+        return msg!("roundtrip_clean", format = format, tasks = report.tasks);
+    }
+    let mut out = msg!("roundtrip_header", format = format, fields = report.losses.len(), tasks = report.tasks);
+    let width = report.losses.iter().map(|loss| loss.field.width()).max().unwrap_or(0);
+    for loss in &report.losses {
+        let how = loss.expected.map_or_else(|| msg!("roundtrip_unexpected", format = format), |loss| loss.describe());
+        let affected = msg!("roundtrip_affected", how = how, count = loss.tasks.len(), ids = task_ids(&loss.tasks));
+        write!(out, "\n  {}  {}", board::pad(&loss.field, width), affected).unwrap();
+    }
+    if !report.missing.is_empty() {
+        write!(out, "\n{}", msg!("roundtrip_missing", ids = task_ids(&report.missing))).unwrap();
+    }
+    out
+}
+
+fn format_stats_by_user(rows: &[CreatorStats]) -> String {
+    if rows.is_empty() {
+        return msg!("no_tasks");
+    }
+    let names: Vec<String> = rows.iter().map(|row| row.user.clone().unwrap_or_else(|| msg!("stats_no_creator"))).collect();
+    let width = names.iter().map(|name| name.width()).max().unwrap_or(0);
+    let mut out = msg!("stats_by_user_header");
+    for (row, name) in rows.iter().zip(&names) {
+        let counts = msg!(
+            "stats_user_counts",
+            total = row.total,
+            completed = row.completed,
+            in_progress = row.in_progress,
+            pending = row.pending
+        );
+        write!(out, "\n  {}  {}", board::pad(name, width), counts).unwrap();
+    }
+    out
+}
+
+fn format_health(issues: &[Issue], repairs: Option<&[Repair]>) -> String {
+    if issues.is_empty() {
+        return msg!("check_clean");
+    }
+    let mut out = msg!("check_found", count = issues.len());
+    for issue in issues {
+        write!(out, "\n  {}", issue.describe()).unwrap();
+    }
+    match repairs {
+        None => write!(out, "\n{}", msg!("check_hint")).unwrap(),
+        Some(repairs) => {
+            write!(out, "\n{}", msg!("check_repaired", count = repairs.len())).unwrap();
+            for repair in repairs {
+                write!(out, "\n  {}", repair.describe()).unwrap();
+            }
+        }
+    }
+    out
+}
+
+const TIMESTAMP_FORMAT: &str = "%Y-%m-%d %H:%M UTC";
+
+// How command results are presented
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OutputFormat {
+    Human,
+    Json,
+}
+
+impl FromStr for OutputFormat {
+    type Err = TaskError;
+
+    fn from_str(s: &str) -> Result<OutputFormat, TaskError> {
+        match s.to_lowercase().as_str() {
+            "human" | "text" => Ok(OutputFormat::Human),
+            "json" => Ok(OutputFormat::Json),
+            _ => Err(TaskError::InvalidInput),
+        }
+    }
+}
+
+// How much is printed after a command succeeds
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum OutputLevel {
+    // Only what was asked for: confirmations such as "Task deleted" are left out
+    Quiet,
+    #[default]
+    Normal,
+    // The changed task in full after a mutation, and how long the command took
+    Verbose,
+}
+
+// The text for one command's result, split by the stream it goes to; errors
+// always go to stderr so scripts can rely on exit code plus stderr
+#[derive(Debug, Default, PartialEq)]
+pub struct Printed {
+    pub stdout: Option<String>,
+    pub stderr: Option<String>,
+}
+
+impl Printed {
+    pub fn print(self) {
+        if let Some(text) = self.stdout {
+            println!("{}", text);
+        }
+        if let Some(text) = self.stderr {
+            eprintln!("{}", text);
+        }
+    }
+}
+
+// `changed` is the task a mutation left behind, for --verbose
+pub fn route(
+    renderer: &dyn Renderer,
+    result: &Result<CommandOutput, TaskError>,
+    level: OutputLevel,
+    changed: Option<&Task>,
+    elapsed: Duration,
+) -> Printed {
+    match result {
+        Ok(output) => Printed { stdout: renderer.render_at(output, level, changed, elapsed), stderr: None },
+        Err(e) => Printed { stdout: None, stderr: Some(renderer.render_task_error(e)) },
+    }
+}
+
+impl OutputFormat {
+    pub fn renderer(self) -> &'static dyn Renderer {
+        match self {
+            OutputFormat::Human => &HumanRenderer,
+            OutputFormat::Json => &JsonRenderer,
+        }
+    }
+}
+
+// Turns command results and failures into the text printed for the user
+pub trait Renderer {
+    fn render(&self, output: &CommandOutput) -> String;
+
+    // The result as printed at `level`, None when nothing is
+    fn render_at(
+        &self,
+        output: &CommandOutput,
+        level: OutputLevel,
+        changed: Option<&Task>,
+        elapsed: Duration,
+    ) -> Option<String> {
+        match level {
+            OutputLevel::Quiet if output.is_confirmation() => None,
+            OutputLevel::Quiet | OutputLevel::Normal => Some(self.render(output)),
+            OutputLevel::Verbose => {
+                let mut text = self.render(output);
+                if let Some(task) = changed {
+                    write!(text, "\n{}", format_task(task, DisplayMode::Verbose).trim_end()).unwrap();
+                }
+                write!(text, "\n{}", msg!("took", ms = format!("{:.2}", elapsed.as_secs_f64() * 1000.0))).unwrap();
+                Some(text)
+            }
+        }
+    }
+
+    fn render_task_error(&self, err: &TaskError) -> String;
+    fn render_parse_error(&self, err: &ParseError) -> String;
+}
+
+pub struct HumanRenderer;
+
+impl Renderer for HumanRenderer {
+    fn render(&self, output: &CommandOutput) -> String {
+        match output {
+            CommandOutput::Help => msg!("help_text"),
+            CommandOutput::Added { id, similar } if similar.is_empty() => msg!("task_added", id = id),
+            CommandOutput::Added { id, similar } => {
+                format!("{}\n{}{}", msg!("task_added", id = id), msg!("similar_tasks"), format_title_matches(similar))
+            }
+            CommandOutput::AddedLines { created, skipped } => {
+                let mut lines: Vec<String> =
+                    skipped.iter().map(|(n, reason)| msg!("line_skipped", line = n, reason = reason)).collect();
+                lines.push(match created.as_slice() {
+                    [] => msg!("lines_added_none", skipped = skipped.len()),
+                    ids => {
+                        let ids: Vec<String> = ids.iter().map(u32::to_string).collect();
+                        msg!("lines_added", count = ids.len(), skipped = skipped.len(), ids = ids.join(", "))
+                    }
+                });
+                lines.join("\n")
+            }
+            CommandOutput::Updated(_) => msg!("task_updated"),
+            CommandOutput::Tagged(_) => msg!("tag_added"),
+            CommandOutput::Noted(_) => msg!("note_added"),
+            CommandOutput::DueSet { id, date } => msg!("due_set", id = id, date = dates::format_date(*date)),
+            CommandOutput::Pinned { id, pinned: false, .. } => msg!("task_unpinned", id = id),
+            CommandOutput::Pinned { id, completed: false, .. } => msg!("task_pinned", id = id),
+            CommandOutput::Pinned { id, .. } => {
+                format!("{}\n{}", msg!("task_pinned", id = id), msg!("pinned_completed"))
+            }
+            CommandOutput::Related { id, other, related: true, changed: true } => msg!("task_related_now", id = id, other = other),
+            CommandOutput::Related { id, other, related: true, .. } => msg!("task_related_already", id = id, other = other),
+            CommandOutput::Related { id, other, changed: true, .. } => msg!("task_unrelated", id = id, other = other),
+            CommandOutput::Related { id, other, .. } => msg!("task_not_related", id = id, other = other),
+            CommandOutput::PrioritySet { priority, changed, unchanged } => {
+                let changed = changed.iter().map(|(id, from)| msg!("priority_changed", id = id, from = from, to = priority));
+                let unchanged = unchanged.iter().map(|id| msg!("priority_unchanged", id = id, priority = priority));
+                changed.chain(unchanged).collect::<Vec<_>>().join("\n")
+            }
+            CommandOutput::Graph(dot) => dot.clone(),
+            CommandOutput::Deleted(_) => msg!("task_deleted"),
+            CommandOutput::Cleared(ids) => msg!("tasks_cleared", count = ids.len()),
+            CommandOutput::Task { task, mode, related } => {
+                let mut out =
+                    format!("{}\n{}", msg!("task_details_header"), format_task(task, mode.unwrap_or(DisplayMode::Verbose)));
+                if !related.is_empty() {
+                    write!(out, "\n{}", msg!("task_related")).unwrap();
+                    for other in related {
+                        write!(out, "\n  #{} {} [{}]", other.id, other.title, other.status).unwrap();
+                    }
+                }
+                out
+            }
+            CommandOutput::Tasks { view, tasks, mode } => {
+                render_task_list(view, tasks, mode.unwrap_or(DisplayMode::Compact))
+            }
+            CommandOutput::Table { view, tasks, .. } if tasks.is_empty() => empty_view(view),
+            CommandOutput::Table { view, tasks, columns } => format!(
+                "{}\n{}",
+                view_header(view),
+                table::layout(tasks, columns, board::terminal_width(), Local::now().date_naive())
+            ),
+            CommandOutput::Matches { keyword, hits, show_score } => render_matches(keyword, hits, *show_score),
+            CommandOutput::Stats { total, completed, in_progress, pending, inbox, pinned, goal } => {
+                let mut lines = vec![
+                    msg!("stats_header"),
+                    msg!("stats_total", count = total),
+                    msg!("stats_completed", count = completed),
+                    msg!("stats_in_progress", count = in_progress),
+                    msg!("stats_pending", count = pending),
+                ];
+                if *inbox > 0 {
+                    lines.push(msg!("stats_inbox", count = inbox));
+                }
+                if *pinned > 0 {
+                    lines.push(msg!("stats_pinned", count = pinned));
+                }
+                if *total > 0 {
+                    let completion_rate = (*completed as f64 / *total as f64) * 100.0;
+                    lines.push(msg!("stats_completion_rate", rate = format!("{:.1}", completion_rate)));
+                }
+                if let Some(goal) = goal {
+                    lines.push(goal.line());
+                }
+                lines.join("\n")
+            }
+            CommandOutput::Count { open, total } => msg!("task_count", open = open, total = total),
+            CommandOutput::StatsByUser(rows) => format_stats_by_user(rows),
+            CommandOutput::StatsWeighted(stats) => stats.lines().join("\n"),
+            CommandOutput::RecurPreview(preview) => preview.lines().join("\n"),
+            CommandOutput::History { task_id, entries, undoable, page, pages } => {
+                render_history(*task_id, entries, undoable, *page, *pages)
+            }
+            CommandOutput::Undone { label, ids } => msg!("undone", label = label, count = ids.len()),
+            CommandOutput::Exported { path, count } => msg!("exported", count = count, path = path.display()),
+            CommandOutput::TagsExported { path, count } => msg!("tags_exported", count = count, path = path.display()),
+            CommandOutput::TagsImported(report) => msg!(
+                "tags_imported",
+                added = report.added.len(),
+                updated = report.updated.len(),
+                removed = report.removed.len()
+            ),
+            CommandOutput::TagDescribed(tag) => msg!("tag_described", tag = tag),
+            CommandOutput::GoalSet(progress) => format!(
+                "{}\n{}",
+                msg!("goal_set", target = progress.target, period = progress.period),
+                progress.line()
+            ),
+            CommandOutput::Goal(Some(progress)) => progress.line(),
+            CommandOutput::Goal(None) => msg!("goal_none"),
+            CommandOutput::GoalCleared(true) => msg!("goal_cleared"),
+            CommandOutput::GoalCleared(false) => msg!("goal_none"),
+            CommandOutput::Tags { rows, .. } if rows.is_empty() => msg!("no_tags"),
+            CommandOutput::Tags { rows, long } => format_tags(rows, *long),
+            CommandOutput::Digest { body, .. } => body.trim_end().to_string(),
+            CommandOutput::Copied { id, clipboard: true, .. } => msg!("copied", id = id),
+            CommandOutput::Copied { text, clipboard: false, .. } => format!("{}\n{}", msg!("copy_manually"), text),
+            CommandOutput::Imported(report) => render_import(report),
+            CommandOutput::TasksImported(transfer) => render_transfer(transfer),
+            CommandOutput::Synced(report) => render_sync(report),
+            CommandOutput::SyncStatus { pending, last_pull, last_push } => {
+                let when = |time: &Option<DateTime<Utc>>| {
+                    time.map_or_else(|| msg!("sync_never"), |t| t.format(TIMESTAMP_FORMAT).to_string())
+                };
+                [
+                    msg!("sync_pending", count = pending),
+                    msg!("sync_last_pull", time = when(last_pull)),
+                    msg!("sync_last_push", time = when(last_push)),
+                ]
+                .join("\n")
+            }
+            CommandOutput::IndexCheck(Ok((tags, titles))) => {
+                msg!("index_ok", tags = tags, titles = titles)
+            }
+            CommandOutput::IndexCheck(Err(e)) => msg!("index_mismatch", detail = e),
+            CommandOutput::Size(report) => render_size(report),
+            CommandOutput::Schema(fields) => format_schema(fields),
+            CommandOutput::JsonSchema(schema) => serde_json::to_string_pretty(schema).unwrap_or_default(),
+            CommandOutput::RoundTrip(report) => format_roundtrip(report),
+            CommandOutput::Health { issues, repairs } => format_health(issues, repairs.as_deref()),
+            CommandOutput::Board(columns) => board::layout(columns, board::terminal_width()),
+            CommandOutput::Timesheet { sheet, csv: true } => sheet.csv().trim_end().to_string(),
+            CommandOutput::Timesheet { sheet, .. } => sheet.table(),
+            CommandOutput::Matrix(matrix) => matrix::layout(matrix, board::terminal_width()),
+            CommandOutput::Month(month) => calendar::layout(month, board::terminal_width(), COLOR.load(Ordering::Relaxed)),
+            CommandOutput::Summary(summary) => summary::layout(summary),
+            CommandOutput::Next(Some(next)) => msg!("summary_next", id = next.id, title = next.title),
+            CommandOutput::Next(None) => msg!("summary_nothing_next"),
+            CommandOutput::Stale { days, tasks, .. } if tasks.is_empty() => msg!("nothing_stale", days = days),
+            CommandOutput::Stale { days, tasks, now } => {
+                let lines: Vec<String> = tasks.iter().map(|task| format_stale(task, *now)).collect();
+                format!("{}\n{}", msg!("stale_header", count = tasks.len(), days = days), lines.join("\n"))
+            }
+            CommandOutput::Tree(forest) if forest.is_empty() => msg!("no_tasks"),
+            CommandOutput::Tree(forest) => tree::draw(forest),
+            CommandOutput::Waiting { groups, .. } if groups.is_empty() => msg!("nothing_waiting"),
+            CommandOutput::Waiting { groups, today } => format_waiting(groups, *today),
+            CommandOutput::DryRun { changes, files } => render_dry_run(changes, files),
+            CommandOutput::ViewSaved { name, replaced: false } => msg!("view_saved", name = name),
+            CommandOutput::ViewSaved { name, replaced: true } => msg!("view_replaced", name = name),
+            CommandOutput::ViewRenamed { from, to } => msg!("view_renamed", from = from, to = to),
+            CommandOutput::ViewDeleted(name) => msg!("view_deleted", name = name),
+            CommandOutput::Views(views) if views.is_empty() => msg!("no_views"),
+            CommandOutput::Views(views) => format_views(views),
+            CommandOutput::BulkPreview { tasks, .. } if tasks.is_empty() => msg!("bulk_no_match"),
+            CommandOutput::BulkPreview { change, tasks } => {
+                let header = match change {
+                    BulkChange::Tag(tag) => msg!("bulk_preview_tag", count = tasks.len(), tag = tag),
+                    BulkChange::Priority(priority) => msg!("bulk_preview_priority", count = tasks.len(), priority = priority),
+                };
+                let lines: Vec<String> = tasks.iter().map(|task| format_task(task, DisplayMode::Compact)).collect();
+                format!("{}\n{}", header, lines.join("\n"))
+            }
+            CommandOutput::BulkApplied { change, changed, unchanged, failed } => {
+                let mut lines: Vec<String> =
+                    failed.iter().map(|(id, reason)| msg!("bulk_failed", id = id, reason = reason)).collect();
+                lines.push(match change {
+                    BulkChange::Tag(_) => msg!("bulk_tagged", changed = changed.len(), unchanged = unchanged.len()),
+                    BulkChange::Priority(priority) => {
+                        msg!("bulk_prioritized", changed = changed.len(), unchanged = unchanged.len(), priority = priority)
+                    }
+                });
+                if !failed.is_empty() {
+                    lines.push(msg!("bulk_failed_count", count = failed.len()));
+                }
+                lines.join("\n")
+            }
+            CommandOutput::Duplicates(clusters) if clusters.is_empty() => msg!("no_duplicates"),
+            CommandOutput::Duplicates(clusters) => {
+                let groups: Vec<String> = clusters.iter().map(|cluster| format_title_matches(cluster)).collect();
+                format!("{}{}\n{}", msg!("header_duplicates", count = clusters.len()), groups.join("\n"), msg!("duplicates_hint"))
+            }
+        }
+    }
+
+    fn render_task_error(&self, err: &TaskError) -> String {
+        task_error_block(err).render(HINTS.load(Ordering::Relaxed))
+    }
+
+    fn render_parse_error(&self, err: &ParseError) -> String {
+        parse_error_block(err).render(HINTS.load(Ordering::Relaxed))
+    }
+}
+
+// What a list says when nothing is in it
+fn empty_view(view: &ListView) -> String {
+    match view {
+        ListView::All => msg!("no_tasks"),
+        ListView::Filter(keyword) => msg!("no_tasks_matching", keyword = keyword),
+        ListView::View(name) => msg!("no_tasks_in_view", name = name),
+        ListView::Tags(tags) => msg!("no_tasks_tagged", tags = tags.join(", ")),
+        ListView::Priority(p) => msg!("no_tasks_priority", priority = p),
+        ListView::Status(s) => msg!("no_tasks_status", status = s),
+        ListView::Inbox => msg!("inbox_empty"),
+        ListView::Pinned => msg!("no_pinned"),
+        ListView::Mine => msg!("no_tasks_mine"),
+        ListView::Energy(energy) => msg!("no_tasks_energy", energy = energy),
+        ListView::DueSoon(days) => msg!("no_tasks_due_soon", days = days),
+        ListView::Source(source) => msg!("no_tasks_in_source", source = source),
+    }
+}
+
+fn view_header(view: &ListView) -> String {
+    match view {
+        ListView::All => msg!("header_all"),
+        ListView::Filter(_) => msg!("header_filtered"),
+        ListView::View(name) => msg!("header_view", name = name),
+        ListView::Tags(tags) => msg!("header_tagged", tags = tags.join(", ")),
+        ListView::Priority(p) => msg!("header_priority", priority = p.to_string().to_uppercase()),
+        ListView::Status(s) => msg!("header_status", status = s.to_string().to_uppercase()),
+        ListView::Inbox => msg!("header_inbox"),
+        ListView::Pinned => msg!("header_pinned"),
+        ListView::Mine => msg!("header_mine"),
+        ListView::Energy(energy) => msg!("header_energy", energy = energy.to_string().to_uppercase()),
+        ListView::DueSoon(days) => msg!("header_due_soon", days = days),
+        ListView::Source(source) => msg!("header_source", source = source),
+    }
+}
+
+fn render_task_list(view: &ListView, tasks: &[Task], mode: DisplayMode) -> String {
+    if tasks.is_empty() {
+        return empty_view(view);
+    }
+    let mut out = view_header(view);
+    for task in tasks {
+        match mode {
+            DisplayMode::Compact => write!(out, "\n{}", format_task(task, mode)).unwrap(),
+            _ => write!(out, "\n{}\n---", format_task(task, mode)).unwrap(),
+        }
+    }
+    out
+}
+
+// `filter` hits best first, each after its score when asked for
+fn render_matches(keyword: &str, hits: &[(u32, Task)], show_score: bool) -> String {
+    if hits.is_empty() {
+        return msg!("no_tasks_matching", keyword = keyword);
+    }
+    let mut out = msg!("header_filtered");
+    for (score, task) in ranked(hits) {
+        let line = format_task(task, DisplayMode::Compact);
+        if show_score {
+            write!(out, "\n{} {}", msg!("match_score", score = score), line).unwrap();
+        } else {
+            write!(out, "\n{}", line).unwrap();
+        }
+    }
+    out
+}
+
+fn ranked(hits: &[(u32, Task)]) -> Vec<(u32, &Task)> {
+    let mut hits: Vec<(u32, &Task)> = hits.iter().map(|(score, task)| (*score, task)).collect();
+    search::rank(&mut hits);
+    hits
+}
+
+fn render_history(task_id: Option<u32>, entries: &[AuditEntry], undoable: &[bool], page: usize, pages: usize) -> String {
+    if entries.is_empty() {
+        return msg!("no_history");
+    }
+    let mut out = match task_id {
+        Some(id) => msg!("header_history_task", id = id),
+        None => msg!("header_history"),
+    };
+    for (index, entry) in entries.iter().enumerate() {
+        let target = entry.task_id.map(|id| format!("#{}", id)).unwrap_or_else(|| "-".to_string());
+        let mut line = format!(
+            "{}  {:<10} {:<8} {:<5} {}",
+            entry.timestamp.format(TIMESTAMP_FORMAT),
+            entry.user,
+            entry.action,
+            target,
+            entry.detail
+        );
+        if undoable.get(index) == Some(&true) {
+            write!(line, "  {}", msg!("history_undoable")).unwrap();
+        }
+        write!(out, "\n{}", line.trim_end()).unwrap();
+    }
+    write!(out, "\n{}", msg!("history_page", page = page, pages = pages)).unwrap();
+    out
+}
+
+fn render_import(report: &ImportReport) -> String {
+    let mut out = msg!(
+        "imported",
+        source = report.source,
+        created = report.created.len(),
+        updated = report.updated.len(),
+        skipped = report.skipped.len()
+    );
+    for (row, reason) in &report.skipped {
+        write!(out, "\n  - {}: {}", row, reason).unwrap();
+    }
+    for warning in &report.warnings {
+        write!(out, "\n  ! {}", warning).unwrap();
+    }
+    out
+}
+
+fn render_transfer(transfer: &Transfer) -> String {
+    let key = if transfer.moved { "tasks_moved_in" } else { "tasks_copied_in" };
+    let mut out = msg!(key, count = transfer.copied.len(), source = transfer.source);
+    for (there, here) in &transfer.copied {
+        write!(out, "\n  {}", msg!("task_brought_in", there = there, source = transfer.source, here = here)).unwrap();
+    }
+    if !transfer.renamed.is_empty() {
+        let ids: Vec<String> = transfer.renamed.iter().map(|id| format!("#{}", id)).collect();
+        write!(out, "\n{}", msg!("tasks_renamed_in", ids = ids.join(", "), source = transfer.source)).unwrap();
+    }
+    for (there, existing) in &transfer.skipped {
+        write!(out, "\n  - {}", msg!("task_left_duplicate", there = there, existing = existing)).unwrap();
+    }
+    for (there, reason) in &transfer.failed {
+        write!(out, "\n  - #{}: {}", there, reason).unwrap();
+    }
+    out
+}
+
+fn render_sync(report: &SyncReport) -> String {
+    let mut out = msg!(
+        "synced",
+        pulled = report.pulled.len(),
+        pushed = report.pushed.len(),
+        deleted = report.deleted.len()
+    );
+    for conflict in &report.conflicts {
+        let kept = match conflict.kept {
+            Side::Local => msg!("sync_kept_local"),
+            Side::Remote => msg!("sync_kept_remote"),
+        };
+        write!(out, "\n  {}", msg!("sync_conflict", title = conflict.title, kept = kept)).unwrap();
+    }
+    for (title, reason) in &report.skipped {
+        write!(out, "\n  - {}: {}", title, reason).unwrap();
+    }
+    out
+}
+
+fn render_size(report: &SizeReport) -> String {
+    let bytes = |count: usize| format_bytes(count as u64);
+    let mut lines = vec![
+        msg!("debug_header"),
+        msg!("debug_version", version = report.version),
+        msg!(
+            "debug_tasks",
+            count = report.tasks,
+            heap = bytes(report.task_heap_bytes),
+            titles = bytes(report.title_bytes),
+            descriptions = bytes(report.description_bytes)
+        ),
+        msg!("debug_indexes", tags = report.tags, links = report.tag_links, titles = report.titles_indexed),
+        msg!("debug_audit", count = report.audit_entries),
+    ];
+    if !report.largest.is_empty() {
+        lines.push(msg!("debug_largest"));
+    }
+    for task in &report.largest {
+        let mut line = msg!(
+            "debug_largest_task",
+            id = task.id,
+            title = task.title,
+            chars = task.description_chars,
+            notes = task.notes,
+            tags = task.tags
+        );
+        if !task.over.is_empty() {
+            line.push_str(&msg!("debug_over_limits", limits = task.over.join(", ")));
+        }
+        lines.push(line);
+    }
+    if let Some(storage) = &report.storage {
+        let format = storage.format.map_or_else(String::new, |format| format.to_string());
+        lines.push(match (&storage.path, storage.bytes_on_disk) {
+            (Some(path), Some(size)) => {
+                msg!("debug_file", path = path.display(), format = format, size = format_bytes(size))
+            }
+            (Some(path), None) => msg!("debug_file_missing", path = path.display(), format = format),
+            (None, _) => msg!("debug_no_file"),
+        });
+        lines.push(match storage.last_save_ms {
+            Some(ms) => msg!("debug_last_save", ms = format!("{:.1}", ms)),
+            None => msg!("debug_not_saved"),
+        });
+    }
+    lines.join("\n")
+}
+
+// One JSON document per command: {"ok": true, "data": ...} on stdout, or a
+// flat error object on stderr
+pub struct JsonRenderer;
+
+impl Renderer for JsonRenderer {
+    fn render(&self, output: &CommandOutput) -> String {
+        json!({ "ok": true, "data": output_json(output) }).to_string()
+    }
+
+    // Whoever asked for JSON reads every reply, confirmations included
+    fn render_at(&self, output: &CommandOutput, _: OutputLevel, _: Option<&Task>, _: Duration) -> Option<String> {
+        Some(self.render(output))
+    }
+
+    // {"error": "<variant>", ...its fields, "message": ...} so a script can match
+    // on the variant without parsing prose
+    fn render_task_error(&self, err: &TaskError) -> String {
+        error_json(err).to_string()
+    }
+
+    fn render_parse_error(&self, err: &ParseError) -> String {
+        error_json(err).to_string()
+    }
+}
+
+// The message stays next to the code so a log read by a person still says what went wrong
+fn error_json<E: Serialize + Display>(err: &E) -> Value {
+    let mut error = serde_json::to_value(err).unwrap_or_else(|_| json!({}));
+    error["message"] = json!(err.to_string());
+    error
+}
+
+// The "kind" shape the control socket answers parse errors with
+pub(crate) fn parse_error_json(err: &ParseError) -> Value {
+    let mut error = match err {
+        ParseError::Empty => json!({ "kind": "Empty" }),
+        ParseError::UnknownCommand(name) => json!({ "kind": "UnknownCommand", "command": name }),
+        ParseError::Usage(_) => json!({ "kind": "Usage" }),
+        ParseError::InvalidId(input) => json!({ "kind": "InvalidId", "input": input }),
+        ParseError::InvalidPriority(input) => json!({ "kind": "InvalidPriority", "input": input }),
+        ParseError::InvalidStatus(input) => json!({ "kind": "InvalidStatus", "input": input }),
+        ParseError::InvalidDate(input) => json!({ "kind": "InvalidDate", "input": input }),
+        ParseError::InvalidEnergy(input) => json!({ "kind": "InvalidEnergy", "input": input }),
+        ParseError::InvalidToken(input) => json!({ "kind": "InvalidToken", "input": input }),
+        ParseError::InvalidColumn(input) => json!({ "kind": "InvalidColumn", "input": input }),
+    };
+    error["message"] = json!(err.to_string());
+    error
+}
+
+fn bulk_change_json(change: &BulkChange) -> Value {
+    match change {
+        BulkChange::Tag(tag) => json!({ "tag": tag }),
+        BulkChange::Priority(priority) => json!({ "priority": priority }),
+    }
+}
+
+// The `data` part of the JSON envelope; also the result of the matching RPC call
+pub(crate) fn output_json(output: &CommandOutput) -> Value {
+    match output {
+        CommandOutput::Help => json!({ "help": msg!("help_text").lines().collect::<Vec<_>>() }),
+        CommandOutput::Added { id, similar } => {
+            let similar: Vec<Value> = similar.iter().map(|(id, title)| json!({ "id": id, "title": title })).collect();
+            json!({ "id": id, "similar": similar })
+        }
+        CommandOutput::Updated(id)
+        | CommandOutput::Tagged(id)
+        | CommandOutput::Noted(id)
+        | CommandOutput::Deleted(id) => json!({ "id": id }),
+        CommandOutput::DueSet { id, date } => json!({ "id": id, "due": date }),
+        CommandOutput::Pinned { id, pinned, completed } => {
+            json!({ "id": id, "pinned": pinned, "completed": completed })
+        }
+        CommandOutput::AddedLines { created, skipped } => {
+            let skipped: Vec<Value> =
+                skipped.iter().map(|(n, reason)| json!({ "line": n, "reason": reason })).collect();
+            json!({ "created": created, "skipped": skipped })
+        }
+        CommandOutput::Cleared(ids) => json!({ "deleted": ids }),
+        CommandOutput::Task { task, related, .. } => {
+            let mut value = json!(task);
+            if !related.is_empty() {
+                value["related"] =
+                    related.iter().map(|other| json!({ "id": other.id, "title": other.title, "status": other.status })).collect();
+            }
+            value
+        }
+        CommandOutput::Related { id, other, related, changed } => {
+            json!({ "id": id, "other": other, "related": related, "changed": changed })
+        }
+        CommandOutput::PrioritySet { priority, changed, unchanged } => {
+            let changed: Vec<Value> = changed.iter().map(|(id, from)| json!({ "id": id, "from": from })).collect();
+            json!({ "priority": priority, "changed": changed, "unchanged": unchanged })
+        }
+        CommandOutput::Graph(dot) => json!({ "dot": dot }),
+        CommandOutput::Tasks { tasks, .. } | CommandOutput::Table { tasks, .. } => json!(tasks),
+        CommandOutput::Matches { hits, show_score, .. } => ranked(hits)
+            .into_iter()
+            .map(|(score, task)| {
+                let mut value = json!(task);
+                if *show_score {
+                    value["score"] = json!(score);
+                }
+                value
+            })
+            .collect(),
+        CommandOutput::Stats { total, completed, in_progress, pending, inbox, pinned, goal } => {
+            let mut stats = json!({
+                "total": total,
+                "completed": completed,
+                "in_progress": in_progress,
+                "pending": pending,
+                "inbox": inbox,
+                "pinned": pinned,
+            });
+            if let Some(goal) = goal {
+                stats["goal"] = json!(goal);
+            }
+            stats
+        }
+        CommandOutput::Count { open, total } => json!({ "open": open, "total": total }),
+        CommandOutput::StatsByUser(rows) => json!(rows),
+        CommandOutput::StatsWeighted(stats) => json!(stats),
+        CommandOutput::RecurPreview(preview) => json!(preview),
+        CommandOutput::History { entries, undoable, page, pages, .. } => json!({
+            "entries": entries,
+            "undoable": undoable,
+            "page": page,
+            "pages": pages,
+        }),
+        CommandOutput::Undone { label, ids } => json!({ "undone": label, "ids": ids }),
+        CommandOutput::Exported { path, count } => json!({ "path": path, "count": count }),
+        CommandOutput::TagsExported { path, count } => json!({ "path": path, "count": count }),
+        CommandOutput::TagsImported(report) => json!(report),
+        CommandOutput::TagDescribed(tag) => json!({ "tag": tag }),
+        CommandOutput::GoalSet(progress) => json!(progress),
+        CommandOutput::Goal(progress) => json!(progress),
+        CommandOutput::GoalCleared(cleared) => json!({ "cleared": cleared }),
+        CommandOutput::Tags { rows, .. } => json!(rows),
+        CommandOutput::DryRun { changes, files } => {
+            let changes: Vec<Value> = changes
+                .iter()
+                .map(|change| {
+                    json!({ "event": change.event, "task_id": change.event.task_id(), "title": change.title })
+                })
+                .collect();
+            json!({ "dry_run": true, "changes": changes, "files": files })
+        }
+        CommandOutput::Digest { subject, body } => json!({ "subject": subject, "body": body }),
+        CommandOutput::Copied { id, text, clipboard } => json!({ "id": id, "text": text, "clipboard": clipboard }),
+        CommandOutput::Imported(report) => json!(report),
+        CommandOutput::TasksImported(transfer) => json!(transfer),
+        CommandOutput::Synced(report) => json!(report),
+        CommandOutput::SyncStatus { pending, last_pull, last_push } => {
+            json!({ "pending": pending, "last_pull": last_pull, "last_push": last_push })
+        }
+        CommandOutput::IndexCheck(result) => match result {
+            Ok((tags, titles)) => json!({ "consistent": true, "tags": tags, "titles": titles }),
+            Err(e) => json!({ "consistent": false, "detail": e }),
+        },
+        CommandOutput::Size(report) => json!(report),
+        CommandOutput::Schema(fields) => json!(fields),
+        CommandOutput::JsonSchema(schema) => schema.clone(),
+        CommandOutput::RoundTrip(report) => json!(report),
+        CommandOutput::Health { issues, repairs } => json!({ "issues": issues, "repairs": repairs }),
+        CommandOutput::Board(board) => json!(board),
+        CommandOutput::Tree(forest) => json!(forest),
+        CommandOutput::Matrix(matrix) => json!(matrix),
+        CommandOutput::Month(month) => json!(month),
+        CommandOutput::Summary(summary) => json!(summary),
+        CommandOutput::Next(next) => json!(next),
+        CommandOutput::Stale { days, tasks, now } => json!({
+            "days": days,
+            "tasks": tasks
+                .iter()
+                .map(|task| json!({ "task": task, "idle_days": review::idle_days(task, *now) }))
+                .collect::<Vec<_>>(),
+        }),
+        CommandOutput::Waiting { groups, .. } => {
+            json!(groups.iter().map(|(who, tasks)| json!({ "who": who, "tasks": tasks })).collect::<Vec<_>>())
+        }
+        CommandOutput::ViewSaved { name, replaced } => json!({ "view": name, "replaced": replaced }),
+        CommandOutput::ViewRenamed { from, to } => json!({ "from": from, "to": to }),
+        CommandOutput::ViewDeleted(name) => json!({ "view": name }),
+        CommandOutput::Views(views) => json!(views),
+        CommandOutput::BulkPreview { change, tasks } => json!({ "change": bulk_change_json(change), "tasks": tasks }),
+        CommandOutput::BulkApplied { change, changed, unchanged, failed } => {
+            let failed: Vec<Value> = failed.iter().map(|(id, reason)| json!({ "id": id, "reason": reason })).collect();
+            json!({ "change": bulk_change_json(change), "changed": changed, "unchanged": unchanged, "failed": failed })
+        }
+        CommandOutput::Duplicates(clusters) => json!(clusters
+            .iter()
+            .map(|cluster| cluster.iter().map(|(id, title)| json!({ "id": id, "title": title })).collect::<Vec<_>>())
+            .collect::<Vec<_>>()),
+        CommandOutput::Timesheet { sheet, .. } => json!({
+            "group": sheet.group,
+            "period": sheet.period,
+            "rows": sheet.rows.iter().map(|(name, time)| json!({ "name": name, "seconds": time.as_secs() })).collect::<Vec<_>>(),
+            "total_seconds": sheet.total.as_secs(),
+        }),
+    }
+}
+
+pub(crate) fn task_error_json(err: &TaskError) -> Value {
+    let mut error = match err {
+        TaskError::TaskNotFound { id } => json!({ "kind": "TaskNotFound", "id": id }),
+        TaskError::NoTitleMatch { text } => json!({ "kind": "TaskNotFound", "title": text }),
+        TaskError::AmbiguousTitle { text, matches } => {
+            let matches: Vec<Value> = matches.iter().map(|(id, title)| json!({ "id": id, "title": title })).collect();
+            json!({ "kind": "AmbiguousTitle", "title": text, "matches": matches })
+        }
+        TaskError::InvalidInput => json!({ "kind": "InvalidInput" }),
+        TaskError::Invalid { reason } => json!({ "kind": "InvalidInput", "reason": reason }),
+        TaskError::DuplicateTask { title, existing_id } => {
+            json!({ "kind": "DuplicateTask", "title": title, "existing_id": existing_id })
+        }
+        TaskError::TaskCompleted { id } => json!({ "kind": "TaskCompleted", "id": id }),
+        TaskError::NotOwner { id, owner } => json!({ "kind": "NotOwner", "id": id, "owner": owner }),
+        TaskError::InvalidTransition { from, to, allowed } => json!({
+            "kind": "InvalidTransition",
+            "from": from,
+            "to": to,
+            "allowed": allowed,
+        }),
+        TaskError::Storage(_) => json!({ "kind": "Storage" }),
+        TaskError::Remote(_) => json!({ "kind": "Remote" }),
+        TaskError::Import(_) => json!({ "kind": "Import" }),
+        TaskError::ValidationFailed(violations) => json!({ "kind": "ValidationFailed", "violations": violations }),
+    };
+    error["message"] = json!(err.to_string());
+    error
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{TaskManager, TaskStatus};
+    use chrono::{TimeZone, Utc};
+
+    fn sample_task() -> Task {
+        let mut task = Task::new(1, "Write report".to_string(), "Q3 numbers".to_string(), Priority::High);
+        task.add_tag("work".to_string());
+        task.add_tag("q3".to_string());
+        task.update_status(TaskStatus::InProgress);
+        task.uid = "6f1c2b8e-0d6a-4a55-9a51-3f0ad3c1e7b2".to_string();
+        task.created_at = Utc.with_ymd_and_hms(2024, 7, 1, 9, 30, 0).unwrap();
+        task.updated_at = Utc.with_ymd_and_hms(2024, 7, 3, 14, 5, 0).unwrap();
+        task
+    }
+
+    #[test]
+    fn test_compact_format() {
+        assert_eq!(format_task(&sample_task(), DisplayMode::Compact), "#1    !!  Write report  [In Progress]  tags: 2");
+        let plain = Task::new(12, "Walk dog".to_string(), String::new(), Priority::Low);
+        assert_eq!(format_task(&plain, DisplayMode::Compact), "#12   ·   Walk dog  [Pending]");
+        let pinned = Task { pinned: true, ..plain };
+        assert_eq!(format_task(&pinned, DisplayMode::Compact), "#12   ·   ★ Walk dog  [Pending]");
+        let late = Task { due: NaiveDate::from_ymd_opt(2024, 7, 1), ..pinned };
+        assert_eq!(format_task(&late, DisplayMode::Compact), "#12   ·   ★ Walk dog  [Pending] ●");
+        assert_eq!(pressure_marker(Some(Pressure::Tight)), " ◐");
+        assert_eq!(pressure_marker(None), "");
+    }
+
+    #[test]
+    fn test_normal_format() {
+        assert_eq!(
+            format_task(&sample_task(), DisplayMode::Normal),
+            "ID: 1 | Write report | Priority: High | Status: In Progress\nDescription: Q3 numbers\nTags: [work, q3]\n"
+        );
+        assert_eq!(sample_task().to_string(), format_task(&sample_task(), DisplayMode::Normal));
+    }
+
+    #[test]
+    fn test_verbose_format() {
+        assert_eq!(
+            format_task(&sample_task(), DisplayMode::Verbose),
+            "ID: 1 | Write report | Priority: High | Status: In Progress\nDescription: Q3 numbers\nTags: [work, q3]\n\
+             Created: 2024-07-01 09:30 UTC\nUpdated: 2024-07-03 14:05 UTC\n"
+        );
+    }
+
+    #[test]
+    fn test_human_history() {
+        let entry = |action: &str, task_id, detail: &str| AuditEntry {
+            timestamp: Utc.with_ymd_and_hms(2024, 7, 3, 14, 5, 0).unwrap(),
+            user: "alice".to_string(),
+            action: action.to_string(),
+            task_id,
+            detail: detail.to_string(),
+        };
+        let output = CommandOutput::History {
+            task_id: None,
+            entries: vec![entry("restored", None, ""), entry("status", Some(1), "Pending -> Completed")],
+            undoable: vec![false, true],
+            page: 1,
+            pages: 3,
+        };
+        assert_eq!(
+            HumanRenderer.render(&output),
+            "=== History ===\n\
+             2024-07-03 14:05 UTC  alice      restored -\n\
+             2024-07-03 14:05 UTC  alice      status   #1    Pending -> Completed  [undo]\n\
+             Page 1 of 3"
+        );
+    }
+
+    #[test]
+    fn test_json_list_schema() {
+        let output = CommandOutput::Tasks { view: ListView::All, tasks: vec![sample_task()], mode: None };
+        assert_eq!(
+            JsonRenderer.render(&output),
+            r#"{"data":[{"created_at":"2024-07-01T09:30:00Z","description":"Q3 numbers","id":1,"pinned":false,"priority":"High","status":"InProgress","tags":["work","q3"],"title":"Write report","uid":"6f1c2b8e-0d6a-4a55-9a51-3f0ad3c1e7b2","updated_at":"2024-07-03T14:05:00Z"}],"ok":true}"#
+        );
+    }
+
+    #[test]
+    fn test_json_show_schema() {
+        let output = CommandOutput::Task { task: Box::new(sample_task()), mode: None, related: Vec::new() };
+        assert_eq!(
+            JsonRenderer.render(&output),
+            r#"{"data":{"created_at":"2024-07-01T09:30:00Z","description":"Q3 numbers","id":1,"pinned":false,"priority":"High","status":"InProgress","tags":["work","q3"],"title":"Write report","uid":"6f1c2b8e-0d6a-4a55-9a51-3f0ad3c1e7b2","updated_at":"2024-07-03T14:05:00Z"},"ok":true}"#
+        );
+
+        // Related tasks come after the task's own fields, and only when there are any
+        let other = Task::new(2, "Book venue".to_string(), String::new(), Priority::Low);
+        let output = CommandOutput::Task { task: Box::new(sample_task()), mode: None, related: vec![other] };
+        let value: Value = serde_json::from_str(&JsonRenderer.render(&output)).unwrap();
+        assert_eq!(value["data"]["related"], json!([{ "id": 2, "title": "Book venue", "status": "Pending" }]));
+    }
+
+    #[test]
+    fn test_matches_are_listed_best_first() {
+        let task = |id, title: &str| Task { id, title: title.to_string(), ..sample_task() };
+        let hits = vec![(60, task(1, "Read the report")), (100, task(2, "Report")), (80, task(3, "Reporting"))];
+        let output = CommandOutput::Matches { keyword: "report".to_string(), hits, show_score: true };
+        let human = HumanRenderer.render(&output);
+        let scores: Vec<&str> = human.lines().skip(1).filter_map(|line| line.split(' ').next()).collect();
+        assert_eq!(scores, vec!["[100]", "[80]", "[60]"]);
+        let json: Value = serde_json::from_str(&JsonRenderer.render(&output)).unwrap();
+        let ids: Vec<&Value> = json["data"].as_array().unwrap().iter().map(|hit| &hit["id"]).collect();
+        assert_eq!(ids, vec![2, 3, 1]);
+        assert_eq!(json["data"][0]["score"], 100);
+    }
+
+    #[test]
+    fn test_json_stats_schema() {
+        let output =
+            CommandOutput::Stats { total: 3, completed: 1, in_progress: 1, pending: 1, inbox: 1, pinned: 1, goal: None };
+        assert_eq!(
+            JsonRenderer.render(&output),
+            r#"{"data":{"completed":1,"in_progress":1,"inbox":1,"pending":1,"pinned":1,"total":3},"ok":true}"#
+        );
+    }
+
+    #[test]
+    fn test_json_not_found_error_schema() {
+        let err = TaskManager::new().get_task(5).unwrap_err();
+        assert_eq!(
+            JsonRenderer.render_task_error(&err),
+            r#"{"error":"TaskNotFound","id":5,"message":"Task 5 not found"}"#
+        );
+        assert_eq!(
+            JsonRenderer.render_parse_error(&ParseError::InvalidId("x".to_string())),
+            r#"{"error":"InvalidId","input":"x","message":"Invalid task ID. Please provide a number."}"#
+        );
+    }
+
+    #[test]
+    fn test_dry_run_says_what_would_happen() {
+        let changes = vec![
+            PlannedChange { event: TaskEvent::Deleted(12), title: "Buy milk".to_string() },
+            PlannedChange {
+                event: TaskEvent::StatusChanged { id: 4, from: TaskStatus::Pending, to: TaskStatus::Completed },
+                title: "Call bank".to_string(),
+            },
+        ];
+        let output = CommandOutput::DryRun { changes, files: vec![PathBuf::from("out.ics")] };
+        assert_eq!(
+            HumanRenderer.render(&output),
+            "Dry run: nothing was changed or saved.\nwould delete task 12: Buy milk\n\
+             would move task 4: Call bank from Pending to Completed\nwould write out.ics"
+        );
+    }
+
+    #[test]
+    fn test_output_levels_route_to_stdout_and_errors_to_stderr() {
+        let deleted: Result<CommandOutput, TaskError> = Ok(CommandOutput::Deleted(1));
+        let count: Result<CommandOutput, TaskError> = Ok(CommandOutput::Count { open: 1, total: 2 });
+        let failed: Result<CommandOutput, TaskError> = Err(TaskError::TaskNotFound { id: 7 });
+        let at = |result: &Result<CommandOutput, TaskError>, level| {
+            route(&HumanRenderer, result, level, None, Duration::ZERO)
+        };
+        let stdout = |text: &str| Printed { stdout: Some(text.to_string()), stderr: None };
+        let stderr = |text: &str| Printed { stdout: None, stderr: Some(text.to_string()) };
+
+        assert_eq!(at(&deleted, OutputLevel::Quiet), Printed::default());
+        assert_eq!(at(&count, OutputLevel::Quiet), stdout("1 open of 2 tasks."));
+        assert_eq!(at(&deleted, OutputLevel::Normal).stdout.as_deref(), Some("Task deleted successfully."));
+        for level in [OutputLevel::Quiet, OutputLevel::Normal, OutputLevel::Verbose] {
+            assert_eq!(at(&failed, level), stderr("Error: Task 7 not found\n  hint: run `list` to see valid ids"));
+        }
+        // JSON readers get every reply
+        let json = route(&JsonRenderer, &deleted, OutputLevel::Quiet, None, Duration::ZERO);
+        assert_eq!(json.stdout.as_deref(), Some(r#"{"data":{"id":1},"ok":true}"#));
+    }
+
+    #[test]
+    fn test_verbose_shows_the_changed_task_and_timing() {
+        let task = sample_task();
+        let printed = route(
+            &HumanRenderer,
+            &Ok(CommandOutput::Updated(1)),
+            OutputLevel::Verbose,
+            Some(&task),
+            Duration::from_micros(1500),
+        );
+        let stdout = printed.stdout.unwrap();
+        assert!(stdout.starts_with("Task status updated successfully.\nID: 1 | Write report"), "{}", stdout);
+        assert!(stdout.contains("\nCreated: "));
+        assert!(stdout.ends_with("\n(took 1.50 ms)"));
+        assert_eq!(printed.stderr, None);
+    }
+
+    #[test]
+    fn test_human_stats_rendering() {
+        let output =
+            CommandOutput::Stats { total: 2, completed: 1, in_progress: 0, pending: 1, inbox: 0, pinned: 0, goal: None };
+        assert_eq!(
+            HumanRenderer.render(&output),
+            "=== Task Statistics ===\nTotal tasks: 2\nCompleted: 1\nIn progress: 0\nPending: 1\nCompletion rate: 50.0%"
+        );
+    }
+
+    #[test]
+    fn test_json_summary() {
+        let summary = summary::Summary {
+            open: 3,
+            overdue: 1,
+            due_today: 0,
+            in_progress: 1,
+            inbox: 2,
+            next: Some(summary::Next { id: 7, title: "Pay rent".to_string() }),
+            goal: None,
+        };
+        assert_eq!(
+            JsonRenderer.render(&CommandOutput::Summary(summary)),
+            r#"{"data":{"due_today":0,"in_progress":1,"inbox":2,"next":{"id":7,"title":"Pay rent"},"open":3,"overdue":1},"ok":true}"#
+        );
+    }
+
+    #[test]
+    fn test_waiting_flags_passed_follow_ups() {
+        let date = |day| NaiveDate::from_ymd_opt(2024, 7, day).unwrap();
+        let mut chase = sample_task();
+        chase.waiting_on = Some(crate::Waiting { who: "Alice".to_string(), since: date(1), follow_up: Some(date(8)) });
+        let mut patient = Task::new(2, "Sign lease".to_string(), String::new(), Priority::Low);
+        patient.waiting_on = Some(crate::Waiting { who: "Alice".to_string(), since: date(6), follow_up: None });
+
+        let output = CommandOutput::Waiting { groups: vec![("Alice".to_string(), vec![chase, patient])], today: date(10) };
+        assert_eq!(
+            HumanRenderer.render(&output),
+            "Alice\n  #1    Write report  follow up with Alice (waiting 9 days)\n  #2    Sign lease  (waiting 4 days)\n"
+        );
+    }
+
+    #[test]
+    fn test_task_error_blocks() {
+        let cases = [
+            (TaskError::TaskNotFound { id: 99 }, "Error: Task 99 not found\n  hint: run `list` to see valid ids"),
+            (
+                TaskError::NoTitleMatch { text: "inv".to_string() },
+                "Error: No open task starts with 'inv'. Use its id, or `filter inv` to look for it.",
+            ),
+            (
+                TaskError::AmbiguousTitle { text: "Fix".to_string(), matches: vec![(3, "Fix login".to_string())] },
+                "Error: Several open tasks start with 'Fix'; use the id of the one you mean:\n  #3    Fix login",
+            ),
+            (TaskError::InvalidInput, "Error: Invalid input provided\n  hint: type `help` for the commands and what they take"),
+            (TaskError::Invalid { reason: "no due date".to_string() }, "Error: Invalid input: no due date"),
+            (
+                TaskError::DuplicateTask { title: "Pay rent".to_string(), existing_id: 4 },
+                "Error: Task with this title already exists (ID: 4)\n  input: Pay rent\n  \
+                 hint: `show 4` opens that task; pick another title for a new one",
+            ),
+            (
+                TaskError::TaskCompleted { id: 2 },
+                "Error: Task 2 is completed. Reopen it with `update 2 pending`, or pass --force to change it anyway.",
+            ),
+            (
+                TaskError::NotOwner { id: 5, owner: "ana".to_string() },
+                "Error: Task 5 was created by ana, and others' tasks are protected. \
+                 Pass --force to change it anyway; the override is logged.",
+            ),
+            (
+                TaskError::InvalidTransition {
+                    from: TaskStatus::Pending,
+                    to: TaskStatus::Completed,
+                    allowed: vec![TaskStatus::InProgress],
+                },
+                "Error: Task cannot move from Pending to Completed. Allowed next: progress.",
+            ),
+            (
+                TaskError::Storage("tasks.json: denied".to_string()),
+                "Error: Storage error: tasks.json: denied\n  hint: check that the data file's folder exists and is writable",
+            ),
+            (
+                TaskError::Remote("timed out".to_string()),
+                "Error: Remote error: timed out\n  hint: check the network and the token in the config",
+            ),
+            (
+                TaskError::ValidationFailed(vec!["a task needs at least one tag".to_string()]),
+                "Error: Task breaks the configured rules:\n  - a task needs at least one tag\n  \
+                 hint: `config` shows the rules and limits in force",
+            ),
+        ];
+        for (err, expected) in cases {
+            assert_eq!(task_error_block(&err).render(true), expected);
+        }
+        // Quiet output keeps the message and input, without the hint
+        let duplicate = TaskError::DuplicateTask { title: "Pay rent".to_string(), existing_id: 4 };
+        assert_eq!(task_error_block(&duplicate).render(false), "Error: Task with this title already exists (ID: 4)\n  input: Pay rent");
+    }
+
+    #[test]
+    fn test_parse_error_blocks() {
+        let cases = [
+            (ParseError::Empty, "No command given\n  hint: type `help` for the commands and what they take"),
+            (
+                ParseError::UnknownCommand("lsit".to_string()),
+                "Unknown command. Type 'help' for available commands.\n  input: lsit\n  hint: did you mean 'list'?",
+            ),
+            (
+                ParseError::UnknownCommand("zzzzzz".to_string()),
+                "Unknown command. Type 'help' for available commands.\n  input: zzzzzz\n  \
+                 hint: type `help` for the commands and what they take",
+            ),
+            (ParseError::Usage("usage_tag"), "Usage: tag <task_id> <tag>"),
+            (
+                ParseError::InvalidId("abc".to_string()),
+                "Invalid task ID. Please provide a number.\n  input: abc\n  hint: ids are the numbers `list` shows",
+            ),
+            (
+                ParseError::InvalidPriority("hgih".to_string()),
+                "Invalid priority. Use: low, medium, high, or critical\n  input: hgih\n  hint: did you mean 'high'?",
+            ),
+            (
+                ParseError::InvalidStatus("pendng".to_string()),
+                "Invalid status. Use: pending, progress, or completed\n  input: pendng\n  hint: did you mean 'pending'?",
+            ),
+            (ParseError::InvalidDate("tomorow".to_string()), "Invalid date. Use YYYY-MM-DD.\n  input: tomorow"),
+            (
+                ParseError::InvalidEnergy("hrad".to_string()),
+                "Invalid energy. Use: low (easy), medium or high (hard)\n  input: hrad\n  hint: did you mean 'hard'?",
+            ),
+            (
+                ParseError::InvalidToken("^".to_string()),
+                "A quick-add word needs a value after its sigil, and !priority, @context, ^due and +project may each be given once; put \\ before a sigil meant for the title.\n  input: ^",
+            ),
+            (
+                ParseError::InvalidColumn("updatd".to_string()),
+                "Unknown column. Use: id, title, pri, status, due, tags, energy, age, updated\n  input: updatd\n  hint: did you mean 'updated'?",
+            ),
+        ];
+        for (err, expected) in cases {
+            assert_eq!(parse_error_block(&err).render(true), expected);
+        }
+    }
+}
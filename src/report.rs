@@ -0,0 +1,79 @@
+use chrono::{DateTime, Utc};
+
+use crate::{Task, TaskStatus};
+
+// Everything a printed report shows, gathered before any markup is produced
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReportData {
+    pub generated_at: DateTime<Utc>,
+    pub total: usize,
+    pub completed: usize,
+    pub in_progress: usize,
+    pub pending: usize,
+    // Open tasks, most urgent first: priority, then earliest due date, then id
+    pub open: Vec<Task>,
+    // Completed tasks, most recently finished first
+    pub done: Vec<Task>,
+}
+
+impl ReportData {
+    pub fn gather<'a>(tasks: impl IntoIterator<Item = &'a Task>, generated_at: DateTime<Utc>) -> ReportData {
+        let (mut done, mut open): (Vec<Task>, Vec<Task>) =
+            tasks.into_iter().cloned().partition(|task| task.status == TaskStatus::Completed);
+        // Tasks without a due date sort after dated ones of the same priority
+        open.sort_by(|a, b| {
+            b.priority
+                .cmp(&a.priority)
+                .then_with(|| a.due.is_none().cmp(&b.due.is_none()))
+                .then_with(|| a.due.cmp(&b.due))
+                .then_with(|| a.id.cmp(&b.id))
+        });
+        done.sort_by(|a, b| b.updated_at.cmp(&a.updated_at).then_with(|| a.id.cmp(&b.id)));
+
+        let in_progress = open.iter().filter(|task| task.status == TaskStatus::InProgress).count();
+        ReportData {
+            generated_at,
+            total: open.len() + done.len(),
+            completed: done.len(),
+            in_progress,
+            pending: open.len() - in_progress,
+            open,
+            done,
+        }
+    }
+
+    // Whole percent of tasks completed; 0 for an empty list
+    pub fn completion_rate(&self) -> u32 {
+        if self.total == 0 {
+            return 0;
+        }
+        (self.completed * 100 / self.total) as u32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Priority;
+    use chrono::NaiveDate;
+
+    #[test]
+    fn test_gather_orders_open_tasks_by_urgency() {
+        let mut tasks = vec![
+            Task::new(1, "Low".to_string(), String::new(), Priority::Low),
+            Task::new(2, "High, undated".to_string(), String::new(), Priority::High),
+            Task::new(3, "High, dated".to_string(), String::new(), Priority::High),
+            Task::new(4, "Done".to_string(), String::new(), Priority::Critical),
+        ];
+        tasks[2].due = NaiveDate::from_ymd_opt(2024, 9, 1);
+        tasks[3].update_status(TaskStatus::Completed);
+        tasks[0].update_status(TaskStatus::InProgress);
+
+        let report = ReportData::gather(&tasks, Utc::now());
+        let open: Vec<u32> = report.open.iter().map(|task| task.id).collect();
+        assert_eq!(open, vec![3, 2, 1]);
+        assert_eq!(report.done[0].id, 4);
+        assert_eq!((report.total, report.completed, report.in_progress, report.pending), (4, 1, 1, 2));
+        assert_eq!(report.completion_rate(), 25);
+    }
+}
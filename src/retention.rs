@@ -0,0 +1,173 @@
+use std::time::Duration;
+
+use chrono::{DateTime, NaiveDate, TimeDelta, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::dates;
+use crate::{Task, TaskStatus, msg};
+
+// `[retention]` in the config: how long a completed task stays in the list
+// before it moves to the archive, and an archived one before it is purged for
+// good. Either left unset does nothing.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct RetentionPolicy {
+    pub archive_completed_after: Option<Duration>,
+    pub purge_archived_after: Option<Duration>,
+}
+
+impl RetentionPolicy {
+    pub fn is_off(&self) -> bool {
+        self.archive_completed_after.is_none() && self.purge_archived_after.is_none()
+    }
+}
+
+// A completed task retention took out of the list, kept in the data file
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ArchivedTask {
+    pub task: Task,
+    pub archived_at: DateTime<Utc>,
+}
+
+// The ids of the tasks one run moves to the archive and purges from it
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct RetentionPlan {
+    pub archive: Vec<u32>,
+    pub purge: Vec<u32>,
+}
+
+impl RetentionPlan {
+    pub fn is_empty(&self) -> bool {
+        self.archive.is_empty() && self.purge.is_empty()
+    }
+
+    pub fn summary(&self) -> String {
+        msg!("retention_applied", archived = self.archive.len(), purged = self.purge.len())
+    }
+}
+
+// Pure over the tasks, the archive, the clock and the policy. A task exactly
+// as old as the limit goes; completion time falls back to the last update for
+// tasks completed before it was recorded.
+pub fn plan<'a>(
+    tasks: impl IntoIterator<Item = &'a Task>,
+    archive: &[ArchivedTask],
+    now: DateTime<Utc>,
+    policy: &RetentionPolicy,
+) -> RetentionPlan {
+    let older_than = |at: DateTime<Utc>, limit: Duration| TimeDelta::from_std(limit).is_ok_and(|limit| now - at >= limit);
+    let archive_ids = match policy.archive_completed_after {
+        Some(limit) => tasks
+            .into_iter()
+            .filter(|task| task.status == TaskStatus::Completed)
+            .filter(|task| older_than(task.completed_at.unwrap_or(task.updated_at), limit))
+            .map(|task| task.id)
+            .collect(),
+        None => Vec::new(),
+    };
+    let purge = match policy.purge_archived_after {
+        Some(limit) => archive.iter().filter(|archived| older_than(archived.archived_at, limit)).map(|archived| archived.task.id).collect(),
+        None => Vec::new(),
+    };
+    RetentionPlan { archive: archive_ids, purge }
+}
+
+// Once a day: not yet run, or last run on an earlier day
+pub fn is_due(last_run: Option<NaiveDate>, today: NaiveDate) -> bool {
+    last_run.is_none_or(|last| last < today)
+}
+
+// "30d", "6w" or "1y" (365 days); shorter units as for reminders
+pub fn parse_age(text: &str) -> Option<Duration> {
+    let units = [('y', 365 * 86_400), ('w', 7 * 86_400), ('d', 86_400), ('h', 3_600), ('m', 60)];
+    dates::parse_suffixed(text, &units)
+}
+
+// The largest of y, w, d, h or m that says it exactly
+pub fn format_age(age: Duration) -> String {
+    let (days, whole_days) = (age.as_secs() / 86_400, age.as_secs().is_multiple_of(86_400));
+    match days {
+        d if whole_days && d > 0 && d.is_multiple_of(365) => format!("{}y", d / 365),
+        d if whole_days && d > 0 && d.is_multiple_of(7) => format!("{}w", d / 7),
+        _ => dates::format_offset(age),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+    use crate::Priority;
+
+    fn now() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 7, 31, 12, 0, 0).unwrap()
+    }
+
+    fn completed(id: u32, ago: TimeDelta) -> Task {
+        let mut task = Task::new(id, format!("Task {}", id), String::new(), Priority::Medium);
+        task.status = TaskStatus::Completed;
+        task.completed_at = Some(now() - ago);
+        task
+    }
+
+    fn archived(id: u32, ago: TimeDelta) -> ArchivedTask {
+        ArchivedTask { task: completed(id, ago + TimeDelta::days(30)), archived_at: now() - ago }
+    }
+
+    fn policy(archive: &str, purge: &str) -> RetentionPolicy {
+        RetentionPolicy { archive_completed_after: parse_age(archive), purge_archived_after: parse_age(purge) }
+    }
+
+    #[test]
+    fn test_plan_acts_on_the_boundary_and_not_before() {
+        let second = TimeDelta::seconds(1);
+        let mut open = completed(4, TimeDelta::days(90));
+        open.status = TaskStatus::Pending;
+        let tasks = [
+            completed(1, TimeDelta::days(30) - second),
+            completed(2, TimeDelta::days(30)),
+            completed(3, TimeDelta::days(30) + second),
+            // Never completed, however old
+            open,
+        ];
+        let archive = [
+            archived(7, TimeDelta::days(365) - second),
+            archived(8, TimeDelta::days(365)),
+            archived(9, TimeDelta::days(400)),
+        ];
+        let plan = plan(&tasks, &archive, now(), &policy("30d", "1y"));
+        assert_eq!(plan, RetentionPlan { archive: vec![2, 3], purge: vec![8, 9] });
+        assert_eq!(plan.summary(), "Retention: archived 2 completed task(s), purged 2 archived task(s).");
+    }
+
+    #[test]
+    fn test_unset_limits_leave_their_half_alone() {
+        let tasks = [completed(1, TimeDelta::days(900))];
+        let archive = [archived(2, TimeDelta::days(900))];
+        let only_archive = RetentionPolicy { purge_archived_after: None, ..policy("30d", "1y") };
+        assert_eq!(plan(&tasks, &archive, now(), &only_archive), RetentionPlan { archive: vec![1], purge: vec![] });
+        assert!(plan(&tasks, &archive, now(), &RetentionPolicy::default()).is_empty());
+        assert!(RetentionPolicy::default().is_off());
+
+        // Tasks completed before completion times were kept age from their last update
+        let mut legacy = completed(3, TimeDelta::zero());
+        (legacy.completed_at, legacy.updated_at) = (None, now() - TimeDelta::days(31));
+        assert_eq!(plan(&[legacy], &[], now(), &only_archive).archive, [3]);
+    }
+
+    #[test]
+    fn test_runs_once_a_day_and_reads_ages() {
+        let today = NaiveDate::from_ymd_opt(2024, 7, 31).unwrap();
+        assert!(is_due(None, today));
+        assert!(is_due(today.pred_opt(), today));
+        assert!(!is_due(Some(today), today));
+
+        assert_eq!(parse_age("30d"), Some(Duration::from_secs(30 * 86_400)));
+        assert_eq!(parse_age("2w"), Some(Duration::from_secs(14 * 86_400)));
+        assert_eq!(parse_age("1y"), Some(Duration::from_secs(365 * 86_400)));
+        assert_eq!((parse_age("0y"), parse_age("y"), parse_age("soon")), (None, None, None));
+        assert_eq!((parse_age("1é"), parse_age("é"), parse_age("3дн")), (None, None, None));
+        let ages: Vec<String> = ["1y", "6w", "30d", "12h"].iter().map(|age| format_age(parse_age(age).unwrap())).collect();
+        assert_eq!(ages, ["1y", "6w", "30d", "12h"]);
+    }
+}
@@ -0,0 +1,102 @@
+use chrono::{DateTime, NaiveDate, TimeDelta, Utc};
+
+use crate::{Priority, Task, TaskEdit, TaskError, TaskManager, TaskStatus};
+
+// `review` without --days
+pub const DEFAULT_DAYS: u32 = 14;
+
+// How long `snooze` keeps a task out of the review
+pub const SNOOZE_DAYS: i64 = 7;
+
+// What to do with one stale task
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Choice {
+    // Still wanted as it is: counts as touched, so it leaves the review
+    Keep,
+    RaisePriority,
+    Snooze,
+    Complete,
+    Delete,
+    // Ask again next review
+    Skip,
+    Quit,
+}
+
+impl Choice {
+    // Enter alone skips, so nothing changes by accident
+    pub fn parse(input: &str) -> Option<Choice> {
+        match input.trim().to_lowercase().as_str() {
+            "k" | "keep" => Some(Choice::Keep),
+            "p" | "priority" => Some(Choice::RaisePriority),
+            "s" | "snooze" => Some(Choice::Snooze),
+            "c" | "complete" => Some(Choice::Complete),
+            "d" | "delete" => Some(Choice::Delete),
+            "" | "n" | "next" => Some(Choice::Skip),
+            "q" | "quit" => Some(Choice::Quit),
+            _ => None,
+        }
+    }
+
+    // Make the change right away, so quitting later keeps it
+    pub fn apply(self, manager: &mut TaskManager, id: u32, today: NaiveDate) -> Result<(), TaskError> {
+        match self {
+            Choice::Keep => manager.touch_task(id),
+            Choice::RaisePriority => {
+                let priority = raised(&manager.get_task(id)?.priority);
+                manager.apply_edit(id, TaskEdit { priority: Some(priority), ..TaskEdit::default() })?;
+                // Already Critical: still looked at
+                manager.touch_task(id)
+            }
+            Choice::Snooze => manager.snooze_task(id, today + TimeDelta::days(SNOOZE_DAYS)),
+            Choice::Complete => manager.update_task_status(id, TaskStatus::Completed),
+            Choice::Delete => manager.delete_task(id),
+            Choice::Skip | Choice::Quit => Ok(()),
+        }
+    }
+}
+
+fn raised(priority: &Priority) -> Priority {
+    match priority {
+        Priority::Low => Priority::Medium,
+        Priority::Medium => Priority::High,
+        Priority::High | Priority::Critical => Priority::Critical,
+    }
+}
+
+// Whole days since the task last changed
+pub fn idle_days(task: &Task, now: DateTime<Utc>) -> i64 {
+    (now - task.updated_at).num_days()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_choices_change_the_task_and_end_its_staleness() {
+        let now = Utc::now();
+        let today = now.date_naive();
+        let mut manager = TaskManager::new();
+        for title in ["Renew passport", "Call plumber", "Plan trip", "Sort photos", "Fix bike"] {
+            let mut task = Task::new(0, title.to_string(), String::new(), Priority::High);
+            task.updated_at = now - TimeDelta::days(30);
+            manager.create_task(task).unwrap();
+        }
+        assert_eq!(idle_days(manager.get_task(1).unwrap(), now), 30);
+
+        for (id, input) in [(1, "k"), (2, "P"), (3, "snooze"), (4, "c"), (5, "next")] {
+            Choice::parse(input).unwrap().apply(&mut manager, id, today).unwrap();
+        }
+        assert_eq!(manager.get_task(2).unwrap().priority, Priority::Critical);
+        assert_eq!(manager.get_task(3).unwrap().snoozed_until, Some(today + TimeDelta::days(SNOOZE_DAYS)));
+        assert_eq!(manager.get_task(4).unwrap().status, TaskStatus::Completed);
+        let left: Vec<u32> = manager.stale_tasks(DEFAULT_DAYS, now).iter().map(|task| task.id).collect();
+        assert_eq!(left, [5]);
+
+        Choice::Delete.apply(&mut manager, 5, today).unwrap();
+        assert!(manager.get_task(5).is_err());
+        assert_eq!(Choice::parse("x"), None);
+        assert_eq!(Choice::parse(""), Some(Choice::Skip));
+        assert_eq!(raised(&Priority::Critical), Priority::Critical);
+    }
+}
@@ -0,0 +1,238 @@
+use std::fmt;
+use std::str::FromStr;
+
+use serde::Serialize;
+
+use crate::diff::TaskDiff;
+use crate::storage::{self, SavedState, StoreFormat};
+use crate::{TaskError, TaskManager, csv_export, msg};
+
+// Formats the task list can be both written out in and read back from
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RoundTripFormat {
+    Json,
+    // Needs the `msgpack` feature
+    #[serde(rename = "msgpack")]
+    MessagePack,
+    Csv,
+}
+
+// How a format loses a field it is known to lose
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Loss {
+    // Not written, so it comes back as on a new task
+    Dropped,
+    // Not written, and made anew on import
+    Regenerated,
+    // A tag holding ';' comes back as several
+    SplitTags,
+}
+
+impl Loss {
+    pub fn describe(self) -> String {
+        match self {
+            Loss::Dropped => msg!("roundtrip_dropped"),
+            Loss::Regenerated => msg!("roundtrip_regenerated"),
+            Loss::SplitTags => msg!("roundtrip_split_tags"),
+        }
+    }
+}
+
+const CSV_LOSSES: [(&str, Loss); 20] = [
+    ("completed_at", Loss::Dropped),
+    ("created_at", Loss::Regenerated),
+    ("created_by", Loss::Dropped),
+    ("external_id", Loss::Dropped),
+    ("key", Loss::Dropped),
+    ("key_prefix", Loss::Dropped),
+    ("last_notified", Loss::Dropped),
+    ("old_keys", Loss::Dropped),
+    ("parent", Loss::Dropped),
+    ("pinned", Loss::Dropped),
+    ("rank", Loss::Dropped),
+    ("relates_to", Loss::Dropped),
+    ("reminders", Loss::Dropped),
+    ("sessions", Loss::Dropped),
+    ("snoozed_until", Loss::Dropped),
+    ("tags", Loss::SplitTags),
+    ("triaged", Loss::Dropped),
+    ("uid", Loss::Regenerated),
+    ("updated_at", Loss::Regenerated),
+    ("waiting_on", Loss::Dropped),
+];
+
+impl RoundTripFormat {
+    pub fn is_available(self) -> bool {
+        match self {
+            RoundTripFormat::MessagePack => StoreFormat::MessagePack.is_available(),
+            RoundTripFormat::Json | RoundTripFormat::Csv => true,
+        }
+    }
+
+    // The fields the format is documented to lose, alphabetically. The data
+    // file formats keep everything.
+    pub fn losses(self) -> &'static [(&'static str, Loss)] {
+        match self {
+            RoundTripFormat::Json | RoundTripFormat::MessagePack => &[],
+            RoundTripFormat::Csv => &CSV_LOSSES,
+        }
+    }
+
+    fn expected(self, field: &str) -> Option<Loss> {
+        self.losses().iter().find(|(name, _)| *name == field).map(|(_, loss)| *loss)
+    }
+
+    // The state written out in this format and read back in
+    fn round_trip(self, state: &SavedState) -> Result<SavedState, String> {
+        match self {
+            RoundTripFormat::Json => storage::decode(&storage::encode(state, StoreFormat::Json)?),
+            RoundTripFormat::MessagePack => storage::decode(&storage::encode(state, StoreFormat::MessagePack)?),
+            RoundTripFormat::Csv => {
+                let tasks: Vec<_> = state.tasks.iter().collect();
+                let tasks = csv_export::from_csv(&csv_export::to_csv(&tasks))?;
+                Ok(SavedState { tasks, next_id: state.next_id, ..SavedState::default() })
+            }
+        }
+    }
+}
+
+impl FromStr for RoundTripFormat {
+    type Err = TaskError;
+
+    fn from_str(s: &str) -> Result<RoundTripFormat, TaskError> {
+        match s.to_lowercase().as_str() {
+            "json" => Ok(RoundTripFormat::Json),
+            "msgpack" | "messagepack" => Ok(RoundTripFormat::MessagePack),
+            "csv" => Ok(RoundTripFormat::Csv),
+            _ => Err(TaskError::InvalidInput),
+        }
+    }
+}
+
+impl fmt::Display for RoundTripFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RoundTripFormat::Json => write!(f, "json"),
+            RoundTripFormat::MessagePack => write!(f, "msgpack"),
+            RoundTripFormat::Csv => write!(f, "csv"),
+        }
+    }
+}
+
+// One field that came back different, on which tasks
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FieldLoss {
+    pub field: String,
+    // None when the format is not known to lose it, which is a bug
+    pub expected: Option<Loss>,
+    pub tasks: Vec<u32>,
+}
+
+// What writing the tasks out and reading them back changed
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RoundTripReport {
+    pub format: RoundTripFormat,
+    pub tasks: usize,
+    // By field, alphabetically
+    pub losses: Vec<FieldLoss>,
+    // Tasks that did not come back at all
+    pub missing: Vec<u32>,
+}
+
+impl RoundTripReport {
+    // Losses the format is not documented to have
+    pub fn unexpected(&self) -> impl Iterator<Item = &FieldLoss> {
+        self.losses.iter().filter(|loss| loss.expected.is_none())
+    }
+
+    pub fn is_lossless(&self) -> bool {
+        self.losses.is_empty() && self.missing.is_empty()
+    }
+}
+
+// Write the manager's tasks out in `format` into memory, load them into a
+// scratch manager and compare each task with the one it came from. The
+// manager itself is left alone.
+pub fn verify(manager: &TaskManager, format: RoundTripFormat) -> Result<RoundTripReport, TaskError> {
+    if !format.is_available() {
+        return Err(TaskError::Storage(msg!("roundtrip_unavailable", format = format)));
+    }
+    let state = manager.to_state();
+    let scratch = TaskManager::from_state(format.round_trip(&state).map_err(TaskError::Storage)?);
+    let mut losses: Vec<FieldLoss> = Vec::new();
+    let mut missing = Vec::new();
+    for task in &state.tasks {
+        let Ok(back) = scratch.get_task(task.id) else {
+            missing.push(task.id);
+            continue;
+        };
+        for field in TaskDiff::between(task, back).fields() {
+            match losses.iter_mut().find(|loss| loss.field == field) {
+                Some(loss) => loss.tasks.push(task.id),
+                None => losses.push(FieldLoss {
+                    field: field.to_string(),
+                    expected: format.expected(field),
+                    tasks: vec![task.id],
+                }),
+            }
+        }
+    }
+    losses.sort_by(|a, b| a.field.cmp(&b.field));
+    Ok(RoundTripReport { format, tasks: state.tasks.len(), losses, missing })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Every field set on some task, with a tag holding ';', next to tasks
+    // that only use what every format keeps
+    fn canonical() -> TaskManager {
+        let state: SavedState = serde_json::from_str(include_str!("../tests/fixtures/roundtrip.json")).unwrap();
+        TaskManager::from_state(state)
+    }
+
+    fn profile(report: &RoundTripReport) -> Vec<(&str, Option<Loss>)> {
+        report.losses.iter().map(|loss| (loss.field.as_str(), loss.expected)).collect()
+    }
+
+    #[test]
+    fn test_data_file_formats_keep_everything() {
+        let manager = canonical();
+        let report = verify(&manager, RoundTripFormat::Json).unwrap();
+        assert!(report.is_lossless(), "{:?}", report);
+        assert_eq!(report.tasks, 4);
+
+        let msgpack = verify(&manager, RoundTripFormat::MessagePack);
+        if cfg!(feature = "msgpack") {
+            assert!(msgpack.unwrap().is_lossless());
+        } else {
+            assert!(matches!(msgpack, Err(TaskError::Storage(e)) if e.contains("msgpack")));
+        }
+    }
+
+    #[test]
+    fn test_csv_loses_exactly_its_documented_fields() {
+        let report = verify(&canonical(), RoundTripFormat::Csv).unwrap();
+        let documented: Vec<(&str, Option<Loss>)> =
+            RoundTripFormat::Csv.losses().iter().map(|(field, loss)| (*field, Some(*loss))).collect();
+        assert_eq!(profile(&report), documented);
+        assert_eq!(report.unexpected().count(), 0);
+        assert!(report.missing.is_empty());
+
+        let affected = |field: &str| report.losses.iter().find(|loss| loss.field == field).unwrap().tasks.clone();
+        // Made anew on every task; dropped fields only where they were set
+        assert_eq!(affected("uid"), [1, 2, 3, 4]);
+        assert_eq!(affected("waiting_on"), [1]);
+        assert_eq!(affected("tags"), [2]);
+        assert_eq!(affected("completed_at"), [1, 3]);
+    }
+
+    #[test]
+    fn test_unknown_format() {
+        assert_eq!("CSV".parse::<RoundTripFormat>().unwrap(), RoundTripFormat::Csv);
+        assert!("todo.txt".parse::<RoundTripFormat>().is_err());
+    }
+}
@@ -0,0 +1,300 @@
+use std::io::{self, BufRead, Write};
+use std::sync::{Arc, Mutex};
+
+use chrono::NaiveDate;
+use serde::Deserialize;
+use serde::de::DeserializeOwned;
+use serde_json::{Value, json};
+
+use crate::command::{Command, CommandOutput, execute};
+use crate::config::Config;
+use crate::render::{output_json, task_error_json};
+use crate::{Energy, Priority, TaskError, TaskEvent, TaskFilter, TaskManager, TaskStatus};
+
+// Error codes from the JSON-RPC 2.0 specification
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+// A command failed; `data` is the TaskError as `--format json` shows it
+const TASK_ERROR: i64 = -32000;
+
+#[derive(Debug, Deserialize)]
+struct Request {
+    jsonrpc: String,
+    method: String,
+    #[serde(default)]
+    params: Value,
+    // Absent for notifications, which get no response
+    id: Option<Value>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ListParams {
+    status: Option<TaskStatus>,
+    priority: Option<Priority>,
+    #[serde(default)]
+    tags: Vec<String>,
+    keyword: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct NoParams {}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct IdParams {
+    id: u32,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct AddParams {
+    title: String,
+    #[serde(default)]
+    description: String,
+    priority: Option<Priority>,
+    due: Option<NaiveDate>,
+    #[serde(default)]
+    tags: Vec<String>,
+    energy: Option<Energy>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct UpdateParams {
+    id: u32,
+    status: Option<TaskStatus>,
+    // Added to the task; existing tags stay
+    #[serde(default)]
+    tags: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct RpcError {
+    code: i64,
+    message: String,
+    data: Option<Value>,
+}
+
+impl RpcError {
+    fn new(code: i64, message: impl Into<String>) -> RpcError {
+        RpcError { code, message: message.into(), data: None }
+    }
+
+    fn to_json(&self) -> Value {
+        let mut error = json!({ "code": self.code, "message": self.message });
+        if let Some(data) = &self.data {
+            error["data"] = data.clone();
+        }
+        error
+    }
+}
+
+impl From<TaskError> for RpcError {
+    fn from(err: TaskError) -> RpcError {
+        RpcError { code: TASK_ERROR, message: err.to_string(), data: Some(task_error_json(&err)) }
+    }
+}
+
+// Answers requests against one manager and collects the events they cause,
+// which go out as `task/event` notifications
+pub struct RpcSession {
+    config: Config,
+    events: Arc<Mutex<Vec<TaskEvent>>>,
+}
+
+impl RpcSession {
+    pub fn new(manager: &mut TaskManager, config: Config) -> RpcSession {
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&events);
+        manager.subscribe(Box::new(move |event, _| sink.lock().unwrap().push(event.clone())));
+        RpcSession { config, events }
+    }
+
+    // The response to one line of input (none for a notification), then one
+    // notification per task event the request caused
+    pub fn handle(&self, manager: &mut TaskManager, line: &str) -> Vec<Value> {
+        let mut messages = Vec::new();
+        match serde_json::from_str::<Value>(line) {
+            Err(e) => messages.push(response(Value::Null, Err(RpcError::new(PARSE_ERROR, e.to_string())))),
+            Ok(value) => {
+                let id = value.get("id").cloned().unwrap_or(Value::Null);
+                match serde_json::from_value::<Request>(value) {
+                    Ok(request) if request.jsonrpc == "2.0" => {
+                        let result = self.call(manager, &request.method, request.params);
+                        if let Some(id) = request.id {
+                            messages.push(response(id, result));
+                        }
+                    }
+                    Ok(_) => {
+                        let error = RpcError::new(INVALID_REQUEST, "jsonrpc must be \"2.0\"");
+                        messages.push(response(id, Err(error)));
+                    }
+                    Err(e) => messages.push(response(id, Err(RpcError::new(INVALID_REQUEST, e.to_string())))),
+                }
+            }
+        }
+        for event in self.events.lock().unwrap().drain(..) {
+            messages.push(json!({ "jsonrpc": "2.0", "method": "task/event", "params": { "event": event } }));
+        }
+        messages
+    }
+
+    fn call(&self, manager: &mut TaskManager, method: &str, params: Value) -> Result<Value, RpcError> {
+        match method {
+            "task/list" => list(manager, parse_params(params)?),
+            "task/get" => {
+                let IdParams { id } = parse_params(params)?;
+                run_command(manager, Command::Show { id, mode: None })
+            }
+            "task/add" => {
+                let AddParams { title, description, priority, due, tags, energy } = parse_params(params)?;
+                if title.trim().is_empty() {
+                    return Err(TaskError::InvalidInput.into());
+                }
+                let command = Command::Add { title, description, priority, due, tags, parent: None, required: None, energy, fields: Vec::new() }.with_defaults(&self.config);
+                match execute(command, manager)? {
+                    CommandOutput::Added { id, .. } => run_command(manager, Command::Show { id, mode: None }),
+                    other => Ok(output_json(&other)),
+                }
+            }
+            "task/update" => {
+                let UpdateParams { id, status, tags } = parse_params(params)?;
+                if let Some(status) = status {
+                    execute(Command::Update { id, status, note: None }, manager)?;
+                }
+                for tag in tags {
+                    execute(Command::Tag { id, tag }, manager)?;
+                }
+                run_command(manager, Command::Show { id, mode: None })
+            }
+            "task/delete" => {
+                let IdParams { id } = parse_params(params)?;
+                run_command(manager, Command::Delete { id })
+            }
+            "stats/get" => {
+                parse_params::<NoParams>(params)?;
+                run_command(manager, Command::Stats)
+            }
+            other => Err(RpcError::new(METHOD_NOT_FOUND, format!("unknown method '{}'", other))),
+        }
+    }
+}
+
+// Serve newline-delimited requests until the input ends; `after` runs once per
+// request, after its messages are written, so the caller can save
+pub fn run(
+    session: &RpcSession,
+    manager: &mut TaskManager,
+    input: impl BufRead,
+    mut output: impl Write,
+    mut after: impl FnMut(&TaskManager),
+) -> io::Result<()> {
+    for line in input.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        for message in session.handle(manager, &line) {
+            writeln!(output, "{}", message)?;
+        }
+        output.flush()?;
+        after(manager);
+    }
+    Ok(())
+}
+
+fn response(id: Value, result: Result<Value, RpcError>) -> Value {
+    match result {
+        Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+        Err(error) => json!({ "jsonrpc": "2.0", "id": id, "error": error.to_json() }),
+    }
+}
+
+// Omitted params count as an empty object
+fn parse_params<T: DeserializeOwned>(params: Value) -> Result<T, RpcError> {
+    let params = if params.is_null() { json!({}) } else { params };
+    serde_json::from_value(params).map_err(|e| RpcError::new(INVALID_PARAMS, e.to_string()))
+}
+
+fn run_command(manager: &mut TaskManager, command: Command) -> Result<Value, RpcError> {
+    Ok(output_json(&execute(command, manager)?))
+}
+
+// Each filter runs as its own command; a task is listed if every one of them matched it
+fn list(manager: &TaskManager, params: ListParams) -> Result<Value, RpcError> {
+    let ListParams { status, priority, tags, keyword } = params;
+    Ok(json!(manager.find(&TaskFilter { status, priority, tags, keyword, ..TaskFilter::default() })))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    // Feed a scripted conversation through in-memory pipes and return every line written
+    fn converse(manager: &mut TaskManager, script: &[&str]) -> Vec<Value> {
+        let session = RpcSession::new(manager, Config::default());
+        let mut output = Vec::new();
+        let mut requests = 0;
+        run(&session, manager, Cursor::new(script.join("\n")), &mut output, |_| requests += 1).unwrap();
+        assert_eq!(requests, script.iter().filter(|line| !line.trim().is_empty()).count());
+        String::from_utf8(output).unwrap().lines().map(|line| serde_json::from_str(line).unwrap()).collect()
+    }
+
+    #[test]
+    fn test_scripted_conversation() {
+        let mut manager = TaskManager::new();
+        let replies = converse(&mut manager, &[
+            r#"{"jsonrpc":"2.0","id":1,"method":"task/add","params":{"title":"Write docs","tags":["work"]}}"#,
+            r#"{"jsonrpc":"2.0","id":2,"method":"task/add","params":{"title":"Buy milk","priority":"Low"}}"#,
+            r#"{"jsonrpc":"2.0","id":3,"method":"task/update","params":{"id":1,"status":"InProgress"}}"#,
+            "",
+            r#"{"jsonrpc":"2.0","id":4,"method":"task/list","params":{"tags":["work"],"status":"InProgress"}}"#,
+            r#"{"jsonrpc":"2.0","id":"s","method":"stats/get"}"#,
+            r#"{"jsonrpc":"2.0","method":"task/delete","params":{"id":2}}"#,
+        ]);
+
+        assert_eq!(replies[0]["id"], 1);
+        assert_eq!(replies[0]["result"]["title"], "Write docs");
+        assert_eq!(replies[0]["result"]["priority"], "Medium");
+        let added = json!({ "jsonrpc": "2.0", "method": "task/event", "params": { "event": { "Added": 1 } } });
+        assert_eq!(replies[1], added);
+        assert_eq!(replies[4]["id"], 3);
+        assert_eq!(replies[4]["result"]["status"], "InProgress");
+        assert_eq!(replies[5]["params"]["event"]["StatusChanged"]["to"], "InProgress");
+        let listed = replies[6]["result"].as_array().unwrap();
+        assert_eq!((listed.len(), &listed[0]["id"]), (1, &json!(1)));
+        assert_eq!(replies[7], json!({ "jsonrpc": "2.0", "id": "s", "result": {
+            "total": 2, "completed": 0, "in_progress": 1, "pending": 1, "inbox": 0, "pinned": 0,
+        } }));
+        // The delete was a notification: no response, only its event
+        assert_eq!(replies[8]["params"]["event"], json!({ "Deleted": 2 }));
+        assert_eq!(replies.len(), 9);
+        assert!(manager.get_task(2).is_err());
+    }
+
+    #[test]
+    fn test_errors_are_error_objects() {
+        let mut manager = TaskManager::new();
+        let replies = converse(&mut manager, &[
+            r#"{"jsonrpc":"2.0","id":1,"method":"task/get","params":{"id":9}}"#,
+            r#"{"jsonrpc":"2.0","id":2,"method":"task/fly"}"#,
+            r#"{"jsonrpc":"2.0","id":3,"method":"task/add","params":{"title":"x","colour":"red"}}"#,
+            r#"{"jsonrpc":"1.0","id":4,"method":"stats/get"}"#,
+            "{not json",
+        ]);
+        let error = |n: usize| &replies[n]["error"];
+        assert_eq!(error(0)["code"], TASK_ERROR);
+        assert_eq!(error(0)["data"]["kind"], "TaskNotFound");
+        assert_eq!(error(0)["data"]["id"], 9);
+        assert_eq!(error(1)["code"], METHOD_NOT_FOUND);
+        assert_eq!(error(2)["code"], INVALID_PARAMS);
+        assert_eq!((error(3)["code"].clone(), replies[3]["id"].clone()), (json!(INVALID_REQUEST), json!(4)));
+        assert_eq!((error(4)["code"].clone(), replies[4]["id"].clone()), (json!(PARSE_ERROR), Value::Null));
+        assert_eq!(replies.len(), 5);
+    }
+}
@@ -0,0 +1,216 @@
+use std::time::Duration;
+
+use chrono::{DateTime, NaiveDate, TimeZone, Utc};
+use serde::Serialize;
+use serde_json::{Map, Value, json};
+
+use crate::storage::SavedState;
+use crate::{Energy, KeyPrefix, Priority, Reminder, Task, TaskStatus, Waiting, WorkSession};
+
+// Fields holding a map from any key, rather than an object with fixed keys
+const MAP_FIELDS: [&str; 1] = ["fields"];
+
+// One key of a task as the data file stores it
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FieldInfo {
+    pub name: String,
+    // The JSON type: string, integer, boolean, array or object
+    pub kind: &'static str,
+    pub required: bool,
+    // "date", "date-time", or the values an enum field takes
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+}
+
+// A task with every field set, so its serialized form shows every key the
+// format has. A field added to Task must be set here too, or `schema` leaves it out.
+pub fn sample_task() -> Task {
+    let day = NaiveDate::from_ymd_opt(2024, 7, 1).expect("valid date");
+    let at = Utc.with_ymd_and_hms(2024, 7, 1, 9, 30, 0).unwrap();
+    let mut task = Task::new(7, "Write report".to_string(), "Q3 numbers".to_string(), Priority::High);
+    task.uid = "6f1c2b8e-0d6a-4a55-9a51-3f0ad3c1e7b2".to_string();
+    task.status = TaskStatus::InProgress;
+    task.tags = vec!["work".into()];
+    task.due = Some(day);
+    task.external_id = Some("github:owner/repo#12".to_string());
+    task.parent = Some("0d6a4a55-6f1c-2b8e-9a51-3f0ad3c1e7b2".to_string());
+    task.sessions = vec![WorkSession { started_at: at, seconds: 1500, completed: true }];
+    task.reminders = vec![Reminder { offset: Duration::from_secs(3600), fired: false }];
+    task.waiting_on = Some(Waiting { who: "ana".to_string(), since: day, follow_up: Some(day) });
+    task.relates_to = vec![3];
+    task.fields.insert("points".to_string(), "3".to_string());
+    task.key_prefix = Some(KeyPrefix { prefix: "AUTH".to_string(), next: 2 });
+    task.key = Some("AUTH-1".to_string());
+    task.old_keys = vec!["WEB-4".to_string()];
+    task.triaged = false;
+    task.pinned = true;
+    task.created_by = Some("ana".to_string());
+    task.rank = 1024;
+    task.last_notified = Some(day);
+    task.snoozed_until = Some(day);
+    task.energy = Some(Energy::Low);
+    task.completed_at = Some(at);
+    task.created_at = at;
+    task.updated_at = at;
+    task
+}
+
+fn variants<T: Serialize>(values: &[T]) -> Vec<Value> {
+    values.iter().map(|value| serde_json::to_value(value).expect("enums serialize")).collect()
+}
+
+// The values an enum field takes, as they are written
+fn enum_values(field: &str) -> Option<Vec<Value>> {
+    match field {
+        "priority" => Some(variants(&[Priority::Low, Priority::Medium, Priority::High, Priority::Critical])),
+        "status" => Some(variants(&[TaskStatus::Pending, TaskStatus::InProgress, TaskStatus::Completed])),
+        "energy" => Some(variants(&[Energy::Low, Energy::Medium, Energy::High])),
+        _ => None,
+    }
+}
+
+fn kind(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(n) if n.is_f64() => "number",
+        Value::Number(_) => "integer",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+// Strings that are dates or timestamps, told apart by parsing the sample
+fn string_format(text: &str) -> Option<&'static str> {
+    if NaiveDate::parse_from_str(text, "%Y-%m-%d").is_ok() {
+        Some("date")
+    } else if DateTime::parse_from_rfc3339(text).is_ok() {
+        Some("date-time")
+    } else {
+        None
+    }
+}
+
+// A JSON Schema for `value`, read off its shape
+fn value_schema(name: &str, value: &Value) -> Value {
+    if let Some(values) = enum_values(name) {
+        return json!({ "type": "string", "enum": values });
+    }
+    let mut schema = json!({ "type": kind(value) });
+    match value {
+        Value::String(text) => {
+            if let Some(format) = string_format(text) {
+                schema["format"] = json!(format);
+            }
+        }
+        Value::Array(items) => {
+            if let Some(first) = items.first() {
+                schema["items"] = value_schema("", first);
+            }
+        }
+        Value::Object(map) if MAP_FIELDS.contains(&name) => {
+            let any = map.values().next().map_or(json!({}), |value| value_schema("", value));
+            schema["additionalProperties"] = any;
+        }
+        Value::Object(map) => {
+            let properties: Map<String, Value> =
+                map.iter().map(|(key, value)| (key.clone(), value_schema(key, value))).collect();
+            schema["properties"] = Value::Object(properties);
+        }
+        _ => {}
+    }
+    schema
+}
+
+fn sample_object() -> Map<String, Value> {
+    match serde_json::to_value(sample_task()) {
+        Ok(Value::Object(map)) => map,
+        _ => unreachable!("a task serializes to an object"),
+    }
+}
+
+// A key is required when a task without it no longer loads
+fn is_required(sample: &Map<String, Value>, key: &str) -> bool {
+    let mut without = sample.clone();
+    without.remove(key);
+    serde_json::from_value::<Task>(Value::Object(without)).is_err()
+}
+
+// Every key of a stored task, alphabetically
+pub fn task_fields() -> Vec<FieldInfo> {
+    let sample = sample_object();
+    sample
+        .iter()
+        .map(|(name, value)| {
+            let detail = match enum_values(name) {
+                Some(values) => Some(values.iter().filter_map(Value::as_str).collect::<Vec<_>>().join(", ")),
+                None => value.as_str().and_then(string_format).map(str::to_string),
+            };
+            FieldInfo { name: name.clone(), kind: kind(value), required: is_required(&sample, name), detail }
+        })
+        .collect()
+}
+
+// JSON Schema (draft 2020-12) for a whole data file, tasks described field by field
+pub fn json_schema() -> Value {
+    let sample = sample_object();
+    let properties: Map<String, Value> = sample.iter().map(|(key, value)| (key.clone(), value_schema(key, value))).collect();
+    let required: Vec<&String> = sample.keys().filter(|key| is_required(&sample, key)).collect();
+    let task = json!({ "type": "object", "properties": properties, "required": required });
+
+    // The rest of the file is kept by the program itself and described only by type
+    let state = serde_json::to_value(SavedState::default()).expect("state serializes");
+    let mut file_properties = Map::new();
+    let mut file_required = Vec::new();
+    for (key, value) in state.as_object().into_iter().flatten() {
+        let schema = if key == "tasks" { json!({ "type": "array", "items": task }) } else { json!({ "type": kind(value) }) };
+        file_properties.insert(key.clone(), schema);
+        let mut without = state.clone();
+        without.as_object_mut().expect("state is an object").remove(key);
+        if serde_json::from_value::<SavedState>(without).is_err() {
+            file_required.push(key.clone());
+        }
+    }
+    json!({
+        "$schema": "https://json-schema.org/draft/2020-12/schema",
+        "title": "Task-Manager data file",
+        "type": "object",
+        "properties": file_properties,
+        "required": file_required,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fields_follow_the_serde_types() {
+        let fields = task_fields();
+        let names: Vec<&str> = fields.iter().map(|field| field.name.as_str()).collect();
+        // The sample sets every field, so each one is written
+        assert_eq!(names.len(), serde_json::to_value(sample_task()).unwrap().as_object().unwrap().len());
+        let required: Vec<&str> = fields.iter().filter(|field| field.required).map(|field| field.name.as_str()).collect();
+        assert_eq!(required, ["description", "id", "priority", "status", "tags", "title"]);
+
+        let field = |name: &str| fields.iter().find(|field| field.name == name).unwrap().clone();
+        assert_eq!(field("status").detail.as_deref(), Some("Pending, InProgress, Completed"));
+        assert_eq!((field("due").kind, field("due").detail.as_deref()), ("string", Some("date")));
+        assert_eq!(field("created_at").detail.as_deref(), Some("date-time"));
+        assert_eq!((field("rank").kind, field("pinned").kind, field("sessions").kind), ("integer", "boolean", "array"));
+    }
+
+    #[test]
+    fn test_json_schema_describes_the_data_file() {
+        let schema = json_schema();
+        assert_eq!(schema["required"], json!(["next_id", "tasks"]));
+        let task = &schema["properties"]["tasks"]["items"];
+        assert_eq!(task["properties"]["priority"]["enum"], json!(["Low", "Medium", "High", "Critical"]));
+        assert_eq!(task["properties"]["updated_at"]["format"], "date-time");
+        assert_eq!(task["properties"]["fields"]["additionalProperties"], json!({ "type": "string" }));
+        assert_eq!(task["properties"]["waiting_on"]["properties"]["since"]["format"], "date");
+        assert_eq!(task["properties"]["sessions"]["items"]["properties"]["seconds"]["type"], "integer");
+        assert!(task["required"].as_array().unwrap().contains(&json!("title")));
+    }
+}
@@ -0,0 +1,375 @@
+use std::borrow::Borrow;
+use std::cmp::Ordering;
+use std::collections::HashSet;
+
+use crate::{Task, TaskStatus};
+
+// Case-fold `text` for comparison, and with `strip_accents` drop its diacritics
+// too, so "Über" and "uber" or "CAFÉ" and "café" come out the same. Folding is
+// per character and locale-free: ß becomes "ss" and final ς becomes σ, while the
+// Turkish dotless ı stays a letter of its own, so "ı" never matches "i", and I
+// folds to i as everywhere but Turkish. İ lowercases to i plus a combining dot,
+// which only matches "i" once accents are stripped.
+pub fn fold(text: &str, strip_accents: bool) -> String {
+    let mut folded = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            'ß' | 'ẞ' => folded.push_str("ss"),
+            'ς' => folded.push('σ'),
+            'ſ' => folded.push('s'),
+            _ => {
+                for lower in c.to_lowercase() {
+                    if !strip_accents {
+                        folded.push(lower);
+                    } else if !is_combining_mark(lower) {
+                        folded.push(strip_accent(lower));
+                    }
+                }
+            }
+        }
+    }
+    folded
+}
+
+// Marks that NFD splits off a letter, and that decomposed input already carries
+fn is_combining_mark(c: char) -> bool {
+    matches!(
+        c,
+        '\u{0300}'..='\u{036F}'
+            | '\u{1AB0}'..='\u{1AFF}'
+            | '\u{1DC0}'..='\u{1DFF}'
+            | '\u{20D0}'..='\u{20FF}'
+            | '\u{FE20}'..='\u{FE2F}'
+    )
+}
+
+// The base letter of a lowercase precomposed one, as NFD would leave it with its
+// marks removed. Covers Latin-1, Latin Extended-A, the Romanian comma-below
+// letters and Greek tonos; letters such as ø, ł and æ that NFD keeps whole are
+// kept whole here too.
+fn strip_accent(c: char) -> char {
+    match c {
+        'à' | 'á' | 'â' | 'ã' | 'ä' | 'å' | 'ā' | 'ă' | 'ą' | 'ǎ' => 'a',
+        'ç' | 'ć' | 'ĉ' | 'ċ' | 'č' => 'c',
+        'ď' => 'd',
+        'è' | 'é' | 'ê' | 'ë' | 'ē' | 'ĕ' | 'ė' | 'ę' | 'ě' => 'e',
+        'ĝ' | 'ğ' | 'ġ' | 'ģ' => 'g',
+        'ĥ' => 'h',
+        'ì' | 'í' | 'î' | 'ï' | 'ĩ' | 'ī' | 'ĭ' | 'į' | 'ǐ' => 'i',
+        'ĵ' => 'j',
+        'ķ' => 'k',
+        'ĺ' | 'ļ' | 'ľ' => 'l',
+        'ñ' | 'ń' | 'ņ' | 'ň' => 'n',
+        'ò' | 'ó' | 'ô' | 'õ' | 'ö' | 'ō' | 'ŏ' | 'ő' | 'ǒ' => 'o',
+        'ŕ' | 'ŗ' | 'ř' => 'r',
+        'ś' | 'ŝ' | 'ş' | 'š' | 'ș' => 's',
+        'ţ' | 'ť' | 'ț' => 't',
+        'ù' | 'ú' | 'û' | 'ü' | 'ũ' | 'ū' | 'ŭ' | 'ů' | 'ű' | 'ų' | 'ǔ' => 'u',
+        'ŵ' => 'w',
+        'ý' | 'ÿ' | 'ŷ' => 'y',
+        'ź' | 'ż' | 'ž' => 'z',
+        'ά' => 'α',
+        'έ' => 'ε',
+        'ή' => 'η',
+        'ί' | 'ϊ' | 'ΐ' => 'ι',
+        'ό' => 'ο',
+        'ύ' | 'ϋ' | 'ΰ' => 'υ',
+        'ώ' => 'ω',
+        _ => c,
+    }
+}
+
+// A task's searchable text, folded once when the task changes instead of on
+// every query. The manager keeps one per task; the words searched for must be
+// folded the same way.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SearchText {
+    title: String,
+    tags: Vec<String>,
+    description: String,
+    fields: Vec<String>,
+}
+
+impl SearchText {
+    pub fn new(task: &Task, strip_accents: bool) -> Self {
+        SearchText {
+            title: fold(&task.title, strip_accents),
+            tags: task.tags.iter().map(|tag| fold(tag, strip_accents)).collect(),
+            description: fold(&task.description, strip_accents),
+            fields: task.fields.values().map(|value| fold(value, strip_accents)).collect(),
+        }
+    }
+
+    // `words` anywhere in the title, description, a tag or a field value, as `filter` matches
+    pub fn contains(&self, words: &str) -> bool {
+        self.title.contains(words)
+            || self.description.contains(words)
+            || self.tags.iter().any(|tag| tag.contains(words))
+            || self.fields.iter().any(|value| value.contains(words))
+    }
+}
+
+// One way a task can match the search words and what it scores. A task scores
+// its best match; the order of RULES is the order results are listed in.
+struct Rule {
+    score: u32,
+    matches: fn(&SearchText, &str) -> bool,
+}
+
+const RULES: &[Rule] = &[
+    // Exact title
+    Rule { score: 100, matches: |text, words| text.title.trim() == words },
+    // Title prefix
+    Rule { score: 80, matches: |text, words| text.title.trim().starts_with(words) },
+    // Anywhere in the title
+    Rule { score: 60, matches: |text, words| text.title.contains(words) },
+    Rule { score: 40, matches: |text, words| text.tags.iter().any(|tag| tag.contains(words)) },
+    Rule { score: 20, matches: |text, words| text.description.contains(words) },
+    // Custom field values, which `filter` has always searched
+    Rule { score: 10, matches: |text, words| text.fields.iter().any(|value| value.contains(words)) },
+];
+
+// How well the task's text matches `words`, already folded; 0 when nothing
+// does, as for a search made only of `key:value` fields
+pub fn score(text: &SearchText, words: &str) -> u32 {
+    let words = words.trim();
+    if words.is_empty() {
+        return 0;
+    }
+    RULES.iter().find(|rule| (rule.matches)(text, words)).map_or(0, |rule| rule.score)
+}
+
+// Titles at least this similar are reported as likely duplicates
+pub const SIMILAR_THRESHOLD: f64 = 0.75;
+
+// How alike two titles are, from 0 to 1, compared folded: the better of their
+// edit distance relative to the longer one and the share of distinct words they
+// have in common, so a typo and an extra "the" both count as close
+pub fn similarity(a: &str, b: &str) -> f64 {
+    let (a, b) = (fold(a.trim(), true), fold(b.trim(), true));
+    let (a_chars, b_chars): (Vec<char>, Vec<char>) = (a.chars().collect(), b.chars().collect());
+    let longest = a_chars.len().max(b_chars.len());
+    if longest == 0 {
+        return 1.0;
+    }
+    let edits = 1.0 - levenshtein(&a_chars, &b_chars) as f64 / longest as f64;
+    let a_words: HashSet<&str> = a.split_whitespace().collect();
+    let b_words: HashSet<&str> = b.split_whitespace().collect();
+    let all = a_words.union(&b_words).count();
+    let words = if all == 0 { 0.0 } else { a_words.intersection(&b_words).count() as f64 / all as f64 };
+    edits.max(words)
+}
+
+pub fn is_similar(a: &str, b: &str) -> bool {
+    similarity(a, b) >= SIMILAR_THRESHOLD
+}
+
+// How alike a mistyped word must be to a known one for `did_you_mean` to offer it
+pub const SUGGEST_THRESHOLD: f64 = 0.5;
+
+// The known word closest to `input`, for "did you mean 'high'?" after "hgih";
+// None when nothing is close enough to be worth offering
+pub fn did_you_mean<'a>(input: &str, candidates: &[&'a str]) -> Option<&'a str> {
+    if input.trim().is_empty() {
+        return None;
+    }
+    candidates
+        .iter()
+        .map(|candidate| (similarity(input, candidate), *candidate))
+        .filter(|(score, _)| *score >= SUGGEST_THRESHOLD)
+        // The first of equally close words, as the list gives them
+        .fold(None, |best: Option<(f64, &str)>, (score, candidate)| match best {
+            Some((best_score, _)) if best_score >= score => best,
+            _ => Some((score, candidate)),
+        })
+        .map(|(_, candidate)| candidate)
+}
+
+// Single-character insertions, deletions and substitutions turning `a` into `b`
+fn levenshtein(a: &[char], b: &[char]) -> usize {
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, a_char) in a.iter().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, b_char) in b.iter().enumerate() {
+            let substitute = diagonal + usize::from(a_char != b_char);
+            diagonal = row[j + 1];
+            row[j + 1] = substitute.min(row[j] + 1).min(diagonal + 1);
+        }
+    }
+    row[b.len()]
+}
+
+// Best scores first; ties put open tasks before completed ones, then the more
+// urgent, then the most recently updated, then the lower id
+pub fn by_relevance(a: (u32, &Task), b: (u32, &Task)) -> Ordering {
+    let ((a_score, a), (b_score, b)) = (a, b);
+    b_score
+        .cmp(&a_score)
+        .then_with(|| (a.status == TaskStatus::Completed).cmp(&(b.status == TaskStatus::Completed)))
+        .then_with(|| b.priority.cmp(&a.priority))
+        .then_with(|| b.updated_at.cmp(&a.updated_at))
+        .then_with(|| a.id.cmp(&b.id))
+}
+
+// Put (score, task) hits in the order `filter` lists them
+pub fn rank<T: Borrow<Task>>(hits: &mut [(u32, T)]) {
+    hits.sort_by(|(a_score, a), (b_score, b)| by_relevance((*a_score, a.borrow()), (*b_score, b.borrow())));
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, Utc};
+
+    use super::*;
+    use crate::Priority;
+
+    // Every title, tag and description here mentions "report" in a different way
+    fn corpus() -> Vec<Task> {
+        let now = Utc::now();
+        let task = |id, title: &str, priority, minutes_ago| {
+            let mut task = Task::new(id, title.to_string(), String::new(), priority);
+            task.updated_at = now - Duration::minutes(minutes_ago);
+            task
+        };
+        let mut tasks = vec![
+            task(1, "Call the bank", Priority::Medium, 0),
+            task(2, "Quarterly report draft", Priority::Low, 5),
+            task(3, "Report", Priority::Low, 50),
+            task(4, "Send the report to Sam", Priority::Medium, 40),
+            task(5, "Report bug in login", Priority::High, 30),
+            task(6, "Tidy inbox", Priority::Critical, 20),
+            task(7, "Report bugs upstream", Priority::High, 10),
+            task(8, "Old report", Priority::Critical, 1),
+            task(9, "Plan sprint", Priority::Low, 3),
+        ];
+        tasks[0].description = "Ask about the yearly report".to_string();
+        tasks[5].add_tag("reports");
+        tasks[7].status = TaskStatus::Completed;
+        tasks[8].fields.insert("source".to_string(), "weekly report".to_string());
+        tasks
+    }
+
+    fn ranked(tasks: &[Task], words: &str) -> Vec<(u32, u32)> {
+        let mut hits: Vec<(u32, &Task)> =
+            tasks.iter().map(|task| (score(&SearchText::new(task, true), words), task)).filter(|(score, _)| *score > 0).collect();
+        rank(&mut hits);
+        hits.into_iter().map(|(score, task)| (task.id, score)).collect()
+    }
+
+    #[test]
+    fn test_rules_score_each_kind_of_match() {
+        let tasks = corpus();
+        let scores: Vec<u32> = tasks.iter().map(|task| score(&SearchText::new(task, true), "report")).collect();
+        assert_eq!(scores, vec![20, 60, 100, 60, 80, 40, 80, 60, 10]);
+        assert_eq!(score(&SearchText::new(&tasks[0], true), ""), 0);
+        assert_eq!(score(&SearchText::new(&tasks[0], true), "groceries"), 0);
+    }
+
+    #[test]
+    fn test_fixed_corpus_keeps_its_order() {
+        let tasks = corpus();
+        // A change to RULES or the tie-breaks that reorders these has to update them here
+        assert_eq!(ranked(&tasks, "report"), vec![
+            (3, 100),
+            // Same score and priority: the more recently updated first
+            (7, 80),
+            (5, 80),
+            // Open before completed, then priority
+            (4, 60),
+            (2, 60),
+            (8, 60),
+            (6, 40),
+            (1, 20),
+            (9, 10),
+        ]);
+    }
+
+    #[test]
+    fn test_fold_ignores_case_and_optionally_accents() {
+        assert_eq!(fold("Über dependency cleanup", true), "uber dependency cleanup");
+        assert_eq!(fold("CAFÉ", true), "cafe");
+        assert_eq!(fold("CAFÉ", false), "café");
+        // German sharp s, in either case, folds to ss as full case folding does
+        assert_eq!(fold("Straße", false), "strasse");
+        assert_eq!(fold("STRAẞE", false), fold("strasse", false));
+        // Final and medial sigma are one letter
+        assert_eq!(fold("ΟΔΟΣ", false), fold("οδος", false));
+        assert_eq!(fold("Αθήνα ΆΘΗΝΑ", true), "αθηνα αθηνα");
+    }
+
+    #[test]
+    fn test_fold_leaves_turkish_i_alone() {
+        // No locale tailoring: I is i, and dotless ı is a different letter
+        assert_eq!(fold("ISTANBUL", true), "istanbul");
+        assert_eq!(fold("ılık", true), "ılık");
+        assert_ne!(fold("ı", true), fold("i", true));
+        // İ lowercases to i and a combining dot above, which accent stripping removes
+        assert_eq!(fold("İstanbul", false), "i\u{307}stanbul");
+        assert_eq!(fold("İstanbul", true), "istanbul");
+    }
+
+    #[test]
+    fn test_fold_treats_decomposed_text_like_precomposed() {
+        let decomposed = "cafe\u{301} nai\u{308}ve";
+        assert_eq!(fold(decomposed, true), fold("café naïve", true));
+        assert_eq!(fold(decomposed, true), "cafe naive");
+        // Kept as written when accents matter, so only the same sequence matches
+        assert_eq!(fold(decomposed, false), decomposed);
+        assert_ne!(fold(decomposed, false), fold("café naïve", false));
+    }
+
+    #[test]
+    fn test_search_text_matches_folded_words() {
+        let mut task = Task::new(1, "Über dependency cleanup".to_string(), "Ask the café".to_string(), Priority::Low);
+        task.add_tag("Straße");
+        let text = SearchText::new(&task, true);
+        assert!(text.contains(&fold("uber", true)));
+        assert!(text.contains(&fold("CAFÉ", true)));
+        assert!(text.contains(&fold("strasse", true)));
+        assert_eq!(score(&text, &fold("UBER DEPENDENCY CLEANUP", true)), 100);
+        let exact = SearchText::new(&task, false);
+        assert!(!exact.contains(&fold("uber", false)));
+        assert!(exact.contains(&fold("ÜBER", false)));
+    }
+
+    #[test]
+    fn test_similar_titles_are_flagged() {
+        let flagged = [
+            ("Fix login bug", "fix the login bug"),
+            ("Fix login bug", "Fix login bgu"),
+            ("Update README", "update readme!"),
+            ("Café menu redesign", "cafe menu redesign"),
+            ("Write quarterly report", "Write the quarterly report"),
+            // One letter apart is close, even when it changes the meaning
+            ("Fix login bug", "Fix logout bug"),
+        ];
+        for (a, b) in flagged {
+            assert!(is_similar(a, b), "{:?} and {:?}: {}", a, b, similarity(a, b));
+            assert_eq!(similarity(a, b), similarity(b, a));
+        }
+        let distinct = [
+            ("Buy milk", "Buy eggs"),
+            ("Fix login bug", "Fix payment rounding bug"),
+            ("Call the bank", "Call the dentist"),
+            ("Write report", "Review report draft with Sam"),
+            ("Deploy", ""),
+        ];
+        for (a, b) in distinct {
+            assert!(!is_similar(a, b), "{:?} and {:?}: {}", a, b, similarity(a, b));
+        }
+        assert_eq!(similarity("Same", " same "), 1.0);
+        assert_eq!(levenshtein(&['k', 'i', 't', 't', 'e', 'n'], &['s', 'i', 't', 't', 'i', 'n', 'g']), 3);
+    }
+
+    #[test]
+    fn test_did_you_mean_offers_the_closest_word() {
+        let priorities = ["low", "medium", "high", "critical"];
+        assert_eq!(did_you_mean("hgih", &priorities), Some("high"));
+        assert_eq!(did_you_mean("CRITCAL", &priorities), Some("critical"));
+        assert_eq!(did_you_mean("meduim", &priorities), Some("medium"));
+        assert_eq!(did_you_mean("urgent", &priorities), None);
+        assert_eq!(did_you_mean("", &priorities), None);
+        assert_eq!(did_you_mean("lst", &["list", "last"]), Some("list"));
+    }
+}
@@ -0,0 +1,560 @@
+use std::str::FromStr;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::thread;
+use std::time::Duration;
+
+use chrono::{Local, NaiveDate};
+use log::{error, info};
+use serde::Deserialize;
+use serde_json::{Value, json};
+use tiny_http::{Header, Method, Request, Response, Server};
+
+use crate::events::{self, EventHub};
+use crate::metrics;
+use crate::render::task_error_json;
+use crate::storage::Storage;
+use crate::{Priority, SharedTaskManager, Task, TaskEdit, TaskError, TaskStatus};
+
+// A comment line goes down idle event streams this often so proxies keep them open
+const HEARTBEAT: Duration = Duration::from_secs(30);
+
+// Body of POST /tasks
+#[derive(Debug, Deserialize)]
+struct NewTask {
+    title: String,
+    #[serde(default)]
+    description: String,
+    priority: Option<Priority>,
+    #[serde(default)]
+    tags: Vec<String>,
+    due: Option<NaiveDate>,
+}
+
+// Body of PATCH /tasks/{id}
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TaskPatch {
+    title: Option<String>,
+    priority: Option<Priority>,
+    status: Option<TaskStatus>,
+}
+
+// A status code and, for everything except /metrics, the JSON envelope used by `--format json`
+struct Reply {
+    status: u16,
+    body: String,
+    content_type: &'static str,
+    // Whether the task list changed and needs saving
+    mutated: bool,
+}
+
+impl Reply {
+    fn json(status: u16, body: Value) -> Reply {
+        Reply { status, body: body.to_string(), content_type: "application/json", mutated: false }
+    }
+
+    fn ok(status: u16, data: Value) -> Reply {
+        Reply::json(status, json!({ "ok": true, "data": data }))
+    }
+
+    fn changed(status: u16, data: Value) -> Reply {
+        Reply { mutated: true, ..Reply::ok(status, data) }
+    }
+
+    fn bad_request(message: String) -> Reply {
+        Reply::json(400, json!({ "ok": false, "error": { "kind": "BadRequest", "message": message } }))
+    }
+
+    fn unauthorized() -> Reply {
+        Reply::json(
+            401,
+            json!({ "ok": false, "error": { "kind": "Unauthorized", "message": "missing or wrong bearer token" } }),
+        )
+    }
+
+    fn not_found() -> Reply {
+        Reply::json(404, json!({ "ok": false, "error": { "kind": "NotFound", "message": "no such endpoint" } }))
+    }
+
+    fn error(err: &TaskError) -> Reply {
+        let status = match err {
+            TaskError::TaskNotFound { .. } | TaskError::NoTitleMatch { .. } => 404,
+            TaskError::AmbiguousTitle { .. } => 409,
+            TaskError::NotOwner { .. } => 403,
+            TaskError::InvalidInput | TaskError::Invalid { .. } | TaskError::ValidationFailed(_) | TaskError::Import(_) => 400,
+            TaskError::DuplicateTask { .. } | TaskError::TaskCompleted { .. } | TaskError::InvalidTransition { .. } => 409,
+            TaskError::Storage(_) => 500,
+            TaskError::Remote(_) => 502,
+        };
+        Reply::json(status, json!({ "ok": false, "error": task_error_json(err) }))
+    }
+}
+
+impl From<Result<Reply, TaskError>> for Reply {
+    fn from(result: Result<Reply, TaskError>) -> Reply {
+        result.unwrap_or_else(|e| Reply::error(&e))
+    }
+}
+
+// JSON API over a shared task list; every change is saved before the response goes out
+pub struct ApiServer {
+    http: Server,
+    manager: SharedTaskManager,
+    // Required as `Authorization: Bearer <token>` on every request when set
+    token: Option<String>,
+    // Requests answered other than /metrics itself, for its command counter
+    commands: AtomicU64,
+    // Open GET /events streams
+    events: Arc<EventHub>,
+}
+
+impl ApiServer {
+    // `addr` such as "127.0.0.1:8080"; port 0 picks a free one
+    pub fn bind(addr: &str, manager: SharedTaskManager) -> Result<ApiServer, TaskError> {
+        let http = Server::http(addr).map_err(|e| TaskError::Storage(format!("cannot listen on {}: {}", addr, e)))?;
+        let events = EventHub::new();
+        manager.with_write(|mgr| mgr.subscribe(events.subscriber()));
+        Ok(ApiServer { http, manager, token: None, commands: AtomicU64::new(0), events })
+    }
+
+    pub fn with_token(mut self, token: Option<String>) -> ApiServer {
+        self.token = token;
+        self
+    }
+
+    fn authorized(&self, request: &Request) -> bool {
+        let Some(token) = &self.token else { return true };
+        let expected = format!("Bearer {}", token);
+        request
+            .headers()
+            .iter()
+            .any(|header| header.field.equiv("Authorization") && header.value.as_str() == expected)
+    }
+
+    pub fn port(&self) -> u16 {
+        self.http.server_addr().to_ip().map_or(0, |addr| addr.port())
+    }
+
+    // Serve requests one at a time until `stop` is called
+    pub fn run(&self, storage: &dyn Storage) {
+        info!("serving HTTP API on port {}", self.port());
+        for request in self.http.incoming_requests() {
+            self.handle(request, storage);
+        }
+    }
+
+    // Make `run` return once the request in progress is answered
+    pub fn stop(&self) {
+        self.http.unblock();
+    }
+
+    fn handle(&self, mut request: Request, storage: &dyn Storage) {
+        if *request.method() == Method::Get && request.url() == "/events" && self.authorized(&request) {
+            return self.stream_events(request);
+        }
+        let mut body = String::new();
+        let mut reply = match request.as_reader().read_to_string(&mut body) {
+            Ok(_) if !self.authorized(&request) => Reply::unauthorized(),
+            Ok(_) if *request.method() == Method::Get && request.url() == "/metrics" => self.metrics(),
+            Ok(_) => {
+                self.commands.fetch_add(1, Ordering::Relaxed);
+                route(&self.manager, request.method(), request.url(), &body)
+            }
+            Err(e) => Reply::bad_request(e.to_string()),
+        };
+        if reply.mutated
+            && let Err(e) = storage.save(&self.manager.with_read(|mgr| mgr.to_state()))
+        {
+            error!("save failed: {}", e);
+            reply = Reply::error(&e);
+        }
+        info!("{} {} -> {}", request.method(), request.url(), reply.status);
+
+        let header = Header::from_bytes(&b"Content-Type"[..], reply.content_type.as_bytes()).expect("valid header");
+        let response = Response::from_string(reply.body)
+            .with_status_code(reply.status)
+            .with_header(header);
+        if let Err(e) = request.respond(response) {
+            error!("could not send response: {}", e);
+        }
+    }
+
+    // GET /events: hand the connection to its own thread, which streams server-sent
+    // events until the client leaves or falls too far behind
+    fn stream_events(&self, request: Request) {
+        info!("GET /events -> streaming ({} open)", self.events.len() + 1);
+        let messages = self.events.subscribe();
+        thread::spawn(move || {
+            let mut writer = request.into_writer();
+            if let Err(e) = events::stream(&mut writer, &messages, HEARTBEAT) {
+                info!("event stream closed: {}", e);
+            }
+        });
+    }
+
+    // GET /metrics: Prometheus text format, computed from the list on each scrape
+    fn metrics(&self) -> Reply {
+        let today = Local::now().date_naive();
+        let commands = self.commands.load(Ordering::Relaxed);
+        let body = self.manager.with_read(|mgr| metrics::render(mgr.list_tasks(), today, commands));
+        Reply { status: 200, body, content_type: metrics::CONTENT_TYPE, mutated: false }
+    }
+}
+
+fn route(manager: &SharedTaskManager, method: &Method, url: &str, body: &str) -> Reply {
+    let (path, query) = url.split_once('?').unwrap_or((url, ""));
+    let segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+
+    match (method, segments.as_slice()) {
+        (Method::Get, ["tasks"]) => list_tasks(manager, query),
+        (Method::Post, ["tasks"]) => create_task(manager, body),
+        (Method::Put, ["sync", "tasks", uid]) => sync_task(manager, uid, body),
+        (Method::Delete, ["sync", "tasks", uid]) => manager
+            .with_write(|mgr| match mgr.find_by_uid(uid).map(|task| task.id) {
+                Some(id) => mgr.delete_task(id).map(|()| Reply::changed(200, json!({ "uid": uid }))),
+                // Already gone, which is what the caller wanted
+                None => Ok(Reply::ok(200, json!({ "uid": uid }))),
+            })
+            .into(),
+        (Method::Get, ["stats"]) => {
+            let (total, completed, in_progress, pending) = manager.get_statistics();
+            Reply::ok(200, json!({
+                "total": total,
+                "completed": completed,
+                "in_progress": in_progress,
+                "pending": pending,
+            }))
+        }
+        (_, ["tasks", id]) => {
+            let Ok(id) = id.parse::<u32>() else {
+                return Reply::bad_request(format!("invalid task id '{}'", id));
+            };
+            match method {
+                Method::Get => manager.get_task(id).map(|task| Reply::ok(200, json!(task))).into(),
+                Method::Patch => patch_task(manager, id, body),
+                Method::Delete => manager.delete_task(id).map(|()| Reply::changed(200, json!({ "id": id }))).into(),
+                _ => Reply::not_found(),
+            }
+        }
+        _ => Reply::not_found(),
+    }
+}
+
+// GET /tasks?status=pending&priority=high&tag=work; every given filter must match
+fn list_tasks(manager: &SharedTaskManager, query: &str) -> Reply {
+    let mut tasks = manager.list_tasks();
+    for pair in query.split('&').filter(|pair| !pair.is_empty()) {
+        let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+        let value = decode_query_value(value);
+        match key {
+            "status" => match TaskStatus::from_str(&value) {
+                Ok(status) => tasks.retain(|task| task.status == status),
+                Err(_) => return Reply::bad_request(format!("invalid status '{}'", value)),
+            },
+            "priority" => match Priority::from_str(&value) {
+                Ok(priority) => tasks.retain(|task| task.priority == priority),
+                Err(_) => return Reply::bad_request(format!("invalid priority '{}'", value)),
+            },
+            "tag" => tasks.retain(|task| task.tags.iter().any(|tag| tag.eq_ignore_ascii_case(&value))),
+            other => return Reply::bad_request(format!("unknown query parameter '{}'", other)),
+        }
+    }
+    Reply::ok(200, json!(tasks))
+}
+
+fn create_task(manager: &SharedTaskManager, body: &str) -> Reply {
+    let new: NewTask = match serde_json::from_str(body) {
+        Ok(new) => new,
+        Err(e) => return Reply::bad_request(e.to_string()),
+    };
+    if new.title.trim().is_empty() {
+        return Reply::error(&TaskError::InvalidInput);
+    }
+    let mut task = Task::new(0, new.title, new.description, new.priority.unwrap_or(Priority::Medium));
+    task.due = new.due;
+    for tag in new.tags {
+        task.add_tag(tag);
+    }
+    manager
+        .with_write(|mgr| {
+            let id = mgr.create_task(task)?;
+            mgr.get_task(id).map(|task| Reply::changed(201, json!(task)))
+        })
+        .into()
+}
+
+fn patch_task(manager: &SharedTaskManager, id: u32, body: &str) -> Reply {
+    let patch: TaskPatch = match serde_json::from_str(body) {
+        Ok(patch) => patch,
+        Err(e) => return Reply::bad_request(e.to_string()),
+    };
+    manager
+        .with_write(|mgr| {
+            let edit = TaskEdit { title: patch.title, priority: patch.priority, ..TaskEdit::default() };
+            mgr.apply_edit(id, edit)?;
+            if let Some(status) = patch.status {
+                mgr.update_task_status(id, status)?;
+            }
+            mgr.get_task(id).map(|task| Reply::changed(200, json!(task)))
+        })
+        .into()
+}
+
+// PUT /sync/tasks/{uid}: store a whole task sent by `sync push`, keeping its timestamps
+fn sync_task(manager: &SharedTaskManager, uid: &str, body: &str) -> Reply {
+    let task: Task = match serde_json::from_str(body) {
+        Ok(task) => task,
+        Err(e) => return Reply::bad_request(e.to_string()),
+    };
+    if task.uid != uid {
+        return Reply::bad_request(format!("body uid does not match '{}'", uid));
+    }
+    manager
+        .with_write(|mgr| {
+            let id = mgr.upsert_task(task)?;
+            mgr.get_task(id).map(|task| Reply::changed(200, json!(task)))
+        })
+        .into()
+}
+
+// Undo form encoding: '+' is a space and %XX an escaped byte
+fn decode_query_value(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'+' => out.push(b' '),
+            b'%' if i + 2 < bytes.len() => {
+                let hex = std::str::from_utf8(&bytes[i + 1..i + 3]).ok();
+                match hex.and_then(|hex| u8::from_str_radix(hex, 16).ok()) {
+                    Some(byte) => {
+                        out.push(byte);
+                        i += 2;
+                    }
+                    None => out.push(b'%'),
+                }
+            }
+            byte => out.push(byte),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::TaskManager;
+    use crate::storage::InMemoryStorage;
+    use std::io::{Read, Write};
+    use std::net::TcpStream;
+    use std::sync::Arc;
+    use std::thread;
+
+    struct TestServer {
+        server: Arc<ApiServer>,
+        storage: Arc<InMemoryStorage>,
+        handle: Option<thread::JoinHandle<()>>,
+    }
+
+    impl TestServer {
+        fn start() -> TestServer {
+            TestServer::start_with_token(None)
+        }
+
+        fn start_with_token(token: Option<&str>) -> TestServer {
+            let server = ApiServer::bind("127.0.0.1:0", SharedTaskManager::new(TaskManager::new())).unwrap();
+            let server = Arc::new(server.with_token(token.map(String::from)));
+            let storage = Arc::new(InMemoryStorage::new());
+            let handle = {
+                let server = Arc::clone(&server);
+                let storage = Arc::clone(&storage);
+                thread::spawn(move || server.run(storage.as_ref()))
+            };
+            TestServer { server, storage, handle: Some(handle) }
+        }
+
+        // A minimal HTTP/1.1 client: one request per connection
+        fn request(&self, method: &str, path: &str, body: &str) -> (u16, Value) {
+            self.request_with(method, path, body, "")
+        }
+
+        // `headers` is extra header lines, each ending in \r\n
+        fn request_with(&self, method: &str, path: &str, body: &str, headers: &str) -> (u16, Value) {
+            let (status, payload) = self.raw_request(method, path, body, headers);
+            (status, serde_json::from_str(&payload).unwrap())
+        }
+
+        fn raw_request(&self, method: &str, path: &str, body: &str, headers: &str) -> (u16, String) {
+            let mut stream = TcpStream::connect(("127.0.0.1", self.server.port())).unwrap();
+            write!(
+                stream,
+                "{} {} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\nContent-Type: application/json\r\n{}Content-Length: {}\r\n\r\n{}",
+                method,
+                path,
+                headers,
+                body.len(),
+                body
+            )
+            .unwrap();
+            let mut response = String::new();
+            stream.read_to_string(&mut response).unwrap();
+            let status = response[9..12].parse().unwrap();
+            let (_, payload) = response.split_once("\r\n\r\n").unwrap();
+            (status, payload.to_string())
+        }
+    }
+
+    impl Drop for TestServer {
+        fn drop(&mut self) {
+            self.server.stop();
+            if let Some(handle) = self.handle.take() {
+                handle.join().unwrap();
+            }
+        }
+    }
+
+    #[test]
+    fn test_crud_round_trip() {
+        let server = TestServer::start();
+
+        let (status, body) = server.request("POST", "/tasks", r#"{"title":"Ship it","priority":"High","tags":["work"]}"#);
+        assert_eq!(status, 201);
+        assert_eq!(body["data"]["id"], 1);
+        assert_eq!(body["data"]["priority"], "High");
+        server.request("POST", "/tasks", r#"{"title":"Water plants"}"#);
+
+        let (status, body) = server.request("GET", "/tasks/1", "");
+        assert_eq!(status, 200);
+        assert_eq!(body["data"]["tags"], json!(["work"]));
+
+        let (status, body) = server.request("PATCH", "/tasks/1", r#"{"status":"Completed","title":"Shipped"}"#);
+        assert_eq!(status, 200);
+        assert_eq!(body["data"]["status"], "Completed");
+        assert_eq!(body["data"]["title"], "Shipped");
+
+        let (_, body) = server.request("GET", "/tasks?status=completed&tag=WORK", "");
+        assert_eq!(body["data"].as_array().unwrap().len(), 1);
+        let (_, body) = server.request("GET", "/tasks?priority=medium", "");
+        assert_eq!(body["data"][0]["title"], "Water plants");
+
+        let (_, body) = server.request("GET", "/stats", "");
+        assert_eq!(body["data"], json!({ "total": 2, "completed": 1, "in_progress": 0, "pending": 1 }));
+
+        let (status, _) = server.request("DELETE", "/tasks/2", "");
+        assert_eq!(status, 200);
+        let saved = server.storage.load().unwrap();
+        assert_eq!(saved.tasks.len(), 1);
+        assert_eq!(saved.tasks[0].title, "Shipped");
+    }
+
+    #[test]
+    fn test_metrics_exposition_format() {
+        let server = TestServer::start();
+        server.request("POST", "/tasks", r#"{"title":"Overdue","priority":"Critical","due":"2020-01-01"}"#);
+        server.request("POST", "/tasks", r#"{"title":"Later"}"#);
+
+        let (status, text) = server.raw_request("GET", "/metrics", "", "");
+        assert_eq!(status, 200);
+        // Every sample belongs to a family declared by a # TYPE line, and no series repeats
+        let mut types = std::collections::HashMap::new();
+        let mut series = std::collections::HashSet::new();
+        for line in text.lines() {
+            if let Some(declaration) = line.strip_prefix("# TYPE ") {
+                let (name, kind) = declaration.split_once(' ').unwrap();
+                assert!(["gauge", "counter"].contains(&kind), "{}", line);
+                assert!(types.insert(name.to_string(), kind.to_string()).is_none(), "{}", line);
+            } else if !line.starts_with("# HELP ") {
+                let (key, value) = line.rsplit_once(' ').unwrap();
+                value.parse::<f64>().unwrap();
+                let name = key.split('{').next().unwrap();
+                assert!(types.contains_key(name), "undeclared {}", line);
+                assert!(series.insert(key.to_string()), "duplicate {}", line);
+            }
+        }
+        assert!(series.contains("tasks_total"));
+        assert!(text.contains("\ntasks_by_priority{priority=\"critical\"} 1\n"));
+        assert!(text.contains("\ntasks_overdue 1\n"));
+        // The two POSTs, not the scrape itself
+        assert!(text.contains("\ntask_commands_total 2\n"));
+        assert_eq!(types["task_commands_total"], "counter");
+    }
+
+    #[test]
+    fn test_events_stream_task_changes() {
+        use std::io::{BufRead, BufReader};
+
+        let server = TestServer::start();
+        let mut stream = TcpStream::connect(("127.0.0.1", server.server.port())).unwrap();
+        stream.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        write!(stream, "GET /events HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+        let mut lines = BufReader::new(stream).lines().map(|line| line.unwrap());
+        assert_eq!(lines.next().unwrap(), "HTTP/1.1 200 OK");
+        let headers: Vec<String> = lines.by_ref().take_while(|line| !line.is_empty()).collect();
+        assert!(headers.contains(&"Content-Type: text/event-stream".to_string()));
+
+        let (status, _) = server.request("POST", "/tasks", r#"{"title":"Watch the stream"}"#);
+        assert_eq!(status, 201);
+        assert_eq!(lines.next().unwrap(), "event: created");
+        let data: Value = serde_json::from_str(lines.next().unwrap().strip_prefix("data: ").unwrap()).unwrap();
+        assert_eq!(data["title"], "Watch the stream");
+        assert_eq!(lines.next().unwrap(), "");
+    }
+
+    #[test]
+    fn test_errors_map_to_status_codes() {
+        let server = TestServer::start();
+        server.request("POST", "/tasks", r#"{"title":"Only once"}"#);
+
+        let (status, body) = server.request("GET", "/tasks/42", "");
+        assert_eq!(status, 404);
+        assert_eq!(body["error"]["kind"], "TaskNotFound");
+        assert_eq!(server.request("DELETE", "/tasks/42", "").0, 404);
+
+        let (status, body) = server.request("POST", "/tasks", r#"{"title":"only ONCE"}"#);
+        assert_eq!(status, 409);
+        assert_eq!(body["error"]["existing_id"], 1);
+
+        assert_eq!(server.request("POST", "/tasks", "not json").0, 400);
+        assert_eq!(server.request("PATCH", "/tasks/1", r#"{"status":"Done"}"#).0, 400);
+        assert_eq!(server.request("GET", "/tasks?status=later", "").0, 400);
+        assert_eq!(server.request("GET", "/tasks/abc", "").0, 400);
+        assert_eq!(server.request("GET", "/nowhere", "").0, 404);
+    }
+
+    #[test]
+    fn test_sync_upsert_requires_the_token() {
+        let server = TestServer::start_with_token(Some("s3cret"));
+        let auth = "Authorization: Bearer s3cret\r\n";
+        let mut task = Task::new(9, "From the laptop".to_string(), String::new(), Priority::High);
+        let path = format!("/sync/tasks/{}", task.uid);
+
+        assert_eq!(server.request("GET", "/tasks", "").0, 401);
+        assert_eq!(server.request_with("GET", "/tasks", "", "Authorization: Bearer nope\r\n").0, 401);
+
+        let (status, body) = server.request_with("PUT", &path, &json!(task).to_string(), auth);
+        assert_eq!(status, 200);
+        assert_eq!(body["data"]["id"], 1);
+        assert_eq!(body["data"]["created_at"], json!(task.created_at));
+
+        task.title = "From the laptop, edited".to_string();
+        server.request_with("PUT", &path, &json!(task).to_string(), auth);
+        let (_, body) = server.request_with("GET", "/tasks", "", auth);
+        assert_eq!(body["data"].as_array().unwrap().len(), 1);
+        assert_eq!(body["data"][0]["title"], "From the laptop, edited");
+        assert_eq!(server.request_with("PUT", "/sync/tasks/other", &json!(task).to_string(), auth).0, 400);
+
+        assert_eq!(server.request_with("DELETE", &path, "", auth).0, 200);
+        assert_eq!(server.request_with("DELETE", &path, "", auth).0, 200);
+        assert!(server.storage.load().unwrap().tasks.is_empty());
+    }
+
+    #[test]
+    fn test_decode_query_value() {
+        assert_eq!(decode_query_value("needs+review"), "needs review");
+        assert_eq!(decode_query_value("a%20b%2Fc"), "a b/c");
+        assert_eq!(decode_query_value("100%"), "100%");
+    }
+}
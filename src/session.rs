@@ -0,0 +1,148 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use log::debug;
+use serde::{Deserialize, Serialize};
+
+use crate::filter::{SortKey, TaskFilter};
+use crate::{Task, TaskError, TaskManager, TaskStatus};
+
+// What `list` is narrowed to while a view or filter is active at the prompt
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Scope {
+    // A saved view and the clauses typed after its name
+    View { name: String, extra: String },
+    Filter { query: String },
+}
+
+impl Scope {
+    pub fn tasks<'a>(&self, manager: &'a TaskManager) -> Result<Vec<&'a Task>, TaskError> {
+        match self {
+            Scope::View { name, extra } => {
+                let extra = TaskFilter::parse(extra).map_err(|e| TaskError::Invalid { reason: e.to_string() })?;
+                manager.find_view(name, &extra)
+            }
+            Scope::Filter { query } => Ok(manager.find(&TaskFilter::query(query))),
+        }
+    }
+
+    pub fn open_count(&self, manager: &TaskManager) -> usize {
+        self.tasks(manager).map_or(0, |tasks| tasks.iter().filter(|task| task.status != TaskStatus::Completed).count())
+    }
+
+    // As the prompt shows it: `(backend)> `
+    pub fn label(&self) -> String {
+        match self {
+            Scope::View { name, extra } if extra.is_empty() => name.clone(),
+            Scope::View { name, extra } => format!("{} {}", name, extra),
+            Scope::Filter { query } => query.clone(),
+        }
+    }
+}
+
+// What the prompt keeps between sessions, in a small file of its own: the active
+// view or filter and the last `list --sort`
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Session {
+    #[serde(default)]
+    pub scope: Option<Scope>,
+    // As typed after --sort
+    #[serde(default)]
+    pub sort: Option<String>,
+}
+
+// Beside the data file: tasks.json -> tasks.session.json
+pub fn session_path(data_path: &Path) -> PathBuf {
+    data_path.with_extension("session.json")
+}
+
+impl Session {
+    // A missing or unreadable file, or one that no longer parses, is an empty
+    // session; so is anything in it that no longer applies, such as a view
+    // deleted since
+    pub fn load(path: &Path, manager: &TaskManager) -> Session {
+        let mut session = match fs::read(path).map(|bytes| serde_json::from_slice::<Session>(&bytes)) {
+            Ok(Ok(session)) => session,
+            Ok(Err(e)) => {
+                debug!("ignoring session file {}: {}", path.display(), e);
+                Session::default()
+            }
+            Err(_) => Session::default(),
+        };
+        if session.scope.as_ref().is_some_and(|scope| scope.tasks(manager).is_err()) {
+            session.scope = None;
+        }
+        if session.sort.as_deref().is_some_and(|sort| SortKey::from_str(sort).is_err()) {
+            session.sort = None;
+        }
+        session
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        fs::write(path, serde_json::to_vec_pretty(self)?)
+    }
+
+    pub fn sort_key(&self) -> Option<SortKey> {
+        self.sort.as_deref().and_then(|sort| SortKey::from_str(sort).ok())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Priority;
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("task-manager-session-{}-{}.json", name, std::process::id()))
+    }
+
+    fn manager() -> TaskManager {
+        let mut manager = TaskManager::new();
+        for title in ["Fix login", "Login page copy", "Invoices"] {
+            let id = manager.add_task(title.to_string(), String::new(), Priority::Medium).unwrap();
+            if title != "Invoices" {
+                manager.add_tag_to_task(id, "backend".to_string()).unwrap();
+            }
+        }
+        manager.update_task_status(2, TaskStatus::Completed).unwrap();
+        manager.save_view("backend", "tag:backend", false).unwrap();
+        manager
+    }
+
+    #[test]
+    fn test_session_round_trip() {
+        let path = temp_path("round-trip");
+        let manager = manager();
+        let session = Session {
+            scope: Some(Scope::View { name: "backend".to_string(), extra: String::new() }),
+            sort: Some("priority".to_string()),
+        };
+        session.save(&path).unwrap();
+        let loaded = Session::load(&path, &manager);
+        assert_eq!(loaded, session);
+        assert_eq!(loaded.sort_key(), Some(SortKey::Priority));
+        assert_eq!(loaded.scope.unwrap().open_count(&manager), 1);
+        assert_eq!(Scope::Filter { query: "login".to_string() }.open_count(&manager), 1);
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_corrupt_or_stale_sessions_are_ignored() {
+        let path = temp_path("stale");
+        let mut manager = manager();
+        assert_eq!(Session::load(&path, &manager), Session::default());
+
+        fs::write(&path, "{\"scope\": {\"kind\": \"view\"").unwrap();
+        assert_eq!(Session::load(&path, &manager), Session::default());
+
+        Session { scope: Some(Scope::View { name: "backend".to_string(), extra: String::new() }), sort: Some("size".to_string()) }
+            .save(&path)
+            .unwrap();
+        manager.delete_view("backend").unwrap();
+        assert_eq!(Session::load(&path, &manager), Session::default());
+        let _ = fs::remove_file(&path);
+    }
+}
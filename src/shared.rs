@@ -0,0 +1,215 @@
+use std::sync::{Arc, PoisonError, RwLock};
+
+use crate::{Priority, Task, TaskError, TaskManager, TaskStatus};
+
+/// A TaskManager that can be cloned into several threads.
+///
+/// Locking is coarse: every call takes the single RwLock for its whole
+/// duration, so each method (and each `with_read`/`with_write` closure) is
+/// atomic with respect to other threads. Many readers may run at once; a
+/// writer excludes everyone. Subscribers fire while the write lock is held,
+/// so they must not call back into the same SharedTaskManager.
+///
+/// A panic while holding the lock does not wedge the other threads: TaskManager
+/// methods leave it consistent between calls, so poisoning is ignored.
+///
+/// ```
+/// use std::thread;
+/// use task_manager::{Priority, SharedTaskManager, TaskManager};
+///
+/// let shared = SharedTaskManager::new(TaskManager::new());
+/// let writer = shared.clone();
+/// thread::spawn(move || writer.add_task("Async".to_string(), String::new(), Priority::Low).unwrap())
+///     .join()
+///     .unwrap();
+/// assert_eq!(shared.list_tasks().len(), 1);
+/// ```
+#[derive(Clone)]
+pub struct SharedTaskManager {
+    inner: Arc<RwLock<TaskManager>>,
+}
+
+impl SharedTaskManager {
+    /// ```
+    /// # use task_manager::{SharedTaskManager, TaskManager};
+    /// let shared = SharedTaskManager::new(TaskManager::new());
+    /// assert!(shared.list_tasks().is_empty());
+    /// ```
+    pub fn new(manager: TaskManager) -> Self {
+        SharedTaskManager {
+            inner: Arc::new(RwLock::new(manager)),
+        }
+    }
+
+    /// Run `f` under the read lock.
+    ///
+    /// ```
+    /// # use task_manager::{SharedTaskManager, TaskManager};
+    /// let shared = SharedTaskManager::new(TaskManager::new());
+    /// assert_eq!(shared.with_read(|manager| manager.list_tasks().len()), 0);
+    /// ```
+    pub fn with_read<R>(&self, f: impl FnOnce(&TaskManager) -> R) -> R {
+        let guard = self.inner.read().unwrap_or_else(PoisonError::into_inner);
+        f(&guard)
+    }
+
+    /// Run `f` under the write lock, so several changes land together.
+    ///
+    /// ```
+    /// # use task_manager::{Priority, SharedTaskManager, TaskManager};
+    /// let shared = SharedTaskManager::new(TaskManager::new());
+    /// shared.with_write(|manager| {
+    ///     let id = manager.add_task("Pair".to_string(), String::new(), Priority::Low)?;
+    ///     manager.add_tag_to_task(id, "team".to_string())
+    /// })
+    /// .unwrap();
+    /// ```
+    pub fn with_write<R>(&self, f: impl FnOnce(&mut TaskManager) -> R) -> R {
+        let mut guard = self.inner.write().unwrap_or_else(PoisonError::into_inner);
+        f(&mut guard)
+    }
+
+    /// ```
+    /// # use task_manager::{Priority, SharedTaskManager, TaskManager};
+    /// let shared = SharedTaskManager::new(TaskManager::new());
+    /// assert_eq!(shared.add_task("One".to_string(), String::new(), Priority::Low).unwrap(), 1);
+    /// ```
+    pub fn add_task(&self, title: String, description: String, priority: Priority) -> Result<u32, TaskError> {
+        self.with_write(|mgr| mgr.add_task(title, description, priority))
+    }
+
+    /// Returns a copy, since a reference can't outlive the lock.
+    ///
+    /// ```
+    /// # use task_manager::{Priority, SharedTaskManager, TaskManager};
+    /// let shared = SharedTaskManager::new(TaskManager::new());
+    /// let id = shared.add_task("One".to_string(), String::new(), Priority::Low).unwrap();
+    /// assert_eq!(shared.get_task(id).unwrap().title(), "One");
+    /// ```
+    pub fn get_task(&self, id: u32) -> Result<Task, TaskError> {
+        self.with_read(|mgr| mgr.get_task(id).cloned())
+    }
+
+    /// ```
+    /// # use task_manager::{Priority, SharedTaskManager, TaskManager, TaskStatus};
+    /// let shared = SharedTaskManager::new(TaskManager::new());
+    /// let id = shared.add_task("One".to_string(), String::new(), Priority::Low).unwrap();
+    /// shared.update_task_status(id, TaskStatus::Completed).unwrap();
+    /// assert_eq!(shared.get_statistics().1, 1);
+    /// ```
+    pub fn update_task_status(&self, id: u32, status: TaskStatus) -> Result<(), TaskError> {
+        self.with_write(|mgr| mgr.update_task_status(id, status))
+    }
+
+    /// ```
+    /// # use task_manager::{Priority, SharedTaskManager, TaskManager};
+    /// let shared = SharedTaskManager::new(TaskManager::new());
+    /// let id = shared.add_task("One".to_string(), String::new(), Priority::Low).unwrap();
+    /// shared.add_tag_to_task(id, "x".to_string()).unwrap();
+    /// assert_eq!(shared.get_task(id).unwrap().tags().len(), 1);
+    /// ```
+    pub fn add_tag_to_task(&self, id: u32, tag: String) -> Result<(), TaskError> {
+        self.with_write(|mgr| mgr.add_tag_to_task(id, tag))
+    }
+
+    /// ```
+    /// # use task_manager::{Priority, SharedTaskManager, TaskManager};
+    /// let shared = SharedTaskManager::new(TaskManager::new());
+    /// let id = shared.add_task("One".to_string(), String::new(), Priority::Low).unwrap();
+    /// shared.delete_task(id).unwrap();
+    /// assert!(shared.list_tasks().is_empty());
+    /// ```
+    pub fn delete_task(&self, id: u32) -> Result<(), TaskError> {
+        self.with_write(|mgr| mgr.delete_task(id))
+    }
+
+    /// Copies of every task, in id order.
+    ///
+    /// ```
+    /// # use task_manager::{SharedTaskManager, TaskManager};
+    /// assert!(SharedTaskManager::new(TaskManager::new()).list_tasks().is_empty());
+    /// ```
+    pub fn list_tasks(&self) -> Vec<Task> {
+        self.with_read(|mgr| mgr.list_tasks().into_iter().cloned().collect())
+    }
+
+    /// (total, completed, in progress, pending)
+    ///
+    /// ```
+    /// # use task_manager::{SharedTaskManager, TaskManager};
+    /// assert_eq!(SharedTaskManager::new(TaskManager::new()).get_statistics(), (0, 0, 0, 0));
+    /// ```
+    pub fn get_statistics(&self) -> (usize, usize, usize, usize) {
+        self.with_read(|mgr| mgr.get_statistics())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::command::{Command, CommandOutput};
+    use crate::TaskEvent;
+    use std::collections::HashSet;
+    use std::thread;
+
+    fn assert_send_sync<T: Send + Sync>() {}
+
+    #[test]
+    fn test_core_types_are_send_and_sync() {
+        assert_send_sync::<Task>();
+        assert_send_sync::<Priority>();
+        assert_send_sync::<TaskStatus>();
+        assert_send_sync::<TaskError>();
+        assert_send_sync::<TaskEvent>();
+        assert_send_sync::<TaskManager>();
+        assert_send_sync::<SharedTaskManager>();
+        assert_send_sync::<Command>();
+        assert_send_sync::<CommandOutput>();
+    }
+
+    #[test]
+    fn test_concurrent_adds_reads_and_deletes_keep_invariants() {
+        let shared = SharedTaskManager::new(TaskManager::new());
+        let threads = 8;
+        let per_thread = 50;
+
+        let handles: Vec<_> = (0..threads)
+            .map(|t| {
+                let shared = shared.clone();
+                thread::spawn(move || {
+                    let mut ids = Vec::new();
+                    for i in 0..per_thread {
+                        let id = shared
+                            .add_task(format!("task {}-{}", t, i), String::new(), Priority::Low)
+                            .unwrap();
+                        assert_eq!(shared.get_task(id).unwrap().id, id);
+                        ids.push(id);
+                        shared.list_tasks();
+                    }
+                    // Delete every other task this thread created
+                    for id in ids.iter().step_by(2) {
+                        shared.delete_task(*id).unwrap();
+                    }
+                    ids
+                })
+            })
+            .collect();
+
+        let mut all_ids = Vec::new();
+        for handle in handles {
+            let ids = handle.join().unwrap();
+            // next_id is monotonic, so each thread sees increasing ids
+            assert!(ids.windows(2).all(|w| w[0] < w[1]));
+            all_ids.extend(ids);
+        }
+
+        let unique: HashSet<u32> = all_ids.iter().copied().collect();
+        assert_eq!(unique.len(), threads * per_thread);
+
+        let remaining = shared.list_tasks();
+        assert_eq!(remaining.len(), threads * per_thread / 2);
+        let max_id = *all_ids.iter().max().unwrap();
+        let next = shared.add_task("after".to_string(), String::new(), Priority::Low).unwrap();
+        assert_eq!(next, max_id + 1);
+    }
+}
@@ -0,0 +1,683 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader, ErrorKind, Read};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::{Mutex, PoisonError};
+use std::time::{Duration, Instant, SystemTime};
+
+use chrono::NaiveDate;
+use log::info;
+use serde::de::{self, DeserializeSeed, Deserializer, IgnoredAny, MapAccess, SeqAccess, Visitor};
+use serde::{Deserialize, Serialize};
+
+use crate::audit::AuditEntry;
+use crate::config::Config;
+use crate::goal::Goal;
+use crate::health::TrashedTask;
+use crate::journal::JournalStorage;
+use crate::migrate::{self, Migrated};
+use crate::retention::ArchivedTask;
+use crate::sync::SyncState;
+use crate::tags::TagRegistry;
+use crate::undo::UndoEntry;
+use crate::{Task, TaskError, TaskManager};
+
+// How many tasks a streaming load reads between progress reports
+pub const LOAD_PROGRESS_STEP: usize = 10_000;
+
+// Everything that is persisted between sessions
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SavedState {
+    pub tasks: Vec<Task>,
+    pub next_id: u32,
+    // Kept after the tasks they mention are deleted; missing in older files
+    #[serde(default)]
+    pub audit: Vec<AuditEntry>,
+    #[serde(default)]
+    pub sync: SyncState,
+    // Saved view name -> query
+    #[serde(default)]
+    pub views: BTreeMap<String, String>,
+    // Steps `undo` can take back, oldest first
+    #[serde(default)]
+    pub undo: Vec<UndoEntry>,
+    // The tag registry, by lowercase tag
+    #[serde(default)]
+    pub tags: TagRegistry,
+    #[serde(default)]
+    pub goal: Option<Goal>,
+    // Tasks `check --fix` could not repair; left out while empty
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub trash: Vec<TrashedTask>,
+    // Completed tasks the retention policy moved out of the list, and the day
+    // it last ran; left out while unused
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub archive: Vec<ArchivedTask>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retention_run: Option<NaiveDate>,
+}
+
+impl Default for SavedState {
+    fn default() -> Self {
+        SavedState {
+            tasks: Vec::new(),
+            next_id: 1,
+            audit: Vec::new(),
+            sync: SyncState::default(),
+            views: BTreeMap::new(),
+            undo: Vec::new(),
+            tags: TagRegistry::new(),
+            goal: None,
+            trash: Vec::new(),
+            archive: Vec::new(),
+            retention_run: None,
+        }
+    }
+}
+
+// A place the task list can be loaded from and saved to; Sync so the control
+// socket can save from its own thread
+pub trait Storage: Send + Sync {
+    fn load(&self) -> Result<SavedState, TaskError>;
+    fn save(&self, state: &SavedState) -> Result<(), TaskError>;
+
+    // Load straight into a manager. `progress` is told how many tasks are in so
+    // far, every LOAD_PROGRESS_STEP of them, by backends that can stream.
+    fn load_manager(&self, progress: &mut dyn FnMut(usize)) -> Result<TaskManager, TaskError> {
+        let _ = progress;
+        Ok(TaskManager::from_state(self.load()?))
+    }
+
+    // Load what the strict loader refused, reading older and hand-written shapes
+    // as `migrate` describes; backends without a plain file just load strictly
+    fn load_lenient(&self) -> Result<Migrated, TaskError> {
+        Ok(Migrated { state: self.load()?, coercions: Vec::new() })
+    }
+
+    // Fold anything saved incrementally back into a single file; called on exit
+    fn compact(&self) -> Result<(), TaskError> {
+        Ok(())
+    }
+
+    // Rewrite the whole list in `format` and keep saving it that way; false for
+    // backends with no file to convert
+    fn convert(&self, state: &SavedState, format: StoreFormat) -> Result<bool, TaskError> {
+        let _ = (state, format);
+        Ok(false)
+    }
+
+    // Where and how the list is kept, for `debug info`
+    fn report(&self) -> StorageReport {
+        StorageReport::default()
+    }
+
+    // The files a save writes, so `watch` can tell when another process saved;
+    // empty for backends with no file
+    fn files(&self) -> Vec<PathBuf> {
+        Vec::new()
+    }
+
+    // Whether another process saved since this one last loaded or saved; false
+    // for backends that cannot tell
+    fn changed_on_disk(&self) -> bool {
+        false
+    }
+}
+
+// The storage half of `debug info`; fields a backend has no answer for stay None
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct StorageReport {
+    pub path: Option<PathBuf>,
+    pub format: Option<StoreFormat>,
+    // The data file plus any journal beside it
+    pub bytes_on_disk: Option<u64>,
+    pub last_save_ms: Option<f64>,
+}
+
+// Remembers how long the latest save took
+#[derive(Default)]
+pub(crate) struct SaveTimer(Mutex<Option<Duration>>);
+
+impl SaveTimer {
+    pub(crate) fn time<T>(&self, save: impl FnOnce() -> T) -> T {
+        let start = Instant::now();
+        let result = save();
+        *self.0.lock().unwrap_or_else(PoisonError::into_inner) = Some(start.elapsed());
+        result
+    }
+
+    pub(crate) fn last_ms(&self) -> Option<f64> {
+        self.0.lock().unwrap_or_else(PoisonError::into_inner).map(|taken| taken.as_secs_f64() * 1000.0)
+    }
+}
+
+// Size of a file, None when it does not exist (yet)
+pub(crate) fn file_size(path: &Path) -> Option<u64> {
+    fs::metadata(path).ok().map(|metadata| metadata.len())
+}
+
+// How the data file is encoded. Loading recognises either one whatever the
+// config says, so switching never strands an existing file.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StoreFormat {
+    Json,
+    // Binary and smaller; needs the `msgpack` feature
+    #[serde(rename = "msgpack")]
+    MessagePack,
+}
+
+// Leads every MessagePack data file; JSON text never starts with a NUL
+const MESSAGEPACK_MAGIC: &[u8] = b"\0TMGR-MP1";
+
+impl StoreFormat {
+    pub fn is_available(self) -> bool {
+        match self {
+            StoreFormat::Json => true,
+            StoreFormat::MessagePack => cfg!(feature = "msgpack"),
+        }
+    }
+
+    pub fn detect(contents: &[u8]) -> StoreFormat {
+        if contents.starts_with(MESSAGEPACK_MAGIC) { StoreFormat::MessagePack } else { StoreFormat::Json }
+    }
+}
+
+impl FromStr for StoreFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "json" => Ok(StoreFormat::Json),
+            "msgpack" | "messagepack" => Ok(StoreFormat::MessagePack),
+            _ => Err(format!("unknown store format '{}', expected json or msgpack", s)),
+        }
+    }
+}
+
+impl fmt::Display for StoreFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StoreFormat::Json => write!(f, "json"),
+            StoreFormat::MessagePack => write!(f, "msgpack"),
+        }
+    }
+}
+
+#[cfg(not(feature = "msgpack"))]
+const NO_MESSAGEPACK: &str = "this build has no MessagePack support; rebuild with the 'msgpack' feature";
+
+// SavedState as the bytes of a data file. Both formats serialize the same struct,
+// field names included, so neither can hold something the other cannot.
+pub fn encode(state: &SavedState, format: StoreFormat) -> Result<Vec<u8>, String> {
+    match format {
+        StoreFormat::Json => serde_json::to_vec_pretty(state).map_err(|e| e.to_string()),
+        StoreFormat::MessagePack => encode_messagepack(state),
+    }
+}
+
+pub fn decode(contents: &[u8]) -> Result<SavedState, String> {
+    match StoreFormat::detect(contents) {
+        StoreFormat::Json => serde_json::from_slice(contents).map_err(|e| e.to_string()),
+        StoreFormat::MessagePack => decode_messagepack(&contents[MESSAGEPACK_MAGIC.len()..]),
+    }
+}
+
+#[cfg(feature = "msgpack")]
+fn encode_messagepack(state: &SavedState) -> Result<Vec<u8>, String> {
+    let mut contents = MESSAGEPACK_MAGIC.to_vec();
+    rmp_serde::encode::write_named(&mut contents, state).map_err(|e| e.to_string())?;
+    Ok(contents)
+}
+
+#[cfg(not(feature = "msgpack"))]
+fn encode_messagepack(_state: &SavedState) -> Result<Vec<u8>, String> {
+    Err(NO_MESSAGEPACK.to_string())
+}
+
+#[cfg(feature = "msgpack")]
+fn decode_messagepack(contents: &[u8]) -> Result<SavedState, String> {
+    rmp_serde::from_slice(contents).map_err(|e| e.to_string())
+}
+
+#[cfg(not(feature = "msgpack"))]
+fn decode_messagepack(_contents: &[u8]) -> Result<SavedState, String> {
+    Err(format!("the file is MessagePack, but {}", NO_MESSAGEPACK))
+}
+
+// The data file the config names, journaled or rewritten whole on every save
+pub fn file_storage(config: &Config) -> Box<dyn Storage> {
+    if config.journal {
+        Box::new(
+            JournalStorage::new(config.data_path.clone(), config.journal_compact_after).with_format(config.store_format),
+        )
+    } else {
+        Box::new(FileStorage::new(config.data_path.clone()).with_format(config.store_format))
+    }
+}
+
+// The whole list in one file, pretty-printed JSON unless another format is
+// chosen; a missing file loads as an empty list
+pub struct FileStorage {
+    path: PathBuf,
+    // Only `convert` changes it
+    format: Mutex<StoreFormat>,
+    timer: SaveTimer,
+    // The file as this process last loaded or saved it; None before the first
+    seen: Mutex<Option<Stamp>>,
+}
+
+// A file's modification time and length, None while it does not exist
+type Stamp = Option<(SystemTime, u64)>;
+
+fn stamp(path: &Path) -> Stamp {
+    let metadata = fs::metadata(path).ok()?;
+    Some((metadata.modified().ok()?, metadata.len()))
+}
+
+impl FileStorage {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        FileStorage {
+            path: path.into(),
+            format: Mutex::new(StoreFormat::Json),
+            timer: SaveTimer::default(),
+            seen: Mutex::new(None),
+        }
+    }
+
+    pub fn with_format(self, format: StoreFormat) -> Self {
+        *self.format.lock().unwrap_or_else(PoisonError::into_inner) = format;
+        self
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn format(&self) -> StoreFormat {
+        *self.format.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    // Loads call this before reading, so a save that lands mid-read still shows
+    // as a change; saves call it once the file is in place
+    fn remember_stamp(&self) {
+        *self.seen.lock().unwrap_or_else(PoisonError::into_inner) = Some(stamp(&self.path));
+    }
+}
+
+impl Storage for FileStorage {
+    fn load(&self) -> Result<SavedState, TaskError> {
+        self.remember_stamp();
+        let contents = match fs::read(&self.path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == ErrorKind::NotFound => {
+                info!("{} does not exist yet, starting empty", self.path.display());
+                return Ok(SavedState::default());
+            }
+            Err(e) => return Err(TaskError::Storage(format!("{}: {}", self.path.display(), e))),
+        };
+        let state = decode(&contents).map_err(|e| TaskError::Storage(format!("{}: {}", self.path.display(), e)))?;
+        info!("loaded {} tasks from {}", state.tasks.len(), self.path.display());
+        Ok(state)
+    }
+
+    fn save(&self, state: &SavedState) -> Result<(), TaskError> {
+        self.timer.time(|| {
+            let contents = encode(state, self.format()).map_err(TaskError::Storage)?;
+            // Write to a sibling file first so a crash never leaves a half-written list
+            let tmp = self.path.with_extension("json.tmp");
+            fs::write(&tmp, contents)
+                .and_then(|_| fs::rename(&tmp, &self.path))
+                .map_err(|e| TaskError::Storage(format!("{}: {}", self.path.display(), e)))?;
+            self.remember_stamp();
+            info!("saved {} tasks to {}", state.tasks.len(), self.path.display());
+            Ok(())
+        })
+    }
+
+    // Reads a JSON file as it parses, handing each task to the manager as soon as
+    // it is complete, so a large list never sits in memory twice
+    fn load_manager(&self, progress: &mut dyn FnMut(usize)) -> Result<TaskManager, TaskError> {
+        let storage_error = |e: &dyn fmt::Display| TaskError::Storage(format!("{}: {}", self.path.display(), e));
+        self.remember_stamp();
+        let file = match File::open(&self.path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == ErrorKind::NotFound => {
+                info!("{} does not exist yet, starting empty", self.path.display());
+                return Ok(TaskManager::new());
+            }
+            Err(e) => return Err(storage_error(&e)),
+        };
+        let mut reader = BufReader::new(file);
+        if StoreFormat::detect(reader.fill_buf().map_err(|e| storage_error(&e))?) == StoreFormat::MessagePack {
+            let mut contents = Vec::new();
+            reader.read_to_end(&mut contents).map_err(|e| storage_error(&e))?;
+            let state = decode(&contents).map_err(|e| storage_error(&e))?;
+            info!("loaded {} tasks from {}", state.tasks.len(), self.path.display());
+            return Ok(TaskManager::from_state(state));
+        }
+        let mut deserializer = serde_json::Deserializer::from_reader(reader);
+        let mut manager = TaskManager::new();
+        StateSeed { manager: &mut manager, progress }
+            .deserialize(&mut deserializer)
+            .and_then(|()| deserializer.end())
+            .map_err(|e| storage_error(&e))?;
+        info!("loaded {} tasks from {}", manager.list_tasks().len(), self.path.display());
+        Ok(manager)
+    }
+
+    fn load_lenient(&self) -> Result<Migrated, TaskError> {
+        let storage_error = |e: &dyn fmt::Display| TaskError::Storage(format!("{}: {}", self.path.display(), e));
+        self.remember_stamp();
+        let contents = fs::read(&self.path).map_err(|e| storage_error(&e))?;
+        if StoreFormat::detect(&contents) == StoreFormat::MessagePack {
+            return Ok(Migrated { state: decode(&contents).map_err(|e| storage_error(&e))?, coercions: Vec::new() });
+        }
+        migrate::decode_lenient(&contents).map_err(|e| storage_error(&e))
+    }
+
+    fn convert(&self, state: &SavedState, format: StoreFormat) -> Result<bool, TaskError> {
+        let previous = std::mem::replace(&mut *self.format.lock().unwrap_or_else(PoisonError::into_inner), format);
+        if let Err(e) = self.save(state) {
+            *self.format.lock().unwrap_or_else(PoisonError::into_inner) = previous;
+            return Err(e);
+        }
+        Ok(true)
+    }
+
+    fn report(&self) -> StorageReport {
+        StorageReport {
+            path: Some(self.path.clone()),
+            format: Some(self.format()),
+            bytes_on_disk: file_size(&self.path),
+            last_save_ms: self.timer.last_ms(),
+        }
+    }
+
+    fn files(&self) -> Vec<PathBuf> {
+        vec![self.path.clone()]
+    }
+
+    fn changed_on_disk(&self) -> bool {
+        self.seen.lock().unwrap_or_else(PoisonError::into_inner).is_some_and(|seen| seen != stamp(&self.path))
+    }
+}
+
+// Deserializes a SavedState document into a manager, field by field
+struct StateSeed<'a> {
+    manager: &'a mut TaskManager,
+    progress: &'a mut dyn FnMut(usize),
+}
+
+impl<'de> DeserializeSeed<'de> for StateSeed<'_> {
+    type Value = ();
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        deserializer.deserialize_map(self)
+    }
+}
+
+impl<'de> Visitor<'de> for StateSeed<'_> {
+    type Value = ();
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a saved task list")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<(), A::Error> {
+        let (mut tasks, mut next_id, mut audit, mut sync, mut views, mut undo, mut tags, mut goal, mut trash) =
+            (false, None, None, None, None, None, None, None, None);
+        let (mut archive, mut retention_run) = (None, None);
+        while let Some(key) = map.next_key::<String>()? {
+            match key.as_str() {
+                "tasks" => {
+                    map.next_value_seed(TasksSeed { manager: self.manager, progress: self.progress })?;
+                    tasks = true;
+                }
+                "next_id" => next_id = Some(map.next_value()?),
+                "audit" => audit = Some(map.next_value()?),
+                "sync" => sync = Some(map.next_value()?),
+                "views" => views = Some(map.next_value()?),
+                "undo" => undo = Some(map.next_value()?),
+                "tags" => tags = Some(map.next_value()?),
+                "goal" => goal = map.next_value()?,
+                "trash" => trash = Some(map.next_value()?),
+                "archive" => archive = Some(map.next_value()?),
+                "retention_run" => retention_run = map.next_value()?,
+                _ => {
+                    map.next_value::<IgnoredAny>()?;
+                }
+            }
+        }
+        if !tasks {
+            return Err(de::Error::missing_field("tasks"));
+        }
+        let next_id = next_id.ok_or_else(|| de::Error::missing_field("next_id"))?;
+        self.manager.finish_load(SavedState {
+            tasks: Vec::new(),
+            next_id,
+            audit: audit.unwrap_or_default(),
+            sync: sync.unwrap_or_default(),
+            views: views.unwrap_or_default(),
+            undo: undo.unwrap_or_default(),
+            tags: tags.unwrap_or_default(),
+            goal,
+            trash: trash.unwrap_or_default(),
+            archive: archive.unwrap_or_default(),
+            retention_run,
+        });
+        Ok(())
+    }
+}
+
+struct TasksSeed<'a> {
+    manager: &'a mut TaskManager,
+    progress: &'a mut dyn FnMut(usize),
+}
+
+impl<'de> DeserializeSeed<'de> for TasksSeed<'_> {
+    type Value = ();
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        deserializer.deserialize_seq(self)
+    }
+}
+
+impl<'de> Visitor<'de> for TasksSeed<'_> {
+    type Value = ();
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a list of tasks")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<(), A::Error> {
+        let mut count = 0;
+        while let Some(task) = seq.next_element::<Task>()? {
+            self.manager.insert_loaded(task);
+            count += 1;
+            if count % LOAD_PROGRESS_STEP == 0 {
+                (self.progress)(count);
+            }
+        }
+        Ok(())
+    }
+}
+
+// Keeps state in memory only; used by tests and the --memory flag
+pub struct InMemoryStorage {
+    state: Mutex<SavedState>,
+}
+
+impl InMemoryStorage {
+    pub fn new() -> Self {
+        InMemoryStorage {
+            state: Mutex::new(SavedState::default()),
+        }
+    }
+}
+
+impl Default for InMemoryStorage {
+    fn default() -> Self {
+        InMemoryStorage::new()
+    }
+}
+
+impl Storage for InMemoryStorage {
+    fn load(&self) -> Result<SavedState, TaskError> {
+        Ok(self.state.lock().unwrap().clone())
+    }
+
+    fn save(&self, state: &SavedState) -> Result<(), TaskError> {
+        *self.state.lock().unwrap() = state.clone();
+        info!("saved {} tasks to memory", state.tasks.len());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Priority, TaskManager, TaskStatus};
+
+    // Shared scenario every backend must pass
+    fn exercise_backend(storage: &dyn Storage) {
+        assert_eq!(storage.load().unwrap(), SavedState::default());
+
+        let mut manager = TaskManager::new();
+        let a = manager.add_task("First".to_string(), "one".to_string(), Priority::High).unwrap();
+        let b = manager.add_task("Second".to_string(), "two".to_string(), Priority::Low).unwrap();
+        manager.add_tag_to_task(a, "work".to_string()).unwrap();
+        manager.update_task_status(b, TaskStatus::Completed).unwrap();
+        storage.save(&manager.to_state()).unwrap();
+
+        let mut reloaded = TaskManager::from_state(storage.load().unwrap());
+        assert_eq!(reloaded.to_state(), manager.to_state());
+        let streamed = storage.load_manager(&mut |_| panic!("no progress for two tasks")).unwrap();
+        assert_eq!(streamed.to_state(), manager.to_state());
+        assert_eq!(streamed.verify_title_index(), Ok(2));
+
+        reloaded.delete_task(a).unwrap();
+        let c = reloaded.add_task("Third".to_string(), String::new(), Priority::Medium).unwrap();
+        assert_eq!(c, 3);
+        storage.save(&reloaded.to_state()).unwrap();
+
+        let state = storage.load().unwrap();
+        assert_eq!(state.next_id, 4);
+        let ids: Vec<u32> = state.tasks.iter().map(|t| t.id).collect();
+        assert_eq!(ids, vec![b, c]);
+    }
+
+    #[test]
+    fn test_in_memory_backend() {
+        exercise_backend(&InMemoryStorage::new());
+    }
+
+    #[test]
+    fn test_json_file_backend() {
+        let path = std::env::temp_dir().join(format!("task-manager-test-{}.json", std::process::id()));
+        let _ = fs::remove_file(&path);
+        exercise_backend(&FileStorage::new(&path));
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_file_backend_notices_saves_by_another_process() {
+        let path = std::env::temp_dir().join(format!("task-manager-stamp-{}.json", std::process::id()));
+        let (ours, theirs) = (FileStorage::new(&path), FileStorage::new(&path));
+        assert!(!ours.changed_on_disk());
+        ours.load().unwrap();
+        theirs.load().unwrap();
+        assert!(!ours.changed_on_disk());
+
+        let mut manager = TaskManager::new();
+        manager.add_task("Theirs".to_string(), String::new(), Priority::Low).unwrap();
+        theirs.save(&manager.to_state()).unwrap();
+        assert!(ours.changed_on_disk() && !theirs.changed_on_disk());
+        ours.load().unwrap();
+        assert!(!ours.changed_on_disk());
+        manager.add_task("Ours".to_string(), String::new(), Priority::Low).unwrap();
+        ours.save(&manager.to_state()).unwrap();
+        assert!(theirs.changed_on_disk());
+        let _ = fs::remove_file(&path);
+    }
+
+    #[cfg(feature = "msgpack")]
+    #[test]
+    fn test_messagepack_backend() {
+        let path = std::env::temp_dir().join(format!("task-manager-test-{}.msgpack", std::process::id()));
+        let _ = fs::remove_file(&path);
+        exercise_backend(&FileStorage::new(&path).with_format(StoreFormat::MessagePack));
+        assert!(fs::read(&path).unwrap().starts_with(MESSAGEPACK_MAGIC));
+
+        // A JSON-configured session still reads it, and can convert it back
+        let storage = FileStorage::new(&path);
+        let state = storage.load().unwrap();
+        assert!(storage.convert(&state, StoreFormat::Json).unwrap());
+        assert_eq!(fs::read(&path).unwrap()[0], b'{');
+        assert_eq!(storage.load_manager(&mut |_| {}).unwrap().to_state(), state);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[cfg(not(feature = "msgpack"))]
+    #[test]
+    fn test_messagepack_file_needs_the_feature() {
+        let path = std::env::temp_dir().join(format!("task-manager-test-{}.msgpack", std::process::id()));
+        fs::write(&path, [MESSAGEPACK_MAGIC, &[0x80]].concat()).unwrap();
+        let storage = FileStorage::new(&path);
+        for result in [storage.load().map(|_| ()), storage.load_manager(&mut |_| {}).map(|_| ())] {
+            assert!(matches!(result, Err(TaskError::Storage(e)) if e.contains("'msgpack' feature")));
+        }
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_legacy_file_loads_leniently() {
+        let path = std::env::temp_dir().join(format!("task-manager-legacy-{}.json", std::process::id()));
+        fs::write(&path, include_str!("../tests/fixtures/legacy_statuses.json")).unwrap();
+        let storage = FileStorage::new(&path);
+        assert!(storage.load_manager(&mut |_| {}).is_err());
+        let migrated = storage.load_lenient().unwrap();
+        assert_eq!(migrated.state.tasks[0].status, TaskStatus::InProgress);
+        assert_eq!(migrated.coercions.len(), 5);
+
+        // Saved again, it is in the current format and loads strictly
+        storage.save(&migrated.state).unwrap();
+        assert_eq!(storage.load().unwrap(), migrated.state);
+        assert!(storage.load_lenient().unwrap().coercions.is_empty());
+        fs::remove_file(&path).unwrap();
+    }
+
+    // cargo test --release -- --ignored --nocapture load_large
+    #[test]
+    #[ignore]
+    fn test_load_large_file() {
+        let tasks: Vec<Task> = (1..=100_000)
+            .map(|id| {
+                let mut task = Task::new(id, format!("Task {}", id), "generated".to_string(), Priority::Medium);
+                task.tags.push(format!("tag{}", id % 50).into());
+                task
+            })
+            .collect();
+        let state = SavedState { tasks, next_id: 100_001, ..SavedState::default() };
+        let path = std::env::temp_dir().join(format!("task-manager-large-{}.json", std::process::id()));
+        let storage = FileStorage::new(&path);
+        storage.save(&state).unwrap();
+
+        let started = std::time::Instant::now();
+        let whole = TaskManager::from_state(storage.load().unwrap());
+        let whole_time = started.elapsed();
+        let started = std::time::Instant::now();
+        let mut reports = Vec::new();
+        let streamed = storage.load_manager(&mut |count| reports.push(count)).unwrap();
+        let streamed_time = started.elapsed();
+        fs::remove_file(&path).unwrap();
+
+        println!("read then index: {:?}, streamed: {:?}", whole_time, streamed_time);
+        assert_eq!(streamed.to_state(), whole.to_state());
+        assert_eq!(streamed.to_state(), state);
+        assert_eq!(streamed.verify_title_index(), Ok(100_000));
+        assert_eq!(reports, (1..=10).map(|n| n * LOAD_PROGRESS_STEP).collect::<Vec<_>>());
+    }
+}
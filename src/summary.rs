@@ -0,0 +1,197 @@
+use std::cmp::Reverse;
+
+use chrono::NaiveDate;
+use serde::Serialize;
+
+use crate::goal::GoalProgress;
+use crate::matrix::Quadrant;
+use crate::msg;
+use crate::render::{Tone, paint};
+use crate::{Energy, Task, TaskManager, TaskStatus};
+
+// What needs attention, shown when the prompt starts and by `summary`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Summary {
+    pub open: usize,
+    pub overdue: usize,
+    pub due_today: usize,
+    pub in_progress: usize,
+    pub inbox: usize,
+    // None when nothing open can be worked on
+    pub next: Option<Next>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub goal: Option<GoalProgress>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Next {
+    pub id: u32,
+    pub title: String,
+}
+
+// Counts come from the manager's counters; due dates and the inbox take one pass
+// over the tasks, and the next task another
+pub fn build(manager: &TaskManager, today: NaiveDate) -> Summary {
+    let (total, completed, in_progress, _) = manager.get_statistics();
+    let mut summary = Summary {
+        open: total - completed,
+        overdue: 0,
+        due_today: 0,
+        in_progress,
+        inbox: manager.inbox().len(),
+        next: next(manager, today, None),
+        goal: manager.goal_progress(today),
+    };
+    for task in manager.list_tasks().into_iter().filter(|task| task.status != TaskStatus::Completed) {
+        match task.due {
+            Some(due) if due < today => summary.overdue += 1,
+            Some(due) if due == today => summary.due_today += 1,
+            _ => {}
+        }
+    }
+    summary
+}
+
+// The open task to work on now, not waiting on anyone; with `energy`, only one
+// needing no more than that, as `can-do` has it
+pub fn next(manager: &TaskManager, today: NaiveDate, energy: Option<Energy>) -> Option<Next> {
+    let mut next: Option<&Task> = None;
+    for task in manager.list_tasks() {
+        if task.status == TaskStatus::Completed || task.waiting_on.is_some() {
+            continue;
+        }
+        if energy.is_some_and(|energy| task.energy.unwrap_or(Energy::High) > energy) {
+            continue;
+        }
+        if next.is_none_or(|best| pick(manager, task, today) < pick(manager, best, today)) {
+            next = Some(task);
+        }
+    }
+    next.map(|task| Next { id: task.id, title: task.title.clone() })
+}
+
+// Lower comes first: pinned tasks, then by quadrant, work already started, the
+// soonest due date, the highest priority and the oldest id
+fn pick(manager: &TaskManager, task: &Task, today: NaiveDate) -> impl Ord {
+    let quadrant = Quadrant::ALL.iter().position(|&q| q == manager.quadrant(task, today));
+    (
+        !task.pinned,
+        quadrant,
+        task.status != TaskStatus::InProgress,
+        (task.due.is_none(), task.due),
+        Reverse(task.priority.clone()),
+        task.id,
+    )
+}
+
+// Three lines, each number coloured by how much it asks for attention
+pub fn layout(summary: &Summary) -> String {
+    let count = |n: usize, tone: Tone| paint(&n.to_string(), if n > 0 { tone } else { Tone::Plain });
+    let next = match &summary.next {
+        Some(next) => msg!("summary_next", id = next.id, title = next.title),
+        None => msg!("summary_nothing_next"),
+    };
+    let mut lines = vec![
+        msg!(
+            "summary_open",
+            open = count(summary.open, Tone::Plain),
+            overdue = count(summary.overdue, Tone::Bad),
+            today = count(summary.due_today, Tone::Warn)
+        ),
+        msg!(
+            "summary_active",
+            in_progress = count(summary.in_progress, Tone::Good),
+            inbox = count(summary.inbox, Tone::Warn)
+        ),
+        next,
+    ];
+    if let Some(goal) = &summary.goal {
+        lines.push(goal.line());
+    }
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Priority, TaskEdit};
+
+    fn date(day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2024, 7, day).unwrap()
+    }
+
+    fn sample() -> TaskManager {
+        let mut manager = TaskManager::new();
+        let tasks = [
+            ("Pay rent", Priority::High, Some(date(1))),
+            ("Renew passport", Priority::Medium, Some(date(3))),
+            ("Call plumber", Priority::Low, Some(date(3))),
+            ("Plan trip", Priority::High, None),
+            ("Water plants", Priority::Low, Some(date(20))),
+            ("File taxes", Priority::Critical, Some(date(2))),
+        ];
+        for (title, priority, due) in tasks {
+            let id = manager.add_task(title.to_string(), String::new(), priority).unwrap();
+            manager.apply_edit(id, TaskEdit { due, ..TaskEdit::default() }).unwrap();
+        }
+        manager.update_task_status(4, TaskStatus::InProgress).unwrap();
+        manager.update_task_status(6, TaskStatus::Completed).unwrap();
+        // Captured with `quick`, so in the inbox
+        let mut idea = Task::new(0, "Someday idea".to_string(), String::new(), Priority::Low);
+        idea.triaged = false;
+        manager.create_task(idea).unwrap();
+        manager
+    }
+
+    #[test]
+    fn test_summary_of_a_fixed_list() {
+        let summary = build(&sample(), date(3));
+        assert_eq!(summary, Summary {
+            open: 6,
+            overdue: 1,
+            due_today: 2,
+            in_progress: 1,
+            inbox: 1,
+            next: Some(Next { id: 1, title: "Pay rent".to_string() }),
+            goal: None,
+        });
+        assert_eq!(
+            layout(&summary),
+            "Open: 6 · Overdue: 1 · Due today: 2\n\
+             In progress: 1 · Inbox: 1\n\
+             Next: #1 Pay rent"
+        );
+    }
+
+    #[test]
+    fn test_pinned_and_started_work_comes_next() {
+        let mut manager = sample();
+        manager.update_task_status(1, TaskStatus::Completed).unwrap();
+        // Important but not urgent beats urgent but not important
+        assert_eq!(build(&manager, date(3)).next.map(|next| next.id), Some(4));
+        manager.update_task_status(4, TaskStatus::Completed).unwrap();
+        // Both due today: the higher priority
+        assert_eq!(build(&manager, date(3)).next.map(|next| next.id), Some(2));
+        manager.update_task_status(3, TaskStatus::InProgress).unwrap();
+        assert_eq!(build(&manager, date(3)).next.map(|next| next.id), Some(3));
+        manager.set_pinned(5, true).unwrap();
+        assert_eq!(build(&manager, date(3)).next.map(|next| next.id), Some(5));
+
+        let empty = build(&TaskManager::new(), date(3));
+        assert_eq!(empty.next, None);
+        assert_eq!(layout(&empty).lines().last(), Some("Next: nothing open"));
+    }
+
+    #[test]
+    fn test_next_within_an_energy_level() {
+        let mut manager = sample();
+        manager.set_energy(1, Some(Energy::High)).unwrap();
+        manager.set_energy(3, Some(Energy::Low)).unwrap();
+        assert_eq!(next(&manager, date(3), None).map(|next| next.id), Some(1));
+        // Unrated tasks could need anything
+        assert_eq!(next(&manager, date(3), Some(Energy::Low)).map(|next| next.id), Some(3));
+        assert_eq!(next(&manager, date(3), Some(Energy::High)).map(|next| next.id), Some(1));
+        manager.update_task_status(3, TaskStatus::Completed).unwrap();
+        assert_eq!(next(&manager, date(3), Some(Energy::Medium)), None);
+    }
+}
@@ -0,0 +1,574 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::str::FromStr;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{Task, TaskError, TaskEvent, TaskManager};
+
+// Sync bookkeeping saved with the tasks: when this list last exchanged changes
+// with the server, and the local changes still waiting to be pushed
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SyncState {
+    pub last_pull: Option<DateTime<Utc>>,
+    pub last_push: Option<DateTime<Utc>>,
+    // Oldest first, at most one entry per task
+    #[serde(default)]
+    pub pending: Vec<PendingChange>,
+}
+
+// A local change not yet pushed
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PendingChange {
+    pub uid: String,
+    pub event: TaskEvent,
+    pub recorded_at: DateTime<Utc>,
+}
+
+impl SyncState {
+    // A push sends the whole current task, so only the latest change to each task matters
+    pub fn record(&mut self, change: PendingChange) {
+        self.discard(&change.uid);
+        self.pending.push(change);
+    }
+
+    pub fn discard(&mut self, uid: &str) {
+        self.pending.retain(|change| change.uid != uid);
+    }
+
+    fn is_pending(&self, uid: &str) -> bool {
+        self.pending.iter().any(|change| change.uid == uid)
+    }
+}
+
+// Turns manager events into pending changes. Events name tasks by id, so the
+// recorder remembers each id's uid to still know it once the task is deleted.
+pub(crate) struct ChangeRecorder {
+    uids: HashMap<u32, String>,
+}
+
+impl ChangeRecorder {
+    pub(crate) fn new(manager: &TaskManager) -> Self {
+        ChangeRecorder { uids: uids_by_id(manager) }
+    }
+
+    pub(crate) fn record(&mut self, event: &TaskEvent, manager: &TaskManager, state: &mut SyncState) {
+        let now = Utc::now();
+        let mut queue = |uid: String, event: TaskEvent| state.record(PendingChange { uid, event, recorded_at: now });
+        match event {
+            TaskEvent::Added(id)
+            | TaskEvent::StatusChanged { id, .. }
+            | TaskEvent::PriorityChanged { id, .. }
+            | TaskEvent::Tagged { id, .. }
+            | TaskEvent::Edited(id)
+            | TaskEvent::Notified(id) => {
+                if let Ok(task) = manager.get_task(*id) {
+                    self.uids.insert(*id, task.uid.clone());
+                    queue(task.uid.clone(), event.clone());
+                }
+            }
+            TaskEvent::Deleted(id) => {
+                if let Some(uid) = self.uids.remove(id) {
+                    queue(uid, event.clone());
+                }
+            }
+            // A rollback may have changed, revived or dropped any task
+            TaskEvent::Restored => {
+                let current = uids_by_id(manager);
+                for (id, uid) in &self.uids {
+                    if current.get(id) != Some(uid) {
+                        queue(uid.clone(), TaskEvent::Deleted(*id));
+                    }
+                }
+                for (id, uid) in &current {
+                    queue(uid.clone(), TaskEvent::Edited(*id));
+                }
+                self.uids = current;
+            }
+            // Archived tasks leave the list as deleted ones do; purged ones already had
+            TaskEvent::RetentionApplied { archived, .. } => {
+                for id in archived {
+                    if let Some(uid) = self.uids.remove(id) {
+                        queue(uid, TaskEvent::Deleted(*id));
+                    }
+                }
+            }
+            // Views, the tag registry and the goal stay with the data file they were saved in
+            TaskEvent::ViewChanged { .. } | TaskEvent::TagDescribed { .. } | TaskEvent::GoalChanged => {}
+            // Not a change to the task; the one that follows is queued
+            TaskEvent::Overridden { .. } => {}
+        }
+    }
+}
+
+fn uids_by_id(manager: &TaskManager) -> HashMap<u32, String> {
+    manager.list_tasks().into_iter().map(|task| (task.id, task.uid.clone())).collect()
+}
+
+// What to do with a task that changed on both sides since the last sync
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ConflictPolicy {
+    PreferLocal,
+    PreferRemote,
+    // Prompt for each conflict
+    Ask,
+}
+
+impl FromStr for ConflictPolicy {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<ConflictPolicy, ()> {
+        match s {
+            "prefer-local" => Ok(ConflictPolicy::PreferLocal),
+            "prefer-remote" => Ok(ConflictPolicy::PreferRemote),
+            "ask" => Ok(ConflictPolicy::Ask),
+            _ => Err(()),
+        }
+    }
+}
+
+impl fmt::Display for ConflictPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConflictPolicy::PreferLocal => write!(f, "prefer-local"),
+            ConflictPolicy::PreferRemote => write!(f, "prefer-remote"),
+            ConflictPolicy::Ask => write!(f, "ask"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Side {
+    Local,
+    Remote,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Direction {
+    Pull,
+    Push,
+    Both,
+}
+
+impl Direction {
+    fn pulls(self) -> bool {
+        self != Direction::Push
+    }
+
+    fn pushes(self) -> bool {
+        self != Direction::Pull
+    }
+}
+
+// How a task changed on both sides was settled
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Conflict {
+    pub uid: String,
+    pub title: String,
+    pub kept: Side,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct SyncReport {
+    // Titles of the tasks copied in each direction
+    pub pulled: Vec<String>,
+    pub pushed: Vec<String>,
+    // Uids of the tasks deleted on the server
+    pub deleted: Vec<String>,
+    pub conflicts: Vec<Conflict>,
+    // (title, reason) for tasks one side refused
+    pub skipped: Vec<(String, String)>,
+}
+
+// The server end of a sync, kept behind a trait so tests can fake it.
+// TaskError::Remote means the server could not be reached and stops the sync;
+// any other error rejects just the one task.
+pub trait SyncTransport {
+    fn fetch(&self) -> Result<Vec<Task>, TaskError>;
+    // Create or replace the server's copy of the task with the same uid
+    fn upload(&self, task: &Task) -> Result<(), TaskError>;
+    // Deleting a uid the server does not have succeeds
+    fn delete(&self, uid: &str) -> Result<(), TaskError>;
+}
+
+// Which copy of each task should travel which way
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Plan {
+    pub pull: Vec<Task>,
+    // Uids of the local tasks to upload
+    pub push: Vec<String>,
+    // Uids deleted here that the server still has
+    pub delete: Vec<String>,
+    pub conflicts: Vec<Conflict>,
+}
+
+// Match tasks by uid. A local task counts as changed when it has a queued change or was
+// edited after the last push; a remote one when it was edited after the last pull.
+// A task only one side has travels to the other if it changed. When both sides hold
+// different copies the newer updated_at wins, unless both changed, in which case
+// `resolve` picks the side to keep.
+pub fn plan(local: &[&Task], remote: &[Task], state: &SyncState, resolve: &mut dyn FnMut(&Task, &Task) -> Side) -> Plan {
+    let local_changed =
+        |task: &Task| state.is_pending(&task.uid) || state.last_push.is_none_or(|mark| task.updated_at > mark);
+    let remote_changed = |task: &Task| state.last_pull.is_none_or(|mark| task.updated_at > mark);
+    let remote_by_uid: BTreeMap<&str, &Task> = remote.iter().map(|task| (task.uid.as_str(), task)).collect();
+    let mut plan = Plan::default();
+
+    for &mine in local {
+        match remote_by_uid.get(mine.uid.as_str()) {
+            None => {
+                if local_changed(mine) {
+                    plan.push.push(mine.uid.clone());
+                }
+            }
+            Some(&theirs) if same_content(mine, theirs) => {}
+            Some(&theirs) => {
+                let side = if local_changed(mine) && remote_changed(theirs) {
+                    let side = resolve(mine, theirs);
+                    plan.conflicts.push(Conflict { uid: mine.uid.clone(), title: mine.title.clone(), kept: side });
+                    side
+                } else if local_changed(mine) || mine.updated_at > theirs.updated_at {
+                    Side::Local
+                } else {
+                    Side::Remote
+                };
+                match side {
+                    Side::Local => plan.push.push(mine.uid.clone()),
+                    Side::Remote => plan.pull.push(theirs.clone()),
+                }
+            }
+        }
+    }
+
+    for theirs in remote {
+        if local.iter().any(|mine| mine.uid == theirs.uid) {
+            continue;
+        }
+        // A local deletion wins over remote edits; tasks are cheap to re-create
+        let deleted_here =
+            state.pending.iter().any(|change| change.uid == theirs.uid && matches!(change.event, TaskEvent::Deleted(_)));
+        if deleted_here {
+            plan.delete.push(theirs.uid.clone());
+        } else if remote_changed(theirs) {
+            plan.pull.push(theirs.clone());
+        }
+    }
+    plan
+}
+
+// Ids and manual-order ranks are per-list, so they are not compared
+fn same_content(a: &Task, b: &Task) -> bool {
+    Task { id: b.id, rank: b.rank, ..a.clone() } == *b
+}
+
+// Fetch, plan and apply one sync. The push replays the change queue in order,
+// dropping each entry once the server has it; if the server goes away part-way
+// the rest stay queued for next time.
+pub fn run(
+    manager: &mut TaskManager,
+    transport: &dyn SyncTransport,
+    direction: Direction,
+    resolve: &mut dyn FnMut(&Task, &Task) -> Side,
+    now: DateTime<Utc>,
+) -> Result<SyncReport, TaskError> {
+    let remote = transport.fetch()?;
+    let state = manager.sync_state();
+    let plan = plan(&manager.list_tasks(), &remote, &state, resolve);
+    let mut report = SyncReport { conflicts: plan.conflicts, ..SyncReport::default() };
+
+    if direction.pushes() {
+        // Queued tasks first, in the order they changed, then anything changed before queueing began
+        let mut order: Vec<String> = state.pending.iter().map(|change| change.uid.clone()).collect();
+        order.extend(plan.push.iter().filter(|uid| !state.is_pending(uid)).cloned());
+        for uid in order {
+            let (label, outcome) = if plan.delete.contains(&uid) {
+                (uid.clone(), transport.delete(&uid).map(|()| report.deleted.push(uid.clone())))
+            } else if plan.push.contains(&uid) {
+                let task = manager.find_by_uid(&uid).expect("planned from the local list").clone();
+                (task.title.clone(), transport.upload(&task).map(|()| report.pushed.push(task.title)))
+            } else {
+                // Already on the server, or the server's copy won a conflict
+                (uid.clone(), Ok(()))
+            };
+            match outcome {
+                Ok(()) => {}
+                Err(e @ TaskError::Remote(_)) => return Err(e),
+                Err(e) => report.skipped.push((label, e.to_string())),
+            }
+            manager.update_sync_state(|state| state.discard(&uid));
+        }
+        manager.update_sync_state(|state| state.last_push = Some(now));
+    }
+    if direction.pulls() {
+        for task in plan.pull {
+            let (uid, title) = (task.uid.clone(), task.title.clone());
+            match manager.upsert_task(task) {
+                Ok(_) => report.pulled.push(title),
+                Err(e) => report.skipped.push((title, e.to_string())),
+            }
+            // Taking the server's copy is not a local change to push back
+            manager.update_sync_state(|state| state.discard(&uid));
+        }
+        manager.update_sync_state(|state| state.last_pull = Some(now));
+    }
+    Ok(report)
+}
+
+// Talks to another instance's `serve` API
+#[cfg(feature = "http-client")]
+pub struct HttpTransport {
+    agent: ureq::Agent,
+    base_url: String,
+    token: Option<String>,
+}
+
+#[cfg(feature = "http-client")]
+impl HttpTransport {
+    pub fn new(base_url: &str, token: Option<String>) -> Self {
+        HttpTransport {
+            agent: ureq::AgentBuilder::new().timeout(std::time::Duration::from_secs(10)).build(),
+            base_url: base_url.trim_end_matches('/').to_string(),
+            token,
+        }
+    }
+
+    fn request(&self, method: &str, path: &str) -> ureq::Request {
+        let request = self.agent.request(method, &format!("{}{}", self.base_url, path));
+        match &self.token {
+            Some(token) => request.set("Authorization", &format!("Bearer {}", token)),
+            None => request,
+        }
+    }
+}
+
+// The `data` of an {"ok": true, "data": ...} envelope, or the server's error message
+#[cfg(feature = "http-client")]
+fn read_envelope(result: Result<ureq::Response, ureq::Error>) -> Result<serde_json::Value, TaskError> {
+    let (status, response) = match result {
+        Ok(response) => (response.status(), response),
+        Err(ureq::Error::Status(status, response)) => (status, response),
+        Err(e) => return Err(TaskError::Remote(e.to_string())),
+    };
+    let text = response.into_string().map_err(|e| TaskError::Remote(e.to_string()))?;
+    let body: serde_json::Value = serde_json::from_str(&text).map_err(|e| TaskError::Remote(e.to_string()))?;
+    if (200..300).contains(&status) {
+        return Ok(body["data"].clone());
+    }
+    let message = body["error"]["message"].as_str().unwrap_or("request failed").to_string();
+    match status {
+        400 | 409 => Err(TaskError::ValidationFailed(vec![message])),
+        _ => Err(TaskError::Remote(format!("{} ({})", message, status))),
+    }
+}
+
+#[cfg(feature = "http-client")]
+impl SyncTransport for HttpTransport {
+    fn fetch(&self) -> Result<Vec<Task>, TaskError> {
+        let data = read_envelope(self.request("GET", "/tasks").call())?;
+        serde_json::from_value(data).map_err(|e| TaskError::Remote(e.to_string()))
+    }
+
+    fn upload(&self, task: &Task) -> Result<(), TaskError> {
+        let body = serde_json::to_string(task).map_err(|e| TaskError::Storage(e.to_string()))?;
+        let request = self.request("PUT", &format!("/sync/tasks/{}", task.uid)).set("Content-Type", "application/json");
+        read_envelope(request.send_string(&body)).map(|_| ())
+    }
+
+    fn delete(&self, uid: &str) -> Result<(), TaskError> {
+        read_envelope(self.request("DELETE", &format!("/sync/tasks/{}", uid)).call()).map(|_| ())
+    }
+}
+
+// The transport used by the CLI, if this build can make HTTP requests
+#[cfg(feature = "http-client")]
+pub fn default_transport(base_url: &str, token: Option<String>) -> Option<Box<dyn SyncTransport>> {
+    Some(Box::new(HttpTransport::new(base_url, token)))
+}
+
+#[cfg(not(feature = "http-client"))]
+pub fn default_transport(_base_url: &str, _token: Option<String>) -> Option<Box<dyn SyncTransport>> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::SavedState;
+    use crate::{Priority, TaskEdit, TaskStatus};
+    use std::cell::RefCell;
+
+    // A server holding canned state, updated by uploads like the real one
+    struct FakeServer {
+        tasks: RefCell<Vec<Task>>,
+        // Requests answered before the server "goes away"; None never fails
+        budget: RefCell<Option<usize>>,
+    }
+
+    impl FakeServer {
+        fn new(tasks: Vec<Task>) -> Self {
+            FakeServer { tasks: RefCell::new(tasks), budget: RefCell::new(None) }
+        }
+
+        fn answer(&self) -> Result<(), TaskError> {
+            match &mut *self.budget.borrow_mut() {
+                Some(0) => Err(TaskError::Remote("connection refused".to_string())),
+                Some(left) => {
+                    *left -= 1;
+                    Ok(())
+                }
+                None => Ok(()),
+            }
+        }
+    }
+
+    impl SyncTransport for FakeServer {
+        fn fetch(&self) -> Result<Vec<Task>, TaskError> {
+            self.answer()?;
+            Ok(self.tasks.borrow().clone())
+        }
+
+        fn upload(&self, task: &Task) -> Result<(), TaskError> {
+            self.answer()?;
+            let mut tasks = self.tasks.borrow_mut();
+            tasks.retain(|t| t.uid != task.uid);
+            tasks.push(task.clone());
+            Ok(())
+        }
+
+        fn delete(&self, uid: &str) -> Result<(), TaskError> {
+            self.answer()?;
+            self.tasks.borrow_mut().retain(|t| t.uid != uid);
+            Ok(())
+        }
+    }
+
+    fn keep(side: Side) -> impl FnMut(&Task, &Task) -> Side {
+        move |_, _| side
+    }
+
+    fn edited(task: &Task, title: &str) -> Task {
+        let mut task = task.clone();
+        task.title = title.to_string();
+        task.touch();
+        task
+    }
+
+    fn queued() -> TaskManager {
+        let mut manager = TaskManager::new();
+        manager.enable_change_queue();
+        manager
+    }
+
+    // A local list and a server that both hold task "Shared", already in sync
+    fn synced() -> (TaskManager, FakeServer, DateTime<Utc>) {
+        let mut manager = queued();
+        manager.add_task("Shared".to_string(), String::new(), Priority::Medium).unwrap();
+        let server = FakeServer::new(Vec::new());
+        run(&mut manager, &server, Direction::Both, &mut keep(Side::Local), Utc::now()).unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(5));
+        (manager, server, Utc::now())
+    }
+
+    #[test]
+    fn test_first_sync_copies_both_ways() {
+        let mut manager = TaskManager::new();
+        manager.add_task("Laptop task".to_string(), String::new(), Priority::Low).unwrap();
+        let server = FakeServer::new(vec![Task::new(7, "Server task".to_string(), String::new(), Priority::High)]);
+
+        let report = run(&mut manager, &server, Direction::Both, &mut keep(Side::Local), Utc::now()).unwrap();
+        assert_eq!(report.pushed, vec!["Laptop task"]);
+        assert_eq!(report.pulled, vec!["Server task"]);
+        assert!(report.conflicts.is_empty());
+        assert_eq!(manager.list_tasks().len(), 2);
+        assert_eq!(server.tasks.borrow().len(), 2);
+        assert!(manager.sync_state().last_pull.is_some() && manager.sync_state().last_push.is_some());
+
+        // Nothing changed, so nothing moves
+        let report = run(&mut manager, &server, Direction::Both, &mut keep(Side::Local), Utc::now()).unwrap();
+        assert!(report.pushed.is_empty() && report.pulled.is_empty());
+    }
+
+    #[test]
+    fn test_one_sided_changes_take_the_newer_copy() {
+        let (mut manager, server, now) = synced();
+        let remote = edited(&server.tasks.borrow()[0], "Shared, renamed on server");
+        server.tasks.borrow_mut()[0] = remote;
+
+        let report = run(&mut manager, &server, Direction::Pull, &mut keep(Side::Local), now).unwrap();
+        assert_eq!(report.pulled, vec!["Shared, renamed on server"]);
+        assert!(report.conflicts.is_empty());
+        assert_eq!(manager.get_task(1).unwrap().title, "Shared, renamed on server");
+        // The pulled copy is not queued to be pushed back
+        assert!(manager.sync_state().pending.is_empty());
+    }
+
+    #[test]
+    fn test_conflicts_follow_the_policy() {
+        for (side, expected_local, expected_server) in [
+            (Side::Local, "Local edit", "Local edit"),
+            (Side::Remote, "Remote edit", "Remote edit"),
+        ] {
+            let (mut manager, server, now) = synced();
+            manager.apply_edit(1, TaskEdit { title: Some("Local edit".to_string()), ..TaskEdit::default() }).unwrap();
+            let remote = edited(&server.tasks.borrow()[0], "Remote edit");
+            server.tasks.borrow_mut()[0] = remote;
+
+            let report = run(&mut manager, &server, Direction::Both, &mut keep(side), now).unwrap();
+            assert_eq!(report.conflicts.len(), 1);
+            assert_eq!(report.conflicts[0].kept, side);
+            assert_eq!(manager.get_task(1).unwrap().title, expected_local);
+            assert_eq!(server.tasks.borrow()[0].title, expected_server);
+            assert!(manager.sync_state().pending.is_empty());
+        }
+    }
+
+    #[test]
+    fn test_queue_collapses_changes_per_task() {
+        let mut manager = queued();
+        let id = manager.add_task("Busy".to_string(), String::new(), Priority::Medium).unwrap();
+        let other = manager.add_task("Quiet".to_string(), String::new(), Priority::Low).unwrap();
+        for status in [TaskStatus::InProgress, TaskStatus::Pending, TaskStatus::InProgress, TaskStatus::Completed] {
+            manager.update_task_status(id, status).unwrap();
+        }
+        let pending = manager.sync_state().pending;
+        assert_eq!(pending.len(), 2);
+        assert_eq!(pending[0].event, TaskEvent::Added(other));
+        let last = TaskEvent::StatusChanged { id, from: TaskStatus::InProgress, to: TaskStatus::Completed };
+        assert_eq!(pending[1].event, last);
+
+        // The queue is saved with the tasks and picked up again after a restart
+        let json = serde_json::to_string(&manager.to_state()).unwrap();
+        let reloaded = TaskManager::from_state(serde_json::from_str::<SavedState>(&json).unwrap());
+        assert_eq!(reloaded.sync_state().pending, pending);
+    }
+
+    #[test]
+    fn test_failed_push_keeps_the_rest_queued() {
+        let (mut manager, server, now) = synced();
+        manager.add_task("First".to_string(), String::new(), Priority::Low).unwrap();
+        manager.add_task("Second".to_string(), String::new(), Priority::Low).unwrap();
+        manager.delete_task(1).unwrap();
+        assert_eq!(manager.sync_state().pending.len(), 3);
+
+        // The fetch and one upload get through, then the server disappears
+        *server.budget.borrow_mut() = Some(2);
+        let before = manager.sync_state().last_push;
+        let err = run(&mut manager, &server, Direction::Push, &mut keep(Side::Local), now).unwrap_err();
+        assert!(matches!(err, TaskError::Remote(_)));
+        let pending = manager.sync_state().pending;
+        assert_eq!(pending.len(), 2);
+        assert_eq!(pending[0].event, TaskEvent::Added(3));
+        assert_eq!(manager.sync_state().last_push, before);
+
+        *server.budget.borrow_mut() = None;
+        let report = run(&mut manager, &server, Direction::Push, &mut keep(Side::Local), now).unwrap();
+        assert_eq!(report.pushed, vec!["Second"]);
+        assert_eq!(report.deleted.len(), 1);
+        assert!(manager.sync_state().pending.is_empty());
+        let titles: Vec<String> = server.tasks.borrow().iter().map(|task| task.title.clone()).collect();
+        assert_eq!(titles, vec!["First", "Second"]);
+        assert_eq!(ConflictPolicy::from_str("prefer-remote"), Ok(ConflictPolicy::PreferRemote));
+    }
+}
@@ -0,0 +1,265 @@
+use std::fmt;
+use std::str::FromStr;
+
+use chrono::NaiveDate;
+use serde::Serialize;
+use unicode_width::UnicodeWidthStr;
+
+use crate::board::{fit, pad};
+use crate::{Task, dates, msg};
+
+const GAP: &str = "  ";
+
+// The title never gets narrower than this while other columns can be dropped
+const MIN_TITLE_WIDTH: usize = 12;
+
+// A column `list --columns` can show
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Column {
+    Id,
+    Title,
+    Priority,
+    Status,
+    Due,
+    Tags,
+    Energy,
+    // Since the task was created
+    Age,
+    // Since it was last changed
+    Updated,
+}
+
+// The name of each column as `--columns` takes it, in the order of Column
+pub const COLUMN_NAMES: [&str; 9] = ["id", "title", "pri", "status", "due", "tags", "energy", "age", "updated"];
+
+impl Column {
+    pub fn header(self) -> String {
+        match self {
+            Column::Id => msg!("column_id"),
+            Column::Title => msg!("column_title"),
+            Column::Priority => msg!("column_priority"),
+            Column::Status => msg!("column_status"),
+            Column::Due => msg!("column_due"),
+            Column::Tags => msg!("column_tags"),
+            Column::Energy => msg!("column_energy"),
+            Column::Age => msg!("column_age"),
+            Column::Updated => msg!("column_updated"),
+        }
+    }
+
+    // The cell for `task`, with dates relative to `today`
+    pub fn cell(self, task: &Task, today: NaiveDate) -> String {
+        match self {
+            Column::Id => task.id.to_string(),
+            Column::Title => task.title.clone(),
+            Column::Priority => task.priority.to_string(),
+            Column::Status => task.status.to_string(),
+            Column::Due => task.due.map(|due| dates::humanize(due, today)).unwrap_or_default(),
+            Column::Tags => task.tags.join(", "),
+            Column::Energy => task.energy.map(|energy| energy.to_string()).unwrap_or_default(),
+            Column::Age => dates::humanize(task.created_at.date_naive(), today),
+            Column::Updated => dates::humanize(task.updated_at.date_naive(), today),
+        }
+    }
+
+    // Numbers and times line up on the right
+    fn right_aligned(self) -> bool {
+        matches!(self, Column::Id | Column::Age | Column::Updated)
+    }
+
+    // Columns with a lower rank are dropped first when the table is too wide;
+    // None is never dropped
+    fn keep_rank(self) -> Option<u8> {
+        match self {
+            Column::Id | Column::Title => None,
+            Column::Priority => Some(6),
+            Column::Status => Some(5),
+            Column::Due => Some(4),
+            Column::Age => Some(3),
+            Column::Updated => Some(2),
+            Column::Energy => Some(1),
+            Column::Tags => Some(0),
+        }
+    }
+}
+
+impl FromStr for Column {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Column, String> {
+        match s.trim().to_lowercase().as_str() {
+            "id" => Ok(Column::Id),
+            "title" => Ok(Column::Title),
+            "pri" | "priority" => Ok(Column::Priority),
+            "status" => Ok(Column::Status),
+            "due" => Ok(Column::Due),
+            "tags" => Ok(Column::Tags),
+            "energy" => Ok(Column::Energy),
+            "age" => Ok(Column::Age),
+            "updated" => Ok(Column::Updated),
+            _ => Err(s.trim().to_string()),
+        }
+    }
+}
+
+impl fmt::Display for Column {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", COLUMN_NAMES[*self as usize])
+    }
+}
+
+// "id,title,pri,age" in the order given; Err holds the first unknown name
+pub fn parse_columns(text: &str) -> Result<Vec<Column>, String> {
+    let mut columns = Vec::new();
+    for name in text.split(',').filter(|name| !name.trim().is_empty()) {
+        let column = name.parse()?;
+        if !columns.contains(&column) {
+            columns.push(column);
+        }
+    }
+    if columns.is_empty() {
+        return Err(text.to_string());
+    }
+    Ok(columns)
+}
+
+// The tasks as a table at most `width` columns wide: when the columns do not
+// fit, the title narrows down to MIN_TITLE_WIDTH and then the lowest-ranked
+// columns are left out. Depends on nothing but its arguments.
+pub fn layout(tasks: &[Task], columns: &[Column], width: usize, today: NaiveDate) -> String {
+    let rows: Vec<Vec<String>> =
+        tasks.iter().map(|task| columns.iter().map(|column| column.cell(task, today)).collect()).collect();
+    let headers: Vec<String> = columns.iter().map(|column| column.header()).collect();
+    let natural: Vec<usize> = (0..columns.len())
+        .map(|i| rows.iter().map(|row| row[i].width()).chain([headers[i].width()]).max().unwrap_or(0))
+        .collect();
+    let narrowest = |i: usize| match columns[i] {
+        Column::Title => natural[i].min(MIN_TITLE_WIDTH),
+        _ => natural[i],
+    };
+
+    // Positions into `columns` still shown
+    let mut shown: Vec<usize> = (0..columns.len()).collect();
+    let needed = |shown: &[usize]| {
+        shown.iter().map(|&i| narrowest(i)).sum::<usize>() + GAP.len() * shown.len().saturating_sub(1)
+    };
+    while needed(&shown) > width {
+        let droppable = shown.iter().enumerate().filter_map(|(at, &i)| columns[i].keep_rank().map(|rank| (rank, at)));
+        match droppable.min() {
+            Some((_, at)) => shown.remove(at),
+            None => break,
+        };
+    }
+
+    // The title takes whatever the other columns leave, up to what it needs
+    let rest = needed(&shown) - shown.iter().filter(|&&i| columns[i] == Column::Title).map(|&i| narrowest(i)).sum::<usize>();
+    let widths: Vec<usize> = shown
+        .iter()
+        .map(|&i| match columns[i] {
+            Column::Title => width.saturating_sub(rest).clamp(narrowest(i), natural[i]),
+            _ => natural[i],
+        })
+        .collect();
+
+    let line = |cells: &[String]| {
+        let cells: Vec<String> = shown
+            .iter()
+            .zip(&widths)
+            .map(|(&i, &width)| {
+                let text = fit(&cells[i], width);
+                if columns[i].right_aligned() {
+                    format!("{}{}", " ".repeat(width - text.width()), text)
+                } else {
+                    pad(&text, width)
+                }
+            })
+            .collect();
+        cells.join(GAP).trim_end().to_string()
+    };
+    std::iter::once(line(&headers)).chain(rows.iter().map(|row| line(row))).collect::<Vec<_>>().join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+
+    use super::*;
+    use crate::{Priority, TaskStatus};
+
+    fn today() -> NaiveDate {
+        NaiveDate::from_ymd_opt(2024, 7, 10).unwrap()
+    }
+
+    fn sample() -> Vec<Task> {
+        let rows = [
+            ("Write the quarterly report for the finance team", Priority::High, 1, 9),
+            ("Fix login bug", Priority::Critical, 8, 10),
+            ("Plan sprint", Priority::Low, 9, 9),
+        ];
+        rows.into_iter()
+            .enumerate()
+            .map(|(i, (title, priority, created, updated))| {
+                let mut task = Task::new(i as u32 + 1, title.to_string(), String::new(), priority);
+                task.created_at = Utc.with_ymd_and_hms(2024, 7, created, 9, 0, 0).unwrap();
+                task.updated_at = Utc.with_ymd_and_hms(2024, 7, updated, 15, 0, 0).unwrap();
+                task
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_parse_columns_keeps_the_order_given() {
+        let columns = parse_columns("id,title,pri,age,updated").unwrap();
+        assert_eq!(columns, [Column::Id, Column::Title, Column::Priority, Column::Age, Column::Updated]);
+        assert_eq!(parse_columns("Updated, ID,id").unwrap(), [Column::Updated, Column::Id]);
+        assert_eq!(parse_columns("id,colour,title"), Err("colour".to_string()));
+        assert_eq!(parse_columns(","), Err(",".to_string()));
+        assert_eq!(Column::Updated.to_string(), "updated");
+    }
+
+    #[test]
+    fn test_wide_table() {
+        let mut tasks = sample();
+        tasks[1].status = TaskStatus::InProgress;
+        let columns = parse_columns("id,title,pri,status,age,updated").unwrap();
+        assert_eq!(
+            layout(&tasks, &columns, 120, today()),
+            [
+                "ID  Title                                            Pri       Status              Age    Updated",
+                " 1  Write the quarterly report for the finance team  High      Pending      9 days ago  yesterday",
+                " 2  Fix login bug                                    Critical  In Progress  2 days ago      today",
+                " 3  Plan sprint                                      Low       Pending       yesterday  yesterday",
+            ]
+            .join("\n")
+        );
+    }
+
+    #[test]
+    fn test_narrow_table_drops_the_lowest_ranked_columns() {
+        let columns = parse_columns("id,title,pri,status,age,updated").unwrap();
+        // The title narrows first, then Updated goes, then Age
+        assert_eq!(
+            layout(&sample(), &columns, 55, today()),
+            [
+                "ID  Title                 Pri       Status          Age",
+                " 1  Write the quarterly…  High      Pending  9 days ago",
+                " 2  Fix login bug         Critical  Pending  2 days ago",
+                " 3  Plan sprint           Low       Pending   yesterday",
+            ]
+            .join("\n")
+        );
+        assert_eq!(
+            layout(&sample(), &columns, 40, today()),
+            [
+                "ID  Title              Pri       Status",
+                " 1  Write the quarte…  High      Pending",
+                " 2  Fix login bug      Critical  Pending",
+                " 3  Plan sprint        Low       Pending",
+            ]
+            .join("\n")
+        );
+        // Too narrow for anything but the id and the title
+        assert_eq!(layout(&sample(), &columns, 10, today()).lines().next(), Some("ID  Title"));
+    }
+}
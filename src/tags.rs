@@ -0,0 +1,99 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::TaskError;
+
+// What the team agreed a tag means, kept in the tag registry under the tag's
+// lowercase spelling
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TagInfo {
+    pub description: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub color: Option<String>,
+}
+
+pub type TagRegistry = BTreeMap<String, TagInfo>;
+
+// A tag as `tags` lists it: registered, in use, or both
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TagRow {
+    pub tag: String,
+    // Tasks carrying it
+    pub count: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub info: Option<TagInfo>,
+}
+
+// The standalone file `export tags` writes, meant to be kept in a repository:
+// `[tags.backend]` tables in TOML, or the same shape in JSON
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+struct TagFile {
+    #[serde(default)]
+    tags: TagRegistry,
+}
+
+fn is_toml(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("toml"))
+}
+
+fn file_error(path: &Path, e: impl std::fmt::Display) -> TaskError {
+    TaskError::Storage(format!("{}: {}", path.display(), e))
+}
+
+// TOML when the path ends in .toml, JSON otherwise
+pub fn write_file(path: &Path, registry: &TagRegistry) -> Result<(), TaskError> {
+    let file = TagFile { tags: registry.clone() };
+    let text = if is_toml(path) {
+        toml::to_string(&file).map_err(|e| file_error(path, e))?
+    } else {
+        serde_json::to_string_pretty(&file).map_err(|e| file_error(path, e))?
+    };
+    fs::write(path, text).map_err(|e| file_error(path, e))
+}
+
+pub fn read_file(path: &Path) -> Result<TagRegistry, TaskError> {
+    let text = fs::read_to_string(path).map_err(|e| file_error(path, e))?;
+    let file: TagFile = if is_toml(path) {
+        toml::from_str(&text).map_err(|e| file_error(path, e))?
+    } else {
+        serde_json::from_str(&text).map_err(|e| file_error(path, e))?
+    };
+    Ok(file.tags.into_iter().map(|(tag, info)| (tag.trim().to_lowercase(), info)).collect())
+}
+
+// How an import changed the registry, by tag
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct TagImport {
+    pub added: Vec<String>,
+    pub updated: Vec<String>,
+    // Only with --replace
+    pub removed: Vec<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_registry_files_round_trip() {
+        let mut registry = TagRegistry::new();
+        registry.insert("backend".to_string(), TagInfo { description: "Server code".to_string(), color: Some("blue".to_string()) });
+        registry.insert("ops".to_string(), TagInfo { description: "Deploys and on-call".to_string(), color: None });
+        for name in ["tags.toml", "tags.json"] {
+            let path = std::env::temp_dir().join(format!("task-manager-{}-{}", std::process::id(), name));
+            write_file(&path, &registry).unwrap();
+            assert_eq!(read_file(&path).unwrap(), registry);
+            let _ = fs::remove_file(&path);
+        }
+
+        let path = std::env::temp_dir().join(format!("task-manager-{}-hand.toml", std::process::id()));
+        fs::write(&path, "[tags.Backend]\ndescription = \"Server code\"\n").unwrap();
+        assert_eq!(read_file(&path).unwrap().keys().collect::<Vec<_>>(), ["backend"]);
+        fs::write(&path, "[tags.backend]\ncolor = 3\n").unwrap();
+        assert!(matches!(read_file(&path), Err(TaskError::Storage(_))));
+        let _ = fs::remove_file(&path);
+    }
+}
@@ -0,0 +1,866 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::TaskError;
+use crate::render::{DisplayMode, format_task};
+use crate::search::{self, SearchText};
+
+/// Task priority levels, ordered from least to most urgent.
+///
+/// ```
+/// use task_manager::Priority;
+///
+/// assert!(Priority::Critical > Priority::Low);
+/// assert_eq!(Priority::try_from("h").unwrap(), Priority::High);
+/// assert_eq!(Priority::High.to_string(), "High");
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum Priority {
+    Low,
+    Medium,
+    High,
+    Critical,
+}
+
+impl fmt::Display for Priority {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Priority::Low => write!(f, "Low"),
+            Priority::Medium => write!(f, "Medium"),
+            Priority::High => write!(f, "High"),
+            Priority::Critical => write!(f, "Critical"),
+        }
+    }
+}
+
+impl TryFrom<&str> for Priority {
+    type Error = TaskError;
+
+    /// A level's name or first letter, in any case.
+    ///
+    /// ```
+    /// # use task_manager::Priority;
+    /// assert_eq!(Priority::try_from("Critical").unwrap(), Priority::Critical);
+    /// assert_eq!(Priority::try_from("m").unwrap(), Priority::Medium);
+    /// assert!(Priority::try_from("urgent").is_err());
+    /// ```
+    fn try_from(s: &str) -> Result<Priority, TaskError> {
+        match s.to_lowercase().as_str() {
+            "low" | "l" => Ok(Priority::Low),
+            "medium" | "m" => Ok(Priority::Medium),
+            "high" | "h" => Ok(Priority::High),
+            "critical" | "c" => Ok(Priority::Critical),
+            _ => Err(TaskError::InvalidInput),
+        }
+    }
+}
+
+impl FromStr for Priority {
+    type Err = TaskError;
+
+    fn from_str(s: &str) -> Result<Priority, TaskError> {
+        Priority::try_from(s)
+    }
+}
+
+/// How much focus a task takes, from least to most.
+///
+/// ```
+/// use task_manager::Energy;
+///
+/// assert_eq!(Energy::try_from("easy").unwrap(), Energy::Low);
+/// assert!(Energy::High > Energy::Medium);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum Energy {
+    Low,
+    Medium,
+    High,
+}
+
+impl fmt::Display for Energy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Energy::Low => write!(f, "Low"),
+            Energy::Medium => write!(f, "Medium"),
+            Energy::High => write!(f, "High"),
+        }
+    }
+}
+
+impl TryFrom<&str> for Energy {
+    type Error = TaskError;
+
+    /// A level's name, first letter or `easy`/`normal`/`hard`, in any case.
+    ///
+    /// ```
+    /// # use task_manager::Energy;
+    /// assert_eq!(Energy::try_from("H").unwrap(), Energy::High);
+    /// assert!(Energy::try_from("tired").is_err());
+    /// ```
+    fn try_from(s: &str) -> Result<Energy, TaskError> {
+        match s.to_lowercase().as_str() {
+            "low" | "l" | "easy" => Ok(Energy::Low),
+            "medium" | "m" | "normal" => Ok(Energy::Medium),
+            "high" | "h" | "hard" => Ok(Energy::High),
+            _ => Err(TaskError::InvalidInput),
+        }
+    }
+}
+
+impl FromStr for Energy {
+    type Err = TaskError;
+
+    fn from_str(s: &str) -> Result<Energy, TaskError> {
+        Energy::try_from(s)
+    }
+}
+
+/// Where a task is in its life. More states may be added, so matches need a
+/// wildcard arm.
+///
+/// ```
+/// use task_manager::TaskStatus;
+///
+/// let status = TaskStatus::try_from("in progress").unwrap();
+/// assert_eq!(status, TaskStatus::InProgress);
+/// assert_eq!(status.to_string(), "In Progress");
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[non_exhaustive]
+pub enum TaskStatus {
+    Pending,
+    InProgress,
+    Completed,
+}
+
+impl fmt::Display for TaskStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TaskStatus::Pending => write!(f, "Pending"),
+            TaskStatus::InProgress => write!(f, "In Progress"),
+            TaskStatus::Completed => write!(f, "Completed"),
+        }
+    }
+}
+
+impl TryFrom<&str> for TaskStatus {
+    type Error = TaskError;
+
+    /// `pending`, `progress` or `in progress`, `completed`, in any case.
+    ///
+    /// ```
+    /// # use task_manager::TaskStatus;
+    /// assert_eq!(TaskStatus::try_from("Completed").unwrap(), TaskStatus::Completed);
+    /// assert!(TaskStatus::try_from("done").is_err());
+    /// ```
+    fn try_from(s: &str) -> Result<TaskStatus, TaskError> {
+        match s.to_lowercase().as_str() {
+            "pending" => Ok(TaskStatus::Pending),
+            "progress" | "in progress" => Ok(TaskStatus::InProgress),
+            "completed" => Ok(TaskStatus::Completed),
+            _ => Err(TaskError::InvalidInput),
+        }
+    }
+}
+
+impl FromStr for TaskStatus {
+    type Err = TaskError;
+
+    fn from_str(s: &str) -> Result<TaskStatus, TaskError> {
+        TaskStatus::try_from(s)
+    }
+}
+
+/// One stretch of work on a task.
+///
+/// ```
+/// use task_manager::{Priority, Task};
+///
+/// let task = Task::new(1, "Draft".to_string(), String::new(), Priority::Low);
+/// let worked: u64 = task.sessions().iter().map(|session| session.seconds).sum();
+/// assert_eq!(worked, 0);
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WorkSession {
+    pub started_at: DateTime<Utc>,
+    pub seconds: u64,
+    // False when the timer was stopped before it ran out
+    pub completed: bool,
+}
+
+/// "Remind me `offset` before the task is due"; `fired` once it has gone out.
+///
+/// ```
+/// use std::time::Duration;
+/// use task_manager::Reminder;
+///
+/// let reminder = Reminder { offset: Duration::from_secs(3600), fired: false };
+/// assert!(!reminder.fired);
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Reminder {
+    pub offset: Duration,
+    #[serde(default)]
+    pub fired: bool,
+}
+
+/// Who a task is blocked on, since when, and when to chase them.
+///
+/// ```
+/// use chrono::NaiveDate;
+/// use task_manager::Waiting;
+///
+/// let day = |d| NaiveDate::from_ymd_opt(2024, 5, d).unwrap();
+/// let waiting = Waiting { who: "ana".to_string(), since: day(1), follow_up: Some(day(8)) };
+/// assert_eq!(waiting.days(day(5)), 4);
+/// assert!(!waiting.follow_up_due(day(5)));
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Waiting {
+    pub who: String,
+    pub since: NaiveDate,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub follow_up: Option<NaiveDate>,
+}
+
+/// Set on a project's top task: the prefix of its tasks' keys and the number the
+/// next one gets, kept with the data so numbers are never handed out twice.
+///
+/// ```
+/// use task_manager::KeyPrefix;
+///
+/// let prefix = KeyPrefix { prefix: "AUTH".to_string(), next: 8 };
+/// assert_eq!(format!("{}-{}", prefix.prefix, prefix.next), "AUTH-8");
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct KeyPrefix {
+    pub prefix: String,
+    pub next: u32,
+}
+
+// Project prefixes are up to ten uppercase letters and digits starting with a letter, such as "AUTH"
+pub fn valid_key_prefix(prefix: &str) -> bool {
+    prefix.len() <= 10
+        && prefix.starts_with(|c: char| c.is_ascii_uppercase())
+        && prefix.bytes().all(|b| b.is_ascii_uppercase() || b.is_ascii_digit())
+}
+
+impl Waiting {
+    /// Whole days waited by `today`.
+    ///
+    /// ```
+    /// # use chrono::NaiveDate;
+    /// # use task_manager::Waiting;
+    /// let since = NaiveDate::from_ymd_opt(2024, 5, 1).unwrap();
+    /// let waiting = Waiting { who: "ana".to_string(), since, follow_up: None };
+    /// assert_eq!(waiting.days(since), 0);
+    /// ```
+    pub fn days(&self, today: NaiveDate) -> i64 {
+        (today - self.since).num_days().max(0)
+    }
+
+    /// The follow-up date has come and the task is still waiting.
+    ///
+    /// ```
+    /// # use chrono::NaiveDate;
+    /// # use task_manager::Waiting;
+    /// let since = NaiveDate::from_ymd_opt(2024, 5, 1).unwrap();
+    /// let waiting = Waiting { who: "ana".to_string(), since, follow_up: Some(since) };
+    /// assert!(waiting.follow_up_due(since));
+    /// ```
+    pub fn follow_up_due(&self, today: NaiveDate) -> bool {
+        self.follow_up.is_some_and(|date| date <= today)
+    }
+}
+
+/// A task and everything recorded about it.
+///
+/// Fields are read through getters and changed through [`TaskManager`](crate::TaskManager),
+/// which keeps its indexes and rules in step with every change.
+///
+/// ```
+/// use task_manager::{Priority, Task, TaskStatus};
+///
+/// let mut task = Task::new(1, "Write report".to_string(), "Q3 numbers".to_string(), Priority::High);
+/// task.add_tag("work");
+/// assert_eq!(task.title(), "Write report");
+/// assert_eq!(task.status(), &TaskStatus::Pending);
+/// assert_eq!(task.tags().collect::<Vec<_>>(), ["work"]);
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Task {
+    pub(crate) id: u32,
+    // Identifies the task across exports and machines, unlike the local id
+    #[serde(default = "new_uid")]
+    pub(crate) uid: String,
+    pub(crate) title: String,
+    pub(crate) description: String,
+    pub(crate) priority: Priority,
+    pub(crate) status: TaskStatus,
+    // Interned by the manager holding the task, so a tag shared by many tasks is
+    // stored once; written and read as plain strings
+    pub(crate) tags: Vec<Arc<str>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) due: Option<NaiveDate>,
+    // Where an imported task came from, e.g. "github:owner/repo#12"; matched on re-import
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) external_id: Option<String>,
+    // Uid of the task this is a subtask of; a uid rather than an id so the link
+    // survives sync, where ids differ between machines
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) parent: Option<String>,
+    // Time worked on the task, one entry per pomodoro
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(crate) sessions: Vec<WorkSession>,
+    // Reminders before the due date, earliest offset first
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(crate) reminders: Vec<Reminder>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) waiting_on: Option<Waiting>,
+    // Ids of tasks this one relates to ("see also"), ascending. Both tasks of a
+    // link list each other; unlike `parent` nothing waits on the other.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(crate) relates_to: Vec<u32>,
+    // Team-specific extras such as a ticket number or story points, by key
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub(crate) fields: BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) key_prefix: Option<KeyPrefix>,
+    // "AUTH-7": the project prefix and the task's number within the project
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) key: Option<String>,
+    // Keys the task had in projects it has since left, still accepted in its place
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(crate) old_keys: Vec<String>,
+    // False for tasks captured with `quick` until `triage` has sorted them out
+    #[serde(default = "triaged_by_default", skip_serializing_if = "is_triaged")]
+    pub(crate) triaged: bool,
+    // Listed ahead of everything else, whatever the sort
+    #[serde(default)]
+    pub(crate) pinned: bool,
+    // The user who added the task; None for tasks added before this was recorded
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) created_by: Option<String>,
+    // Place in the manual order (`list --sort manual`), handed out in steps of
+    // RANK_GAP so a task can move between two others without renumbering the rest.
+    // Tasks saved before manual ordering all have 0 and fall back to id order.
+    #[serde(default, skip_serializing_if = "is_unranked")]
+    pub(crate) rank: u64,
+    // Day the last due-date reminder was raised, so it fires once per day
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) last_notified: Option<NaiveDate>,
+    // Left out of `review` until this day
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) snoozed_until: Option<NaiveDate>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) energy: Option<Energy>,
+    // When the task last moved to Completed; None while open and for tasks
+    // completed before this was recorded
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) completed_at: Option<DateTime<Utc>>,
+    // Files written before timestamps existed load with the time of loading
+    #[serde(default = "Utc::now")]
+    pub(crate) created_at: DateTime<Utc>,
+    #[serde(default = "Utc::now")]
+    pub(crate) updated_at: DateTime<Utc>,
+}
+
+// Custom fields one task may carry
+pub const MAX_FIELDS: usize = 20;
+
+// Field keys are lowercase letters, digits and dashes starting with a letter, such
+// as "story-points"; the letter keeps times like 10:30 out of `key:value` queries
+pub fn valid_field_key(key: &str) -> bool {
+    key.starts_with(|c: char| c.is_ascii_lowercase())
+        && key.bytes().all(|b| b.is_ascii_lowercase() || b.is_ascii_digit() || b == b'-')
+}
+
+// Tasks saved before the inbox existed were sorted out by hand
+fn triaged_by_default() -> bool {
+    true
+}
+
+fn is_triaged(triaged: &bool) -> bool {
+    *triaged
+}
+
+fn is_unranked(rank: &u64) -> bool {
+    *rank == 0
+}
+
+// Tasks saved before uids existed get one when loaded
+pub fn new_uid() -> String {
+    uuid::Uuid::new_v4().to_string()
+}
+
+impl Task {
+    /// A pending task with no tags or due date. Managers give it their own id
+    /// when it is stored, so `id` only matters for tasks kept outside one.
+    ///
+    /// ```
+    /// use task_manager::{Priority, Task};
+    ///
+    /// let task = Task::new(0, "Call the bank".to_string(), String::new(), Priority::Medium);
+    /// assert_eq!(task.priority(), &Priority::Medium);
+    /// assert!(task.due().is_none());
+    /// ```
+    pub fn new(id: u32, title: String, description: String, priority: Priority) -> Self {
+        Task {
+            id,
+            uid: new_uid(),
+            title,
+            description,
+            priority,
+            status: TaskStatus::Pending,
+            tags: Vec::new(),
+            due: None,
+            external_id: None,
+            parent: None,
+            sessions: Vec::new(),
+            reminders: Vec::new(),
+            waiting_on: None,
+            relates_to: Vec::new(),
+            fields: BTreeMap::new(),
+            key_prefix: None,
+            key: None,
+            old_keys: Vec::new(),
+            triaged: true,
+            pinned: false,
+            created_by: None,
+            rank: 0,
+            last_notified: None,
+            snoozed_until: None,
+            energy: None,
+            completed_at: None,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+        }
+    }
+
+    /// The same task due on `due`.
+    ///
+    /// ```
+    /// use chrono::NaiveDate;
+    /// use task_manager::{Priority, Task};
+    ///
+    /// let date = NaiveDate::from_ymd_opt(2024, 5, 1).unwrap();
+    /// let task = Task::new(0, "Pay rent".to_string(), String::new(), Priority::High).with_due(date);
+    /// assert_eq!(task.due(), Some(date));
+    /// ```
+    pub fn with_due(mut self, due: NaiveDate) -> Self {
+        self.due = Some(due);
+        self
+    }
+
+    /// The local id, unique within one list.
+    ///
+    /// ```
+    /// # use task_manager::{Priority, Task};
+    /// assert_eq!(Task::new(7, "Tidy".to_string(), String::new(), Priority::Low).id(), 7);
+    /// ```
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+
+    /// The id that stays the same across exports and machines.
+    ///
+    /// ```
+    /// # use task_manager::{Priority, Task};
+    /// assert_eq!(Task::new(1, "Tidy".to_string(), String::new(), Priority::Low).uid().len(), 36);
+    /// ```
+    pub fn uid(&self) -> &str {
+        &self.uid
+    }
+
+    /// ```
+    /// # use task_manager::{Priority, Task};
+    /// assert_eq!(Task::new(1, "Tidy".to_string(), String::new(), Priority::Low).title(), "Tidy");
+    /// ```
+    pub fn title(&self) -> &str {
+        &self.title
+    }
+
+    /// ```
+    /// # use task_manager::{Priority, Task};
+    /// let task = Task::new(1, "Tidy".to_string(), "The desk".to_string(), Priority::Low);
+    /// assert_eq!(task.description(), "The desk");
+    /// ```
+    pub fn description(&self) -> &str {
+        &self.description
+    }
+
+    /// ```
+    /// # use task_manager::{Priority, Task};
+    /// let task = Task::new(1, "Tidy".to_string(), String::new(), Priority::Low);
+    /// assert_eq!(task.priority(), &Priority::Low);
+    /// ```
+    pub fn priority(&self) -> &Priority {
+        &self.priority
+    }
+
+    /// ```
+    /// # use task_manager::{Priority, Task, TaskStatus};
+    /// let task = Task::new(1, "Tidy".to_string(), String::new(), Priority::Low);
+    /// assert_eq!(task.status(), &TaskStatus::Pending);
+    /// ```
+    pub fn status(&self) -> &TaskStatus {
+        &self.status
+    }
+
+    /// The tags in the order they were added.
+    ///
+    /// ```
+    /// # use task_manager::{Priority, Task};
+    /// let mut task = Task::new(1, "Tidy".to_string(), String::new(), Priority::Low);
+    /// task.add_tag("home");
+    /// task.add_tag("weekend");
+    /// assert_eq!(task.tags().collect::<Vec<_>>(), ["home", "weekend"]);
+    /// ```
+    pub fn tags(&self) -> impl ExactSizeIterator<Item = &str> {
+        self.tags.iter().map(|tag| &**tag)
+    }
+
+    /// ```
+    /// # use task_manager::{Priority, Task};
+    /// assert_eq!(Task::new(1, "Tidy".to_string(), String::new(), Priority::Low).due(), None);
+    /// ```
+    pub fn due(&self) -> Option<NaiveDate> {
+        self.due
+    }
+
+    /// Where an imported task came from, such as `github:owner/repo#12`.
+    ///
+    /// ```
+    /// # use task_manager::{Priority, Task};
+    /// assert_eq!(Task::new(1, "Tidy".to_string(), String::new(), Priority::Low).external_id(), None);
+    /// ```
+    pub fn external_id(&self) -> Option<&str> {
+        self.external_id.as_deref()
+    }
+
+    /// Uid of the task this one is a subtask of.
+    ///
+    /// ```
+    /// # use task_manager::{Priority, Task};
+    /// assert_eq!(Task::new(1, "Tidy".to_string(), String::new(), Priority::Low).parent(), None);
+    /// ```
+    pub fn parent(&self) -> Option<&str> {
+        self.parent.as_deref()
+    }
+
+    /// Time worked on the task, one entry per pomodoro.
+    ///
+    /// ```
+    /// # use task_manager::{Priority, Task};
+    /// assert!(Task::new(1, "Tidy".to_string(), String::new(), Priority::Low).sessions().is_empty());
+    /// ```
+    pub fn sessions(&self) -> &[WorkSession] {
+        &self.sessions
+    }
+
+    /// Reminders before the due date, earliest offset first.
+    ///
+    /// ```
+    /// # use task_manager::{Priority, Task};
+    /// assert!(Task::new(1, "Tidy".to_string(), String::new(), Priority::Low).reminders().is_empty());
+    /// ```
+    pub fn reminders(&self) -> &[Reminder] {
+        &self.reminders
+    }
+
+    /// Who the task is blocked on, if anyone.
+    ///
+    /// ```
+    /// # use task_manager::{Priority, Task};
+    /// assert!(Task::new(1, "Tidy".to_string(), String::new(), Priority::Low).waiting_on().is_none());
+    /// ```
+    pub fn waiting_on(&self) -> Option<&Waiting> {
+        self.waiting_on.as_ref()
+    }
+
+    /// Ids of related tasks, ascending.
+    ///
+    /// ```
+    /// # use task_manager::{Priority, Task};
+    /// assert!(Task::new(1, "Tidy".to_string(), String::new(), Priority::Low).relates_to().is_empty());
+    /// ```
+    pub fn relates_to(&self) -> &[u32] {
+        &self.relates_to
+    }
+
+    /// Custom fields by key.
+    ///
+    /// ```
+    /// # use task_manager::{Priority, Task};
+    /// assert!(Task::new(1, "Tidy".to_string(), String::new(), Priority::Low).fields().is_empty());
+    /// ```
+    pub fn fields(&self) -> &BTreeMap<String, String> {
+        &self.fields
+    }
+
+    /// One custom field's value.
+    ///
+    /// ```
+    /// # use task_manager::{Priority, Task};
+    /// assert_eq!(Task::new(1, "Tidy".to_string(), String::new(), Priority::Low).field("estimate"), None);
+    /// ```
+    pub fn field(&self, key: &str) -> Option<&str> {
+        self.fields.get(key).map(String::as_str)
+    }
+
+    /// The project key, such as `AUTH-7`.
+    ///
+    /// ```
+    /// # use task_manager::{Priority, Task};
+    /// assert_eq!(Task::new(1, "Tidy".to_string(), String::new(), Priority::Low).key(), None);
+    /// ```
+    pub fn key(&self) -> Option<&str> {
+        self.key.as_deref()
+    }
+
+    /// False for quickly captured tasks not yet triaged.
+    ///
+    /// ```
+    /// # use task_manager::{Priority, Task};
+    /// assert!(Task::new(1, "Tidy".to_string(), String::new(), Priority::Low).is_triaged());
+    /// ```
+    pub fn is_triaged(&self) -> bool {
+        self.triaged
+    }
+
+    /// ```
+    /// # use task_manager::{Priority, Task};
+    /// assert!(!Task::new(1, "Tidy".to_string(), String::new(), Priority::Low).is_pinned());
+    /// ```
+    pub fn is_pinned(&self) -> bool {
+        self.pinned
+    }
+
+    /// The user who added the task, when that was recorded.
+    ///
+    /// ```
+    /// # use task_manager::{Priority, Task};
+    /// assert_eq!(Task::new(1, "Tidy".to_string(), String::new(), Priority::Low).created_by(), None);
+    /// ```
+    pub fn created_by(&self) -> Option<&str> {
+        self.created_by.as_deref()
+    }
+
+    /// ```
+    /// # use task_manager::{Priority, Task};
+    /// assert_eq!(Task::new(1, "Tidy".to_string(), String::new(), Priority::Low).energy(), None);
+    /// ```
+    pub fn energy(&self) -> Option<Energy> {
+        self.energy
+    }
+
+    /// When the task was last completed; None while it is open.
+    ///
+    /// ```
+    /// # use task_manager::{Priority, Task, TaskStatus};
+    /// let mut task = Task::new(1, "Tidy".to_string(), String::new(), Priority::Low);
+    /// task.update_status(TaskStatus::Completed);
+    /// assert!(task.completed_at().is_some());
+    /// ```
+    pub fn completed_at(&self) -> Option<DateTime<Utc>> {
+        self.completed_at
+    }
+
+    /// ```
+    /// # use task_manager::{Priority, Task};
+    /// let task = Task::new(1, "Tidy".to_string(), String::new(), Priority::Low);
+    /// assert!(task.created_at() <= chrono::Utc::now());
+    /// ```
+    pub fn created_at(&self) -> DateTime<Utc> {
+        self.created_at
+    }
+
+    /// ```
+    /// # use task_manager::{Priority, Task};
+    /// let task = Task::new(1, "Tidy".to_string(), String::new(), Priority::Low);
+    /// assert!(task.updated_at() >= task.created_at());
+    /// ```
+    pub fn updated_at(&self) -> DateTime<Utc> {
+        self.updated_at
+    }
+
+    /// Marks the task as changed now.
+    ///
+    /// ```
+    /// # use task_manager::{Priority, Task};
+    /// let mut task = Task::new(1, "Tidy".to_string(), String::new(), Priority::Low);
+    /// let before = task.updated_at();
+    /// task.touch();
+    /// assert!(task.updated_at() >= before);
+    /// ```
+    pub fn touch(&mut self) {
+        self.updated_at = Utc::now();
+    }
+
+    /// Heap memory the task holds on its own. Tag text is shared between tasks
+    /// through the manager's interner, so only the pointers to it count here.
+    ///
+    /// ```
+    /// # use task_manager::{Priority, Task};
+    /// let task = Task::new(1, "Tidy".to_string(), String::new(), Priority::Low);
+    /// assert!(task.heap_bytes() >= "Tidy".len());
+    /// ```
+    pub fn heap_bytes(&self) -> usize {
+        self.uid.capacity()
+            + self.title.capacity()
+            + self.description.capacity()
+            + self.tags.capacity() * std::mem::size_of::<Arc<str>>()
+            + self.external_id.as_ref().map_or(0, String::capacity)
+            + self.parent.as_ref().map_or(0, String::capacity)
+            + self.sessions.capacity() * std::mem::size_of::<WorkSession>()
+            + self.reminders.capacity() * std::mem::size_of::<Reminder>()
+            + self.waiting_on.as_ref().map_or(0, |waiting| waiting.who.capacity())
+            + self.fields.iter().map(|(key, value)| key.capacity() + value.capacity()).sum::<usize>()
+            + self.key_prefix.as_ref().map_or(0, |prefix| prefix.prefix.capacity())
+            + self.key.as_ref().map_or(0, String::capacity)
+            + self.old_keys.iter().map(String::capacity).sum::<usize>()
+            + self.old_keys.capacity() * std::mem::size_of::<String>()
+    }
+
+    /// Sessions that ran their full length.
+    ///
+    /// ```
+    /// # use task_manager::{Priority, Task};
+    /// assert_eq!(Task::new(1, "Tidy".to_string(), String::new(), Priority::Low).pomodoros(), 0);
+    /// ```
+    pub fn pomodoros(&self) -> usize {
+        self.sessions.iter().filter(|session| session.completed).count()
+    }
+
+    /// Time worked across all sessions.
+    ///
+    /// ```
+    /// # use std::time::Duration;
+    /// # use task_manager::{Priority, Task};
+    /// let task = Task::new(1, "Tidy".to_string(), String::new(), Priority::Low);
+    /// assert_eq!(task.time_tracked(), Duration::ZERO);
+    /// ```
+    pub fn time_tracked(&self) -> Duration {
+        Duration::from_secs(self.sessions.iter().map(|session| session.seconds).sum())
+    }
+
+    /// Returns false when the tag was already present.
+    ///
+    /// ```
+    /// # use task_manager::{Priority, Task};
+    /// let mut task = Task::new(1, "Tidy".to_string(), String::new(), Priority::Low);
+    /// assert!(task.add_tag("home"));
+    /// assert!(!task.add_tag("home"));
+    /// ```
+    pub fn add_tag(&mut self, tag: impl Into<Arc<str>>) -> bool {
+        let tag = tag.into();
+        if self.tags.contains(&tag) {
+            return false;
+        }
+        self.tags.push(tag);
+        self.touch();
+        true
+    }
+
+    /// Sets the status, recording the completion time on the way to Completed.
+    ///
+    /// ```
+    /// # use task_manager::{Priority, Task, TaskStatus};
+    /// let mut task = Task::new(1, "Tidy".to_string(), String::new(), Priority::Low);
+    /// task.update_status(TaskStatus::InProgress);
+    /// assert_eq!(task.status(), &TaskStatus::InProgress);
+    /// ```
+    pub fn update_status(&mut self, status: TaskStatus) {
+        if status != TaskStatus::Completed {
+            self.completed_at = None;
+        } else if self.status != TaskStatus::Completed {
+            self.completed_at = Some(Utc::now());
+        }
+        self.status = status;
+        self.touch();
+    }
+
+    /// Case- and accent-insensitive text in the title, description, a tag or a field.
+    ///
+    /// ```
+    /// # use task_manager::{Priority, Task};
+    /// let task = Task::new(1, "Café order".to_string(), String::new(), Priority::Low);
+    /// assert!(task.matches_filter("cafe"));
+    /// ```
+    pub fn matches_filter(&self, filter: &str) -> bool {
+        SearchText::new(self, true).contains(&search::fold(filter, true))
+    }
+}
+
+impl fmt::Display for Task {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", format_task(self, DisplayMode::Normal))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_task_creation() {
+        let task = Task::new(1, "Test Task".to_string(), "Description".to_string(), Priority::High);
+        assert_eq!(task.id, 1);
+        assert_eq!(task.title, "Test Task");
+        assert_eq!(task.priority, Priority::High);
+        assert_eq!(task.status, TaskStatus::Pending);
+    }
+
+    #[test]
+    fn test_new_tasks_get_distinct_uids() {
+        let task = Task::new(1, "Test Task".to_string(), "Description".to_string(), Priority::High);
+        let other = Task::new(2, "Other".to_string(), String::new(), Priority::Low);
+        assert_eq!(task.uid.len(), 36);
+        assert_ne!(task.uid, other.uid);
+    }
+
+    #[test]
+    fn test_completion_time_follows_the_status() {
+        let mut task = Task::new(1, "Ship".to_string(), String::new(), Priority::High);
+        task.update_status(TaskStatus::InProgress);
+        assert_eq!(task.completed_at, None);
+        task.update_status(TaskStatus::Completed);
+        let done = task.completed_at.expect("set on completion");
+        // Completing it again keeps the first time
+        task.update_status(TaskStatus::Completed);
+        assert_eq!(task.completed_at, Some(done));
+        task.update_status(TaskStatus::Pending);
+        assert_eq!(task.completed_at, None);
+    }
+
+    #[test]
+    fn test_filter_folds_case_and_accents() {
+        let matches = |field: &str, filter: &str| {
+            Task::new(1, field.to_string(), String::new(), Priority::Low).matches_filter(filter)
+        };
+        assert!(matches("Deploy the API", "deploy"));
+        assert!(matches("Deploy the API", "THE api"));
+        assert!(matches("ÉCOLE d'été", "ete"));
+        assert!(matches("Über dependency cleanup", "uber"));
+        assert!(matches("café", "CAFÉ"));
+        assert!(matches("Straße", "STRASSE"));
+        assert!(matches("STRASSE", "ß"));
+        assert!(matches("ΟΔΟΣ", "ς"));
+        assert!(matches("İstanbul", "istanbul"));
+        assert!(!matches("Istanbul", "ı"));
+        assert!(matches("tab\there", "\t"));
+        assert!(matches("", ""));
+        assert!(!matches("Deploy", "deploys"));
+        // The Kelvin sign lowercases to an ASCII k
+        let task = Task::new(1, "temp".to_string(), "300K".to_string(), Priority::Low);
+        assert!(task.matches_filter("\u{212A}"));
+        assert!(!task.matches_filter("kelvin"));
+    }
+}
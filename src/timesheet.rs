@@ -0,0 +1,174 @@
+use std::time::Duration;
+
+use chrono::{DateTime, Datelike, Days, NaiveTime, TimeZone, Utc, Weekday};
+use serde::Serialize;
+
+use crate::focus::format_elapsed;
+use crate::msg;
+
+// The tag group of tasks that have none
+pub const UNTAGGED: &str = "(untagged)";
+
+// What the hours of a time report are summed by
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GroupKey {
+    Tag,
+    // The top task of a subtask hierarchy
+    Project,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReportPeriod {
+    Week,
+    Month,
+    All,
+}
+
+// Half-open span of time; None leaves that side unbounded
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DateRange {
+    pub start: Option<DateTime<Utc>>,
+    pub end: Option<DateTime<Utc>>,
+}
+
+impl DateRange {
+    pub fn all() -> DateRange {
+        DateRange { start: None, end: None }
+    }
+
+    pub fn between(start: DateTime<Utc>, end: DateTime<Utc>) -> DateRange {
+        DateRange { start: Some(start), end: Some(end) }
+    }
+
+    // The current week or month up to now, starting at midnight in `now`'s time zone
+    pub fn for_period<Tz: TimeZone>(period: ReportPeriod, now: &DateTime<Tz>, week_start: Weekday) -> DateRange {
+        let today = now.date_naive();
+        let first = match period {
+            ReportPeriod::All => return DateRange::all(),
+            ReportPeriod::Week => today - Days::new(u64::from(today.weekday().days_since(week_start))),
+            ReportPeriod::Month => today.with_day(1).expect("every month has a first day"),
+        };
+        let start = now.timezone().from_local_datetime(&first.and_time(NaiveTime::MIN)).earliest();
+        DateRange { start: start.map(|start| start.with_timezone(&Utc)), end: None }
+    }
+
+    // How much of the span from `start` lasting `length` falls inside the range
+    pub fn overlap(&self, start: DateTime<Utc>, length: Duration) -> Duration {
+        let end = start + length;
+        let from = self.start.map_or(start, |bound| bound.max(start));
+        let to = self.end.map_or(end, |bound| bound.min(end));
+        (to - from).to_std().unwrap_or(Duration::ZERO)
+    }
+}
+
+// `timesheet` results: hours per group, and the hours actually worked, which is
+// less than the sum of the groups when tasks carry several tags
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Timesheet {
+    pub group: GroupKey,
+    pub period: ReportPeriod,
+    pub rows: Vec<(String, Duration)>,
+    pub total: Duration,
+}
+
+impl Timesheet {
+    pub fn counted_twice(&self) -> bool {
+        self.rows.iter().map(|(_, time)| *time).sum::<Duration>() > self.total
+    }
+
+    pub fn table(&self) -> String {
+        let period = match self.period {
+            ReportPeriod::Week => msg!("timesheet_week"),
+            ReportPeriod::Month => msg!("timesheet_month"),
+            ReportPeriod::All => msg!("timesheet_all"),
+        };
+        let heading = match self.group {
+            GroupKey::Tag => msg!("timesheet_tag", period = period),
+            GroupKey::Project => msg!("timesheet_project", period = period),
+        };
+        let mut lines = vec![heading];
+        if self.rows.is_empty() {
+            lines.push(msg!("timesheet_empty"));
+            return lines.join("\n");
+        }
+        let width = self.rows.iter().map(|(name, _)| name.chars().count()).max().unwrap_or(0).max(5);
+        for (name, time) in &self.rows {
+            lines.push(format!("{:<width$}  {:>8}", name, format_elapsed(*time), width = width));
+        }
+        lines.push("─".repeat(width + 10));
+        lines.push(format!("{:<width$}  {:>8}", msg!("timesheet_total"), format_elapsed(self.total), width = width));
+        if self.counted_twice() {
+            lines.push(msg!("timesheet_overlap"));
+        }
+        lines.join("\n")
+    }
+
+    // group,hours rows in decimal hours, then the total
+    pub fn csv(&self) -> String {
+        let mut writer = csv::Writer::from_writer(Vec::new());
+        let hours = |time: &Duration| format!("{:.2}", time.as_secs_f64() / 3600.0);
+        let mut rows = vec![(self.group_name().to_string(), "hours".to_string())];
+        rows.extend(self.rows.iter().map(|(name, time)| (name.clone(), hours(time))));
+        rows.push(("total".to_string(), hours(&self.total)));
+        for row in rows {
+            writer.serialize(row).expect("writing to memory cannot fail");
+        }
+        String::from_utf8(writer.into_inner().expect("writing to memory cannot fail")).expect("csv of strings is utf-8")
+    }
+
+    fn group_name(&self) -> &'static str {
+        match self.group {
+            GroupKey::Tag => "tag",
+            GroupKey::Project => "project",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(day: u32, hour: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 7, day, hour, 0, 0).unwrap()
+    }
+
+    #[test]
+    fn test_period_ranges_start_at_midnight() {
+        // 2024-07-10 is a Wednesday
+        let now = Utc.with_ymd_and_hms(2024, 7, 10, 15, 30, 0).unwrap();
+        assert_eq!(DateRange::for_period(ReportPeriod::Week, &now, Weekday::Mon).start, Some(at(8, 0)));
+        assert_eq!(DateRange::for_period(ReportPeriod::Week, &now, Weekday::Sun).start, Some(at(7, 0)));
+        assert_eq!(DateRange::for_period(ReportPeriod::Week, &now, Weekday::Wed).start, Some(at(10, 0)));
+        assert_eq!(DateRange::for_period(ReportPeriod::Month, &now, Weekday::Mon).start, Some(at(1, 0)));
+        assert_eq!(DateRange::for_period(ReportPeriod::All, &now, Weekday::Mon), DateRange::all());
+    }
+
+    #[test]
+    fn test_overlap_clips_to_the_range() {
+        let range = DateRange::between(at(8, 0), at(9, 0));
+        let hours = |n: u64| Duration::from_secs(n * 3600);
+        assert_eq!(range.overlap(at(7, 23), hours(2)), hours(1));
+        assert_eq!(range.overlap(at(8, 23), hours(2)), hours(1));
+        assert_eq!(range.overlap(at(8, 10), hours(2)), hours(2));
+        assert_eq!(range.overlap(at(6, 10), hours(2)), Duration::ZERO);
+        assert_eq!(DateRange::all().overlap(at(6, 10), hours(2)), hours(2));
+    }
+
+    #[test]
+    fn test_table_and_csv() {
+        let sheet = Timesheet {
+            group: GroupKey::Tag,
+            period: ReportPeriod::Week,
+            rows: vec![("migration".to_string(), Duration::from_secs(5400)), ("ops".to_string(), Duration::from_secs(1800))],
+            total: Duration::from_secs(5400),
+        };
+        assert_eq!(
+            sheet.table(),
+            "=== Time by Tag, This Week ===\nmigration    1h 30m\nops         30m 00s\n───────────────────\nTotal        1h 30m\n\
+             Tasks with several tags count under each, so the groups add up to more than the total."
+        );
+        assert_eq!(sheet.csv(), "tag,hours\nmigration,1.50\nops,0.50\ntotal,1.50\n");
+    }
+}
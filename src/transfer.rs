@@ -0,0 +1,318 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use serde::Serialize;
+
+use crate::command::ParseError;
+use crate::storage::Storage;
+use crate::task::new_uid;
+use crate::{Task, TaskError, TaskFilter, TaskManager};
+
+const IMPORT_USAGE: &str = "usage_import";
+
+// What happens to a task whose title the list already has
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OnDuplicate {
+    // Leave it where it is
+    #[default]
+    Skip,
+    // Bring it in as "Title (source)"
+    Rename,
+}
+
+impl FromStr for OnDuplicate {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<OnDuplicate, ()> {
+        match s.to_lowercase().as_str() {
+            "skip" => Ok(OnDuplicate::Skip),
+            "rename" => Ok(OnDuplicate::Rename),
+            _ => Err(()),
+        }
+    }
+}
+
+// import tasks <path> [query...] [--move] [--duplicates skip|rename] [--yes]
+#[derive(Debug, Clone, PartialEq)]
+pub struct TransferRequest {
+    // The other list's data file
+    pub path: PathBuf,
+    // Which of its tasks to bring in, as a view query; every task when empty
+    pub filter: TaskFilter,
+    // Also delete them there once this list is saved
+    pub moving: bool,
+    pub on_duplicate: OnDuplicate,
+    // --yes: no preview and question first
+    pub confirmed: bool,
+}
+
+impl TransferRequest {
+    pub fn parse(args: &[String]) -> Result<TransferRequest, ParseError> {
+        let (path, rest) = args.split_first().ok_or(ParseError::Usage(IMPORT_USAGE))?;
+        let (mut moving, mut on_duplicate, mut confirmed) = (false, OnDuplicate::Skip, false);
+        let mut words = Vec::new();
+        let mut rest = rest.iter();
+        while let Some(arg) = rest.next() {
+            match arg.as_str() {
+                "--move" => moving = true,
+                "--yes" => confirmed = true,
+                "--duplicates" => {
+                    on_duplicate = rest
+                        .next()
+                        .and_then(|policy| policy.parse().ok())
+                        .ok_or(ParseError::Usage(IMPORT_USAGE))?;
+                }
+                word => words.push(word),
+            }
+        }
+        let filter = TaskFilter::parse(&words.join(" "))?;
+        Ok(TransferRequest { path: PathBuf::from(path), filter, moving, on_duplicate, confirmed })
+    }
+}
+
+// The other list loaded on its own, and the tasks the query picked from it
+pub struct Picked {
+    pub source: TaskManager,
+    pub tasks: Vec<Task>,
+}
+
+// Load the other list without touching it and pick the tasks the filter finds
+pub fn pick(storage: &dyn Storage, filter: &TaskFilter, user: Option<&str>) -> Result<Picked, TaskError> {
+    let mut source = TaskManager::from_state(storage.load()?);
+    if let Some(user) = user {
+        source.set_user(user.to_string());
+    }
+    let tasks = source.find(filter).into_iter().cloned().collect();
+    Ok(Picked { source, tasks })
+}
+
+// How an import went, ids as (there, here)
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct Transfer {
+    pub source: String,
+    pub moved: bool,
+    pub copied: Vec<(u32, u32)>,
+    // Ids here of copies that took a new title because theirs was taken
+    pub renamed: Vec<u32>,
+    // Tasks left behind because a task here has the title, with that task's id
+    pub skipped: Vec<(u32, u32)>,
+    // Tasks this list refused, with why
+    pub failed: Vec<(u32, String)>,
+}
+
+// The list being imported into, and how to save it
+pub trait Destination {
+    fn manager(&mut self) -> &mut TaskManager;
+    fn save(&mut self) -> Result<(), TaskError>;
+}
+
+// "personal" for ~/lists/personal.json
+pub fn source_name(path: &Path) -> String {
+    path.file_stem().map_or_else(|| path.display().to_string(), |stem| stem.to_string_lossy().into_owned())
+}
+
+// Copy the picked tasks in under fresh ids. Copies get new uids, moved tasks
+// keep theirs; parents and related tasks carry over where they came along.
+pub fn copy_into(manager: &mut TaskManager, tasks: &[Task], moving: bool, on_duplicate: OnDuplicate, source: &str) -> Transfer {
+    let mut transfer = Transfer { source: source.to_string(), moved: moving, ..Transfer::default() };
+    let uids: HashMap<&str, String> = tasks
+        .iter()
+        .map(|task| (task.uid.as_str(), if moving { task.uid.clone() } else { new_uid() }))
+        .collect();
+    let mut ids: HashMap<u32, u32> = HashMap::new();
+    for task in tasks {
+        let mut copy = task.clone();
+        copy.uid = uids[task.uid.as_str()].clone();
+        copy.parent = task.parent.as_deref().and_then(|parent| uids.get(parent).cloned());
+        copy.relates_to.clear();
+        copy.key = None;
+        copy.old_keys.clear();
+        if let Some(existing) = manager.find_by_title(&task.title) {
+            match on_duplicate {
+                OnDuplicate::Skip => {
+                    transfer.skipped.push((task.id, existing.id));
+                    continue;
+                }
+                OnDuplicate::Rename => copy.title = format!("{} ({})", task.title, source),
+            }
+        }
+        let renamed = copy.title != task.title;
+        match manager.create_task(copy) {
+            Ok(id) => {
+                ids.insert(task.id, id);
+                transfer.copied.push((task.id, id));
+                if renamed {
+                    transfer.renamed.push(id);
+                }
+            }
+            Err(TaskError::DuplicateTask { existing_id, .. }) => transfer.skipped.push((task.id, existing_id)),
+            Err(e) => transfer.failed.push((task.id, e.to_string())),
+        }
+    }
+    for task in tasks {
+        for other in &task.relates_to {
+            if let (Some(&id), Some(&other)) = (ids.get(&task.id), ids.get(other))
+                && id < other
+            {
+                let _ = manager.relate(id, other);
+            }
+        }
+    }
+    transfer
+}
+
+// Copy the picked tasks into `destination`. A move then saves the destination
+// and only once that worked deletes the tasks from the source and saves it; a
+// failed save takes the copies back out and leaves the source as it was.
+pub fn run(
+    destination: &mut dyn Destination,
+    picked: Picked,
+    source_storage: &dyn Storage,
+    request: &TransferRequest,
+) -> Result<Transfer, TaskError> {
+    let Picked { mut source, tasks } = picked;
+    let name = source_name(&request.path);
+    let before = destination.manager().snapshot();
+    let transfer = copy_into(destination.manager(), &tasks, request.moving, request.on_duplicate, &name);
+    if !request.moving || transfer.copied.is_empty() {
+        return Ok(transfer);
+    }
+    if let Err(e) = destination.save() {
+        destination.manager().restore(before);
+        return Err(e);
+    }
+    for (id, _) in &transfer.copied {
+        source.delete_task(*id)?;
+    }
+    source_storage.save(&source.to_state())?;
+    Ok(transfer)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::{InMemoryStorage, SavedState};
+    use crate::Priority;
+
+    struct List {
+        manager: TaskManager,
+        storage: InMemoryStorage,
+        fail: bool,
+    }
+
+    impl Destination for List {
+        fn manager(&mut self) -> &mut TaskManager {
+            &mut self.manager
+        }
+
+        fn save(&mut self) -> Result<(), TaskError> {
+            if self.fail {
+                return Err(TaskError::Storage("disk full".to_string()));
+            }
+            self.storage.save(&self.manager.to_state())
+        }
+    }
+
+    fn list(titles: &[&str]) -> TaskManager {
+        let mut manager = TaskManager::new();
+        for title in titles {
+            manager.add_task(title.to_string(), String::new(), Priority::Medium).unwrap();
+        }
+        manager
+    }
+
+    // "personal": #1 Dentist, then #2 Taxes and #3 Expense report tagged work and related
+    fn personal() -> InMemoryStorage {
+        let mut manager = list(&["Dentist", "Taxes", "Expense report"]);
+        manager.add_tag_to_task(2, "work".to_string()).unwrap();
+        manager.add_tag_to_task(3, "work".to_string()).unwrap();
+        manager.relate(2, 3).unwrap();
+        let storage = InMemoryStorage::new();
+        storage.save(&manager.to_state()).unwrap();
+        storage
+    }
+
+    fn request(args: &str) -> TransferRequest {
+        let args: Vec<String> = format!("personal.json {}", args).split_whitespace().map(str::to_string).collect();
+        TransferRequest::parse(&args).unwrap()
+    }
+
+    fn titles(manager: &TaskManager) -> Vec<(u32, String)> {
+        let mut tasks: Vec<(u32, String)> = manager.list_tasks().into_iter().map(|task| (task.id, task.title.clone())).collect();
+        tasks.sort();
+        tasks
+    }
+
+    fn import(work: &mut List, source: &InMemoryStorage, args: &str) -> Result<Transfer, TaskError> {
+        let request = request(args);
+        let picked = pick(source, &request.filter, None)?;
+        run(work, picked, source, &request)
+    }
+
+    #[test]
+    fn test_parse_request() {
+        let request = request("tag:work status:pending --move --duplicates rename --yes");
+        assert_eq!(request.path, PathBuf::from("personal.json"));
+        assert_eq!(request.filter, TaskFilter::parse("tag:work status:pending").unwrap());
+        assert!(request.moving && request.confirmed);
+        assert_eq!(request.on_duplicate, OnDuplicate::Rename);
+        assert_eq!(TransferRequest::parse(&[]), Err(ParseError::Usage(IMPORT_USAGE)));
+        let bad: Vec<String> = ["x.json", "--duplicates", "merge"].map(str::to_string).to_vec();
+        assert_eq!(TransferRequest::parse(&bad), Err(ParseError::Usage(IMPORT_USAGE)));
+    }
+
+    #[test]
+    fn test_copy_picks_by_query_and_leaves_the_source() {
+        let source = personal();
+        let mut work = List { manager: list(&["Standup"]), storage: InMemoryStorage::new(), fail: false };
+        let transfer = import(&mut work, &source, "tag:work").unwrap();
+        assert_eq!(transfer.copied, vec![(2, 2), (3, 3)]);
+        assert_eq!(titles(&work.manager), vec![(1, "Standup".into()), (2, "Taxes".into()), (3, "Expense report".into())]);
+        // The link came along; the copies are new tasks with uids of their own
+        assert_eq!(work.manager.get_task(2).unwrap().relates_to, vec![3]);
+        let original = TaskManager::from_state(source.load().unwrap());
+        assert_ne!(work.manager.get_task(2).unwrap().uid, original.get_task(2).unwrap().uid);
+        assert_eq!(original.list_tasks().len(), 3);
+        // Copies wait for the usual save
+        assert_eq!(work.storage.load().unwrap(), SavedState::default());
+    }
+
+    #[test]
+    fn test_duplicate_titles_follow_the_policy() {
+        let source = personal();
+        let mut work = List { manager: list(&["Taxes"]), storage: InMemoryStorage::new(), fail: false };
+        let transfer = import(&mut work, &source, "tag:work").unwrap();
+        assert_eq!((transfer.copied, transfer.skipped), (vec![(3, 2)], vec![(2, 1)]));
+
+        let mut work = List { manager: list(&["Taxes"]), storage: InMemoryStorage::new(), fail: false };
+        let transfer = import(&mut work, &source, "tag:work --duplicates rename").unwrap();
+        assert_eq!((transfer.copied, transfer.renamed), (vec![(2, 2), (3, 3)], vec![2]));
+        assert_eq!(work.manager.get_task(2).unwrap().title, "Taxes (personal)");
+    }
+
+    #[test]
+    fn test_move_saves_here_before_deleting_there() {
+        let source = personal();
+        let mut work = List { manager: list(&["Standup"]), storage: InMemoryStorage::new(), fail: false };
+        let uid = TaskManager::from_state(source.load().unwrap()).get_task(2).unwrap().uid.clone();
+        let transfer = import(&mut work, &source, "tag:work --move").unwrap();
+        assert!(transfer.moved);
+        assert_eq!(titles(&TaskManager::from_state(source.load().unwrap())), vec![(1, "Dentist".into())]);
+        let saved = TaskManager::from_state(work.storage.load().unwrap());
+        assert_eq!(titles(&saved), vec![(1, "Standup".into()), (2, "Taxes".into()), (3, "Expense report".into())]);
+        assert_eq!(saved.get_task(2).unwrap().uid, uid);
+    }
+
+    #[test]
+    fn test_failed_save_leaves_both_lists_as_they_were() {
+        let source = personal();
+        let before = source.load().unwrap();
+        let mut work = List { manager: list(&["Standup"]), storage: InMemoryStorage::new(), fail: true };
+        let result = import(&mut work, &source, "tag:work --move");
+        assert!(matches!(result, Err(TaskError::Storage(reason)) if reason == "disk full"));
+        assert_eq!(source.load().unwrap(), before);
+        assert_eq!(titles(&work.manager), vec![(1, "Standup".into())]);
+    }
+}
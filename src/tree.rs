@@ -0,0 +1,195 @@
+use std::collections::{HashMap, HashSet};
+
+use serde::Serialize;
+
+use crate::msg;
+use crate::render::{pin_marker, status_glyph};
+use crate::{Task, TaskStatus};
+
+// A task and everything filed under it
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TreeNode {
+    pub id: u32,
+    pub title: String,
+    pub status: TaskStatus,
+    pub pinned: bool,
+    // Descendants at every depth, and how many of those are completed
+    pub done: usize,
+    pub total: usize,
+    pub children: Vec<TreeNode>,
+    // The task turned up again below itself; drawn as a stub and not descended into
+    pub cycle: bool,
+}
+
+// The hierarchy under `root`, or under every task whose parent is unset or gone.
+// Tasks caught in a parent cycle have no such root, so each loop left over is
+// started from its lowest id. Children come in id order.
+pub fn build(tasks: &[&Task], root: Option<u32>) -> Vec<TreeNode> {
+    let by_uid: HashMap<&str, &Task> = tasks.iter().map(|task| (task.uid.as_str(), *task)).collect();
+    let mut children: HashMap<u32, Vec<&Task>> = HashMap::new();
+    let mut roots = Vec::new();
+    for task in tasks {
+        match task.parent.as_deref().and_then(|uid| by_uid.get(uid)) {
+            Some(parent) => children.entry(parent.id).or_default().push(task),
+            None => roots.push(*task),
+        }
+    }
+
+    let mut walk = Walk { children: &children, path: Vec::new(), seen: HashSet::new() };
+    if let Some(id) = root {
+        return tasks.iter().filter(|task| task.id == id).map(|task| walk.node(task)).collect();
+    }
+    let mut forest: Vec<TreeNode> = roots.into_iter().map(|task| walk.node(task)).collect();
+    for task in tasks {
+        if !walk.seen.contains(&task.id) {
+            forest.push(walk.node(task));
+        }
+    }
+    forest
+}
+
+struct Walk<'a> {
+    children: &'a HashMap<u32, Vec<&'a Task>>,
+    // Ids from the root down to the node being built
+    path: Vec<u32>,
+    seen: HashSet<u32>,
+}
+
+impl Walk<'_> {
+    fn node(&mut self, task: &Task) -> TreeNode {
+        let mut node = TreeNode {
+            id: task.id,
+            title: task.title.clone(),
+            status: task.status.clone(),
+            pinned: task.pinned,
+            done: 0,
+            total: 0,
+            children: Vec::new(),
+            cycle: self.path.contains(&task.id),
+        };
+        if node.cycle {
+            return node;
+        }
+        self.seen.insert(task.id);
+        self.path.push(task.id);
+        let children = self.children;
+        for child in children.get(&task.id).into_iter().flatten() {
+            let child = self.node(child);
+            if !child.cycle {
+                node.total += 1 + child.total;
+                node.done += child.done + usize::from(child.status == TaskStatus::Completed);
+            }
+            node.children.push(child);
+        }
+        self.path.pop();
+        node
+    }
+}
+
+// Box-drawn outline of the forest, one line per task
+pub fn draw(forest: &[TreeNode]) -> String {
+    let mut lines = Vec::new();
+    for tree in forest {
+        lines.push(label(tree));
+        draw_children(tree, "", &mut lines);
+    }
+    lines.join("\n")
+}
+
+fn draw_children(node: &TreeNode, prefix: &str, lines: &mut Vec<String>) {
+    for (i, child) in node.children.iter().enumerate() {
+        let last = i + 1 == node.children.len();
+        let (branch, rail) = if last { ("└── ", "    ") } else { ("├── ", "│   ") };
+        lines.push(format!("{}{}{}", prefix, branch, label(child)));
+        draw_children(child, &format!("{}{}", prefix, rail), lines);
+    }
+}
+
+fn label(node: &TreeNode) -> String {
+    if node.cycle {
+        return format!("#{} …", node.id);
+    }
+    let mut label = format!("#{} {} {}{}", node.id, status_glyph(&node.status), pin_marker(node.pinned), node.title);
+    if node.total > 0 {
+        label.push(' ');
+        label.push_str(&msg!("tree_rollup", done = node.done, total = node.total));
+    }
+    label
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Priority;
+
+    // Tasks 1..=titles.len(), each filed under the task at `parents[i]` (0 for none)
+    fn hierarchy(titles: &[&str], parents: &[u32]) -> Vec<Task> {
+        let mut tasks: Vec<Task> = titles
+            .iter()
+            .enumerate()
+            .map(|(i, title)| Task::new(i as u32 + 1, title.to_string(), String::new(), Priority::Medium))
+            .collect();
+        for (i, &parent) in parents.iter().enumerate() {
+            if parent > 0 {
+                tasks[i].parent = Some(tasks[parent as usize - 1].uid.clone());
+            }
+        }
+        tasks
+    }
+
+    #[test]
+    fn test_deep_hierarchy_snapshot() {
+        let titles = ["Launch", "Design", "Wireframes", "Logo", "Colours", "Shades", "Tints", "Build", "Ship notes"];
+        let mut tasks = hierarchy(&titles, &[0, 1, 2, 3, 4, 5, 6, 1, 0]);
+        tasks[3].status = TaskStatus::Completed;
+        tasks[6].status = TaskStatus::Completed;
+        tasks[7].status = TaskStatus::InProgress;
+        let refs: Vec<&Task> = tasks.iter().collect();
+
+        let forest = build(&refs, None);
+        assert_eq!((forest[0].done, forest[0].total), (2, 7));
+        assert_eq!(
+            draw(&forest),
+            "\
+#1 ○ Launch (2/7 done)
+├── #2 ○ Design (2/5 done)
+│   └── #3 ○ Wireframes (2/4 done)
+│       └── #4 ● Logo (1/3 done)
+│           └── #5 ○ Colours (1/2 done)
+│               └── #6 ○ Shades (1/1 done)
+│                   └── #7 ● Tints
+└── #8 ◐ Build
+#9 ○ Ship notes"
+        );
+    }
+
+    #[test]
+    fn test_tree_rooted_at_a_task() {
+        let tasks = hierarchy(&["Launch", "Design", "Logo", "Build"], &[0, 1, 2, 1]);
+        let refs: Vec<&Task> = tasks.iter().collect();
+        assert_eq!(draw(&build(&refs, Some(2))), "#2 ○ Design (0/1 done)\n└── #3 ○ Logo");
+        assert!(build(&refs, Some(9)).is_empty());
+    }
+
+    #[test]
+    fn test_parent_cycles_are_cut() {
+        // 1 -> 2 -> 3 -> 1 has no root; 4 hangs off the loop and 5 names a deleted parent
+        let mut tasks = hierarchy(&["A", "B", "C", "D", "E"], &[3, 1, 2, 2, 0]);
+        tasks[4].parent = Some("gone".to_string());
+        let refs: Vec<&Task> = tasks.iter().collect();
+
+        let forest = build(&refs, None);
+        assert_eq!(forest.len(), 2);
+        assert_eq!(
+            draw(&forest),
+            "\
+#5 ○ E
+#1 ○ A (0/3 done)
+└── #2 ○ B (0/2 done)
+    ├── #3 ○ C
+    │   └── #1 …
+    └── #4 ○ D"
+        );
+        assert!(forest[1].children[0].children[0].children[0].cycle);
+    }
+}
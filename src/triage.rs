@@ -0,0 +1,147 @@
+use std::str::FromStr;
+
+use chrono::NaiveDate;
+
+use crate::command::ParseError;
+use crate::dates::DueSpec;
+use crate::{Priority, TaskEdit, TaskError, TaskManager};
+
+// What to do with one inbox task
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Choice {
+    Triage,
+    // Leave it in the inbox for next time
+    Skip,
+    Delete,
+    Quit,
+}
+
+impl Choice {
+    // Enter alone triages, the default when sorting through the inbox
+    pub fn parse(input: &str) -> Option<Choice> {
+        match input.trim().to_lowercase().as_str() {
+            "" | "t" | "triage" => Some(Choice::Triage),
+            "s" | "skip" => Some(Choice::Skip),
+            "d" | "delete" => Some(Choice::Delete),
+            "q" | "quit" => Some(Choice::Quit),
+            _ => None,
+        }
+    }
+}
+
+// The answers given for one task; a blank answer leaves that field as it is
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Answers {
+    pub priority: String,
+    pub due: String,
+    pub tags: String,
+    pub project: String,
+}
+
+// The changes the answers ask for, checked before any of them is made
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Triage {
+    pub priority: Option<Priority>,
+    pub due: Option<NaiveDate>,
+    pub tags: Vec<String>,
+    // Id of the task to file this one under
+    pub parent: Option<u32>,
+}
+
+impl Triage {
+    pub fn from_answers(answers: &Answers, today: NaiveDate, holidays: &[NaiveDate]) -> Result<Triage, ParseError> {
+        let priority = match answers.priority.trim() {
+            "" => None,
+            text => Some(Priority::from_str(text).map_err(|_| ParseError::InvalidPriority(text.to_string()))?),
+        };
+        let due = match answers.due.trim() {
+            "" => None,
+            text => {
+                let spec = DueSpec::parse(text).ok_or_else(|| ParseError::InvalidDate(text.to_string()))?;
+                Some(spec.resolve(today, holidays).map_err(|_| ParseError::InvalidDate(text.to_string()))?)
+            }
+        };
+        let parent = match answers.project.trim() {
+            "" => None,
+            text => Some(text.parse::<u32>().map_err(|_| ParseError::InvalidId(text.to_string()))?),
+        };
+        let tags = answers.tags.split_whitespace().map(String::from).collect();
+        Ok(Triage { priority, due, tags, parent })
+    }
+
+    // Make the changes and take the task out of the inbox
+    pub fn apply(self, manager: &mut TaskManager, id: u32) -> Result<(), TaskError> {
+        if let Some(parent) = self.parent {
+            manager.set_parent(id, Some(parent))?;
+        }
+        if self.priority.is_some() || self.due.is_some() {
+            manager.apply_edit(id, TaskEdit { priority: self.priority, due: self.due, ..TaskEdit::default() })?;
+        }
+        for tag in self.tags {
+            manager.add_tag_to_task(id, tag)?;
+        }
+        manager.mark_triaged(id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Task;
+
+    fn answers(priority: &str, due: &str, tags: &str, project: &str) -> Answers {
+        Answers { priority: priority.into(), due: due.into(), tags: tags.into(), project: project.into() }
+    }
+
+    #[test]
+    fn test_choices() {
+        assert_eq!(Choice::parse(""), Some(Choice::Triage));
+        assert_eq!(Choice::parse("S"), Some(Choice::Skip));
+        assert_eq!(Choice::parse("delete"), Some(Choice::Delete));
+        assert_eq!(Choice::parse("q"), Some(Choice::Quit));
+        assert_eq!(Choice::parse("maybe"), None);
+    }
+
+    #[test]
+    fn test_answers_become_one_triage() {
+        let today = NaiveDate::from_ymd_opt(2024, 7, 1).unwrap();
+        assert_eq!(Triage::from_answers(&Answers::default(), today, &[]), Ok(Triage::default()));
+        assert_eq!(
+            Triage::from_answers(&answers("high", "2 days", "home errands", "4"), today, &[]),
+            Ok(Triage {
+                priority: Some(Priority::High),
+                due: NaiveDate::from_ymd_opt(2024, 7, 3),
+                tags: vec!["home".to_string(), "errands".to_string()],
+                parent: Some(4),
+            })
+        );
+        assert_eq!(
+            Triage::from_answers(&answers("urgent", "", "", ""), today, &[]),
+            Err(ParseError::InvalidPriority("urgent".to_string()))
+        );
+        assert_eq!(
+            Triage::from_answers(&answers("", "someday", "", ""), today, &[]),
+            Err(ParseError::InvalidDate("someday".to_string()))
+        );
+        assert_eq!(Triage::from_answers(&answers("", "", "", "x"), today, &[]), Err(ParseError::InvalidId("x".to_string())));
+    }
+
+    #[test]
+    fn test_apply_takes_the_task_out_of_the_inbox() {
+        let mut manager = TaskManager::new();
+        let project = manager.add_task("Move house".to_string(), String::new(), Priority::Medium).unwrap();
+        let mut quick = Task::new(0, "Book van".to_string(), String::new(), Priority::Medium);
+        quick.triaged = false;
+        let id = manager.create_task(quick).unwrap();
+        assert_eq!(manager.inbox().len(), 1);
+
+        let triage = Triage { priority: Some(Priority::High), tags: vec!["move".to_string()], parent: Some(project), ..Triage::default() };
+        triage.apply(&mut manager, id).unwrap();
+        let task = manager.get_task(id).unwrap();
+        assert!(task.triaged);
+        assert_eq!(task.priority, Priority::High);
+        assert_eq!(task.tags, vec!["move".into()]);
+        assert_eq!(task.parent.as_deref(), Some(manager.get_task(project).unwrap().uid.as_str()));
+        assert!(manager.inbox().is_empty());
+    }
+}
@@ -0,0 +1,495 @@
+#[cfg(unix)]
+use std::io::{self, Write};
+#[cfg(unix)]
+use std::sync::{Mutex, Once};
+
+use crate::board::{fit, pad};
+use crate::command::Command;
+use crate::msg;
+use crate::render::{DisplayMode, format_task};
+use crate::{Priority, TaskFilter, TaskManager, TaskStatus};
+
+// A key as the list reacts to it
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Key {
+    Char(char),
+    Enter,
+    Esc,
+    Backspace,
+    Up,
+    Down,
+    // Ctrl+C, which raw mode delivers as a byte
+    Interrupt,
+}
+
+// What the bottom line is taking input for
+#[derive(Debug, Clone, PartialEq)]
+enum Mode {
+    Browse,
+    Filter,
+    Tag,
+    ConfirmDelete(u32),
+}
+
+// What the caller should do after a key
+#[derive(Debug, Clone, PartialEq)]
+pub enum Action {
+    Stay,
+    Quit,
+    // `line` is the command as it would be typed, for the undo log
+    Run { line: String, command: Command },
+    Priority { line: String, id: u32, priority: Priority },
+}
+
+// The list, the selection and whatever is being typed; drawing is `frame`'s job
+#[derive(Debug, Clone)]
+pub struct TuiState {
+    filter: String,
+    ids: Vec<u32>,
+    selected: usize,
+    detail: bool,
+    mode: Mode,
+    input: String,
+    // The outcome of the last change, until the next key
+    message: Option<String>,
+}
+
+impl TuiState {
+    pub fn new(manager: &TaskManager) -> Self {
+        let mut state = TuiState {
+            filter: String::new(),
+            ids: Vec::new(),
+            selected: 0,
+            detail: false,
+            mode: Mode::Browse,
+            input: String::new(),
+            message: None,
+        };
+        state.refresh(manager);
+        state
+    }
+
+    // Re-read the list after a change, keeping the same task selected while it is
+    // still there
+    pub fn refresh(&mut self, manager: &TaskManager) {
+        let current = self.selected_id();
+        self.ids = if self.filter.trim().is_empty() {
+            manager.list_tasks().iter().map(|task| task.id).collect()
+        } else {
+            manager.find(&TaskFilter::query(&self.filter)).iter().map(|task| task.id).collect()
+        };
+        self.selected = match current.and_then(|id| self.ids.iter().position(|&other| other == id)) {
+            Some(index) => index,
+            None => self.selected.min(self.ids.len().saturating_sub(1)),
+        };
+    }
+
+    pub fn selected_id(&self) -> Option<u32> {
+        self.ids.get(self.selected).copied()
+    }
+
+    pub fn report(&mut self, message: String) {
+        self.message = Some(message);
+    }
+
+    pub fn handle_key(&mut self, key: Key, manager: &TaskManager) -> Action {
+        self.message = None;
+        if key == Key::Interrupt {
+            return Action::Quit;
+        }
+        match self.mode.clone() {
+            Mode::Browse => self.browse(key, manager),
+            Mode::Filter => {
+                match key {
+                    Key::Enter => self.mode = Mode::Browse,
+                    Key::Esc => {
+                        self.filter.clear();
+                        self.mode = Mode::Browse;
+                    }
+                    key => {
+                        edit_line(&mut self.filter, key);
+                    }
+                }
+                self.refresh(manager);
+                Action::Stay
+            }
+            Mode::Tag => {
+                let Some(id) = self.selected_id() else {
+                    self.mode = Mode::Browse;
+                    return Action::Stay;
+                };
+                match key {
+                    Key::Enter => {
+                        self.mode = Mode::Browse;
+                        let tag = std::mem::take(&mut self.input).trim().to_string();
+                        if !tag.is_empty() {
+                            return Action::Run { line: format!("tag {} {}", id, tag), command: Command::Tag { id, tag } };
+                        }
+                    }
+                    Key::Esc => {
+                        self.input.clear();
+                        self.mode = Mode::Browse;
+                    }
+                    key => {
+                        edit_line(&mut self.input, key);
+                    }
+                }
+                Action::Stay
+            }
+            Mode::ConfirmDelete(id) => {
+                self.mode = Mode::Browse;
+                match key {
+                    Key::Char('y') | Key::Char('Y') => {
+                        Action::Run { line: format!("delete {}", id), command: Command::Delete { id } }
+                    }
+                    _ => Action::Stay,
+                }
+            }
+        }
+    }
+
+    fn browse(&mut self, key: Key, manager: &TaskManager) -> Action {
+        match key {
+            Key::Char('q') => return Action::Quit,
+            Key::Char('j') | Key::Down => self.selected = (self.selected + 1).min(self.ids.len().saturating_sub(1)),
+            Key::Char('k') | Key::Up => self.selected = self.selected.saturating_sub(1),
+            Key::Enter => self.detail = !self.detail,
+            Key::Esc => self.detail = false,
+            Key::Char('/') => self.mode = Mode::Filter,
+            _ => {}
+        }
+        let Some(task) = self.selected_id().and_then(|id| manager.get_task(id).ok()) else { return Action::Stay };
+        let id = task.id;
+        match key {
+            Key::Char('d') => Action::Run {
+                line: format!("update {} completed", id),
+                command: Command::Update { id, status: TaskStatus::Completed, note: None },
+            },
+            Key::Char('x') => {
+                self.mode = Mode::ConfirmDelete(id);
+                self.message = Some(msg!("tui_confirm_delete", id = id, title = task.title));
+                Action::Stay
+            }
+            Key::Char('p') => {
+                let priority = next_priority(&task.priority);
+                Action::Priority { line: format!("priority {} {}", id, priority.to_string().to_lowercase()), id, priority }
+            }
+            Key::Char('t') => {
+                self.input.clear();
+                self.mode = Mode::Tag;
+                Action::Stay
+            }
+            _ => Action::Stay,
+        }
+    }
+}
+
+fn edit_line(line: &mut String, key: Key) {
+    match key {
+        Key::Char(c) if !c.is_control() => line.push(c),
+        Key::Backspace => {
+            line.pop();
+        }
+        _ => {}
+    }
+}
+
+// `p` goes round the levels, back to Low after Critical
+fn next_priority(priority: &Priority) -> Priority {
+    match priority {
+        Priority::Low => Priority::Medium,
+        Priority::Medium => Priority::High,
+        Priority::High => Priority::Critical,
+        Priority::Critical => Priority::Low,
+    }
+}
+
+// One screen's worth of lines, each exactly `width` wide, and the row shown
+// highlighted
+#[derive(Debug, Clone, PartialEq)]
+pub struct Frame {
+    pub lines: Vec<String>,
+    pub highlight: Option<usize>,
+}
+
+// The list on top, the detail pane below it when open, then the input line and
+// the status bar
+pub fn frame(state: &TuiState, manager: &TaskManager, width: usize, height: usize) -> Frame {
+    let body = height.saturating_sub(2);
+    let task = state.selected_id().and_then(|id| manager.get_task(id).ok());
+    let detail = task.filter(|_| state.detail);
+    let rows = if detail.is_some() { body.div_ceil(2) } else { body };
+
+    let mut lines = Vec::with_capacity(height);
+    let mut highlight = None;
+    let offset = (state.selected + 1).saturating_sub(rows);
+    if state.ids.is_empty() && rows > 0 {
+        lines.push(if state.filter.is_empty() { msg!("tui_empty") } else { msg!("tui_no_match") });
+    }
+    for (index, id) in state.ids.iter().enumerate().skip(offset).take(rows) {
+        let Ok(task) = manager.get_task(*id) else { continue };
+        if index == state.selected {
+            highlight = Some(lines.len());
+        }
+        let marker = if index == state.selected { "> " } else { "  " };
+        lines.push(format!("{}{}", marker, format_task(task, DisplayMode::Compact)));
+    }
+    lines.resize(rows, String::new());
+    if let Some(task) = detail {
+        lines.push("─".repeat(width));
+        lines.extend(format_task(task, DisplayMode::Normal).lines().map(str::to_string));
+        lines.resize(body, String::new());
+    }
+
+    lines.push(match &state.mode {
+        Mode::Filter => format!("/{}", state.filter),
+        Mode::Tag => format!("{}{}", msg!("tui_tag_prompt"), state.input),
+        Mode::Browse | Mode::ConfirmDelete(_) => state.message.clone().unwrap_or_else(|| msg!("tui_keys")),
+    });
+    let (total, completed, _, _) = manager.get_statistics();
+    let mut status = msg!("tui_status", open = total - completed, done = completed, shown = state.ids.len());
+    if !state.filter.is_empty() {
+        status = format!("{} · {}", status, msg!("tui_status_filter", filter = state.filter));
+    }
+    lines.push(status);
+
+    let lines = lines.iter().skip(lines.len().saturating_sub(height)).map(|line| pad(&fit(line, width), width)).collect();
+    Frame { lines, highlight }
+}
+
+// Bytes read from the terminal in raw mode, as keys; an escape not followed by
+// an arrow key's sequence is Esc on its own
+pub fn parse_keys(bytes: &[u8]) -> Vec<Key> {
+    let mut keys = Vec::new();
+    let mut rest = bytes;
+    while let Some(&byte) = rest.first() {
+        let (key, used) = match (byte, rest.get(1), rest.get(2)) {
+            (0x1b, Some(b'['), Some(b'A')) => (Some(Key::Up), 3),
+            (0x1b, Some(b'['), Some(b'B')) => (Some(Key::Down), 3),
+            (0x1b, _, _) => (Some(Key::Esc), 1),
+            (b'\r' | b'\n', _, _) => (Some(Key::Enter), 1),
+            (0x7f | 0x08, _, _) => (Some(Key::Backspace), 1),
+            (0x03, _, _) => (Some(Key::Interrupt), 1),
+            _ => {
+                let text = (1..=rest.len().min(4)).find_map(|len| std::str::from_utf8(&rest[..len]).ok());
+                match text.and_then(|text| text.chars().next()) {
+                    Some(c) => (Some(Key::Char(c)), c.len_utf8()),
+                    None => (None, 1),
+                }
+            }
+        };
+        keys.extend(key);
+        rest = &rest[used..];
+    }
+    keys
+}
+
+// The terminal settings from before raw mode, taken by whichever restores them
+// first: the screen going away or the panic hook
+#[cfg(unix)]
+static SAVED: Mutex<Option<libc::termios>> = Mutex::new(None);
+
+// The terminal in raw mode on the alternate screen, until dropped
+#[cfg(unix)]
+pub struct Screen;
+
+#[cfg(unix)]
+impl Screen {
+    pub fn open() -> io::Result<Screen> {
+        // SAFETY: termios is plain data; tcgetattr fills it in before it is read
+        let mut saved: libc::termios = unsafe { std::mem::zeroed() };
+        if unsafe { libc::tcgetattr(libc::STDIN_FILENO, &mut saved) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let mut raw = saved;
+        // SAFETY: both calls only read and write the termios they are given
+        unsafe { libc::cfmakeraw(&mut raw) };
+        if unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &raw) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        *SAVED.lock().unwrap_or_else(|e| e.into_inner()) = Some(saved);
+        restore_on_panic();
+        let mut out = io::stdout();
+        write!(out, "\x1b[?1049h\x1b[?25l")?;
+        out.flush()?;
+        Ok(Screen)
+    }
+
+    // (width, height), 80x24 when the terminal will not say
+    pub fn size(&self) -> (usize, usize) {
+        let mut size = libc::winsize { ws_row: 0, ws_col: 0, ws_xpixel: 0, ws_ypixel: 0 };
+        // SAFETY: TIOCGWINSZ only writes a winsize through the pointer it is given
+        let ok = unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut size) } == 0;
+        match ok && size.ws_col > 0 && size.ws_row > 0 {
+            true => (usize::from(size.ws_col), usize::from(size.ws_row)),
+            false => (80, 24),
+        }
+    }
+
+    pub fn draw(&self, frame: &Frame) -> io::Result<()> {
+        let mut out = io::stdout().lock();
+        write!(out, "\x1b[H")?;
+        for (row, line) in frame.lines.iter().enumerate() {
+            if row > 0 {
+                write!(out, "\r\n")?;
+            }
+            match frame.highlight == Some(row) {
+                true => write!(out, "\x1b[7m{}\x1b[0m", line)?,
+                false => write!(out, "{}", line)?,
+            }
+        }
+        write!(out, "\x1b[J")?;
+        out.flush()
+    }
+
+    // Blocks until at least one byte arrives
+    pub fn read_keys(&self) -> io::Result<Vec<Key>> {
+        let mut buf = [0u8; 64];
+        // SAFETY: read writes at most buf.len() bytes into buf
+        let read = unsafe { libc::read(libc::STDIN_FILENO, buf.as_mut_ptr().cast(), buf.len()) };
+        if read < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(parse_keys(&buf[..read as usize]))
+    }
+}
+
+#[cfg(unix)]
+impl Drop for Screen {
+    fn drop(&mut self) {
+        restore();
+    }
+}
+
+#[cfg(unix)]
+fn restore() {
+    let Some(saved) = SAVED.lock().unwrap_or_else(|e| e.into_inner()).take() else { return };
+    // SAFETY: puts back settings tcgetattr gave us
+    unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &saved) };
+    let mut out = io::stdout();
+    let _ = write!(out, "\x1b[?25h\x1b[?1049l");
+    let _ = out.flush();
+}
+
+// Installed once: a panic puts the terminal back before its message is printed
+#[cfg(unix)]
+fn restore_on_panic() {
+    static INSTALL: Once = Once::new();
+    INSTALL.call_once(|| {
+        let previous = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |info| {
+            restore();
+            previous(info);
+        }));
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::TaskEdit;
+
+    fn manager() -> TaskManager {
+        let mut manager = TaskManager::new();
+        for title in ["Fix login", "Login page copy", "Invoices", "Backup server"] {
+            manager.add_task(title.to_string(), String::new(), Priority::Low).unwrap();
+        }
+        manager.update_task_status(4, TaskStatus::Completed).unwrap();
+        manager
+    }
+
+    fn keys(state: &mut TuiState, manager: &TaskManager, text: &str) -> Vec<Action> {
+        parse_keys(text.as_bytes()).into_iter().map(|key| state.handle_key(key, manager)).collect()
+    }
+
+    #[test]
+    fn test_selection_stays_on_the_list() {
+        let manager = manager();
+        let mut state = TuiState::new(&manager);
+        keys(&mut state, &manager, "k");
+        assert_eq!(state.selected_id(), Some(1));
+        keys(&mut state, &manager, "jjjjjj");
+        assert_eq!(state.selected_id(), Some(4));
+        keys(&mut state, &manager, "\x1b[A\x1b[A");
+        assert_eq!(state.selected_id(), Some(2));
+
+        // Filtering keeps the selected task when it still matches
+        keys(&mut state, &manager, "/login\r");
+        assert_eq!((state.ids.clone(), state.selected_id()), (vec![1, 2], Some(2)));
+        keys(&mut state, &manager, "/\x7f\x7f\x7f\x7f\x7finv");
+        assert_eq!((state.ids.clone(), state.selected_id()), (vec![3], Some(3)));
+        keys(&mut state, &manager, "\x1b");
+        assert_eq!((state.filter.as_str(), state.ids.len(), state.mode.clone()), ("", 4, Mode::Browse));
+        keys(&mut state, &manager, "/nothing\r");
+        assert_eq!(state.selected_id(), None);
+        assert_eq!(keys(&mut state, &manager, "dxpt"), vec![Action::Stay; 4]);
+    }
+
+    #[test]
+    fn test_keys_become_commands() {
+        let mut manager = manager();
+        let mut state = TuiState::new(&manager);
+        let done = keys(&mut state, &manager, "jd");
+        assert_eq!(done[1], Action::Run {
+            line: "update 2 completed".to_string(),
+            command: Command::Update { id: 2, status: TaskStatus::Completed, note: None },
+        });
+        let bumped = keys(&mut state, &manager, "p");
+        let Action::Priority { id, priority, .. } = &bumped[0] else { panic!("unexpected action: {:?}", bumped) };
+        manager.apply_edit(*id, TaskEdit { priority: Some(priority.clone()), ..TaskEdit::default() }).unwrap();
+        assert_eq!(manager.get_task(2).unwrap().priority, Priority::Medium);
+        assert_eq!(next_priority(&Priority::Critical), Priority::Low);
+
+        let tagged = keys(&mut state, &manager, "t urgent \r");
+        assert_eq!(tagged.last(), Some(&Action::Run {
+            line: "tag 2 urgent".to_string(),
+            command: Command::Tag { id: 2, tag: "urgent".to_string() },
+        }));
+
+        // Deleting asks first; anything but y leaves the task alone
+        assert_eq!(keys(&mut state, &manager, "xn"), vec![Action::Stay; 2]);
+        assert_eq!(keys(&mut state, &manager, "xy")[1], Action::Run {
+            line: "delete 2".to_string(),
+            command: Command::Delete { id: 2 },
+        });
+        assert_eq!(keys(&mut state, &manager, "\x03"), vec![Action::Quit]);
+
+        // After a change the list is read again and the selection moves on
+        manager.delete_task(2).unwrap();
+        state.refresh(&manager);
+        assert_eq!(state.selected_id(), Some(3));
+    }
+
+    #[test]
+    fn test_frame_shows_the_list_detail_and_status() {
+        let manager = manager();
+        let mut state = TuiState::new(&manager);
+        keys(&mut state, &manager, "/login\rj");
+        let screen = frame(&state, &manager, 44, 6);
+        assert_eq!(screen.lines.len(), 6);
+        assert!(screen.lines.iter().all(|line| line.chars().count() == 44));
+        assert_eq!(screen.highlight, Some(1));
+        assert!(screen.lines[1].starts_with("> #2"));
+        assert_eq!(screen.lines[5].trim_end(), "3 open · 1 done · 2 shown · filter: login");
+
+        keys(&mut state, &manager, "\rx");
+        let screen = frame(&state, &manager, 60, 8);
+        // Half the body for the list, the rest for the detail pane
+        assert_eq!(screen.lines[3], "─".repeat(60));
+        assert!(screen.lines[4].contains("Login page copy"));
+        assert!(screen.lines[6].starts_with("Delete #2 Login page copy? (y/n)"));
+    }
+
+    #[test]
+    fn test_parse_keys() {
+        assert_eq!(parse_keys(b"j\x1b[B\x1b\r\x7f"), vec![
+            Key::Char('j'),
+            Key::Down,
+            Key::Esc,
+            Key::Enter,
+            Key::Backspace
+        ]);
+        assert_eq!(parse_keys("té".as_bytes()), vec![Key::Char('t'), Key::Char('é')]);
+        assert_eq!(parse_keys(&[0xff, b'q']), vec![Key::Char('q')]);
+    }
+}
@@ -0,0 +1,157 @@
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::Task;
+use crate::audit::AuditEntry;
+
+// Steps kept, in memory and in the data file; older ones can no longer be undone
+pub const UNDO_LIMIT: usize = 50;
+
+// How to take back what a step did to one task: put `before` back, or remove the
+// task when the step created it. `after` is what the step left at the id, None
+// when it deleted the task; undo checks it first, so a task changed since, here
+// or by editing the file, is not overwritten.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Inverse {
+    pub id: u32,
+    pub before: Option<Task>,
+    pub after: Option<Task>,
+}
+
+// Everything one command changed, taken back together by `undo`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UndoEntry {
+    // The command as typed
+    pub label: String,
+    pub started: DateTime<Utc>,
+    pub finished: DateTime<Utc>,
+    pub changes: Vec<Inverse>,
+}
+
+impl UndoEntry {
+    // Whether the audit entry records part of this step
+    pub fn covers(&self, entry: &AuditEntry) -> bool {
+        entry.task_id.is_some_and(|id| self.changes.iter().any(|change| change.id == id))
+            && (self.started..=self.finished).contains(&entry.timestamp)
+    }
+}
+
+// Finished steps, oldest first, and the one being recorded
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct UndoStack {
+    entries: Vec<UndoEntry>,
+    open: Option<OpenStep>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct OpenStep {
+    label: String,
+    started: DateTime<Utc>,
+    // Each task the step touched as it was before the first change
+    before: BTreeMap<u32, Option<Task>>,
+}
+
+impl UndoStack {
+    pub fn new(mut entries: Vec<UndoEntry>) -> Self {
+        if entries.len() > UNDO_LIMIT {
+            entries.drain(..entries.len() - UNDO_LIMIT);
+        }
+        UndoStack { entries, open: None }
+    }
+
+    pub fn entries(&self) -> &[UndoEntry] {
+        &self.entries
+    }
+
+    pub fn begin(&mut self, label: &str) {
+        self.open = Some(OpenStep { label: label.to_string(), started: Utc::now(), before: BTreeMap::new() });
+    }
+
+    // Whether `id` still needs its before-image taken in the open step
+    pub fn wants(&self, id: u32) -> bool {
+        self.open.as_ref().is_some_and(|step| !step.before.contains_key(&id))
+    }
+
+    pub fn remember(&mut self, id: u32, before: Option<Task>) {
+        if let Some(step) = &mut self.open {
+            step.before.entry(id).or_insert(before);
+        }
+    }
+
+    // Close the open step against the tasks as they are now. Tasks it left as
+    // they were are dropped, and so is a step that changed nothing.
+    pub fn finish(&mut self, tasks: &BTreeMap<u32, Task>) {
+        let Some(step) = self.open.take() else { return };
+        let changes: Vec<Inverse> = step
+            .before
+            .into_iter()
+            .filter(|(id, before)| tasks.get(id) != before.as_ref())
+            .map(|(id, before)| Inverse { id, before, after: tasks.get(&id).cloned() })
+            .collect();
+        if changes.is_empty() {
+            return;
+        }
+        self.entries.push(UndoEntry { label: step.label, started: step.started, finished: Utc::now(), changes });
+        if self.entries.len() > UNDO_LIMIT {
+            self.entries.remove(0);
+        }
+    }
+
+    pub fn last(&self) -> Option<&UndoEntry> {
+        self.entries.last()
+    }
+
+    pub fn pop(&mut self) -> Option<UndoEntry> {
+        self.entries.pop()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Priority;
+
+    #[test]
+    fn test_steps_keep_first_before_image_and_drop_no_ops() {
+        let mut tasks = BTreeMap::new();
+        let task = Task::new(1, "Write report".to_string(), String::new(), Priority::Low);
+        tasks.insert(1, task.clone());
+        let mut stack = UndoStack::default();
+
+        stack.begin("tag 1 work");
+        assert!(stack.wants(1));
+        stack.remember(1, Some(task.clone()));
+        assert!(!stack.wants(1));
+        stack.finish(&tasks);
+        assert!(stack.entries().is_empty(), "nothing changed");
+
+        stack.begin("update 1 completed");
+        stack.remember(1, Some(task.clone()));
+        tasks.get_mut(&1).unwrap().title = "Renamed".to_string();
+        stack.remember(1, tasks.get(&1).cloned());
+        stack.remember(2, None);
+        tasks.insert(2, Task::new(2, "New".to_string(), String::new(), Priority::Low));
+        stack.finish(&tasks);
+        let entry = stack.last().unwrap();
+        assert_eq!(entry.label, "update 1 completed");
+        assert_eq!(entry.changes[0], Inverse { id: 1, before: Some(task), after: tasks.get(&1).cloned() });
+        assert_eq!((entry.changes[1].before.as_ref(), entry.changes[1].after.as_ref()), (None, tasks.get(&2)));
+    }
+
+    #[test]
+    fn test_stack_keeps_the_newest_steps() {
+        let mut tasks = BTreeMap::new();
+        let mut stack = UndoStack::default();
+        for id in 1..=UNDO_LIMIT as u32 + 5 {
+            stack.begin(&format!("add {}", id));
+            stack.remember(id, None);
+            tasks.insert(id, Task::new(id, format!("Task {}", id), String::new(), Priority::Low));
+            stack.finish(&tasks);
+        }
+        assert_eq!(stack.entries().len(), UNDO_LIMIT);
+        assert_eq!(stack.entries()[0].label, "add 6");
+        assert_eq!(UndoStack::new(stack.entries().to_vec()).entries().len(), UNDO_LIMIT);
+    }
+}
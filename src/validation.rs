@@ -0,0 +1,326 @@
+use unicode_width::UnicodeWidthChar;
+
+use crate::config::Config;
+use crate::{Priority, Task, TaskStatus};
+
+// What a rule may look at besides the task being checked
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ValidationContext {
+    // Tasks not yet completed, excluding the one being checked
+    pub open_tasks: usize,
+}
+
+// A guardrail checked before a task is created or changed
+pub trait ValidationRule: Send + Sync {
+    fn check(&self, task: &Task, context: &ValidationContext) -> Result<(), String>;
+}
+
+// Every task needs at least one tag
+pub struct RequireTag;
+
+impl ValidationRule for RequireTag {
+    fn check(&self, task: &Task, _: &ValidationContext) -> Result<(), String> {
+        if task.tags.is_empty() {
+            Err("a task needs at least one tag".to_string())
+        } else {
+            Ok(())
+        }
+    }
+}
+
+// Tasks at or above this priority need a due date
+pub struct RequireDueForPriority(pub Priority);
+
+impl ValidationRule for RequireDueForPriority {
+    fn check(&self, task: &Task, _: &ValidationContext) -> Result<(), String> {
+        if task.priority >= self.0 && task.due.is_none() {
+            Err(format!("{} priority tasks need a due date", task.priority))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+// No more than this many pending or in-progress tasks at once
+pub struct MaxOpenTasks(pub usize);
+
+impl ValidationRule for MaxOpenTasks {
+    fn check(&self, task: &Task, context: &ValidationContext) -> Result<(), String> {
+        let open = context.open_tasks + usize::from(task.status != TaskStatus::Completed);
+        if open > self.0 {
+            Err(format!("at most {} open tasks are allowed", self.0))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+// What `add` insists on before creating a task. Unlike the rules these are not
+// checked on later edits, imports or `quick` captures.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct RequiredFields {
+    pub due_date: bool,
+    pub tags: bool,
+    pub description: bool,
+}
+
+impl RequiredFields {
+    // One message per required field the task lacks
+    pub fn missing(&self, task: &Task) -> Vec<String> {
+        let mut missing = Vec::new();
+        if self.description && task.description.trim().is_empty() {
+            missing.push("a description is required".to_string());
+        }
+        if self.due_date && task.due.is_none() {
+            missing.push("a due date is required".to_string());
+        }
+        if self.tags && task.tags.is_empty() {
+            missing.push("at least one tag is required".to_string());
+        }
+        missing
+    }
+}
+
+// `[limits]`: how big one task may get. Unlike the rules these can be met by
+// cutting the task down, which `--truncate` does instead of refusing it.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Limits {
+    // Counted in characters, not bytes
+    pub description_chars: Option<usize>,
+    // Each line of the description is a note, as `note` appends them
+    pub notes_per_task: Option<usize>,
+    pub tags_per_task: Option<usize>,
+}
+
+// Marks where a description was cut
+pub const ELLIPSIS: char = '…';
+
+const ZWJ: char = '\u{200D}';
+
+pub fn note_count(description: &str) -> usize {
+    description.lines().count()
+}
+
+impl Limits {
+    pub fn is_unlimited(&self) -> bool {
+        *self == Limits::default()
+    }
+
+    // One message per limit the task is over, naming the config key
+    pub fn check(&self, task: &Task) -> Vec<String> {
+        let mut over = Vec::new();
+        let notes = note_count(&task.description);
+        if let Some(limit) = self.notes_per_task.filter(|&limit| notes > limit) {
+            over.push(format!("the task has {} notes; limits.notes_per_task allows {}", notes, limit));
+        }
+        let chars = task.description.chars().count();
+        if let Some(limit) = self.description_chars.filter(|&limit| chars > limit) {
+            over.push(format!("the description has {} characters; limits.description_chars allows {}", chars, limit));
+        }
+        if let Some(limit) = self.tags_per_task.filter(|&limit| task.tags.len() > limit) {
+            over.push(too_many_tags(task.tags.len(), limit));
+        }
+        over
+    }
+
+    // Why a task that has `tags` already cannot take another
+    pub fn extra_tag(&self, tags: usize) -> Option<String> {
+        self.tags_per_task.filter(|&limit| tags >= limit).map(|limit| too_many_tags(tags + 1, limit))
+    }
+
+    // Cut the task down to fit: the first notes and tags are kept and the
+    // description ends in an ellipsis. Returns what was cut, one line per limit.
+    pub fn truncate(&self, task: &mut Task) -> Vec<String> {
+        let mut cut = Vec::new();
+        let notes = note_count(&task.description);
+        if let Some(limit) = self.notes_per_task.filter(|&limit| notes > limit) {
+            task.description = task.description.lines().take(limit).collect::<Vec<_>>().join("\n");
+            cut.push(format!("kept the first {} of {} notes (limits.notes_per_task)", limit, notes));
+        }
+        let chars = task.description.chars().count();
+        if let Some(limit) = self.description_chars.filter(|&limit| chars > limit) {
+            task.description = truncate_text(&task.description, limit);
+            cut.push(format!("cut the description from {} to {} characters (limits.description_chars)", chars, limit));
+        }
+        if let Some(limit) = self.tags_per_task.filter(|&limit| task.tags.len() > limit) {
+            let dropped: Vec<String> = task.tags.split_off(limit).iter().map(|tag| tag.to_string()).collect();
+            cut.push(format!("dropped tags {} (limits.tags_per_task)", dropped.join(", ")));
+        }
+        cut
+    }
+}
+
+fn too_many_tags(tags: usize, limit: usize) -> String {
+    format!("the task has {} tags; limits.tags_per_task allows {}", tags, limit)
+}
+
+// Marks, variation selectors and emoji modifiers that belong to the character before them
+fn extends(c: char) -> bool {
+    matches!(c, ZWJ | '\u{1F3FB}'..='\u{1F3FF}' | '\u{E0020}'..='\u{E007F}')
+        || (!c.is_control() && UnicodeWidthChar::width(c) == Some(0))
+}
+
+fn is_regional_indicator(c: char) -> bool {
+    ('\u{1F1E6}'..='\u{1F1FF}').contains(&c)
+}
+
+// Whether cutting before `chars[at]` leaves every visible character whole
+fn is_boundary(chars: &[char], at: usize) -> bool {
+    let (before, after) = (chars[at - 1], chars[at]);
+    if before == ZWJ || extends(after) {
+        return false;
+    }
+    // Flags are pairs of regional indicators
+    if is_regional_indicator(after) {
+        return chars[..at].iter().rev().take_while(|c| is_regional_indicator(**c)).count() % 2 == 0;
+    }
+    true
+}
+
+// At most `max` characters, ellipsis included. The cut never falls inside a
+// character as the reader sees it: accents, joined emoji and flags stay whole
+// or go together.
+pub fn truncate_text(text: &str, max: usize) -> String {
+    let chars: Vec<char> = text.chars().collect();
+    if chars.len() <= max {
+        return text.to_string();
+    }
+    if max == 0 {
+        return String::new();
+    }
+    let mut cut = max - 1;
+    while cut > 0 && !is_boundary(&chars, cut) {
+        cut -= 1;
+    }
+    let kept: String = chars[..cut].iter().collect();
+    format!("{}{}", kept.trim_end(), ELLIPSIS)
+}
+
+// The built-in rules switched on in the config
+pub fn rules_from_config(config: &Config) -> Vec<Box<dyn ValidationRule>> {
+    let mut rules: Vec<Box<dyn ValidationRule>> = Vec::new();
+    if config.require_tag {
+        rules.push(Box::new(RequireTag));
+    }
+    if let Some(priority) = &config.require_due_for_priority {
+        rules.push(Box::new(RequireDueForPriority(priority.clone())));
+    }
+    if let Some(limit) = config.max_open_tasks {
+        rules.push(Box::new(MaxOpenTasks(limit)));
+    }
+    rules
+}
+
+// Run every rule, collecting all the failures rather than stopping at the first
+pub fn check_all(rules: &[Box<dyn ValidationRule>], task: &Task, context: &ValidationContext) -> Vec<String> {
+    rules.iter().filter_map(|rule| rule.check(task, context).err()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{TaskError, TaskManager};
+    use chrono::NaiveDate;
+
+    fn task(priority: Priority) -> Task {
+        Task::new(1, "Check me".to_string(), String::new(), priority)
+    }
+
+    const NONE_OPEN: ValidationContext = ValidationContext { open_tasks: 0 };
+
+    #[test]
+    fn test_require_tag() {
+        let mut t = task(Priority::Low);
+        assert!(RequireTag.check(&t, &NONE_OPEN).is_err());
+        t.add_tag("work".to_string());
+        assert!(RequireTag.check(&t, &NONE_OPEN).is_ok());
+    }
+
+    #[test]
+    fn test_require_due_for_priority() {
+        let rule = RequireDueForPriority(Priority::High);
+        assert!(rule.check(&task(Priority::Medium), &NONE_OPEN).is_ok());
+        assert!(rule.check(&task(Priority::High), &NONE_OPEN).is_err());
+        let mut t = task(Priority::Critical);
+        assert_eq!(rule.check(&t, &NONE_OPEN), Err("Critical priority tasks need a due date".to_string()));
+        t.due = NaiveDate::from_ymd_opt(2024, 7, 1);
+        assert!(rule.check(&t, &NONE_OPEN).is_ok());
+    }
+
+    #[test]
+    fn test_max_open_tasks() {
+        let rule = MaxOpenTasks(2);
+        assert!(rule.check(&task(Priority::Low), &ValidationContext { open_tasks: 1 }).is_ok());
+        assert!(rule.check(&task(Priority::Low), &ValidationContext { open_tasks: 2 }).is_err());
+        let mut done = task(Priority::Low);
+        done.update_status(TaskStatus::Completed);
+        assert!(rule.check(&done, &ValidationContext { open_tasks: 2 }).is_ok());
+    }
+
+    #[test]
+    fn test_failures_are_aggregated() {
+        let mut config = Config::default();
+        config.require_tag = true;
+        config.require_due_for_priority = Some(Priority::Critical);
+        config.max_open_tasks = Some(1);
+
+        let mut manager = TaskManager::new();
+        manager.set_rules(rules_from_config(&config));
+        let mut first = Task::new(0, "First".to_string(), String::new(), Priority::Low);
+        first.add_tag("work".to_string());
+        manager.create_task(first).unwrap();
+
+        let second = Task::new(0, "Second".to_string(), String::new(), Priority::Critical);
+        match manager.create_task(second) {
+            Err(TaskError::ValidationFailed(violations)) => assert_eq!(violations, vec![
+                "a task needs at least one tag".to_string(),
+                "Critical priority tasks need a due date".to_string(),
+                "at most 1 open tasks are allowed".to_string(),
+            ]),
+            other => panic!("unexpected result: {:?}", other),
+        }
+        assert_eq!(manager.list_tasks().len(), 1);
+    }
+
+    #[test]
+    fn test_limits_name_the_key_they_enforce() {
+        let limits = Limits { description_chars: Some(10), notes_per_task: Some(2), tags_per_task: Some(1) };
+        let mut t = task(Priority::Low);
+        t.description = "called\nemailed\nwaiting on reply".to_string();
+        t.add_tag("work");
+        t.add_tag("calls");
+        let over = limits.check(&t);
+        assert_eq!(over.len(), 3);
+        assert!(over[0].contains("limits.notes_per_task") && over[1].contains("limits.description_chars"));
+        assert!(over[2].contains("limits.tags_per_task"));
+        assert!(Limits::default().check(&t).is_empty());
+
+        let cut = limits.truncate(&mut t);
+        assert_eq!(cut.len(), 3);
+        assert_eq!(t.description, "called\nem…");
+        assert_eq!(t.tags.iter().map(|tag| tag.to_string()).collect::<Vec<_>>(), ["work"]);
+        assert!(limits.check(&t).is_empty());
+        assert!(limits.truncate(&mut t).is_empty());
+    }
+
+    #[test]
+    fn test_truncate_text_keeps_characters_whole() {
+        assert_eq!(truncate_text("short", 5), "short");
+        assert_eq!(truncate_text("shorter", 5), "shor…");
+        assert_eq!(truncate_text("anything", 0), "");
+        // Multi-byte characters are counted once
+        assert_eq!(truncate_text("日本語のテキスト", 4), "日本語…");
+        // An accent typed as a combining mark goes with its letter
+        assert_eq!(truncate_text("cafe\u{301} au lait", 5), "caf…");
+        // A family emoji is one character built from four joined ones
+        let family = "\u{1F468}\u{200D}\u{1F469}\u{200D}\u{1F467}\u{200D}\u{1F466}";
+        assert_eq!(truncate_text(&format!("ab{}cd", family), 6), "ab…");
+        assert_eq!(truncate_text(&format!("ab{}cd", family), 10), format!("ab{}…", family));
+        // Skin tones, variation selectors and flags
+        assert_eq!(truncate_text("ok\u{1F44D}\u{1F3FD} thanks", 4), "ok…");
+        assert_eq!(truncate_text("I \u{2764}\u{FE0F} it", 4), "I…");
+        assert_eq!(truncate_text("go \u{1F1EB}\u{1F1F7}\u{1F1EF}\u{1F1F5} now", 7), "go \u{1F1EB}\u{1F1F7}…");
+        // The cut never leaves trailing space before the ellipsis
+        assert_eq!(truncate_text("one two three", 5), "one…");
+    }
+}
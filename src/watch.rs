@@ -0,0 +1,96 @@
+use std::fs;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant, SystemTime};
+
+// How often the files are looked at
+pub const POLL_EVERY: Duration = Duration::from_millis(200);
+
+// Writes closer together than this count as one save
+pub const DEBOUNCE: Duration = Duration::from_millis(300);
+
+// A file that will not load is read once more after this, in case it was caught
+// half written
+pub const RETRY_AFTER: Duration = Duration::from_millis(250);
+
+// Each file's modification time and length, None while it does not exist
+type Stamp = Vec<Option<(SystemTime, u64)>>;
+
+fn stamp(paths: &[PathBuf]) -> Stamp {
+    paths
+        .iter()
+        .map(|path| fs::metadata(path).ok().map(|meta| (meta.modified().unwrap_or(SystemTime::UNIX_EPOCH), meta.len())))
+        .collect()
+}
+
+// Notices saves to the data file, and the journal beside it, by polling their
+// modification times; a burst of writes is reported once it has settled
+pub struct Watcher {
+    paths: Vec<PathBuf>,
+    last: Stamp,
+    // When the files were last seen changing, until that is reported
+    changed_at: Option<Instant>,
+}
+
+impl Watcher {
+    pub fn new(paths: Vec<PathBuf>) -> Self {
+        let last = stamp(&paths);
+        Watcher { paths, last, changed_at: None }
+    }
+
+    // True once after the files changed and then stayed as they are for DEBOUNCE
+    pub fn poll(&mut self, now: Instant) -> bool {
+        let current = stamp(&self.paths);
+        if current != self.last {
+            self.last = current;
+            self.changed_at = Some(now);
+            return false;
+        }
+        match self.changed_at {
+            Some(at) if now.duration_since(at) >= DEBOUNCE => {
+                self.changed_at = None;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    // Block until a change has settled (true) or `stop` is raised (false)
+    pub fn wait(&mut self, stop: &AtomicBool) -> bool {
+        while !stop.load(Ordering::SeqCst) {
+            std::thread::sleep(POLL_EVERY);
+            if self.poll(Instant::now()) {
+                return true;
+            }
+        }
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_changes_are_reported_once_settled() {
+        let path = std::env::temp_dir().join(format!("task-manager-watch-{}.json", std::process::id()));
+        let _ = fs::remove_file(&path);
+        let mut watcher = Watcher::new(vec![path.clone()]);
+        let start = Instant::now();
+        assert!(!watcher.poll(start));
+
+        // Created, then written again before the first write settled
+        fs::write(&path, "[]").unwrap();
+        assert!(!watcher.poll(start));
+        fs::write(&path, "[1, 2]").unwrap();
+        assert!(!watcher.poll(start + DEBOUNCE / 2));
+        assert!(!watcher.poll(start + DEBOUNCE));
+        assert!(watcher.poll(start + DEBOUNCE / 2 + DEBOUNCE));
+        // Reported once
+        assert!(!watcher.poll(start + DEBOUNCE * 3));
+
+        fs::remove_file(&path).unwrap();
+        assert!(!watcher.poll(start + DEBOUNCE * 4));
+        assert!(watcher.poll(start + DEBOUNCE * 5));
+    }
+}
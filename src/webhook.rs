@@ -0,0 +1,349 @@
+use std::str::FromStr;
+use std::sync::Arc;
+use std::sync::mpsc::{self, Sender};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use chrono::Utc;
+use log::{error, warn};
+use serde_json::{Value, json};
+
+use crate::{Priority, Subscriber, Task, TaskError, TaskEvent, TaskManager, TaskStatus, msg};
+
+// Which changes a webhook wants to hear about
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WebhookEvent {
+    // A task moved to Completed
+    Completed,
+    // A task was added with Critical priority
+    CreatedCritical,
+}
+
+impl WebhookEvent {
+    pub fn name(self) -> &'static str {
+        match self {
+            WebhookEvent::Completed => "completed",
+            WebhookEvent::CreatedCritical => "created_critical",
+        }
+    }
+}
+
+impl FromStr for WebhookEvent {
+    type Err = TaskError;
+
+    fn from_str(s: &str) -> Result<WebhookEvent, TaskError> {
+        match s {
+            "completed" => Ok(WebhookEvent::Completed),
+            "created_critical" => Ok(WebhookEvent::CreatedCritical),
+            _ => Err(TaskError::InvalidInput),
+        }
+    }
+}
+
+// One `[[webhooks]]` entry from the config file
+#[derive(Debug, Clone, PartialEq)]
+pub struct WebhookConfig {
+    pub url: String,
+    pub events: Vec<WebhookEvent>,
+}
+
+// Sends a JSON body to a URL; swapped out in tests to capture requests
+pub trait WebhookClient: Send + Sync {
+    fn post(&self, url: &str, body: &str) -> Result<(), String>;
+}
+
+// Slack-compatible: `text` is what the channel shows, the rest is for other consumers
+pub fn payload(event: WebhookEvent, task: &Task) -> Value {
+    let text = match event {
+        WebhookEvent::Completed => msg!("webhook_completed", id = task.id, title = task.title),
+        WebhookEvent::CreatedCritical => msg!("webhook_created_critical", id = task.id, title = task.title),
+    };
+    json!({
+        "text": text,
+        "event": event.name(),
+        "task_id": task.id,
+        "title": task.title,
+        "priority": task.priority,
+        "timestamp": Utc::now(),
+    })
+}
+
+// Which webhook event, if any, a task event amounts to
+fn classify(event: &TaskEvent, manager: &TaskManager) -> Option<(WebhookEvent, Task)> {
+    match event {
+        TaskEvent::StatusChanged { id, to: TaskStatus::Completed, from } if *from != TaskStatus::Completed => {
+            Some((WebhookEvent::Completed, manager.get_task(*id).ok()?.clone()))
+        }
+        TaskEvent::Added(id) => {
+            let task = manager.get_task(*id).ok()?;
+            (task.priority == Priority::Critical).then(|| (WebhookEvent::CreatedCritical, task.clone()))
+        }
+        _ => None,
+    }
+}
+
+// How long one request may take, and how long exit waits for the queue
+pub const POST_TIMEOUT: Duration = Duration::from_secs(5);
+
+enum Job {
+    Post(Box<dyn FnOnce() + Send>),
+    // Everything queued before this has been sent; the worker answers and ends
+    Stop(Sender<()>),
+}
+
+// The one thread background webhooks are posted from, in the order they were
+// raised. Dropping it waits up to `timeout` for the queue to drain, so a
+// one-shot command does not exit with its webhooks unsent.
+pub struct WebhookWorker {
+    queue: Sender<Job>,
+    thread: Option<JoinHandle<()>>,
+    timeout: Duration,
+}
+
+impl WebhookWorker {
+    pub fn start(timeout: Duration) -> Self {
+        let (queue, jobs) = mpsc::channel();
+        let thread = thread::spawn(move || {
+            for job in jobs {
+                match job {
+                    Job::Post(send) => send(),
+                    Job::Stop(done) => {
+                        let _ = done.send(());
+                        return;
+                    }
+                }
+            }
+        });
+        WebhookWorker { queue, thread: Some(thread), timeout }
+    }
+}
+
+impl Drop for WebhookWorker {
+    fn drop(&mut self) {
+        let (done, finished) = mpsc::channel();
+        if self.queue.send(Job::Stop(done)).is_err() {
+            return;
+        }
+        match finished.recv_timeout(self.timeout) {
+            Ok(()) => {
+                if let Some(thread) = self.thread.take() {
+                    let _ = thread.join();
+                }
+            }
+            // Left running; exiting the process ends it
+            Err(_) => warn!("gave up waiting for webhooks after {}s", self.timeout.as_secs_f64()),
+        }
+    }
+}
+
+// Fires configured webhooks from the TaskEvent hook
+pub struct WebhookNotifier {
+    hooks: Vec<WebhookConfig>,
+    client: Arc<dyn WebhookClient>,
+    // Post from the worker so a slow endpoint never holds up a command
+    queue: Option<Sender<Job>>,
+}
+
+impl WebhookNotifier {
+    pub fn new(hooks: Vec<WebhookConfig>, client: Arc<dyn WebhookClient>) -> Self {
+        WebhookNotifier { hooks, client, queue: None }
+    }
+
+    pub fn in_background(mut self, worker: &WebhookWorker) -> Self {
+        self.queue = Some(worker.queue.clone());
+        self
+    }
+
+    pub fn subscriber(self) -> Subscriber {
+        Box::new(move |event, manager| {
+            if let Some((kind, task)) = classify(event, manager) {
+                self.notify(kind, &task);
+            }
+        })
+    }
+
+    fn notify(&self, event: WebhookEvent, task: &Task) {
+        let body = payload(event, task).to_string();
+        for hook in self.hooks.iter().filter(|hook| hook.events.contains(&event)) {
+            let (client, url, body) = (Arc::clone(&self.client), hook.url.clone(), body.clone());
+            let send = move || {
+                if let Err(e) = client.post(&url, &body) {
+                    warn!("webhook {} failed: {}", url, e);
+                }
+            };
+            match &self.queue {
+                Some(queue) => {
+                    if queue.send(Job::Post(Box::new(send))).is_err() {
+                        warn!("webhook {} dropped: the worker has stopped", hook.url);
+                    }
+                }
+                None => send(),
+            }
+        }
+    }
+
+    // `webhook test`: post a sample payload to every hook, waiting for each answer
+    pub fn send_test(&self) -> Vec<(String, Result<(), String>)> {
+        let mut sample = Task::new(0, "Webhook test".to_string(), String::new(), Priority::Critical);
+        sample.update_status(TaskStatus::Completed);
+        self.hooks
+            .iter()
+            .map(|hook| {
+                let event = hook.events.first().copied().unwrap_or(WebhookEvent::Completed);
+                let body = payload(event, &sample).to_string();
+                let result = self.client.post(&hook.url, &body);
+                if let Err(e) = &result {
+                    error!("webhook test to {} failed: {}", hook.url, e);
+                }
+                (hook.url.clone(), result)
+            })
+            .collect()
+    }
+}
+
+// Real HTTP client with a short timeout
+#[cfg(feature = "http-client")]
+pub struct HttpClient {
+    agent: ureq::Agent,
+}
+
+#[cfg(feature = "http-client")]
+impl Default for HttpClient {
+    fn default() -> Self {
+        HttpClient {
+            agent: ureq::AgentBuilder::new().timeout(POST_TIMEOUT).build(),
+        }
+    }
+}
+
+#[cfg(feature = "http-client")]
+impl WebhookClient for HttpClient {
+    fn post(&self, url: &str, body: &str) -> Result<(), String> {
+        self.agent
+            .post(url)
+            .set("Content-Type", "application/json")
+            .send_string(body)
+            .map(|_| ())
+            .map_err(|e| e.to_string())
+    }
+}
+
+// The client used by the CLI, if this build can make HTTP requests
+#[cfg(feature = "http-client")]
+pub fn default_client() -> Option<Arc<dyn WebhookClient>> {
+    Some(Arc::new(HttpClient::default()))
+}
+
+#[cfg(not(feature = "http-client"))]
+pub fn default_client() -> Option<Arc<dyn WebhookClient>> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    // Records requests instead of sending them
+    #[derive(Default)]
+    struct Capture {
+        requests: Mutex<Vec<(String, Value)>>,
+    }
+
+    impl WebhookClient for Capture {
+        fn post(&self, url: &str, body: &str) -> Result<(), String> {
+            self.requests.lock().unwrap().push((url.to_string(), serde_json::from_str(body).unwrap()));
+            Ok(())
+        }
+    }
+
+    fn hook(url: &str, events: &[WebhookEvent]) -> WebhookConfig {
+        WebhookConfig { url: url.to_string(), events: events.to_vec() }
+    }
+
+    #[test]
+    fn test_events_reach_matching_hooks() {
+        let capture = Arc::new(Capture::default());
+        let notifier = WebhookNotifier::new(
+            vec![hook("http://done", &[WebhookEvent::Completed]), hook("http://critical", &[WebhookEvent::CreatedCritical])],
+            capture.clone(),
+        );
+        let mut manager = TaskManager::new();
+        manager.subscribe(notifier.subscriber());
+
+        manager.add_task("Routine".to_string(), String::new(), Priority::Low).unwrap();
+        let fire = manager.add_task("Fire".to_string(), String::new(), Priority::Critical).unwrap();
+        manager.update_task_status(1, TaskStatus::InProgress).unwrap();
+        manager.update_task_status(fire, TaskStatus::Completed).unwrap();
+        manager.update_task_status(fire, TaskStatus::Completed).unwrap();
+
+        let requests = capture.requests.lock().unwrap();
+        let urls: Vec<&str> = requests.iter().map(|(url, _)| url.as_str()).collect();
+        assert_eq!(urls, vec!["http://critical", "http://done"]);
+        let (_, body) = &requests[1];
+        assert_eq!(body["event"], "completed");
+        assert_eq!(body["task_id"], fire);
+        assert_eq!(body["title"], "Fire");
+        assert_eq!(body["priority"], "Critical");
+        assert_eq!(body["text"], "Completed task #2: Fire");
+        assert!(body["timestamp"].is_string());
+    }
+
+    // Answers after `delay`, recording the order requests arrived in
+    struct Slow {
+        delay: Duration,
+        capture: Capture,
+    }
+
+    impl WebhookClient for Slow {
+        fn post(&self, url: &str, body: &str) -> Result<(), String> {
+            thread::sleep(self.delay);
+            self.capture.post(url, body)
+        }
+    }
+
+    #[test]
+    fn test_background_posts_are_sent_in_order_before_the_worker_goes() {
+        let slow = Arc::new(Slow { delay: Duration::from_millis(20), capture: Capture::default() });
+        let worker = WebhookWorker::start(POST_TIMEOUT);
+        let notifier = WebhookNotifier::new(vec![hook("http://done", &[WebhookEvent::Completed])], slow.clone());
+        let mut manager = TaskManager::new();
+        manager.subscribe(notifier.in_background(&worker).subscriber());
+        for i in 1..=5 {
+            manager.add_task(format!("Task {}", i), String::new(), Priority::Low).unwrap();
+            manager.update_task_status(i, TaskStatus::Completed).unwrap();
+        }
+        drop(worker);
+        let requests = slow.capture.requests.lock().unwrap();
+        let ids: Vec<&Value> = requests.iter().map(|(_, body)| &body["task_id"]).collect();
+        assert_eq!(ids, [1, 2, 3, 4, 5]);
+    }
+
+    #[test]
+    fn test_a_stuck_endpoint_holds_exit_up_only_until_the_timeout() {
+        let stuck = Arc::new(Slow { delay: Duration::from_secs(5), capture: Capture::default() });
+        let worker = WebhookWorker::start(Duration::from_millis(50));
+        let notifier = WebhookNotifier::new(vec![hook("http://stuck", &[WebhookEvent::CreatedCritical])], stuck);
+        let mut manager = TaskManager::new();
+        manager.subscribe(notifier.in_background(&worker).subscriber());
+        manager.add_task("Fire".to_string(), String::new(), Priority::Critical).unwrap();
+        let started = std::time::Instant::now();
+        drop(worker);
+        assert!(started.elapsed() < Duration::from_secs(2));
+    }
+
+    #[test]
+    fn test_send_test_posts_to_every_hook() {
+        let capture = Arc::new(Capture::default());
+        let notifier = WebhookNotifier::new(
+            vec![hook("http://a", &[WebhookEvent::CreatedCritical]), hook("http://b", &[])],
+            capture.clone(),
+        );
+        let results = notifier.send_test();
+        assert_eq!(results.len(), 2);
+        assert!(results.iter().all(|(_, result)| result.is_ok()));
+        let requests = capture.requests.lock().unwrap();
+        assert_eq!(requests[0].1["event"], "created_critical");
+        assert_eq!(requests[1].1["event"], "completed");
+    }
+}
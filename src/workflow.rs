@@ -0,0 +1,210 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::{Task, TaskError, TaskStatus};
+
+const STATUSES: [TaskStatus; 3] = [TaskStatus::Pending, TaskStatus::InProgress, TaskStatus::Completed];
+
+// The status as `update` takes it
+pub fn status_word(status: &TaskStatus) -> &'static str {
+    match status {
+        TaskStatus::Pending => "pending",
+        TaskStatus::InProgress => "progress",
+        TaskStatus::Completed => "completed",
+    }
+}
+
+// What a move between two statuses needs besides being allowed
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Requirement {
+    // Given with the move: `update 4 pending --note <why>`
+    Note,
+    // An `assignee` custom field on the task
+    Assignee,
+}
+
+impl FromStr for Requirement {
+    type Err = TaskError;
+
+    fn from_str(s: &str) -> Result<Requirement, TaskError> {
+        match s.to_lowercase().as_str() {
+            "note" => Ok(Requirement::Note),
+            "assignee" => Ok(Requirement::Assignee),
+            _ => Err(TaskError::InvalidInput),
+        }
+    }
+}
+
+impl fmt::Display for Requirement {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Requirement::Note => write!(f, "note"),
+            Requirement::Assignee => write!(f, "assignee"),
+        }
+    }
+}
+
+// Which status changes the team allows, from `[transitions]` and
+// `[[transition_requires]]` in the config. The default allows every move and
+// needs nothing for it.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Workflow {
+    // Where a status may go; one not listed may go anywhere
+    allowed: Vec<(TaskStatus, Vec<TaskStatus>)>,
+    requirements: Vec<(TaskStatus, TaskStatus, Requirement)>,
+}
+
+impl Workflow {
+    pub fn is_permissive(&self) -> bool {
+        self.allowed.is_empty() && self.requirements.is_empty()
+    }
+
+    // Limit where `from` may go, replacing any earlier list for it
+    pub fn allow(&mut self, from: TaskStatus, to: Vec<TaskStatus>) {
+        self.allowed.retain(|(status, _)| *status != from);
+        self.allowed.push((from, to));
+    }
+
+    pub fn require(&mut self, from: TaskStatus, to: TaskStatus, requirement: Requirement) {
+        if !self.requires(&from, &to).contains(&requirement) {
+            self.requirements.push((from, to, requirement));
+        }
+    }
+
+    // The statuses a task in `from` may move to, in the usual order
+    pub fn next_statuses(&self, from: &TaskStatus) -> Vec<TaskStatus> {
+        let allowed = self.allowed.iter().find(|(status, _)| status == from).map(|(_, to)| to);
+        STATUSES
+            .iter()
+            .filter(|to| *to != from && allowed.is_none_or(|allowed| allowed.contains(to)))
+            .cloned()
+            .collect()
+    }
+
+    pub fn requires(&self, from: &TaskStatus, to: &TaskStatus) -> Vec<Requirement> {
+        self.requirements.iter().filter(|(f, t, _)| f == from && t == to).map(|(_, _, requirement)| *requirement).collect()
+    }
+
+    // Whether `task` may move to `to`, given the note that comes with the move.
+    // Staying put is not a move and always passes.
+    pub fn check(&self, task: &Task, to: &TaskStatus, note: Option<&str>) -> Result<(), TaskError> {
+        let from = &task.status;
+        if from == to {
+            return Ok(());
+        }
+        let allowed = self.next_statuses(from);
+        if !allowed.contains(to) {
+            return Err(TaskError::InvalidTransition { from: from.clone(), to: to.clone(), allowed });
+        }
+        let missing: Vec<String> = self
+            .requires(from, to)
+            .into_iter()
+            .filter(|requirement| match requirement {
+                Requirement::Note => note.is_none_or(|note| note.trim().is_empty()),
+                Requirement::Assignee => task.fields.get("assignee").is_none_or(|who| who.trim().is_empty()),
+            })
+            .map(|requirement| match requirement {
+                Requirement::Note => format!("moving from {} to {} needs a note (--note <text>)", from, to),
+                Requirement::Assignee => format!("moving from {} to {} needs an assignee field", from, to),
+            })
+            .collect();
+        if missing.is_empty() { Ok(()) } else { Err(TaskError::ValidationFailed(missing)) }
+    }
+
+    // As `config` shows it: "pending -> progress; completed -> pending"
+    pub fn describe_allowed(&self) -> String {
+        if self.allowed.is_empty() {
+            return "(any)".to_string();
+        }
+        let moves: Vec<String> = self
+            .allowed
+            .iter()
+            .map(|(from, to)| {
+                let to: Vec<&str> = to.iter().map(status_word).collect();
+                format!("{} -> {}", status_word(from), if to.is_empty() { "(none)".to_string() } else { to.join(", ") })
+            })
+            .collect();
+        moves.join("; ")
+    }
+
+    pub fn describe_requirements(&self) -> String {
+        if self.requirements.is_empty() {
+            return "(none)".to_string();
+        }
+        let rules: Vec<String> = self
+            .requirements
+            .iter()
+            .map(|(from, to, requirement)| format!("{} -> {}: {}", status_word(from), status_word(to), requirement))
+            .collect();
+        rules.join("; ")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Priority;
+
+    fn task(status: TaskStatus) -> Task {
+        let mut task = Task::new(1, "Ship release".to_string(), String::new(), Priority::High);
+        task.status = status;
+        task
+    }
+
+    #[test]
+    fn test_default_allows_every_move() {
+        let workflow = Workflow::default();
+        assert!(workflow.is_permissive());
+        for from in STATUSES {
+            for to in STATUSES {
+                assert!(workflow.check(&task(from.clone()), &to, None).is_ok());
+            }
+        }
+        assert_eq!(workflow.next_statuses(&TaskStatus::Pending), [TaskStatus::InProgress, TaskStatus::Completed]);
+    }
+
+    #[test]
+    fn test_restricted_moves_name_the_allowed_ones() {
+        let mut workflow = Workflow::default();
+        workflow.allow(TaskStatus::Pending, vec![TaskStatus::InProgress]);
+        match workflow.check(&task(TaskStatus::Pending), &TaskStatus::Completed, None) {
+            Err(TaskError::InvalidTransition { from, to, allowed }) => {
+                assert_eq!((from, to, allowed), (TaskStatus::Pending, TaskStatus::Completed, vec![TaskStatus::InProgress]));
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+        assert!(workflow.check(&task(TaskStatus::Pending), &TaskStatus::InProgress, None).is_ok());
+        // Statuses without a list keep every move
+        assert!(workflow.check(&task(TaskStatus::InProgress), &TaskStatus::Pending, None).is_ok());
+
+        workflow.allow(TaskStatus::Completed, Vec::new());
+        assert!(workflow.next_statuses(&TaskStatus::Completed).is_empty());
+        assert_eq!(workflow.describe_allowed(), "pending -> progress; completed -> (none)");
+    }
+
+    #[test]
+    fn test_note_requirement() {
+        let mut workflow = Workflow::default();
+        workflow.require(TaskStatus::Completed, TaskStatus::Pending, Requirement::Note);
+        let done = task(TaskStatus::Completed);
+        assert!(matches!(workflow.check(&done, &TaskStatus::Pending, None), Err(TaskError::ValidationFailed(_))));
+        assert!(workflow.check(&done, &TaskStatus::Pending, Some("  ")).is_err());
+        assert!(workflow.check(&done, &TaskStatus::Pending, Some("customer found a regression")).is_ok());
+        // Only the move it was set for
+        assert!(workflow.check(&done, &TaskStatus::InProgress, None).is_ok());
+    }
+
+    #[test]
+    fn test_assignee_requirement() {
+        let mut workflow = Workflow::default();
+        workflow.require(TaskStatus::Pending, TaskStatus::InProgress, Requirement::Assignee);
+        let mut pending = task(TaskStatus::Pending);
+        assert_eq!(
+            workflow.check(&pending, &TaskStatus::InProgress, Some("starting")).unwrap_err().to_string(),
+            "Validation failed: moving from Pending to In Progress needs an assignee field"
+        );
+        pending.fields.insert("assignee".to_string(), "ana".to_string());
+        assert!(workflow.check(&pending, &TaskStatus::InProgress, None).is_ok());
+        assert_eq!(workflow.describe_requirements(), "pending -> progress: assignee");
+    }
+}
@@ -0,0 +1,65 @@
+use std::fs::{self, File};
+use std::path::PathBuf;
+use std::process::{Command, Output, Stdio};
+
+const BINARY: &str = env!("CARGO_BIN_EXE_Task-Manager");
+
+fn fixture(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures").join(name)
+}
+
+fn run(data: &PathBuf, args: &[&str], stdin: Stdio) -> Output {
+    Command::new(BINARY)
+        .arg("--data")
+        .arg(data)
+        .args(args)
+        .env_remove("TASKMGR_LOG")
+        .stdin(stdin)
+        .output()
+        .expect("the binary runs")
+}
+
+#[test]
+fn test_piped_lines_become_tasks() {
+    let dir = std::env::temp_dir().join(format!("task-manager-add-stdin-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let data = dir.join("tasks.json");
+    let _ = fs::remove_file(&data);
+
+    let ideas = File::open(fixture("ideas.txt")).unwrap();
+    let output = run(&data, &["add", "--stdin", "--priority", "low", "--tag", "someday"], Stdio::from(ideas));
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert_eq!(
+        stdout,
+        "Line 6: skipped, a task with this title already exists (ID: 1)\n\
+         Line 7: skipped, 'whenever' is not a priority\n\
+         Added 4 tasks, skipped 2 lines. New IDs: 1, 2, 3, 4\n"
+    );
+
+    // Saved once, with every task in it
+    let listed = run(&data, &["--format", "json", "tagged", "someday"], Stdio::null());
+    let json: serde_json::Value = serde_json::from_slice(&listed.stdout).unwrap();
+    let tasks = json["data"].as_array().unwrap();
+    let titles: Vec<&str> = tasks.iter().map(|task| task["title"].as_str().unwrap()).collect();
+    assert_eq!(titles, ["Learn the cello", "Repaint the shed", "Write a novel", "Visit Lisbon"]);
+    assert_eq!(tasks[1]["description"], "the green one, before winter");
+    assert_eq!(tasks[2]["priority"], "High");
+    assert_eq!(tasks[3]["priority"], "Low");
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_errors_go_to_stderr() {
+    let dir = std::env::temp_dir().join(format!("task-manager-add-stdin-errors-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let data = dir.join("tasks.json");
+
+    let output = run(&data, &["add", "--stdin", "--priority", "urgent"], Stdio::null());
+    assert!(!output.status.success());
+    assert!(output.stdout.is_empty());
+    assert_eq!(String::from_utf8(output.stderr).unwrap(), "Invalid priority. Use: low, medium, high, or critical\n  input: urgent\n");
+
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,41 @@
+use std::fs;
+use std::path::Path;
+use std::process::{Command, Output};
+
+const BINARY: &str = env!("CARGO_BIN_EXE_Task-Manager");
+
+fn run(config: &Path, data: &Path, args: &[&str]) -> Output {
+    Command::new(BINARY)
+        .arg("--config")
+        .arg(config)
+        .arg("--data")
+        .arg(data)
+        .args(args)
+        .env_remove("TASKMGR_LOG")
+        .output()
+        .expect("the binary runs")
+}
+
+#[test]
+fn test_configured_date_format_reaches_show_list_and_export() {
+    let dir = std::env::temp_dir().join(format!("task-manager-date-format-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let (config, data, page) = (dir.join("config.toml"), dir.join("tasks.json"), dir.join("report.html"));
+    let _ = fs::remove_file(&data);
+    fs::write(&config, "date_format = \"%d/%m/%Y\"\nabsolute_dates = true\ncolor = false\n").unwrap();
+
+    let output = run(&config, &data, &["add", "Pay", "rent", "--due", "2031-05-04"]);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let show = String::from_utf8(run(&config, &data, &["show", "1"]).stdout).unwrap();
+    assert!(show.contains("04/05/2031"), "{}", show);
+    let list = String::from_utf8(run(&config, &data, &["list", "--columns", "id,title,due"]).stdout).unwrap();
+    assert!(list.contains("04/05/2031"), "{}", list);
+    let moved = String::from_utf8(run(&config, &data, &["--verbose", "due", "1", "2031-06-01"]).stdout).unwrap();
+    assert!(moved.contains("01/06/2031"), "{}", moved);
+
+    assert!(run(&config, &data, &["export", "html", page.to_str().unwrap()]).status.success());
+    let html = fs::read_to_string(&page).unwrap();
+    assert!(html.contains(r#"<td data-sort="2031-06-01">01/06/2031</td>"#));
+
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,46 @@
+use std::fs;
+use std::process::{Command, Output};
+
+use serde_json::{Value, json};
+
+const BINARY: &str = env!("CARGO_BIN_EXE_Task-Manager");
+
+fn run(args: &[&str]) -> Output {
+    Command::new(BINARY).args(args).env_remove("TASKMGR_LOG").output().expect("the binary runs")
+}
+
+#[test]
+fn test_script_errors_are_json_on_stderr() {
+    let dir = std::env::temp_dir().join(format!("task-manager-json-errors-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let data = dir.join("tasks.json");
+    let _ = fs::remove_file(&data);
+    let data = data.to_str().unwrap();
+
+    assert!(run(&["--data", data, "add", "Buy", "milk"]).status.success());
+    let output = run(&["--data", data, "--format", "json", "add", "Buy", "milk"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(output.stdout.is_empty());
+    let error: Value = serde_json::from_slice(&output.stderr).unwrap();
+    assert_eq!(
+        error,
+        json!({
+            "error": "DuplicateTask",
+            "title": "Buy milk",
+            "existing_id": 1,
+            "message": "Task with this title already exists (ID: 1)",
+        })
+    );
+
+    let output = run(&["--data", data, "--format", "json", "frobnicate"]);
+    assert_eq!(output.status.code(), Some(1));
+    let error: Value = serde_json::from_slice(&output.stderr).unwrap();
+    assert_eq!((&error["error"], &error["command"]), (&json!("UnknownCommand"), &json!("frobnicate")));
+    assert!(error["message"].is_string());
+
+    // Without --format json the error stays prose
+    let output = run(&["--data", data, "show", "9"]);
+    assert_eq!(String::from_utf8(output.stderr).unwrap(), "Error: Task 9 not found\n");
+
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,85 @@
+// Drives the library the way an embedding program would: only items from the
+// crate root, no modules, no private fields
+use std::sync::{Arc, Mutex};
+
+use chrono::NaiveDate;
+use task_manager::{
+    Priority, SharedTaskManager, SortKey, Task, TaskEdit, TaskError, TaskEvent, TaskFilter, TaskManager, TaskStatus,
+};
+
+fn titles(tasks: &[&Task]) -> Vec<String> {
+    tasks.iter().map(|task| task.title().to_string()).collect()
+}
+
+#[test]
+fn test_end_to_end_through_the_public_api() {
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let seen = Arc::clone(&events);
+    let mut manager = TaskManager::new();
+    manager.subscribe(Box::new(move |event, _| seen.lock().unwrap().push(event.clone())));
+
+    // Add, with and without a fully built task
+    let report = manager.add_task("Write report".to_string(), "Q3".to_string(), Priority::try_from("high").unwrap()).unwrap();
+    let due = NaiveDate::from_ymd_opt(2024, 5, 1).unwrap();
+    let mut rent = Task::new(0, "Pay rent".to_string(), String::new(), Priority::Critical).with_due(due);
+    rent.add_tag("home");
+    let rent = manager.create_task(rent).unwrap();
+    manager.add_task("Tidy desk".to_string(), String::new(), Priority::Low).unwrap();
+    assert!(matches!(
+        manager.add_task("write REPORT".to_string(), String::new(), Priority::Low),
+        Err(TaskError::DuplicateTask { existing_id, .. }) if existing_id == report
+    ));
+    assert!(Priority::try_from("urgent").is_err());
+
+    // Change
+    manager.add_tag_to_task(report, "work".to_string()).unwrap();
+    manager.update_task_status(report, TaskStatus::InProgress).unwrap();
+    manager.apply_edit(rent, TaskEdit { description: Some("Transfer".to_string()), ..TaskEdit::default() }).unwrap();
+    let task = manager.get_task(report).unwrap();
+    assert_eq!((task.title(), task.description(), task.status()), ("Write report", "Q3", &TaskStatus::InProgress));
+    assert_eq!(task.tags().collect::<Vec<_>>(), ["work"]);
+    assert_eq!(manager.get_task(rent).unwrap().due(), Some(due));
+    assert_eq!(manager.get_task(rent).unwrap().description(), "Transfer");
+
+    // Query and sort
+    assert_eq!(titles(&manager.find(&TaskFilter::parse("tag:work status:progress").unwrap())), ["Write report"]);
+    assert_eq!(titles(&manager.filter_tasks("rent")), ["Pay rent"]);
+    let mut tasks = manager.list_tasks();
+    SortKey::Priority.sort(&mut tasks);
+    assert_eq!(titles(&tasks), ["Pay rent", "Write report", "Tidy desk"]);
+
+    // Finish, remove, and count
+    manager.update_task_status(rent, TaskStatus::Completed).unwrap();
+    assert!(manager.get_task(rent).unwrap().completed_at().is_some());
+    manager.delete_task(3).unwrap();
+    assert!(matches!(manager.get_task(3), Err(TaskError::TaskNotFound { id: 3 })));
+    assert_eq!(manager.get_statistics(), (2, 1, 1, 0));
+
+    let events = events.lock().unwrap();
+    assert_eq!(events.first(), Some(&TaskEvent::Added(report)));
+    assert_eq!(events.last(), Some(&TaskEvent::Deleted(3)));
+
+    // Saved and loaded again, as a storage backend would
+    let saved = serde_json::to_string(&manager.to_state()).unwrap();
+    let loaded = TaskManager::from_state(serde_json::from_str(&saved).unwrap());
+    assert_eq!(loaded.get_task(rent).unwrap(), manager.get_task(rent).unwrap());
+    assert_eq!(loaded.get_statistics(), manager.get_statistics());
+}
+
+#[test]
+fn test_failed_changes_roll_back_through_a_snapshot() {
+    let shared = SharedTaskManager::new(TaskManager::new());
+    shared.add_task("Keep".to_string(), String::new(), Priority::Medium).unwrap();
+    let result = shared.with_write(|manager| {
+        let snapshot = manager.snapshot();
+        manager.add_task("Temporary".to_string(), String::new(), Priority::Low)?;
+        let outcome = manager.update_task_status(42, TaskStatus::Completed);
+        if outcome.is_err() {
+            manager.restore(snapshot);
+        }
+        outcome
+    });
+    assert!(matches!(result, Err(TaskError::TaskNotFound { id: 42 })));
+    let tasks = shared.list_tasks();
+    assert_eq!(tasks.iter().map(Task::title).collect::<Vec<_>>(), ["Keep"]);
+}